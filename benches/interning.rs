@@ -0,0 +1,55 @@
+//! Measures what string interning buys on a large parsed document.
+//!
+//! Run with `cargo bench --bench interning`.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::{css, dom, html, style};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Build a document with `count` repeated blocks of markup.
+fn large_document(count: usize) -> String {
+    let mut source: String = String::from("<html><body>");
+    for i in 0..count {
+        source.push_str(&format!(
+            "<div class=\"item c{}\"><p id=\"p{}\">Item <em>{}</em></p><span class=\"note\">note</span></div>",
+            i % 10, i, i
+        ));
+    }
+    source.push_str("</body></html>");
+    source
+}
+
+/// Run `f` a few times and return the fastest run.
+fn time<T>(mut f: impl FnMut() -> T) -> Duration {
+    (0..5).map(|_| {
+        let start: Instant = Instant::now();
+        black_box(f());
+        start.elapsed()
+    }).min().unwrap()
+}
+
+fn main() {
+    let source: String = large_document(20_000);
+    let stylesheet: css::Stylesheet = css::parse("div { display: block; } p.item { margin: 1px; } .c3 { padding: 2px; } #p42 { color: #ff0000; } em, span { display: inline; }").unwrap();
+
+    println!("parse {} bytes: {:?}", source.len(), time(|| html::parse(&source)));
+
+    let document: dom::Document = html::parse(&source).unwrap();
+    println!("style {} nodes: {:?}", document.tree.len(), time(|| style::style_tree(&document.tree, document.root_element, &stylesheet)));
+
+    // Compare tag-name equality checks as atoms and as strings.
+    let tags: Vec<Atom> = document.tree.descendants(document.root_element)
+        .filter_map(|node: dom::NodeId| match document.tree[node].node_type {
+            dom::NodeType::Element(ref element) => Some(element.tag_name),
+            _ => None,
+        })
+        .collect();
+    let strings: Vec<String> = tags.iter().map(|tag: &Atom| tag.to_string()).collect();
+    let div_atom: Atom = Atom::from("div");
+    let div_string: String = "div".to_string();
+
+    let atom_time: Duration = time(|| (0..100).map(|_| tags.iter().filter(|&&tag| black_box(tag) == div_atom).count()).sum::<usize>());
+    let string_time: Duration = time(|| (0..100).map(|_| strings.iter().filter(|&tag| black_box(tag) == &div_string).count()).sum::<usize>());
+    println!("{} tag comparisons x100: atoms {:?}, strings {:?}", tags.len(), atom_time, string_time);
+}
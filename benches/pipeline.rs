@@ -0,0 +1,141 @@
+//! Times each stage of the pipeline on the generated pages in `bench_fixtures`, and
+//! compares the times with a saved baseline to catch regressions.
+//!
+//! Run with `cargo bench --bench pipeline --features bench-fixtures`.
+//!
+//! The first run saves its times as the baseline, in `target/bench-baselines/pipeline.txt`.
+//! Later runs print how far each workload moved from it, and flag the ones more than
+//! `THRESHOLD` slower. To make the current times the new baseline (e.g. after a change
+//! that is meant to be slower), pass `--save-baseline`; to fail when something regressed
+//! (e.g. in CI, against a baseline from the main branch), pass `--check`:
+//!
+//!     cargo bench --bench pipeline --features bench-fixtures -- --check
+
+use build_a_browser_engine_in_rust::fonts::FontCollection;
+use build_a_browser_engine_in_rust::image::ImageCache;
+use build_a_browser_engine_in_rust::{bench_fixtures, css, dom, html, layout, painting, style};
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::hint::black_box;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+/// How much slower than the baseline a workload may get before it counts as a regression.
+const THRESHOLD: f64 = 0.10;
+
+/// How deeply the deep tree nests: as deep as the parser allows, counting `<html>` and
+/// `<body>`.
+const DEPTH: usize = html::MAX_DEPTH - 2;
+
+/// Run `f` until it has run for about a second (and at least 5 times), and return the
+/// fastest run, which is the least disturbed by whatever else the machine is doing.
+fn time<T>(mut f: impl FnMut() -> T) -> Duration {
+    let mut fastest: Duration = Duration::MAX;
+    let start: Instant = Instant::now();
+    let mut runs: usize = 0;
+    while runs < 5 || start.elapsed() < Duration::from_secs(1) {
+        let run: Instant = Instant::now();
+        black_box(f());
+        fastest = fastest.min(run.elapsed());
+        runs += 1;
+    }
+    fastest
+}
+
+/// Where the baseline is saved.
+fn baseline_path() -> PathBuf {
+    let target: PathBuf = env::var_os("CARGO_TARGET_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| Path::new(env!("CARGO_MANIFEST_DIR")).join("target"));
+    target.join("bench-baselines/pipeline.txt")
+}
+
+/// Read a saved baseline: one workload per line, its name and then its time in ns.
+fn read_baseline(path: &Path) -> BTreeMap<String, u128> {
+    let source: String = fs::read_to_string(path).unwrap_or_default();
+    source.lines()
+        .filter_map(|line: &str| {
+            let (name, nanos) = line.rsplit_once(' ')?;
+            Some((name.to_string(), nanos.parse().ok()?))
+        })
+        .collect()
+}
+
+/// Lay out a styled tree in an 800x600 viewport, with the stand-in font so the timings
+/// don't depend on the machine's fonts.
+fn lay_out<'a>(root: &'a style::StyledNode<'a>, images: &'a ImageCache, arena: &'a layout::Bump) -> layout::LayoutBox<'a> {
+    let mut viewport: layout::Dimensions = Default::default();
+    (viewport.content.width, viewport.content.height) = (800.0, 600.0);
+    layout::layout_tree(root, viewport, images, &FontCollection::new(), arena).unwrap()
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let check: bool = args.iter().any(|arg: &String| arg == "--check");
+    let save: bool = args.iter().any(|arg: &String| arg == "--save-baseline");
+
+    let article: String = bench_fixtures::wikipedia_sized_article();
+    let stylesheet_source: String = bench_fixtures::bootstrap_sized_stylesheet();
+    let deep: String = bench_fixtures::deep_tree(DEPTH);
+    println!("article: {} KB, stylesheet: {} KB, deep tree: {} levels", article.len() >> 10, stylesheet_source.len() >> 10, DEPTH);
+
+    let document: dom::Document = html::parse(&article).unwrap();
+    let stylesheet: css::Stylesheet = css::parse(&stylesheet_source).unwrap();
+    let deep_document: dom::Document = html::parse(&deep).unwrap();
+    let images: ImageCache = ImageCache::new();
+    let style_root: style::StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+    let arena: layout::Bump = layout::Bump::new();
+    let layout_root: layout::LayoutBox = lay_out(&style_root, &images, &arena);
+    let bounds: layout::Rect = layout::Rect { x: 0.0, y: 0.0, width: 800.0, height: 600.0 };
+
+    let results: Vec<(&str, Duration)> = vec![
+        ("html::parse article", time(|| html::parse(&article).unwrap())),
+        ("css::parse stylesheet", time(|| css::parse(&stylesheet_source).unwrap())),
+        ("style article", time(|| style::style_tree(&document.tree, document.root_element, &stylesheet))),
+        ("layout article", time(|| {
+            let arena: layout::Bump = layout::Bump::new();
+            let root: layout::LayoutBox = lay_out(&style_root, &images, &arena);
+            root.dimensions
+        })),
+        ("paint article", time(|| painting::rasterize(&painting::build_display_list(&layout_root), bounds))),
+        ("html::parse deep tree", time(|| html::parse(&deep).unwrap())),
+        ("style and layout deep tree", time(|| {
+            let style_root: style::StyledNode = style::style_tree(&deep_document.tree, deep_document.root_element, &stylesheet);
+            let arena: layout::Bump = layout::Bump::new();
+            let root: layout::LayoutBox = lay_out(&style_root, &images, &arena);
+            root.dimensions
+        })),
+    ];
+
+    let path: PathBuf = baseline_path();
+    let baseline: BTreeMap<String, u128> = read_baseline(&path);
+    let mut regressions: usize = 0;
+    println!("{:<28} {:>10} {:>10} {:>8}", "workload", "time (ms)", "baseline", "change");
+    for (name, duration) in &results {
+        let ms: f64 = duration.as_secs_f64() * 1000.0;
+        match baseline.get(*name) {
+            Some(&nanos) => {
+                let change: f64 = duration.as_nanos() as f64 / nanos as f64 - 1.0;
+                let flag: &str = if change > THRESHOLD { "  regressed" } else { "" };
+                regressions += (change > THRESHOLD) as usize;
+                println!("{:<28} {:>10.3} {:>10.3} {:>+7.1}%{}", name, ms, nanos as f64 / 1e6, change * 100.0, flag);
+            }
+            None => println!("{:<28} {:>10.3} {:>10} {:>8}", name, ms, "-", "-"),
+        }
+    }
+
+    if save || baseline.is_empty() {
+        let lines: String = results.iter().map(|(name, duration): &(&str, Duration)| format!("{} {}\n", name, duration.as_nanos())).collect();
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, lines).unwrap();
+        println!("saved the baseline to {}", path.display());
+    }
+    if regressions > 0 {
+        println!("{} workload(s) more than {}% slower than the baseline", regressions, THRESHOLD * 100.0);
+        if check {
+            process::exit(1);
+        }
+    }
+}
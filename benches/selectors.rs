@@ -0,0 +1,60 @@
+//! Measures what the quick check before selector matching buys (see "Fast Rejection" in
+//! `style`), on a Bootstrap-sized stylesheet and a long article.
+//!
+//! Run with `cargo bench --bench selectors --features bench-fixtures`.
+
+use build_a_browser_engine_in_rust::{bench_fixtures, css, dom, html, style};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// Run `f` a few times and return the fastest run.
+fn time<T>(mut f: impl FnMut() -> T) -> Duration {
+    (0..5).map(|_| {
+        let start: Instant = Instant::now();
+        black_box(f());
+        start.elapsed()
+    }).min().unwrap()
+}
+
+/// Every rule matching `element`, found by running the general matcher on every
+/// selector, as `style` did before it had a quick check.
+fn matched_without_quick_check<'a>(element: &dom::Element, node: dom::NodeId, stylesheet: &'a css::Stylesheet,
+                                   states: &style::ElementStates) -> Vec<(css::Specificity, &'a css::Rule)> {
+    let mut rules: Vec<(css::Specificity, &css::Rule)> = stylesheet.rules.iter().filter_map(|rule: &css::Rule| {
+        rule.selectors.iter()
+            .find(|selector: &&css::Selector| style::matches(element, node, selector, states))
+            .map(|selector: &css::Selector| (selector.specificity(), rule))
+    }).collect();
+    rules.sort_by_key(|&(specificity, _): &(css::Specificity, &css::Rule)| specificity);
+    rules
+}
+
+fn main() {
+    let document: dom::Document = html::parse(&bench_fixtures::article(40)).unwrap();
+    let stylesheet: css::Stylesheet = css::parse(&bench_fixtures::bootstrap_sized_stylesheet()).unwrap();
+    let states: style::ElementStates = style::ElementStates::default();
+    let elements: Vec<(dom::NodeId, &dom::Element)> = document.tree.descendants(document.root_element)
+        .filter_map(|node: dom::NodeId| match document.tree[node].node_type {
+            dom::NodeType::Element(ref element) => Some((node, element)),
+            _ => None,
+        })
+        .collect();
+
+    // Both ways have to find the same rules.
+    let matched: usize = elements.iter().map(|&(node, element): &(dom::NodeId, &dom::Element)| {
+        let quick: Vec<(css::Specificity, &css::Rule)> = style::matched_rules(element, node, &stylesheet, &states);
+        let general: Vec<(css::Specificity, &css::Rule)> = matched_without_quick_check(element, node, &stylesheet, &states);
+        assert!(quick.len() == general.len() && quick.iter().zip(&general).all(|(a, b)| a.0 == b.0 && std::ptr::eq(a.1, b.1)));
+        quick.len()
+    }).sum();
+
+    let general: Duration = time(|| elements.iter()
+        .map(|&(node, element): &(dom::NodeId, &dom::Element)| matched_without_quick_check(element, node, &stylesheet, &states).len())
+        .sum::<usize>());
+    let quick: Duration = time(|| elements.iter()
+        .map(|&(node, element): &(dom::NodeId, &dom::Element)| style::matched_rules(element, node, &stylesheet, &states).len())
+        .sum::<usize>());
+    println!("{} elements x {} rules ({} matches)", elements.len(), stylesheet.rules.len(), matched);
+    println!("general matcher: {:?}", general);
+    println!("with quick check: {:?} ({:.1}x faster)", quick, general.as_secs_f64() / quick.as_secs_f64());
+}
@@ -0,0 +1,18 @@
+//! The CSS parser never panics, and a stylesheet it parsed prints as CSS that parses back
+//! to the same stylesheet.
+
+#![no_main]
+
+use build_a_browser_engine_in_rust::css;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let stylesheet: css::Stylesheet = match css::parse_bytes(data) {
+        Ok(stylesheet) => stylesheet,
+        Err(_) => return,
+    };
+    let printed: String = stylesheet.to_string();
+    let reparsed: css::Stylesheet = css::parse(&printed)
+        .unwrap_or_else(|err: css::ParseError| panic!("{} in printed stylesheet:\n{}", err, printed));
+    assert_eq!(printed, reparsed.to_string());
+});
@@ -0,0 +1,13 @@
+//! The HTML parser returns a document or an error for any input, and never panics.
+
+#![no_main]
+
+use build_a_browser_engine_in_rust::{encoding, html};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Err(err) = html::parse_bytes(data) {
+        // Errors point into the decoded text.
+        assert!(err.position <= encoding::decode_html(data, None).0.len());
+    }
+});
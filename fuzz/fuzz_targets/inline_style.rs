@@ -0,0 +1,14 @@
+//! `parse_inline` skips what it can't parse, and never panics.
+
+#![no_main]
+
+use build_a_browser_engine_in_rust::css;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &str| {
+    for declaration in css::parse_inline(data) {
+        // Whatever it keeps is a declaration a stylesheet could hold.
+        let rule: String = format!("* {{ {} }}", declaration);
+        assert!(css::parse(&rule).is_ok(), "{:?} doesn't parse", rule);
+    }
+});
@@ -0,0 +1,18 @@
+//! The whole pipeline up to the display list: whatever parses can be styled and laid out
+//! without panicking. The input is the HTML, then a NUL byte, then the CSS.
+
+#![no_main]
+
+use build_a_browser_engine_in_rust::engine::Page;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let (html, css) = match data.iter().position(|&b: &u8| b == 0) {
+        Some(i) => (&data[..i], &data[i + 1..]),
+        None => (data, &[][..]),
+    };
+    let (html, css) = (String::from_utf8_lossy(html), String::from_utf8_lossy(css));
+    if let Ok(mut page) = Page::load(&html, &css) {
+        page.layout();
+    }
+});
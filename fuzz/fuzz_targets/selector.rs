@@ -0,0 +1,11 @@
+//! `parse_selector_list` (behind `querySelector`) takes untrusted strings from scripts and
+//! from the automation API, and must never panic.
+
+#![no_main]
+
+use build_a_browser_engine_in_rust::css;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &str| {
+    let _ = css::parse_selector_list(data);
+});
@@ -0,0 +1,133 @@
+//! Property tests for the parsers, run with `cargo test` in this directory. They check
+//! the same things as the fuzz targets, on inputs shaped like HTML and CSS, so they find
+//! edge cases in seconds on stable Rust.
+
+use build_a_browser_engine_in_rust::{css, dom, html};
+use proptest::prelude::*;
+use std::collections::BTreeMap;
+
+/// A document to generate, as markup and as the tree it should parse to.
+#[derive(Clone, Debug)]
+enum Node {
+    Element(String, BTreeMap<String, String>, Vec<Node>),
+    Text(String),
+}
+
+impl Node {
+    fn to_html(&self) -> String {
+        match self {
+            Node::Text(text) => text.clone(),
+            Node::Element(tag_name, attributes, children) => {
+                let mut html: String = format!("<{}", tag_name);
+                for (name, value) in attributes {
+                    html.push_str(&format!(" {}=\"{}\"", name, value));
+                }
+                html.push('>');
+                for child in children {
+                    html.push_str(&child.to_html());
+                }
+                html.push_str(&format!("</{}>", tag_name));
+                html
+            }
+        }
+    }
+
+    /// Does `node` in `tree` have this shape?
+    fn matches(&self, tree: &dom::Tree, node: dom::NodeId) -> bool {
+        match (self, &tree[node].node_type) {
+            (Node::Text(text), dom::NodeType::Text(parsed)) => text == parsed,
+            (Node::Element(tag_name, attributes, children), dom::NodeType::Element(element)) => {
+                let parsed: Vec<dom::NodeId> = tree.children(node).collect();
+                element.tag_name == *tag_name
+                    && attributes.len() == element.attributes.len()
+                    && attributes.iter().all(|(name, value): (&String, &String)| element.attr(name) == Some(value.as_str()))
+                    && children.len() == parsed.len()
+                    && children.iter().zip(parsed).all(|(child, parsed): (&Node, dom::NodeId)| child.matches(tree, parsed))
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Well-formed markup in the dialect the parser supports. Text never starts with
+/// whitespace (the parser skips it between nodes), and never follows other text.
+fn document() -> impl Strategy<Value = Node> {
+    let text = "[a-zA-Z0-9.,!?&;'][a-zA-Z0-9 .,!?&;']{0,20}".prop_map(Node::Text);
+    let attributes = prop::collection::btree_map("[a-z][a-z0-9]{0,5}", "[^\"]{0,10}", 0..3);
+    let leaf = ("[a-z][a-z0-9]{0,5}", attributes.clone()).prop_map(|(tag_name, attributes)| Node::Element(tag_name, attributes, Vec::new()));
+    leaf.prop_recursive(6, 64, 6, move |inner| {
+        let child = (prop::option::of(text.clone()), inner);
+        ("[a-z][a-z0-9]{0,5}", attributes.clone(), prop::collection::vec(child, 0..6), prop::option::of(text.clone()))
+            .prop_map(|(tag_name, attributes, children, last)| {
+                let mut nodes: Vec<Node> = Vec::new();
+                for (text, element) in children {
+                    nodes.extend(text);
+                    nodes.push(element);
+                }
+                nodes.extend(last);
+                Node::Element(tag_name, attributes, nodes)
+            })
+    })
+}
+
+/// Stylesheet-shaped text: mostly valid, with some of everything the parser rejects.
+fn stylesheet_source() -> impl Strategy<Value = String> {
+    let selector = "([a-z]{1,4}|\\*)?(#[a-z]{1,4})?(\\.[a-z]{1,4}){0,2}(:(hover|active|focus|visited))?";
+    let value = "(auto|block|none|[0-9]{1,3}(\\.[0-9])?(px|em)|#[0-9a-fA-F]{6}|#[0-9g]{2}|[0-9]{40}px)";
+    let declaration = ("[a-z-]{1,10}", value).prop_map(|(name, value)| format!("{}: {};", name, value));
+    let rule = (prop::collection::vec(selector, 1..3), prop::collection::vec(declaration, 0..4))
+        .prop_map(|(selectors, declarations)| format!("{} {{ {} }}", selectors.join(", "), declarations.join(" ")));
+    prop::collection::vec(rule, 0..5).prop_map(|rules| rules.join("\n"))
+}
+
+proptest! {
+    #[test]
+    fn html_parser_never_panics(source in any::<String>()) {
+        let _ = html::parse(&source);
+    }
+
+    #[test]
+    fn html_parser_never_panics_on_markup(source in "[<>/=\" a-z!-]{0,200}") {
+        if let Err(err) = html::parse(&source) {
+            prop_assert!(err.position <= source.len());
+        }
+    }
+
+    #[test]
+    fn well_formed_html_parses_to_its_tree(root in document()) {
+        let document: dom::Document = html::parse(&root.to_html()).unwrap();
+        prop_assert!(root.matches(&document.tree, document.root_element), "{}", dom::dump(&document.tree, document.root_element));
+    }
+
+    #[test]
+    fn deeply_nested_html_is_an_error(depth in html::MAX_DEPTH + 1..html::MAX_DEPTH * 4) {
+        let source: String = format!("{}{}", "<a>".repeat(depth), "</a>".repeat(depth));
+        prop_assert!(html::parse(&source).is_err());
+    }
+
+    #[test]
+    fn css_parser_never_panics(source in any::<String>()) {
+        let _ = css::parse(&source);
+    }
+
+    #[test]
+    fn printed_stylesheets_parse_back(source in stylesheet_source()) {
+        if let Ok(stylesheet) = css::parse(&source) {
+            let printed: String = stylesheet.to_string();
+            let reparsed: css::Stylesheet = css::parse(&printed).unwrap();
+            prop_assert_eq!(printed, reparsed.to_string());
+        }
+    }
+
+    #[test]
+    fn inline_styles_keep_only_valid_declarations(source in "[a-z:;#0-9 .-]{0,60}") {
+        for declaration in css::parse_inline(&source) {
+            prop_assert!(css::parse(&format!("* {{ {} }}", declaration)).is_ok());
+        }
+    }
+
+    #[test]
+    fn selector_lists_never_panic(source in any::<String>()) {
+        let _ = css::parse_selector_list(&source);
+    }
+}
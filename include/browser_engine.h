@@ -0,0 +1,95 @@
+/*
+ * The engine's C API. Build the shared library with
+ *
+ *     cargo build --release --features capi
+ *
+ * and link against target/release/libbuild_a_browser_engine_in_rust.so (or .dylib, or
+ * .dll). See src/capi.rs for how pages, strings, and errors are handled.
+ *
+ * Generated from src/capi.rs by tests/capi.rs; don't edit it by hand.
+ */
+
+#ifndef BROWSER_ENGINE_H
+#define BROWSER_ENGINE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A page, owned by the caller until it is passed to engine_free. */
+typedef struct EnginePage EnginePage;
+
+/* Parse html and css into a new page, with an 800x600 viewport. Returns NULL if
+ * either doesn't parse. */
+EnginePage *engine_load_html(const char *html, const char *css);
+
+/* Load a page from an http:// URL or a file, with its <style> elements as its
+ * stylesheet. Returns NULL if it can't be loaded. */
+EnginePage *engine_load_url(const char *url);
+
+/* Free a page. Does nothing if page is NULL. */
+void engine_free(EnginePage *page);
+
+/* Free a string returned by another function. Does nothing if s is NULL. */
+void engine_free_string(char *s);
+
+/* The message for the last call on this thread that failed, or NULL. The string is
+ * valid until the next failing call on the same thread. */
+const char *engine_last_error(void);
+
+/* Resize the viewport, in pixels. */
+void engine_set_viewport(EnginePage *page, size_t width, size_t height);
+
+/* Paint the page into buffer as RGBA pixels, row by row from the top left. Returns the
+ * number of bytes the viewport needs (width * height * 4); if length is smaller than
+ * that, nothing is written, so passing a NULL buffer asks for the size. */
+size_t engine_render_rgba(EnginePage *page, uint8_t *buffer, size_t length);
+
+/* Paint the page and encode the viewport as a PNG file into buffer. Returns the size of
+ * the file; if length is smaller than that, nothing is written. */
+size_t engine_screenshot_png(EnginePage *page, uint8_t *buffer, size_t length);
+
+/* The deepest node at a point in the viewport, or -1 if there is none. */
+int64_t engine_hit_test(EnginePage *page, float x, float y);
+
+/* The first element matching selector, or -1 if there is none (or the selector
+ * doesn't parse). */
+int64_t engine_query_selector(EnginePage *page, const char *selector);
+
+/* Every element matching selector, in document order, written to nodes. Returns how
+ * many there are; if length is smaller than that, nothing is written. */
+size_t engine_query_selector_all(EnginePage *page, const char *selector, int64_t *nodes, size_t length);
+
+/* The text of node and its descendants, or NULL if there is no such node. */
+char *engine_text_content(EnginePage *page, int64_t node);
+
+/* The value of attribute name on element node, or NULL if it doesn't have one. */
+char *engine_attribute(EnginePage *page, int64_t node, const char *name);
+
+/* The value of CSS property property on node, e.g. "block" or "#ff0000", or NULL if
+ * the node isn't rendered or the property isn't set. */
+char *engine_computed_style(EnginePage *page, int64_t node, const char *property);
+
+/* Move the mouse to a point in the viewport. Returns true if the page needs to be
+ * rendered again. */
+bool engine_hover(EnginePage *page, float x, float y);
+
+/* Click at a point in the viewport. Returns true if the page needs to be rendered again. */
+bool engine_click(EnginePage *page, float x, float y);
+
+/* Type text into the focused text field. Returns true if the page needs to be rendered
+ * again. */
+bool engine_type_text(EnginePage *page, const char *text);
+
+/* Scroll the viewport by a distance in pixels. */
+void engine_scroll_by(EnginePage *page, float dx, float dy);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BROWSER_ENGINE_H */
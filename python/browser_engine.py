@@ -0,0 +1,227 @@
+"""Python bindings for the browser engine.
+
+Load pages, query them with CSS selectors, inspect their styles, and take screenshots,
+for scraping and testing. The bindings call the engine's C API (see src/capi.rs) through
+ctypes, so there is nothing to compile but the shared library:
+
+    cargo build --release --features capi
+
+The library is looked for in $BROWSER_ENGINE_LIB, then in target/release and
+target/debug next to this directory.
+
+e.g.
+    from browser_engine import Page
+
+    with Page.from_html('<html><body><p class="note">Hi</p></body></html>',
+                        "html, body, p { display: block; }") as page:
+        note = page.query_selector(".note")
+        print(note.text, note.computed_style("display"))  # Hi block
+        open("page.png", "wb").write(page.screenshot())
+"""
+
+import ctypes
+import os
+import sys
+
+__all__ = ["EngineError", "Node", "Page"]
+
+
+class EngineError(Exception):
+    """A page couldn't be loaded, or a call into the engine failed."""
+
+
+def _library_path():
+    if "BROWSER_ENGINE_LIB" in os.environ:
+        return os.environ["BROWSER_ENGINE_LIB"]
+    if sys.platform == "win32":
+        name = "build_a_browser_engine_in_rust.dll"
+    elif sys.platform == "darwin":
+        name = "libbuild_a_browser_engine_in_rust.dylib"
+    else:
+        name = "libbuild_a_browser_engine_in_rust.so"
+    root = os.path.join(os.path.dirname(os.path.abspath(__file__)), os.pardir, "target")
+    for profile in ("release", "debug"):
+        path = os.path.join(root, profile, name)
+        if os.path.exists(path):
+            return path
+    raise EngineError("can't find %s; build it with `cargo build --release --features capi`" % name)
+
+
+def _load_library():
+    lib = ctypes.CDLL(_library_path())
+    page, string, owned = ctypes.c_void_p, ctypes.c_char_p, ctypes.c_void_p
+    signatures = {
+        "engine_load_html": (page, [string, string]),
+        "engine_load_url": (page, [string]),
+        "engine_free": (None, [page]),
+        "engine_free_string": (None, [owned]),
+        "engine_last_error": (string, []),
+        "engine_set_viewport": (None, [page, ctypes.c_size_t, ctypes.c_size_t]),
+        "engine_render_rgba": (ctypes.c_size_t, [page, ctypes.c_void_p, ctypes.c_size_t]),
+        "engine_screenshot_png": (ctypes.c_size_t, [page, ctypes.c_void_p, ctypes.c_size_t]),
+        "engine_hit_test": (ctypes.c_int64, [page, ctypes.c_float, ctypes.c_float]),
+        "engine_query_selector": (ctypes.c_int64, [page, string]),
+        "engine_query_selector_all": (ctypes.c_size_t, [page, string, ctypes.c_void_p, ctypes.c_size_t]),
+        "engine_text_content": (owned, [page, ctypes.c_int64]),
+        "engine_attribute": (owned, [page, ctypes.c_int64, string]),
+        "engine_computed_style": (owned, [page, ctypes.c_int64, string]),
+        "engine_hover": (ctypes.c_bool, [page, ctypes.c_float, ctypes.c_float]),
+        "engine_click": (ctypes.c_bool, [page, ctypes.c_float, ctypes.c_float]),
+        "engine_type_text": (ctypes.c_bool, [page, string]),
+        "engine_scroll_by": (None, [page, ctypes.c_float, ctypes.c_float]),
+    }
+    for name, (restype, argtypes) in signatures.items():
+        function = getattr(lib, name)
+        function.restype = restype
+        function.argtypes = argtypes
+    return lib
+
+
+_lib = None
+
+
+def _engine():
+    global _lib
+    if _lib is None:
+        _lib = _load_library()
+    return _lib
+
+
+def _last_error():
+    message = _engine().engine_last_error()
+    return message.decode("utf-8") if message else "unknown error"
+
+
+def _take_string(pointer):
+    """Decode a string the engine returned, and free it. NULL becomes None."""
+    if not pointer:
+        return None
+    try:
+        return ctypes.string_at(pointer).decode("utf-8")
+    finally:
+        _engine().engine_free_string(pointer)
+
+
+def _read_buffer(function, page):
+    """Call a function that fills a buffer, asking for its size first."""
+    size = function(page, None, 0)
+    buffer = ctypes.create_string_buffer(size)
+    function(page, buffer, size)
+    return buffer.raw
+
+
+class Node:
+    """A node in a page's document, identified by its index."""
+
+    def __init__(self, page, index):
+        self.page = page
+        self.index = index
+
+    def __eq__(self, other):
+        return isinstance(other, Node) and other.page is self.page and other.index == self.index
+
+    def __hash__(self):
+        return hash((id(self.page), self.index))
+
+    def __repr__(self):
+        return "Node(%d)" % self.index
+
+    @property
+    def text(self):
+        """The text of this node and its descendants, as in the DOM's textContent."""
+        return _take_string(_engine().engine_text_content(self.page._handle(), self.index))
+
+    def attribute(self, name):
+        """The value of attribute `name`, or None."""
+        return _take_string(_engine().engine_attribute(self.page._handle(), self.index, name.encode("utf-8")))
+
+    def computed_style(self, name):
+        """The value of CSS property `name` on this node, e.g. "block", or None if the node
+        isn't rendered or the property isn't set."""
+        return _take_string(_engine().engine_computed_style(self.page._handle(), self.index, name.encode("utf-8")))
+
+
+class Page:
+    """A loaded page. Close it (or use it in a `with` block) to free it."""
+
+    def __init__(self, handle):
+        self._page = handle
+
+    @classmethod
+    def from_html(cls, html, css=""):
+        """Parse `html` and `css` into a page, with an 800x600 viewport."""
+        handle = _engine().engine_load_html(html.encode("utf-8"), css.encode("utf-8"))
+        if not handle:
+            raise EngineError(_last_error())
+        return cls(handle)
+
+    @classmethod
+    def load(cls, url):
+        """Load a page from an http:// URL or a file, styled by its <style> elements."""
+        handle = _engine().engine_load_url(url.encode("utf-8"))
+        if not handle:
+            raise EngineError(_last_error())
+        return cls(handle)
+
+    def close(self):
+        if self._page:
+            _engine().engine_free(self._page)
+            self._page = None
+
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *exception):
+        self.close()
+
+    def __del__(self):
+        self.close()
+
+    def _handle(self):
+        if not self._page:
+            raise EngineError("the page is closed")
+        return self._page
+
+    def set_viewport(self, width, height):
+        _engine().engine_set_viewport(self._handle(), width, height)
+
+    def query_selector(self, selector):
+        """The first element matching `selector`, or None."""
+        index = _engine().engine_query_selector(self._handle(), selector.encode("utf-8"))
+        return Node(self, index) if index >= 0 else None
+
+    def query_selector_all(self, selector):
+        """Every element matching `selector`, in document order."""
+        selector = selector.encode("utf-8")
+        count = _engine().engine_query_selector_all(self._handle(), selector, None, 0)
+        nodes = (ctypes.c_int64 * count)()
+        _engine().engine_query_selector_all(self._handle(), selector, nodes, count)
+        return [Node(self, index) for index in nodes]
+
+    def node_at(self, x, y):
+        """The deepest node at a point in the viewport, or None."""
+        index = _engine().engine_hit_test(self._handle(), x, y)
+        return Node(self, index) if index >= 0 else None
+
+    def screenshot(self):
+        """The viewport as a PNG file."""
+        return _read_buffer(_engine().engine_screenshot_png, self._handle())
+
+    def render_rgba(self):
+        """The viewport's pixels, as RGBA bytes row by row from the top left."""
+        return _read_buffer(_engine().engine_render_rgba, self._handle())
+
+    def click(self, x, y):
+        """Click at a point. Returns True if the page changed."""
+        return _engine().engine_click(self._handle(), x, y)
+
+    def hover(self, x, y):
+        """Move the mouse to a point. Returns True if the page changed."""
+        return _engine().engine_hover(self._handle(), x, y)
+
+    def type_text(self, text):
+        """Type into the focused text field. Returns True if the page changed."""
+        return _engine().engine_type_text(self._handle(), text.encode("utf-8"))
+
+    def scroll_by(self, dx, dy):
+        _engine().engine_scroll_by(self._handle(), dx, dy)
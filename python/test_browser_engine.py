@@ -0,0 +1,62 @@
+"""Tests for the Python bindings. Build the library first, then run:
+
+    cargo build --features capi
+    python3 -m unittest discover python
+"""
+
+import unittest
+
+from browser_engine import EngineError, Page
+
+HTML = """<html><body>
+<div id="main" class="box"><p class="note">First</p><p class="note">Second</p></div>
+<a href="/next">Next</a>
+</body></html>"""
+
+CSS = """html, body, div, p { display: block; }
+body { margin: 0px; }
+#main { background: #ff0000; height: 40px; }
+.note { color: #0000ff; }"""
+
+
+class PageTest(unittest.TestCase):
+    def setUp(self):
+        self.page = Page.from_html(HTML, CSS)
+        self.page.set_viewport(100, 50)
+
+    def tearDown(self):
+        self.page.close()
+
+    def test_query_selector(self):
+        notes = self.page.query_selector_all(".note")
+        self.assertEqual([note.text for note in notes], ["First", "Second"])
+        self.assertEqual(self.page.query_selector(".note"), notes[0])
+        self.assertIsNone(self.page.query_selector(".missing"))
+
+    def test_attributes_and_styles(self):
+        self.assertEqual(self.page.query_selector("a").attribute("href"), "/next")
+        self.assertIsNone(self.page.query_selector("a").attribute("title"))
+        main = self.page.query_selector("#main")
+        self.assertEqual(main.computed_style("display"), "block")
+        self.assertEqual(main.computed_style("background"), "#ff0000")
+        self.assertIsNone(main.computed_style("border-color"))
+
+    def test_rendering(self):
+        pixels = self.page.render_rgba()
+        self.assertEqual(len(pixels), 100 * 50 * 4)
+        self.assertEqual(pixels[:4], bytes([255, 0, 0, 255]))
+        self.assertTrue(self.page.screenshot().startswith(b"\x89PNG"))
+        # The deepest node there is the text of the first note.
+        self.assertEqual(self.page.node_at(5, 5).text, "First")
+        self.assertIsNone(self.page.node_at(500, 500))
+
+    def test_errors(self):
+        with self.assertRaises(EngineError):
+            Page.from_html("<div>", "")
+        self.page.close()
+        with self.assertRaises(EngineError):
+            self.page.query_selector("p")
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,464 @@
+//! The accessibility tree: what a screen reader sees of a page.
+
+use crate::collections::{HashMap, HashSet};
+use crate::{dom, layout, style};
+use std::fmt;
+
+
+/*
+    Accessibility
+
+    Screen readers don't look at pixels. They walk a tree of accessible objects, each with
+    a role (what it is: a link, a heading, a button), a name (what it says), and bounds
+    (where it is, for magnifiers and touch exploration). Browsers derive this tree from
+    the DOM, styles, and layout:
+        - The role comes from the element's tag (<a href> is a link, <h1> is a heading),
+          unless its `role` attribute says otherwise.
+        - The name comes from `aria-label`, then `alt` for images, then the rendered text
+          for roles that are named by their content (links, buttons, headings, cells),
+          or the `<caption>` for tables, then the `title` attribute.
+        - Anything not rendered (`display: none`) is left out, and so is anything marked
+          `aria-hidden="true"`, and images with an empty `alt` (which are decorative).
+        - Generic containers like <div> and <span> with no name add nothing, so they are
+          left out too, and their children take their place.
+
+    We build the tree from the layout tree, which already leaves out `display: none`.
+    Bounds are border boxes in document coordinates (CSS px), with the scroll positions of
+    any `overflow` boxes applied.
+
+    e.g.
+        <body><nav><a href="/">Home</a></nav><div><h1>Title</h1><img alt="A cat"></div></body>
+        => (printed, without bounds)
+        document
+          navigation
+            link "Home"
+              text "Home"
+          heading "Title" level 1
+            text "Title"
+          image "A cat"
+ */
+
+/// What kind of thing an accessible node is, named after the ARIA roles.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Role {
+    Document,
+    Article,
+    Banner,
+    Button,
+    Caption,
+    Cell,
+    CheckBox,
+    ColumnHeader,
+    ComboBox,
+    Complementary,
+    ContentInfo,
+    Form,
+    Generic,
+    Heading,
+    Image,
+    Link,
+    List,
+    ListItem,
+    Main,
+    Navigation,
+    Paragraph,
+    RadioButton,
+    Region,
+    Row,
+    RowHeader,
+    Table,
+    Text,
+    TextBox,
+}
+
+/// One node of the accessibility tree.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AccessibleNode {
+    pub node: dom::NodeId,
+    pub role: Role,
+    pub name: String,
+    pub level: Option<u8>,                  // for headings
+    pub row_index: Option<usize>,           // for rows and cells, counting from 1
+    pub column_index: Option<usize>,        // for cells, counting from 1
+    pub table_size: Option<(usize, usize)>, // for tables, how many rows and columns they have
+    pub bounds: layout::Rect,
+    pub children: Vec<AccessibleNode>,
+}
+
+/// Build the accessibility tree for a laid-out page. The root is always a `Document`.
+pub fn accessibility_tree(root: &layout::LayoutBox, offsets: &layout::ScrollOffsets) -> AccessibleNode {
+    let mut children: Vec<AccessibleNode> = Vec::new();
+    let (translate, _) = root.children_transform(offsets, (0.0, 0.0), None);
+    for child in root.children.iter() {
+        collect(child, offsets, translate, &mut children);
+    }
+    AccessibleNode {
+        node: root.node_id().expect("the root box belongs to the root element"),
+        role: Role::Document,
+        name: String::new(),
+        level: None,
+        row_index: None,
+        column_index: None,
+        table_size: None,
+        bounds: root.dimensions.border_box(),
+        children,
+    }
+}
+
+/// Add the accessible nodes for a box (and its descendants) to `result`.
+fn collect(layout_box: &layout::LayoutBox, offsets: &layout::ScrollOffsets, translate: (f32, f32), result: &mut Vec<AccessibleNode>) {
+    let (child_translate, _) = layout_box.children_transform(offsets, translate, None);
+    let style_node: &style::StyledNode = match layout_box.box_type {
+        layout::BoxType::BlockNode(style_node) | layout::BoxType::InlineNode(style_node) => style_node,
+        layout::BoxType::AnonymousBlock => {
+            for child in layout_box.children.iter() {
+                collect(child, offsets, child_translate, result);
+            }
+            return;
+        }
+    };
+    let bounds: layout::Rect = layout_box.dimensions.border_box().translated(translate.0, translate.1);
+
+    let element: &dom::Element = match style_node.node.node_type {
+        dom::NodeType::Element(ref element) => element,
+        dom::NodeType::Text(ref text) => {
+            let name: String = collapse_whitespace(text);
+            if !name.is_empty() {
+                result.push(AccessibleNode {
+                    node: style_node.node_id, role: Role::Text, name, level: None, row_index: None, column_index: None, table_size: None,
+                    bounds, children: Vec::new(),
+                });
+            }
+            return;
+        }
+        dom::NodeType::DocumentFragment => return,
+    };
+    if element.attr("aria-hidden") == Some("true") {
+        return;
+    }
+    let role: Role = match element.attr("role").map(Role::from_aria) {
+        Some(Some(role)) => role,
+        _ => match tag_role(element) {
+            Some(role) => role,
+            None => return, // not rendered content, like <script>, or a decorative image
+        },
+    };
+
+    let mut children: Vec<AccessibleNode> = Vec::new();
+    for child in layout_box.children.iter() {
+        collect(child, offsets, child_translate, &mut children);
+    }
+
+    let name: String = accessible_name(element, role, style_node);
+    if role == Role::Generic && name.is_empty() {
+        // Nothing to say about this node; its children stand in for it.
+        result.extend(children);
+        return;
+    }
+    let level: Option<u8> = match role {
+        Role::Heading => Some(heading_level(element)),
+        _ => None,
+    };
+    let mut node: AccessibleNode = AccessibleNode {
+        node: style_node.node_id, role, name, level, row_index: None, column_index: None, table_size: None, bounds, children,
+    };
+    if element.tag_name == "table" {
+        let grid: TableGrid = TableGrid::new(style_node);
+        node.table_size = Some((grid.rows, grid.columns));
+        grid.apply(&mut node.children);
+    }
+    result.push(node);
+}
+
+
+/*
+    Tables
+
+    A screen reader reads a table cell with where it is ("row 2, column 3") and can move
+    between cells by row and column, so the tree gives each row its row index, and each
+    cell its row and column indices, counting from 1, as ARIA's `aria-rowindex` and
+    `aria-colindex` do. The table itself says how many rows and columns it has, and takes
+    its name from its `<caption>`.
+
+    The indices come from the HTML [table model](https://html.spec.whatwg.org/multipage/tables.html#forming-a-table),
+    not from layout. Rows are the table's `<tr>`s, directly or in a `<thead>`, `<tbody>`,
+    or `<tfoot>`, in source order, and each row's `<td>` and `<th>` cells fill the grid
+    from left to right. A cell with `colspan` takes up that many columns, and one with
+    `rowspan` takes up that many rows, so a cell in a later row skips the columns that
+    a cell from above still covers:
+
+        <tr><td rowspan=2>A</td><td>B</td></tr>
+        <tr><td>C</td></tr>
+            -> A is at row 1, column 1; B at row 1, column 2; C at row 2, column 2
+
+    A `rowspan` of 0 reaches the last row. A table inside a cell is a table of its own.
+ */
+/// The rows and cells of a table, placed in its grid.
+struct TableGrid {
+    positions: HashMap<dom::NodeId, (usize, Option<usize>)>, // each row's row index, and each cell's row and column
+    rows: usize,
+    columns: usize,
+}
+
+impl TableGrid {
+    /// Place the rows and cells of the table `table`. See "Tables", above.
+    fn new(table: &style::StyledNode) -> TableGrid {
+        let rows: Vec<&style::StyledNode> = table.children.iter()
+            .flat_map(|child: &style::StyledNode| match element_tag(child) {
+                Some("thead" | "tbody" | "tfoot") => child.children.iter().collect(),
+                _ => vec![child],
+            })
+            .filter(|row: &&style::StyledNode| element_tag(row) == Some("tr"))
+            .collect();
+        let mut positions: HashMap<dom::NodeId, (usize, Option<usize>)> = HashMap::default();
+        let mut covered: HashSet<(usize, usize)> = HashSet::default(); // slots taken by cells from rows above
+        let mut columns: usize = 0;
+        for (y, row) in rows.iter().enumerate() {
+            positions.insert(row.node_id, (y + 1, None));
+            let mut x: usize = 0;
+            for cell in row.children.iter().filter(|cell: &&style::StyledNode| matches!(element_tag(cell), Some("td" | "th"))) {
+                while covered.contains(&(y, x)) {
+                    x += 1;
+                }
+                let span = |name: &str, default: usize| -> usize {
+                    match cell.node.node_type {
+                        dom::NodeType::Element(ref element) => element.attr(name).and_then(|span: &str| span.trim().parse().ok()).unwrap_or(default),
+                        _ => default,
+                    }
+                };
+                let column_span: usize = span("colspan", 1).clamp(1, 1000);
+                let row_span: usize = match span("rowspan", 1) {
+                    0 => rows.len() - y,
+                    span => span.min(rows.len() - y),
+                };
+                for covered_y in y..y + row_span {
+                    covered.extend((x..x + column_span).map(|covered_x: usize| (covered_y, covered_x)));
+                }
+                positions.insert(cell.node_id, (y + 1, Some(x + 1)));
+                x += column_span;
+            }
+            columns = columns.max(x);
+        }
+        columns = covered.iter().map(|&(_, x): &(usize, usize)| x + 1).fold(columns, usize::max);
+        TableGrid { positions, rows: rows.len(), columns }
+    }
+
+    /// Give the rows and cells among `nodes` and their descendants their indices,
+    /// leaving out nested tables.
+    fn apply(&self, nodes: &mut [AccessibleNode]) {
+        for node in nodes.iter_mut() {
+            if let Some(&(row, column)) = self.positions.get(&node.node) {
+                node.row_index = Some(row);
+                node.column_index = column;
+            }
+            if node.table_size.is_none() {
+                self.apply(&mut node.children);
+            }
+        }
+    }
+}
+
+/// The tag name of the element `node`, if it's an element.
+fn element_tag<'a>(node: &'a style::StyledNode) -> Option<&'a str> {
+    match node.node.node_type {
+        dom::NodeType::Element(ref element) if node.pseudo_element.is_none() => Some(element.tag_name.as_str()),
+        _ => None,
+    }
+}
+
+/// The role an element's tag implies, or `None` if it shouldn't be in the tree at all.
+fn tag_role(element: &dom::Element) -> Option<Role> {
+    Some(match element.tag_name.as_str() {
+        "head" | "script" | "style" | "title" | "meta" | "link" | "template" => return None,
+        "img" if element.attr("alt") == Some("") => return None,
+        "a" if element.attr("href").is_some() => Role::Link,
+        "article" => Role::Article,
+        "aside" => Role::Complementary,
+        "button" => Role::Button,
+        "caption" => Role::Caption,
+        "footer" => Role::ContentInfo,
+        "form" => Role::Form,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => Role::Heading,
+        "header" => Role::Banner,
+        "img" => Role::Image,
+        "input" => match element.attr("type").unwrap_or("text").to_ascii_lowercase().as_str() {
+            "checkbox" => Role::CheckBox,
+            "radio" => Role::RadioButton,
+            "button" | "submit" | "reset" => Role::Button,
+            "hidden" => return None,
+            _ => Role::TextBox,
+        },
+        "li" => Role::ListItem,
+        "main" => Role::Main,
+        "nav" => Role::Navigation,
+        "ol" | "ul" => Role::List,
+        "p" => Role::Paragraph,
+        "section" => Role::Region,
+        "select" => Role::ComboBox,
+        "table" => Role::Table,
+        "td" => Role::Cell,
+        "textarea" => Role::TextBox,
+        "th" => match element.attr("scope").map(str::to_ascii_lowercase).as_deref() {
+            Some("row" | "rowgroup") => Role::RowHeader,
+            _ => Role::ColumnHeader,
+        },
+        "tr" => Role::Row,
+        _ => Role::Generic,
+    })
+}
+
+/// Work out an element's name, in order of preference (see above).
+fn accessible_name(element: &dom::Element, role: Role, style_node: &style::StyledNode) -> String {
+    let label: Option<&str> = element.attr("aria-label")
+        .or(if role == Role::Image { element.attr("alt") } else { None })
+        .or(if element.tag_name == "input" && role == Role::Button { element.attr("value") } else { None })
+        .map(str::trim)
+        .filter(|label: &&str| !label.is_empty());
+    if let Some(label) = label {
+        return collapse_whitespace(label);
+    }
+    if role.named_from_content() {
+        let text: String = collapse_whitespace(&style_node.inner_text());
+        if !text.is_empty() {
+            return text;
+        }
+    }
+    if role == Role::Table {
+        let caption: Option<&style::StyledNode> = style_node.children.iter().find(|child: &&style::StyledNode| element_tag(child) == Some("caption"));
+        let text: String = caption.map(|caption: &style::StyledNode| collapse_whitespace(&caption.inner_text())).unwrap_or_default();
+        if !text.is_empty() {
+            return text;
+        }
+    }
+    element.attr("title").map(collapse_whitespace).unwrap_or_default()
+}
+
+/// The level of an <h1> to <h6>, or the `aria-level` of another heading.
+fn heading_level(element: &dom::Element) -> u8 {
+    element.attr("aria-level").and_then(|level: &str| level.parse().ok())
+        .or_else(|| element.tag_name.strip_prefix('h').and_then(|level: &str| level.parse().ok()))
+        .unwrap_or(2)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+impl Role {
+    /// The role for a `role` attribute value, if it's one we know.
+    pub fn from_aria(name: &str) -> Option<Role> {
+        Some(match name.trim().to_ascii_lowercase().as_str() {
+            "article" => Role::Article,
+            "banner" => Role::Banner,
+            "button" => Role::Button,
+            "caption" => Role::Caption,
+            "cell" | "gridcell" => Role::Cell,
+            "checkbox" => Role::CheckBox,
+            "columnheader" => Role::ColumnHeader,
+            "combobox" => Role::ComboBox,
+            "complementary" => Role::Complementary,
+            "contentinfo" => Role::ContentInfo,
+            "document" => Role::Document,
+            "form" => Role::Form,
+            "generic" | "none" | "presentation" => Role::Generic,
+            "heading" => Role::Heading,
+            "img" | "image" => Role::Image,
+            "link" => Role::Link,
+            "list" => Role::List,
+            "listitem" => Role::ListItem,
+            "main" => Role::Main,
+            "navigation" => Role::Navigation,
+            "paragraph" => Role::Paragraph,
+            "radio" => Role::RadioButton,
+            "region" => Role::Region,
+            "row" => Role::Row,
+            "rowheader" => Role::RowHeader,
+            "table" | "grid" => Role::Table,
+            "textbox" => Role::TextBox,
+            _ => return None,
+        })
+    }
+
+    /// Return true if nodes with this role take their name from their text.
+    pub fn named_from_content(self) -> bool {
+        matches!(self, Role::Button | Role::Caption | Role::Cell | Role::CheckBox | Role::ColumnHeader | Role::Heading
+            | Role::Link | Role::ListItem | Role::RadioButton | Role::RowHeader)
+    }
+}
+
+
+/*
+    Printing the tree, one node per line, makes it easy to compare in tests:
+        heading "Title" level 1 at 8,8 784x37
+        cell "B" row 1 column 2 at 8,45 100x18
+ */
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Role::Document => "document",
+            Role::Article => "article",
+            Role::Banner => "banner",
+            Role::Button => "button",
+            Role::Caption => "caption",
+            Role::Cell => "cell",
+            Role::CheckBox => "checkbox",
+            Role::ColumnHeader => "columnheader",
+            Role::ComboBox => "combobox",
+            Role::Complementary => "complementary",
+            Role::ContentInfo => "contentinfo",
+            Role::Form => "form",
+            Role::Generic => "generic",
+            Role::Heading => "heading",
+            Role::Image => "image",
+            Role::Link => "link",
+            Role::List => "list",
+            Role::ListItem => "listitem",
+            Role::Main => "main",
+            Role::Navigation => "navigation",
+            Role::Paragraph => "paragraph",
+            Role::RadioButton => "radio",
+            Role::Region => "region",
+            Role::Row => "row",
+            Role::RowHeader => "rowheader",
+            Role::Table => "table",
+            Role::Text => "text",
+            Role::TextBox => "textbox",
+        })
+    }
+}
+
+impl AccessibleNode {
+    fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        write!(f, "{}{}", "  ".repeat(depth), self.role)?;
+        if !self.name.is_empty() {
+            write!(f, " {:?}", self.name)?;
+        }
+        if let Some(level) = self.level {
+            write!(f, " level {}", level)?;
+        }
+        if let Some((rows, columns)) = self.table_size {
+            write!(f, " {} rows {} columns", rows, columns)?;
+        }
+        if let Some(row) = self.row_index {
+            write!(f, " row {}", row)?;
+        }
+        if let Some(column) = self.column_index {
+            write!(f, " column {}", column)?;
+        }
+        let b: layout::Rect = self.bounds;
+        writeln!(f, " at {},{} {}x{}", b.x, b.y, b.width, b.height)?;
+        for child in &self.children {
+            child.fmt_indented(f, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for AccessibleNode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_indented(f, 0)
+    }
+}
@@ -0,0 +1,188 @@
+//! Interned strings for names that repeat all over a document.
+
+use crate::collections::HashSet;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use core::borrow::Borrow;
+use core::fmt;
+use core::hash::{Hash, Hasher};
+use core::ops::Deref;
+#[cfg(not(feature = "std"))]
+use core::{cell::UnsafeCell, hash::BuildHasherDefault, sync::atomic};
+#[cfg(feature = "std")]
+use std::sync::{Mutex, OnceLock};
+
+
+/*
+    String Interning
+
+    A large document says "div", "class", and "href" thousands of times. Storing a separate
+    `String` for each of them wastes memory, and comparing them during selector matching
+    means comparing bytes over and over.
+
+    Instead, each distinct name is stored once in a global table, and everything else holds
+    an `Atom`: a pointer to the shared copy. Two atoms are equal exactly when they point to
+    the same string, so comparing them is a single pointer comparison.
+
+    The table is shared by every thread, so it is behind a `Mutex`, or without `std`, a
+    spin lock: a flag each caller waits to set, which is enough for the few threads an
+    embedded device has.
+
+    Interned strings are never freed. That's fine for tag names, attribute names, and CSS
+    identifiers, since a program only ever sees a limited vocabulary of them.
+
+    e.g.
+        Atom::from("div") == Atom::from(String::from("div"))   // same pointer
+ */
+
+/// An interned string with O(1) equality.
+#[derive(Clone, Copy)]
+pub struct Atom(&'static str);
+
+/// Run `f` with the table of every string interned so far.
+#[cfg(feature = "std")]
+fn with_interner<R>(f: impl FnOnce(&mut HashSet<&'static str>) -> R) -> R {
+    static INTERNER: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+    let mut table = INTERNER.get_or_init(|| Mutex::new(HashSet::default())).lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    f(&mut table)
+}
+
+/// Run `f` with the table of every string interned so far, holding a spin lock.
+#[cfg(not(feature = "std"))]
+fn with_interner<R>(f: impl FnOnce(&mut HashSet<&'static str>) -> R) -> R {
+    struct Interner {
+        locked: atomic::AtomicBool,
+        table: UnsafeCell<HashSet<&'static str>>,
+    }
+    // The table is only touched while `locked` is set.
+    unsafe impl Sync for Interner {}
+
+    static INTERNER: Interner = Interner {
+        locked: atomic::AtomicBool::new(false),
+        table: UnsafeCell::new(HashSet::with_hasher(BuildHasherDefault::new())),
+    };
+    while INTERNER.locked.compare_exchange_weak(false, true, atomic::Ordering::Acquire, atomic::Ordering::Relaxed).is_err() {
+        core::hint::spin_loop();
+    }
+    let result: R = f(unsafe { &mut *INTERNER.table.get() });
+    INTERNER.locked.store(false, atomic::Ordering::Release);
+    result
+}
+
+impl Atom {
+    /// Return the atom for `s`, adding it to the table if this is the first time we see it.
+    pub fn new(s: &str) -> Atom {
+        with_interner(|table: &mut HashSet<&'static str>| match table.get(s) {
+            Some(&interned) => Atom(interned),
+            None => {
+                let interned: &'static str = Box::leak(s.to_string().into_boxed_str());
+                table.insert(interned);
+                Atom(interned)
+            }
+        })
+    }
+
+    /// The interned string.
+    pub fn as_str(&self) -> &'static str {
+        self.0
+    }
+}
+
+impl From<&str> for Atom {
+    fn from(s: &str) -> Atom {
+        Atom::new(s)
+    }
+}
+
+impl From<String> for Atom {
+    fn from(s: String) -> Atom {
+        Atom::new(&s)
+    }
+}
+
+impl Deref for Atom {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.0
+    }
+}
+
+// Lets a `HashMap<Atom, _>` be queried with a plain `&str`.
+impl Borrow<str> for Atom {
+    fn borrow(&self) -> &str {
+        self.0
+    }
+}
+
+impl PartialEq for Atom {
+    fn eq(&self, other: &Atom) -> bool {
+        core::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Atom {}
+
+impl PartialEq<str> for Atom {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Atom {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+impl PartialEq<String> for Atom {
+    fn eq(&self, other: &String) -> bool {
+        self.0 == other
+    }
+}
+
+// Hash the contents (not the pointer), so that hashing agrees with `Borrow<str>`.
+impl Hash for Atom {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialOrd for Atom {
+    fn partial_cmp(&self, other: &Atom) -> Option<core::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Atom {
+    fn cmp(&self, other: &Atom) -> core::cmp::Ordering {
+        self.0.cmp(other.0)
+    }
+}
+
+impl fmt::Debug for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.0, f)
+    }
+}
+
+impl fmt::Display for Atom {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Atom {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Atom {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Atom, D::Error> {
+        let s: alloc::borrow::Cow<'de, str> = serde::Deserialize::deserialize(deserializer)?;
+        Ok(Atom::new(&s))
+    }
+}
@@ -0,0 +1,121 @@
+//! Synthetic documents and stylesheets of any size, for benchmarks.
+
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use core::fmt::Write;
+
+
+/*
+    Benchmark Fixtures
+
+    Timing the engine on real pages means checking megabytes of other people's HTML into
+    the repository, and the numbers only mean something if every run uses the same
+    input. Instead, these functions generate pages shaped like the real thing, from a
+    size and nothing else, so a benchmark always sees the same bytes:
+
+    - `article` is a long article with headings, paragraphs of text, links, and lists,
+      like an encyclopedia page. `wikipedia_sized_article` is about the size of a long
+      Wikipedia article's HTML (about 1 MB).
+    - `stylesheet` is a framework-style stylesheet: many small rules on classes, tags,
+      IDs, and `:hover`, most of which some element in an `article` matches.
+      `bootstrap_sized_stylesheet` is about the size of Bootstrap's CSS (about 200 KB).
+    - `deep_tree` nests `<div>`s inside each other, which is hard on everything that
+      walks the tree recursively.
+
+    Everything parses with `html::parse` and `css::parse`, and the stylesheet makes the
+    article's elements blocks (or inline, for links), so the whole pipeline runs on them.
+    `benches/pipeline.rs` times each stage on them; enable the `bench-fixtures` feature to
+    use them elsewhere.
+
+    e.g.
+        let html: String = bench_fixtures::article(50);
+        let css: String = bench_fixtures::stylesheet(500);
+        let page: Page = Page::load(&html, &css)?;
+ */
+
+/// How many distinct classes the generated article and stylesheet share.
+const CLASSES: usize = 40;
+
+/// Sentences that paragraphs are built from, long and short, with a link now and then.
+const SENTENCES: [&str; 6] = [
+    "The engine reads the document one character at a time and builds a tree of nodes.",
+    "Each rule in a stylesheet has selectors, which say which elements it applies to.",
+    "See <a href=\"#layout\" class=\"link\">layout</a> for how boxes get their sizes.",
+    "Short sentences happen too.",
+    "Painting walks the boxes in order and fills rectangles with <b class=\"term\">colors</b>, from the back to the front, until every pixel has been covered.",
+    "Nothing here is real, but it is shaped like the pages people actually read.",
+];
+
+/// An article with `sections` sections of headings, paragraphs, and lists. Each section
+/// adds about 5 KB.
+pub fn article(sections: usize) -> String {
+    let mut html: String = String::from("<html>\n<head><title>A Generated Article</title></head>\n<body class=\"page\">\n");
+    html.push_str("<div id=\"header\" class=\"header\"><h1 class=\"title\">A Generated Article</h1></div>\n<div id=\"contents\" class=\"toc\">\n<ul class=\"toc-list\">\n");
+    for section in 0..sections {
+        let _ = writeln!(html, "<li class=\"toc-item c{}\"><a href=\"#s{}\" class=\"link\">Section {}</a></li>", section % CLASSES, section, section);
+    }
+    html.push_str("</ul>\n</div>\n<div id=\"content\" class=\"content\">\n");
+    let mut sentence: usize = 0;
+    for section in 0..sections {
+        let _ = writeln!(html, "<div id=\"s{}\" class=\"section c{}\">\n<h2 class=\"heading\">Section {}</h2>", section, section % CLASSES, section);
+        for paragraph in 0..8 {
+            let _ = write!(html, "<p class=\"para c{}\">", (section + paragraph) % CLASSES);
+            for _ in 0..7 {
+                html.push_str(SENTENCES[sentence % SENTENCES.len()]);
+                html.push(' ');
+                sentence += 1;
+            }
+            html.push_str("</p>\n");
+        }
+        html.push_str("<ul class=\"list\">\n");
+        for item in 0..6 {
+            let _ = writeln!(html, "<li class=\"item c{}\">Item {} of section {}, with a little text after it.</li>", (section + item) % CLASSES, item, section);
+        }
+        html.push_str("</ul>\n</div>\n");
+    }
+    html.push_str("</div>\n</body>\n</html>\n");
+    html
+}
+
+/// An article about the size of a long Wikipedia article's HTML.
+pub fn wikipedia_sized_article() -> String {
+    article(180)
+}
+
+/// A stylesheet of `rules` rules (plus a few that make the article's elements blocks),
+/// on the classes, tags, and IDs that `article` uses. Each rule is about 60 bytes.
+pub fn stylesheet(rules: usize) -> String {
+    let mut css: String = String::from("html, body, div, h1, h2, p, ul, li { display: block; }\nhead, title { display: none; }\n");
+    for rule in 0..rules {
+        let class: usize = rule % CLASSES;
+        let _ = match rule % 5 {
+            0 => writeln!(css, ".c{} {{ margin-top: {}px; padding-left: {}px; }}", class, rule % 7, rule % 11),
+            1 => writeln!(css, "p.c{}.para {{ background: #{:06x}; }}", class, rule.wrapping_mul(2654435761) & 0xffffff),
+            2 => writeln!(css, "li.c{}:hover, a.link:hover {{ background: #e0e0e0; border-color: #{:06x}; }}", class, rule.wrapping_mul(40503) & 0xffffff),
+            3 => writeln!(css, "#s{} {{ margin-bottom: {}px; border-width: {}px; border-color: #cccccc; }}", rule % 200, 8 + rule % 5, rule % 2),
+            _ => writeln!(css, "ul.c{}, div.section.c{} {{ padding: {}px; margin-left: {}px; }}", class, class, rule % 9, rule % 13),
+        };
+    }
+    css
+}
+
+/// A stylesheet about the size of Bootstrap's CSS.
+pub fn bootstrap_sized_stylesheet() -> String {
+    stylesheet(3500)
+}
+
+/// A document whose body holds `depth` `<div>`s, each inside the one before, with some
+/// text at the bottom. `html::parse` refuses to nest more than `html::MAX_DEPTH`
+/// elements, so `depth` can be at most two less than that.
+pub fn deep_tree(depth: usize) -> String {
+    let mut html: String = String::from("<html><body>");
+    for level in 0..depth {
+        let _ = write!(html, "<div class=\"level c{}\">", level % CLASSES);
+    }
+    html.push_str("At the bottom.");
+    for _ in 0..depth {
+        html.push_str("</div>");
+    }
+    html.push_str("</body></html>");
+    html
+}
@@ -0,0 +1,322 @@
+//! A C API, for embedding the engine in programs written in other languages.
+
+use crate::dom;
+use crate::engine::Page;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+
+/*
+    The C API
+
+    With the `capi` feature, the shared library exports a handful of C functions, declared
+    in `include/browser_engine.h`, so that a C or C++ program (a game engine drawing its
+    menus, say) can render HTML and CSS into its own textures:
+
+        EnginePage *page = engine_load_html("<p>Hello</p>", "p { display: block; }");
+        engine_set_viewport(page, 256, 128);
+        uint8_t pixels[256 * 128 * 4];
+        engine_render_rgba(page, pixels, sizeof pixels);
+        ... upload the pixels, and on a click:
+        if (engine_click(page, x, y)) { engine_render_rgba(page, pixels, sizeof pixels); }
+        engine_free(page);
+
+    A page is an opaque pointer owned by the caller, who must free it with `engine_free`.
+    Strings passed in are UTF-8 and NUL-terminated, and only borrowed for the call.
+    Strings returned are UTF-8 and NUL-terminated too, and the caller must free them with
+    `engine_free_string`. Functions that can fail return NULL or -1, and
+    `engine_last_error` says why.
+
+    Nodes are identified by their index in the DOM (see `dom::NodeId::index`), which stays
+    the same for as long as the document does.
+
+    The header is generated from the signatures and doc comments here by `tests/capi.rs`,
+    which fails when the saved copy is out of date. Keep the signatures on one line, in
+    the types it knows how to spell in C.
+ */
+
+/// A page, as a C program sees it.
+pub struct EnginePage {
+    page: Page,
+}
+
+thread_local! {
+    /// The message for the last call on this thread that failed.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Remember `message` for `engine_last_error`.
+fn set_error(message: String) {
+    let message: CString = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error: &RefCell<Option<CString>>| *error.borrow_mut() = Some(message));
+}
+
+/// Borrow a C string as a `&str`, or record an error if it is NULL or not UTF-8.
+unsafe fn to_str<'a>(s: *const c_char, name: &str) -> Option<&'a str> {
+    if s.is_null() {
+        set_error(format!("{} is NULL", name));
+        return None;
+    }
+    match CStr::from_ptr(s).to_str() {
+        Ok(s) => Some(s),
+        Err(_) => {
+            set_error(format!("{} is not UTF-8", name));
+            None
+        }
+    }
+}
+
+/// Copy `s` into a string for the caller to free with `engine_free_string`.
+fn to_c_string(s: &str) -> *mut c_char {
+    CString::new(s.replace('\0', " ")).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// The node with index `node` in the page's document, or an error if there isn't one.
+fn node_id(page: &Page, node: i64) -> Option<dom::NodeId> {
+    let id: Option<dom::NodeId> = usize::try_from(node).ok().and_then(|index: usize| page.document().tree.node_id(index));
+    if id.is_none() {
+        set_error(format!("No node {}", node));
+    }
+    id
+}
+
+
+/// Parse `html` and `css` into a new page, with an 800x600 viewport. Returns NULL if
+/// either doesn't parse.
+///
+/// # Safety
+/// `html` and `css` must be NULL or point to NUL-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn engine_load_html(html: *const c_char, css: *const c_char) -> *mut EnginePage {
+    let (Some(html), Some(css)) = (to_str(html, "html"), to_str(css, "css")) else {
+        return ptr::null_mut();
+    };
+    match Page::load(html, css) {
+        Ok(page) => Box::into_raw(Box::new(EnginePage { page })),
+        Err(err) => {
+            set_error(err.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Load a page from an `http://` URL or a file, with its `<style>` elements as its
+/// stylesheet. Returns NULL if it can't be loaded.
+///
+/// # Safety
+/// `url` must be NULL or point to a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn engine_load_url(url: *const c_char) -> *mut EnginePage {
+    let Some(url) = to_str(url, "url") else {
+        return ptr::null_mut();
+    };
+    match Page::open(url) {
+        Ok(page) => Box::into_raw(Box::new(EnginePage { page })),
+        Err(err) => {
+            set_error(err.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Free a page. Does nothing if `page` is NULL.
+///
+/// # Safety
+/// `page` must be NULL or come from `engine_load_html`, and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn engine_free(page: *mut EnginePage) {
+    if !page.is_null() {
+        drop(Box::from_raw(page));
+    }
+}
+
+/// Free a string returned by another function. Does nothing if `s` is NULL.
+///
+/// # Safety
+/// `s` must be NULL or come from this API, and not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn engine_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// The message for the last call on this thread that failed, or NULL. The string is
+/// valid until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn engine_last_error() -> *const c_char {
+    LAST_ERROR.with(|error: &RefCell<Option<CString>>| {
+        error.borrow().as_ref().map_or(ptr::null(), |message: &CString| message.as_ptr())
+    })
+}
+
+/// Resize the viewport, in pixels.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`.
+#[no_mangle]
+pub unsafe extern "C" fn engine_set_viewport(page: *mut EnginePage, width: usize, height: usize) {
+    (*page).page.set_viewport(width, height);
+}
+
+/// Paint the page into `buffer` as RGBA pixels, row by row from the top left. Returns the
+/// number of bytes the viewport needs (`width * height * 4`); if `length` is smaller than
+/// that, nothing is written, so passing a NULL buffer asks for the size.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`, and `buffer` must be NULL or have room for
+/// `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn engine_render_rgba(page: *mut EnginePage, buffer: *mut u8, length: usize) -> usize {
+    let pixels: Vec<u8> = (*page).page.paint().to_rgba();
+    if !buffer.is_null() && length >= pixels.len() {
+        ptr::copy_nonoverlapping(pixels.as_ptr(), buffer, pixels.len());
+    }
+    pixels.len()
+}
+
+/// Paint the page and encode the viewport as a PNG file into `buffer`. Returns the size of
+/// the file; if `length` is smaller than that, nothing is written.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`, and `buffer` must be NULL or have room for
+/// `length` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn engine_screenshot_png(page: *mut EnginePage, buffer: *mut u8, length: usize) -> usize {
+    let png: Vec<u8> = (*page).page.screenshot();
+    if !buffer.is_null() && length >= png.len() {
+        ptr::copy_nonoverlapping(png.as_ptr(), buffer, png.len());
+    }
+    png.len()
+}
+
+/// The deepest node at a point in the viewport, or -1 if there is none.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`.
+#[no_mangle]
+pub unsafe extern "C" fn engine_hit_test(page: *mut EnginePage, x: f32, y: f32) -> i64 {
+    (*page).page.node_at(x, y).map_or(-1, |node| node.index() as i64)
+}
+
+/// The first element matching `selector`, or -1 if there is none (or the selector
+/// doesn't parse).
+///
+/// # Safety
+/// `page` must come from `engine_load_html`, and `selector` must be NULL or point to a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn engine_query_selector(page: *mut EnginePage, selector: *const c_char) -> i64 {
+    let Some(selector) = to_str(selector, "selector") else {
+        return -1;
+    };
+    (*page).page.query_selector(selector).map_or(-1, |node| node.index() as i64)
+}
+
+/// Every element matching `selector`, in document order, written to `nodes`. Returns how
+/// many there are; if `length` is smaller than that, nothing is written.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`, `selector` must be NULL or point to a
+/// NUL-terminated string, and `nodes` must be NULL or have room for `length` nodes.
+#[no_mangle]
+pub unsafe extern "C" fn engine_query_selector_all(page: *mut EnginePage, selector: *const c_char, nodes: *mut i64, length: usize) -> usize {
+    let Some(selector) = to_str(selector, "selector") else {
+        return 0;
+    };
+    let found: Vec<dom::NodeId> = (*page).page.query_selector_all(selector);
+    if !nodes.is_null() && length >= found.len() {
+        for (i, node) in found.iter().enumerate() {
+            *nodes.add(i) = node.index() as i64;
+        }
+    }
+    found.len()
+}
+
+/// The text of `node` and its descendants, or NULL if there is no such node.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`.
+#[no_mangle]
+pub unsafe extern "C" fn engine_text_content(page: *mut EnginePage, node: i64) -> *mut c_char {
+    let page: &Page = &(*page).page;
+    match node_id(page, node) {
+        Some(node) => to_c_string(&page.document().tree.text_content(node)),
+        None => ptr::null_mut(),
+    }
+}
+
+/// The value of attribute `name` on element `node`, or NULL if it doesn't have one.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`, and `name` must be NULL or point to a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn engine_attribute(page: *mut EnginePage, node: i64, name: *const c_char) -> *mut c_char {
+    let page: &Page = &(*page).page;
+    let (Some(node), Some(name)) = (node_id(page, node), to_str(name, "name")) else {
+        return ptr::null_mut();
+    };
+    match page.document().tree[node].node_type {
+        dom::NodeType::Element(ref element) => element.attr(name).map_or(ptr::null_mut(), to_c_string),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// The value of CSS property `property` on `node`, e.g. "block" or "#ff0000", or NULL if
+/// the node isn't rendered or the property isn't set.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`, and `property` must be NULL or point to a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn engine_computed_style(page: *mut EnginePage, node: i64, property: *const c_char) -> *mut c_char {
+    let page: &Page = &(*page).page;
+    let (Some(node), Some(property)) = (node_id(page, node), to_str(property, "property")) else {
+        return ptr::null_mut();
+    };
+    page.computed_value(node, property).map_or(ptr::null_mut(), |value| to_c_string(&value.to_string()))
+}
+
+/// Move the mouse to a point in the viewport. Returns true if the page needs to be
+/// rendered again.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`.
+#[no_mangle]
+pub unsafe extern "C" fn engine_hover(page: *mut EnginePage, x: f32, y: f32) -> bool {
+    (*page).page.hover_at(x, y)
+}
+
+/// Click at a point in the viewport. Returns true if the page needs to be rendered again.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`.
+#[no_mangle]
+pub unsafe extern "C" fn engine_click(page: *mut EnginePage, x: f32, y: f32) -> bool {
+    (*page).page.click_at(x, y)
+}
+
+/// Type `text` into the focused text field. Returns true if the page needs to be rendered
+/// again.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`, and `text` must be NULL or point to a
+/// NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn engine_type_text(page: *mut EnginePage, text: *const c_char) -> bool {
+    match to_str(text, "text") {
+        Some(text) => (*page).page.type_text(text),
+        None => false,
+    }
+}
+
+/// Scroll the viewport by a distance in pixels.
+///
+/// # Safety
+/// `page` must come from `engine_load_html`.
+#[no_mangle]
+pub unsafe extern "C" fn engine_scroll_by(page: *mut EnginePage, dx: f32, dy: f32) {
+    (*page).page.scroll_by(dx, dy);
+}
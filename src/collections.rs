@@ -0,0 +1,86 @@
+//! The hash maps and sets the engine uses, with or without `std`.
+
+#[cfg(any(not(feature = "std"), feature = "deterministic"))]
+use core::hash::{BuildHasherDefault, Hasher};
+
+
+/*
+    Hash Maps Without std
+
+    `std::collections::HashMap` seeds its hasher from the operating system's random
+    number generator, so it isn't in `alloc`. Without `std`, we use `hashbrown` (the same
+    table the standard library's map is built on) with a fixed hasher instead.
+
+    The hasher is [FxHash](https://github.com/rust-lang/rustc-hash), the one rustc uses:
+    a multiply and a rotate per word. It is fast, but not resistant to inputs crafted to
+    collide, which matters less on a device rendering its own UI than in a browser
+    loading pages from anywhere.
+
+    The `deterministic` feature uses the same maps with `std`. Their iteration order then
+    depends only on what was inserted, not on a seed that changes every run, so anything
+    printed by walking a map (a dataset, the properties in a JSON style dump) comes out
+    the same each time. The order still depends on the table's layout, which can differ
+    between CPU architectures; output meant to be compared across machines is sorted
+    (see `debug` and `snapshot`).
+
+    Code in the engine makes maps with `HashMap::default()` rather than
+    `HashMap::new()`, which only exists for the standard hasher.
+ */
+
+#[cfg(all(feature = "std", not(feature = "deterministic")))]
+pub use std::collections::{HashMap, HashSet};
+
+/// A hash map, with `FxHasher` since there is no random seed (or it isn't wanted).
+#[cfg(any(not(feature = "std"), feature = "deterministic"))]
+pub type HashMap<K, V> = hashbrown::HashMap<K, V, BuildHasherDefault<FxHasher>>;
+
+/// A hash set, with `FxHasher` since there is no random seed (or it isn't wanted).
+#[cfg(any(not(feature = "std"), feature = "deterministic"))]
+pub type HashSet<T> = hashbrown::HashSet<T, BuildHasherDefault<FxHasher>>;
+
+/// The hasher rustc uses for its own tables.
+#[cfg(any(not(feature = "std"), feature = "deterministic"))]
+#[derive(Default, Clone, Copy)]
+pub struct FxHasher {
+    hash: u64,
+}
+
+#[cfg(any(not(feature = "std"), feature = "deterministic"))]
+impl FxHasher {
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(0x51_7c_c1_b7_27_22_0a_95);
+    }
+}
+
+#[cfg(any(not(feature = "std"), feature = "deterministic"))]
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            self.add(u64::from_le_bytes(chunk.try_into().unwrap()));
+        }
+        for &byte in chunks.remainder() {
+            self.add(byte as u64);
+        }
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.add(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.add(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.add(i);
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.add(i as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
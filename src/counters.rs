@@ -0,0 +1,183 @@
+//! CSS counters: numbering things in document order, for generated content.
+
+use crate::atom::Atom;
+use crate::css;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+/*
+    Counters
+
+    A counter is a named number that elements change as the document goes along:
+    `counter-reset: chapter` starts a new `chapter` counter at 0, `counter-increment:
+    chapter` adds one to it, and `counter-set: chapter 5` sets it. Generated content shows
+    its value with `counter(chapter)`, so headings and list items can number themselves:
+
+        body { counter-reset: chapter; }
+        h2 { counter-increment: chapter; }
+        h2::before { content: "Chapter " counter(chapter) ". "; }
+
+    Each property takes names, each optionally followed by a number: the value to start
+    or set the counter at (0 by default), or how much to add to it (1 by default), as in
+    `counter-reset: chapter 0 figure 0`.
+
+    Counters nest. A reset makes a new counter with the same name as any that are already
+    there, which lasts until the end of the element's parent: the element, its later
+    siblings, and all their descendants see it, and its own children can start yet
+    another inside it. That's how nested lists number each level from 1, and
+    `counters(item, ".")` joins all of a name's counters, outermost first, into "2.1.3".
+    Incrementing or showing a counter that doesn't exist starts one at 0.
+
+    The style tree is built in document order, which is the order counters change in, so
+    it keeps a `Counters` as it goes (see "Generated Content" in `style`). Lists have a
+    counter of their own, as in browsers: `<ol>` and `<ul>` reset `list-item` (to one
+    less than `<ol start>`), and each `<li>` increments it (or sets it, to `<li value>`).
+ */
+/// The counters in scope at one point in the document.
+#[derive(Clone, Default, Debug)]
+pub struct Counters {
+    counters: Vec<(Atom, i32)>, // outermost first
+}
+
+impl Counters {
+    /// How many counters there are. Passing this to `leave` later drops the ones made in
+    /// between.
+    pub fn mark(&self) -> usize {
+        self.counters.len()
+    }
+
+    /// Drop the counters made since `mark`.
+    pub fn leave(&mut self, mark: usize) {
+        self.counters.truncate(mark);
+    }
+
+    /// Start a new counter named `name` at `value`.
+    pub fn reset(&mut self, name: Atom, value: i32) {
+        self.counters.push((name, value));
+    }
+
+    /// Set the innermost counter named `name` to `value`, starting one if there isn't one.
+    pub fn set(&mut self, name: Atom, value: i32) {
+        match self.innermost(name) {
+            Some(counter) => *counter = value,
+            None => self.reset(name, value),
+        }
+    }
+
+    /// Add `by` to the innermost counter named `name`, starting one at 0 if there isn't
+    /// one.
+    pub fn increment(&mut self, name: Atom, by: i32) {
+        match self.innermost(name) {
+            Some(counter) => *counter = counter.saturating_add(by),
+            None => self.reset(name, by),
+        }
+    }
+
+    /// The value of the innermost counter named `name`, or 0 if there isn't one.
+    pub fn value(&self, name: Atom) -> i32 {
+        self.values(name).last().copied().unwrap_or(0)
+    }
+
+    /// The values of all the counters named `name`, outermost first.
+    pub fn values(&self, name: Atom) -> Vec<i32> {
+        self.counters.iter().filter(|&&(counter, _): &&(Atom, i32)| counter == name).map(|&(_, value): &(Atom, i32)| value).collect()
+    }
+
+    fn innermost(&mut self, name: Atom) -> Option<&mut i32> {
+        self.counters.iter_mut().rev().find(|(counter, _): &&mut (Atom, i32)| *counter == name).map(|(_, value): &mut (Atom, i32)| value)
+    }
+}
+
+/// The names and numbers in a `counter-reset`, `counter-increment`, or `counter-set`
+/// value, with `default` for names that have no number. `none` is no names.
+pub fn changes(value: &css::Value, default: i32) -> Vec<(Atom, i32)> {
+    let values: &[css::Value] = match value {
+        css::Value::Sequence(values) => values,
+        value => core::slice::from_ref(value),
+    };
+    let mut changes: Vec<(Atom, i32)> = Vec::new();
+    for value in values {
+        match *value {
+            css::Value::Keyword(name) if name != "none" => changes.push((name, default)),
+            css::Value::Number(number) => {
+                if let Some((_, value)) = changes.last_mut() {
+                    *value = number as i32;
+                }
+            }
+            _ => {}
+        }
+    }
+    changes
+}
+
+
+/*
+    Counter Styles
+
+    `counter(name, style)` writes a counter in one of CSS's predefined counter styles:
+        decimal               1, 2, 3 (the default)
+        decimal-leading-zero  01, 02, 03
+        lower-roman           i, ii, iii (and upper-roman: I, II, III)
+        lower-alpha           a, b, ..., z, aa, ab (also lower-latin, and upper-alpha or
+                              upper-latin for capitals)
+        lower-greek           α, β, γ
+        disc, circle, square  •, ◦, ▪ (the same symbol for every number)
+        none                  nothing
+    A number a style can't write, like 0 in `lower-alpha` or 4000 in `lower-roman`, is
+    written in decimal instead, as in browsers. An unknown style is decimal too.
+ */
+/// Write `value` in counter style `style`.
+pub fn format(value: i32, style: &str) -> String {
+    match style {
+        "decimal-leading-zero" if (0..10).contains(&value) => format!("0{}", value),
+        "decimal-leading-zero" if (-9..0).contains(&value) => format!("-0{}", -value),
+        "lower-roman" | "upper-roman" if (1..4000).contains(&value) => {
+            let roman: String = roman(value as u32);
+            if style == "upper-roman" { roman.to_uppercase() } else { roman }
+        }
+        "lower-alpha" | "lower-latin" if value >= 1 => alphabetic(value as u32, &LATIN),
+        "upper-alpha" | "upper-latin" if value >= 1 => alphabetic(value as u32, &LATIN).to_uppercase(),
+        "lower-greek" if value >= 1 => alphabetic(value as u32, &GREEK),
+        "disc" => "\u{2022}".to_string(),
+        "circle" => "\u{25E6}".to_string(),
+        "square" => "\u{25AA}".to_string(),
+        "none" => String::new(),
+        _ => format!("{}", value),
+    }
+}
+
+const LATIN: [char; 26] = [
+    'a', 'b', 'c', 'd', 'e', 'f', 'g', 'h', 'i', 'j', 'k', 'l', 'm', 'n', 'o', 'p', 'q', 'r', 's', 't', 'u', 'v', 'w', 'x', 'y', 'z',
+];
+
+const GREEK: [char; 24] = [
+    'α', 'β', 'γ', 'δ', 'ε', 'ζ', 'η', 'θ', 'ι', 'κ', 'λ', 'μ', 'ν', 'ξ', 'ο', 'π', 'ρ', 'σ', 'τ', 'υ', 'φ', 'χ', 'ψ', 'ω',
+];
+
+/// `value` (at least 1) in lowercase Roman numerals.
+fn roman(mut value: u32) -> String {
+    const NUMERALS: [(u32, &str); 13] = [
+        (1000, "m"), (900, "cm"), (500, "d"), (400, "cd"), (100, "c"), (90, "xc"),
+        (50, "l"), (40, "xl"), (10, "x"), (9, "ix"), (5, "v"), (4, "iv"), (1, "i"),
+    ];
+    let mut roman: String = String::new();
+    for &(step, numeral) in NUMERALS.iter() {
+        while value >= step {
+            roman.push_str(numeral);
+            value -= step;
+        }
+    }
+    roman
+}
+
+/// `value` (at least 1) counted with `letters`: a, b, ..., z, aa, ab, ...
+fn alphabetic(mut value: u32, letters: &[char]) -> String {
+    let base: u32 = letters.len() as u32;
+    let mut reversed: Vec<char> = Vec::new();
+    while value > 0 {
+        value -= 1;
+        reversed.push(letters[(value % base) as usize]);
+        value /= base;
+    }
+    reversed.iter().rev().collect()
+}
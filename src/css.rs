@@ -4,7 +4,15 @@
 //!     h1, h2, h3 { margin: auto; color: #cc0000; }
 //!     div.note { margin-bottom: 20px; padding: 10px; }
 //!     #answer { display: none; }
+//!     @media print { nav { display: none; } }
 
+use crate::atom::Atom;
+use crate::scan;
+use crate::trace::{stage, Stage};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use core::error::Error;
+use core::fmt::{self, Write};
 
 // Data structures
 
@@ -13,6 +21,8 @@
     A CSS stylesheet is a series of rules. (In the example stylesheet above,
     each line contains one rule.)
  */
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Stylesheet {
     pub rules: Vec<Rule>,
 }
@@ -24,10 +34,68 @@ pub struct Stylesheet {
     series of declarations enclosed in braces.
 
     Rule = Selector (External/Internal CSS) + Declaration (Inline CSS)
+
+    A rule inside an `@media` block applies only when rendering for one of the media
+    types it lists (see "Media Types", below).
  */
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rule {
     pub selectors: Vec<Selector>,
     pub declarations: Vec<Declaration>,
+    pub media: Option<Vec<Atom>>, // the media types of the `@media` block it's in, in lowercase
+}
+
+
+/*
+    Media Types
+
+    A page can style itself differently on paper than on a screen:
+
+        nav, .ads { display: block; }
+        @media print {
+            nav, .ads { display: none; }
+            a::after { content: " (" attr(href) ")"; }
+        }
+
+    The rules inside `@media <types> { ... }` apply only when the page is rendered for one
+    of the comma-separated media types: `screen`, `print`, or `all`, which is both. Other
+    types, like the old `tv` and `handheld`, never match, and an `only` before a type is
+    ignored (it's there to hide the rule from very old browsers). Media features, like
+    `(max-width: 600px)`, aren't supported, and neither are `@media` blocks inside each
+    other or other at-rules. The rules keep their place in the stylesheet, so the order
+    they apply in is the same whichever medium the page is rendered for.
+
+    The page is rendered for the screen unless it's asked to render for print (see
+    `engine::Page::set_media`).
+ */
+/// The medium a page is rendered for.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Media {
+    #[default]
+    Screen,
+    Print,
+}
+
+impl Media {
+    /// The media type's name, as `@media` rules write it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Media::Screen => "screen",
+            Media::Print => "print",
+        }
+    }
+}
+
+impl Rule {
+    /// Whether the rule applies when rendering for `media`.
+    pub fn applies_to(&self, media: Media) -> bool {
+        match self.media {
+            Some(ref types) => types.iter().any(|media_type: &Atom| *media_type == "all" || *media_type == media.name()),
+            None => true,
+        }
+    }
 }
 
 
@@ -37,10 +105,14 @@ pub struct Rule {
     joined by combinators.
 
     In here, a simple selector can include a tag name, an ID prefixed by '#',
-    any number of class names prefixed by '.', or some combination of the above.
-    If the tag name is empty or '*' then it is a “universal selector” that can
-    match any tag.
+    any number of class names prefixed by '.', any number of pseudo-classes prefixed
+    by ':', or some combination of the above. If the tag name is empty or '*' then it
+    is a “universal selector” that can match any tag. It can end with a pseudo-element
+    prefixed by '::', which selects a box generated inside the element rather than the
+    element itself.
  */
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Selector {
     Simple(SimpleSelector),
 }
@@ -53,12 +125,49 @@ pub enum Selector {
             tag_name: "div",
             id: "div-id",
             class: "div-class",
+            pseudo_classes: [PseudoClass::Hover],
+            pseudo_element: Some(PseudoElement::Before),
         }
  */
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SimpleSelector {
-    pub tag_name: Option<String>,
-    pub id: Option<String>,
-    pub class: Vec<String>,
+    pub tag_name: Option<Atom>,
+    pub id: Option<Atom>,
+    pub class: Vec<Atom>,
+    pub pseudo_classes: Vec<PseudoClass>,
+    pub pseudo_element: Option<PseudoElement>,
+}
+
+
+/// The dynamic pseudo-classes, which match based on what the user is doing
+/*
+    Unlike the rest of a selector, these don't depend on the document at all: `a:hover`
+    matches a link only while the mouse is over it. The style module is told which
+    elements are in each state (see `style::ElementStates`).
+
+    e.g.
+        a:hover, button:active, input:focus
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PseudoClass {
+    Hover,
+    Active,
+    Focus,
+}
+
+/// The pseudo-elements: boxes of generated content at the start or end of an element
+/*
+    `h2::before { content: "§ " }` puts a box holding "§ " inside every <h2>, before
+    its first child. Old stylesheets write them with one colon, `h2:before`, which works
+    too. See "Generated Content" in `style`.
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PseudoElement {
+    Before,
+    After,
 }
 
 /// Declaration structure
@@ -71,24 +180,46 @@ pub struct SimpleSelector {
     e.g.
         Declaration { name: "display", value: Value::Keyword("block") }
  */
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Declaration {
-    pub name: String,
+    pub name: Atom,
     pub value: Value,
 }
 
 
 /// This engine supports only a handful of CSS's many value types.
 /*
+    Keywords come from a small vocabulary ("auto", "block", "none", ...), so like property
+    names they are interned: copying one is free, and comparing two is a pointer comparison.
+
     e.g.
         Value::Keywords("block")
         Value::Length(30, Unit::Px)
+        Value::Number(3)
         Value::ColorValue(Color { r: 0, g: 0, b: 0, a: 1 })
+        Value::StringValue("DejaVu Sans")
+        Value::List([StringValue("DejaVu Sans"), Keyword("sans-serif")])
+        Value::Sequence([StringValue("Chapter "), Function("counter", [Keyword("chapter")])])
+        Value::Function("counter", [Keyword("item"), Keyword("upper-roman")])
+
+    A number is a value with no unit, like the `3` in `line-clamp: 3`. A list is a
+    comma-separated value, like the families in `font-family`, and a sequence is a
+    space-separated one, like the parts of `content: "Chapter " counter(chapter)`. A list's
+    items are never lists themselves, and a sequence's are never lists or sequences. A
+    function's arguments are separated by commas, like a list's items.
  */
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
-    Keyword(String),
+    Keyword(Atom),
     Length(f32, Unit),
+    Number(f32),
     ColorValue(Color),
+    StringValue(String),
+    List(Vec<Value>),
+    Sequence(Vec<Value>),
+    Function(Atom, Vec<Value>),
     // insert more values here
 }
 
@@ -99,7 +230,8 @@ pub enum Value {
     e.g.
         Unit::Px, Unit::Em, Unit::Rem
  */
-#[derive(Clone, PartialEq)]
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Unit {
     Px,
     // insert more units here
@@ -114,7 +246,8 @@ pub enum Unit {
 
     Rust note: u8 is an 8-bit unsigned integer, and f32 is a 32-bit float
  */
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Color {
     pub r: u8, // red
     pub g: u8, // green
@@ -146,12 +279,58 @@ impl Selector {
         // http://www.w3.org/TR/selectors/#specificity
         let Selector::Simple(ref simple) = *self;
         let id_count: usize = simple.id.iter().count();
-        let class_count: usize = simple.class.len();
-        let tag_count: usize = simple.tag_name.iter().count();
+        let class_count: usize = simple.class.len() + simple.pseudo_classes.len();
+        let tag_count: usize = simple.tag_name.iter().count() + simple.pseudo_element.iter().count();
         (id_count, class_count, tag_count)
     }
 }
 
+/*
+    Statistics
+
+    How big a stylesheet is, and how specific its selectors are, for tools that report on
+    a site's CSS. The specificity histogram counts every selector, so a rule like
+    `h1, h2 { ... }` adds two to (0, 0, 1). A histogram bunched up at the top (lots of
+    IDs, long chains of classes) usually means rules fighting each other to win the
+    cascade. (To find rules that are mistyped or never match, see `lint`.)
+
+    e.g.
+        css::parse("h1, h2 { margin: auto; } #a.b { color: #cc0000; }")?.stats()
+        => StylesheetStats {
+               rules: 2, selectors: 3, declarations: 2,
+               specificity: [((0, 0, 1), 2), ((1, 1, 0), 1)],
+           }
+ */
+/// Counts of what's in a stylesheet.
+#[derive(Clone, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StylesheetStats {
+    pub rules: usize,
+    pub selectors: usize,
+    pub declarations: usize,
+    pub specificity: Vec<(Specificity, usize)>, // how many selectors have each specificity, least specific first
+}
+
+impl Stylesheet {
+    /// Count the rules, selectors, and declarations, and how many selectors have each
+    /// specificity.
+    pub fn stats(&self) -> StylesheetStats {
+        let mut stats: StylesheetStats = StylesheetStats { rules: self.rules.len(), ..Default::default() };
+        for rule in &self.rules {
+            stats.selectors += rule.selectors.len();
+            stats.declarations += rule.declarations.len();
+            for selector in &rule.selectors {
+                let specificity: Specificity = selector.specificity();
+                match stats.specificity.binary_search_by_key(&specificity, |&(s, _): &(Specificity, usize)| s) {
+                    Ok(index) => stats.specificity[index].1 += 1,
+                    Err(index) => stats.specificity.insert(index, (specificity, 1)),
+                }
+            }
+        }
+        stats
+    }
+}
+
 impl Value {
     /// Return the size of a length in px, or zero for non-lengths.
     pub fn to_px(&self) -> f32 {
@@ -162,6 +341,174 @@ impl Value {
     }
 }
 
+/*
+    Displaying CSS
+
+    Printing the parsed data structures back out as CSS text makes it easy to see what the
+    parser understood. The output isn't always identical to the input (whitespace and
+    selector order may change), but parsing it again gives the same stylesheet, which
+    `tests/round_trip.rs` checks on random stylesheets.
+
+    e.g.
+        println!("{}", stylesheet);
+        => h1, h2 { margin: auto; color: #cc0000; }
+ */
+
+impl fmt::Display for Stylesheet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for rule in &self.rules {
+            writeln!(f, "{}", rule)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Rule {
+    /// Each rule in an `@media` block is written in a block of its own:
+    /// `@media print { h1 { color: #000000; } }`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(ref types) = self.media {
+            write!(f, "@media ")?;
+            for (i, media_type) in types.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", media_type)?;
+            }
+            write!(f, " {{ ")?;
+        }
+        for (i, selector) in self.selectors.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", selector)?;
+        }
+        write!(f, " {{")?;
+        for declaration in &self.declarations {
+            write!(f, " {}", declaration)?;
+        }
+        write!(f, " }}")?;
+        if self.media.is_some() {
+            write!(f, " }}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Selector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Selector::Simple(simple) => write!(f, "{}", simple),
+        }
+    }
+}
+
+impl fmt::Display for SimpleSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.tag_name.is_none() && self.id.is_none() && self.class.is_empty() && self.pseudo_classes.is_empty() {
+            f.write_str("*")?;
+        }
+        if let Some(ref tag_name) = self.tag_name {
+            write!(f, "{}", tag_name)?;
+        }
+        if let Some(ref id) = self.id {
+            write!(f, "#{}", id)?;
+        }
+        for class in &self.class {
+            write!(f, ".{}", class)?;
+        }
+        for pseudo_class in &self.pseudo_classes {
+            write!(f, ":{}", pseudo_class)?;
+        }
+        if let Some(pseudo_element) = self.pseudo_element {
+            write!(f, "::{}", pseudo_element)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for PseudoElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PseudoElement::Before => "before",
+            PseudoElement::After => "after",
+        })
+    }
+}
+
+impl fmt::Display for PseudoClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            PseudoClass::Hover => "hover",
+            PseudoClass::Active => "active",
+            PseudoClass::Focus => "focus",
+        })
+    }
+}
+
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {};", self.name, self.value)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Keyword(keyword) => write!(f, "{}", keyword),
+            Value::Length(length, unit) => write!(f, "{}{}", length, unit),
+            Value::Number(number) => write!(f, "{}", number),
+            Value::ColorValue(color) => write!(f, "{}", color),
+            Value::StringValue(string) => {
+                f.write_char('"')?;
+                for c in string.chars() {
+                    if c == '"' || c == '\\' {
+                        f.write_char('\\')?;
+                    }
+                    f.write_char(c)?;
+                }
+                f.write_char('"')
+            }
+            Value::List(values) => write_separated(f, values, ", "),
+            Value::Sequence(values) => write_separated(f, values, " "),
+            Value::Function(name, arguments) => {
+                write!(f, "{}(", name)?;
+                write_separated(f, arguments, ", ")?;
+                f.write_char(')')
+            }
+        }
+    }
+}
+
+/// Write `values` with `separator` between them.
+fn write_separated(f: &mut fmt::Formatter, values: &[Value], separator: &str) -> fmt::Result {
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            f.write_str(separator)?;
+        }
+        write!(f, "{}", value)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unit::Px => write!(f, "px"),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02x}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
 // Parser
 
 /*
@@ -171,213 +518,418 @@ impl Value {
     it discards the unrecognized part of the stylesheet but still process the remaining portions.
     This is useful because it allows stylesheets to include new syntax but still produce well-defined
     output in older browsers.
+
+    Our parser isn't that forgiving (except for `style` attributes; see `parse_inline`): it
+    stops at the first thing it doesn't understand, and returns a `ParseError` saying what
+    and where.
  */
 
+/// Why the parser gave up, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize, // in bytes from the start of the input
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.position)
+    }
+}
+
+impl Error for ParseError {}
+
 /// CSS Parser structure
 /*
+    The parser borrows its input, and the identifiers it consumes are slices of it, so the
+    only allocations are for the stylesheet's own rules, selectors, and declarations. (Names
+    and keywords are interned; see `atom`.)
+
     e.g.
         Parser {
             input: "str type css input",
             pos: 0, // current position of css input
         }
  */
-struct Parser {
-    input: String,
+struct Parser<'input> {
+    input: &'input str,
     position: usize,
 }
 
 // impl
 
-impl Parser {
+impl<'input> Parser<'input> {
     /// Return true if all input is consumed.
     fn eof(&self) -> bool {
         self.position >= self.input.len()
     }
 
     /// Read the current character without consuming it.
-    fn next_char(&self) -> char {
-        self.input[self.position..].chars().next().unwrap()
+    fn next_char(&self) -> Result<char, ParseError> {
+        self.peek().ok_or_else(|| self.error("Unexpected end of input".to_string()))
+    }
+
+    /// Read the current character without consuming it, or `None` at the end of the input.
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    /// An error at the current position.
+    fn error(&self, message: String) -> ParseError {
+        ParseError { message, position: self.position }
     }
 
     /// Return the current character, and advance self.position to the next character.
-    fn consume_char(&mut self) -> char {
-        let c: char = self.next_char();
+    fn consume_char(&mut self) -> Result<char, ParseError> {
+        let c: char = self.next_char()?;
         self.position += c.len_utf8();
-        c
+        Ok(c)
     }
 
-    /// If the exact string `s` is found at the current position, consume it.
-    /// Otherwise, panic.
-    fn expect_char(&mut self, c: char) {
-        if self.consume_char() != c {
-            panic!("Expected {:?} at byte {} but it was not found", c, self.position);
+    /// If the character `c` is found at the current position, consume it.
+    /// Otherwise, return an error.
+    fn expect_char(&mut self, c: char) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(next) if next == c => {
+                self.position += c.len_utf8();
+                Ok(())
+            }
+            _ => Err(self.error(format!("Expected {:?}", c))),
         }
     }
 
-    /// Consume characters until `test` returns false.
-    fn consume_while(&mut self, test: impl Fn(char) -> bool) -> String {
-        let mut result: String = String::new();
-        while !self.eof() && test(self.next_char()) {
-            result.push(self.consume_char());
-        }
-        result
+    /// Consume the number of bytes `scan` returns for the rest of the input, returning them
+    /// as a slice of the input. See the `scan` module.
+    fn consume(&mut self, scan: impl Fn(&str) -> usize) -> &'input str {
+        let start: usize = self.position;
+        self.position += scan(&self.input[start..]);
+        &self.input[start..self.position]
     }
 
     /// Consume and discard zero or more whitespace characters.
     fn consume_whitespace(&mut self) {
-        self.consume_while(char::is_whitespace);
+        self.consume(scan::whitespace);
     }
 
     /// Parse a property name or keyword.
-    fn parse_identifier(&mut self) -> String {
-        self.consume_while(valid_identifier_char)
+    fn parse_identifier(&mut self) -> &'input str {
+        self.consume(|rest: &str| scan::ascii_run(rest, |byte: u8| valid_identifier_char(byte as char)))
     }
 
     /// Parse two hexadecimal digits.
-    fn parse_hex_pair(&mut self) -> u8 {
-        let s: &str = &self.input[self.position..self.position + 2];
+    fn parse_hex_pair(&mut self) -> Result<u8, ParseError> {
+        let pair: Option<u8> = self.input.get(self.position..self.position + 2)
+            .filter(|s: &&str| s.chars().all(|c: char| c.is_ascii_hexdigit()))
+            .and_then(|s: &str| u8::from_str_radix(s, 16).ok());
+        let value: u8 = pair.ok_or_else(|| self.error("Expected two hexadecimal digits".to_string()))?;
         self.position += 2;
-        u8::from_str_radix(s, 16).unwrap()
+        Ok(value)
     }
 
-    /// Parse color.
-    fn parse_color(&mut self) -> Value {
-        self.expect_char('#');
-        Value::ColorValue(Color {
-            r: self.parse_hex_pair(),
-            g: self.parse_hex_pair(),
-            b: self.parse_hex_pair(),
-            a: 255,
-        })
+    /// Parse a color: `#rrggbb`, or `#rrggbbaa` with an alpha channel.
+    fn parse_color(&mut self) -> Result<Value, ParseError> {
+        self.expect_char('#')?;
+        let (r, g, b) = (self.parse_hex_pair()?, self.parse_hex_pair()?, self.parse_hex_pair()?);
+        let a: u8 = if self.peek().is_some_and(|c: char| c.is_ascii_hexdigit()) { self.parse_hex_pair()? } else { 255 };
+        Ok(Value::ColorValue(Color { r, g, b, a }))
     }
 
     /// Parse unit
-    fn parse_unit(&mut self) -> Unit {
-        match &*self.parse_identifier().to_ascii_lowercase() {
-            "px" => Unit::Px,
-            _ => panic!("unrecognized unit"),
+    fn parse_unit(&mut self) -> Result<Unit, ParseError> {
+        let start: usize = self.position;
+        match self.parse_identifier() {
+            unit if unit.eq_ignore_ascii_case("px") => Ok(Unit::Px),
+            unit => Err(ParseError { message: format!("Unrecognized unit {:?}", unit.to_ascii_lowercase()), position: start }),
         }
     }
 
     /// Parse float
-    fn parse_float(&mut self) -> f32 {
-        self.consume_while(|c: char| matches!(c, '0'..='9' | '.')).parse().unwrap()
+    fn parse_float(&mut self) -> Result<f32, ParseError> {
+        let start: usize = self.position;
+        let number: &str = self.consume(|rest: &str| scan::ascii_run(rest, |byte: u8| matches!(byte, b'0'..=b'9' | b'.')));
+        // Too many digits overflow to infinity, which isn't a length.
+        number.parse().ok().filter(|n: &f32| n.is_finite())
+            .ok_or_else(|| ParseError { message: format!("Invalid number {:?}", number), position: start })
     }
 
     // Methods for parsing values
 
-    /// Parse length.
-    fn parse_length(&mut self) -> Value {
-        Value::Length(self.parse_float(), self.parse_unit())
+    /// Parse a length, or a number if no unit follows.
+    fn parse_length(&mut self) -> Result<Value, ParseError> {
+        let number: f32 = self.parse_float()?;
+        if !self.peek().is_some_and(valid_identifier_char) {
+            return Ok(Value::Number(number));
+        }
+        Ok(Value::Length(number, self.parse_unit()?))
     }
 
-    /// Parse value.
-    fn parse_value(&mut self) -> Value {
-        match self.next_char() {
+    /// Parse a quoted string, e.g. `"DejaVu Sans"` or `'Times New Roman'`. A backslash
+    /// escapes the next character.
+    fn parse_string(&mut self) -> Result<Value, ParseError> {
+        let quote: char = self.consume_char()?;
+        let mut string: String = String::new();
+        loop {
+            match self.consume_char()? {
+                c if c == quote => return Ok(Value::StringValue(string)),
+                '\\' => string.push(self.consume_char()?),
+                c => string.push(c),
+            }
+        }
+    }
+
+    /// Parse one value that isn't a list or a sequence.
+    fn parse_single_value(&mut self) -> Result<Value, ParseError> {
+        match self.next_char()? {
             '0'..='9' => self.parse_length(),
             '#' => self.parse_color(),
-            _ => Value::Keyword(self.parse_identifier()),
+            '"' | '\'' => self.parse_string(),
+            _ => {
+                let name: Atom = Atom::from(self.parse_identifier());
+                if self.peek() != Some('(') {
+                    return Ok(Value::Keyword(name));
+                }
+                self.consume_char()?;
+                self.consume_whitespace();
+                let arguments: Vec<Value> = if self.peek() == Some(')') { Vec::new() } else { self.parse_items()? };
+                self.consume_whitespace();
+                self.expect_char(')')?;
+                Ok(Value::Function(name, arguments))
+            }
         }
     }
 
+    /// Parse a single value, or a space-separated sequence of them.
+    fn parse_sequence(&mut self) -> Result<Value, ParseError> {
+        let mut values: Vec<Value> = vec![self.parse_single_value()?];
+        loop {
+            let start: usize = self.position;
+            self.consume_whitespace();
+            if self.position == start || matches!(self.peek(), None | Some(',' | ';' | '}' | ')' | '!')) {
+                self.position = start;
+                break;
+            }
+            values.push(self.parse_single_value()?);
+        }
+        Ok(if values.len() == 1 { values.pop().unwrap() } else { Value::Sequence(values) })
+    }
+
+    /// Parse a comma-separated list of sequences (or single values).
+    fn parse_items(&mut self) -> Result<Vec<Value>, ParseError> {
+        let mut values: Vec<Value> = vec![self.parse_sequence()?];
+        loop {
+            let start: usize = self.position;
+            self.consume_whitespace();
+            if self.peek() != Some(',') {
+                self.position = start;
+                break;
+            }
+            self.consume_char()?;
+            self.consume_whitespace();
+            values.push(self.parse_sequence()?);
+        }
+        Ok(values)
+    }
+
+    /// Parse value: a single value, a space-separated sequence of them, or a
+    /// comma-separated list of those.
+    fn parse_value(&mut self) -> Result<Value, ParseError> {
+        let mut values: Vec<Value> = self.parse_items()?;
+        Ok(if values.len() == 1 { values.pop().unwrap() } else { Value::List(values) })
+    }
+
     /// Parse one `<property>: <value>;` declaration (Inline CSS).
-    fn parse_declaration(&mut self) -> Declaration {
-        let name: String = self.parse_identifier();
+    fn parse_declaration(&mut self) -> Result<Declaration, ParseError> {
+        let name: Atom = Atom::from(self.parse_identifier());
         self.consume_whitespace();
-        self.expect_char(':');
+        self.expect_char(':')?;
         self.consume_whitespace();
 
-        let value: Value = self.parse_value();
+        let value: Value = self.parse_value()?;
         self.consume_whitespace();
-        self.expect_char(';');
+        self.expect_char(';')?;
 
-        Declaration { name, value }
+        Ok(Declaration { name, value })
     }
 
     /// Parse a list of declarations (Inline CSSs) enclosed in `{ ... }`
-    fn parse_declarations(&mut self) -> Vec<Declaration> {
-        self.expect_char('{');
+    fn parse_declarations(&mut self) -> Result<Vec<Declaration>, ParseError> {
+        self.expect_char('{')?;
         let mut declarations: Vec<Declaration> = Vec::new();
         loop {
             self.consume_whitespace();
-            if self.next_char() == '}' {
-                self.consume_char();
+            if self.next_char()? == '}' {
+                self.consume_char()?;
                 break;
             }
-            declarations.push(self.parse_declaration());
+            declarations.push(self.parse_declaration()?);
         }
-        declarations
+        Ok(declarations)
     }
 
-    /// Parse one simple selector, e.g: `type#id.class1.class2.class3`
-    fn parse_simple_selector(&mut self) -> SimpleSelector {
+    /// Parse one simple selector, e.g: `type#id.class1.class2.class3:hover::before`
+    fn parse_simple_selector(&mut self) -> Result<SimpleSelector, ParseError> {
         let mut selector = SimpleSelector {
             tag_name: None,
             id: None,
             class: Vec::new(),
+            pseudo_classes: Vec::new(),
+            pseudo_element: None,
         };
-        while !self.eof() {
-            match self.next_char() {
+        while let Some(c) = self.peek() {
+            if selector.pseudo_element.is_some() {
+                break; // nothing can follow a pseudo-element
+            }
+            match c {
                 '#' => {
-                    self.consume_char();
-                    selector.id = Some(self.parse_identifier());
+                    self.consume_char()?;
+                    selector.id = Some(Atom::from(self.parse_identifier()));
                 }
                 '.' => {
-                    self.consume_char();
-                    selector.class.push(self.parse_identifier());
+                    self.consume_char()?;
+                    selector.class.push(Atom::from(self.parse_identifier()));
+                }
+                ':' => {
+                    self.consume_char()?;
+                    if self.peek() == Some(':') {
+                        self.consume_char()?;
+                        selector.pseudo_element = Some(self.parse_pseudo_element()?);
+                    } else if let Some(pseudo_element) = self.parse_legacy_pseudo_element() {
+                        selector.pseudo_element = Some(pseudo_element);
+                    } else {
+                        selector.pseudo_classes.push(self.parse_pseudo_class()?);
+                    }
                 }
                 '*' => {
                     // universal selector
-                    self.consume_char();
+                    self.consume_char()?;
                 }
                 c if valid_identifier_char(c) => {
-                    selector.tag_name = Some(self.parse_identifier());
+                    selector.tag_name = Some(Atom::from(self.parse_identifier()));
                 }
                 _ => break
             }
         }
-        selector
+        Ok(selector)
+    }
+
+    /// Parse the name of a pseudo-class, after its ':'.
+    fn parse_pseudo_class(&mut self) -> Result<PseudoClass, ParseError> {
+        let start: usize = self.position;
+        match self.parse_identifier() {
+            name if name.eq_ignore_ascii_case("hover") => Ok(PseudoClass::Hover),
+            name if name.eq_ignore_ascii_case("active") => Ok(PseudoClass::Active),
+            name if name.eq_ignore_ascii_case("focus") => Ok(PseudoClass::Focus),
+            name => Err(ParseError { message: format!("Unsupported pseudo-class :{}", name.to_ascii_lowercase()), position: start }),
+        }
+    }
+
+    /// Parse the name of a pseudo-element, after its '::'.
+    fn parse_pseudo_element(&mut self) -> Result<PseudoElement, ParseError> {
+        let start: usize = self.position;
+        match self.parse_identifier() {
+            name if name.eq_ignore_ascii_case("before") => Ok(PseudoElement::Before),
+            name if name.eq_ignore_ascii_case("after") => Ok(PseudoElement::After),
+            name => Err(ParseError { message: format!("Unsupported pseudo-element ::{}", name.to_ascii_lowercase()), position: start }),
+        }
+    }
+
+    /// Parse `before` or `after` after a single ':', the old way of writing them, if
+    /// that's what comes next.
+    fn parse_legacy_pseudo_element(&mut self) -> Option<PseudoElement> {
+        let start: usize = self.position;
+        let pseudo_element: Option<PseudoElement> = self.parse_pseudo_element().ok();
+        if pseudo_element.is_none() {
+            self.position = start;
+        }
+        pseudo_element
     }
 
     /// Parse a comma-separated list of selectors.
-    fn parse_selectors(&mut self) -> Vec<Selector> {
+    fn parse_selectors(&mut self) -> Result<Vec<Selector>, ParseError> {
         let mut selectors: Vec<Selector> = Vec::new();
         loop {
-            selectors.push(Selector::Simple(self.parse_simple_selector()));
+            selectors.push(Selector::Simple(self.parse_simple_selector()?));
             self.consume_whitespace();
-            match self.next_char() {
+            match self.next_char()? {
                 ',' => {
-                    self.consume_char();
+                    self.consume_char()?;
                     self.consume_whitespace();
                 }
                 '{' => break,
-                c => panic!("Unexpected character {} in selector list", c),
+                c => return Err(self.error(format!("Unexpected character {:?} in selector list", c))),
             }
         }
         // Return selectors with highest specificity first, for use in matching.
         selectors.sort_by_key(|s: &Selector| s.specificity());
-        selectors
+        Ok(selectors)
     }
 
     /// Parse a rule set: `<selectors> { <declarations> }`.
-    fn parse_rule(&mut self) -> Rule {
-        Rule {
-            selectors: self.parse_selectors(),
-            declarations: self.parse_declarations(),
-        }
+    fn parse_rule(&mut self) -> Result<Rule, ParseError> {
+        Ok(Rule {
+            selectors: self.parse_selectors()?,
+            declarations: self.parse_declarations()?,
+            media: None,
+        })
     }
 
-    /// Parse a list of rule sets, separated by optional whitespace.
-    fn parse_rules(&mut self) -> Vec<Rule> {
+    /// Parse a list of rule sets and `@media` blocks, separated by optional whitespace.
+    fn parse_rules(&mut self) -> Result<Vec<Rule>, ParseError> {
         let mut rules: Vec<Rule> = Vec::new();
         loop {
             self.consume_whitespace();
             if self.eof() {
                 break;
             }
-            rules.push(self.parse_rule());
+            if self.peek() == Some('@') {
+                rules.extend(self.parse_media_block()?);
+            } else {
+                rules.push(self.parse_rule()?);
+            }
+        }
+        Ok(rules)
+    }
+
+    /// Parse an `@media <types> { <rules> }` block, returning its rules. See "Media Types".
+    fn parse_media_block(&mut self) -> Result<Vec<Rule>, ParseError> {
+        self.expect_char('@')?;
+        let start: usize = self.position;
+        let name: &str = self.parse_identifier();
+        if !name.eq_ignore_ascii_case("media") {
+            return Err(ParseError { message: format!("Unsupported at-rule @{}", name.to_ascii_lowercase()), position: start });
+        }
+        let mut types: Vec<Atom> = Vec::new();
+        loop {
+            self.consume_whitespace();
+            let start: usize = self.position;
+            let mut media_type: String = self.parse_identifier().to_ascii_lowercase();
+            if media_type == "only" {
+                self.consume_whitespace();
+                media_type = self.parse_identifier().to_ascii_lowercase();
+            }
+            if media_type.is_empty() || media_type == "not" || media_type == "and" {
+                return Err(ParseError { message: "Expected a media type".to_string(), position: start });
+            }
+            types.push(Atom::from(media_type));
+            self.consume_whitespace();
+            match self.next_char()? {
+                ',' => self.consume_char().map(drop)?,
+                '{' => break,
+                c => return Err(self.error(format!("Unexpected character {:?} in media query list", c))),
+            }
+        }
+        self.expect_char('{')?;
+        let mut rules: Vec<Rule> = Vec::new();
+        loop {
+            self.consume_whitespace();
+            match self.next_char()? {
+                '}' => break,
+                '@' => return Err(self.error("Nested at-rules aren't supported".to_string())),
+                _ => rules.push(Rule { media: Some(types.clone()), ..self.parse_rule()? }),
+            }
         }
-        rules
+        self.expect_char('}')?;
+        Ok(rules)
     }
 }
 
@@ -393,7 +945,86 @@ fn valid_identifier_char(c: char) -> bool {
 
 
 /// Parse a whole CSS stylesheet.
-pub fn parse(source: String) -> Stylesheet {
+pub fn parse(source: &str) -> Result<Stylesheet, ParseError> {
+    let stage: Stage = stage!("css::parse", bytes, rules);
+    stage.record("bytes", || source.len());
     let mut parser: Parser = Parser { input: source, position: 0 };
-    Stylesheet { rules: parser.parse_rules() }
+    let rules: Vec<Rule> = parser.parse_rules()?;
+    stage.record("rules", || rules.len());
+    Ok(Stylesheet { rules })
+}
+
+/// Parse a stylesheet from raw bytes. Invalid UTF-8 is replaced with U+FFFD rather than
+/// being an error.
+pub fn parse_bytes(source: &[u8]) -> Result<Stylesheet, ParseError> {
+    parse(&String::from_utf8_lossy(source))
+}
+
+
+/// Parse a selector list like "div.note, #main", as given to `querySelector`.
+pub fn parse_selector_list(source: &str) -> Result<Vec<Selector>, String> {
+    if source.trim().is_empty() {
+        return Err("'' is not a valid selector".to_string());
+    }
+    parse(&format!("{} {{}}", source))
+        .ok()
+        .and_then(|stylesheet: Stylesheet| stylesheet.rules.into_iter().next())
+        .map(|rule: Rule| rule.selectors)
+        .ok_or_else(|| format!("'{}' is not a valid selector", source))
+}
+
+
+/*
+    Inline Styles
+
+    An element's `style` attribute holds declarations without a selector or braces:
+
+        <p style="color: #cc0000; margin-top: 10px">
+
+    Stylesheets come from the page's author, and a parse error points at a bug in the
+    CSS. Style attributes are everywhere (and scripts write them), so `parse_inline` is
+    forgiving like a real CSS parser: a declaration it doesn't understand is skipped, and
+    the rest still apply. The last `;` is optional.
+ */
+/// Parse the declarations of a `style` attribute, skipping any it can't parse.
+pub fn parse_inline(source: &str) -> Vec<Declaration> {
+    source.split(';').filter_map(parse_inline_declaration).collect()
+}
+
+/// Parse one `<property>: <value>` declaration, or return `None`.
+fn parse_inline_declaration(source: &str) -> Option<Declaration> {
+    let (name, value) = source.split_once(':')?;
+    let (name, value) = (name.trim(), value.trim());
+    if name.is_empty() || !name.chars().all(valid_identifier_char) {
+        return None;
+    }
+    Some(Declaration { name: Atom::from(name.to_ascii_lowercase()), value: parse_inline_value(value)? })
+}
+
+/// Parse a value the way `Parser::parse_value` does, but return `None` instead of an error.
+fn parse_inline_value(value: &str) -> Option<Value> {
+    if value.starts_with(['"', '\'']) || value.contains([',', '(', ' ']) {
+        let mut parser: Parser = Parser { input: value, position: 0 };
+        let value: Value = parser.parse_value().ok()?;
+        return parser.eof().then_some(value);
+    }
+    if let Some(hex) = value.strip_prefix('#') {
+        if !matches!(hex.len(), 6 | 8) || !hex.chars().all(|c: char| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        let a: u8 = if hex.len() == 8 { channel(6)? } else { 255 };
+        return Some(Value::ColorValue(Color { r: channel(0)?, g: channel(2)?, b: channel(4)?, a }));
+    }
+    if value.starts_with(|c: char| c.is_ascii_digit()) {
+        let parse = |number: &str| number.parse().ok().filter(|n: &f32| n.is_finite());
+        return match value.strip_suffix("px").or_else(|| value.strip_suffix("PX")) {
+            Some(number) => parse(number).map(|number: f32| Value::Length(number, Unit::Px)),
+            None => parse(value).map(Value::Number),
+        };
+    }
+    if !value.is_empty() && value.chars().all(valid_identifier_char) {
+        return Some(Value::Keyword(Atom::from(value)));
+    }
+    None
 }
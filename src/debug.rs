@@ -0,0 +1,454 @@
+//! Text dumps of each stage's output, and an HTML report of them all, for debugging why a
+//! page renders the way it does.
+
+use crate::{css, dom, layout, painting, snapshot, style};
+use std::collections::HashMap;
+use std::fmt::Write;
+
+
+/*
+    Debug Dumps
+
+    When a page renders wrong, the first question is which stage went wrong: did the parser
+    build the wrong tree, did the wrong rules match, did layout put a box in the wrong
+    place, or did painting draw the wrong thing? These functions print the output of each
+    stage, either as indented text for reading or as JSON for tools.
+
+    e.g. the layout of <div class="a"><div class="b"></div></div>
+        block <div class="a"> content 10,10 80x20 padding 10,10,10,10
+          block <div class="b"> content 10,10 80x20
+
+    The JSON output for the DOM is the snapshot format (see `snapshot`); the others are:
+        style node   = { "node": int, "label": string, "values": { string: string, ... },
+                         "children": [style node, ...] }
+        layout box   = { "box": "block" | "inline" | "anonymous", "node": int | null,
+                         "content": rect, "padding": edges, "border": edges, "margin": edges,
+                         "children": [layout box, ...] }
+        command      = { "type": "solid-color", "color": "#rrggbb", "rect": rect }
+                     | { "type": "image", "width": int, "height": int, "rect": rect }
+                     | { "type": "push-clip", "rect": rect } | { "type": "pop-clip" }
+        rect         = { "x": num, "y": num, "width": num, "height": num }
+        edges        = { "top": num, "right": num, "bottom": num, "left": num }
+
+    Layout boxes are printed one per line, indented by depth, so that a diff between two
+    saved dumps shows which boxes moved (see `tests/layout_snapshots.rs`).
+ */
+
+/// How a dump is printed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DumpFormat {
+    Pretty,
+    Json,
+}
+
+/// Dump the DOM subtree at `node`.
+pub fn dump_dom(tree: &dom::Tree, node: dom::NodeId, format: DumpFormat) -> String {
+    match format {
+        DumpFormat::Pretty => dom::dump(tree, node),
+        DumpFormat::Json => snapshot::node_to_json(tree, node),
+    }
+}
+
+/// Dump a style tree, with each node's specified values.
+pub fn dump_style(root: &style::StyledNode, format: DumpFormat) -> String {
+    let mut result: String = String::new();
+    match format {
+        DumpFormat::Pretty => pretty_style(root, 0, &mut result),
+        DumpFormat::Json => json_style(root, &mut result),
+    }
+    result
+}
+
+/// Dump a layout tree, with each box's dimensions.
+pub fn dump_layout(root: &layout::LayoutBox, format: DumpFormat) -> String {
+    let mut result: String = String::new();
+    match format {
+        DumpFormat::Pretty => pretty_layout(root, 0, &mut result),
+        DumpFormat::Json => json_layout(root, 0, &mut result),
+    }
+    result
+}
+
+/// Dump a display list, one command per line (or as a JSON array).
+pub fn dump_display_list(list: &painting::DisplayList, format: DumpFormat) -> String {
+    let mut result: String = String::new();
+    match format {
+        DumpFormat::Pretty => {
+            for command in list {
+                match command {
+                    painting::DisplayCommand::SolidColor(color, rect) => {
+                        writeln!(result, "solid-color {} {}", color, pretty_rect(rect)).unwrap();
+                    }
+                    painting::DisplayCommand::Image(image, rect) => {
+                        writeln!(result, "image {}x{} {}", image.width, image.height, pretty_rect(rect)).unwrap();
+                    }
+                    painting::DisplayCommand::Text(run, color, rect) => {
+                        writeln!(result, "text {:?} {} {}px {}", run.text, color, run.size, pretty_rect(rect)).unwrap();
+                    }
+                    painting::DisplayCommand::PushClip(rect) => {
+                        writeln!(result, "push-clip {}", pretty_rect(rect)).unwrap();
+                    }
+                    painting::DisplayCommand::PopClip => result.push_str("pop-clip\n"),
+                }
+            }
+        }
+        DumpFormat::Json => {
+            result.push('[');
+            for (i, command) in list.iter().enumerate() {
+                if i > 0 {
+                    result.push(',');
+                }
+                match command {
+                    painting::DisplayCommand::SolidColor(color, rect) => {
+                        write!(result, "{{\"type\":\"solid-color\",\"color\":\"{}\",\"rect\":{}}}", color, json_rect(rect)).unwrap();
+                    }
+                    painting::DisplayCommand::Image(image, rect) => {
+                        write!(result, "{{\"type\":\"image\",\"width\":{},\"height\":{},\"rect\":{}}}",
+                            image.width, image.height, json_rect(rect)).unwrap();
+                    }
+                    painting::DisplayCommand::Text(run, color, rect) => {
+                        write!(result, "{{\"type\":\"text\",\"text\":{},\"color\":\"{}\",\"size\":{},\"rect\":{}}}",
+                            json_string(&run.text), color, run.size, json_rect(rect)).unwrap();
+                    }
+                    painting::DisplayCommand::PushClip(rect) => {
+                        write!(result, "{{\"type\":\"push-clip\",\"rect\":{}}}", json_rect(rect)).unwrap();
+                    }
+                    painting::DisplayCommand::PopClip => result.push_str("{\"type\":\"pop-clip\"}"),
+                }
+            }
+            result.push(']');
+        }
+    }
+    result
+}
+
+
+// Style trees
+
+fn pretty_style(node: &style::StyledNode, depth: usize, result: &mut String) {
+    result.push_str(&"  ".repeat(depth));
+    result.push_str(&node.node.node_type.to_string());
+    let values: Vec<(String, String)> = sorted_values(node);
+    if !values.is_empty() {
+        result.push_str(" {");
+        for (name, value) in values {
+            write!(result, " {}: {};", name, value).unwrap();
+        }
+        result.push_str(" }");
+    }
+    result.push('\n');
+    for child in &node.children {
+        pretty_style(child, depth + 1, result);
+    }
+}
+
+fn json_style(node: &style::StyledNode, result: &mut String) {
+    write!(result, "{{\"node\":{},\"label\":{},\"values\":{{", node.node_id.index(), json_string(&node.node.node_type.to_string())).unwrap();
+    for (i, (name, value)) in sorted_values(node).into_iter().enumerate() {
+        if i > 0 {
+            result.push(',');
+        }
+        write!(result, "{}:{}", json_string(&name), json_string(&value)).unwrap();
+    }
+    result.push_str("},\"children\":[");
+    for (i, child) in node.children.iter().enumerate() {
+        if i > 0 {
+            result.push(',');
+        }
+        json_style(child, result);
+    }
+    result.push_str("]}");
+}
+
+/// The specified values of a node as (name, value) strings, sorted by name.
+fn sorted_values(node: &style::StyledNode) -> Vec<(String, String)> {
+    let mut values: Vec<(String, String)> = node.specified_values.iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    values.sort();
+    values
+}
+
+
+// Layout trees
+
+fn pretty_layout(layout_box: &layout::LayoutBox, depth: usize, result: &mut String) {
+    result.push_str(&"  ".repeat(depth));
+    match layout_box.box_type {
+        layout::BoxType::BlockNode(node) => write!(result, "block {}", node.node.node_type).unwrap(),
+        layout::BoxType::InlineNode(node) => write!(result, "inline {}", node.node.node_type).unwrap(),
+        layout::BoxType::AnonymousBlock => result.push_str("anonymous"),
+    }
+    writeln!(result, " {}", pretty_dimensions(&layout_box.dimensions)).unwrap();
+    for fragment in &layout_box.text {
+        writeln!(result, "{}  text {:?} {}", "  ".repeat(depth), fragment.run.text, pretty_rect(&fragment.rect)).unwrap();
+    }
+    for child in layout_box.children.iter() {
+        pretty_layout(child, depth + 1, result);
+    }
+}
+
+fn json_layout(layout_box: &layout::LayoutBox, depth: usize, result: &mut String) {
+    let (kind, node) = match layout_box.box_type {
+        layout::BoxType::BlockNode(node) => ("block", node.node_id.index().to_string()),
+        layout::BoxType::InlineNode(node) => ("inline", node.node_id.index().to_string()),
+        layout::BoxType::AnonymousBlock => ("anonymous", "null".to_string()),
+    };
+    let d: &layout::Dimensions = &layout_box.dimensions;
+    let indent: String = "  ".repeat(depth);
+    write!(result, "{}{{\"box\":\"{}\",\"node\":{},\"content\":{},\"padding\":{},\"border\":{},\"margin\":{},",
+        indent, kind, node, json_rect(&d.content), json_edges(&d.padding), json_edges(&d.border), json_edges(&d.margin)).unwrap();
+    // Text boxes list their text, line by line.
+    if !layout_box.text.is_empty() {
+        result.push_str("\"text\":[");
+        for (i, fragment) in layout_box.text.iter().enumerate() {
+            if i > 0 {
+                result.push(',');
+            }
+            write!(result, "{{\"text\":{},\"rect\":{}}}", json_string(&fragment.run.text), json_rect(&fragment.rect)).unwrap();
+        }
+        result.push_str("],");
+    }
+    result.push_str("\"children\":[");
+    if layout_box.children.is_empty() {
+        result.push_str("]}");
+        return;
+    }
+    for (i, child) in layout_box.children.iter().enumerate() {
+        result.push_str(if i > 0 { ",\n" } else { "\n" });
+        json_layout(child, depth + 1, result);
+    }
+    write!(result, "\n{}]}}", indent).unwrap();
+}
+
+
+/*
+    HTML Reports
+
+    The dumps show one stage at a time. `html_report` puts them all on one page, a poor
+    man's inspector that needs nothing but a browser to open: the screenshot is inlined
+    as a data URL, and there is no script.
+
+    On the left is the whole document, painted, with every box outlined. On the right is
+    the DOM tree, and under each element:
+        - the rules that matched it, in cascade order (later rules win), with the
+          specificity each matched with, then its `style` attribute
+        - its specified values
+        - its boxes' content, padding, border, and margin
+    Clicking a box selects its node in the tree, and clicking a node selects its box
+    (with `:target`, so the selection is in the URL, and survives a reload).
+
+    The document is painted at 1 CSS px per pixel, without scrolling, so the picture and
+    the rects agree. It is cut off at `MAX_REPORT_HEIGHT`, to keep reports on very long
+    pages from taking hundreds of megabytes.
+ */
+
+/// The tallest picture of the document a report includes, in CSS px.
+const MAX_REPORT_HEIGHT: f32 = 8192.0;
+
+const REPORT_STYLE: &str = "\
+body { display: flex; margin: 0; font: 13px sans-serif; }
+.page { position: sticky; top: 0; align-self: flex-start; flex: none; margin: 8px; border: 1px solid #ccc; }
+.page img { display: block; }
+.box { position: absolute; outline: 1px solid rgba(0, 0, 255, 0.2); }
+.box:hover { outline-color: #00f; background: rgba(0, 0, 255, 0.1); }
+.box:target { outline: 2px solid #f00; background: rgba(255, 0, 0, 0.15); }
+.dom { flex: 1; padding: 8px; }
+ul { list-style: none; margin: 0; padding-left: 16px; }
+summary a, .text { font-family: monospace; color: #881280; text-decoration: none; }
+.text { color: #333; }
+li:target > details > summary { background: #fff3b0; }
+table, .values, .boxes { margin: 2px 0 6px 16px; font: 12px monospace; color: #555; border-collapse: collapse; }
+td { padding: 0 8px 0 0; vertical-align: top; }
+";
+
+/// Write a self-contained HTML page showing the document painted with its boxes, and its
+/// DOM with each node's matched rules, specified values, and boxes.
+pub fn html_report(document: &dom::Document, stylesheet: &css::Stylesheet, states: &style::ElementStates, style_root: &style::StyledNode, layout_root: &layout::LayoutBox) -> String {
+    let mut styles: HashMap<dom::NodeId, &style::StyledNode> = HashMap::new();
+    collect_styles(style_root, &mut styles);
+    let mut boxes: HashMap<dom::NodeId, Vec<&layout::LayoutBox>> = HashMap::new();
+    let mut overlays: String = String::new();
+    collect_boxes(layout_root, &mut boxes, &mut overlays);
+
+    let margin_box: layout::Rect = layout_root.dimensions.margin_box();
+    let width: f32 = (margin_box.x + margin_box.width).ceil().max(1.0);
+    let height: f32 = (margin_box.y + margin_box.height).ceil().clamp(1.0, MAX_REPORT_HEIGHT);
+    let canvas: painting::Canvas = painting::rasterize(
+        &painting::build_display_list(layout_root),
+        layout::Rect { x: 0.0, y: 0.0, width, height },
+    );
+
+    let mut result: String = String::new();
+    write!(result, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Rendering report</title>\n<style>\n{}</style>\n</head>\n<body>\n", REPORT_STYLE).unwrap();
+    write!(result, "<div class=\"page\" style=\"width: {}px; height: {}px\">\n<img src=\"data:image/png;base64,{}\" width=\"{}\" height=\"{}\" alt=\"\">\n{}</div>\n",
+        width, height, base64(&canvas.to_png()), width, height, overlays).unwrap();
+    result.push_str("<div class=\"dom\">\n<ul>\n");
+    report_node(document, document.root_element, stylesheet, states, &styles, &boxes, &mut result);
+    result.push_str("</ul>\n</div>\n</body>\n</html>\n");
+    result
+}
+
+fn collect_styles<'a, 'b>(node: &'b style::StyledNode<'a>, styles: &mut HashMap<dom::NodeId, &'b style::StyledNode<'a>>) {
+    styles.insert(node.node_id, node);
+    for child in &node.children {
+        collect_styles(child, styles);
+    }
+}
+
+/// Collect each node's boxes, and write an outline over the picture for each box.
+fn collect_boxes<'a, 'b>(layout_box: &'b layout::LayoutBox<'a>, boxes: &mut HashMap<dom::NodeId, Vec<&'b layout::LayoutBox<'a>>>, overlays: &mut String) {
+    let rect: layout::Rect = layout_box.dimensions.border_box();
+    let position: String = format!("left: {}px; top: {}px; width: {}px; height: {}px", rect.x, rect.y, rect.width, rect.height);
+    match layout_box.box_type {
+        layout::BoxType::BlockNode(node) | layout::BoxType::InlineNode(node) => {
+            let id: usize = node.node_id.index();
+            let node_boxes: &mut Vec<&layout::LayoutBox> = boxes.entry(node.node_id).or_default();
+            // Only a node's first box can be the target of a link to it.
+            let anchor: String = if node_boxes.is_empty() { format!(" id=\"box-{}\"", id) } else { String::new() };
+            node_boxes.push(layout_box);
+            writeln!(overlays, "<a class=\"box\"{} href=\"#node-{}\" style=\"{}\" title=\"{}\"></a>",
+                anchor, id, position, escape_html(&node.node.node_type.to_string())).unwrap();
+        }
+        layout::BoxType::AnonymousBlock => {
+            writeln!(overlays, "<div class=\"box\" style=\"{}\" title=\"anonymous\"></div>", position).unwrap();
+        }
+    }
+    for child in layout_box.children.iter() {
+        collect_boxes(child, boxes, overlays);
+    }
+}
+
+fn report_node(
+    document: &dom::Document,
+    node: dom::NodeId,
+    stylesheet: &css::Stylesheet,
+    states: &style::ElementStates,
+    styles: &HashMap<dom::NodeId, &style::StyledNode>,
+    boxes: &HashMap<dom::NodeId, Vec<&layout::LayoutBox>>,
+    result: &mut String,
+) {
+    let id: usize = node.index();
+    let element: &dom::Element = match document.tree[node].node_type {
+        dom::NodeType::Element(ref element) => element,
+        ref other => {
+            writeln!(result, "<li id=\"node-{}\" class=\"text\">{}</li>", id, escape_html(&other.to_string())).unwrap();
+            return;
+        }
+    };
+    let node_boxes: &[&layout::LayoutBox] = boxes.get(&node).map(Vec::as_slice).unwrap_or_default();
+    let label: String = escape_html(&element.to_string());
+    if node_boxes.is_empty() {
+        writeln!(result, "<li id=\"node-{}\"><details open>\n<summary><a href=\"#node-{}\">{}</a> (no box)</summary>", id, id, label).unwrap();
+    } else {
+        writeln!(result, "<li id=\"node-{}\"><details open>\n<summary><a href=\"#box-{}\">{}</a></summary>", id, id, label).unwrap();
+    }
+
+    let stylesheet: &css::Stylesheet = states.stylesheet_for(&document.tree, node, stylesheet);
+    let rules: Vec<(css::Specificity, &css::Rule)> = style::matched_rules(element, node, stylesheet, states);
+    if !rules.is_empty() || element.attr("style").is_some() {
+        result.push_str("<table>\n");
+        for ((ids, classes, tags), rule) in rules {
+            writeln!(result, "<tr><td>({}, {}, {})</td><td>{}</td></tr>", ids, classes, tags, escape_html(&rule.to_string())).unwrap();
+        }
+        if let Some(style) = element.attr("style") {
+            writeln!(result, "<tr><td>style</td><td>{}</td></tr>", escape_html(style)).unwrap();
+        }
+        result.push_str("</table>\n");
+    }
+    if let Some(styled) = styles.get(&node) {
+        let values: Vec<(String, String)> = sorted_values(styled);
+        if !values.is_empty() {
+            let values: Vec<String> = values.iter().map(|(name, value): &(String, String)| format!("{}: {};", name, value)).collect();
+            writeln!(result, "<div class=\"values\">{}</div>", escape_html(&values.join(" "))).unwrap();
+        }
+    }
+    for layout_box in node_boxes {
+        let kind: &str = if matches!(layout_box.box_type, layout::BoxType::InlineNode(_)) { "inline" } else { "block" };
+        writeln!(result, "<div class=\"boxes\">{} {}</div>", kind, pretty_dimensions(&layout_box.dimensions)).unwrap();
+    }
+
+    let mut children = document.tree.children(node).peekable();
+    if children.peek().is_some() {
+        result.push_str("<ul>\n");
+        for child in children {
+            report_node(document, child, stylesheet, states, styles, boxes, result);
+        }
+        result.push_str("</ul>\n");
+    }
+    result.push_str("</details></li>\n");
+}
+
+
+// Helpers
+
+/// A box's content rect, and its padding, border, and margin where they aren't zero.
+fn pretty_dimensions(d: &layout::Dimensions) -> String {
+    let mut result: String = format!("content {}", pretty_rect(&d.content));
+    for (name, edges) in [("padding", &d.padding), ("border", &d.border), ("margin", &d.margin)] {
+        if edges.top != 0.0 || edges.right != 0.0 || edges.bottom != 0.0 || edges.left != 0.0 {
+            write!(result, " {} {},{},{},{}", name, edges.top, edges.right, edges.bottom, edges.left).unwrap();
+        }
+    }
+    result
+}
+
+fn pretty_rect(rect: &layout::Rect) -> String {
+    format!("{},{} {}x{}", rect.x, rect.y, rect.width, rect.height)
+}
+
+fn json_rect(rect: &layout::Rect) -> String {
+    format!("{{\"x\":{},\"y\":{},\"width\":{},\"height\":{}}}", rect.x, rect.y, rect.width, rect.height)
+}
+
+fn json_edges(edges: &layout::EdgeSizes) -> String {
+    format!("{{\"top\":{},\"right\":{},\"bottom\":{},\"left\":{}}}", edges.top, edges.right, edges.bottom, edges.left)
+}
+
+/// Escape text for HTML, in content or in a quoted attribute value.
+fn escape_html(s: &str) -> String {
+    let mut result: String = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// Encode bytes as base64, for a data URL.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut result: String = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n: u32 = chunk.iter().enumerate().fold(0, |n: u32, (i, &byte): (usize, &u8)| n | (byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                result.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                result.push('=');
+            }
+        }
+    }
+    result
+}
+
+/// Quote and escape a string for JSON.
+pub(crate) fn json_string(s: &str) -> String {
+    let mut result: String = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            c if (c as u32) < 0x20 => write!(result, "\\u{:04x}", c as u32).unwrap(),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+    result
+}
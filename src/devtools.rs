@@ -0,0 +1,405 @@
+//! A local JSON endpoint for inspecting and editing a page from another program.
+
+use crate::debug::{self, DumpFormat};
+use crate::engine::Page;
+use crate::snapshot::{self, Json};
+use crate::{css, dom, style};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::Duration;
+
+
+/*
+    Developer Tools
+
+    An inspector (the panel that shows the DOM tree, the styles of the selected element,
+    and its box model) is a program of its own, usually written with a UI toolkit or as a
+    web page. Rather than build one into the engine, we let one talk to the engine: a
+    `DevtoolsServer` listens on a local port and answers requests for the page's DOM,
+    computed styles, and layout boxes, and applies edits sent back to it.
+
+    The protocol is plain HTTP with JSON bodies, so anything from `curl` to `fetch` in a
+    web page can use it without a special client. Responses allow any origin, so an
+    inspector page opened from a file can call it directly.
+
+        GET  /                     the list of endpoints
+        GET  /dom                  the document, as nodes with ids (see below)
+        GET  /node/<id>            the subtree at node <id>
+        GET  /node/<id>/style      the specified values of node <id>
+        GET  /style                the style tree (the `dump-style --json` format)
+        GET  /layout               the layout tree (the `dump-layout --json` format)
+        GET  /display-list         the display list (the `dump-display-list --json` format)
+        GET  /stylesheet           { "css": string }
+        POST /mutate               apply one command, or an array of them
+
+    DOM nodes use the snapshot format (see `snapshot`) plus each node's id, which is how
+    the other endpoints and the commands refer to it:
+        node = { "id": int, "type": "element", "tag": string, "attrs": { ... }, "children": [...] }
+             | { "id": int, "type": "text", "text": string }
+
+    Commands:
+        { "op": "set-attribute", "node": int, "name": string, "value": string }
+        { "op": "remove-attribute", "node": int, "name": string }
+        { "op": "set-text", "node": int, "text": string }  (replaces an element's children)
+        { "op": "remove", "node": int }
+        { "op": "set-stylesheet", "css": string }
+        { "op": "hover", "node": int | null }
+
+    Errors are reported as { "error": string } with a 4xx status. A list of commands stops
+    at the first one that fails; the ones before it stay applied.
+
+    The server is single-threaded and owns nothing: each call to `poll` borrows the page,
+    answers whatever requests are waiting, and returns, so it can share a thread (and the
+    page) with an event loop. `serve` is the loop for when there is nothing else to do.
+
+    e.g.
+        let server: DevtoolsServer = DevtoolsServer::bind("127.0.0.1:9222")?;
+        loop {
+            server.poll(&mut page)?;
+            // ...handle window events, repaint if needed...
+        }
+ */
+
+/// How long `serve` sleeps when no requests are waiting.
+const SERVE_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The most a request (headers and body) may be, in bytes.
+const MAX_REQUEST_SIZE: usize = 1 << 20;
+
+/// A listening devtools endpoint.
+pub struct DevtoolsServer {
+    listener: TcpListener,
+}
+
+impl DevtoolsServer {
+    /// Listen on `address`, e.g. "127.0.0.1:9222" (or port 0 for any free port).
+    pub fn bind(address: impl ToSocketAddrs) -> io::Result<DevtoolsServer> {
+        let listener: TcpListener = TcpListener::bind(address)?;
+        listener.set_nonblocking(true)?;
+        Ok(DevtoolsServer { listener })
+    }
+
+    /// The address the server is listening on.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Answer every request that is waiting, without blocking for new ones. Returns the
+    /// number of requests answered.
+    pub fn poll(&self, page: &mut Page) -> io::Result<usize> {
+        let mut handled: usize = 0;
+        loop {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    // A client that hangs up or sends garbage shouldn't stop the server.
+                    if let Err(err) = answer(stream, page) {
+                        eprintln!("devtools: {}", err);
+                    }
+                    handled += 1;
+                }
+                Err(ref err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(handled),
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Answer requests forever, loading the page's images as they arrive.
+    pub fn serve(&self, page: &mut Page) -> io::Result<()> {
+        loop {
+            page.poll_images();
+            if self.poll(page)? == 0 {
+                thread::sleep(SERVE_INTERVAL);
+            }
+        }
+    }
+}
+
+
+/*
+    HTTP
+
+    Each connection carries one request, and is closed after the response. We only need
+    the method, the path, and a body of `Content-Length` bytes.
+ */
+
+/// Read one request from `stream` and write the response.
+fn answer(stream: TcpStream, page: &mut Page) -> io::Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader: BufReader<&TcpStream> = BufReader::new(&stream);
+
+    let mut request_line: String = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return write_response(&stream, 400, &error_json("Malformed request line")),
+    };
+
+    let mut content_length: usize = 0;
+    loop {
+        let mut line: String = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    if content_length > MAX_REQUEST_SIZE {
+        return write_response(&stream, 413, &error_json("Request body too large"));
+    }
+    let mut body: Vec<u8> = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+
+    let (status, json) = handle(page, &method, &path, &String::from_utf8_lossy(&body));
+    write_response(&stream, status, &json)
+}
+
+fn write_response(mut stream: &TcpStream, status: u16, body: &str) -> io::Result<()> {
+    let reason: &str = match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        413 => "Payload Too Large",
+        _ => "",
+    };
+    write!(stream, "HTTP/1.1 {} {}\r\n\
+                    Content-Type: application/json\r\n\
+                    Content-Length: {}\r\n\
+                    Access-Control-Allow-Origin: *\r\n\
+                    Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+                    Access-Control-Allow-Headers: Content-Type\r\n\
+                    Connection: close\r\n\r\n{}", status, reason, body.len(), body)?;
+    stream.flush()
+}
+
+
+/*
+    Requests
+
+    `handle` is the whole protocol, separate from the socket, so it can also be served over
+    some other transport (or called directly from tests).
+ */
+
+/// Answer one request, returning the status code and the JSON body.
+pub fn handle(page: &mut Page, method: &str, path: &str, body: &str) -> (u16, String) {
+    let path: &str = path.split('?').next().unwrap_or(path);
+    let segments: Vec<&str> = path.split('/').filter(|s: &&str| !s.is_empty()).collect();
+    let result: Result<String, (u16, String)> = match (method, segments.as_slice()) {
+        ("OPTIONS", _) => return (204, String::new()), // CORS preflight
+        ("GET", []) => Ok(ENDPOINTS.to_string()),
+        ("GET", ["dom"]) => {
+            let document: &dom::Document = page.document();
+            Ok(dom_json(&document.tree, document.root_element))
+        }
+        ("GET", ["node", id]) => node_id(page, &Json::String(id.to_string()))
+            .map(|node: dom::NodeId| dom_json(&page.document().tree, node)),
+        ("GET", ["node", id, "style"]) => node_id(page, &Json::String(id.to_string()))
+            .and_then(|node: dom::NodeId| node_style(page, node)),
+        ("GET", ["style"]) => Ok(page.with_style_tree(|root: &style::StyledNode| debug::dump_style(root, DumpFormat::Json))),
+        ("GET", ["layout"]) => Ok(page.with_layout_tree(|root| debug::dump_layout(root, DumpFormat::Json))),
+        ("GET", ["display-list"]) => Ok(debug::dump_display_list(page.layout(), DumpFormat::Json)),
+        ("GET", ["stylesheet"]) => {
+            let mut result: String = String::from("{\"css\":");
+            snapshot::write_string(&page.stylesheet().to_string(), &mut result);
+            result.push('}');
+            Ok(result)
+        }
+        ("POST", ["mutate"]) => mutate(page, body).map(|count: usize| format!("{{\"applied\":{}}}", count)),
+        (_, ["dom"] | ["node", ..] | ["style"] | ["layout"] | ["display-list"] | ["stylesheet"] | ["mutate"] | []) => {
+            Err((405, format!("{} is not allowed on {}", method, path)))
+        }
+        _ => Err((404, format!("No endpoint {}", path))),
+    };
+    match result {
+        Ok(json) => (200, json),
+        Err((status, message)) => (status, error_json(&message)),
+    }
+}
+
+const ENDPOINTS: &str = "{\"endpoints\":[\"GET /dom\",\"GET /node/<id>\",\"GET /node/<id>/style\",\"GET /style\",\
+    \"GET /layout\",\"GET /display-list\",\"GET /stylesheet\",\"POST /mutate\"]}";
+
+fn error_json(message: &str) -> String {
+    let mut result: String = String::from("{\"error\":");
+    snapshot::write_string(message, &mut result);
+    result.push('}');
+    result
+}
+
+/// The subtree at `node`, in the snapshot format with ids added.
+fn dom_json(tree: &dom::Tree, node: dom::NodeId) -> String {
+    let mut result: String = String::new();
+    write_dom_node(tree, node, &mut result);
+    result
+}
+
+fn write_dom_node(tree: &dom::Tree, node: dom::NodeId, result: &mut String) {
+    write!(result, "{{\"id\":{},", node.index()).unwrap();
+    match tree[node].node_type {
+        dom::NodeType::Text(ref text) => {
+            result.push_str("\"type\":\"text\",\"text\":");
+            snapshot::write_string(text, result);
+            result.push('}');
+            return;
+        }
+        dom::NodeType::Element(ref element) => {
+            result.push_str("\"type\":\"element\",\"tag\":");
+            snapshot::write_string(&element.tag_name, result);
+            result.push_str(",\"attrs\":{");
+            for (i, (name, value)) in element.attributes.iter().enumerate() {
+                if i > 0 {
+                    result.push(',');
+                }
+                snapshot::write_string(name, result);
+                result.push(':');
+                snapshot::write_string(value, result);
+            }
+            result.push('}');
+        }
+        dom::NodeType::DocumentFragment => result.push_str("\"type\":\"fragment\""),
+    }
+    result.push_str(",\"children\":[");
+    for (i, child) in tree.children(node).enumerate() {
+        if i > 0 {
+            result.push(',');
+        }
+        write_dom_node(tree, child, result);
+    }
+    result.push_str("]}");
+}
+
+/// The specified values of one node, as `{ "node": int, "values": { name: value, ... } }`.
+fn node_style(page: &Page, node: dom::NodeId) -> Result<String, (u16, String)> {
+    page.with_style_tree(|root: &style::StyledNode| {
+        let styled: &style::StyledNode = root.find(node)
+            .ok_or_else(|| (404, format!("Node {} is not rendered", node.index())))?;
+        let values: BTreeMap<String, String> = styled.specified_values.iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        let mut result: String = format!("{{\"node\":{},\"values\":{{", node.index());
+        for (i, (name, value)) in values.iter().enumerate() {
+            if i > 0 {
+                result.push(',');
+            }
+            snapshot::write_string(name, &mut result);
+            result.push(':');
+            snapshot::write_string(value, &mut result);
+        }
+        result.push_str("}}");
+        Ok(result)
+    })
+}
+
+
+/*
+    Mutations
+
+    Commands go through the `Document` methods, so its id and class indexes stay up to
+    date, and through `Page::document_mut`, so the page is laid out again afterwards.
+ */
+
+/// Apply the command (or array of commands) in `body`, returning how many were applied.
+fn mutate(page: &mut Page, body: &str) -> Result<usize, (u16, String)> {
+    let value: Json = snapshot::parse_json(body).map_err(|err: snapshot::SnapshotError| (400, err.to_string()))?;
+    let commands: Vec<&Json> = match value {
+        Json::Array(ref commands) => commands.iter().collect(),
+        ref command => vec![command],
+    };
+    for (i, command) in commands.iter().enumerate() {
+        apply(page, command).map_err(|message: String| (400, format!("Command {}: {}", i, message)))?;
+    }
+    Ok(commands.len())
+}
+
+fn apply(page: &mut Page, command: &Json) -> Result<(), String> {
+    let members: &BTreeMap<String, Json> = match command {
+        Json::Object(members) => members,
+        _ => return Err("Expected a command object".to_string()),
+    };
+    let string = |key: &str| -> Result<&str, String> {
+        match members.get(key) {
+            Some(Json::String(s)) => Ok(s),
+            _ => Err(format!("Expected a string for {:?}", key)),
+        }
+    };
+    let node = |page: &Page| -> Result<dom::NodeId, String> {
+        let id: &Json = members.get("node").ok_or("Missing \"node\"")?;
+        node_id(page, id).map_err(|(_, message)| message)
+    };
+
+    match string("op")? {
+        "set-attribute" => {
+            let node: dom::NodeId = element(page, node(page)?)?;
+            page.document_mut().set_attribute(node, string("name")?, string("value")?);
+        }
+        "remove-attribute" => {
+            let node: dom::NodeId = element(page, node(page)?)?;
+            page.document_mut().remove_attribute(node, string("name")?);
+        }
+        "set-text" => {
+            let node: dom::NodeId = node(page)?;
+            let text: String = string("text")?.to_string();
+            let document: &mut dom::Document = page.document_mut();
+            match document.tree[node].node_type {
+                dom::NodeType::Text(ref mut data) => *data = text,
+                dom::NodeType::Element(_) => {
+                    let children: Vec<dom::NodeId> = document.tree.children(node).collect();
+                    for child in children {
+                        document.remove_child(node, child);
+                    }
+                    let text: dom::NodeId = document.tree.text(text);
+                    document.append_child(node, text);
+                }
+                dom::NodeType::DocumentFragment => return Err("Cannot set the text of a fragment".to_string()),
+            }
+        }
+        "remove" => {
+            let node: dom::NodeId = node(page)?;
+            let parent: dom::NodeId = page.document().tree[node].parent
+                .ok_or_else(|| format!("Node {} has no parent", node.index()))?;
+            page.document_mut().remove_child(parent, node);
+        }
+        "set-stylesheet" => {
+            let stylesheet: css::Stylesheet = css::parse(string("css")?)
+                .map_err(|err: css::ParseError| format!("Invalid stylesheet: {}", err))?;
+            page.set_stylesheet(stylesheet);
+        }
+        "hover" => {
+            let node: Option<dom::NodeId> = match members.get("node") {
+                None | Some(Json::Literal) => None,
+                Some(_) => Some(element(page, node(page)?)?),
+            };
+            page.set_hovered_node(node);
+        }
+        other => return Err(format!("Unknown op {:?}", other)),
+    }
+    Ok(())
+}
+
+/// Turn a node id from a request (a number, or a string of digits) into a `NodeId`.
+fn node_id(page: &Page, id: &Json) -> Result<dom::NodeId, (u16, String)> {
+    let index: Option<usize> = match id {
+        Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+        Json::String(s) => s.parse().ok(),
+        _ => None,
+    };
+    let index: usize = index.ok_or_else(|| (400, "Expected a node id".to_string()))?;
+    page.document().tree.node_id(index).ok_or_else(|| (404, format!("No node {}", index)))
+}
+
+/// Check that `node` is an element.
+fn element(page: &Page, node: dom::NodeId) -> Result<dom::NodeId, String> {
+    match page.document().tree[node].node_type {
+        dom::NodeType::Element(_) => Ok(node),
+        _ => Err(format!("Node {} is not an element", node.index())),
+    }
+}
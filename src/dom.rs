@@ -1,16 +1,111 @@
 //! Basic DOM data structures.
 
-use std::collections::HashMap;
+use crate::atom::Atom;
+use crate::collections::{HashMap, HashSet};
+use crate::html;
+#[cfg(feature = "std")]
+use crate::memory::HeapSize;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::{Index, IndexMut};
+use core::str::FromStr;
 
 
 /*
     The DOM
 
     The DOM is a tree of nodes. A node has zero or more children.
+
+    Selector combinators, `:nth-child`, and walks that go back up the tree all need to get
+    from a node to its parent and siblings, not just to its children. In Rust, a tree of
+    owned `Vec<Node>` children can't point back up at its parent, so the nodes live in an
+    "arena" instead: one `Vec` owned by a `Tree`, where nodes refer to each other by index.
+
+    e.g.
+        <p>Hello <em>world</em></p>
+
+        nodes[0] = p       { parent: None,    first_child: 1, last_child: 2 }
+        nodes[1] = "Hello" { parent: Some(0), next_sibling: 2 }
+        nodes[2] = em      { parent: Some(0), prev_sibling: 1, first_child: 3, last_child: 3 }
+        nodes[3] = "world" { parent: Some(2) }
  */
+
+/// The index of a node in its `Tree`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeId(usize);
+
+impl NodeId {
+    /// The position of this node in the tree's arena.
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+
+/// A node, plus links to its neighbours in the tree.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub node_type: NodeType, // data specific to each node type
-    pub children: Vec<Node>, // data common to all nodes
+    pub parent: Option<NodeId>, // links common to all nodes
+    pub first_child: Option<NodeId>,
+    pub last_child: Option<NodeId>,
+    pub prev_sibling: Option<NodeId>,
+    pub next_sibling: Option<NodeId>,
+    pub source: Option<SourceSpan>, // where in the HTML source it was parsed from, if that was tracked
+}
+
+
+/*
+    Source Positions
+
+    A parser asked to track them (see `html::Parser::with_source_positions`) records where
+    in the source each element and text node came from, so a tool can point back at the
+    markup: an error message can quote the line, an inspector can show the source of the
+    node it's looking at, and a layout bug can be traced to the tag that caused it.
+
+    A span runs from the first byte of a node to just past its last: for an element, from
+    the `<` of its opening tag to the `>` of its closing tag, or of its opening tag if it
+    has no closing tag. Each end is a byte offset into the source, and a line and column
+    (both counting from 1, the column in characters), since that's what a person looking
+    at the file goes by.
+
+    e.g.
+        <p>Hello
+        <em>world</em></p>
+
+        p         0..27, 1:1 to 2:19
+        "Hello\n" 3..9,  1:4 to 2:1
+        em        9..23, 2:1 to 2:15
+
+    Nodes the parser didn't make (the implicit `<html>`, `<head>`, and `<body>`, copies,
+    and nodes made by scripts) have no span.
+ */
+/// A place in the source a document was parsed from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourcePosition {
+    pub offset: usize, // in bytes from the start of the source
+    pub line: usize,   // from 1
+    pub column: usize, // from 1, in characters
+}
+
+/// The part of the source a node was parsed from, from `start` up to (not including)
+/// `end`. See "Source Positions", above.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceSpan {
+    pub start: SourcePosition,
+    pub end: SourcePosition,
+}
+
+impl fmt::Display for SourcePosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
 
@@ -20,22 +115,30 @@ pub struct Node {
     In a language with inheritance these would be subtypes of Node.
     In Rust, they can be an enum (Rust's keyword for a "tagged union" or "sum type").
 
+    The one other kind we need is the [DocumentFragment](https://dom.spec.whatwg.org/#interface-documentfragment):
+    a parentless container for a batch of nodes. Inserting a fragment into the tree inserts
+    its children instead, leaving the fragment empty.
+
     e.g.
         NodeType {
             Element(ElementData),
             Text("Hello, World!"),
+            DocumentFragment,
         }
  */
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NodeType {
     Element(Element),
     Text(String),
+    DocumentFragment,
 }
 
 
 /*
     An element includes a tag name and any number of attributes, which can be stored as a map from
     names to values. This engine doesn't support namespaces, so it just stores tag and attribute names
-    as simple strings.
+    as simple strings. Tag and attribute names repeat a lot, so they are interned (see `atom`).
 
     e.g.
         Element {
@@ -43,34 +146,1505 @@ pub enum NodeType {
             attributes: AttributeMap,
         }
  */
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Element {
-    pub tag_name: String,
+    pub tag_name: Atom,
     pub attributes: AttributeMap,
 }
 
 /*
+    Attributes
+
+    An element's attributes are kept in the order they were written, so that anything
+    that walks them (a serializer, a debug dump, a test comparing output) sees the same
+    order every time, and the one the author wrote. Most elements have a few attributes,
+    so `AttributeMap` is a list of name-value pairs, looked up by a linear search, which
+    for a handful of entries is as quick as hashing the name.
+
+    A name appears at most once. When the source repeats one, as in `<p id=a id=b>`, the
+    first one counts and the rest are dropped, as the HTML spec says. `insert` replaces
+    the value of an attribute that's already there, in place, as `setAttribute` does, and
+    `remove` keeps the order of the rest.
+
     e.g.
         { "id": "...", "class": "...", "style": "..." }
  */
-type AttributeMap = HashMap<String, String>;
+/// An element's attributes, in document order. See "Attributes", above.
+#[derive(Clone, Default)]
+pub struct AttributeMap {
+    entries: Vec<(Atom, String)>,
+}
+
+impl AttributeMap {
+    pub fn new() -> AttributeMap {
+        AttributeMap::default()
+    }
+
+    /// The number of attributes.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Return true if there are no attributes.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The value of attribute `name`, if it is present.
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.entries.iter().find(|(key, _): &&(Atom, String)| *key == name).map(|(_, value): &(Atom, String)| value)
+    }
+
+    /// The value of attribute `name`, to change in place, if it is present.
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut String> {
+        self.entries.iter_mut().find(|(key, _): &&mut (Atom, String)| *key == name).map(|(_, value): &mut (Atom, String)| value)
+    }
+
+    /// Return true if attribute `name` is present.
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    /// Set attribute `name` to `value`, returning its old value. A new attribute goes at
+    /// the end; one that's already there keeps its place.
+    pub fn insert(&mut self, name: Atom, value: String) -> Option<String> {
+        match self.get_mut(&name) {
+            Some(old) => Some(core::mem::replace(old, value)),
+            None => {
+                self.entries.push((name, value));
+                None
+            }
+        }
+    }
+
+    /// Remove attribute `name`, returning its value if it was present.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let index: usize = self.entries.iter().position(|(key, _): &(Atom, String)| *key == name)?;
+        Some(self.entries.remove(index).1)
+    }
+
+    /// The attributes' names and values, in order.
+    pub fn iter(&self) -> Attributes<'_> {
+        Attributes(self.entries.iter())
+    }
+
+    /// The attributes' names, in order.
+    pub fn keys(&self) -> impl Iterator<Item = &Atom> {
+        self.entries.iter().map(|(name, _): &(Atom, String)| name)
+    }
+
+    /// The attributes' values, in order.
+    pub fn values(&self) -> impl Iterator<Item = &String> {
+        self.entries.iter().map(|(_, value): &(Atom, String)| value)
+    }
+}
+
+/// The names and values in an `AttributeMap`, in order.
+pub struct Attributes<'a>(core::slice::Iter<'a, (Atom, String)>);
+
+impl<'a> Iterator for Attributes<'a> {
+    type Item = (&'a Atom, &'a String);
+
+    fn next(&mut self) -> Option<(&'a Atom, &'a String)> {
+        self.0.next().map(|(name, value): &(Atom, String)| (name, value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.0.size_hint()
+    }
+}
+
+impl<'a> IntoIterator for &'a AttributeMap {
+    type Item = (&'a Atom, &'a String);
+    type IntoIter = Attributes<'a>;
+
+    fn into_iter(self) -> Attributes<'a> {
+        self.iter()
+    }
+}
+
+impl IntoIterator for AttributeMap {
+    type Item = (Atom, String);
+    type IntoIter = alloc::vec::IntoIter<(Atom, String)>;
+
+    fn into_iter(self) -> alloc::vec::IntoIter<(Atom, String)> {
+        self.entries.into_iter()
+    }
+}
+
+/// Collecting keeps the first value of a name that comes more than once.
+impl FromIterator<(Atom, String)> for AttributeMap {
+    fn from_iter<I: IntoIterator<Item = (Atom, String)>>(iter: I) -> AttributeMap {
+        let mut attributes: AttributeMap = AttributeMap::new();
+        for (name, value) in iter {
+            if !attributes.contains_key(&name) {
+                attributes.entries.push((name, value));
+            }
+        }
+        attributes
+    }
+}
+
+/// Two elements' attributes are equal if they have the same names and values, in any
+/// order, as for the DOM's `isEqualNode`.
+impl PartialEq for AttributeMap {
+    fn eq(&self, other: &AttributeMap) -> bool {
+        self.len() == other.len() && self.iter().all(|(name, value): (&Atom, &String)| other.get(name) == Some(value))
+    }
+}
+
+impl fmt::Debug for AttributeMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+#[cfg(feature = "std")]
+impl HeapSize for AttributeMap {
+    fn heap_size(&self) -> usize {
+        self.entries.capacity() * size_of::<(Atom, String)>() + self.values().map(String::heap_size).sum::<usize>()
+    }
+}
+
+/// Attributes are serialized as a map, in order.
+#[cfg(feature = "serde")]
+impl serde::Serialize for AttributeMap {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for AttributeMap {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<AttributeMap, D::Error> {
+        struct Visitor;
+
+        impl<'de> serde::de::Visitor<'de> for Visitor {
+            type Value = AttributeMap;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "a map of attribute names to values")
+            }
+
+            fn visit_map<M: serde::de::MapAccess<'de>>(self, mut map: M) -> Result<AttributeMap, M::Error> {
+                let mut attributes: AttributeMap = AttributeMap::new();
+                while let Some((name, value)) = map.next_entry::<Atom, String>()? {
+                    if !attributes.contains_key(&name) {
+                        attributes.entries.push((name, value));
+                    }
+                }
+                Ok(attributes)
+            }
+        }
+
+        deserializer.deserialize_map(Visitor)
+    }
+}
+
+
+/*
+    Element Accessors
+
+    Most code doesn't care about the attribute map as a whole; it wants the value of one
+    attribute, or the element's id, or its list of classes. These helpers answer those
+    questions without every caller repeating the map lookup and string splitting.
+ */
+impl Element {
+    /// Return the value of attribute `name`, if it is present.
+    pub fn attr(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(String::as_str)
+    }
+
+    /// The value of the `id` attribute.
+    pub fn id(&self) -> Option<&str> {
+        self.attr("id")
+    }
+
+    /// The class names in the `class` attribute, split on whitespace, without duplicates.
+    pub fn classes(&self) -> impl Iterator<Item = &str> {
+        let mut seen: HashSet<&str> = HashSet::default();
+        self.attr("class")
+            .unwrap_or("")
+            .split_whitespace()
+            .filter(move |class: &&str| seen.insert(class))
+    }
+
+    /// Return true if `class_name` is one of the element's classes.
+    pub fn has_class(&self, class_name: &str) -> bool {
+        self.attr("class").is_some_and(|list: &str| list.split_whitespace().any(|class: &str| class == class_name))
+    }
+
+    /// The element's `data-*` attributes, keyed by their camelCased names.
+    pub fn dataset(&self) -> Dataset<'_> {
+        Dataset { element: self }
+    }
+
+    /// Like `dataset`, but also allows setting and removing entries.
+    pub fn dataset_mut(&mut self) -> DatasetMut<'_> {
+        DatasetMut { element: self }
+    }
+}
+
+
+/*
+    Data Attributes
+
+    Pages (and programs embedding the engine) pass configuration to components through
+    `data-*` attributes. As in the DOM's [dataset](https://html.spec.whatwg.org/multipage/dom.html#dom-dataset),
+    each attribute is exposed under a camelCased key: `data-max-count` becomes `maxCount`.
+    Values can be read back as any type that implements `FromStr`.
+
+    e.g.
+        <div data-max-count="3" data-label="Items">
+
+        element.dataset().get("label")                   // Some("Items")
+        element.dataset().parse::<u32>("maxCount")       // Some(3)
+        element.dataset_mut().set("maxCount", 4)         // data-max-count="4"
+ */
+
+/// A read-only view of an element's `data-*` attributes.
+#[derive(Clone, Copy, Debug)]
+pub struct Dataset<'a> {
+    element: &'a Element,
+}
+
+/// A view of an element's `data-*` attributes that can change them.
+#[derive(Debug)]
+pub struct DatasetMut<'a> {
+    element: &'a mut Element,
+}
+
+impl<'a> Dataset<'a> {
+    /// The value for `key`, if there is one.
+    pub fn get(&self, key: &str) -> Option<&'a str> {
+        self.element.attr(&data_attribute_name(key)?)
+    }
+
+    /// The value for `key` parsed as a `T`, or `None` if it's missing or doesn't parse.
+    pub fn parse<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key)?.parse().ok()
+    }
+
+    /// Iterate over every (key, value) pair, in document order.
+    pub fn iter(&self) -> impl Iterator<Item = (String, &'a str)> + 'a {
+        self.element.attributes.iter().filter_map(|(name, value): (&Atom, &String)| {
+            Some((dataset_key(name)?, value.as_str()))
+        })
+    }
+}
+
+impl DatasetMut<'_> {
+    /// The value for `key`, if there is one.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.element.attr(&data_attribute_name(key)?)
+    }
+
+    /// The value for `key` parsed as a `T`, or `None` if it's missing or doesn't parse.
+    pub fn parse<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.get(key)?.parse().ok()
+    }
+
+    /// Set the attribute for `key`. Panics if `key` has a hyphen followed by a lowercase
+    /// letter, since no attribute name would map back to it.
+    pub fn set<T: ToString>(&mut self, key: &str, value: T) {
+        let name: String = match data_attribute_name(key) {
+            Some(name) => name,
+            None => panic!("Invalid dataset key: {:?}", key),
+        };
+        self.element.attributes.insert(Atom::from(name), value.to_string());
+    }
+
+    /// Remove the attribute for `key`, returning its old value.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.element.attributes.remove(data_attribute_name(key)?.as_str())
+    }
+}
+
+/// Convert an attribute name like `data-max-count` to its dataset key, `maxCount`.
+fn dataset_key(name: &str) -> Option<String> {
+    let rest: &str = name.strip_prefix("data-")?;
+    let mut key: String = String::new();
+    let mut chars = rest.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '-' && next.is_ascii_lowercase() => {
+                key.push(next.to_ascii_uppercase());
+                chars.next();
+            }
+            _ => key.push(c),
+        }
+    }
+    Some(key)
+}
+
+/// Convert a dataset key like `maxCount` to its attribute name, `data-max-count`.
+fn data_attribute_name(key: &str) -> Option<String> {
+    let mut name: String = String::from("data-");
+    let mut chars = key.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '-' && chars.peek().is_some_and(char::is_ascii_lowercase) {
+            return None;
+        }
+        if c.is_ascii_uppercase() {
+            name.push('-');
+            name.push(c.to_ascii_lowercase());
+        } else {
+            name.push(c);
+        }
+    }
+    Some(name)
+}
 
 
 /*
-    Finally, some constructor functions to make it easy to create new nodes.
+    The Tree
+
+    The tree owns every node. Nodes are never freed: a removed node is just unlinked from
+    its parent and siblings, and can be inserted somewhere else later.
  */
+/// An arena holding the nodes of a DOM tree.
+#[derive(Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tree {
+    nodes: Vec<Node>,
+}
 
-// Constructor functions for convenience
+impl Index<NodeId> for Tree {
+    type Output = Node;
 
-pub fn text(data: String) -> Node {
-    Node {
-        children: Vec::new(),
-        node_type: NodeType::Text(data),
+    fn index(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
     }
 }
 
-pub fn element(tag_name: String, attributes: AttributeMap, children: Vec<Node>) -> Node {
-    Node {
-        children,
-        node_type: NodeType::Element(Element { tag_name, attributes }),
+impl IndexMut<NodeId> for Tree {
+    fn index_mut(&mut self, id: NodeId) -> &mut Node {
+        &mut self.nodes[id.0]
+    }
+}
+
+impl Tree {
+    /// Create an empty tree.
+    pub fn new() -> Tree {
+        Default::default()
+    }
+
+    /// The number of nodes ever created in this tree (including detached ones).
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Return true if no nodes have been created.
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    /// The id of the node at `index` in the arena, if there is one. This is how ids that
+    /// went outside the engine (e.g. in a debug dump) are turned back into nodes.
+    pub fn node_id(&self, index: usize) -> Option<NodeId> {
+        if index < self.nodes.len() { Some(NodeId(index)) } else { None }
     }
+
+    /// Add a new, unattached node to the arena.
+    fn push(&mut self, node_type: NodeType) -> NodeId {
+        self.nodes.push(Node {
+            node_type,
+            parent: None,
+            first_child: None,
+            last_child: None,
+            prev_sibling: None,
+            next_sibling: None,
+            source: None,
+        });
+        NodeId(self.nodes.len() - 1)
+    }
+
+
+    /*
+        Finally, some constructor functions to make it easy to create new nodes.
+     */
+
+    // Constructor functions for convenience
+
+    pub fn text(&mut self, data: String) -> NodeId {
+        self.push(NodeType::Text(data))
+    }
+
+    pub fn element(&mut self, tag_name: Atom, attributes: AttributeMap, children: Vec<NodeId>) -> NodeId {
+        let id: NodeId = self.push(NodeType::Element(Element { tag_name, attributes }));
+        for child in children {
+            self.append_child(id, child);
+        }
+        id
+    }
+
+    pub fn fragment(&mut self, children: Vec<NodeId>) -> NodeId {
+        let id: NodeId = self.push(NodeType::DocumentFragment);
+        for child in children {
+            self.append_child(id, child);
+        }
+        id
+    }
+
+
+    /*
+        Mutations
+
+        Every mutation keeps the five links consistent: a node's parent, the first and last
+        child of that parent, and the previous and next siblings on either side of it.
+     */
+
+    /// Unlink `node` from its parent and siblings. Its own children stay attached to it.
+    pub fn detach(&mut self, node: NodeId) {
+        let (parent, prev, next) = {
+            let n: &Node = &self[node];
+            (n.parent, n.prev_sibling, n.next_sibling)
+        };
+
+        match prev {
+            Some(prev) => self[prev].next_sibling = next,
+            None => if let Some(parent) = parent { self[parent].first_child = next },
+        }
+        match next {
+            Some(next) => self[next].prev_sibling = prev,
+            None => if let Some(parent) = parent { self[parent].last_child = prev },
+        }
+
+        let n: &mut Node = &mut self[node];
+        n.parent = None;
+        n.prev_sibling = None;
+        n.next_sibling = None;
+    }
+
+    /// Insert `child` as the last child of `parent`, moving it from wherever it was.
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) {
+        self.insert_before(parent, child, None);
+    }
+
+    /// Insert `child` into `parent` just before `reference`, or at the end if `reference` is
+    /// `None`. If `child` is a fragment, its children are moved in instead. Panics if
+    /// `reference` isn't a child of `parent`, or if `child` is `parent` or one of its ancestors.
+    pub fn insert_before(&mut self, parent: NodeId, child: NodeId, reference: Option<NodeId>) {
+        if self.ancestors(parent).any(|ancestor: NodeId| ancestor == child) {
+            panic!("Cannot insert a node into itself or its own descendant");
+        }
+        if let Some(reference) = reference {
+            if self[reference].parent != Some(parent) {
+                panic!("Reference node is not a child of the parent node");
+            }
+            if reference == child {
+                return;
+            }
+        }
+
+        if let NodeType::DocumentFragment = self[child].node_type {
+            let children: Vec<NodeId> = self.children(child).collect();
+            for grandchild in children {
+                self.insert_before(parent, grandchild, reference);
+            }
+            return;
+        }
+
+        self.detach(child);
+
+        let prev: Option<NodeId> = match reference {
+            Some(reference) => self[reference].prev_sibling,
+            None => self[parent].last_child,
+        };
+
+        {
+            let c: &mut Node = &mut self[child];
+            c.parent = Some(parent);
+            c.prev_sibling = prev;
+            c.next_sibling = reference;
+        }
+        match prev {
+            Some(prev) => self[prev].next_sibling = Some(child),
+            None => self[parent].first_child = Some(child),
+        }
+        match reference {
+            Some(reference) => self[reference].prev_sibling = Some(child),
+            None => self[parent].last_child = Some(child),
+        }
+    }
+
+    /// Remove `child` from `parent`. Panics if it isn't a child of `parent`.
+    pub fn remove_child(&mut self, parent: NodeId, child: NodeId) {
+        if self[child].parent != Some(parent) {
+            panic!("Node is not a child of the parent node");
+        }
+        self.detach(child);
+    }
+
+
+    /*
+        Traversal
+
+        With the links in place, walking the tree in any direction is just a matter of
+        following them. Each walk is an iterator so it can be used with `filter`, `find`,
+        `any`, and so on.
+     */
+
+    /// Iterate over the children of `node`, first to last.
+    pub fn children(&self, node: NodeId) -> Siblings<'_> {
+        Siblings { tree: self, next: self[node].first_child }
+    }
+
+    /// Iterate over the siblings that come after `node`.
+    pub fn following_siblings(&self, node: NodeId) -> Siblings<'_> {
+        Siblings { tree: self, next: self[node].next_sibling }
+    }
+
+    /// Iterate over the siblings that come before `node`, nearest first.
+    pub fn preceding_siblings(&self, node: NodeId) -> PrecedingSiblings<'_> {
+        PrecedingSiblings { tree: self, next: self[node].prev_sibling }
+    }
+
+    /// Iterate over `node` and then its ancestors, up to the root.
+    pub fn ancestors(&self, node: NodeId) -> Ancestors<'_> {
+        Ancestors { tree: self, next: Some(node) }
+    }
+
+    /// Iterate over `node` and all of its descendants, in document (pre-)order.
+    pub fn descendants(&self, node: NodeId) -> Descendants<'_> {
+        Descendants { tree: self, root: node, next: Some(node) }
+    }
+
+    /// The text of `node` and all of its descendants, concatenated in document order.
+    /*
+        This is the DOM's [textContent](https://dom.spec.whatwg.org/#dom-node-textcontent):
+        it ignores styling completely, so hidden text and whitespace are all included. See
+        `style::StyledNode::inner_text` for text as the user would see it.
+     */
+    pub fn text_content(&self, node: NodeId) -> String {
+        let mut result: String = String::new();
+        for descendant in self.descendants(node) {
+            if let NodeType::Text(ref text) = self[descendant].node_type {
+                result.push_str(text);
+            }
+        }
+        result
+    }
+
+    /// The markup for `node` and all of its descendants, like `outerHTML` in the DOM. See
+    /// "Serializing" in `html`.
+    pub fn to_html(&self, node: NodeId) -> String {
+        html::serialize(self, node)
+    }
+
+    /*
+        Normalization
+
+        Building and editing a tree can leave text split across neighbouring text nodes, or
+        leave text nodes with nothing in them. [normalize](https://dom.spec.whatwg.org/#dom-node-normalize)
+        cleans this up, so that code walking the tree sees each run of text as one node.
+
+        The whitespace between tags ("<ul>\n  <li>") is often irrelevant too, and tests in
+        particular are easier to write without it, so it can be stripped separately.
+     */
+
+    /// Merge adjacent text nodes under `node`, and remove empty ones.
+    pub fn normalize(&mut self, node: NodeId) {
+        let descendants: Vec<NodeId> = self.descendants(node).skip(1).collect();
+        for current in descendants {
+            // Skip nodes already merged into an earlier sibling.
+            let data: String = match self[current].node_type {
+                NodeType::Text(ref data) if self.is_attached_to(current, node) => data.clone(),
+                _ => continue,
+            };
+            let mut merged: String = data;
+            while let Some(next) = self[current].next_sibling {
+                match self[next].node_type {
+                    NodeType::Text(ref data) => merged.push_str(data),
+                    _ => break,
+                }
+                self.detach(next);
+            }
+            if merged.is_empty() {
+                self.detach(current);
+            } else {
+                self[current].node_type = NodeType::Text(merged);
+            }
+        }
+    }
+
+    /// Remove every text node under `node` that contains only whitespace.
+    pub fn strip_whitespace_text(&mut self, node: NodeId) {
+        let whitespace: Vec<NodeId> = self.descendants(node)
+            .filter(|&n: &NodeId| match self[n].node_type {
+                NodeType::Text(ref data) => data.chars().all(char::is_whitespace),
+                _ => false,
+            })
+            .collect();
+        for text in whitespace {
+            self.detach(text);
+        }
+    }
+
+    /// Return true if `node` is `root` or one of its descendants.
+    fn is_attached_to(&self, node: NodeId, root: NodeId) -> bool {
+        self.ancestors(node).any(|ancestor: NodeId| ancestor == root)
+    }
+
+    /*
+        Cloning and Comparing Subtrees
+
+        Templates need to stamp out fresh copies of a subtree, and parser tests need to
+        check that the tree they got looks like the tree they expected. Both work on
+        structure only: node types, tag names, attributes, and text. Node ids are never
+        compared, since the same tree can be built with nodes in a different arena order.
+     */
+
+    /// Copy `node` and all of its descendants. The copy is not attached to any parent.
+    pub fn deep_clone(&mut self, node: NodeId) -> NodeId {
+        let node_type: NodeType = self[node].node_type.clone();
+        let copy: NodeId = self.push(node_type);
+        let children: Vec<NodeId> = self.children(node).collect();
+        for child in children {
+            let child_copy: NodeId = self.deep_clone(child);
+            self.append_child(copy, child_copy);
+        }
+        copy
+    }
+
+    /// Copy the children of a `<template>` into a new fragment, ready to be inserted.
+    pub fn template_contents(&mut self, template: NodeId) -> NodeId {
+        let children: Vec<NodeId> = self.children(template).collect();
+        let copies: Vec<NodeId> = children.into_iter().map(|child: NodeId| self.deep_clone(child)).collect();
+        self.fragment(copies)
+    }
+
+    /// Copy `node` and its descendants from another tree into this one, unattached.
+    pub fn import(&mut self, other: &Tree, node: NodeId) -> NodeId {
+        let copy: NodeId = self.push(other[node].node_type.clone());
+        for child in other.children(node) {
+            let child_copy: NodeId = self.import(other, child);
+            self.append_child(copy, child_copy);
+        }
+        copy
+    }
+
+    /// Return true if the subtree at `node` has the same structure, attributes, and text as
+    /// the subtree at `other_node` in `other` (which may be this same tree).
+    pub fn tree_eq(&self, node: NodeId, other: &Tree, other_node: NodeId) -> bool {
+        self[node].node_type == other[other_node].node_type
+            && self.children(node).count() == other.children(other_node).count()
+            && self.children(node).zip(other.children(other_node))
+                .all(|(a, b)| self.tree_eq(a, other, b))
+    }
+
+    /// Compare the positions of two nodes in document order.
+    /*
+        Walk down from the root towards both nodes. Where the two paths split, the node
+        whose branch comes first among the siblings comes first in the document. If one
+        path ends before they split, that node is an ancestor of the other, and comes first.
+        Nodes in unrelated subtrees are ordered by id, so the ordering is still consistent.
+     */
+    pub fn compare_document_order(&self, a: NodeId, b: NodeId) -> Ordering {
+        if a == b {
+            return Ordering::Equal;
+        }
+        let mut a_path: Vec<NodeId> = self.ancestors(a).collect();
+        let mut b_path: Vec<NodeId> = self.ancestors(b).collect();
+        a_path.reverse();
+        b_path.reverse();
+        if a_path[0] != b_path[0] {
+            return a.0.cmp(&b.0);
+        }
+
+        let common: usize = a_path.iter().zip(&b_path).take_while(|(x, y)| x == y).count();
+        match (a_path.get(common), b_path.get(common)) {
+            (None, _) => Ordering::Less,
+            (_, None) => Ordering::Greater,
+            (Some(&x), Some(&y)) => {
+                if self.following_siblings(x).any(|sibling: NodeId| sibling == y) {
+                    Ordering::Less
+                } else {
+                    Ordering::Greater
+                }
+            }
+        }
+    }
+}
+
+
+/// Iterator over a run of siblings, following `next_sibling` links.
+pub struct Siblings<'a> {
+    tree: &'a Tree,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Siblings<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current: NodeId = self.next?;
+        self.next = self.tree[current].next_sibling;
+        Some(current)
+    }
+}
+
+/// Iterator over a run of siblings, following `prev_sibling` links.
+pub struct PrecedingSiblings<'a> {
+    tree: &'a Tree,
+    next: Option<NodeId>,
+}
+
+impl Iterator for PrecedingSiblings<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current: NodeId = self.next?;
+        self.next = self.tree[current].prev_sibling;
+        Some(current)
+    }
+}
+
+/// Iterator over a node and its ancestors, following `parent` links.
+pub struct Ancestors<'a> {
+    tree: &'a Tree,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Ancestors<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current: NodeId = self.next?;
+        self.next = self.tree[current].parent;
+        Some(current)
+    }
+}
+
+/// Pre-order iterator over a subtree.
+/*
+    No stack is needed: from any node, the next one in document order is its first child,
+    or else its next sibling, or else the next sibling of the nearest ancestor that has one
+    (without climbing out of the subtree we started from).
+ */
+pub struct Descendants<'a> {
+    tree: &'a Tree,
+    root: NodeId,
+    next: Option<NodeId>,
+}
+
+impl Iterator for Descendants<'_> {
+    type Item = NodeId;
+
+    fn next(&mut self) -> Option<NodeId> {
+        let current: NodeId = self.next?;
+        self.next = self.tree[current].first_child.or_else(|| {
+            self.tree.ancestors(current)
+                .take_while(|&ancestor: &NodeId| ancestor != self.root)
+                .find_map(|ancestor: NodeId| self.tree[ancestor].next_sibling)
+        });
+        Some(current)
+    }
+}
+
+
+/*
+    The Document
+
+    The parser doesn't just produce a tree of nodes; it produces a document. Besides the
+    tree, a document knows which node is its root `<html>` element, what doctype it
+    declared, where it was loaded from, which URL relative links are resolved against,
+    and which "mode" it should be rendered in. (Very old pages rely on
+    [quirks mode](https://quirks.spec.whatwg.org/), which changes a few layout rules.)
+
+    The base URL is usually the document's own URL, but a `<base href="...">` element
+    can name another one, and then every relative URL in the document (links, images,
+    stylesheets, frames, and scripts) is resolved against that instead. The parser knows
+    neither, so whoever loads the document sets `url`, and works out `base_url` from it
+    and `base_href`.
+
+    e.g.
+        Document {
+            tree: Tree { ... },
+            doctype: Some("html"),
+            root_element: NodeId(0),
+            url: Some("http://example.com/blog/post.html"),
+            base_url: Some("http://example.com/"),
+            quirks_mode: QuirksMode::NoQuirks,
+        }
+ */
+
+/// The rendering mode of a document.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum QuirksMode {
+    NoQuirks,
+    LimitedQuirks,
+    Quirks,
+}
+
+/// A parsed HTML document.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    pub tree: Tree,
+    pub doctype: Option<String>,
+    pub root_element: NodeId,
+    pub url: Option<String>,      // where the document was loaded from
+    pub base_url: Option<String>, // what relative URLs in it are resolved against
+    pub quirks_mode: QuirksMode,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    indexes: Option<Indexes>,
+}
+
+impl Document {
+    /// Create a document whose root element is `root_element`.
+    pub fn new(tree: Tree, root_element: NodeId) -> Document {
+        Document {
+            tree,
+            doctype: None,
+            root_element,
+            url: None,
+            base_url: None,
+            quirks_mode: QuirksMode::NoQuirks,
+            indexes: None,
+        }
+    }
+
+    /// The markup for the whole document: its doctype, if it has one, and its root
+    /// element. See "Serializing" in `html`.
+    pub fn to_html(&self) -> String {
+        let doctype: String = self.doctype.as_ref().map(|name: &String| format!("<!DOCTYPE {}>", name)).unwrap_or_default();
+        doctype + &self.tree.to_html(self.root_element)
+    }
+
+    /// The root element of the document (usually `<html>`).
+    pub fn document_element(&self) -> NodeId {
+        self.root_element
+    }
+
+    /// The `<head>` element, if the root element has one as a child.
+    pub fn head(&self) -> Option<NodeId> {
+        self.child_element_named(self.root_element, "head")
+    }
+
+    /// The `<body>` element, if the root element has one as a child.
+    pub fn body(&self) -> Option<NodeId> {
+        self.child_element_named(self.root_element, "body")
+    }
+
+    /// The `href` of the document's first `<base>` element that has one, unresolved.
+    pub fn base_href(&self) -> Option<&str> {
+        self.tree.descendants(self.root_element).find_map(|node: NodeId| match self.tree[node].node_type {
+            NodeType::Element(ref element) if element.tag_name == "base" => element.attr("href"),
+            _ => None,
+        })
+    }
+
+    /// Find the first child of `parent` that is an element named `tag_name`.
+    fn child_element_named(&self, parent: NodeId, tag_name: &str) -> Option<NodeId> {
+        self.tree.children(parent).find(|&child: &NodeId| match self.tree[child].node_type {
+            NodeType::Element(ref element) => element.tag_name == tag_name,
+            _ => false,
+        })
+    }
+}
+
+
+/*
+    Lookup Indexes
+
+    `getElementById` and friends are called constantly by scripts and tools, and scanning the
+    whole tree every time gets slow on big pages. A document can optionally keep maps from
+    each id, class name, and tag name to the elements that have it, so lookups only cost as
+    much as the number of results.
+
+    The maps only cover elements that are connected to the document (i.e. inside the root
+    element), and every list is kept in document order. To keep them correct, make changes
+    through the `Document` mutation methods below rather than through `tree` directly. If the
+    tree was changed behind the document's back, call `build_indexes` again.
+ */
+
+/// Maps from ids, class names, and tag names to elements, in document order.
+#[derive(Default, Debug)]
+struct Indexes {
+    ids: HashMap<String, Vec<NodeId>>,
+    classes: HashMap<String, Vec<NodeId>>,
+    tags: HashMap<String, Vec<NodeId>>,
+}
+
+#[cfg(feature = "std")]
+impl HeapSize for Tree {
+    fn heap_size(&self) -> usize {
+        self.nodes.heap_size()
+    }
+}
+
+#[cfg(feature = "std")]
+impl HeapSize for Document {
+    fn heap_size(&self) -> usize {
+        let indexes: usize = self.indexes.as_ref().map_or(0, |indexes: &Indexes| {
+            indexes.ids.heap_size() + indexes.classes.heap_size() + indexes.tags.heap_size()
+        });
+        self.tree.heap_size() + self.doctype.heap_size() + self.url.heap_size() + self.base_url.heap_size() + indexes
+    }
+}
+
+/// Insert `node` into the list for `key`, keeping the list in document order.
+fn index_insert(map: &mut HashMap<String, Vec<NodeId>>, tree: &Tree, key: &str, node: NodeId) {
+    let list: &mut Vec<NodeId> = map.entry(key.to_string()).or_default();
+    if let Err(position) = list.binary_search_by(|&other: &NodeId| tree.compare_document_order(other, node)) {
+        list.insert(position, node);
+    }
+}
+
+/// Remove `node` from the list for `key`.
+fn index_remove(map: &mut HashMap<String, Vec<NodeId>>, key: &str, node: NodeId) {
+    if let Some(list) = map.get_mut(key) {
+        list.retain(|&other: &NodeId| other != node);
+        if list.is_empty() {
+            map.remove(key);
+        }
+    }
+}
+
+impl Document {
+    /// Build the id, class, and tag indexes, and keep them up to date from now on.
+    pub fn build_indexes(&mut self) {
+        self.indexes = Some(Indexes::default());
+        let nodes: Vec<NodeId> = self.tree.descendants(self.root_element).collect();
+        for node in nodes {
+            self.index_node(node);
+        }
+    }
+
+    /// Stop maintaining the indexes and free their memory.
+    pub fn drop_indexes(&mut self) {
+        self.indexes = None;
+    }
+
+    /// Return true if lookups are currently served from indexes.
+    pub fn has_indexes(&self) -> bool {
+        self.indexes.is_some()
+    }
+
+    /// Return true if `node` is inside the document's root element.
+    pub fn is_connected(&self, node: NodeId) -> bool {
+        self.tree.ancestors(node).any(|ancestor: NodeId| ancestor == self.root_element)
+    }
+
+    /// Add a single element to the indexes (its descendants are not included).
+    fn index_node(&mut self, node: NodeId) {
+        let (tree, indexes) = match (&self.tree, &mut self.indexes) {
+            (tree, Some(indexes)) => (tree, indexes),
+            _ => return,
+        };
+        if let NodeType::Element(ref element) = tree[node].node_type {
+            index_insert(&mut indexes.tags, tree, &element.tag_name, node);
+            if let Some(id) = element.id() {
+                index_insert(&mut indexes.ids, tree, id, node);
+            }
+            for class in element.classes() {
+                index_insert(&mut indexes.classes, tree, class, node);
+            }
+        }
+    }
+
+    /// Remove a single element from the indexes.
+    fn unindex_node(&mut self, node: NodeId) {
+        let (tree, indexes) = match (&self.tree, &mut self.indexes) {
+            (tree, Some(indexes)) => (tree, indexes),
+            _ => return,
+        };
+        if let NodeType::Element(ref element) = tree[node].node_type {
+            index_remove(&mut indexes.tags, &element.tag_name, node);
+            if let Some(id) = element.id() {
+                index_remove(&mut indexes.ids, id, node);
+            }
+            for class in element.classes() {
+                index_remove(&mut indexes.classes, class, node);
+            }
+        }
+    }
+
+    /// Run `f` on `node` and each of its descendants, if `node` is connected.
+    fn for_connected_subtree(&mut self, node: NodeId, f: fn(&mut Document, NodeId)) {
+        if self.indexes.is_none() || !self.is_connected(node) {
+            return;
+        }
+        let nodes: Vec<NodeId> = self.tree.descendants(node).collect();
+        for node in nodes {
+            f(self, node);
+        }
+    }
+
+
+    // Mutations that keep the indexes up to date
+
+    /// Insert `child` as the last child of `parent`. See `Tree::append_child`.
+    pub fn append_child(&mut self, parent: NodeId, child: NodeId) {
+        self.insert_before(parent, child, None);
+    }
+
+    /// Insert `child` into `parent` before `reference`. See `Tree::insert_before`.
+    pub fn insert_before(&mut self, parent: NodeId, child: NodeId, reference: Option<NodeId>) {
+        // A fragment is never connected itself; what gets indexed is its children.
+        let inserted: Vec<NodeId> = match self.tree[child].node_type {
+            NodeType::DocumentFragment => self.tree.children(child).collect(),
+            _ => vec![child],
+        };
+        self.for_connected_subtree(child, Document::unindex_node);
+        self.tree.insert_before(parent, child, reference);
+        for node in inserted {
+            self.for_connected_subtree(node, Document::index_node);
+        }
+    }
+
+    /// Remove `child` from `parent`. See `Tree::remove_child`.
+    pub fn remove_child(&mut self, parent: NodeId, child: NodeId) {
+        self.for_connected_subtree(child, Document::unindex_node);
+        self.tree.remove_child(parent, child);
+    }
+
+    /// Set an attribute on an element. Does nothing for text nodes.
+    pub fn set_attribute(&mut self, node: NodeId, name: &str, value: &str) {
+        let connected: bool = self.indexes.is_some() && self.is_connected(node);
+        if connected {
+            self.unindex_node(node);
+        }
+        if let NodeType::Element(ref mut element) = self.tree[node].node_type {
+            element.attributes.insert(Atom::from(name), value.to_string());
+        }
+        if connected {
+            self.index_node(node);
+        }
+    }
+
+    /// Remove an attribute from an element. Does nothing for text nodes.
+    pub fn remove_attribute(&mut self, node: NodeId, name: &str) {
+        let connected: bool = self.indexes.is_some() && self.is_connected(node);
+        if connected {
+            self.unindex_node(node);
+        }
+        if let NodeType::Element(ref mut element) = self.tree[node].node_type {
+            element.attributes.remove(name);
+        }
+        if connected {
+            self.index_node(node);
+        }
+    }
+
+
+    // Lookups
+
+    /// Return the first element (in document order) whose id is `id`.
+    pub fn get_element_by_id(&self, id: &str) -> Option<NodeId> {
+        match self.indexes {
+            Some(ref indexes) => indexes.ids.get(id).and_then(|list: &Vec<NodeId>| list.first().copied()),
+            None => self.tree.descendants(self.root_element).find(|&node: &NodeId| match self.tree[node].node_type {
+                NodeType::Element(ref element) => element.id() == Some(id),
+                _ => false,
+            }),
+        }
+    }
+
+    /// Return every element that has the class `class_name`, in document order.
+    pub fn get_elements_by_class_name(&self, class_name: &str) -> Vec<NodeId> {
+        match self.indexes {
+            Some(ref indexes) => indexes.classes.get(class_name).cloned().unwrap_or_default(),
+            None => self.tree.descendants(self.root_element).filter(|&node: &NodeId| match self.tree[node].node_type {
+                NodeType::Element(ref element) => element.has_class(class_name),
+                _ => false,
+            }).collect(),
+        }
+    }
+
+    /// Return every element named `tag_name`, in document order.
+    pub fn get_elements_by_tag_name(&self, tag_name: &str) -> Vec<NodeId> {
+        match self.indexes {
+            Some(ref indexes) => indexes.tags.get(tag_name).cloned().unwrap_or_default(),
+            None => self.tree.descendants(self.root_element).filter(|&node: &NodeId| match self.tree[node].node_type {
+                NodeType::Element(ref element) => element.tag_name == tag_name,
+                _ => false,
+            }).collect(),
+        }
+    }
+}
+
+
+/*
+    Metadata
+
+    Plenty of programs only want to know what a page is about, not what it looks like:
+    link previews, crawlers, bookmark managers. The document's head says, and reading it
+    needs no styling or layout:
+
+        <head>
+            <title>Build a Browser Engine</title>
+            <meta name="description" content="A toy engine, one stage at a time.">
+            <link rel="canonical" href="https://example.com/engine">
+            <meta property="og:title" content="Let's build a browser engine!">
+            <meta property="og:image" content="https://example.com/cover.png">
+        </head>
+
+    - `title` is the text of the first `<title>`, with its white space collapsed, as
+      `document.title` is in browsers.
+    - `meta` is the `content` of the first `<meta>` with that `name` (matched
+      case-insensitively), like "description", "author", or "keywords".
+    - `canonical_url` is the `href` of the first `<link rel="canonical">`: the URL the
+      page prefers to be known by, when it can be reached by several.
+    - `open_graph` lists the [Open Graph](https://ogp.me/) properties, which social sites
+      read for their previews: each `<meta property="og:...">`, in document order, since
+      some (like `og:image`) may repeat. `open_graph_property` picks out the first one.
+
+    URLs are returned as they were written; `engine::Page::resolve_url` resolves them.
+    Like browsers, these look anywhere in the document, not only in `<head>`.
+ */
+impl Document {
+    /// The text of the document's first `<title>` element, with leading and trailing
+    /// white space removed and runs of it inside collapsed to one space.
+    pub fn title(&self) -> Option<String> {
+        let title: NodeId = *self.get_elements_by_tag_name("title").first()?;
+        Some(self.tree.text_content(title).split_whitespace().collect::<Vec<&str>>().join(" "))
+    }
+
+    /// The `content` of the first `<meta>` element whose `name` is `name` (in any case).
+    pub fn meta(&self, name: &str) -> Option<&str> {
+        self.meta_elements().find_map(|element: &Element| {
+            element.attr("name").filter(|meta: &&str| meta.eq_ignore_ascii_case(name)).and(element.attr("content"))
+        })
+    }
+
+    /// The `href` of the first `<link rel="canonical">`, unresolved.
+    pub fn canonical_url(&self) -> Option<&str> {
+        self.get_elements_by_tag_name("link").into_iter().find_map(|node: NodeId| match self.tree[node].node_type {
+            NodeType::Element(ref element) if element.attr("rel").is_some_and(|rel: &str| {
+                rel.split_ascii_whitespace().any(|rel: &str| rel.eq_ignore_ascii_case("canonical"))
+            }) => element.attr("href"),
+            _ => None,
+        })
+    }
+
+    /// The document's Open Graph properties, as (property, content) pairs in document
+    /// order, without the `og:` prefix: ("title", "..."), ("image", "..."), ...
+    pub fn open_graph(&self) -> Vec<(&str, &str)> {
+        self.meta_elements().filter_map(|element: &Element| {
+            let property: &str = element.attr("property")?;
+            let property: &str = property.get(..3).filter(|prefix: &&str| prefix.eq_ignore_ascii_case("og:")).and(property.get(3..))?;
+            Some((property, element.attr("content")?))
+        }).collect()
+    }
+
+    /// The content of the first Open Graph property named `property` (without `og:`).
+    pub fn open_graph_property(&self, property: &str) -> Option<&str> {
+        self.open_graph().into_iter().find(|&(name, _): &(&str, &str)| name == property).map(|(_, content)| content)
+    }
+
+    /// The document's `<meta>` elements, in document order.
+    fn meta_elements(&self) -> impl Iterator<Item = &Element> {
+        self.get_elements_by_tag_name("meta").into_iter().filter_map(move |node: NodeId| match self.tree[node].node_type {
+            NodeType::Element(ref element) => Some(element),
+            _ => None,
+        })
+    }
+}
+
+
+/*
+    Dumping the Tree
+
+    When a test fails, we want to see what the parser actually built. `dump` prints a
+    subtree with one node per line, indented by depth. Elements are printed as their opening
+    tag (with attributes in document order) and text nodes as quoted strings.
+
+    e.g.
+        <html>
+          <body>
+            <p class="note">
+              "Hello "
+              <em>
+                "world"
+ */
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "<{}", self.tag_name)?;
+        for (name, value) in &self.attributes {
+            write!(f, " {}={:?}", name, value)?;
+        }
+        write!(f, ">")
+    }
+}
+
+impl fmt::Display for NodeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NodeType::Element(element) => write!(f, "{}", element),
+            NodeType::Text(text) => write!(f, "{:?}", text),
+            NodeType::DocumentFragment => write!(f, "#document-fragment"),
+        }
+    }
+}
+
+/// Print the subtree rooted at `node`, one node per line.
+pub fn dump(tree: &Tree, node: NodeId) -> String {
+    let mut result: String = String::new();
+    dump_node(tree, node, 0, &mut result);
+    result
+}
+
+fn dump_node(tree: &Tree, node: NodeId, depth: usize, result: &mut String) {
+    result.push_str(&"  ".repeat(depth));
+    result.push_str(&tree[node].node_type.to_string());
+    result.push('\n');
+    for child in tree.children(node) {
+        dump_node(tree, child, depth + 1, result);
+    }
+}
+
+
+/*
+    Diffing
+
+    A live-reloading or reactive embedder re-renders its HTML and ends up with a whole new
+    tree, but rebuilding the page from scratch would throw away the work the engine has
+    already done. Instead, `diff` compares the old and new trees and produces a list of
+    patches that turn the old tree into the new one. Applying them with
+    `Document::apply_patches` touches only the nodes that actually changed.
+
+    Children are matched with a longest common subsequence: two children match if they are
+    the same kind of node (text, or elements with the same tag and the same `id`). Matched
+    children are diffed recursively, old children with no match are removed, and new
+    children with no match are inserted. Patches refer to nodes of the old tree, except for
+    inserted content, which is copied from the new tree when the patches are applied.
+
+    e.g.
+        old: <ul><li>a</li><li>b</li></ul>
+        new: <ul class="x"><li>a</li><li>c</li><p>d</p></ul>
+
+        SetAttribute { node: ul, name: "class", value: "x" }
+        SetText { node: "b", text: "c" }
+        Insert { parent: ul, before: None, new_node: p }
+ */
+
+/// One change needed to turn an old tree into a new one.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Patch {
+    /// Replace the old node (and its subtree) with a copy of `new_node` from the new tree.
+    Replace { node: NodeId, new_node: NodeId },
+    /// Insert a copy of `new_node` from the new tree into `parent`, before `before`.
+    Insert { parent: NodeId, before: Option<NodeId>, new_node: NodeId },
+    /// Remove `node` from `parent`.
+    Remove { parent: NodeId, node: NodeId },
+    SetAttribute { node: NodeId, name: Atom, value: String },
+    RemoveAttribute { node: NodeId, name: Atom },
+    SetText { node: NodeId, text: String },
+}
+
+/// Compute the patches that turn the subtree at `old_root` into the one at `new_root`.
+pub fn diff(old: &Tree, old_root: NodeId, new: &Tree, new_root: NodeId) -> Vec<Patch> {
+    let mut patches: Vec<Patch> = Vec::new();
+    if same_kind(old, old_root, new, new_root) {
+        diff_node(old, old_root, new, new_root, &mut patches);
+    } else {
+        patches.push(Patch::Replace { node: old_root, new_node: new_root });
+    }
+    patches
+}
+
+/// Return true if two nodes could be patched into each other rather than replaced.
+fn same_kind(old: &Tree, old_node: NodeId, new: &Tree, new_node: NodeId) -> bool {
+    match (&old[old_node].node_type, &new[new_node].node_type) {
+        (NodeType::Element(a), NodeType::Element(b)) => a.tag_name == b.tag_name && a.id() == b.id(),
+        (NodeType::Text(_), NodeType::Text(_)) => true,
+        (NodeType::DocumentFragment, NodeType::DocumentFragment) => true,
+        _ => false,
+    }
+}
+
+/// Diff two nodes of the same kind, then their children.
+fn diff_node(old: &Tree, old_node: NodeId, new: &Tree, new_node: NodeId, patches: &mut Vec<Patch>) {
+    match (&old[old_node].node_type, &new[new_node].node_type) {
+        (NodeType::Text(a), NodeType::Text(b)) if a != b => {
+            patches.push(Patch::SetText { node: old_node, text: b.clone() });
+        }
+        (NodeType::Element(a), NodeType::Element(b)) => {
+            // Sorted, so the patches come out in the same order every time.
+            let mut names: Vec<&Atom> = a.attributes.keys().chain(b.attributes.keys()).collect();
+            names.sort();
+            names.dedup();
+            for &name in names {
+                match (a.attributes.get(&name), b.attributes.get(&name)) {
+                    (Some(x), Some(y)) if x == y => {}
+                    (_, Some(y)) => patches.push(Patch::SetAttribute { node: old_node, name, value: y.clone() }),
+                    (Some(_), None) => patches.push(Patch::RemoveAttribute { node: old_node, name }),
+                    (None, None) => {}
+                }
+            }
+        }
+        _ => {}
+    }
+    diff_children(old, old_node, new, new_node, patches);
+}
+
+/// Match up the children of two nodes, and diff, remove, or insert each of them.
+fn diff_children(old: &Tree, old_parent: NodeId, new: &Tree, new_parent: NodeId, patches: &mut Vec<Patch>) {
+    let old_children: Vec<NodeId> = old.children(old_parent).collect();
+    let new_children: Vec<NodeId> = new.children(new_parent).collect();
+    let (n, m) = (old_children.len(), new_children.len());
+
+    // lengths[i][j] = length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths: Vec<Vec<usize>> = vec![vec![0; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lengths[i][j] = if same_kind(old, old_children[i], new, new_children[j]) {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    // Walk the table, collecting new children until they can be placed before a match.
+    let (mut i, mut j) = (0, 0);
+    let mut pending: Vec<NodeId> = Vec::new();
+    while i < n || j < m {
+        if i < n && j < m && same_kind(old, old_children[i], new, new_children[j])
+            && lengths[i][j] == lengths[i + 1][j + 1] + 1 {
+            for new_node in pending.drain(..) {
+                patches.push(Patch::Insert { parent: old_parent, before: Some(old_children[i]), new_node });
+            }
+            diff_node(old, old_children[i], new, new_children[j], patches);
+            i += 1;
+            j += 1;
+        } else if j < m && (i == n || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            pending.push(new_children[j]);
+            j += 1;
+        } else {
+            patches.push(Patch::Remove { parent: old_parent, node: old_children[i] });
+            i += 1;
+        }
+    }
+    for new_node in pending {
+        patches.push(Patch::Insert { parent: old_parent, before: None, new_node });
+    }
+}
+
+impl Document {
+    /// Apply patches produced by `diff(&self.tree, ..., new, ...)`, copying inserted nodes
+    /// from `new`. Keeps the indexes up to date.
+    pub fn apply_patches(&mut self, new: &Tree, patches: &[Patch]) {
+        for patch in patches {
+            match *patch {
+                Patch::Replace { node, new_node } => {
+                    let copy: NodeId = self.tree.import(new, new_node);
+                    match self.tree[node].parent {
+                        Some(parent) => {
+                            self.insert_before(parent, copy, Some(node));
+                            self.remove_child(parent, node);
+                        }
+                        None => {
+                            if self.root_element == node {
+                                self.root_element = copy;
+                                if self.has_indexes() {
+                                    self.build_indexes();
+                                }
+                            }
+                        }
+                    }
+                }
+                Patch::Insert { parent, before, new_node } => {
+                    let copy: NodeId = self.tree.import(new, new_node);
+                    self.insert_before(parent, copy, before);
+                }
+                Patch::Remove { parent, node } => self.remove_child(parent, node),
+                Patch::SetAttribute { node, name, ref value } => self.set_attribute(node, &name, value),
+                Patch::RemoveAttribute { node, name } => self.remove_attribute(node, &name),
+                Patch::SetText { node, ref text } => {
+                    if let NodeType::Text(ref mut data) = self.tree[node].node_type {
+                        data.clone_from(text);
+                    }
+                }
+            }
+        }
+    }
+}
+
+
+/*
+    Building Trees in Code
+
+    Tests for the style and layout modules need small DOM trees, and building them one
+    `Tree::element` call at a time is noisy. The `html!` macro builds a `Document` from a
+    compact description instead:
+        - `tag` or `tag { children }` is an element
+        - `tag(name = "value", ...)` gives it attributes; names that aren't Rust identifiers
+          can be written as strings: `div("data-id" = "1")`
+        - a string literal is a text node
+
+    Like the parser, it wraps several top-level nodes in an `<html>` element.
+
+    e.g.
+        let document: Document = html! {
+            div(class = "note") {
+                p { "Hello " em { "world" } }
+                br
+            }
+        };
+ */
+/// Build a `dom::Document` from a declarative description of its nodes.
+#[macro_export]
+macro_rules! html {
+    // Sibling nodes, pushed onto the `Vec<NodeId>` named `$nodes`.
+    (@nodes $tree:ident, $nodes:ident $(,)?) => {};
+    (@nodes $tree:ident, $nodes:ident, $text:literal $($rest:tt)*) => {
+        $nodes.push($tree.text(($text).to_string()));
+        $crate::html!(@nodes $tree, $nodes, $($rest)*);
+    };
+    (@nodes $tree:ident, $nodes:ident, $tag:ident ( $($attributes:tt)* ) { $($children:tt)* } $($rest:tt)*) => {
+        $nodes.push($crate::html!(@element $tree, $tag, ($($attributes)*), { $($children)* }));
+        $crate::html!(@nodes $tree, $nodes, $($rest)*);
+    };
+    (@nodes $tree:ident, $nodes:ident, $tag:ident ( $($attributes:tt)* ) $($rest:tt)*) => {
+        $nodes.push($crate::html!(@element $tree, $tag, ($($attributes)*), {}));
+        $crate::html!(@nodes $tree, $nodes, $($rest)*);
+    };
+    (@nodes $tree:ident, $nodes:ident, $tag:ident { $($children:tt)* } $($rest:tt)*) => {
+        $nodes.push($crate::html!(@element $tree, $tag, (), { $($children)* }));
+        $crate::html!(@nodes $tree, $nodes, $($rest)*);
+    };
+    (@nodes $tree:ident, $nodes:ident, $tag:ident $($rest:tt)*) => {
+        $nodes.push($crate::html!(@element $tree, $tag, (), {}));
+        $crate::html!(@nodes $tree, $nodes, $($rest)*);
+    };
+
+    // A single element with its attributes and children.
+    (@element $tree:ident, $tag:ident, ($($attributes:tt)*), { $($children:tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut attributes: $crate::dom::AttributeMap = $crate::dom::AttributeMap::default();
+        $crate::html!(@attributes attributes, $($attributes)*);
+        #[allow(unused_mut)]
+        let mut children: Vec<$crate::dom::NodeId> = Vec::new();
+        $crate::html!(@nodes $tree, children, $($children)*);
+        $tree.element($crate::atom::Atom::from(stringify!($tag)), attributes, children)
+    }};
+
+    // `name = value` pairs, separated by commas.
+    (@attributes $map:ident $(,)?) => {};
+    (@attributes $map:ident, $name:tt = $value:expr $(, $($rest:tt)*)?) => {
+        $map.insert($crate::atom::Atom::from($crate::html!(@name $name)), ($value).to_string());
+        $crate::html!(@attributes $map, $($($rest)*)?);
+    };
+    (@name $name:ident) => { stringify!($name) };
+    (@name $name:literal) => { $name };
+
+    // Entry point.
+    ($($body:tt)+) => {{
+        let mut tree: $crate::dom::Tree = $crate::dom::Tree::new();
+        let mut nodes: Vec<$crate::dom::NodeId> = Vec::new();
+        $crate::html!(@nodes tree, nodes, $($body)+);
+        let root: $crate::dom::NodeId = if nodes.len() == 1 {
+            nodes[0]
+        } else {
+            tree.element($crate::atom::Atom::from("html"), $crate::dom::AttributeMap::default(), nodes)
+        };
+        $crate::dom::Document::new(tree, root)
+    }};
 }
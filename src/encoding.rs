@@ -0,0 +1,253 @@
+//! Character encodings: working out which one a page's bytes are in, and decoding them
+//! into a string to parse.
+
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use alloc::borrow::Cow;
+
+
+/*
+    Encodings
+
+    The parsers work on strings, but a page arrives as bytes, and not every page is
+    written in UTF-8. Older pages are often in Windows-1252 (a superset of ISO-8859-1,
+    which browsers treat as the same thing), and some are in UTF-16. The bytes don't say
+    which, so a browser has to work it out, and if it guesses wrong, every accented
+    letter comes out as mojibake: "café" in Windows-1252 read as UTF-8 is "caf�".
+
+    `detect` follows the order in the [HTML spec](https://html.spec.whatwg.org/multipage/parsing.html#determining-the-character-encoding),
+    stopping at the first that gives an answer:
+        1. A byte order mark at the start: EF BB BF is UTF-8, FF FE is UTF-16LE, and
+           FE FF is UTF-16BE.
+        2. The `charset` parameter of the HTTP `Content-Type` header:
+           `text/html; charset=windows-1252`.
+        3. A `<meta charset="...">` tag, or a `<meta http-equiv="Content-Type"
+           content="text/html; charset=...">` tag, in the first 1024 bytes (see `prescan`).
+        4. A guess: UTF-8 if the bytes are valid UTF-8, and Windows-1252 if they're not,
+           since every sequence of bytes is valid Windows-1252.
+
+    Each source names the encoding with a label, matched case-insensitively with
+    the surrounding white space ignored; an encoding has many (`latin1`, `iso-8859-1`,
+    `us-ascii`, ... all mean Windows-1252). Labels for encodings this engine doesn't
+    support, like `shift_jis`, are skipped as though they weren't there. A `<meta>` tag
+    that says UTF-16 means UTF-8, since a page that can be read far enough to find the
+    tag can't be in UTF-16.
+ */
+/// A character encoding that pages can be decoded from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Windows1252,
+}
+
+/// What bytes 0x80-0x9F stand for in Windows-1252 (and in numeric character references).
+/// The five bytes Windows-1252 doesn't use stand for themselves.
+pub const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/// The labels of each encoding, from the [Encoding spec](https://encoding.spec.whatwg.org/#names-and-labels).
+const LABELS: [(Encoding, &[&str]); 4] = [
+    (Encoding::Utf8, &["unicode-1-1-utf-8", "unicode11utf8", "unicode20utf8", "utf-8", "utf8", "x-unicode20utf8"]),
+    (Encoding::Utf16Le, &["csunicode", "iso-10646-ucs-2", "ucs-2", "unicode", "unicodefeff", "utf-16", "utf-16le"]),
+    (Encoding::Utf16Be, &["unicodefffe", "utf-16be"]),
+    (Encoding::Windows1252, &[
+        "ansi_x3.4-1968", "ascii", "cp1252", "cp819", "csisolatin1", "ibm819", "iso-8859-1", "iso-ir-100", "iso8859-1",
+        "iso88591", "iso_8859-1", "iso_8859-1:1987", "l1", "latin1", "us-ascii", "windows-1252", "x-cp1252",
+    ]),
+];
+
+impl Encoding {
+    /// The encoding that `label` names, if it's one this engine supports.
+    pub fn for_label(label: &str) -> Option<Encoding> {
+        let label: &str = label.trim_matches(|c: char| c.is_ascii_whitespace());
+        LABELS.iter()
+            .find(|(_, labels)| labels.iter().any(|name: &&str| label.eq_ignore_ascii_case(name)))
+            .map(|&(encoding, _)| encoding)
+    }
+
+    /// The encoding's name, as `document.characterSet` gives it.
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Windows1252 => "windows-1252",
+        }
+    }
+
+    /// Decode `bytes` from this encoding. Sequences that aren't valid in it become U+FFFD.
+    /// The result borrows `bytes` when they're already valid UTF-8 text that means the
+    /// same thing in this encoding.
+    pub fn decode(self, bytes: &[u8]) -> Cow<'_, str> {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes),
+            Encoding::Utf16Le | Encoding::Utf16Be => {
+                let unit = |pair: &[u8]| -> u16 {
+                    let pair: [u8; 2] = [pair[0], pair[1]];
+                    if self == Encoding::Utf16Le { u16::from_le_bytes(pair) } else { u16::from_be_bytes(pair) }
+                };
+                let mut text: String = char::decode_utf16(bytes.chunks_exact(2).map(unit))
+                    .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                    .collect();
+                // A lone byte at the end is half a code unit.
+                if bytes.len() % 2 == 1 {
+                    text.push(char::REPLACEMENT_CHARACTER);
+                }
+                Cow::Owned(text)
+            }
+            Encoding::Windows1252 => match core::str::from_utf8(bytes) {
+                Ok(text) if bytes.is_ascii() => Cow::Borrowed(text),
+                _ => Cow::Owned(bytes.iter().map(|&byte: &u8| match byte {
+                    0x80..=0x9F => WINDOWS_1252[usize::from(byte - 0x80)],
+                    byte => char::from(byte),
+                }).collect()),
+            },
+        }
+    }
+}
+
+/// The encoding that a byte order mark at the start of `bytes` says, and the mark's length.
+pub fn sniff_bom(bytes: &[u8]) -> Option<(Encoding, usize)> {
+    match bytes {
+        [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+        [0xFF, 0xFE, ..] => Some((Encoding::Utf16Le, 2)),
+        [0xFE, 0xFF, ..] => Some((Encoding::Utf16Be, 2)),
+        _ => None,
+    }
+}
+
+/// The encoding named by the `charset` parameter of a `Content-Type` header value (or
+/// `<meta>` tag content), like `text/html; charset="utf-8"`.
+pub fn from_content_type(content_type: &str) -> Option<Encoding> {
+    let lower: String = content_type.to_ascii_lowercase();
+    let mut offset: usize = 0;
+    // "charset" may come up elsewhere, so keep looking until it's followed by "=".
+    let rest: &str = loop {
+        offset += lower[offset..].find("charset")? + "charset".len();
+        if let Some(value) = content_type[offset..].trim_start().strip_prefix('=') {
+            break value.trim_start();
+        }
+    };
+    let value: &str = match rest.chars().next() {
+        Some(quote @ ('"' | '\'')) => rest[1..].split(quote).next().unwrap_or(""),
+        _ => rest.split([';', ' ', '\t']).next().unwrap_or(""),
+    };
+    Encoding::for_label(value)
+}
+
+/// Work out the encoding of an HTML document's `bytes`, given the `Content-Type` header
+/// it came with, if any. See "Encodings", above.
+pub fn detect(bytes: &[u8], content_type: Option<&str>) -> Encoding {
+    sniff_bom(bytes).map(|(encoding, _)| encoding)
+        .or_else(|| content_type.and_then(from_content_type))
+        .or_else(|| prescan(bytes))
+        .unwrap_or(if core::str::from_utf8(bytes).is_ok() { Encoding::Utf8 } else { Encoding::Windows1252 })
+}
+
+/// Decode an HTML document's `bytes` into text, in the encoding `detect` picks, without
+/// its byte order mark. Returns the text and the encoding.
+pub fn decode_html<'bytes>(bytes: &'bytes [u8], content_type: Option<&str>) -> (Cow<'bytes, str>, Encoding) {
+    let encoding: Encoding = detect(bytes, content_type);
+    let bytes: &[u8] = match sniff_bom(bytes) {
+        Some((bom, length)) if bom == encoding => &bytes[length..],
+        _ => bytes,
+    };
+    (encoding.decode(bytes), encoding)
+}
+
+
+/*
+    Prescanning
+
+    The `<meta>` tag that names the encoding is inside the document it describes, so the
+    browser has to read some of the document before it knows how to read the document.
+    That works because the markup itself is ASCII, which means the same thing in every
+    encoding a page can declare this way. The spec's [prescan](https://html.spec.whatwg.org/multipage/parsing.html#prescan-a-byte-stream-to-determine-its-encoding)
+    reads the first 1024 bytes as bytes, without decoding them, and looks only for
+    `<meta>` tags, skipping comments, since a commented-out tag doesn't count:
+
+        <!-- <meta charset="utf-8"> -->
+        <meta http-equiv="Content-Type" content="text/html; charset=iso-8859-1">
+
+    is Windows-1252. Every other tag, and all the text, is passed over.
+ */
+/// How much of a document `prescan` looks at, in bytes.
+pub const PRESCAN_LENGTH: usize = 1024;
+
+/// The encoding a `<meta>` tag in the first `PRESCAN_LENGTH` bytes of `bytes` declares.
+/// See "Prescanning", above.
+pub fn prescan(bytes: &[u8]) -> Option<Encoding> {
+    let bytes: &[u8] = &bytes[..bytes.len().min(PRESCAN_LENGTH)];
+    let mut position: usize = 0;
+    while position < bytes.len() {
+        let rest: &[u8] = &bytes[position..];
+        if rest.starts_with(b"<!--") {
+            position += find(&rest[4..], b"-->").map_or(rest.len(), |end: usize| 4 + end + 3);
+        } else if rest.len() > 5 && rest[..5].eq_ignore_ascii_case(b"<meta") && (rest[5].is_ascii_whitespace() || rest[5] == b'/') {
+            let (attributes, length) = meta_attributes(&rest[5..]);
+            position += 5 + length;
+            let attribute = |name: &str| -> Option<&str> {
+                attributes.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+            };
+            let encoding: Option<Encoding> = match attribute("charset") {
+                Some(charset) => Encoding::for_label(charset),
+                None if attribute("http-equiv").is_some_and(|equiv: &str| equiv.eq_ignore_ascii_case("content-type")) => {
+                    attribute("content").and_then(from_content_type)
+                }
+                None => None,
+            };
+            if let Some(encoding) = encoding {
+                // Bytes that can be read as ASCII aren't UTF-16.
+                return Some(match encoding {
+                    Encoding::Utf16Le | Encoding::Utf16Be => Encoding::Utf8,
+                    encoding => encoding,
+                });
+            }
+        } else {
+            position += 1;
+        }
+    }
+    None
+}
+
+/// Where `needle` first appears in `haystack`.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window: &[u8]| window == needle)
+}
+
+/// Read the attributes of a `<meta>` tag, from just after its name to its `>`, returning
+/// them, as (name, value) pairs, and how many bytes they took up.
+fn meta_attributes(bytes: &[u8]) -> (Vec<(String, String)>, usize) {
+    let mut attributes: Vec<(String, String)> = Vec::new();
+    let mut position: usize = 0;
+    let run = |position: usize, stop: &dyn Fn(u8) -> bool| -> usize {
+        bytes[position..].iter().position(|&byte: &u8| stop(byte)).map_or(bytes.len(), |length: usize| position + length)
+    };
+    let skip_whitespace = |position: usize| -> usize { run(position, &|byte: u8| !byte.is_ascii_whitespace()) };
+    loop {
+        position = run(position, &|byte: u8| !byte.is_ascii_whitespace() && byte != b'/');
+        if position >= bytes.len() || bytes[position] == b'>' {
+            return (attributes, (position + 1).min(bytes.len()));
+        }
+        let name_end: usize = run(position, &|byte: u8| byte.is_ascii_whitespace() || matches!(byte, b'=' | b'>' | b'/'));
+        let name: String = String::from_utf8_lossy(&bytes[position..name_end]).into_owned();
+        position = skip_whitespace(name_end);
+        let mut value: String = String::new();
+        if bytes.get(position) == Some(&b'=') {
+            position = skip_whitespace(position + 1);
+            let (start, end) = match bytes.get(position) {
+                Some(&quote @ (b'"' | b'\'')) => (position + 1, run(position + 1, &|byte: u8| byte == quote)),
+                _ => (position, run(position, &|byte: u8| byte.is_ascii_whitespace() || byte == b'>')),
+            };
+            value = String::from_utf8_lossy(&bytes[start..end]).into_owned();
+            position = if end < bytes.len() && bytes[end] != b'>' && !bytes[end].is_ascii_whitespace() { end + 1 } else { end };
+        }
+        attributes.push((name, value));
+    }
+}
@@ -0,0 +1,1811 @@
+//! The whole pipeline behind one type: load a page, lay it out, and paint it. Sessions
+//! group pages into tabs with navigation history.
+
+use crate::{accessibility, css, debug, dom, encoding, find, fonts, forms, html, layout, loader, net, painting, readability, style, trace, viewport};
+use crate::collections::{HashMap, HashSet};
+use crate::error::EngineError;
+use crate::memory::{HeapSize, MemoryReport};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::mem::size_of;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+use std::time::{Duration, Instant};
+#[cfg(feature = "js")]
+use crate::{events, js, script};
+
+
+/*
+    Pages
+
+    Each module so far handles one stage of the pipeline:
+
+        HTML -> html::parse  -> DOM  \
+                                      style::style_tree -> layout::layout_tree -> painting -> pixels
+        CSS  -> css::parse   -> CSS  /
+
+    and wiring them together by hand takes a dozen lines of boilerplate. A `Page` owns the
+    document, its stylesheet, the viewport, and the image loader, and runs the stages for
+    you. It also remembers the output of each stage, so asking for the pixels twice only
+    paints once, and changing only the viewport height doesn't lay the page out again.
+
+    The style and layout trees borrow from the document, so the page can't keep them
+    around between calls. What it caches instead is the display list (the end product of
+    layout) and the painted canvas. `with_layout_tree` builds a fresh layout tree for
+    callers that need to inspect the boxes themselves.
+
+    The viewport shows the document from its scroll position. Scrolling the document
+    doesn't change layout or the display list: the page shifts the pixels it already
+    painted and paints only the strips that came into view. Boxes with `overflow: auto` or
+    `scroll` have scroll positions of their own, which do change the display list, since
+    their contents move relative to everything else.
+
+    The page also tracks which element the mouse is over, for `:hover` rules. Moving the
+    mouse restyles only if a rule cares about the elements that entered or left the hover
+    chain, and if the rule only changes colors, only those elements are repainted.
+
+    Zoom and HiDPI screens both mean more device pixels per CSS px. The viewport and the
+    canvas are measured in device pixels, and so are points in the viewport (as passed to
+    `node_at`), but layout, scroll positions, and scroll distances are all in CSS px: the
+    page lays out at the viewport size divided by the scale, then scales the display list
+    up to device pixels before painting.
+
+    A page can ask for a scale and a layout width of its own, with a viewport meta tag
+    (see `viewport`). Its scale multiplies the other two, and if it asks to be laid out
+    wider than the viewport, the rest can be scrolled to.
+
+    Form controls are interactive: `click_at` focuses the control under a point (ticking or
+    unticking checkboxes and radio buttons), and `type_text` and `delete_backward` edit the
+    value of the focused text field. The focus ring and caret are drawn on top of the
+    display list, like find highlights. Clicking a link doesn't load anything by itself:
+    the page remembers the link's `href` for whoever owns it (`take_followed_link`), and
+    a `Session` navigates there.
+
+    With the `js` feature, a click is first dispatched as a `click` event to the page's
+    scripts. Focus moves either way, but a listener that calls `preventDefault` stops the
+    rest: the checkbox keeps its state, and the link isn't followed. (Browsers tick the
+    checkbox before listeners run and untick it if the click is canceled, so listeners
+    see the new state; here they see the old one.)
+
+    An `<iframe>` shows another document, so a page may own other pages: one per frame,
+    loaded in the background like images (through `loader::DocumentLoader`), styled by
+    their own `<style>` elements, and laid out at the size of their frame. When the parent
+    lays out, it paints each frame's page and draws the pixels into the frame's box, as if
+    it were an image. Frames can contain frames, down to `MAX_FRAME_DEPTH`.
+
+    With the `js` feature, the page runs the document's `<script>` elements when it is
+    created, before anything is laid out (see `script`), and `execute_script` runs more
+    JavaScript against the document later. A script that only edits classes and inline
+    styles is handled like a `:hover` change: if the new styles only change colors, just
+    the restyled boxes are painted over.
+
+    Script timers and animation frames run on the page's own clock (see `event_loop`),
+    which only moves when `advance_time` is called: a window calls it as real time
+    passes, waking up after `next_task_delay`, and a headless caller can step through an
+    animation frame by frame and get the same pixels every time.
+
+    For end-to-end tests there is a small automation API, in the style of Puppeteer:
+    `click` and `type_into` act on the first element matching a selector,
+    `wait_for_selector` waits (on the page's clock) for scripts to add one, `evaluate`
+    runs JavaScript, and `screenshot` returns the painted viewport as a PNG file. Their
+    errors are `AutomationError`s.
+
+        page.type_into("#name", "Ada")?;
+        page.click("button.submit")?;
+        page.wait_for_selector(".done", Duration::from_secs(1))?;
+
+    The page keeps a timeline of the pipeline stages it ran (see `trace`): parsing,
+    styling, layout, building the display list, and painting, in this page and its
+    frames. `export_trace` writes it as a Chrome trace, with any events the embedder added
+    (`add_trace_event`) alongside, to see where a slow frame went in Perfetto.
+
+    Find-in-page searches the laid-out text (see `find`). The page remembers the search
+    and which match is current, and repeats the search whenever it lays out again, so the
+    highlights stay in the right places when the document or the viewport changes.
+
+    Golden tests (reftests, layout snapshots) need the same output on every run. In
+    deterministic mode (`set_deterministic`, on from the start with the `deterministic`
+    feature), images and frame documents load on the calling thread, so nothing depends
+    on which load finished first, and `wait_for_images` never has anything to wait for.
+    The feature also gives the engine's hash maps a fixed hasher (see `collections`), so
+    anything that walks one comes out in the same order. Scripts are deterministic
+    anyway: their clock is virtual and `Math.random` has a fixed seed. Text is measured
+    with the stand-in font rather than the machine's fonts (unless the embedder gave the
+    page its own with `set_fonts`), so there is no font matching to vary between machines.
+
+    e.g.
+        let mut page: Page = Page::load("<div class='a'></div>", ".a { display: block; height: 10px; }")?;
+        page.set_viewport(800, 600);
+        let png: Vec<u8> = page.screenshot();
+ */
+
+/// A loaded document, ready to lay out and paint.
+pub struct Page {
+    document: dom::Document,
+    stylesheet: css::Stylesheet,
+    viewport: (usize, usize), // width, height in device px
+    zoom: f32,                // the user's zoom level
+    device_scale: f32,        // device px per CSS px on the screen, e.g. 2 for HiDPI
+    viewport_meta: Option<viewport::ViewportMeta>, // what the page's `<meta name="viewport">` asked for
+    images: loader::ImageLoader,
+    display_list: Option<painting::DisplayList>, // in device px; cleared when layout is out of date
+    hit_regions: Vec<layout::HitRegion>,        // in CSS px; built along with the display list
+    document_size: (f32, f32),                  // ditto
+    canvas: Option<painting::Canvas>,           // cleared when painting is out of date
+    scroll: (f32, f32),                         // the document point at the viewport's top left, in CSS px
+    element_scroll: layout::ScrollOffsets,      // scroll positions of `overflow` boxes
+    states: style::ElementStates,               // elements matching `:hover` and friends
+    find_query: Option<String>,                 // what find-in-page is looking for
+    matches: Vec<find::Match>,                  // built along with the display list
+    current_match: Option<usize>,               // index into `matches`
+    documents: loader::DocumentLoader,          // loads the documents in `<iframe>`s
+    pending_frames: Vec<(dom::NodeId, String)>, // `<iframe>`s waiting for their documents
+    frames: HashMap<dom::NodeId, Page>,         // the page shown in each `<iframe>`
+    frame_depth: usize,                         // how many frames this page is nested in
+    followed_link: Option<String>,              // the `href` of a clicked link, until taken
+    deterministic: bool,                        // load subresources synchronously
+    fonts: Option<Arc<fonts::FontCollection>>,  // set by the embedder; see `fonts`
+    timeline: trace::Timeline,                  // the stages the page ran, for `export_trace`
+    html_errors: Vec<html::ParseError>,         // where the markup was broken, for fetched documents
+    #[cfg(feature = "js")]
+    scripts: script::ScriptHost,                // runs the page's JavaScript
+}
+
+/// The viewport size pages start with.
+pub const DEFAULT_VIEWPORT: (usize, usize) = (800, 600);
+
+/// The zoom levels pages can be set to: 25% to 500%.
+pub const ZOOM_RANGE: (f32, f32) = (0.25, 5.0);
+
+/// How deeply frames may nest. Deeper `<iframe>`s (e.g. a page that frames itself) stay empty.
+pub const MAX_FRAME_DEPTH: usize = 4;
+
+impl Page {
+    /// Create a page from HTML and CSS source. Any `<style>` elements in the HTML are
+    /// applied after `css`. Fails if either doesn't parse.
+    pub fn load(html: &str, css: &str) -> Result<Page, EngineError> {
+        let start: Option<Instant> = trace::now();
+        let document: dom::Document = html::parse(html)?;
+        let parse_html: Option<trace::TraceEvent> = stage_event("parse html", start);
+        let mut source: String = css.to_string();
+        source.push_str(&inline_css(&document));
+        let start: Option<Instant> = trace::now();
+        let stylesheet: css::Stylesheet = css::parse(&source)?;
+        let parse_css: Option<trace::TraceEvent> = stage_event("parse css", start);
+        let mut page: Page = Page::new(document, stylesheet, loader::ImageLoader::new());
+        page.timeline.extend(parse_html.into_iter().chain(parse_css));
+        Ok(page)
+    }
+
+    /// Fetch a page over HTTP, along with its `<style>` and `<link rel="stylesheet">` CSS.
+    /// Broken markup doesn't fail the load: the page shows what parsed, and `html_errors`
+    /// says what didn't. If the URL has a fragment, the page is scrolled to the element
+    /// it names (see `scroll_to_fragment`).
+    pub fn load_url(url: &str) -> Result<Page, EngineError> {
+        Page::load_url_with_client(url, Arc::new(Mutex::new(net::Client::new())))
+    }
+
+    /// Like `load_url`, but with a client shared with other pages (for its cookies and headers).
+    pub fn load_url_with_client(url: &str, client: Arc<Mutex<net::Client>>) -> Result<Page, EngineError> {
+        let url_string: &str = url;
+        let url: net::Url = net::Url::parse(url)?;
+        let start: Option<Instant> = trace::now();
+        let response: net::Response = lock(&client).get(&url.to_string())?;
+        let fetch: Option<trace::TraceEvent> = stage_event("fetch", start);
+        let start: Option<Instant> = trace::now();
+        let (source, _) = encoding::decode_html(&response.body, response.header("content-type"));
+        // The stylesheets load while the document is built; see "Stylesheets" in `loader`.
+        let (((mut document, html_errors), parse_html), stylesheet, parse_css) = loader::with_stylesheets(&source, &response.url, &client, || {
+            let parsed: (dom::Document, Vec<html::ParseError>) = html::parse_with_errors(&source);
+            (parsed, stage_event("parse html", start))
+        });
+        let stylesheet: css::Stylesheet = stylesheet?;
+        document.url = Some(response.url.to_string());
+        resolve_base_url(&mut document);
+        let mut page: Page = Page::new(document, stylesheet, loader::ImageLoader::with_client(client));
+        page.timeline.extend(fetch.into_iter().chain(parse_html).chain(parse_css));
+        page.html_errors = html_errors;
+        if let Some((_, fragment)) = split_fragment(url_string) {
+            page.scroll_to_fragment(fragment);
+        }
+        Ok(page)
+    }
+
+    /// Load a page from an `http://` URL, as `load_url` does, or from a file, styled by its
+    /// `<style>` elements.
+    pub fn open(url: &str) -> Result<Page, EngineError> {
+        load(url, &Arc::new(Mutex::new(net::Client::new())), DEFAULT_VIEWPORT)
+    }
+
+    fn new(document: dom::Document, stylesheet: css::Stylesheet, images: loader::ImageLoader) -> Page {
+        Page::new_frame(document, stylesheet, images, 0)
+    }
+
+    fn new_frame(mut document: dom::Document, stylesheet: css::Stylesheet, mut images: loader::ImageLoader, frame_depth: usize) -> Page {
+        resolve_base_url(&mut document);
+        images.set_base_url(document.base_url.as_deref());
+        let deterministic: bool = images.is_synchronous();
+        let mut documents: loader::DocumentLoader = loader::DocumentLoader::with_client(images.client());
+        documents.set_synchronous(deterministic);
+        let mut page: Page = Page {
+            document,
+            stylesheet,
+            viewport: DEFAULT_VIEWPORT,
+            zoom: 1.0,
+            device_scale: 1.0,
+            viewport_meta: None,
+            images,
+            display_list: None,
+            hit_regions: Vec::new(),
+            document_size: (0.0, 0.0),
+            canvas: None,
+            scroll: (0.0, 0.0),
+            element_scroll: layout::ScrollOffsets::default(),
+            states: style::ElementStates::default(),
+            find_query: None,
+            matches: Vec::new(),
+            current_match: None,
+            documents,
+            pending_frames: Vec::new(),
+            frames: HashMap::default(),
+            frame_depth,
+            followed_link: None,
+            deterministic,
+            fonts: None,
+            timeline: trace::Timeline::default(),
+            html_errors: Vec::new(),
+            #[cfg(feature = "js")]
+            scripts: script::ScriptHost::new(),
+        };
+        // Scripts may add images and frames, so they run first.
+        #[cfg(feature = "js")]
+        {
+            page.scripts.run_scripts(&mut page.document, &page.images.client());
+            // Nothing is laid out yet, so there is nothing to invalidate.
+            page.scripts.take_changes();
+        }
+        page.viewport_meta = viewport::ViewportMeta::from_document(&page.document);
+        page.images.request_images(&page.document.tree, page.document.root_element);
+        page.request_frames();
+        page
+    }
+
+
+    // Inputs
+
+    /// The page's document.
+    pub fn document(&self) -> &dom::Document {
+        &self.document
+    }
+
+    /// The errors in the markup of a fetched document (see `html::parse_with_errors`).
+    /// Empty for documents that parsed cleanly, and for pages made with `load`, which
+    /// fails on broken markup instead.
+    pub fn html_errors(&self) -> &[html::ParseError] {
+        &self.html_errors
+    }
+
+    /// The page's document, for making changes. The page is laid out again afterwards.
+    pub fn document_mut(&mut self) -> &mut dom::Document {
+        self.invalidate_layout();
+        &mut self.document
+    }
+
+    /// Replace the document, e.g. after its source changed, keeping the stylesheet, the
+    /// viewport, zoom, and scroll position, and any images that are still used. Hover
+    /// state, scoped stylesheets, and the scroll positions of elements are reset, since
+    /// their nodes are gone.
+    pub fn set_document(&mut self, mut document: dom::Document) {
+        resolve_base_url(&mut document);
+        self.images.set_base_url(document.base_url.as_deref());
+        self.images.request_images(&document.tree, document.root_element);
+        self.document = document;
+        self.states = style::ElementStates { media: self.states.media, ..style::ElementStates::default() };
+        self.element_scroll.clear();
+        for node in self.frames.keys() {
+            self.images.cache.remove(&layout::frame_key(*node));
+        }
+        self.frames.clear();
+        self.pending_frames.clear();
+        self.documents = loader::DocumentLoader::with_client(self.images.client()); // drop loads in flight
+        self.documents.set_synchronous(self.deterministic);
+        self.request_frames();
+        self.invalidate_layout();
+    }
+
+    /// The page's stylesheet.
+    pub fn stylesheet(&self) -> &css::Stylesheet {
+        &self.stylesheet
+    }
+
+    /// Replace the page's stylesheet.
+    pub fn set_stylesheet(&mut self, stylesheet: css::Stylesheet) {
+        self.stylesheet = stylesheet;
+        self.invalidate_layout();
+    }
+
+    /// Style the descendants of `node` with `stylesheet` alone, keeping the page's rules
+    /// out and its rules in, and replacing any stylesheet `node` already had. See "Scoped
+    /// Styles" in `style`.
+    pub fn attach_scoped_stylesheet(&mut self, node: dom::NodeId, stylesheet: css::Stylesheet) {
+        self.states.scopes.insert(node, stylesheet);
+        self.invalidate_layout();
+    }
+
+    /// Remove the stylesheet scoped to `node`, if it has one, and return it.
+    pub fn detach_scoped_stylesheet(&mut self, node: dom::NodeId) -> Option<css::Stylesheet> {
+        let stylesheet: Option<css::Stylesheet> = self.states.scopes.remove(&node);
+        if stylesheet.is_some() {
+            self.invalidate_layout();
+        }
+        stylesheet
+    }
+
+    /// The media the page is styled for: `Screen` unless `set_media` said otherwise.
+    pub fn media(&self) -> css::Media {
+        self.states.media
+    }
+
+    /// Style the page, and every frame in it, for `media`, so that only the `@media`
+    /// rules for it apply. The page is laid out again afterwards.
+    pub fn set_media(&mut self, media: css::Media) {
+        for frame in self.frames.values_mut() {
+            frame.set_media(media);
+        }
+        if media != self.states.media {
+            self.states.media = media;
+            self.invalidate_layout();
+        }
+    }
+
+    /// The viewport size, in device px.
+    pub fn viewport(&self) -> (usize, usize) {
+        self.viewport
+    }
+
+    /// Resize the viewport (in device px). Only a change of width affects layout; a change
+    /// of height just needs a new canvas.
+    pub fn set_viewport(&mut self, width: usize, height: usize) {
+        if width != self.viewport.0 {
+            self.invalidate_layout();
+        } else if height != self.viewport.1 {
+            self.canvas = None;
+        }
+        self.viewport = (width, height);
+    }
+
+    /// The zoom level: 1 is 100%.
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    /// Zoom the page, as far as `ZOOM_RANGE` allows. The page is laid out again afterwards.
+    pub fn set_zoom(&mut self, zoom: f32) {
+        let zoom: f32 = zoom.clamp(ZOOM_RANGE.0, ZOOM_RANGE.1);
+        if zoom != self.zoom {
+            self.zoom = zoom;
+            self.invalidate_layout();
+        }
+    }
+
+    /// The screen's device pixels per CSS px, as reported by the windowing system.
+    pub fn device_scale(&self) -> f32 {
+        self.device_scale
+    }
+
+    /// Set the screen's device pixels per CSS px. The page is laid out again afterwards.
+    pub fn set_device_scale(&mut self, device_scale: f32) {
+        if device_scale > 0.0 && device_scale != self.device_scale {
+            self.device_scale = device_scale;
+            self.invalidate_layout();
+        }
+    }
+
+    /// The fonts the page's text is laid out and drawn with: the ones set with `set_fonts`,
+    /// or else the system's, or none at all (so the stand-in font) in deterministic mode.
+    pub fn fonts(&self) -> Arc<fonts::FontCollection> {
+        match self.fonts {
+            Some(ref fonts) => fonts.clone(),
+            None if self.deterministic => Arc::new(fonts::FontCollection::new()),
+            None => fonts::FontCollection::system(),
+        }
+    }
+
+    /// Lay out and draw text with the faces in `fonts`, here and in every frame. The page
+    /// is laid out again afterwards.
+    pub fn set_fonts(&mut self, fonts: Arc<fonts::FontCollection>) {
+        for frame in self.frames.values_mut() {
+            frame.set_fonts(fonts.clone());
+        }
+        self.fonts = Some(fonts);
+        self.invalidate_layout();
+    }
+
+    /// What the page's `<meta name="viewport">` asked for, if it has one.
+    pub fn viewport_meta(&self) -> Option<&viewport::ViewportMeta> {
+        self.viewport_meta.as_ref()
+    }
+
+    /// The width the page is laid out at, in CSS px, and the scale it asked for with its
+    /// viewport meta tag (1 without one).
+    pub fn page_viewport(&self) -> (f32, f32) {
+        let device_width: f32 = self.viewport.0 as f32 / (self.zoom * self.device_scale);
+        match self.viewport_meta {
+            Some(ref meta) => meta.resolve(device_width),
+            None => (device_width, 1.0),
+        }
+    }
+
+    /// Device px per CSS px, counting zoom, the screen, and the page's own scale.
+    pub fn scale(&self) -> f32 {
+        self.zoom * self.device_scale * self.page_viewport().1
+    }
+
+    /// The viewport size in CSS px.
+    fn css_viewport(&self) -> (f32, f32) {
+        (self.viewport.0 as f32 / self.scale(), self.viewport.1 as f32 / self.scale())
+    }
+
+    fn invalidate_layout(&mut self) {
+        self.display_list = None;
+        self.canvas = None;
+    }
+
+    /// Return true if the page is in deterministic mode.
+    pub fn is_deterministic(&self) -> bool {
+        self.deterministic
+    }
+
+    /// Turn deterministic mode on or off, here and in every frame. Turning it on waits
+    /// for the loads already in flight, however long they take.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+        self.images.set_synchronous(deterministic);
+        self.documents.set_synchronous(deterministic);
+        for frame in self.frames.values_mut() {
+            frame.set_deterministic(deterministic);
+        }
+        if deterministic {
+            while self.is_loading_images() {
+                self.poll_images();
+                #[cfg(not(target_arch = "wasm32"))]
+                thread::sleep(Duration::from_millis(1));
+            }
+        }
+    }
+
+
+    // Images
+
+    /// Load an image again, e.g. because the file changed.
+    pub fn reload_image(&mut self, src: &str) {
+        self.images.reload(src);
+        self.invalidate_layout();
+    }
+
+    /// Pick up images and frame documents that finished loading (in this page or its
+    /// frames). Returns true if the page needs to be painted again (and possibly laid out
+    /// again) because of them.
+    pub fn poll_images(&mut self) -> bool {
+        let mut changed: bool = !self.images.poll().is_empty();
+        changed |= self.poll_frames();
+        if changed {
+            self.invalidate_layout();
+        }
+        changed
+    }
+
+    /// Return true if any of the page's images or frames are still loading.
+    pub fn is_loading_images(&self) -> bool {
+        self.images.is_loading() || self.documents.is_loading() || self.frames.values().any(Page::is_loading_images)
+    }
+
+    /// The images the page has loaded, for callers that run the pipeline stages themselves.
+    pub fn image_cache(&self) -> &loader::ImageCache {
+        &self.images.cache
+    }
+
+    /// Wait until every image has loaded (or failed), or until `timeout` has passed.
+    /// Returns true if nothing is still loading.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn wait_for_images(&mut self, timeout: Duration) -> bool {
+        let deadline: Instant = Instant::now() + timeout;
+        loop {
+            self.poll_images();
+            if !self.is_loading_images() {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    /// WebAssembly loads images on the calling thread, so they have all finished (or
+    /// failed) by now, and there is nothing to wait for.
+    #[cfg(target_arch = "wasm32")]
+    pub fn wait_for_images(&mut self, _timeout: Duration) -> bool {
+        self.poll_images();
+        !self.is_loading_images()
+    }
+
+
+    // Frames
+
+    /// The page shown in the `<iframe>` at `node`, once its document has loaded.
+    pub fn frame(&self, node: dom::NodeId) -> Option<&Page> {
+        self.frames.get(&node)
+    }
+
+    /// The page shown in the `<iframe>` at `node`, for making changes. The parent page is
+    /// laid out again afterwards.
+    pub fn frame_mut(&mut self, node: dom::NodeId) -> Option<&mut Page> {
+        self.invalidate_layout();
+        self.frames.get_mut(&node)
+    }
+
+    /// Start loading the document of every `<iframe>` with a `src`, resolved against the
+    /// document's base URL.
+    fn request_frames(&mut self) {
+        if self.frame_depth >= MAX_FRAME_DEPTH {
+            return;
+        }
+        let base: Option<net::Url> = self.document.base_url.as_deref().and_then(|base: &str| net::Url::parse(base).ok());
+        for node in self.document.get_elements_by_tag_name("iframe") {
+            let src: Option<String> = match self.document.tree[node].node_type {
+                dom::NodeType::Element(ref element) => element.attr("src").map(str::to_string),
+                _ => None,
+            };
+            let src: String = match (src, &base) {
+                (Some(src), Some(base)) => match base.join(&src) {
+                    Ok(url) => url.to_string(),
+                    Err(_) => continue,
+                },
+                (Some(src), None) => src,
+                (None, _) => continue,
+            };
+            self.documents.request(&src);
+            self.pending_frames.push((node, src));
+        }
+    }
+
+    /// Create pages for frame documents that finished loading, and pick up changes in the
+    /// frames' own pages. Returns true if anything changed.
+    fn poll_frames(&mut self) -> bool {
+        let mut changed: bool = false;
+        for (src, html) in self.documents.poll() {
+            let index: usize = match self.pending_frames.iter().position(|(_, pending): &(dom::NodeId, String)| *pending == src) {
+                Some(index) => index,
+                None => continue,
+            };
+            let (node, _) = self.pending_frames.remove(index);
+            // A frame whose document fails to load stays empty. One with broken markup
+            // shows what parsed.
+            let (mut document, html_errors) = match html {
+                Some(html) => html::parse_with_errors(&html),
+                None => continue,
+            };
+            if src.contains("://") {
+                document.url = Some(src);
+            }
+            let stylesheet: css::Stylesheet = match css::parse(&inline_css(&document)) {
+                Ok(stylesheet) => stylesheet,
+                Err(_) => continue,
+            };
+            let mut images: loader::ImageLoader = loader::ImageLoader::with_client(self.images.client());
+            images.set_synchronous(self.deterministic);
+            let mut frame: Page = Page::new_frame(document, stylesheet, images, self.frame_depth + 1);
+            frame.fonts = self.fonts.clone();
+            frame.states.media = self.states.media;
+            frame.html_errors = html_errors;
+            self.frames.insert(node, frame);
+            changed = true;
+        }
+        for frame in self.frames.values_mut() {
+            changed |= frame.poll_images();
+        }
+        changed
+    }
+
+    /// Lay out and paint each frame's page at the size of its `<iframe>` box, and put the
+    /// pixels in the image cache for the box to draw.
+    fn paint_frames(&mut self) {
+        let boxes: Vec<(dom::NodeId, layout::Rect)> = self.with_layout_tree(|root: &layout::LayoutBox| {
+            let mut boxes: Vec<(dom::NodeId, layout::Rect)> = Vec::new();
+            collect_frames(root, &mut boxes);
+            boxes
+        });
+        let scale: f32 = self.scale();
+        for (node, content) in boxes {
+            let frame: &mut Page = match self.frames.get_mut(&node) {
+                Some(frame) => frame,
+                None => continue,
+            };
+            frame.set_device_scale(scale);
+            frame.set_viewport(((content.width * scale).round() as usize).max(1), ((content.height * scale).round() as usize).max(1));
+            let canvas: &painting::Canvas = frame.paint();
+            let image: loader::Image = loader::Image { width: canvas.width as u32, height: canvas.height as u32, pixels: canvas.pixels.clone() };
+            self.images.cache.insert(layout::frame_key(node), loader::ImageState::Ready(Arc::new(image)));
+        }
+    }
+
+
+    // Scripts
+
+    /// Run JavaScript against the document, returning the value of its last expression
+    /// statement. If the script only changed classes or inline styles, just the elements
+    /// it restyled are repainted (unless the new styles need a new layout).
+    #[cfg(feature = "js")]
+    pub fn execute_script(&mut self, source: &str) -> Result<js::Value, js::ScriptError> {
+        let result: Result<js::Value, js::ScriptError> = self.scripts.eval(&mut self.document, source);
+        self.apply_script_changes();
+        result
+    }
+
+    /// Everything the page's scripts wrote to the console so far (and the errors they
+    /// threw), oldest first. Nothing is printed; showing them is up to the caller.
+    #[cfg(feature = "js")]
+    pub fn console_messages(&self) -> &[script::ConsoleMessage] {
+        self.scripts.console_messages()
+    }
+
+    /// Redo as little as possible after scripts changed the document. Returns true if
+    /// the page needs to be painted again.
+    #[cfg(feature = "js")]
+    fn apply_script_changes(&mut self) -> bool {
+        let changes: script::Changes = self.scripts.take_changes();
+        if changes.other {
+            self.images.request_images(&self.document.tree, self.document.root_element);
+            self.invalidate_layout();
+            return true;
+        }
+        match style::restyle_invalidation(&self.document.tree, &self.stylesheet, &self.states, &changes.restyled) {
+            layout::Invalidation::None => false,
+            layout::Invalidation::Repaint => {
+                let nodes: HashSet<dom::NodeId> = changes.restyled.into_keys().collect();
+                self.repaint_nodes(&nodes);
+                true
+            }
+            layout::Invalidation::Relayout => {
+                self.invalidate_layout();
+                true
+            }
+        }
+    }
+
+
+    /// Move the page's clock forward by `ms`, running the script timers and animation
+    /// frame callbacks due by then, in order, in the page and in its frames. Returns true
+    /// if the page needs to be painted again.
+    #[cfg(feature = "js")]
+    pub fn advance_time(&mut self, ms: f64) -> bool {
+        let mut changed: bool = false;
+        for frame in self.frames.values_mut() {
+            changed |= frame.advance_time(ms);
+        }
+        if changed {
+            self.invalidate_layout(); // to paint the frames again
+        }
+        let until: f64 = self.scripts.now() + ms.max(0.0);
+        self.scripts.run_tasks(&mut self.document, until);
+        changed | self.apply_script_changes()
+    }
+
+    /// How long until the next script timer or animation frame is due, in ms, or `None`
+    /// if nothing is scheduled.
+    #[cfg(feature = "js")]
+    pub fn next_task_delay(&self) -> Option<f64> {
+        let own: Option<f64> = self.scripts.next_deadline().map(|deadline: f64| (deadline - self.scripts.now()).max(0.0));
+        self.frames.values().filter_map(Page::next_task_delay).chain(own).min_by(f64::total_cmp)
+    }
+
+
+    // Automation
+
+    /// Click the first element matching `selector`, e.g. "#submit", as `click_node` does.
+    pub fn click(&mut self, selector: &str) -> Result<(), AutomationError> {
+        let node: dom::NodeId = self.find_element(selector)?;
+        self.click_node(node);
+        Ok(())
+    }
+
+    /// Focus the text field matching `selector` and type `text` at the end of its value,
+    /// like Puppeteer's `type`. With the `js` feature, scripts then get an `input` event.
+    pub fn type_into(&mut self, selector: &str, text: &str) -> Result<(), AutomationError> {
+        let node: dom::NodeId = self.find_element(selector)?;
+        if !forms::is_text_field(&self.document.tree, node) {
+            return Err(AutomationError::NotATextField(selector.to_string()));
+        }
+        self.set_focused_node(Some(node));
+        self.type_text(text);
+        #[cfg(feature = "js")]
+        {
+            let mut event: events::Event = events::Event::new("input");
+            event.bubbles = true;
+            self.scripts.dispatch_event(&mut self.document, node, &mut event);
+            self.apply_script_changes();
+        }
+        Ok(())
+    }
+
+    /// Wait until an element matches `selector`, and return the first one. Scripts are
+    /// what add elements, and they run on the page's clock, so this doesn't sleep: it
+    /// moves the clock from one timer or animation frame to the next (see `advance_time`),
+    /// for up to `timeout` of page time.
+    pub fn wait_for_selector(&mut self, selector: &str, timeout: Duration) -> Result<dom::NodeId, AutomationError> {
+        let selectors: Vec<css::Selector> = css::parse_selector_list(selector).map_err(AutomationError::InvalidSelector)?;
+        let mut remaining: f64 = timeout.as_secs_f64() * 1000.0;
+        loop {
+            if let Some(node) = self.query_selectors(&selectors) {
+                return Ok(node);
+            }
+            if !self.run_next_task_within(&mut remaining) {
+                return Err(AutomationError::Timeout(selector.to_string(), timeout));
+            }
+        }
+    }
+
+    /// Move the clock to the next timer or animation frame and run it, if one is due
+    /// within `remaining` ms, and take the time from `remaining`. Return false if none is.
+    #[cfg(feature = "js")]
+    fn run_next_task_within(&mut self, remaining: &mut f64) -> bool {
+        match self.next_task_delay().filter(|&delay: &f64| delay <= *remaining) {
+            Some(delay) => {
+                self.advance_time(delay);
+                *remaining -= delay;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Without scripts, nothing can change the page while we wait.
+    #[cfg(not(feature = "js"))]
+    fn run_next_task_within(&mut self, _remaining: &mut f64) -> bool {
+        false
+    }
+
+    /// Run JavaScript against the document and return the value of its last expression
+    /// statement, like `execute_script`, but with the error type of the other automation
+    /// methods.
+    #[cfg(feature = "js")]
+    pub fn evaluate(&mut self, source: &str) -> Result<js::Value, AutomationError> {
+        self.execute_script(source).map_err(AutomationError::Script)
+    }
+
+    /// The first element matching `selector`, or why there isn't one.
+    fn find_element(&self, selector: &str) -> Result<dom::NodeId, AutomationError> {
+        let selectors: Vec<css::Selector> = css::parse_selector_list(selector).map_err(AutomationError::InvalidSelector)?;
+        self.query_selectors(&selectors).ok_or_else(|| AutomationError::NoSuchElement(selector.to_string()))
+    }
+
+
+    // Outputs
+
+    /// Style the page, with its current `:hover` state, and pass the style tree to `f`.
+    pub fn with_style_tree<R>(&self, f: impl FnOnce(&style::StyledNode) -> R) -> R {
+        let style_root: style::StyledNode = style::style_tree_with_states(&self.document.tree, self.document.root_element, &self.stylesheet, &self.states);
+        f(&style_root)
+    }
+
+    /// The value of property `name` on `node`, as styled with the page's current `:hover`
+    /// state, or `None` if the node isn't rendered or the property isn't set.
+    pub fn computed_value(&self, node: dom::NodeId, name: &str) -> Option<css::Value> {
+        self.with_style_tree(|root: &style::StyledNode| root.find(node)?.value(name))
+    }
+
+    /// Style and lay out the page, and pass the layout tree to `f`. If the root element
+    /// has `display: none`, the tree is an empty box, and the page is blank.
+    pub fn with_layout_tree<R>(&self, f: impl FnOnce(&layout::LayoutBox) -> R) -> R {
+        self.build_layout_tree(&mut Vec::new(), |_: &style::StyledNode, root: &layout::LayoutBox| f(root))
+    }
+
+    /// Like `with_layout_tree`, passing the style tree too, and adding how long styling and
+    /// layout took to `events`.
+    fn build_layout_tree<R>(&self, events: &mut Vec<trace::TraceEvent>, f: impl FnOnce(&style::StyledNode, &layout::LayoutBox) -> R) -> R {
+        let start: Option<Instant> = trace::now();
+        let style_root: style::StyledNode = style::style_tree_with_states(&self.document.tree, self.document.root_element, &self.stylesheet, &self.states);
+        events.extend(stage_event("style", start));
+        let start: Option<Instant> = trace::now();
+        let mut viewport: layout::Dimensions = Default::default();
+        (viewport.content.width, viewport.content.height) = (self.page_viewport().0, self.css_viewport().1);
+        let arena: layout::Bump = layout::Bump::new();
+        let layout_root: layout::LayoutBox = layout::layout_tree(&style_root, viewport, &self.images.cache, &self.fonts(), &arena)
+            .unwrap_or_else(|_: layout::LayoutError| layout::LayoutBox::empty(&arena));
+        events.extend(stage_event("layout", start));
+        f(&style_root, &layout_root)
+    }
+
+    /// Estimate how much memory the page is using, and where. See `memory`.
+    pub fn memory_report(&self) -> MemoryReport {
+        MemoryReport {
+            dom: self.document.heap_size(),
+            stylesheet: self.stylesheet.heap_size(),
+            style_tree: self.with_style_tree(|root: &style::StyledNode| size_of::<style::StyledNode>() + root.heap_size()),
+            layout_tree: self.with_layout_tree(|root: &layout::LayoutBox| size_of::<layout::LayoutBox>() + root.heap_size()),
+            display_list: self.display_list.heap_size() + self.hit_regions.heap_size() + self.matches.heap_size(),
+            canvas: self.canvas.heap_size(),
+            images: self.images.cache.heap_size(),
+            frames: self.frames.values().map(|frame: &Page| frame.memory_report().total()).sum(),
+        }
+    }
+
+    /// Lay out the page (if it isn't already), returning the display list to paint, in
+    /// device px.
+    pub fn layout(&mut self) -> &painting::DisplayList {
+        if self.display_list.is_none() {
+            if !self.frames.is_empty() {
+                self.paint_frames();
+            }
+            let element_scroll: &layout::ScrollOffsets = &self.element_scroll;
+            let find_query: Option<&str> = self.find_query.as_deref();
+            let mut events: Vec<trace::TraceEvent> = Vec::new();
+            let (mut display_list, hit_regions, document_size, matches, display_event) = self.build_layout_tree(&mut events, |_: &style::StyledNode, root: &layout::LayoutBox| {
+                let start: Option<Instant> = trace::now();
+                let margin_box: layout::Rect = root.dimensions.margin_box();
+                let size: (f32, f32) = (margin_box.x + margin_box.width, margin_box.y + margin_box.height);
+                let matches: Vec<find::Match> = find_query
+                    .map(|query: &str| find::find_in_layout(root, element_scroll, query))
+                    .unwrap_or_default();
+                let display_list: painting::DisplayList = painting::build_scrolled_display_list(root, element_scroll);
+                (display_list, root.hit_regions(element_scroll), size, matches, stage_event("display list", start))
+            });
+            self.timeline.extend(events.into_iter().chain(display_event));
+            self.current_match = self.current_match.filter(|&i: &usize| i < matches.len());
+            display_list.extend(find::highlight_matches(&matches, self.current_match));
+            if let Some(focus) = self.states.focus {
+                let value: Option<String> = forms::is_text_field(&self.document.tree, focus)
+                    .then(|| forms::value(&self.document, focus));
+                if let Some(region) = hit_regions.iter().find(|region: &&layout::HitRegion| region.node == focus) {
+                    display_list.extend(forms::focus_decorations(region.border_box, value.as_deref()));
+                }
+            }
+            self.matches = matches;
+            if self.scale() != 1.0 {
+                display_list = painting::scale_display_list(&display_list, self.scale());
+            }
+            self.hit_regions = hit_regions;
+            self.document_size = document_size;
+            self.display_list = Some(display_list);
+            // The document may have shrunk since we last scrolled it.
+            let scroll: (f32, f32) = self.clamp_scroll(self.scroll.0, self.scroll.1);
+            if scroll != self.scroll {
+                self.scroll = scroll;
+                self.canvas = None;
+            }
+        }
+        self.display_list.as_ref().unwrap()
+    }
+
+    /// Lay out and paint the page (if it isn't already), returning the viewport's pixels.
+    pub fn paint(&mut self) -> &painting::Canvas {
+        if self.canvas.is_none() {
+            self.layout();
+            self.scroll = self.clamp_scroll(self.scroll.0, self.scroll.1); // the viewport may have grown
+            let (width, height) = self.viewport;
+            let (x, y) = self.device_scroll();
+            let bounds: layout::Rect = layout::Rect { x, y, width: width as f32, height: height as f32 };
+            let start: Option<Instant> = trace::now();
+            let canvas: painting::Canvas = painting::rasterize(self.display_list.as_ref().unwrap(), bounds);
+            self.timeline.extend(stage_event("paint", start));
+            self.canvas = Some(canvas);
+        }
+        self.canvas.as_ref().unwrap()
+    }
+
+    /// Paint the page and encode the viewport as a PNG image.
+    pub fn screenshot(&mut self) -> Vec<u8> {
+        self.paint().to_png()
+    }
+
+    /// The pipeline stages the page and its frames ran (and the events added with
+    /// `add_trace_event`), most recent last. See `trace::Timeline`.
+    pub fn trace_events(&self) -> Vec<&trace::TraceEvent> {
+        let mut events: Vec<&trace::TraceEvent> = self.timeline.events().collect();
+        for frame in self.frames.values() {
+            events.extend(frame.trace_events());
+        }
+        events.sort_by_key(|event: &&trace::TraceEvent| event.start);
+        events
+    }
+
+    /// Add an event of the embedder's own to the page's timeline, e.g. the time it spent
+    /// handling input or presenting a frame. Start times are from `trace::now`.
+    pub fn add_trace_event(&mut self, event: trace::TraceEvent) {
+        self.timeline.push(event);
+    }
+
+    /// Forget the events recorded so far, e.g. to trace just the next interaction.
+    pub fn clear_trace(&mut self) {
+        self.timeline.clear();
+        for frame in self.frames.values_mut() {
+            frame.clear_trace();
+        }
+    }
+
+    /// Write a report on how the page was rendered to an HTML file: a picture of the whole
+    /// document with its boxes outlined, and the DOM with each node's matched rules,
+    /// specified values, and boxes. See `debug::html_report`.
+    pub fn debug_report(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let report: String = self.build_layout_tree(&mut Vec::new(), |style_root: &style::StyledNode, layout_root: &layout::LayoutBox| {
+            debug::html_report(&self.document, &self.stylesheet, &self.states, style_root, layout_root)
+        });
+        fs::write(path, report)
+    }
+
+    /// Write `trace_events` to a file in the Chrome trace event format, for
+    /// chrome://tracing or Perfetto.
+    pub fn export_trace(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, trace::chrome_trace(self.trace_events()))
+    }
+
+    /// Open the page's main content in reader mode: a new page showing only the article,
+    /// styled with `readability::READER_CSS`, at the same viewport size and zoom. Returns
+    /// `None` if no part of the page looks like an article.
+    pub fn reader_mode(&self) -> Option<Page> {
+        let article: readability::Article = readability::extract(&self.document)?;
+        let mut page: Page = Page::new(article.document, css::parse(readability::READER_CSS).ok()?, loader::ImageLoader::new());
+        page.viewport = self.viewport;
+        page.zoom = self.zoom;
+        page.device_scale = self.device_scale;
+        Some(page)
+    }
+
+    /// Build the page's accessibility tree.
+    pub fn accessibility_tree(&self) -> accessibility::AccessibleNode {
+        self.with_layout_tree(|root: &layout::LayoutBox| accessibility::accessibility_tree(root, &self.element_scroll))
+    }
+
+    /// The topmost DOM node at a point in the viewport (in device px), if any.
+    pub fn node_at(&mut self, x: f32, y: f32) -> Option<dom::NodeId> {
+        self.layout();
+        let (x, y) = self.document_point(x, y);
+        layout::hit_test(&self.hit_regions, x, y)
+            .map(|region: &layout::HitRegion| region.node)
+    }
+
+
+    /// The first element in the document matching `selector`, e.g. "div.note, #main",
+    /// or `None` if nothing matches or the selector can't be parsed.
+    pub fn query_selector(&self, selector: &str) -> Option<dom::NodeId> {
+        self.query_selectors(&css::parse_selector_list(selector).ok()?)
+    }
+
+    /// Every element in the document matching `selector`, in document order. Empty if
+    /// nothing matches or the selector can't be parsed.
+    pub fn query_selector_all(&self, selector: &str) -> Vec<dom::NodeId> {
+        let selectors: Vec<css::Selector> = css::parse_selector_list(selector).unwrap_or_default();
+        self.document.tree.descendants(self.document.root_element)
+            .filter(|&node: &dom::NodeId| self.matches_any(node, &selectors))
+            .collect()
+    }
+
+    /// The first element in the document matching any of `selectors`.
+    fn query_selectors(&self, selectors: &[css::Selector]) -> Option<dom::NodeId> {
+        self.document.tree.descendants(self.document.root_element)
+            .find(|&node: &dom::NodeId| self.matches_any(node, selectors))
+    }
+
+    /// Is `node` an element matching any of `selectors`?
+    fn matches_any(&self, node: dom::NodeId, selectors: &[css::Selector]) -> bool {
+        match self.document.tree[node].node_type {
+            dom::NodeType::Element(ref element) => selectors.iter()
+                .any(|selector: &css::Selector| style::matches(element, node, selector, &self.states)),
+            _ => false,
+        }
+    }
+
+
+    // Hovering
+
+    /// The element the mouse is over, if any.
+    pub fn hovered_node(&self) -> Option<dom::NodeId> {
+        // The hovered node is the only one in the chain with no hovered children.
+        self.states.hover.iter().copied().find(|&node: &dom::NodeId| {
+            self.document.tree.children(node).all(|child: dom::NodeId| !self.states.hover.contains(&child))
+        })
+    }
+
+    /// Move the mouse over `node` (and its ancestors), or off the page. Returns true if
+    /// the page needs to be painted again because of `:hover` rules.
+    pub fn set_hovered_node(&mut self, node: Option<dom::NodeId>) -> bool {
+        let chain: HashSet<dom::NodeId> = match node {
+            Some(node) => style::hover_chain(&self.document.tree, node),
+            None => HashSet::default(),
+        };
+        let changed: HashSet<dom::NodeId> = chain.symmetric_difference(&self.states.hover).copied().collect();
+        if changed.is_empty() {
+            return false;
+        }
+        self.states.hover = chain;
+
+        match style::state_change_invalidation(&self.document.tree, &self.stylesheet, &self.states, &changed) {
+            layout::Invalidation::None => false,
+            layout::Invalidation::Repaint => {
+                self.repaint_nodes(&changed);
+                true
+            }
+            layout::Invalidation::Relayout => {
+                self.invalidate_layout();
+                true
+            }
+        }
+    }
+
+    /// Rebuild the display list after a paint-only style change to `nodes`. Boxes stay
+    /// where they are, so only the canvas under the changed ones is painted over.
+    fn repaint_nodes(&mut self, nodes: &HashSet<dom::NodeId>) {
+        self.display_list = None;
+        self.layout();
+        let (scroll, scale) = (self.scroll, self.scale());
+        let origin: (f32, f32) = self.device_scroll();
+        if let (Some(canvas), Some(display_list)) = (self.canvas.as_mut(), self.display_list.as_ref()) {
+            for region in &self.hit_regions {
+                if nodes.contains(&region.node) {
+                    let dirty: layout::Rect = region.border_box.translated(-scroll.0, -scroll.1).scaled(scale);
+                    canvas.paint_region(display_list, origin, dirty);
+                }
+            }
+        }
+    }
+
+    /// Move the mouse to a point in the viewport (in device px). Returns true if the page needs to be
+    /// painted again.
+    pub fn hover_at(&mut self, x: f32, y: f32) -> bool {
+        let node: Option<dom::NodeId> = self.node_at(x, y);
+        self.set_hovered_node(node)
+    }
+
+
+    // Forms
+
+    /// The element with keyboard focus, if any.
+    pub fn focused_node(&self) -> Option<dom::NodeId> {
+        self.states.focus
+    }
+
+    /// Give `node` keyboard focus (or take it away from everything). The focused element
+    /// matches `:focus`, and is drawn with a focus ring (and a caret, for text fields).
+    pub fn set_focused_node(&mut self, node: Option<dom::NodeId>) {
+        if node != self.states.focus {
+            self.states.focus = node;
+            self.invalidate_layout();
+        }
+    }
+
+    /// Click at a point in the viewport (in device px), as `click_node` does for the node
+    /// there. Clicking where there is no node takes the focus away. Returns true if the
+    /// page needs to be painted again.
+    pub fn click_at(&mut self, x: f32, y: f32) -> bool {
+        match self.node_at(x, y) {
+            Some(node) => self.click_node(node),
+            None => {
+                let changed: bool = self.states.focus.is_some();
+                self.set_focused_node(None);
+                changed
+            }
+        }
+    }
+
+    /// Click `node`: focus the form control it is in (or nothing, if there isn't one),
+    /// dispatch a `click` event to scripts, and then, unless a listener canceled it, tick
+    /// or untick a checkbox or radio button, or follow a link. Returns true if the page
+    /// needs to be painted again.
+    pub fn click_node(&mut self, node: dom::NodeId) -> bool {
+        let control: Option<dom::NodeId> = forms::control_element(&self.document.tree, node);
+        let mut changed: bool = control != self.states.focus;
+        self.set_focused_node(control);
+
+        #[cfg(feature = "js")]
+        {
+            let mut event: events::Event = events::Event::bubbling("click");
+            let proceed: bool = self.scripts.dispatch_event(&mut self.document, node, &mut event);
+            changed |= self.apply_script_changes();
+            if !proceed {
+                return changed;
+            }
+        }
+
+        if let Some(control) = control.filter(|&control: &dom::NodeId| forms::is_checkable(&self.document.tree, control)) {
+            if forms::toggle_checked(&mut self.document, control) {
+                self.invalidate_layout();
+                changed = true;
+            }
+        }
+        let link: Option<&str> = self.document.tree.ancestors(node).find_map(|ancestor: dom::NodeId| match self.document.tree[ancestor].node_type {
+            dom::NodeType::Element(ref element) if element.tag_name == "a" => element.attr("href"),
+            _ => None,
+        });
+        if let Some(href) = link {
+            self.followed_link = Some(self.resolve_url(href));
+        }
+        changed
+    }
+
+    /// Resolve `url` against the document's base URL, if it has one (see "The Document"
+    /// in `dom`), the way `Session` resolves the links it follows: the fragment is set
+    /// aside while the rest is resolved, and a URL with a scheme of its own (`mailto:`,
+    /// `javascript:`, `data:`) is left as it is.
+    pub fn resolve_url(&self, url: &str) -> String {
+        match self.document.base_url {
+            Some(ref base) => resolve(base, url).unwrap_or_else(|_| url.to_string()),
+            None => url.to_string(),
+        }
+    }
+
+    /// Return the `href` of the link the user last clicked, and forget it. The page
+    /// doesn't navigate by itself; `Session::click_at` does.
+    pub fn take_followed_link(&mut self) -> Option<String> {
+        self.followed_link.take()
+    }
+
+    /// Type `text` at the end of the focused text field. Newlines are only kept in a
+    /// `<textarea>`. Returns true if anything changed.
+    pub fn type_text(&mut self, text: &str) -> bool {
+        let focus: dom::NodeId = match self.focused_text_field() {
+            Some(focus) => focus,
+            None => return false,
+        };
+        let multiline: bool = matches!(self.document.tree[focus].node_type,
+            dom::NodeType::Element(ref element) if element.tag_name == "textarea");
+        let text: String = text.chars().filter(|&c: &char| c == '\n' && multiline || !c.is_control()).collect();
+        if text.is_empty() {
+            return false;
+        }
+        let mut value: String = forms::value(&self.document, focus);
+        value.push_str(&text);
+        forms::set_value(self.document_mut(), focus, &value);
+        true
+    }
+
+    /// Delete the last character of the focused text field, as Backspace does. Returns
+    /// true if anything changed.
+    pub fn delete_backward(&mut self) -> bool {
+        let focus: dom::NodeId = match self.focused_text_field() {
+            Some(focus) => focus,
+            None => return false,
+        };
+        let mut value: String = forms::value(&self.document, focus);
+        if value.pop().is_none() {
+            return false;
+        }
+        forms::set_value(self.document_mut(), focus, &value);
+        true
+    }
+
+    fn focused_text_field(&self) -> Option<dom::NodeId> {
+        self.states.focus.filter(|&focus: &dom::NodeId| forms::is_text_field(&self.document.tree, focus))
+    }
+
+
+    // Finding
+
+    /// Search the page's rendered text for `text`, highlight the matches, and scroll the
+    /// first one into view.
+    pub fn find(&mut self, text: &str) -> &[find::Match] {
+        self.find_query = Some(text.to_string());
+        self.current_match = Some(0);
+        self.invalidate_layout();
+        self.layout();
+        self.scroll_to_current_match();
+        &self.matches
+    }
+
+    /// The matches for the current search, in document order.
+    pub fn find_matches(&self) -> &[find::Match] {
+        &self.matches
+    }
+
+    /// The selected match, if there is one.
+    pub fn current_match(&self) -> Option<&find::Match> {
+        self.current_match.map(|i: usize| &self.matches[i])
+    }
+
+    /// Select the next match (wrapping around to the first), and scroll it into view.
+    pub fn find_next(&mut self) -> Option<&find::Match> {
+        let count: usize = self.matches.len();
+        let next: usize = self.current_match.map_or(0, |i: usize| i + 1);
+        self.select_match((count > 0).then(|| next % count))
+    }
+
+    /// Select the previous match (wrapping around to the last), and scroll it into view.
+    pub fn find_previous(&mut self) -> Option<&find::Match> {
+        let count: usize = self.matches.len();
+        let previous: usize = self.current_match.map_or(0, |i: usize| i + count - 1);
+        self.select_match((count > 0).then(|| previous % count))
+    }
+
+    /// Stop searching, and remove the highlights.
+    pub fn clear_find(&mut self) {
+        if self.find_query.take().is_some() {
+            self.current_match = None;
+            self.invalidate_layout();
+        }
+    }
+
+    fn select_match(&mut self, index: Option<usize>) -> Option<&find::Match> {
+        if index != self.current_match {
+            self.current_match = index;
+            self.invalidate_layout();
+            self.layout();
+        }
+        self.scroll_to_current_match();
+        self.current_match()
+    }
+
+    fn scroll_to_current_match(&mut self) {
+        if let Some(rect) = self.current_match().map(|m: &find::Match| m.rect) {
+            self.scroll_into_view(rect);
+        }
+    }
+
+    /// Scroll the document so `rect` (in document CSS px) is visible, centering it along
+    /// any axis where it is out of view. A rect larger than the viewport shows its top left.
+    pub fn scroll_into_view(&mut self, rect: layout::Rect) {
+        self.layout();
+        let (width, height) = self.css_viewport();
+        let reveal = |scroll: f32, size: f32, start: f32, length: f32| -> f32 {
+            if start >= scroll && start + length <= scroll + size {
+                scroll
+            } else if length > size {
+                start
+            } else {
+                start - (size - length) / 2.0
+            }
+        };
+        let x: f32 = reveal(self.scroll.0, width, rect.x, rect.width);
+        let y: f32 = reveal(self.scroll.1, height, rect.y, rect.height);
+        self.scroll_to(x, y);
+    }
+
+
+    // Scrolling
+
+    /// The document point shown at the viewport's top left corner, in CSS px.
+    pub fn scroll_position(&self) -> (f32, f32) {
+        self.scroll
+    }
+
+    /// How far the document can be scrolled right and down, in CSS px.
+    pub fn max_scroll(&mut self) -> (f32, f32) {
+        self.layout();
+        let (width, height) = self.css_viewport();
+        ((self.document_size.0 - width).max(0.0), (self.document_size.1 - height).max(0.0))
+    }
+
+    /// Scroll the document so (x, y) is at the viewport's top left, as far as it can go.
+    /// Only the part of the viewport that comes into view is painted again.
+    pub fn scroll_to(&mut self, x: f32, y: f32) {
+        self.layout();
+        let before: (f32, f32) = self.device_scroll();
+        self.scroll = self.clamp_scroll(x, y);
+        let origin: (f32, f32) = self.device_scroll();
+        let (dx, dy) = ((origin.0 - before.0).round(), (origin.1 - before.1).round());
+        if let (Some(canvas), Some(display_list)) = (self.canvas.as_mut(), self.display_list.as_ref()) {
+            for region in canvas.scroll(dx as isize, dy as isize) {
+                canvas.paint_region(display_list, origin, region);
+            }
+        }
+    }
+
+    /// Scroll the document by (dx, dy) px.
+    pub fn scroll_by(&mut self, dx: f32, dy: f32) {
+        self.scroll_to(self.scroll.0 + dx, self.scroll.1 + dy);
+    }
+
+    /// Where `node` was laid out: the border box of its first box, in document CSS px, or
+    /// `None` if it isn't rendered.
+    pub fn element_rect(&mut self, node: dom::NodeId) -> Option<layout::Rect> {
+        self.layout();
+        self.hit_regions.iter().find(|region: &&layout::HitRegion| region.node == node).map(|region: &layout::HitRegion| region.border_box)
+    }
+
+    /// Scroll the document so `node`'s top is at the top of the viewport (or as near as
+    /// the document's height allows), like `element.scrollIntoView()`. It scrolls
+    /// sideways only if the node's left edge is out of view. Returns false, and doesn't
+    /// scroll, if the node isn't rendered.
+    pub fn scroll_node_into_view(&mut self, node: dom::NodeId) -> bool {
+        let rect: layout::Rect = match self.element_rect(node) {
+            Some(rect) => rect,
+            None => return false,
+        };
+        let width: f32 = self.css_viewport().0;
+        let x: f32 = if rect.x >= self.scroll.0 && rect.x < self.scroll.0 + width { self.scroll.0 } else { rect.x };
+        self.scroll_to(x, rect.y);
+        true
+    }
+
+    /// Scroll the first element matching `selector` into view, as `scroll_node_into_view`
+    /// does.
+    pub fn scroll_to_selector(&mut self, selector: &str) -> Result<(), AutomationError> {
+        let node: dom::NodeId = self.find_element(selector)?;
+        self.scroll_node_into_view(node);
+        Ok(())
+    }
+
+    /// Scroll to the element a URL's fragment (the part after `#`) names: the element with
+    /// that id, or else the `<a>` with that `name`. The empty fragment and `top` scroll
+    /// to the top of the document. Returns false, and doesn't scroll, if the fragment
+    /// names nothing that is rendered.
+    pub fn scroll_to_fragment(&mut self, fragment: &str) -> bool {
+        let target: Option<dom::NodeId> = self.document.get_element_by_id(fragment).or_else(|| {
+            self.document.get_elements_by_tag_name("a").into_iter().find(|&node: &dom::NodeId| {
+                matches!(self.document.tree[node].node_type, dom::NodeType::Element(ref element) if element.attr("name") == Some(fragment))
+            })
+        });
+        match target {
+            Some(node) => self.scroll_node_into_view(node),
+            None if fragment.is_empty() || fragment.eq_ignore_ascii_case("top") => {
+                self.scroll_to(0.0, 0.0);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The scroll position of an `overflow: auto` or `overflow: scroll` element.
+    pub fn element_scroll_position(&self, node: dom::NodeId) -> (f32, f32) {
+        self.element_scroll.get(&node).copied().unwrap_or((0.0, 0.0))
+    }
+
+    /// Scroll an `overflow: auto` or `overflow: scroll` element, as far as it can go.
+    /// Does nothing for other elements.
+    pub fn set_element_scroll_position(&mut self, node: dom::NodeId, x: f32, y: f32) {
+        self.layout();
+        let max_scroll: (f32, f32) = match self.hit_regions.iter().find(|region: &&layout::HitRegion| region.node == node) {
+            Some(&layout::HitRegion { max_scroll: Some(max_scroll), .. }) => max_scroll,
+            _ => return,
+        };
+        let scroll: (f32, f32) = (x.clamp(0.0, max_scroll.0).round(), y.clamp(0.0, max_scroll.1).round());
+        if scroll != self.element_scroll_position(node) {
+            self.element_scroll.insert(node, scroll);
+            self.invalidate_layout();
+        }
+    }
+
+    /// Scroll whatever is under a point in the viewport (in device px) by (dx, dy) CSS px,
+    /// like a mouse wheel: the innermost scrollable element that can still move that way,
+    /// or else the document. Returns true if anything scrolled.
+    pub fn scroll_at(&mut self, x: f32, y: f32, dx: f32, dy: f32) -> bool {
+        self.layout();
+        let (x, y) = self.document_point(x, y);
+        let mut target: Option<dom::NodeId> = None;
+        for region in self.hit_regions.iter().rev() {
+            let max_scroll: (f32, f32) = match region.max_scroll {
+                Some(max_scroll) if region.contains(x, y) => max_scroll,
+                _ => continue,
+            };
+            let (sx, sy) = self.element_scroll_position(region.node);
+            let (new_x, new_y) = ((sx + dx).clamp(0.0, max_scroll.0), (sy + dy).clamp(0.0, max_scroll.1));
+            if (new_x - sx).abs() >= 1.0 || (new_y - sy).abs() >= 1.0 {
+                target = Some(region.node);
+                break;
+            }
+        }
+
+        match target {
+            Some(node) => {
+                let (sx, sy) = self.element_scroll_position(node);
+                self.set_element_scroll_position(node, sx + dx, sy + dy);
+                true
+            }
+            None => {
+                let before: (f32, f32) = self.scroll;
+                self.scroll_by(dx, dy);
+                self.scroll != before
+            }
+        }
+    }
+
+    /// Clamp a document scroll position to the scrollable range, rounded to whole device px.
+    fn clamp_scroll(&self, x: f32, y: f32) -> (f32, f32) {
+        let (width, height) = self.css_viewport();
+        let max_x: f32 = (self.document_size.0 - width).max(0.0);
+        let max_y: f32 = (self.document_size.1 - height).max(0.0);
+        let scale: f32 = self.scale();
+        ((x.clamp(0.0, max_x) * scale).round() / scale, (y.clamp(0.0, max_y) * scale).round() / scale)
+    }
+
+    /// The scroll position in device px: where the canvas's top left is in the display list.
+    fn device_scroll(&self) -> (f32, f32) {
+        ((self.scroll.0 * self.scale()).round(), (self.scroll.1 * self.scale()).round())
+    }
+
+    /// Convert a point in the viewport (in device px) to a document point (in CSS px).
+    fn document_point(&self, x: f32, y: f32) -> (f32, f32) {
+        (x / self.scale() + self.scroll.0, y / self.scale() + self.scroll.1)
+    }
+}
+
+/// Why a step of `Page`'s automation API failed.
+#[derive(Debug)]
+pub enum AutomationError {
+    /// The selector couldn't be parsed (the message says why).
+    InvalidSelector(String),
+    /// Nothing matched the selector.
+    NoSuchElement(String),
+    /// The element matching the selector can't be typed into.
+    NotATextField(String),
+    /// Nothing matched the selector before the timeout.
+    Timeout(String, Duration),
+    /// A script threw an exception.
+    #[cfg(feature = "js")]
+    Script(js::ScriptError),
+}
+
+impl fmt::Display for AutomationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AutomationError::InvalidSelector(message) => write!(f, "{}", message),
+            AutomationError::NoSuchElement(selector) => write!(f, "no element matches {:?}", selector),
+            AutomationError::NotATextField(selector) => write!(f, "{:?} is not a text field", selector),
+            AutomationError::Timeout(selector, timeout) => write!(f, "no element matched {:?} within {:?}", selector, timeout),
+            #[cfg(feature = "js")]
+            AutomationError::Script(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for AutomationError {}
+
+
+/*
+    Sessions
+
+    A browser shell shows several tabs, each with a history it can go back and forward
+    through. A `Session` holds the tabs, and the HTTP client they share (so a cookie set
+    in one tab is sent from the others). Each `Tab` keeps a list of history entries: the
+    URL, and the page loaded from it.
+
+    Going back keeps the page that was showing, so going forward again is instant and
+    lands at the same scroll position, with the images it already loaded: each page keeps
+    its own resource caches. Only the pages of the few entries nearest the current one are
+    kept (see `MAX_CACHED_PAGES`); the others are loaded again if the user goes back to
+    them. `reload` always loads the current entry again from scratch.
+
+    URLs without a scheme are paths on the local file system, like image sources. A URL
+    given to `navigate` is resolved against the current one, so links can be followed
+    with their `href` as it is; `click_at` does that for the link under the mouse.
+
+    A URL's fragment names a place in the document: a page loaded from `guide.html#usage`
+    is scrolled so the element with the id `usage` is at the top of the viewport (see
+    `Page::scroll_to_fragment`). Following a link to another fragment of the document
+    that's showing, like `#install`, just scrolls: the new history entry takes over the
+    page, and the entry it came from loads it again if the user goes back.
+
+    e.g.
+        let mut session: Session = Session::new();
+        session.open_tab("http://example.com/")?;
+        session.navigate("/about")?;   // http://example.com/about
+        session.back()?;               // http://example.com/, as it was left
+ */
+
+/// How many pages each tab keeps besides the current one, for going back and forward.
+pub const MAX_CACHED_PAGES: usize = 4;
+
+/// A set of tabs sharing one HTTP client.
+pub struct Session {
+    client: Arc<Mutex<net::Client>>,
+    tabs: Vec<Tab>,
+    active: usize, // index into `tabs`; meaningless while there are none
+    viewport: (usize, usize),
+}
+
+/// One tab: a history of pages, one of which is showing.
+pub struct Tab {
+    entries: Vec<HistoryEntry>,
+    current: usize, // index into `entries`
+}
+
+/// One page a tab has visited.
+struct HistoryEntry {
+    url: String,
+    page: Option<Page>, // `None` once the page was dropped to save memory
+}
+
+impl Default for Session {
+    fn default() -> Session {
+        Session::new()
+    }
+}
+
+impl Session {
+    /// Create a session with no tabs and a new HTTP client.
+    pub fn new() -> Session {
+        Session::with_client(Arc::new(Mutex::new(net::Client::new())))
+    }
+
+    /// Create a session with no tabs, fetching with `client`.
+    pub fn with_client(client: Arc<Mutex<net::Client>>) -> Session {
+        Session { client, tabs: Vec::new(), active: 0, viewport: DEFAULT_VIEWPORT }
+    }
+
+    /// The HTTP client the session's pages are fetched with.
+    pub fn client(&self) -> Arc<Mutex<net::Client>> {
+        self.client.clone()
+    }
+
+    /// Load `url` in a new tab after the others, and make it the active tab. Returns the
+    /// new tab's index.
+    pub fn open_tab(&mut self, url: &str) -> Result<usize, EngineError> {
+        let page: Page = load(url, &self.client, self.viewport)?;
+        self.tabs.push(Tab { entries: vec![HistoryEntry { url: url.to_string(), page: Some(page) }], current: 0 });
+        self.active = self.tabs.len() - 1;
+        Ok(self.active)
+    }
+
+    /// Close tab `index`. The next tab becomes active if it was active (or the previous
+    /// one, if it was the last). Panics if there is no such tab.
+    pub fn close_tab(&mut self, index: usize) {
+        self.tabs.remove(index);
+        if index < self.active || self.active == self.tabs.len() {
+            self.active = self.active.saturating_sub(1);
+        }
+    }
+
+    /// The open tabs, in order.
+    pub fn tabs(&self) -> &[Tab] {
+        &self.tabs
+    }
+
+    /// The index of the active tab, if any tabs are open.
+    pub fn active_tab(&self) -> Option<usize> {
+        (!self.tabs.is_empty()).then_some(self.active)
+    }
+
+    /// Switch to tab `index`. Panics if there is no such tab.
+    pub fn set_active_tab(&mut self, index: usize) {
+        assert!(index < self.tabs.len(), "No tab {}", index);
+        self.active = index;
+    }
+
+    /// The page showing in the active tab.
+    pub fn page(&self) -> Option<&Page> {
+        self.tabs.get(self.active).and_then(Tab::page)
+    }
+
+    /// The page showing in the active tab, for making changes.
+    pub fn page_mut(&mut self) -> Option<&mut Page> {
+        self.tabs.get_mut(self.active).and_then(Tab::page_mut)
+    }
+
+    /// Resize the viewport of every page in the session.
+    pub fn set_viewport(&mut self, width: usize, height: usize) {
+        self.viewport = (width, height);
+        for entry in self.tabs.iter_mut().flat_map(|tab: &mut Tab| tab.entries.iter_mut()) {
+            if let Some(ref mut page) = entry.page {
+                page.set_viewport(width, height);
+            }
+        }
+    }
+
+    /// Click at a point in the active tab's viewport (in device px), and navigate to the
+    /// link there, if the click followed one. Returns true if the tab needs to be painted
+    /// again.
+    pub fn click_at(&mut self, x: f32, y: f32) -> Result<bool, EngineError> {
+        let page: &mut Page = match self.page_mut() {
+            Some(page) => page,
+            None => return Ok(false),
+        };
+        let changed: bool = page.click_at(x, y);
+        match page.take_followed_link() {
+            Some(href) => self.navigate(&href).map(|_| true),
+            None => Ok(changed),
+        }
+    }
+
+    /// Load `url` (resolved against the current URL) in the active tab, or in a new tab if
+    /// none are open. Entries after the current one are dropped from the history.
+    pub fn navigate(&mut self, url: &str) -> Result<(), EngineError> {
+        let tab: &mut Tab = match self.tabs.get_mut(self.active) {
+            Some(tab) => tab,
+            None => return self.open_tab(url).map(|_| ()),
+        };
+        let url: String = resolve(tab.url(), url)?;
+        let current: &str = split_fragment(tab.url()).map_or(tab.url(), |(document, _)| document);
+        let page: Page = match split_fragment(&url) {
+            // A link to another part of the same document scrolls, without loading it again.
+            Some((document, fragment)) if document == current => {
+                let mut page: Page = match tab.entries[tab.current].page.take() {
+                    Some(page) => page,
+                    None => load(&url, &self.client, self.viewport)?,
+                };
+                page.scroll_to_fragment(fragment);
+                page
+            }
+            _ => load(&url, &self.client, self.viewport)?,
+        };
+        tab.entries.truncate(tab.current + 1);
+        tab.entries.push(HistoryEntry { url, page: Some(page) });
+        tab.current += 1;
+        tab.evict_pages();
+        Ok(())
+    }
+
+    /// Go back one entry in the active tab's history. Returns false if there was nothing
+    /// to go back to.
+    pub fn back(&mut self) -> Result<bool, EngineError> {
+        self.go(-1)
+    }
+
+    /// Go forward one entry in the active tab's history. Returns false if there was
+    /// nothing to go forward to.
+    pub fn forward(&mut self) -> Result<bool, EngineError> {
+        self.go(1)
+    }
+
+    /// Load the active tab's current entry again. Returns false if no tabs are open.
+    pub fn reload(&mut self) -> Result<bool, EngineError> {
+        let tab: &mut Tab = match self.tabs.get_mut(self.active) {
+            Some(tab) => tab,
+            None => return Ok(false),
+        };
+        let page: Page = load(tab.url(), &self.client, self.viewport)?;
+        tab.entries[tab.current].page = Some(page);
+        Ok(true)
+    }
+
+    /// Move `delta` entries through the active tab's history, loading the page there if it
+    /// isn't kept. If loading fails, the tab stays where it was.
+    fn go(&mut self, delta: isize) -> Result<bool, EngineError> {
+        let tab: &mut Tab = match self.tabs.get_mut(self.active) {
+            Some(tab) => tab,
+            None => return Ok(false),
+        };
+        let target: usize = match tab.current.checked_add_signed(delta).filter(|&i: &usize| i < tab.entries.len()) {
+            Some(target) => target,
+            None => return Ok(false),
+        };
+        if tab.entries[target].page.is_none() {
+            let page: Page = load(&tab.entries[target].url, &self.client, self.viewport)?;
+            tab.entries[target].page = Some(page);
+        }
+        tab.current = target;
+        tab.evict_pages();
+        Ok(true)
+    }
+}
+
+impl Tab {
+    /// The URL of the page showing.
+    pub fn url(&self) -> &str {
+        &self.entries[self.current].url
+    }
+
+    /// The page showing.
+    pub fn page(&self) -> Option<&Page> {
+        self.entries[self.current].page.as_ref()
+    }
+
+    /// The page showing, for making changes.
+    pub fn page_mut(&mut self) -> Option<&mut Page> {
+        self.entries[self.current].page.as_mut()
+    }
+
+    /// The URLs in the tab's history, oldest first, and the index of the one showing.
+    pub fn history(&self) -> (Vec<&str>, usize) {
+        (self.entries.iter().map(|entry: &HistoryEntry| entry.url.as_str()).collect(), self.current)
+    }
+
+    /// Return true if there is an entry to go back to.
+    pub fn can_go_back(&self) -> bool {
+        self.current > 0
+    }
+
+    /// Return true if there is an entry to go forward to.
+    pub fn can_go_forward(&self) -> bool {
+        self.current + 1 < self.entries.len()
+    }
+
+    /// Drop the pages of all but the `MAX_CACHED_PAGES` entries nearest the current one.
+    fn evict_pages(&mut self) {
+        let mut kept: Vec<usize> = (0..self.entries.len())
+            .filter(|&i: &usize| i != self.current && self.entries[i].page.is_some())
+            .collect();
+        kept.sort_by_key(|&i: &usize| i.abs_diff(self.current));
+        for i in kept.into_iter().skip(MAX_CACHED_PAGES) {
+            self.entries[i].page = None;
+        }
+    }
+}
+
+/// Resolve `url` against the URL of the page it was found on. Local paths are resolved
+/// against the directory of a local base, and a bare fragment (`#intro`) against the
+/// base itself. A URL with a scheme is already absolute.
+fn resolve(base: &str, url: &str) -> Result<String, net::LoadError> {
+    if net::scheme(url).is_some() {
+        return Ok(url.to_string());
+    }
+    if let Some(fragment) = url.strip_prefix('#') {
+        let document: &str = split_fragment(base).map_or(base, |(document, _)| document);
+        return Ok(format!("{}#{}", document, fragment));
+    }
+    if let Some((document, fragment)) = split_fragment(url) {
+        return Ok(format!("{}#{}", resolve(base, document)?, fragment));
+    }
+    if base.contains("://") {
+        return Ok(net::Url::parse(base)?.join(url)?.to_string());
+    }
+    let base: &Path = Path::new(base).parent().unwrap_or(Path::new(""));
+    Ok(base.join(url).to_string_lossy().into_owned())
+}
+
+/// An event for a stage of the pipeline that started at `start` and ends now.
+fn stage_event(name: &str, start: Option<Instant>) -> Option<trace::TraceEvent> {
+    trace::TraceEvent::since(name, "engine", start)
+}
+
+/// Set the base URL of `document` from its `<base>` element's `href`, resolved against the
+/// URL it was loaded from. Without one, a document that has no base URL yet gets its own
+/// URL, and one that has (like a reader mode copy) keeps it.
+fn resolve_base_url(document: &mut dom::Document) {
+    let url: Option<net::Url> = document.url.as_deref().and_then(|url: &str| net::Url::parse(url).ok());
+    let base: Option<net::Url> = document.base_href().and_then(|href: &str| match url {
+        Some(ref url) => url.join(href).ok(),
+        None => net::Url::parse(href).ok(),
+    });
+    match base {
+        Some(base) => document.base_url = Some(base.to_string()),
+        None if document.base_url.is_none() => document.base_url = document.url.clone(),
+        None => {}
+    }
+}
+
+/// Load a page from an `http://` URL, or from a file, with a viewport of `viewport`
+/// device px, scrolled to the URL's fragment if it has one.
+fn load(url: &str, client: &Arc<Mutex<net::Client>>, viewport: (usize, usize)) -> Result<Page, EngineError> {
+    let (document, fragment) = split_fragment(url).unwrap_or((url, ""));
+    let mut page: Page = if url.contains("://") {
+        Page::load_url_with_client(url, client.clone())?
+    } else {
+        let document: dom::Document = html::parse(&fs::read_to_string(document)?)?;
+        let stylesheet: css::Stylesheet = css::parse(&inline_css(&document))?;
+        Page::new(document, stylesheet, loader::ImageLoader::with_client(client.clone()))
+    };
+    page.set_viewport(viewport.0, viewport.1);
+    // Layout depends on the viewport, so the fragment's element may have moved.
+    if url.contains('#') {
+        page.scroll_to_fragment(fragment);
+    }
+    Ok(page)
+}
+
+/// Split a URL into the document it names and its fragment, if it has one:
+/// "page.html#intro" -> ("page.html", "intro").
+fn split_fragment(url: &str) -> Option<(&str, &str)> {
+    url.split_once('#')
+}
+
+
+/// The text of the document's `<style>` elements, in document order.
+pub fn inline_css(document: &dom::Document) -> String {
+    let mut source: String = String::new();
+    for style_element in document.get_elements_by_tag_name("style") {
+        source.push('\n');
+        source.push_str(&document.tree.text_content(style_element));
+    }
+    source
+}
+
+/// The `<iframe>` boxes in a layout tree, with their content boxes.
+fn collect_frames(layout_box: &layout::LayoutBox, boxes: &mut Vec<(dom::NodeId, layout::Rect)>) {
+    if let (Some(node), Some(_)) = (layout_box.node_id(), &layout_box.replaced) {
+        if let layout::BoxType::BlockNode(style_node) | layout::BoxType::InlineNode(style_node) = layout_box.box_type {
+            if matches!(style_node.node.node_type, dom::NodeType::Element(ref element) if element.tag_name == "iframe") {
+                boxes.push((node, layout_box.dimensions.content));
+            }
+        }
+    }
+    for child in layout_box.children.iter() {
+        collect_frames(child, boxes);
+    }
+}
+
+/// Lock the shared client, even if another thread panicked while holding it.
+fn lock(client: &Mutex<net::Client>) -> std::sync::MutexGuard<'_, net::Client> {
+    client.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
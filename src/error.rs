@@ -0,0 +1,88 @@
+//! One error type for everything that can go wrong between a URL and a painted page.
+
+use crate::{css, html, layout, net};
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+
+/*
+    Errors
+
+    Each stage of the pipeline reports its own kind of failure: the HTML and CSS parsers
+    return `ParseError`s saying where they gave up, layout returns a `LayoutError` when the
+    style tree has nothing to lay out, and loading returns a `net::LoadError`. None of them
+    panic, so an engine embedded in a long-running server can turn a bad page into an
+    error response and carry on with the next one.
+
+    Code that drives the whole pipeline, like `engine::Page`, wraps them in an
+    `EngineError`, and `?` does the wrapping:
+
+    e.g.
+        fn render(html: &str, css: &str) -> Result<Vec<u8>, EngineError> {
+            let document: dom::Document = html::parse(html.to_string())?;   // EngineError::Html
+            let stylesheet: css::Stylesheet = css::parse(css.to_string())?; // EngineError::Css
+            ...
+        }
+ */
+
+/// A failure in any stage of loading, parsing, or laying out a page.
+#[derive(Debug)]
+pub enum EngineError {
+    Html(html::ParseError),
+    Css(css::ParseError),
+    Layout(layout::LayoutError),
+    Load(net::LoadError),
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EngineError::Html(err) => write!(f, "HTML parse error: {}", err),
+            EngineError::Css(err) => write!(f, "CSS parse error: {}", err),
+            EngineError::Layout(err) => write!(f, "layout error: {}", err),
+            EngineError::Load(err) => write!(f, "load error: {}", err),
+        }
+    }
+}
+
+impl Error for EngineError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            EngineError::Html(err) => Some(err),
+            EngineError::Css(err) => Some(err),
+            EngineError::Layout(err) => Some(err),
+            EngineError::Load(err) => Some(err),
+        }
+    }
+}
+
+impl From<html::ParseError> for EngineError {
+    fn from(err: html::ParseError) -> EngineError {
+        EngineError::Html(err)
+    }
+}
+
+impl From<css::ParseError> for EngineError {
+    fn from(err: css::ParseError) -> EngineError {
+        EngineError::Css(err)
+    }
+}
+
+impl From<layout::LayoutError> for EngineError {
+    fn from(err: layout::LayoutError) -> EngineError {
+        EngineError::Layout(err)
+    }
+}
+
+impl From<net::LoadError> for EngineError {
+    fn from(err: net::LoadError) -> EngineError {
+        EngineError::Load(err)
+    }
+}
+
+impl From<io::Error> for EngineError {
+    fn from(err: io::Error) -> EngineError {
+        EngineError::Load(net::LoadError::Io(err))
+    }
+}
@@ -0,0 +1,187 @@
+//! Timers and animation frames on a clock the page controls.
+
+
+/*
+    The Event Loop
+
+    A browser runs scripts in tasks: parsing the page is one, each timer that fires is
+    another, and so is each [animation frame](https://html.spec.whatwg.org/#run-the-animation-frame-callbacks),
+    which runs every `requestAnimationFrame` callback and then paints. `EventLoop` keeps
+    the queue of timers and frame callbacks, and hands out whichever task is due next.
+
+    Its clock doesn't run by itself: time only moves when the owner says so, by asking
+    for the tasks due before some point. A window moves it with the wall clock, and a test
+    moves it by exactly as much as it likes, so an animation plays out the same way every
+    time. Frames come every `FRAME_INTERVAL` ms (60 per second), but only while callbacks
+    are waiting for one, like a browser that doesn't paint when nothing changed.
+
+    Timers due at the same time fire in the order they were set. As in browsers, a timer
+    set from inside a deeply nested chain of timers waits at least `MIN_NESTED_DELAY` ms,
+    so a `setTimeout(f, 0)` that keeps setting itself can't stop the clock. Repeating
+    timers always wait that long, for the same reason.
+
+    e.g.
+        let mut event_loop: EventLoop<&str> = EventLoop::new();
+        event_loop.set_timer("tick", 100.0, true);
+        event_loop.request_animation_frame("draw");
+        event_loop.next_task(1000.0);   // Some(Task::AnimationFrame(["draw"])), now = 16.67
+        event_loop.next_task(1000.0);   // Some(Task::Timer("tick")), now = 100
+ */
+
+/// The time between animation frames, in ms.
+pub const FRAME_INTERVAL: f64 = 1000.0 / 60.0;
+
+/// How deeply timers can nest before their delays are raised to `MIN_NESTED_DELAY`.
+const MAX_NESTING: u32 = 5;
+
+/// The shortest delay of a deeply nested timer, in ms.
+pub const MIN_NESTED_DELAY: f64 = 4.0;
+
+/// Identifies a timer or animation frame callback, so it can be canceled.
+pub type TaskId = u32;
+
+/// Something the owner of the loop has to run now.
+#[derive(Debug, PartialEq)]
+pub enum Task<T> {
+    /// A timer fired.
+    Timer(T),
+    /// An animation frame: call these, in order, with the current time, then paint.
+    AnimationFrame(Vec<T>),
+}
+
+#[derive(Debug)]
+struct Timer<T> {
+    id: TaskId,
+    due: f64,
+    interval: Option<f64>, // for repeating timers
+    nesting: u32,
+    callback: T,
+}
+
+/// The timers and animation frame callbacks of one page.
+#[derive(Debug)]
+pub struct EventLoop<T> {
+    now: f64,
+    next_id: TaskId,
+    timers: Vec<Timer<T>>,
+    frame_callbacks: Vec<(TaskId, T)>,
+    last_frame: f64, // when the last animation frame ran
+    nesting: u32,    // of the timer task running now, or 0
+}
+
+impl<T: Clone> Default for EventLoop<T> {
+    fn default() -> EventLoop<T> {
+        EventLoop::new()
+    }
+}
+
+impl<T: Clone> EventLoop<T> {
+    /// Create an empty loop whose clock reads 0.
+    pub fn new() -> EventLoop<T> {
+        EventLoop { now: 0.0, next_id: 1, timers: Vec::new(), frame_callbacks: Vec::new(), last_frame: 0.0, nesting: 0 }
+    }
+
+    /// The time on the loop's clock, in ms since it was created.
+    pub fn now(&self) -> f64 {
+        self.now
+    }
+
+    /// Run `callback` after `delay` ms, and every `delay` ms after that if `repeat` is set.
+    pub fn set_timer(&mut self, callback: T, delay: f64, repeat: bool) -> TaskId {
+        let nesting: u32 = self.nesting + 1;
+        let mut delay: f64 = if delay.is_finite() { delay.max(0.0) } else { 0.0 };
+        if nesting > MAX_NESTING || repeat {
+            delay = delay.max(MIN_NESTED_DELAY);
+        }
+        let id: TaskId = self.take_id();
+        self.timers.push(Timer { id, due: self.now + delay, interval: repeat.then_some(delay), nesting, callback });
+        id
+    }
+
+    /// Cancel a timer. Does nothing if it already fired (and doesn't repeat) or was canceled.
+    pub fn clear_timer(&mut self, id: TaskId) {
+        self.timers.retain(|timer: &Timer<T>| timer.id != id);
+    }
+
+    /// Run `callback` in the next animation frame.
+    pub fn request_animation_frame(&mut self, callback: T) -> TaskId {
+        let id: TaskId = self.take_id();
+        self.frame_callbacks.push((id, callback));
+        id
+    }
+
+    /// Cancel an animation frame callback. Does nothing if it already ran.
+    pub fn cancel_animation_frame(&mut self, id: TaskId) {
+        self.frame_callbacks.retain(|&(callback_id, _): &(TaskId, T)| callback_id != id);
+    }
+
+    /// Return true if no timers or frame callbacks are waiting.
+    pub fn is_idle(&self) -> bool {
+        self.timers.is_empty() && self.frame_callbacks.is_empty()
+    }
+
+    /// When the next task is due on the loop's clock, or `None` if nothing is waiting.
+    pub fn next_deadline(&self) -> Option<f64> {
+        let timer: Option<f64> = self.next_timer().map(|i: usize| self.timers[i].due);
+        let frame: Option<f64> = (!self.frame_callbacks.is_empty()).then(|| self.next_frame_time());
+        match (timer, frame) {
+            (Some(timer), Some(frame)) => Some(timer.min(frame)),
+            (timer, frame) => timer.or(frame),
+        }
+    }
+
+    /// Move the clock to the next task due at or before `until`, and return it. If there
+    /// is none, move the clock to `until` and return `None`.
+    pub fn next_task(&mut self, until: f64) -> Option<Task<T>> {
+        self.nesting = 0;
+        let deadline: f64 = match self.next_deadline() {
+            Some(deadline) if deadline <= until => deadline,
+            _ => {
+                self.now = self.now.max(until);
+                return None;
+            }
+        };
+        self.now = self.now.max(deadline);
+
+        // Timers first, so a frame at the same time paints what they did.
+        if let Some(i) = self.next_timer().filter(|&i: &usize| self.timers[i].due <= deadline) {
+            let timer: &mut Timer<T> = &mut self.timers[i];
+            self.nesting = timer.nesting;
+            let callback: T = timer.callback.clone();
+            match timer.interval {
+                Some(interval) => timer.due += interval,
+                None => {
+                    self.timers.remove(i);
+                }
+            }
+            return Some(Task::Timer(callback));
+        }
+
+        // Callbacks requested during this frame wait for the next one.
+        self.last_frame = self.now;
+        let callbacks: Vec<T> = self.frame_callbacks.drain(..).map(|(_, callback): (TaskId, T)| callback).collect();
+        Some(Task::AnimationFrame(callbacks))
+    }
+
+    /// The index of the timer due first (and set first, among those due together).
+    fn next_timer(&self) -> Option<usize> {
+        (0..self.timers.len()).min_by(|&a: &usize, &b: &usize| {
+            let (a, b) = (&self.timers[a], &self.timers[b]);
+            a.due.total_cmp(&b.due).then(a.id.cmp(&b.id))
+        })
+    }
+
+    /// The first frame boundary that is after the last frame, and not before the current time.
+    fn next_frame_time(&self) -> f64 {
+        // Allow for rounding: 50 ms is the third boundary, not just after it.
+        let now: f64 = (self.now / FRAME_INTERVAL - 1e-9).ceil();
+        let after_last: f64 = (self.last_frame / FRAME_INTERVAL + 1e-9).floor() + 1.0;
+        now.max(after_last) * FRAME_INTERVAL
+    }
+
+    fn take_id(&mut self) -> TaskId {
+        let id: TaskId = self.next_id;
+        self.next_id += 1;
+        id
+    }
+}
@@ -0,0 +1,230 @@
+//! DOM events: listeners on nodes, and capture/bubble dispatch.
+
+use crate::dom::{NodeId, Tree};
+use crate::collections::HashMap;
+
+
+/*
+    Events
+
+    Clicks, key presses, and scripts all talk to the page through events. An event is
+    dispatched at a target node, but listeners on the target's ancestors get to see it too,
+    in three [phases](https://dom.spec.whatwg.org/#concept-event-dispatch):
+        1. capture: from the root down to the target's parent, calling capturing listeners
+        2. target:  at the target itself, calling all of its listeners
+        3. bubble:  from the target's parent back up to the root, calling the other
+                    listeners (only if the event bubbles)
+
+    e.g. a click on the <em> in
+        <div><p><em>Hi</em></p></div>
+
+        capture: div, p    target: em    bubble: p, div
+
+    Any listener can stop the event from going further with `stop_propagation`, or tell
+    the engine not to perform the default action (following a link, say) with
+    `prevent_default`.
+
+    Listeners are kept outside the DOM tree, so that the tree stays plain data that can be
+    cloned, compared, and serialized.
+ */
+
+/// Which part of the dispatch an event is in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Phase {
+    None,
+    Capturing,
+    AtTarget,
+    Bubbling,
+}
+
+/// An event being dispatched through the tree.
+#[derive(Clone, Debug)]
+pub struct Event {
+    pub event_type: String,
+    pub target: Option<NodeId>,
+    pub current_target: Option<NodeId>,
+    pub phase: Phase,
+    pub bubbles: bool,
+    pub cancelable: bool,
+    pub default_prevented: bool,
+    propagation_stopped: bool,
+    immediate_propagation_stopped: bool,
+}
+
+impl Event {
+    /// Create an event that neither bubbles nor can be canceled.
+    pub fn new(event_type: &str) -> Event {
+        Event {
+            event_type: event_type.to_string(),
+            target: None,
+            current_target: None,
+            phase: Phase::None,
+            bubbles: false,
+            cancelable: false,
+            default_prevented: false,
+            propagation_stopped: false,
+            immediate_propagation_stopped: false,
+        }
+    }
+
+    /// Create an event that bubbles and can be canceled, like `click`.
+    pub fn bubbling(event_type: &str) -> Event {
+        Event { bubbles: true, cancelable: true, ..Event::new(event_type) }
+    }
+
+    /// Don't call listeners on any further nodes.
+    pub fn stop_propagation(&mut self) {
+        self.propagation_stopped = true;
+    }
+
+    /// Don't call any further listeners, even on the current node.
+    pub fn stop_immediate_propagation(&mut self) {
+        self.propagation_stopped = true;
+        self.immediate_propagation_stopped = true;
+    }
+
+    /// Return true if a listener called `stop_immediate_propagation`, so no more listeners
+    /// should run for this event.
+    pub fn immediate_propagation_stopped(&self) -> bool {
+        self.immediate_propagation_stopped
+    }
+
+    /// Ask the engine to skip the default action. Has no effect if the event isn't cancelable.
+    pub fn prevent_default(&mut self) {
+        if self.cancelable {
+            self.default_prevented = true;
+        }
+    }
+}
+
+
+/// A function called when an event reaches the node it is registered on.
+pub type Listener = Box<dyn FnMut(&mut Event)>;
+
+/// Identifies a registered listener, so it can be removed again.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ListenerId(usize);
+
+struct Registration {
+    id: ListenerId,
+    event_type: String,
+    capture: bool,
+    listener: Listener,
+}
+
+/// The event listeners registered on the nodes of one tree.
+#[derive(Default)]
+pub struct EventListeners {
+    listeners: HashMap<NodeId, Vec<Registration>>,
+    next_id: usize,
+}
+
+impl EventListeners {
+    /// Create an empty set of listeners.
+    pub fn new() -> EventListeners {
+        Default::default()
+    }
+
+    /// Call `listener` whenever an event of `event_type` reaches `node`. Capturing listeners
+    /// run on the way down to the target; the others run at the target and while bubbling.
+    pub fn add_event_listener(&mut self, node: NodeId, event_type: &str, capture: bool, listener: Listener) -> ListenerId {
+        let id: ListenerId = ListenerId(self.next_id);
+        self.next_id += 1;
+        self.listeners.entry(node).or_default().push(Registration {
+            id,
+            event_type: event_type.to_string(),
+            capture,
+            listener,
+        });
+        id
+    }
+
+    /// Unregister a listener. Does nothing if it was already removed.
+    pub fn remove_event_listener(&mut self, id: ListenerId) {
+        for registrations in self.listeners.values_mut() {
+            registrations.retain(|registration: &Registration| registration.id != id);
+        }
+    }
+
+    /// Return true if any listener for `event_type` is registered on `node`.
+    pub fn has_listener(&self, node: NodeId, event_type: &str) -> bool {
+        self.listeners.get(&node).is_some_and(|registrations: &Vec<Registration>| {
+            registrations.iter().any(|registration: &Registration| registration.event_type == event_type)
+        })
+    }
+
+    /// Dispatch `event` at `target`, running the capture, target, and bubble phases.
+    /// Returns false if a listener canceled the event, like `dispatchEvent` in the DOM.
+    pub fn dispatch_event(&mut self, tree: &Tree, target: NodeId, event: &mut Event) -> bool {
+        event.target = Some(target);
+        let path: Vec<NodeId> = tree.ancestors(target).collect();
+        dispatch_along(&path, event, |node: NodeId, event: &mut Event, capture: bool| self.invoke(node, event, capture))
+    }
+
+    /// Run the capturing (or non-capturing) listeners on `node` for this event, in
+    /// registration order.
+    fn invoke(&mut self, node: NodeId, event: &mut Event, capture: bool) {
+        let registrations: &mut Vec<Registration> = match self.listeners.get_mut(&node) {
+            Some(registrations) => registrations,
+            None => return,
+        };
+        event.current_target = Some(node);
+        for registration in registrations.iter_mut() {
+            if registration.event_type != event.event_type || registration.capture != capture {
+                continue;
+            }
+            (registration.listener)(event);
+            if event.immediate_propagation_stopped {
+                return;
+            }
+        }
+    }
+
+}
+
+
+/// Run the phases of `event` along `path` (the target, then its ancestors up to the root),
+/// calling `invoke(node, event, capture)` wherever listeners could run. This is the walk
+/// `dispatch_event` does, for callers that keep their listeners somewhere else (like the
+/// scripts in `script`, whose path ends at the document). Returns false if a listener
+/// canceled the event.
+/*
+    The path is fixed before any listener runs, so moving nodes around in a listener
+    doesn't change which nodes see the event.
+ */
+pub fn dispatch_along<T: Copy>(path: &[T], event: &mut Event, mut invoke: impl FnMut(T, &mut Event, bool)) -> bool {
+    let target: T = path[0];
+
+    event.phase = Phase::Capturing;
+    for &node in path[1..].iter().rev() {
+        invoke(node, event, true);
+        if event.propagation_stopped {
+            return finish(event);
+        }
+    }
+
+    event.phase = Phase::AtTarget;
+    invoke(target, event, true);
+    if !event.immediate_propagation_stopped {
+        invoke(target, event, false);
+    }
+    if event.propagation_stopped || !event.bubbles {
+        return finish(event);
+    }
+
+    event.phase = Phase::Bubbling;
+    for &node in &path[1..] {
+        invoke(node, event, false);
+        if event.propagation_stopped {
+            break;
+        }
+    }
+    finish(event)
+}
+
+/// Reset the per-dispatch state and report whether the event was canceled.
+fn finish(event: &mut Event) -> bool {
+    event.phase = Phase::None;
+    event.current_target = None;
+    !event.default_prevented
+}
@@ -0,0 +1,144 @@
+//! Find-in-page: search the text the user can see.
+
+use crate::{css, dom, layout, painting};
+use std::ops::Range;
+
+
+/*
+    Find in Page
+
+    Searching the page should only find text that is actually rendered, so we search the
+    layout tree rather than the DOM: text inside `display: none` elements has no box, and
+    is never found. The search ignores case and treats any run of whitespace as a single
+    space, just as the text is displayed.
+
+    Each match is a range of bytes in one text node. Matches don't span text nodes, so
+    "big world" isn't found in `<em>big</em> world`.
+
+    A match's rect is the box of the text node it's in, which covers the node's text on
+    every line it spans. (A text node with no text on any line, like one that is all
+    collapsed white space, falls back to the nearest box around it with a size.) It is in
+    document coordinates, with the scroll positions of any `overflow` boxes applied.
+
+    e.g.
+        <p>Hello <em>World</em></p>, find "world"
+        => [Match { node: <the text node "World">, range: 0..5, rect: <the text's box> }]
+ */
+
+/// One place the search text was found.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Match {
+    pub node: dom::NodeId,  // the text node
+    pub range: Range<usize>, // byte offsets into its text
+    pub rect: layout::Rect,  // where to highlight, in CSS px
+}
+
+/// Find every occurrence of `query` in the rendered text of a layout tree, in document
+/// order. An empty (or all-whitespace) query finds nothing.
+pub fn find_in_layout(root: &layout::LayoutBox, offsets: &layout::ScrollOffsets, query: &str) -> Vec<Match> {
+    let query: Vec<char> = fold(query).into_iter().map(|(c, _)| c).collect();
+    let query: &[char] = trim_spaces(&query);
+    let mut matches: Vec<Match> = Vec::new();
+    if !query.is_empty() {
+        search_box(root, offsets, query, (0.0, 0.0), None, &mut matches);
+    }
+    matches
+}
+
+fn search_box(layout_box: &layout::LayoutBox, offsets: &layout::ScrollOffsets, query: &[char],
+              translate: (f32, f32), container: Option<layout::Rect>, matches: &mut Vec<Match>) {
+    let border_box: layout::Rect = layout_box.dimensions.border_box().translated(translate.0, translate.1);
+    let container: Option<layout::Rect> = if border_box.width > 0.0 && border_box.height > 0.0 {
+        Some(border_box)
+    } else {
+        container
+    };
+
+    if let layout::BoxType::InlineNode(style_node) = layout_box.box_type {
+        if let dom::NodeType::Text(ref text) = style_node.node.node_type {
+            let rect: layout::Rect = container.unwrap_or(border_box);
+            for range in find_in_text(text, query) {
+                matches.push(Match { node: style_node.node_id, range, rect });
+            }
+        }
+    }
+
+    let (translate, _) = layout_box.children_transform(offsets, translate, None);
+    for child in layout_box.children.iter() {
+        search_box(child, offsets, query, translate, container, matches);
+    }
+}
+
+/// The byte ranges of `text` matching the (already folded) query, without overlaps.
+fn find_in_text(text: &str, query: &[char]) -> Vec<Range<usize>> {
+    let folded: Vec<(char, usize)> = fold(text);
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut i: usize = 0;
+    while i + query.len() <= folded.len() {
+        if folded[i..i + query.len()].iter().map(|&(c, _)| c).eq(query.iter().copied()) {
+            let last_offset: usize = folded[i + query.len() - 1].1;
+            let last_len: usize = text[last_offset..].chars().next().unwrap().len_utf8();
+            ranges.push(folded[i].1..last_offset + last_len);
+            i += query.len();
+        } else {
+            i += 1;
+        }
+    }
+    ranges
+}
+
+/// Lowercase `text` and collapse each run of whitespace to one space, keeping the byte
+/// offset each character came from.
+fn fold(text: &str) -> Vec<(char, usize)> {
+    let mut folded: Vec<(char, usize)> = Vec::new();
+    for (offset, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if folded.last().is_none_or(|&(last, _): &(char, usize)| last != ' ') {
+                folded.push((' ', offset));
+            }
+        } else {
+            folded.extend(c.to_lowercase().map(|lower: char| (lower, offset)));
+        }
+    }
+    folded
+}
+
+fn trim_spaces(chars: &[char]) -> &[char] {
+    let start: usize = chars.iter().position(|&c: &char| c != ' ').unwrap_or(chars.len());
+    let end: usize = chars.iter().rposition(|&c: &char| c != ' ').map_or(start, |i: usize| i + 1);
+    &chars[start..end]
+}
+
+
+/*
+    Highlighting
+
+    Filling a match's rect would hide whatever is inside it, so matches are outlined
+    instead: every match in yellow, and the current one in orange.
+ */
+/// How thick highlight outlines are, in CSS px.
+const OUTLINE_WIDTH: f32 = 2.0;
+
+/// Display commands outlining each match, making the one at index `current` stand out.
+pub fn highlight_matches(matches: &[Match], current: Option<usize>) -> painting::DisplayList {
+    let yellow = css::Color { r: 255, g: 220, b: 0, a: 255 };
+    let orange = css::Color { r: 255, g: 140, b: 0, a: 255 };
+    let mut list: painting::DisplayList = Vec::new();
+    // Draw the current match last, so it's on top of any others sharing its rect.
+    let order = (0..matches.len()).filter(|&i: &usize| Some(i) != current).chain(current);
+    for i in order {
+        let color: css::Color = if Some(i) == current { orange } else { yellow };
+        let rect: layout::Rect = matches[i].rect;
+        let w: f32 = OUTLINE_WIDTH;
+        for edge in [
+            layout::Rect { height: w, ..rect },
+            layout::Rect { y: rect.y + rect.height - w, height: w, ..rect },
+            layout::Rect { width: w, ..rect },
+            layout::Rect { x: rect.x + rect.width - w, width: w, ..rect },
+        ] {
+            list.push(painting::DisplayCommand::SolidColor(color, edge));
+        }
+    }
+    list
+}
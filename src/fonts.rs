@@ -0,0 +1,1014 @@
+//! Fonts: finding a face for each CSS font description, and measuring text with it.
+
+use crate::atom::Atom;
+use crate::hyphenation::Hyphenator;
+use crate::math;
+use crate::shaping::Script;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use alloc::sync::Arc;
+use core::fmt;
+#[cfg(feature = "std")]
+use crate::collections::HashMap;
+#[cfg(feature = "std")]
+use crate::layout::Rect;
+#[cfg(feature = "std")]
+use crate::shaping;
+#[cfg(feature = "std")]
+use ab_glyph::{Font, FontVec};
+#[cfg(feature = "std")]
+use std::fs;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "std")]
+use std::sync::{Mutex, OnceLock};
+
+/*
+    Fonts
+
+    Pages ask for fonts by description, not by file:
+
+        p { font-family: "DejaVu Sans", Arial, sans-serif; font-weight: bold; font-style: italic; }
+
+    A `FontCollection` is the set of faces the engine may use, each with the family name,
+    weight, and style its font file gives. `FontCollection::system` finds the fonts
+    installed on the machine by looking through the usual font directories. An embedder
+    can add its own files or bytes to a collection, or start from an empty one so that
+    only its own fonts are used.
+
+    Matching follows CSS's [font matching algorithm](https://www.w3.org/TR/css-fonts-4/#font-matching-algorithm),
+    simplified:
+        1. Try each family in `font-family` in order. A generic family (`serif`,
+           `sans-serif`, `monospace`, ...) stands for a list of real families, which the
+           embedder can change (`set_generic_family`). The first family with any face wins.
+        2. If none has a face, try the collection's fallback families, and then any face.
+    That face is the text's "primary" face. A character it doesn't have is drawn with
+    another face instead (see "Fallback" below).
+        3. Among the family's faces, prefer the requested style: italic falls back to
+           oblique and then normal, and oblique to italic and then normal.
+        4. Then the nearest weight: for bold (above 500), the nearest heavier one first;
+           for light (below 400), the nearest lighter one; and for 400 and 500, up to 500
+           first, then lighter, then heavier.
+    If the face chosen is still lighter or more upright than the text asks for, it's
+    drawn bolder or slanted (see "Synthesis" below).
+
+    A face's file is read when the face is first matched, and kept, so every page using
+    the collection shares it. Matches are cached too. Finding the system's fonts means
+    reading every font file's name table, so `system` does it once per process.
+
+    Without `std` there is no file system and no font parser, so a collection has no
+    faces. Text is then measured with stand-in metrics (every character half an em wide,
+    or a whole em for wide CJK characters) and isn't painted. Deterministic mode does the
+    same, so layout doesn't depend on which fonts a machine has (see `engine::Page`).
+ */
+
+/// CSS's `font-style` property.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FontStyle {
+    Normal,
+    Italic,
+    Oblique,
+}
+
+/// U+00AD SOFT HYPHEN, which marks where a word may be hyphenated and isn't drawn
+/// otherwise (see `hyphenation`).
+pub const SOFT_HYPHEN: char = '\u{AD}';
+
+/// `font-weight: normal`.
+pub const NORMAL_WEIGHT: u16 = 400;
+
+/// `font-weight: bold`.
+pub const BOLD_WEIGHT: u16 = 700;
+
+/// The font some text asks for: its computed `font-family`, `font-weight`, and `font-style`.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FontDescriptor {
+    pub families: Vec<Atom>, // in order of preference, including generic families
+    pub weight: u16,         // 100 to 900
+    pub style: FontStyle,
+}
+
+impl Default for FontDescriptor {
+    /// The initial value of the font properties: normal `serif`.
+    fn default() -> FontDescriptor {
+        FontDescriptor { families: vec![Atom::from("serif")], weight: NORMAL_WEIGHT, style: FontStyle::Normal }
+    }
+}
+
+/// A face's vertical metrics at some size, in px.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LineMetrics {
+    pub ascent: f32,   // above the baseline
+    pub descent: f32,  // below the baseline (positive)
+    pub line_gap: f32, // recommended extra space between lines
+}
+
+impl LineMetrics {
+    /// The metrics of the stand-in font, for text with no face.
+    pub fn stand_in(size: f32) -> LineMetrics {
+        LineMetrics { ascent: 0.8 * size, descent: 0.2 * size, line_gap: 0.2 * size }
+    }
+
+    /// The height of a line with `line-height: normal`.
+    pub fn normal_line_height(&self) -> f32 {
+        self.ascent + self.descent + self.line_gap
+    }
+}
+
+/// One glyph in a run of text.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Glyph {
+    pub id: u16,            // in the run's face; 0 for "no glyph"
+    pub x: f32,             // where its advance starts, from the start of the run in reading order, in px
+    pub advance: f32,       // in px
+    pub offset: (f32, f32), // how far right and down it's drawn from where its advance puts it, in px
+    pub cluster: usize,     // byte offset of the first character it came from
+}
+
+/// A run of text measured with one face at one size.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TextRun {
+    pub text: String,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub face: Option<Arc<Face>>, // `None` for the stand-in font
+    pub size: f32, // font size in px
+    pub glyphs: Vec<Glyph>, // in the order of their characters
+    pub width: f32,
+    pub metrics: LineMetrics,
+    pub rtl: bool, // drawn right to left
+    pub synthesis: Synthesis,
+}
+
+impl TextRun {
+    /// Shape `text`, which is all in one script, in `face` (or the stand-in font) at
+    /// `size` px.
+    pub fn new(text: &str, face: Option<Arc<Face>>, size: f32) -> TextRun {
+        let script: Script = Script::of_text(text);
+        let (glyphs, metrics) = match &face {
+            #[cfg(feature = "std")]
+            Some(face) => (face.glyphs(text, script, size), face.metrics(size)),
+            _ => (stand_in_glyphs(text, size), LineMetrics::stand_in(size)),
+        };
+        let width: f32 = glyphs.last().map_or(0.0, |glyph: &Glyph| glyph.x + glyph.advance);
+        TextRun { text: text.to_string(), face, size, glyphs, width, metrics, rtl: script.is_rtl(), synthesis: Synthesis::default() }
+    }
+
+    /// The run drawn with `synthesis`, with each glyph widened by as much as it's
+    /// emboldened (see "Synthesis").
+    pub fn with_synthesis(mut self, synthesis: Synthesis) -> TextRun {
+        let extra: f32 = synthesis.embolden(self.size);
+        let mut x: f32 = 0.0;
+        for glyph in self.glyphs.iter_mut() {
+            glyph.x = x;
+            if glyph.advance > 0.0 {
+                glyph.advance += extra;
+            }
+            x += glyph.advance;
+        }
+        self.width = x;
+        self.synthesis = synthesis;
+        self
+    }
+
+    /// Where to draw `glyph`, one of the run's glyphs, from the left end of the run's
+    /// baseline: right and down, in px.
+    pub fn glyph_origin(&self, glyph: &Glyph) -> (f32, f32) {
+        let x: f32 = if self.rtl { self.width - glyph.x - glyph.advance } else { glyph.x };
+        (x + glyph.offset.0, glyph.offset.1)
+    }
+
+    /// The run with `extra` px added to each space, to stretch a justified line.
+    pub fn with_word_spacing(mut self, extra: f32) -> TextRun {
+        if extra == 0.0 {
+            return self;
+        }
+        let mut x: f32 = 0.0;
+        for glyph in self.glyphs.iter_mut() {
+            glyph.x = x;
+            if self.text[glyph.cluster..].starts_with(' ') {
+                glyph.advance += extra;
+            }
+            x += glyph.advance;
+        }
+        self.width = x;
+        self
+    }
+
+    /// The run with each tab reaching to the next multiple of `interval` px from the start
+    /// of its line, where the run starts `start` px into the line. A newline in the run
+    /// starts a new line.
+    pub fn with_tab_stops(mut self, start: f32, interval: f32) -> TextRun {
+        if interval <= 0.0 || !self.text.contains('\t') {
+            return self;
+        }
+        let mut x: f32 = 0.0;
+        let mut line_x: f32 = start; // where the glyph starts, from the start of its line
+        for glyph in self.glyphs.iter_mut() {
+            glyph.x = x;
+            match self.text[glyph.cluster..].chars().next() {
+                Some('\t') => glyph.advance = (math::floor(line_x / interval) + 1.0) * interval - line_x,
+                Some('\n') => line_x = -glyph.advance,
+                _ => {}
+            }
+            x += glyph.advance;
+            line_x += glyph.advance;
+        }
+        self.width = x;
+        self
+    }
+
+    /// How far from the start of the run the character at byte `offset` is drawn.
+    pub fn x_at(&self, offset: usize) -> f32 {
+        // Glyphs are in the order of their characters.
+        let index: usize = self.glyphs.partition_point(|glyph: &Glyph| glyph.cluster < offset);
+        self.glyphs.get(index).map_or(self.width, |glyph: &Glyph| glyph.x)
+    }
+
+    /// The part of the run for bytes `start..end` of its text, as a run of its own.
+    pub fn slice(&self, start: usize, end: usize) -> TextRun {
+        let x: f32 = self.x_at(start);
+        let first: usize = self.glyphs.partition_point(|glyph: &Glyph| glyph.cluster < start);
+        let last: usize = self.glyphs.partition_point(|glyph: &Glyph| glyph.cluster < end);
+        let glyphs: Vec<Glyph> = self.glyphs[first..last].iter()
+            .map(|glyph: &Glyph| Glyph { x: glyph.x - x, cluster: glyph.cluster - start, ..*glyph })
+            .collect();
+        TextRun {
+            text: self.text[start..end].to_string(),
+            face: self.face.clone(),
+            size: self.size,
+            glyphs,
+            width: self.x_at(end) - x,
+            metrics: self.metrics,
+            rtl: self.rtl,
+            synthesis: self.synthesis,
+        }
+    }
+
+    /// The same run at `factor` times the size, e.g. for a HiDPI screen.
+    pub fn scaled(&self, factor: f32) -> TextRun {
+        TextRun {
+            text: self.text.clone(),
+            face: self.face.clone(),
+            size: self.size * factor,
+            glyphs: self.glyphs.iter().map(|glyph: &Glyph| Glyph {
+                x: glyph.x * factor,
+                advance: glyph.advance * factor,
+                offset: (glyph.offset.0 * factor, glyph.offset.1 * factor),
+                ..*glyph
+            }).collect(),
+            width: self.width * factor,
+            metrics: LineMetrics {
+                ascent: self.metrics.ascent * factor,
+                descent: self.metrics.descent * factor,
+                line_gap: self.metrics.line_gap * factor,
+            },
+            rtl: self.rtl,
+            synthesis: self.synthesis,
+        }
+    }
+}
+
+/// Return true for characters that take up a whole em in the stand-in font, and that
+/// lines may break between: CJK ideographs, kana, hangul, and full-width forms.
+pub fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF |
+        0xA000..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 |
+        0xFFE0..=0xFFE6 | 0x20000..=0x2FFFD | 0x30000..=0x3FFFD)
+}
+
+/// Lay out `text` in the stand-in font: every character is a "no glyph" glyph, half an em
+/// wide, or a whole em if it's wide. Soft hyphens have no glyph.
+fn stand_in_glyphs(text: &str, size: f32) -> Vec<Glyph> {
+    let mut x: f32 = 0.0;
+    text.char_indices().filter(|&(_, c): &(usize, char)| c != SOFT_HYPHEN).map(|(cluster, c): (usize, char)| {
+        let advance: f32 = if is_wide(c) { size } else { size / 2.0 };
+        let glyph: Glyph = Glyph { id: 0, x, advance, offset: (0.0, 0.0), cluster };
+        x += advance;
+        glyph
+    }).collect()
+}
+
+
+/**
+ *  Faces
+ *
+ *  A face is one font file (or one font in a collection file): a family in one weight and
+ *  style. It maps characters to glyphs, knows their advances, and can draw them.
+ */
+#[cfg(feature = "std")]
+pub struct Face {
+    pub family: String,
+    pub weight: u16,
+    pub style: FontStyle,
+    font: FontVec,
+    index: u32, // in its font file
+}
+
+/// Without `std` there are no faces.
+#[cfg(not(feature = "std"))]
+#[derive(Debug)]
+pub enum Face {}
+
+/// The most pixels a glyph may cover and still be drawn: a 2048 px square. Only absurd
+/// font sizes make glyphs that big, and each would need a buffer of that many floats.
+#[cfg(feature = "std")]
+pub const MAX_GLYPH_PIXELS: usize = 2048 * 2048;
+
+#[cfg(feature = "std")]
+impl Face {
+    /// Read the face at `index` in a font file's data.
+    fn parse(data: Vec<u8>, index: u32) -> Option<Face> {
+        let (family, weight, style) = describe(&data, index)?;
+        let font: FontVec = FontVec::try_from_vec_and_index(data, index).ok()?;
+        Some(Face { family, weight, style, font, index })
+    }
+
+    /// How many px one font unit is at `size`.
+    fn unit(&self, size: f32) -> f32 {
+        size / self.font.units_per_em().unwrap_or(1000.0)
+    }
+
+    /// The face's vertical metrics at `size` px.
+    pub fn metrics(&self, size: f32) -> LineMetrics {
+        let unit: f32 = self.unit(size);
+        LineMetrics {
+            ascent: self.font.ascent_unscaled() * unit,
+            descent: -self.font.descent_unscaled() * unit,
+            line_gap: self.font.line_gap_unscaled() * unit,
+        }
+    }
+
+    /// Return true if the face has a glyph for `c`.
+    pub fn has_glyph(&self, c: char) -> bool {
+        self.font.glyph_id(c).0 != 0
+    }
+
+    /// Return true if the face has glyphs for all the visible characters in `text`.
+    pub fn covers(&self, text: &str) -> bool {
+        text.chars().all(|c: char| is_default_ignorable(c) || self.has_glyph(c))
+    }
+
+    /// Shape `text`, which is all in `script`, into glyphs at `size` px (see `shaping`).
+    pub fn glyphs(&self, text: &str, script: Script, size: f32) -> Vec<Glyph> {
+        match ttf_parser::Face::parse(self.font.as_slice(), self.index) {
+            Ok(face) => shaping::shape(&face, text, script, size),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Rasterize glyph `id` at `size` px, with its origin at `origin` (x, baseline), faking
+    /// what `synthesis` says, and call `plot` with each pixel it covers and how much of the
+    /// pixel it covers (0 to 1). Only glyphs that reach into `visible` are drawn, and only
+    /// if they cover at most `MAX_GLYPH_PIXELS`.
+    pub fn draw_glyph(&self, id: u16, size: f32, origin: (f32, f32), synthesis: Synthesis, visible: Rect,
+                      mut plot: impl FnMut(i32, i32, f32)) {
+        let (x, baseline): (f32, f32) = origin;
+        // ab_glyph's scale is the height from descent to ascent, not the em size.
+        let scale: f32 = self.font.height_unscaled() * self.unit(size);
+        let glyph: ab_glyph::Glyph = ab_glyph::GlyphId(id).with_scale_and_position(scale, ab_glyph::point(x, baseline));
+        let Some(outlined) = self.font.outline_glyph(glyph) else { return };
+        let bounds: ab_glyph::Rect = outlined.px_bounds();
+        let (left, top) = (bounds.min.x as i32, bounds.min.y as i32);
+
+        // A faked slant shifts each row of pixels sideways, and a faked bold smears them right.
+        let skew: f32 = if synthesis.oblique { OBLIQUE_SKEW } else { 0.0 };
+        let embolden: f32 = synthesis.embolden(size);
+        let shift = |y: i32| (baseline - (y as f32 + 0.5)) * skew;
+        let min_shift: i32 = shift(bounds.max.y as i32).floor() as i32;
+        let max_shift: i32 = shift(top).ceil() as i32;
+
+        // Rasterizing takes time and memory in proportion to the glyph's area, so first work
+        // out where it lands: glyphs outside `visible` are skipped, and so are huge ones.
+        let reach: Rect = Rect {
+            x: (left + min_shift) as f32,
+            y: top as f32,
+            width: bounds.width() + (max_shift - min_shift) as f32 + embolden.ceil() + 2.0,
+            height: bounds.height() + 1.0,
+        };
+        let shown: Rect = reach.intersection(visible);
+        if shown.width == 0.0 || shown.height == 0.0 || reach.width * reach.height > MAX_GLYPH_PIXELS as f32 {
+            return;
+        }
+
+        if synthesis == Synthesis::default() {
+            outlined.draw(|gx: u32, gy: u32, coverage: f32| plot(left + gx as i32, top + gy as i32, coverage));
+            return;
+        }
+
+        // Draw into a buffer wide enough for the slant and the smear, shifting each pixel's
+        // coverage along its row (split between the two pixels it lands on).
+        let (width, height): (usize, usize) = (reach.width as usize, reach.height as usize);
+        let mut buffer: Vec<f32> = vec![0.0; width * height];
+        outlined.draw(|gx: u32, gy: u32, coverage: f32| {
+            let row: usize = gy as usize;
+            let x: f32 = gx as f32 + shift(top + gy as i32) - min_shift as f32;
+            let (column, fraction) = (x.floor() as usize, x.fract());
+            for (column, share) in [(column, 1.0 - fraction), (column + 1, fraction)] {
+                if column < width && row < height {
+                    buffer[column + row * width] += coverage * share;
+                }
+            }
+        });
+
+        // Then smear it right by `embolden` px, a whole pixel at a time and then the rest,
+        // keeping the most coverage each pixel gets.
+        let at = |column: f32, row: usize| -> f32 {
+            let (whole, fraction) = (column.floor(), column.fract());
+            let pixel = |column: f32| if column < 0.0 || column >= width as f32 { 0.0 } else { buffer[column as usize + row * width] };
+            pixel(whole) * (1.0 - fraction) + pixel(whole + 1.0) * fraction
+        };
+        let mut offsets: Vec<f32> = (0..=embolden as usize).map(|d: usize| d as f32).collect();
+        offsets.push(embolden);
+        for row in 0..height {
+            for column in 0..width {
+                let coverage: f32 = offsets.iter()
+                    .map(|&offset: &f32| at(column as f32 - offset, row))
+                    .fold(0.0, f32::max);
+                if coverage > 0.0 {
+                    plot(left + min_shift + column as i32, top + row as i32, coverage.min(1.0));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Debug for Face {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Face({:?} {} {:?})", self.family, self.weight, self.style)
+    }
+}
+
+/// Read the family name, weight, and style of the face at `index` in a font file.
+#[cfg(feature = "std")]
+fn describe(data: &[u8], index: u32) -> Option<(String, u16, FontStyle)> {
+    let face: ttf_parser::Face = ttf_parser::Face::parse(data, index).ok()?;
+    // Prefer the "typographic" family, which groups all weights under one name.
+    let family: String = [ttf_parser::name_id::TYPOGRAPHIC_FAMILY, ttf_parser::name_id::FAMILY].iter()
+        .find_map(|&id: &u16| {
+            face.names().into_iter()
+                .filter(|name: &ttf_parser::name::Name| name.name_id == id && name.is_unicode())
+                .find_map(|name: ttf_parser::name::Name| name.to_string())
+        })?;
+    let style: FontStyle = if face.is_italic() {
+        FontStyle::Italic
+    } else if face.is_oblique() {
+        FontStyle::Oblique
+    } else {
+        FontStyle::Normal
+    };
+    Some((family, face.weight().to_number(), style))
+}
+
+
+/**
+ *  Collections
+ */
+/// The faces text can be drawn with, and how to choose between them.
+pub struct FontCollection {
+    #[cfg(feature = "std")]
+    faces: Vec<FaceEntry>,
+    generic_families: Vec<(Atom, Vec<Atom>)>,
+    fallback: Vec<Atom>,
+    hyphenators: Vec<(String, Arc<Hyphenator>)>, // by lowercase language tag
+    #[cfg(feature = "std")]
+    matches: Mutex<HashMap<FontDescriptor, Option<usize>>>, // index into `faces`
+    #[cfg(feature = "std")]
+    fallbacks: Mutex<HashMap<(FontDescriptor, String), Option<usize>>>, // by cluster
+}
+
+/// A face the collection knows about, which may not have been read yet.
+#[cfg(feature = "std")]
+struct FaceEntry {
+    family: String,
+    weight: u16,
+    style: FontStyle,
+    path: Option<(PathBuf, u32)>, // where to read it from, unless it was added as data
+    face: OnceLock<Option<Arc<Face>>>, // `None` if the file couldn't be read after all
+}
+
+/// The families each generic family stands for, until the embedder says otherwise. The
+/// first of them installed is used.
+const GENERIC_FAMILIES: [(&str, &[&str]); 7] = [
+    ("serif", &["DejaVu Serif", "Liberation Serif", "Noto Serif", "Times New Roman", "Times"]),
+    ("sans-serif", &["DejaVu Sans", "Liberation Sans", "Noto Sans", "Arial", "Helvetica", "Segoe UI"]),
+    ("monospace", &["DejaVu Sans Mono", "Liberation Mono", "Noto Sans Mono", "Courier New", "Menlo", "Consolas"]),
+    ("cursive", &["Comic Sans MS", "Apple Chancery", "URW Chancery L"]),
+    ("fantasy", &["Impact", "Papyrus"]),
+    ("system-ui", &["DejaVu Sans", "Noto Sans", "Segoe UI", "Helvetica Neue", "Helvetica"]),
+    ("ui-monospace", &["DejaVu Sans Mono", "Noto Sans Mono", "Menlo", "Consolas"]),
+];
+
+/// The families tried when none of the requested ones has a face.
+const FALLBACK_FAMILIES: [&str; 6] = ["DejaVu Sans", "Liberation Sans", "Noto Sans", "Arial", "Helvetica", "Segoe UI"];
+
+impl Default for FontCollection {
+    fn default() -> FontCollection {
+        FontCollection::new()
+    }
+}
+
+impl fmt::Debug for FontCollection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FontCollection({} faces)", self.len())
+    }
+}
+
+impl FontCollection {
+    /// A collection with no faces, so all text uses the stand-in font.
+    pub fn new() -> FontCollection {
+        let families = |names: &[&str]| names.iter().map(|&name: &&str| Atom::from(name)).collect::<Vec<Atom>>();
+        FontCollection {
+            #[cfg(feature = "std")]
+            faces: Vec::new(),
+            generic_families: GENERIC_FAMILIES.iter()
+                .map(|&(generic, names): &(&str, &[&str])| (Atom::from(generic), families(names)))
+                .collect(),
+            fallback: families(&FALLBACK_FAMILIES),
+            hyphenators: Vec::new(),
+            #[cfg(feature = "std")]
+            matches: Mutex::new(HashMap::default()),
+            #[cfg(feature = "std")]
+            fallbacks: Mutex::new(HashMap::default()),
+        }
+    }
+
+    /// How many faces the collection has.
+    pub fn len(&self) -> usize {
+        #[cfg(feature = "std")]
+        return self.faces.len();
+        #[cfg(not(feature = "std"))]
+        return 0;
+    }
+
+    /// Return true if the collection has no faces.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Make the generic family `generic` (e.g. `sans-serif`) stand for `families`.
+    pub fn set_generic_family(&mut self, generic: &str, families: &[&str]) {
+        let families: Vec<Atom> = families.iter().map(|&family: &&str| Atom::from(family)).collect();
+        let generic: Atom = Atom::from(generic.to_ascii_lowercase());
+        match self.generic_families.iter_mut().find(|(name, _): &&mut (Atom, Vec<Atom>)| *name == generic) {
+            Some((_, existing)) => *existing = families,
+            None => self.generic_families.push((generic, families)),
+        }
+        self.clear_matches();
+    }
+
+    /// Set the families tried when none of the requested ones has a face.
+    pub fn set_fallback(&mut self, families: &[&str]) {
+        self.fallback = families.iter().map(|&family: &&str| Atom::from(family)).collect();
+        self.clear_matches();
+    }
+
+    /// Hyphenate text in language `lang` (a language tag like `en` or `en-US`) with
+    /// `hyphenator`, where it has `hyphens: auto` (see `hyphenation`). Hyphenation
+    /// dictionaries live with the fonts, since layout needs both to break lines.
+    pub fn set_hyphenator(&mut self, lang: &str, hyphenator: Arc<Hyphenator>) {
+        let lang: String = lang.to_ascii_lowercase();
+        self.hyphenators.retain(|(existing, _): &(String, Arc<Hyphenator>)| *existing != lang);
+        self.hyphenators.push((lang, hyphenator));
+    }
+
+    /// The hyphenator for language `lang`: the one set for exactly that language, or else
+    /// for a less specific one (`en` for `en-US`).
+    pub fn hyphenator(&self, lang: &str) -> Option<&Hyphenator> {
+        let mut lang: String = lang.to_ascii_lowercase();
+        loop {
+            if let Some((_, hyphenator)) = self.hyphenators.iter().find(|(existing, _): &&(String, Arc<Hyphenator>)| *existing == lang) {
+                return Some(hyphenator);
+            }
+            lang.truncate(lang.rfind('-')?);
+        }
+    }
+
+    fn clear_matches(&mut self) {
+        #[cfg(feature = "std")]
+        self.matches.get_mut().unwrap_or_else(|err| err.into_inner()).clear();
+        #[cfg(feature = "std")]
+        self.fallbacks.get_mut().unwrap_or_else(|err| err.into_inner()).clear();
+    }
+
+    /// Shape `text` in the face that best matches `font`, at `size` px, falling back to
+    /// other faces for the characters it doesn't have. Returns a run for each stretch of
+    /// text in one face and one script, in order; there is always at least one.
+    pub fn shape(&self, text: &str, font: &FontDescriptor, size: f32) -> Vec<TextRun> {
+        let primary: Option<Arc<Face>> = self.face_for(font);
+        #[cfg(feature = "std")]
+        if let Some(primary) = primary {
+            return self.shape_with_fallback(text, font, primary, size);
+        }
+        vec![TextRun::new(text, primary, size)]
+    }
+
+    /// The vertical metrics of the face that best matches `font`, at `size` px.
+    pub fn metrics(&self, font: &FontDescriptor, size: f32) -> LineMetrics {
+        match self.face_for(font) {
+            #[cfg(feature = "std")]
+            Some(face) => face.metrics(size),
+            _ => LineMetrics::stand_in(size),
+        }
+    }
+
+    /// The face that best matches `font`, or `None` if the collection is empty.
+    #[cfg(not(feature = "std"))]
+    pub fn face_for(&self, _font: &FontDescriptor) -> Option<Arc<Face>> {
+        None
+    }
+
+    /// The face that best matches `font`, or `None` if the collection is empty.
+    #[cfg(feature = "std")]
+    pub fn face_for(&self, font: &FontDescriptor) -> Option<Arc<Face>> {
+        let cached: Option<Option<usize>> = self.matches.lock().unwrap_or_else(|err| err.into_inner()).get(font).copied();
+        let index: Option<usize> = match cached {
+            Some(index) => index,
+            None => {
+                let index: Option<usize> = self.find_face(font);
+                self.matches.lock().unwrap_or_else(|err| err.into_inner()).insert(font.clone(), index);
+                index
+            }
+        };
+        self.load(index?)
+    }
+}
+
+
+/**
+ *  Matching
+ */
+#[cfg(feature = "std")]
+impl FontCollection {
+    /// The index of the face that best matches `font`.
+    fn find_face(&self, font: &FontDescriptor) -> Option<usize> {
+        let families = font.families.iter()
+            .flat_map(|family: &Atom| self.expand_generic(family))
+            .chain(self.fallback.iter());
+        for family in families {
+            let faces = (0..self.faces.len()).filter(|&i: &usize| self.faces[i].family.eq_ignore_ascii_case(family.as_str()));
+            if let Some(index) = self.best_face(faces, font) {
+                return Some(index);
+            }
+        }
+        self.best_face(0..self.faces.len(), font)
+    }
+
+    /// The real families `family` stands for, if it's generic, or else `family` itself.
+    fn expand_generic<'s>(&'s self, family: &'s Atom) -> &'s [Atom] {
+        self.generic_families.iter()
+            .find(|(generic, _): &&(Atom, Vec<Atom>)| generic.as_str().eq_ignore_ascii_case(family.as_str()))
+            .map_or(core::slice::from_ref(family), |(_, families): &(Atom, Vec<Atom>)| families.as_slice())
+    }
+
+    /// The face among `candidates` whose style, and then weight, is closest to `font`'s.
+    fn best_face(&self, candidates: impl Iterator<Item = usize>, font: &FontDescriptor) -> Option<usize> {
+        candidates.min_by_key(|&i: &usize| {
+            let entry: &FaceEntry = &self.faces[i];
+            (style_rank(font.style, entry.style), weight_rank(font.weight, entry.weight))
+        })
+    }
+
+    /// Read face `index`, the first time it's needed.
+    fn load(&self, index: usize) -> Option<Arc<Face>> {
+        let entry: &FaceEntry = &self.faces[index];
+        entry.face.get_or_init(|| {
+            let (path, face_index) = entry.path.as_ref()?;
+            Face::parse(fs::read(path).ok()?, *face_index).map(Arc::new)
+        }).clone()
+    }
+}
+
+
+
+/**
+ *  Synthesis
+ *
+ *  Many families come in fewer faces than CSS can ask for: a family may have no bold face,
+ *  or no italic one. Matching then picks the nearest face it has, and `<strong>` or `<em>`
+ *  would look just like the text around it. So, as browsers do, the missing face is faked
+ *  when the text is drawn:
+ *      - Bold (`font-weight` 600 or more, with a face lighter than 600) is drawn with its
+ *        strokes thickened: each glyph is smeared sideways by a sixteenth of an em. Each
+ *        glyph's advance grows by as much, so the thicker letters don't run together.
+ *      - Italic or oblique, with an upright face, is drawn slanted: each row of pixels is
+ *        shifted right by a quarter of its height above the baseline (a slant of about 14
+ *        degrees), which is what an oblique face looks like.
+ *  A run records which of these it needs in its `synthesis`.
+ */
+/// The parts of a run's font that its face doesn't have, and that are faked when it's drawn.
+#[derive(Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Synthesis {
+    pub bold: bool,
+    pub oblique: bool,
+}
+
+/// How much a synthesized oblique face is slanted: the horizontal shift per px of height.
+pub const OBLIQUE_SKEW: f32 = 0.25;
+
+impl Synthesis {
+    /// What needs faking to draw `font` with a face of `weight` and `style`.
+    pub fn needed(font: &FontDescriptor, weight: u16, style: FontStyle) -> Synthesis {
+        Synthesis {
+            bold: font.weight >= 600 && weight < 600,
+            oblique: font.style != FontStyle::Normal && style == FontStyle::Normal,
+        }
+    }
+
+    /// How far glyphs are smeared sideways to embolden them at `size` px (0 if they aren't).
+    pub fn embolden(&self, size: f32) -> f32 {
+        if self.bold { size / 16.0 } else { 0.0 }
+    }
+}
+
+
+/**
+ *  Fallback
+ *
+ *  No face has every character: a Latin font has no CJK ideographs, and few fonts have
+ *  emoji. When the primary face lacks a character, the text would show "tofu" (the
+ *  face's empty box), so the character is drawn with another face that has it. The
+ *  faces are tried in the same order as for matching: the rest of the `font-family`
+ *  list, then the fallback families, then any face at all, choosing by style and weight
+ *  within each family. If none has it, the primary face draws its box after all.
+ *
+ *  Faces are chosen for whole clusters, not characters: a character and the combining
+ *  marks, variation selectors, and emoji modifiers after it (and the characters joined
+ *  to it with a zero-width joiner) are drawn with one face, since a mark drawn with a
+ *  different face than its letter won't sit on it. White space stays in whatever face
+ *  the text before it uses, so a line of CJK text isn't cut into a run per word.
+ *
+ *  The text is then cut into a run for each stretch in one face, and in one script, since
+ *  scripts are shaped differently (see `shaping`). Finding a face for a cluster can mean
+ *  reading every font file, so the choice is cached.
+ */
+#[cfg(feature = "std")]
+impl FontCollection {
+    /// Cut `text` into runs by which face draws each cluster; see "Fallback".
+    fn shape_with_fallback(&self, text: &str, font: &FontDescriptor, primary: Arc<Face>, size: f32) -> Vec<TextRun> {
+        let mut runs: Vec<TextRun> = Vec::new();
+        let mut run_start: usize = 0;
+        let mut current: Arc<Face> = primary.clone();
+        let mut script: Script = Script::Common;
+        for (start, end) in clusters(text) {
+            let cluster: &str = &text[start..end];
+            // Spaces, digits, and punctuation go with the script around them.
+            let cluster_script: Script = cluster.chars().next().map_or(Script::Common, Script::of);
+            let face: Arc<Face> = if cluster.starts_with(char::is_whitespace) {
+                current.clone()
+            } else if primary.covers(cluster) {
+                primary.clone()
+            } else {
+                self.fallback_for(font, cluster).unwrap_or_else(|| primary.clone())
+            };
+            let new_script: bool = cluster_script != Script::Common && script != Script::Common && cluster_script != script;
+            if !Arc::ptr_eq(&face, &current) || new_script {
+                if start > run_start {
+                    runs.push(run(&text[run_start..start], font, current, size));
+                }
+                run_start = start;
+                current = face;
+                script = Script::Common;
+            }
+            if cluster_script != Script::Common {
+                script = cluster_script;
+            }
+        }
+        runs.push(run(&text[run_start..], font, current, size));
+        runs
+    }
+
+    /// The face that draws `cluster` when `font`'s primary face can't, if any can.
+    fn fallback_for(&self, font: &FontDescriptor, cluster: &str) -> Option<Arc<Face>> {
+        let key: (FontDescriptor, String) = (font.clone(), cluster.to_string());
+        let cached: Option<Option<usize>> = self.fallbacks.lock().unwrap_or_else(|err| err.into_inner()).get(&key).copied();
+        let index: Option<usize> = match cached {
+            Some(index) => index,
+            None => {
+                let covers = |i: &usize| self.load(*i).is_some_and(|face: Arc<Face>| face.covers(cluster));
+                let families = font.families.iter()
+                    .flat_map(|family: &Atom| self.expand_generic(family))
+                    .chain(self.fallback.iter());
+                let mut index: Option<usize> = None;
+                for family in families {
+                    let faces = (0..self.faces.len()).filter(|&i: &usize| self.faces[i].family.eq_ignore_ascii_case(family.as_str()));
+                    index = self.best_face(faces.filter(covers), font);
+                    if index.is_some() {
+                        break;
+                    }
+                }
+                let index: Option<usize> = index.or_else(|| self.best_face((0..self.faces.len()).filter(covers), font));
+                self.fallbacks.lock().unwrap_or_else(|err| err.into_inner()).insert(key, index);
+                index
+            }
+        };
+        self.load(index?)
+    }
+}
+
+/// Shape `text` in `face`, faking whatever of `font` the face lacks.
+#[cfg(feature = "std")]
+fn run(text: &str, font: &FontDescriptor, face: Arc<Face>, size: f32) -> TextRun {
+    let synthesis: Synthesis = Synthesis::needed(font, face.weight, face.style);
+    TextRun::new(text, Some(face), size).with_synthesis(synthesis)
+}
+
+/// The byte ranges of the clusters in `text`; see "Fallback".
+#[cfg(feature = "std")]
+fn clusters(text: &str) -> Vec<(usize, usize)> {
+    let mut clusters: Vec<(usize, usize)> = Vec::new();
+    let mut joined: bool = false; // the last character was a zero-width joiner
+    for (i, c) in text.char_indices() {
+        let end: usize = i + c.len_utf8();
+        match clusters.last_mut() {
+            Some(last) if joined || extends_cluster(c) => last.1 = end,
+            _ => clusters.push((i, end)),
+        }
+        joined = c == '\u{200D}';
+    }
+    clusters
+}
+
+/// Return true for characters that belong with the character before them: combining
+/// marks, variation selectors, emoji modifiers and tags, and joiners.
+#[cfg(feature = "std")]
+fn extends_cluster(c: char) -> bool {
+    is_default_ignorable(c) || matches!(c as u32,
+        0x0300..=0x036F | 0x0483..=0x0489 | 0x0591..=0x05BD | 0x064B..=0x065F | 0x0670 |
+        0x0E31 | 0x0E34..=0x0E3A | 0x0E47..=0x0E4E | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF |
+        0x20D0..=0x20FF | 0x3099..=0x309A | 0xFE20..=0xFE2F | 0x1F3FB..=0x1F3FF)
+}
+
+/// Return true for characters that aren't drawn, so a face doesn't need glyphs for them.
+#[cfg(feature = "std")]
+fn is_default_ignorable(c: char) -> bool {
+    matches!(c as u32,
+        0x00AD | 0x200B..=0x200F | 0x2060..=0x2064 | 0xFE00..=0xFE0F | 0xFEFF |
+        0xE0001 | 0xE0020..=0xE007F | 0xE0100..=0xE01EF)
+}
+
+/// How far `style` is from the `wanted` one, in CSS's order of preference.
+#[cfg(feature = "std")]
+fn style_rank(wanted: FontStyle, style: FontStyle) -> u8 {
+    let order: [FontStyle; 3] = match wanted {
+        FontStyle::Italic => [FontStyle::Italic, FontStyle::Oblique, FontStyle::Normal],
+        FontStyle::Oblique => [FontStyle::Oblique, FontStyle::Italic, FontStyle::Normal],
+        FontStyle::Normal => [FontStyle::Normal, FontStyle::Oblique, FontStyle::Italic],
+    };
+    order.iter().position(|&s: &FontStyle| s == style).unwrap_or(3) as u8
+}
+
+/// How far `weight` is from the `wanted` one, in CSS's order of preference: first which
+/// side of the wanted weight it's on, then the distance.
+#[cfg(feature = "std")]
+fn weight_rank(wanted: u16, weight: u16) -> (u8, u16) {
+    let distance: u16 = wanted.abs_diff(weight);
+    let side: u8 = if (400..=500).contains(&wanted) {
+        match weight {
+            w if w >= wanted && w <= 500 => 0,
+            w if w < wanted => 1,
+            _ => 2,
+        }
+    } else if wanted < 400 {
+        if weight <= wanted { 0 } else { 1 }
+    } else if weight >= wanted {
+        0
+    } else {
+        1
+    };
+    (side, distance)
+}
+
+
+/**
+ *  Adding Fonts
+ */
+#[cfg(feature = "std")]
+impl FontCollection {
+    /// The fonts installed on this machine, found the first time this is called.
+    pub fn system() -> Arc<FontCollection> {
+        static SYSTEM: OnceLock<Arc<FontCollection>> = OnceLock::new();
+        SYSTEM.get_or_init(|| {
+            let mut collection: FontCollection = FontCollection::new();
+            for dir in system_font_dirs() {
+                collection.add_directory(&dir);
+            }
+            Arc::new(collection)
+        }).clone()
+    }
+
+    /// Add the faces in a font file's data (TrueType, OpenType, or a collection of either).
+    /// Returns how many faces there were, or an error if the data isn't a font.
+    pub fn add_data(&mut self, data: Vec<u8>) -> io::Result<usize> {
+        let count: u32 = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        let mut added: usize = 0;
+        for index in 0..count {
+            if let Some(face) = Face::parse(data.clone(), index) {
+                self.faces.push(FaceEntry {
+                    family: face.family.clone(),
+                    weight: face.weight,
+                    style: face.style,
+                    path: None,
+                    face: OnceLock::from(Some(Arc::new(face))),
+                });
+                added += 1;
+            }
+        }
+        self.clear_matches();
+        not_a_font(added)
+    }
+
+    /// Add the faces in a font file. They are read again when first used.
+    pub fn add_file(&mut self, path: &Path) -> io::Result<usize> {
+        let data: Vec<u8> = fs::read(path)?;
+        let count: u32 = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        let mut added: usize = 0;
+        for index in 0..count {
+            if let Some((family, weight, style)) = describe(&data, index) {
+                self.faces.push(FaceEntry { family, weight, style, path: Some((path.to_path_buf(), index)), face: OnceLock::new() });
+                added += 1;
+            }
+        }
+        self.clear_matches();
+        not_a_font(added)
+    }
+
+    /// Add every font file in a directory and its subdirectories, skipping any that can't
+    /// be read. Returns how many faces were added.
+    pub fn add_directory(&mut self, dir: &Path) -> usize {
+        let mut entries: Vec<PathBuf> = match fs::read_dir(dir) {
+            Ok(entries) => entries.filter_map(|entry: io::Result<fs::DirEntry>| Some(entry.ok()?.path())).collect(),
+            Err(_) => return 0,
+        };
+        // Sort, so the faces (and which of two same-named ones wins) don't depend on the
+        // order the file system lists them in.
+        entries.sort();
+        let mut added: usize = 0;
+        for path in entries {
+            if path.is_dir() {
+                added += self.add_directory(&path);
+            } else if is_font_file(&path) {
+                added += self.add_file(&path).unwrap_or(0);
+            }
+        }
+        added
+    }
+
+    /// The family names in the collection, sorted, without duplicates.
+    pub fn families(&self) -> Vec<&str> {
+        let mut families: Vec<&str> = self.faces.iter().map(|entry: &FaceEntry| entry.family.as_str()).collect();
+        families.sort_unstable();
+        families.dedup();
+        families
+    }
+}
+
+/// `Ok(added)`, or an error if no faces were added.
+#[cfg(feature = "std")]
+fn not_a_font(added: usize) -> io::Result<usize> {
+    if added == 0 {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a font file"));
+    }
+    Ok(added)
+}
+
+/// Return true if the file's extension is one of a font's.
+#[cfg(feature = "std")]
+fn is_font_file(path: &Path) -> bool {
+    path.extension().and_then(|ext| ext.to_str()).is_some_and(|ext: &str| {
+        ["ttf", "otf", "ttc", "otc"].iter().any(|font: &&str| ext.eq_ignore_ascii_case(font))
+    })
+}
+
+/// Where this platform keeps its fonts.
+#[cfg(feature = "std")]
+fn system_font_dirs() -> Vec<PathBuf> {
+    let home: Option<PathBuf> = std::env::var_os("HOME").map(PathBuf::from);
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    if cfg!(target_os = "windows") {
+        let windows: PathBuf = std::env::var_os("WINDIR").map_or(PathBuf::from("C:\\Windows"), PathBuf::from);
+        dirs.push(windows.join("Fonts"));
+        if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+            dirs.push(PathBuf::from(local).join("Microsoft\\Windows\\Fonts"));
+        }
+    } else if cfg!(target_os = "macos") {
+        dirs.extend(["/System/Library/Fonts", "/Library/Fonts"].map(PathBuf::from));
+        dirs.extend(home.map(|home: PathBuf| home.join("Library/Fonts")));
+    } else {
+        dirs.extend(["/usr/share/fonts", "/usr/local/share/fonts"].map(PathBuf::from));
+        let data_home: Option<PathBuf> = std::env::var_os("XDG_DATA_HOME").map(PathBuf::from)
+            .or_else(|| home.as_ref().map(|home: &PathBuf| home.join(".local/share")));
+        dirs.extend(data_home.map(|data: PathBuf| data.join("fonts")));
+        dirs.extend(home.map(|home: PathBuf| home.join(".fonts")));
+    }
+    dirs
+}
@@ -0,0 +1,345 @@
+//! Form controls: text fields, buttons, checkboxes, and the like.
+
+use crate::{css, dom, layout, math, painting, style};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+
+/*
+    Form Controls
+
+    `<input>`, `<button>`, `<textarea>`, and `<select>` don't look like anything CSS
+    describes: a checkbox is a little box that may have a tick in it, a text field is a
+    sunken box with a caret. Browsers draw them as "native" widgets, and lay them out like
+    replaced elements (see `layout::Replaced`): each has an intrinsic size, worked out
+    from its attributes, and draws its own contents instead of laying out its children.
+
+    Sizes that depend on text use a fixed average character width, rather than measuring
+    the text with the page's fonts, and the text inside controls (values, labels, and
+    options) isn't painted.
+
+        control                     intrinsic size (px)
+        <input> (text, etc.)        `size` (default 20) characters wide, one line high
+        <input type=checkbox>       13 x 13
+        <input type=radio>          13 x 13
+        <input type=button> etc.    its `value`, plus padding
+        <button>                    its text, plus padding
+        <textarea>                  `cols` (default 20) by `rows` (default 2) characters
+        <select>                    its longest option, plus room for the arrow
+
+    A control's state lives in the DOM, so it survives serializing and diffing: a
+    checkbox is ticked if it has a `checked` attribute, and a text field's value is its
+    `value` attribute (or, for a `<textarea>`, its text). `Page` changes them as the user
+    clicks and types.
+ */
+
+/// The kinds of form control, with what their size and look depend on.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Control {
+    TextInput { size: usize },
+    TextArea { cols: usize, rows: usize },
+    Button { label_len: usize },
+    CheckBox { checked: bool },
+    Radio { checked: bool },
+    Select { label_len: usize },
+}
+
+/// The average width of a character, in px.
+pub const CHAR_WIDTH: f32 = 7.0;
+
+/// The height of a line of text, in px.
+pub const LINE_HEIGHT: f32 = 16.0;
+
+/// Padding between a control's edge and its text, in px.
+const PADDING: f32 = 3.0;
+
+/// The control a styled node is, if it is one.
+pub fn control(style_node: &style::StyledNode) -> Option<Control> {
+    let element: &dom::Element = match style_node.node.node_type {
+        dom::NodeType::Element(ref element) => element,
+        _ => return None,
+    };
+    let number = |name: &str, default: usize| -> usize {
+        element.attr(name).and_then(|v: &str| v.trim().parse().ok()).filter(|&n: &usize| n > 0).unwrap_or(default)
+    };
+    Some(match element.tag_name.as_str() {
+        "input" => match input_type(element).as_str() {
+            "hidden" => return None,
+            "checkbox" => Control::CheckBox { checked: element.attr("checked").is_some() },
+            "radio" => Control::Radio { checked: element.attr("checked").is_some() },
+            "button" | "submit" | "reset" => {
+                let default: &str = match input_type(element).as_str() {
+                    "submit" => "Submit",
+                    "reset" => "Reset",
+                    _ => "",
+                };
+                Control::Button { label_len: element.attr("value").unwrap_or(default).chars().count() }
+            }
+            _ => Control::TextInput { size: number("size", 20) },
+        },
+        "button" => Control::Button { label_len: collapsed_len(&style_node.inner_text()) },
+        "textarea" => Control::TextArea { cols: number("cols", 20), rows: number("rows", 2) },
+        "select" => {
+            let label_len: usize = style_node.children.iter()
+                .filter(|child: &&style::StyledNode| is_element(&child.node, "option"))
+                .map(|option: &style::StyledNode| collapsed_len(&option.inner_text()))
+                .max()
+                .unwrap_or(0);
+            Control::Select { label_len }
+        }
+        _ => return None,
+    })
+}
+
+/// The lowercased `type` of an `<input>`, defaulting to "text".
+fn input_type(element: &dom::Element) -> String {
+    element.attr("type").unwrap_or("text").trim().to_ascii_lowercase()
+}
+
+fn is_element(node: &dom::Node, tag_name: &str) -> bool {
+    matches!(node.node_type, dom::NodeType::Element(ref element) if element.tag_name == tag_name)
+}
+
+/// The number of characters in `text` once its whitespace is collapsed.
+fn collapsed_len(text: &str) -> usize {
+    text.split_whitespace().map(|word: &str| word.chars().count() + 1).sum::<usize>().saturating_sub(1)
+}
+
+impl Control {
+    /// The size the control takes up when CSS doesn't say, in px.
+    pub fn intrinsic_size(self) -> (f32, f32) {
+        let line: f32 = LINE_HEIGHT + 2.0 * PADDING;
+        match self {
+            Control::TextInput { size } => (size as f32 * CHAR_WIDTH + 2.0 * PADDING, line),
+            Control::TextArea { cols, rows } => (cols as f32 * CHAR_WIDTH + 2.0 * PADDING, rows as f32 * LINE_HEIGHT + 2.0 * PADDING),
+            Control::Button { label_len } => (label_len as f32 * CHAR_WIDTH + 4.0 * PADDING, line),
+            Control::CheckBox { .. } | Control::Radio { .. } => (13.0, 13.0),
+            Control::Select { label_len } => (label_len as f32 * CHAR_WIDTH + 2.0 * PADDING + line, line),
+        }
+    }
+}
+
+
+/*
+    Finding and Changing Controls
+
+    A click usually lands on a control's text, not the control itself, so lookups walk up
+    to the nearest control. Disabled controls ignore the user.
+ */
+
+/// `node` or its nearest ancestor that is an enabled form control.
+pub fn control_element(tree: &dom::Tree, node: dom::NodeId) -> Option<dom::NodeId> {
+    tree.ancestors(node).find(|&ancestor: &dom::NodeId| match tree[ancestor].node_type {
+        dom::NodeType::Element(ref element) => {
+            matches!(element.tag_name.as_str(), "input" | "button" | "textarea" | "select")
+                && element.attr("disabled").is_none()
+                && !(element.tag_name == "input" && input_type(element) == "hidden")
+        }
+        _ => false,
+    })
+}
+
+/// Return true if `node` is a text field: a `<textarea>`, or an `<input>` that takes text.
+pub fn is_text_field(tree: &dom::Tree, node: dom::NodeId) -> bool {
+    match tree[node].node_type {
+        dom::NodeType::Element(ref element) => match element.tag_name.as_str() {
+            "textarea" => true,
+            "input" => !matches!(input_type(element).as_str(),
+                "hidden" | "checkbox" | "radio" | "button" | "submit" | "reset" | "image" | "file"),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Return true if `node` is a checkbox or radio button.
+pub fn is_checkable(tree: &dom::Tree, node: dom::NodeId) -> bool {
+    matches!(tree[node].node_type, dom::NodeType::Element(ref element)
+        if element.tag_name == "input" && matches!(input_type(element).as_str(), "checkbox" | "radio"))
+}
+
+/// The value of a text field.
+pub fn value(document: &dom::Document, node: dom::NodeId) -> String {
+    match document.tree[node].node_type {
+        dom::NodeType::Element(ref element) if element.tag_name == "textarea" => document.tree.text_content(node),
+        dom::NodeType::Element(ref element) => element.attr("value").unwrap_or("").to_string(),
+        _ => String::new(),
+    }
+}
+
+/// Change the value of a text field.
+pub fn set_value(document: &mut dom::Document, node: dom::NodeId, value: &str) {
+    if is_element(&document.tree[node], "textarea") {
+        let children: Vec<dom::NodeId> = document.tree.children(node).collect();
+        for child in children {
+            document.remove_child(node, child);
+        }
+        let text: dom::NodeId = document.tree.text(value.to_string());
+        document.append_child(node, text);
+    } else {
+        document.set_attribute(node, "value", value);
+    }
+}
+
+/// Tick or untick a checkbox or radio button. Ticking a radio button unticks the others
+/// with the same `name`.
+pub fn set_checked(document: &mut dom::Document, node: dom::NodeId, checked: bool) {
+    if !checked {
+        document.remove_attribute(node, "checked");
+        return;
+    }
+    let group: Option<String> = match document.tree[node].node_type {
+        dom::NodeType::Element(ref element) if input_type(element) == "radio" => element.attr("name").map(str::to_string),
+        _ => None,
+    };
+    if let Some(group) = group {
+        let radios: Vec<dom::NodeId> = document.get_elements_by_tag_name("input").into_iter()
+            .filter(|&other: &dom::NodeId| other != node && match document.tree[other].node_type {
+                dom::NodeType::Element(ref element) => input_type(element) == "radio" && element.attr("name") == Some(group.as_str()),
+                _ => false,
+            })
+            .collect();
+        for radio in radios {
+            document.remove_attribute(radio, "checked");
+        }
+    }
+    document.set_attribute(node, "checked", "");
+}
+
+/// Click a checkbox or radio button: a checkbox flips, and a radio button is ticked (a
+/// ticked one stays ticked). Returns true if anything changed.
+pub fn toggle_checked(document: &mut dom::Document, node: dom::NodeId) -> bool {
+    let radio: bool = matches!(document.tree[node].node_type, dom::NodeType::Element(ref element) if input_type(element) == "radio");
+    let checked: bool = is_checked(&document.tree, node);
+    if radio && checked {
+        return false;
+    }
+    set_checked(document, node, !checked);
+    true
+}
+
+/// Return true if a checkbox or radio button is ticked.
+pub fn is_checked(tree: &dom::Tree, node: dom::NodeId) -> bool {
+    matches!(tree[node].node_type, dom::NodeType::Element(ref element) if element.attr("checked").is_some())
+}
+
+
+/*
+    Painting
+
+    Controls are drawn into their content box in the colors of a plain native theme:
+    a grey border around a white field (or a grey button face), with checked boxes and
+    radio buttons filled in blue. Without curves to draw with, a radio button's circle
+    and a checkbox's tick are built from one-pixel-high rows, which look right at the
+    intrinsic size and scale with zoom like everything else.
+ */
+
+const BORDER: css::Color = css::Color { r: 118, g: 118, b: 118, a: 255 };
+const FIELD: css::Color = css::Color { r: 255, g: 255, b: 255, a: 255 };
+const BUTTON_FACE: css::Color = css::Color { r: 239, g: 239, b: 239, a: 255 };
+const ACCENT: css::Color = css::Color { r: 0, g: 117, b: 255, a: 255 };
+const ARROW: css::Color = css::Color { r: 51, g: 51, b: 51, a: 255 };
+const FOCUS_RING: css::Color = css::Color { r: 0, g: 95, b: 204, a: 255 };
+const CARET: css::Color = css::Color { r: 0, g: 0, b: 0, a: 255 };
+
+/// The display commands drawing `control` into `rect`.
+pub fn paint_control(control: Control, rect: layout::Rect) -> painting::DisplayList {
+    let mut list: painting::DisplayList = Vec::new();
+    match control {
+        Control::TextInput { .. } | Control::TextArea { .. } => bordered_box(&mut list, rect, FIELD),
+        Control::Button { .. } => bordered_box(&mut list, rect, BUTTON_FACE),
+        Control::Select { .. } => {
+            bordered_box(&mut list, rect, FIELD);
+            // A downward triangle centered in a square at the right end.
+            let size: f32 = rect.height.min(rect.width);
+            let rows: usize = math::round(size / 4.0).max(1.0) as usize;
+            let (center_x, top) = (rect.x + rect.width - size / 2.0, rect.y + (rect.height - rows as f32) / 2.0);
+            for row in 0..rows {
+                let half: f32 = (rows - row) as f32;
+                list.push(painting::DisplayCommand::SolidColor(ARROW, layout::Rect {
+                    x: center_x - half, y: top + row as f32, width: 2.0 * half, height: 1.0,
+                }));
+            }
+        }
+        Control::CheckBox { checked } => {
+            if !checked {
+                bordered_box(&mut list, rect, FIELD);
+                return list;
+            }
+            list.push(painting::DisplayCommand::SolidColor(ACCENT, rect));
+            // A tick: down from the left third to the bottom third, then up to the right.
+            let unit: f32 = rect.width.min(rect.height) / 13.0;
+            let tick: [(f32, f32); 7] = [(3.0, 6.0), (4.0, 7.0), (5.0, 8.0), (6.0, 7.0), (7.0, 6.0), (8.0, 5.0), (9.0, 4.0)];
+            for (x, y) in tick {
+                list.push(painting::DisplayCommand::SolidColor(FIELD, layout::Rect {
+                    x: rect.x + x * unit, y: rect.y + y * unit, width: 2.0 * unit, height: 2.0 * unit,
+                }));
+            }
+        }
+        Control::Radio { checked } => {
+            let ring: css::Color = if checked { ACCENT } else { BORDER };
+            circle(&mut list, rect, 0.0, ring);
+            circle(&mut list, rect, rect.width.min(rect.height) / 13.0, FIELD);
+            if checked {
+                circle(&mut list, rect, rect.width.min(rect.height) * 3.0 / 13.0, ACCENT);
+            }
+        }
+    }
+    list
+}
+
+/// A box with a one-pixel border.
+fn bordered_box(list: &mut painting::DisplayList, rect: layout::Rect, fill: css::Color) {
+    list.push(painting::DisplayCommand::SolidColor(BORDER, rect));
+    list.push(painting::DisplayCommand::SolidColor(fill, layout::Rect {
+        x: rect.x + 1.0, y: rect.y + 1.0, width: (rect.width - 2.0).max(0.0), height: (rect.height - 2.0).max(0.0),
+    }));
+}
+
+/// A filled circle inscribed in `rect`, shrunk by `inset` on every side, one row at a time.
+fn circle(list: &mut painting::DisplayList, rect: layout::Rect, inset: f32, color: css::Color) {
+    let radius: f32 = rect.width.min(rect.height) / 2.0 - inset;
+    let (center_x, center_y) = (rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+    let mut y: f32 = math::floor(center_y - radius);
+    while y < center_y + radius {
+        let dy: f32 = (y + 0.5 - center_y).abs();
+        if dy < radius {
+            let half: f32 = math::sqrt(radius * radius - dy * dy);
+            list.push(painting::DisplayCommand::SolidColor(color, layout::Rect { x: center_x - half, y, width: 2.0 * half, height: 1.0 }));
+        }
+        y += 1.0;
+    }
+}
+
+/// A focus ring around the border box of the focused element, plus a caret at the end of
+/// `value` if it is a text field. (Typing always happens at the end of the value, so
+/// that's where the caret goes.)
+pub fn focus_decorations(border_box: layout::Rect, value: Option<&str>) -> painting::DisplayList {
+    let mut list: painting::DisplayList = Vec::new();
+    let w: f32 = 2.0;
+    let ring: layout::Rect = layout::Rect { x: border_box.x - w, y: border_box.y - w, width: border_box.width + 2.0 * w, height: border_box.height + 2.0 * w };
+    for edge in [
+        layout::Rect { height: w, ..ring },
+        layout::Rect { y: ring.y + ring.height - w, height: w, ..ring },
+        layout::Rect { width: w, ..ring },
+        layout::Rect { x: ring.x + ring.width - w, width: w, ..ring },
+    ] {
+        list.push(painting::DisplayCommand::SolidColor(FOCUS_RING, edge));
+    }
+    if let Some(value) = value {
+        let line: usize = value.matches('\n').count();
+        let column: usize = value.rsplit('\n').next().unwrap_or("").chars().count();
+        let inner: layout::Rect = layout::Rect {
+            x: border_box.x + PADDING,
+            y: border_box.y + PADDING,
+            width: (border_box.width - 2.0 * PADDING).max(0.0),
+            height: (border_box.height - 2.0 * PADDING).max(0.0),
+        };
+        let x: f32 = (inner.x + column as f32 * CHAR_WIDTH).min(inner.x + inner.width);
+        let y: f32 = (inner.y + line as f32 * LINE_HEIGHT).min(inner.y + inner.height);
+        let height: f32 = LINE_HEIGHT.min(inner.y + inner.height - y);
+        list.push(painting::DisplayCommand::SolidColor(CARET, layout::Rect { x, y, width: 1.0, height }));
+    }
+    list
+}
@@ -1,15 +1,25 @@
 //! A simple parser for a tiny subset of HTML.
 //!
-//! Can parse basic opening and closing tags, and text nodes.
+//! Can parse basic opening and closing tags, void and self-closing tags, text nodes,
+//! comments, character references, and the raw text inside `<script>` and `<style>`, from
+//! a whole string or from chunks of one as they arrive (`Parser`). The tokens themselves
+//! are available too (`tokenize`).
 //!
 //! Not yet supported:
-//!     - Comments
-//!     - Doctypes and processing instructions
-//!     - Self-closing tags
-//!     - Non-well-formed markup
-//!     - Character entities
+//!     - Processing instructions
+//!     - Non-well-formed markup (beyond fixing up misnested tags and keeping what came
+//!       before a syntax error; see `parse_with_errors`)
 
+use crate::atom::Atom;
 use crate::dom;
+use crate::encoding;
+use crate::scan;
+use crate::trace::{stage, Stage};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use alloc::borrow::Cow;
+use core::error::Error;
+use core::fmt;
 
 
 /*
@@ -27,54 +37,876 @@ use crate::dom;
 
     The following syntax is allowed:
         - Balanced tags: <p>...</p>
-        - Attributes with quoted values: id="main"
+        - Attributes with double-quoted, single-quoted, or unquoted values, or none:
+          id="main" title='Hi' width=80 disabled
         - Text nodes: <em>world</em>
+        - Void elements, which never have contents or a closing tag: <br> <img src="x">
+        - Self-closing tags: <br/> <input />
+        - Character references in text and attribute values: &amp; &eacute; &#233; &#xE9;
+        - A doctype declaration before the root element: <!DOCTYPE html>
+        - Raw text in <script>, <style>, <textarea>, and <title>: <style>a > b {}</style>
+        - Leaving out <html>, <head>, and <body>: <title>Hi</title><p>Hello</p>
+        - Comments, which are skipped: <!-- TODO -->
 
     Everything else is unsupported, including:
-        - Comments
-        - Doctype declarations
-        - Escaped characters (like &amp;) and CDATA sections
-        - Self-closing tags: <br/> or <br> with no closing tag
-        - Error handling (e.g. unbalanced or improperly nested tags)
+        - CDATA sections
+        - Most of the HTML spec's error recovery (all but the simple cases in "Misnested
+          Tags", below)
         - Namespaces and other XHTML syntax: <html:body>
         - Character encoding detection
 
     Let's walk through this HTML parser, keeping in mind that this is just one way to do it (and
     probably not the best way). Its structure is based loosely on the [tokenizer](https://github.com/servo/rust-cssparser/blob/032e7aed7acc31350fadbbc3eb5a9bbf6f4edb2e/src/tokenizer.rs)
-    module from Servo's [cssparser](https://github.com/servo/rust-cssparser) libaray.
-    It has no real error handling; in most cases, it just abouts when faced with unexpected syntax.
+    module from Servo's [cssparser](https://github.com/servo/rust-cssparser) libaray,
+    and split into a tokenizer and a tree builder (see "Tokens", below).
+    It has no real error recovery: faced with unexpected syntax, it gives up and returns a
+    `ParseError` saying what it expected and where. `parse_with_errors` is more forgiving,
+    fixing up misnested tags and keeping what it parsed before a syntax error (see
+    "Best-Effort Parsing", below).
 
-    The parser stores its input string and a current position within the string.
-    The position is the index of the next character we haven't processed yet.
+    The parser doesn't need its input all at once: it can be fed a page in chunks as they
+    arrive from the network (see "Parser State", below).
  */
 
+/// Why the parser gave up, and where.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub position: usize, // in bytes from the start of the input
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.position)
+    }
+}
+
+impl Error for ParseError {}
+
+
 /*
-    Default HTML Parser structure.
+    Tokens
 
-    input: A String type input parameter.
-    position: position of input.
+    Parsing happens in two stages, as it does in the HTML spec. The tokenizer reads the
+    source and splits it into tokens: a doctype, a tag, a run of text, a comment. The tree
+    builder takes the tokens in order and builds the document out of them, deciding which
+    element each node goes in:
 
-    e.g.
+        <p class="x">Hello <b>world</b></p>
+            -> StartTag p {class: x}, Text "Hello ", StartTag b, Text "world", EndTag b,
+               EndTag p, Eof
+
+    The tokenizer knows nothing about the tree: it doesn't know which elements are open,
+    or whether a closing tag matches one. That's the tree builder's job, and keeping it
+    separate means the rules for where nodes go can change without touching the syntax.
+    `tokenize` gives the tokens themselves, for programs that want the markup without a
+    tree (a syntax highlighter, say, or a link checker that only looks at `<a>` tags).
+
+    Character references are already decoded in a token's text and attribute values, and
+    the white space before each token is skipped, unless the parser keeps it (see
+    "Whitespace", below).
+ */
+/// A piece of HTML source, as the tokenizer reads it. See "Tokens", above.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token<'input> {
+    /// `<!DOCTYPE html>`: the name, in lowercase, and the public and system identifiers.
+    Doctype { name: Option<String>, public_id: Option<String>, system_id: Option<String> },
+    /// `<p class="x">`, or `<br/>` if `self_closing`.
+    StartTag { name: Atom, attributes: dom::AttributeMap, self_closing: bool },
+    /// `</p>`.
+    EndTag { name: Atom },
+    /// Text, with its character references decoded.
+    Text(Cow<'input, str>),
+    /// `<!-- ... -->`: the text between the dashes.
+    Comment(&'input str),
+    /// The end of the input.
+    Eof,
+}
+
+/// Splits HTML source into `Token`s. See "Tokens", above, and "Reading the Input", below.
+pub struct Tokenizer<'input> {
+    cursor: Cursor<'input>,
+    state: TokenizerState,
+    finished: bool,     // no more input is coming after this
+    done: bool,         // the end of the input, or an error, has been returned
+    token_start: usize, // where the last token read starts, after the white space before it
+}
+
+/// What a tokenizer needs to remember between chunks of input.
+#[derive(Clone, Copy, Default)]
+struct TokenizerState {
+    started: bool,          // past the point where a doctype may come
+    raw_text: Option<Atom>, // inside this raw text element, whose closing tag hasn't come yet
+    keep_whitespace: bool,  // white space before a token is text, rather than skipped
+}
+
+/// Split a whole HTML document into tokens, ending with `Token::Eof`, or with the first
+/// error in the markup.
+pub fn tokenize(source: &str) -> Tokenizer<'_> {
+    Tokenizer::resume(source, TokenizerState::default(), true)
+}
+
+impl<'input> Iterator for Tokenizer<'input> {
+    type Item = Result<Token<'input>, ParseError>;
+
+    fn next(&mut self) -> Option<Result<Token<'input>, ParseError>> {
+        if self.done {
+            return None;
+        }
+        // With all of the input, there's always a next token.
+        let token: Result<Token<'input>, ParseError> = self.next_token().map(|token: Option<Token>| token.unwrap_or(Token::Eof));
+        self.done = matches!(token, Ok(Token::Eof) | Err(_));
+        Some(token)
+    }
+}
+
+
+/*
+    Parser State
+
+    Pages arrive over the network a packet at a time, and a browser starts building the
+    document before the last one comes in. So this parser doesn't need all of its input
+    at once: `feed` gives it the next chunk, it parses as much as it can, and `finish`
+    says there is no more.
+
+        let mut parser: Parser = Parser::new();
+        parser.feed("<html><body><p>Hel")?;
+        parser.feed("lo</p></body></html>")?;
+        let document: dom::Document = parser.finish()?;
+
+    A chunk can end anywhere, even in the middle of a tag or a character reference, so
+    the parser can't be a set of functions that each parse a whole element, with the call
+    stack remembering which elements they're inside: the call stack is gone when `feed`
+    returns. Instead the parser keeps what it needs between chunks:
+        - in a `TokenizerState`, whether it's past the doctype, and the raw text element
+          it's inside, if any (see "Raw Text", below)
+        - in a `TreeBuilder`, the stack of open elements, whose closing tags haven't come
+          yet, the nodes at the top level, outside every element, and the doctype
+    along with the input it was given but hasn't tokenized yet. A token that runs into
+    the end of the input so far is left for the next chunk, so every token is read whole,
+    just as if the input had come all at once. Text waits for the `<` after it, since the
+    next chunk might carry on where it left off, and the text of a raw text element waits
+    for its closing tag.
+
+    `parse` and the other functions below feed the whole source in one chunk.
+
+    e.g. after the first `feed` above
         Parser {
-            input: "...",
+            buffer: "Hel",
+            offset: 15,
+            tokenizer: TokenizerState { started: true, raw_text: None, keep_whitespace: false },
+            builder: TreeBuilder { open: [html, body, p], top_level: [html], doctype: None, ... },
+            ...
+        }
+ */
+/// An HTML parser that takes its input in chunks. See "Parser State", above.
+pub struct Parser {
+    buffer: String,                       // input that hasn't been tokenized yet
+    offset: usize,                        // how much input came before `buffer`, for error positions
+    tokenizer: TokenizerState,            // where the tokenizer left off
+    builder: TreeBuilder,                 // the document so far
+    failed: Option<ParseError>,           // why parsing stopped, when not recovering
+    located: Option<dom::SourcePosition>, // how far source positions have been counted, if they're tracked
+    whitespace: Whitespace,               // what happens to the white space in text
+}
+
+/// Builds a document out of tokens. See "Building the Tree", below.
+struct TreeBuilder {
+    tree: dom::Tree,                                    // the arena that parsed nodes are added to
+    open: Vec<dom::NodeId>,                             // elements whose closing tag hasn't come yet, outermost first
+    top_level: Vec<dom::NodeId>,                        // nodes outside every element
+    doctype: Option<(Option<String>, dom::QuirksMode)>, // `None` unless the document has one
+    stopped: bool,                                      // the rest of the input is ignored
+    recover: bool,                                      // carry on after errors; see `parse_with_errors`
+    errors: Vec<ParseError>,                            // what went wrong, when recovering
+    reopen: Vec<dom::NodeId>,                           // formatting elements closed early, to reopen before the next text
+}
+
+/// How deeply elements may nest. Styling and layout recurse once per level, so deeper
+/// input is an error rather than a stack overflow.
+pub const MAX_DEPTH: usize = 512;
+
+/// Elements that can't have contents, so they're never closed: `<br>` is a whole element.
+/// Tag names are matched case-insensitively.
+pub const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr",
+];
+
+/// Whether `tag_name` is one of the `VOID_ELEMENTS`.
+pub fn is_void_element(tag_name: &str) -> bool {
+    VOID_ELEMENTS.iter().any(|void: &&str| tag_name.eq_ignore_ascii_case(void))
+}
+
+/// Elements whose contents are text, not markup, each with whether its character
+/// references are decoded. See "Raw Text", below.
+pub const RAW_TEXT_ELEMENTS: [(&str, bool); 4] = [("script", false), ("style", false), ("textarea", true), ("title", true)];
+
+/// Whether `tag_name` is one of the `RAW_TEXT_ELEMENTS`, and if so, whether its character
+/// references are decoded.
+pub fn raw_text_kind(tag_name: &str) -> Option<bool> {
+    RAW_TEXT_ELEMENTS.iter().find(|&&(name, _): &&(&str, bool)| tag_name.eq_ignore_ascii_case(name)).map(|&(_, decode): &(&str, bool)| decode)
+}
+
+impl Default for Parser {
+    fn default() -> Parser {
+        Parser::new()
+    }
+}
+
+impl Parser {
+    /// Create a parser that fails at the first error in the markup.
+    pub fn new() -> Parser {
+        Parser::with_recovery(false)
+    }
+
+    /// Create a parser that keeps what it parsed when it finds an error in the markup,
+    /// and ignores the rest, as `parse_with_errors` does. Its `feed` and `finish` never
+    /// fail; `errors` says what went wrong.
+    pub fn best_effort() -> Parser {
+        Parser::with_recovery(true)
+    }
+
+    fn with_recovery(recover: bool) -> Parser {
+        let builder: TreeBuilder = TreeBuilder {
+            tree: dom::Tree::new(),
+            open: Vec::new(),
+            top_level: Vec::new(),
+            doctype: None,
+            stopped: false,
+            recover,
+            errors: Vec::new(),
+            reopen: Vec::new(),
+        };
+        Parser { buffer: String::new(), offset: 0, tokenizer: TokenizerState::default(), builder, failed: None, located: None, whitespace: Whitespace::Trim }
+    }
+
+    /// Record where in the source each element and text node came from, in its `source`
+    /// (see "Source Positions" in `dom`). This has to be asked for before the parser is
+    /// fed any input.
+    pub fn with_source_positions(mut self) -> Parser {
+        assert!(self.offset == 0 && self.buffer.is_empty(), "source positions must be tracked from the start of the input");
+        self.located = Some(dom::SourcePosition { offset: 0, line: 1, column: 1 });
+        self
+    }
+
+    /// Treat the white space in text as `whitespace` says, rather than trimming it (see
+    /// "Whitespace", below). This has to be asked for before the parser is fed any input.
+    pub fn with_whitespace(mut self, whitespace: Whitespace) -> Parser {
+        assert!(self.offset == 0 && self.buffer.is_empty(), "white space must be handled the same way from the start of the input");
+        self.whitespace = whitespace;
+        self.tokenizer.keep_whitespace = whitespace != Whitespace::Trim;
+        self
+    }
+
+    /// Parse the next chunk of input, as far as it can be parsed so far.
+    pub fn feed(&mut self, chunk: &str) -> Result<(), ParseError> {
+        if let Some(ref err) = self.failed {
+            return Err(err.clone());
+        }
+        self.buffer.push_str(chunk);
+        self.parse_buffer(false)
+    }
+
+    /// The errors a `best_effort` parser has found so far.
+    pub fn errors(&self) -> &[ParseError] {
+        &self.builder.errors
+    }
+
+    /// Parse the rest of the input and return the document, with `<html>`, `<head>`, and
+    /// `<body>` elements whether the source had them or not (see "Implicit Elements").
+    pub fn finish(self) -> Result<dom::Document, ParseError> {
+        self.finish_document().map(|(document, _)| document)
+    }
+
+    /// `finish`, also returning the errors a `best_effort` parser found.
+    fn finish_document(mut self) -> Result<(dom::Document, Vec<ParseError>), ParseError> {
+        let stage: Stage = stage!("html::parse", bytes, nodes);
+        self.finish_nodes()?;
+        stage.record("bytes", || self.offset);
+        let TreeBuilder { mut tree, top_level, doctype, errors, .. } = self.builder;
+        let root: dom::NodeId = build_document(&mut tree, top_level);
+        if self.whitespace == Whitespace::Collapse {
+            collapse_whitespace(&mut tree, root);
+        }
+        stage.record("nodes", || tree.len());
+        let mut document: dom::Document = dom::Document::new(tree, root);
+        (document.doctype, document.quirks_mode) = doctype.unwrap_or((None, dom::QuirksMode::Quirks));
+        Ok((document, errors))
+    }
+
+    /// Parse the rest of the input.
+    fn finish_nodes(&mut self) -> Result<(), ParseError> {
+        if let Some(ref err) = self.failed {
+            return Err(err.clone());
+        }
+        self.parse_buffer(true)
+    }
+
+    /// Tokenize the buffered input and build the tree out of the tokens, as far as the
+    /// input goes. With `finished`, no more input is coming, so a token that runs to the
+    /// end is all there is.
+    fn parse_buffer(&mut self, finished: bool) -> Result<(), ParseError> {
+        let mut tokenizer: Tokenizer = Tokenizer::resume(&self.buffer, self.tokenizer, finished);
+        let mut error: Option<ParseError> = None;
+        while !self.builder.stopped {
+            let start: usize = self.offset + tokenizer.position();
+            let token: Token = match tokenizer.next_token() {
+                Ok(Some(token)) => token,
+                Ok(None) => break,
+                Err(err) => {
+                    error = Some(ParseError { position: self.offset + err.position, ..err });
+                    break;
+                }
+            };
+            let end: bool = token == Token::Eof;
+            let (offset, buffer) = (self.offset, self.buffer.as_str());
+            let span: Option<dom::SourceSpan> = self.located.as_mut().map(|located: &mut dom::SourcePosition| {
+                let start: dom::SourcePosition = advance(*located, &buffer[located.offset - offset..tokenizer.token_start]);
+                let end: dom::SourcePosition = advance(start, &buffer[tokenizer.token_start..tokenizer.position()]);
+                *located = end;
+                dom::SourceSpan { start, end }
+            });
+            if let Err(err) = self.builder.process(token, start, span) {
+                error = Some(err);
+                break;
+            }
+            if end {
+                break;
+            }
+        }
+        let consumed: usize = if self.builder.stopped || error.is_some() { self.buffer.len() } else { tokenizer.position() };
+        self.tokenizer = tokenizer.state;
+        if let Some(ref mut located) = self.located {
+            *located = advance(*located, &self.buffer[located.offset - self.offset..consumed]);
+        }
+        self.buffer.drain(..consumed);
+        self.offset += consumed;
+        match error {
+            Some(err) => self.stop(err),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Where the source gets to after `text`, which starts at `position`.
+fn advance(position: dom::SourcePosition, text: &str) -> dom::SourcePosition {
+    let mut position: dom::SourcePosition = position;
+    for c in text.chars() {
+        if c == '\n' {
+            (position.line, position.column) = (position.line + 1, 1);
+        } else {
+            position.column += 1;
+        }
+    }
+    position.offset += text.len();
+    position
+}
+
+
+/*
+    Whitespace
+
+    Markup is indented for people to read, so there's white space between most tags:
+
+        <ul>
+          <li>One <b>two</b> <i>three</i></li>
+        </ul>
+
+    Most of it doesn't matter, but some does: the space between "two" and "three", say,
+    or everything in a `<pre>`. What a parser does with it is up to its `Whitespace`:
+        Trim        the white space before every token is skipped, so text never starts
+                    with it, and no text is only white space. This is the default, and it
+                    keeps the tree uncluttered, but it loses the space in `</b> <i>` too.
+                    -> ul: [li: ["One ", b: ["two"], i: ["three"]]]
+        Preserve    all of the text is kept, as written, and every newline between tags is
+                    a text node of its own.
+                    -> ul: ["\n  ", li: ["One ", b: ["two"], " ", i: ["three"]], "\n"]
+        Collapse    the white space that could show is kept, and the rest is left out, as
+                    the document is finished:
+                        - text that's only white space, with a block on each side (or
+                          nothing, at the start or end of its parent), is dropped
+                        - every other run of white space is collapsed to one space
+                    -> ul: [li: ["One ", b: ["two"], " ", i: ["three"]]]
+
+    With Collapse, the tree holds the text that `white-space: normal` would show (see
+    `text::collapse_white_space`), without the empty anonymous boxes that the white
+    space between blocks would otherwise make in the layout tree. Which elements are
+    blocks is decided by tag name (`BLOCK_ELEMENTS`), since the parser has no stylesheet;
+    the elements in `<head>` count too, as they aren't shown. The text in the
+    `PREFORMATTED_ELEMENTS` is kept as it's written.
+ */
+/// What a parser does with the white space in text. See "Whitespace", above.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Whitespace {
+    #[default]
+    Trim,     // skip the white space before every token
+    Preserve, // keep all of it
+    Collapse, // keep one space wherever white space could show
+}
+
+/// Elements that are laid out as blocks, for parsers that collapse white space. Tag names
+/// are matched case-insensitively.
+pub const BLOCK_ELEMENTS: [&str; 46] = [
+    "address", "article", "aside", "base", "blockquote", "body", "caption", "dd", "details", "div", "dl", "dt",
+    "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "head", "header",
+    "hr", "html", "li", "link", "main", "meta", "nav", "ol", "p", "pre", "script", "section", "style", "summary",
+    "table", "tbody", "td", "tfoot", "th", "title", "ul",
+];
+
+/// Elements whose text keeps its white space as written.
+pub const PREFORMATTED_ELEMENTS: [&str; 6] = ["listing", "plaintext", "pre", "script", "style", "textarea"];
+
+/// Whether `tag_name` is one of the `BLOCK_ELEMENTS`.
+pub fn is_block_element(tag_name: &str) -> bool {
+    BLOCK_ELEMENTS.iter().any(|block: &&str| tag_name.eq_ignore_ascii_case(block))
+}
+
+/// Drop the white space between blocks under `root`, and collapse the rest. See
+/// "Whitespace", above.
+fn collapse_whitespace(tree: &mut dom::Tree, root: dom::NodeId) {
+    let is_block = |tree: &dom::Tree, node: Option<dom::NodeId>| -> bool {
+        node.is_none_or(|node: dom::NodeId| matches!(tree[node].node_type, dom::NodeType::Element(ref element) if is_block_element(&element.tag_name)))
+    };
+    let texts: Vec<dom::NodeId> = tree.descendants(root).filter(|&node: &dom::NodeId| matches!(tree[node].node_type, dom::NodeType::Text(_))).collect();
+    for node in texts {
+        let preformatted: bool = tree.ancestors(node).any(|ancestor: dom::NodeId| matches!(tree[ancestor].node_type,
+            dom::NodeType::Element(ref element) if PREFORMATTED_ELEMENTS.iter().any(|name: &&str| element.tag_name.eq_ignore_ascii_case(name))));
+        let dom::NodeType::Text(ref text) = tree[node].node_type else { continue };
+        if preformatted {
+            continue;
+        }
+        if text.chars().all(|c: char| c.is_ascii_whitespace()) && is_block(tree, tree[node].prev_sibling) && is_block(tree, tree[node].next_sibling) {
+            tree.detach(node);
+            continue;
+        }
+        let mut collapsed: String = String::with_capacity(text.len());
+        for c in text.chars() {
+            if !c.is_ascii_whitespace() {
+                collapsed.push(c);
+            } else if !collapsed.ends_with(' ') {
+                collapsed.push(' ');
+            }
+        }
+        tree[node].node_type = dom::NodeType::Text(collapsed);
+    }
+}
+
+
+/*
+    Best-Effort Parsing
+
+    A browser never refuses to show a page, however broken its markup. A `best_effort`
+    parser doesn't either. Tags in the wrong order are fixed up as it goes (see
+    "Misnested Tags", below). At the first error in the syntax itself, it records the
+    error and stops reading, as if the input ended there. Every element that's still open
+    is closed where it stands, so the tree holds everything parsed up to the error, and
+    only the token being read when it happened (an unfinished tag, say) is dropped.
+
+    e.g.
+        <div><p>One</p><p>Two <b id=">Three</p></div>
+            -> <div><p>One</p><p>Two </p></div>, with the error at the unclosed quote
+
+    Each tag fixed up along the way records an error of its own, and so does the error
+    that stopped the parser, so there can be several: `<P>x</p>` has two, for the `</p>`
+    that doesn't close the `<P>`, and for the `<P>` still open at the end. The elements
+    closed early when parsing stops would each report a missing closing tag too, but
+    those are consequences of the error that stopped it, so they aren't recorded.
+ */
+impl Parser {
+    /// Stop parsing at `err`, ignoring the rest of the input. When recovering, the error
+    /// is recorded; otherwise it's returned, now and from every later call.
+    fn stop(&mut self, err: ParseError) -> Result<(), ParseError> {
+        self.builder.stopped = true;
+        if self.builder.recover {
+            self.builder.errors.push(err);
+            Ok(())
+        } else {
+            self.failed = Some(err.clone());
+            Err(err)
+        }
+    }
+}
+
+
+/*
+    Building the Tree
+
+    The tree builder puts each token in the tree: a text node or an element goes at the
+    end of the innermost open element (or the top level, if none is open), an element's
+    opening tag pushes it onto the stack of open elements, and its closing tag pops it off
+    again. Void and self-closing elements (see "Elements", below) are never left open, and
+    a closing tag for a void element, as in <img></img>, is ignored, as browsers do.
+
+    A closing tag has to match the innermost open element, and at the end of the input
+    no element may be open; anything else is an error (which a `best_effort` parser
+    recovers from; see "Misnested Tags", below), and so is a closing tag with no element
+    open, which a `best_effort` parser ignores. Comments are dropped, since the DOM has
+    nowhere to keep them.
+
+    By default the tokenizer skips the white space before each token, so text never starts
+    with it, and the document's structure isn't cluttered with the indentation between
+    tags (see "Whitespace", above).
+ */
+impl TreeBuilder {
+    /// Put `token`, which starts at `position` in the input, in the tree. `span` is where
+    /// the token is in the source, if source positions are tracked.
+    fn process(&mut self, token: Token, position: usize, span: Option<dom::SourceSpan>) -> Result<(), ParseError> {
+        let error = |message: String| -> ParseError { ParseError { message, position } };
+        match token {
+            Token::Doctype { name, public_id, system_id } => {
+                let mode: dom::QuirksMode = quirks_mode(name.as_deref().unwrap_or(""), public_id.as_deref(), system_id.as_deref());
+                self.doctype = Some((name, mode));
+            }
+            Token::StartTag { name, attributes, self_closing } => {
+                if self.open.len() == MAX_DEPTH {
+                    return Err(error(format!("Elements nested more than {} deep", MAX_DEPTH)));
+                }
+                let element: dom::NodeId = self.tree.element(name, attributes, Vec::new());
+                self.tree[element].source = span;
+                self.insert(element);
+                if !self_closing && !is_void_element(&name) {
+                    self.open.push(element);
+                }
+            }
+            Token::EndTag { name } => {
+                if is_void_element(&name) {
+                    return Ok(());
+                }
+                let element: dom::NodeId = match self.open.last() {
+                    Some(&element) => element,
+                    // Nothing is open, but a formatting element closed early is done with.
+                    None if self.reopen.iter().any(|&element: &dom::NodeId| tag_name(&self.tree, element) == name) => {
+                        self.close_misnested(name);
+                        return Ok(());
+                    }
+                    // Nothing is open for it to close, so it's ignored.
+                    None => {
+                        let err: ParseError = error(format!("Unexpected \"</{}>\"", name));
+                        if !self.recover {
+                            return Err(err);
+                        }
+                        self.errors.push(err);
+                        return Ok(());
+                    }
+                };
+                if tag_name(&self.tree, element) != name {
+                    let err: ParseError = error(format!("Expected \"</{}>\"", tag_name(&self.tree, element)));
+                    if !self.recover {
+                        return Err(err);
+                    }
+                    self.errors.push(err);
+                    self.close_misnested(name);
+                    return Ok(());
+                }
+                if let (Some(ref mut source), Some(span)) = (&mut self.tree[element].source, span) {
+                    source.end = span.end;
+                }
+                self.open.pop();
+            }
+            Token::Text(text) => {
+                self.reopen_formatting_elements();
+                let text: dom::NodeId = self.tree.text(text.into_owned());
+                self.tree[text].source = span;
+                self.insert(text);
+            }
+            Token::Comment(_) => {}
+            Token::Eof => {
+                if !self.open.is_empty() {
+                    // The innermost element's closing tag was due here.
+                    return Err(error("Expected \"</\"".to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Add `node` at the end of the innermost open element, or at the top level.
+    fn insert(&mut self, node: dom::NodeId) {
+        match self.open.last() {
+            Some(&parent) => self.tree.append_child(parent, node),
+            None => self.top_level.push(node),
+        }
+    }
+}
+
+/// The tag name of `element`, which the tree builder made.
+fn tag_name(tree: &dom::Tree, element: dom::NodeId) -> Atom {
+    match tree[element].node_type {
+        dom::NodeType::Element(ref element) => element.tag_name,
+        _ => unreachable!("only elements are opened"),
+    }
+}
+
+
+/*
+    Misnested Tags
+
+    Real pages close their tags in the wrong order, or not at all:
+
+        <p><b>bold</p>plain</b>         <div><span>text</div>
+
+    A `best_effort` parser records the error and fixes the tree up, in a simplified
+    version of what the HTML spec's tree builder does:
+        - A closing tag for an element that's open, but not innermost, closes every element
+          inside it too, as if their closing tags had come first.
+        - Unless it's the closing tag of a formatting element (`FORMATTING_ELEMENTS`) with
+          a block inside it, like the `</b>` in `<b><p>bold</b>plain</p>`. The block can't
+          just be closed, since the text after it is still in it. Instead the block is
+          moved out, after the formatting element, and what was in it is wrapped in a copy
+          of the formatting element, so only what came before the `</b>` is bold:
+              <b></b><p><b>bold</b>plain</p>
+          (The spec's "adoption agency algorithm" goes further, with formatting elements
+          between the block and the one being closed, but this is the common case.)
+        - Formatting elements closed early, like the `<b>` above, still apply to the text
+          after them. They're opened again, as copies, before the next text, so the first
+          example is `<p><b>bold</b></p><b>plain</b>`.
+        - A closing tag for an element that isn't open is ignored.
+    Elements still open at the end of the input are closed there, as for any other error.
+    Copies have no source position (see "Source Positions" in `dom`), since they aren't in
+    the source.
+ */
+/// Elements that style the text inside them, which a misnested closing tag doesn't end.
+/// See "Misnested Tags", above.
+pub const FORMATTING_ELEMENTS: [&str; 14] = ["a", "b", "big", "code", "em", "font", "i", "nobr", "s", "small", "strike", "strong", "tt", "u"];
+
+/// Whether `tag_name` is one of the `FORMATTING_ELEMENTS`.
+pub fn is_formatting_element(tag_name: &str) -> bool {
+    FORMATTING_ELEMENTS.iter().any(|formatting: &&str| tag_name.eq_ignore_ascii_case(formatting))
+}
+
+impl TreeBuilder {
+    /// Handle a closing tag for `name` that doesn't match the innermost open element.
+    fn close_misnested(&mut self, name: Atom) {
+        let index: usize = match self.open.iter().rposition(|&element: &dom::NodeId| tag_name(&self.tree, element) == name) {
+            Some(index) => index,
+            None => {
+                // An early-closed formatting element that was never reopened is done with.
+                if let Some(index) = self.reopen.iter().rposition(|&element: &dom::NodeId| tag_name(&self.tree, element) == name) {
+                    self.reopen.remove(index);
+                }
+                return;
+            }
+        };
+        let block: Option<usize> = self.open[index + 1..].iter()
+            .position(|&element: &dom::NodeId| !is_formatting_element(&tag_name(&self.tree, element)))
+            .map(|offset: usize| index + 1 + offset);
+        match block {
+            Some(block) if is_formatting_element(&name) => self.adopt(index, block),
+            _ => {
+                let closed: Vec<dom::NodeId> = self.open.split_off(index);
+                self.reopen.extend(closed[1..].iter().filter(|&&element: &&dom::NodeId| is_formatting_element(&tag_name(&self.tree, element))));
+            }
+        }
+    }
+
+    /// Close the formatting element at `index` in the stack of open elements, moving the
+    /// block at `block_index` out of it. See "Misnested Tags", above.
+    fn adopt(&mut self, index: usize, block_index: usize) {
+        let (formatting, block): (dom::NodeId, dom::NodeId) = (self.open[index], self.open[block_index]);
+        // Wrap the block's contents in copies of the formatting elements it's inside.
+        let closed: Vec<dom::NodeId> = self.open.drain(index..block_index).collect();
+        let children: Vec<dom::NodeId> = self.tree.children(block).collect();
+        let mut parent: dom::NodeId = block;
+        for element in closed {
+            let copy: dom::NodeId = self.copy_element(element);
+            self.tree.append_child(parent, copy);
+            parent = copy;
+        }
+        for child in children {
+            self.tree.detach(child);
+            self.tree.append_child(parent, child);
+        }
+        // Then move the block out, after the formatting element.
+        self.tree.detach(block);
+        match self.tree[formatting].parent {
+            Some(grandparent) => {
+                let next: Option<dom::NodeId> = self.tree[formatting].next_sibling;
+                self.tree.insert_before(grandparent, block, next);
+            }
+            None => {
+                let position: usize = self.top_level.iter().position(|&node: &dom::NodeId| node == formatting).map_or(self.top_level.len(), |position: usize| position + 1);
+                self.top_level.insert(position, block);
+            }
+        }
+    }
+
+    /// Open copies of the formatting elements closed early, before text that they still
+    /// apply to.
+    fn reopen_formatting_elements(&mut self) {
+        for element in core::mem::take(&mut self.reopen) {
+            if self.open.len() == MAX_DEPTH {
+                break;
+            }
+            let copy: dom::NodeId = self.copy_element(element);
+            self.insert(copy);
+            self.open.push(copy);
+        }
+    }
+
+    /// A new element with the same tag name and attributes as `element`, and nothing in it.
+    fn copy_element(&mut self, element: dom::NodeId) -> dom::NodeId {
+        let (name, attributes): (Atom, dom::AttributeMap) = match self.tree[element].node_type {
+            dom::NodeType::Element(ref element) => (element.tag_name, element.attributes.clone()),
+            _ => unreachable!("only elements are opened"),
+        };
+        self.tree.element(name, attributes, Vec::new())
+    }
+}
+
+
+/*
+    Reading the Input
+
+    The tokenizer reads the input through a `Cursor`: the unparsed input, borrowed for the
+    lifetime 'input, and a position within it. The position is the index of the next
+    character we haven't processed yet.
+
+    When a read runs into the end of the input, the cursor sets `cut_off`, so the
+    tokenizer can tell an error in the markup from a token that just hasn't all arrived
+    yet. It backs up to the start of such a token and waits for more input.
+
+    e.g.
+        Cursor {
+            input: "<p class=\"a\">Hello</p>",
             position: 0,
+            cut_off: false,
         }
  */
-struct Parser {
-    input: String,
+impl<'input> Tokenizer<'input> {
+    /// Tokenize `input`, starting in `state`. With `finished`, `input` is the rest of the
+    /// document.
+    fn resume(input: &'input str, state: TokenizerState, finished: bool) -> Tokenizer<'input> {
+        Tokenizer { cursor: Cursor::new(input), state, finished, done: false, token_start: 0 }
+    }
+
+    /// How far into its input the tokenizer has read, in bytes.
+    pub fn position(&self) -> usize {
+        self.cursor.position
+    }
+
+    /// Read the next token, or `None` if it runs past the end of the input so far.
+    fn next_token(&mut self) -> Result<Option<Token<'input>>, ParseError> {
+        let (position, state) = (self.cursor.position, self.state);
+        self.cursor.cut_off = false;
+        match self.read_token() {
+            Ok(Some(token)) => return Ok(Some(token)),
+            // A token cut off by the end of the input may be finished by the next chunk.
+            Err(_) if self.cursor.cut_off && !self.finished => {}
+            Err(err) => return Err(err),
+            Ok(None) => {}
+        }
+        (self.cursor.position, self.state) = (position, state);
+        Ok(None)
+    }
+
+    fn read_token(&mut self) -> Result<Option<Token<'input>>, ParseError> {
+        if let Some(tag_name) = self.state.raw_text {
+            return self.read_raw_text(tag_name);
+        }
+        let (cursor, finished) = (&mut self.cursor, self.finished);
+        // White space before a doctype is skipped either way.
+        if !self.state.keep_whitespace || !self.state.started {
+            cursor.consume_whitespace();
+        }
+        self.token_start = cursor.position;
+        if !self.state.started {
+            // Wait until there's enough input to tell whether a doctype comes first.
+            let rest: &str = cursor.rest();
+            if !finished && rest.len() < "<!doctype".len() && "<!doctype".starts_with(&rest.to_ascii_lowercase()) {
+                return Ok(None);
+            }
+            self.state.started = true;
+            if rest.get(..9).is_some_and(|start: &str| start.eq_ignore_ascii_case("<!doctype")) {
+                return cursor.parse_doctype().map(Some);
+            }
+        }
+
+        if cursor.eof() {
+            return Ok(finished.then_some(Token::Eof));
+        }
+        if cursor.starts_with("<!--") {
+            return cursor.parse_comment().map(Some);
+        }
+        if !finished && "<!--".starts_with(cursor.rest()) {
+            return Ok(None);
+        }
+        if cursor.starts_with("</") {
+            let name: Atom = cursor.parse_closing_tag()?;
+            return Ok(Some(Token::EndTag { name }));
+        }
+        if cursor.starts_with("<") {
+            let (name, attributes, self_closing) = cursor.parse_opening_tag()?;
+            if !self_closing && raw_text_kind(&name).is_some() {
+                self.state.raw_text = Some(name);
+            }
+            return Ok(Some(Token::StartTag { name, attributes, self_closing }));
+        }
+        let text: &str = cursor.consume_until(b'<');
+        if cursor.eof() && !finished {
+            return Ok(None);
+        }
+        Ok(Some(Token::Text(decode_entities(text, false))))
+    }
+
+    /// Read the text of the raw text element `tag_name`, or its closing tag if the text
+    /// has been read.
+    fn read_raw_text(&mut self, tag_name: Atom) -> Result<Option<Token<'input>>, ParseError> {
+        let decode: bool = raw_text_kind(&tag_name).unwrap_or(false);
+        self.token_start = self.cursor.position;
+        match self.cursor.parse_raw_text(&tag_name, decode, self.finished) {
+            None => Ok(None),
+            Some(text) if !text.is_empty() => Ok(Some(Token::Text(text))),
+            Some(_) => {
+                self.cursor.parse_raw_text_closing_tag(&tag_name)?;
+                self.state.raw_text = None;
+                Ok(Some(Token::EndTag { name: tag_name }))
+            }
+        }
+    }
+}
+
+struct Cursor<'input> {
+    input: &'input str,
     position: usize, // "usize" is an unsigned integer, similar to "size_t" in C
+    cut_off: bool,   // a read ran into the end of the input
 }
 
 
 /*
     We can use this to implement some simple methods for peeking at the next characters in the input.
-
-    Implemented HTML Parser based on Default HTML Parser
  */
-impl Parser {
+impl<'input> Cursor<'input> {
+    fn new(input: &'input str) -> Cursor<'input> {
+        Cursor { input, position: 0, cut_off: false }
+    }
+
     /// Read the current character without consuming it.
-    fn next_char(&self) -> char {
-        self.input[self.position..].chars().next().unwrap()
+    fn next_char(&mut self) -> Result<char, ParseError> {
+        match self.peek() {
+            Some(c) => Ok(c),
+            None => Err(self.end_of_input()),
+        }
+    }
+
+    /// Read the current character without consuming it, or `None` at the end of the input.
+    fn peek(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    /// The input that hasn't been consumed.
+    fn rest(&self) -> &'input str {
+        &self.input[self.position..]
+    }
+
+    /// An error at the current position.
+    fn error(&self, message: String) -> ParseError {
+        ParseError { message, position: self.position }
+    }
+
+    /// An error for running into the end of the input.
+    fn end_of_input(&mut self) -> ParseError {
+        self.cut_off = true;
+        self.error("Unexpected end of input".to_string())
     }
 
     /// Does the next characters start with the given string?
@@ -82,12 +914,16 @@ impl Parser {
         self.input[self.position..].starts_with(s)
     }
 
-    /// If the exact string `s` is found at the current position, consume it. Otherwise, panic.
-    fn expect(&mut self, s: &str) {
+    /// If the exact string `s` is found at the current position, consume it. Otherwise,
+    /// return an error.
+    fn expect(&mut self, s: &str) -> Result<(), ParseError> {
         if self.starts_with(s) {
             self.position += s.len();
+            Ok(())
         } else {
-            panic!("Expected {:?} at byte {} but it was not found", s, self.position);
+            // The input may have ended partway through `s`.
+            self.cut_off |= s.starts_with(self.rest());
+            Err(self.error(format!("Expected {:?}", s)))
         }
     }
 
@@ -98,31 +934,33 @@ impl Parser {
 
 
     /*
-        Rust strings are stored as [UTF-8](https://en.wikipedia.org/wiki/UTF-8) byte arrays.
-        To go to the next character, we can't just advance by one byte.
-     */
-
-    /// Return the current character, and advance `self.pos` to the next character.
-    fn consume_char(&mut self) -> char {
-        let c: char = self.next_char();
-        self.position += c.len_utf8();
-        c
-    }
+        Often we will want to consume a string of consecutive characters. The `consume` method
+        takes a function that says how many bytes to consume from the rest of the input, and
+        returns them. The functions we give it come from the `scan` module, which searches the
+        bytes directly instead of decoding one `char` at a time.
 
+        Rust strings are stored as [UTF-8](https://en.wikipedia.org/wiki/UTF-8) byte arrays,
+        so a position must never land in the middle of a character. Every byte we scan for
+        is ASCII, and ASCII bytes never appear inside a multi-byte character, so it won't.
+        (Chunks given to `feed` are `&str`s, so they can't split a character either.)
 
-    /*
-        Often we will want to consume a string of consecutive character. The `consume_while` method
-        consumes characters that meet a given condition, and returns them as a string.
-        This method's argument is a function that takes a char and returns a bool.
+        The characters consumed are a contiguous run of the input, so rather than copying them
+        into a new `String`, we return a slice of the input itself. Tag and attribute names
+        go straight from that slice into the atom table, so a name the document has used
+        before costs no allocation at all.
      */
 
-    /// Consume characters until `test` returns false.
-    fn consume_while(&mut self, test: impl Fn(char) -> bool) -> String {
-        let mut result: String = String::new();
-        while !self.eof() && test(self.next_char()) {
-            result.push(self.consume_char());
-        }
-        result
+    /// Consume the number of bytes `scan` returns for the rest of the input, returning them
+    /// as a slice of the input.
+    fn consume(&mut self, scan: impl Fn(&str) -> usize) -> &'input str {
+        let start: usize = self.position;
+        self.position += scan(&self.input[start..]);
+        &self.input[start..self.position]
+    }
+
+    /// Consume characters up to (but not including) the next `byte`, or to the end of the input.
+    fn consume_until(&mut self, byte: u8) -> &'input str {
+        self.consume(|rest: &str| scan::until(rest, byte))
     }
 
 
@@ -133,40 +971,884 @@ impl Parser {
 
     /// Consume and discard zero or more whitespace character.
     fn consume_whitespace(&mut self) {
-        self.consume_while(char::is_whitespace);
+        self.consume(scan::whitespace);
     }
 
     /// Parse a tag or attribute name.
-    fn parse_name(&mut self) -> String {
-        self.consume_while(|c: char| matches!(c, 'a'..='z' | 'A'..='Z' | '0'..='9'))
+    fn parse_name(&mut self) -> &'input str {
+        self.consume(|rest: &str| scan::ascii_run(rest, |byte: u8| byte.is_ascii_alphanumeric() || byte == b'-'))
     }
 
 
     /*
-        Now we're ready to start parsing HTML. To parse a single node,
-        we look at its first character to see if it is an element or a text node.
+        Elements
+
+        In our simplified version of HTML, a text node can contain any character expect "<".
+        Its character references are decoded (see "Character References", below). Anything
+        that starts with "<" is a tag.
+
+        An element includes opening and closing tags, and between them any number of child
+        nodes. Some elements, like <br> and <img>, can't have contents at all, so they have
+        no closing tag: the opening tag is the whole element. Any tag can also close itself
+        by ending with "/>", as in <br/> or <x-icon />, the way XML and SVG are written.
+        (Real HTML ignores the slash on anything but a void element; we take it at its
+        word instead, since the alternative would be an element that never closes.)
      */
 
-    /// Parse a single node.
-    fn parse_node(&mut self) -> dom::Node {
-        if self.starts_with("<") {
-            // parse element
-            dom::Node {
-                children: Vec::new(),
-                node_type: dom::NodeType::Text(String::new()),
+    /// Parse an opening tag, returning the element's name and attributes, and whether the
+    /// tag closed itself.
+    fn parse_opening_tag(&mut self) -> Result<(Atom, dom::AttributeMap, bool), ParseError> {
+        self.expect("<")?;
+        let tag_name: Atom = Atom::from(self.parse_name());
+        let attributes: dom::AttributeMap = self.parse_attributes()?;
+        let self_closing: bool = self.starts_with("/");
+        self.expect(if self_closing { "/>" } else { ">" })?;
+        Ok((tag_name, attributes, self_closing))
+    }
+
+    /// Parse a closing tag, returning the element's name. White space may come before
+    /// the `>`.
+    fn parse_closing_tag(&mut self) -> Result<Atom, ParseError> {
+        self.expect("</")?;
+        let start: usize = self.position;
+        let tag_name: &'input str = self.parse_name();
+        self.consume_whitespace();
+        self.expect(">")?;
+        if tag_name.is_empty() {
+            return Err(ParseError { message: "Expected a tag name".to_string(), position: start });
+        }
+        Ok(Atom::from(tag_name))
+    }
+
+
+    /*
+        Comments
+
+        A comment runs from `<!--` to the next `-->`, and may hold anything else,
+        including markup: `<!-- <p>old version</p> -->`. The tree builder drops them.
+     */
+
+    /// Parse a comment, returning the text between `<!--` and `-->`.
+    fn parse_comment(&mut self) -> Result<Token<'input>, ParseError> {
+        self.expect("<!--")?;
+        let text: &'input str = match self.rest().find("-->") {
+            Some(end) => &self.rest()[..end],
+            None => {
+                self.position = self.input.len();
+                return Err(self.end_of_input());
             }
-        } else {
-            self.parse_text()
+        };
+        self.position += text.len() + "-->".len();
+        Ok(Token::Comment(text))
+    }
+
+
+    /*
+        Raw Text
+
+        The contents of <script> and <style> aren't HTML: a script can compare with `<`
+        and a stylesheet can hold `a > b`, and neither means markup. So once their opening
+        tag is parsed, the parser switches to taking text verbatim, up to the closing tag
+        for the same element, which is the only thing that ends it (its name matched
+        case-insensitively, so </SCRIPT> ends a <script> too).
+
+        <textarea> and <title> work the same way, except that their character references
+        are decoded, since they hold text for people to read: this is RCDATA, as opposed
+        to the plain RAWTEXT of the others.
+
+        e.g.
+            <script>if (a < b && c) { x = "</p>"; }</script>
+                -> script, with one text child: `if (a < b && c) { x = "</p>"; }`
+            <textarea>&lt;b&gt; is bold</textarea>
+                -> textarea, with one text child: `<b> is bold`
+
+        Unlike other text, the white space at the start isn't skipped, and a raw text
+        element with no contents has no text child at all.
+     */
+
+    /// Parse the contents of a raw text element, up to (not including) its closing tag.
+    /// Character references are decoded if `decode` is set. Returns `None` if the closing
+    /// tag hasn't arrived yet.
+    fn parse_raw_text(&mut self, tag_name: &str, decode: bool, finished: bool) -> Option<Cow<'input, str>> {
+        let rest: &'input str = self.rest();
+        let name_end = |start: usize| -> usize { start + 2 + tag_name.len() };
+        let closes = |start: usize| -> bool {
+            rest.get(start + 2..name_end(start)).is_some_and(|name: &str| name.eq_ignore_ascii_case(tag_name))
+                && rest[name_end(start)..].bytes().next().is_none_or(|byte: u8| byte == b'>' || byte.is_ascii_whitespace())
+        };
+        let end: usize = match rest.match_indices("</").map(|(start, _)| start).find(|&start: &usize| closes(start)) {
+            // What follows the name decides whether it's the closing tag.
+            Some(start) if finished || name_end(start) < rest.len() => start,
+            None if finished => rest.len(),
+            _ => return None,
+        };
+        let text: &'input str = &rest[..end];
+        self.position += end;
+        Some(if decode { decode_entities(text, false) } else { Cow::Borrowed(text) })
+    }
+
+    /// Parse the closing tag of a raw text element, whose name may be in any case and be
+    /// followed by white space.
+    fn parse_raw_text_closing_tag(&mut self, tag_name: &str) -> Result<(), ParseError> {
+        if self.eof() {
+            return Err(self.error(format!("Expected \"</{}>\"", tag_name)));
         }
+        self.position += 2 + tag_name.len();
+        self.consume_whitespace();
+        self.expect(">")
     }
 
 
     /*
-        In our simplified version of HTML, a text node can contain any character expect "<".
+        Parsing attributes is pretty easy in our simplified syntax. Until we reach the end of
+        the opening tag (>) we repeatedly look for a name, optionally followed by = and a
+        value. The value can be in double or single quotes, or unquoted, in which case it
+        runs up to the next white space or >. An attribute with no value, like `disabled`,
+        has the empty string as its value, and if a name comes twice the first one counts,
+        as in browsers.
+
+        Text and attribute values are `Cow`s: borrowed from the input when the value is
+        exactly what's in the source, and owned only when it had to be rewritten (as
+        decoding `&amp;` does). The DOM keeps its own `String`s, since a page outlives
+        the source it was parsed from, so each one is copied once, into the tree, and
+        nowhere else.
+     */
+
+    /// Parse a single name="value" pair, or a name on its own.
+    fn parse_attribute(&mut self) -> Result<(Atom, Cow<'input, str>), ParseError> {
+        let name: &'input str = self.parse_name();
+        if name.is_empty() {
+            return Err(self.error("Expected an attribute name".to_string()));
+        }
+        self.consume_whitespace();
+        if !self.starts_with("=") {
+            return Ok((Atom::from(name), Cow::Borrowed("")));
+        }
+        self.expect("=")?;
+        self.consume_whitespace();
+        let value: Cow<'input, str> = self.parse_attribute_value()?;
+        Ok((Atom::from(name), value))
+    }
+
+    /// Parse a value in double or single quotes, or without quotes.
+    fn parse_attribute_value(&mut self) -> Result<Cow<'input, str>, ParseError> {
+        let value: &'input str = match self.peek() {
+            Some(quote @ ('"' | '\'')) => {
+                self.position += 1;
+                let value: &'input str = self.consume_until(quote as u8);
+                self.expect(if quote == '"' { "\"" } else { "'" })?;
+                value
+            }
+            _ => self.consume(|rest: &str| rest.find(|c: char| c.is_ascii_whitespace() || c == '>').unwrap_or(rest.len())),
+        };
+        Ok(decode_entities(value, true))
+    }
+
+    /// Parse a list of name="value" pairs, separated by whitespace, up to the `>` or `/>`
+    /// that ends the tag, in the order they're written.
+    fn parse_attributes(&mut self) -> Result<dom::AttributeMap, ParseError> {
+        let mut attributes: dom::AttributeMap = dom::AttributeMap::default();
+        loop {
+            self.consume_whitespace();
+            if self.next_char()? == '>' || self.starts_with("/>") {
+                break;
+            }
+            if self.rest() == "/" {
+                return Err(self.end_of_input());
+            }
+            let (name, value) = self.parse_attribute()?;
+            // A repeated attribute is dropped: the first one counts.
+            if !attributes.contains_key(&name) {
+                attributes.insert(name, value.into_owned());
+            }
+        }
+        Ok(attributes)
+    }
+
+
+    /*
+        Doctypes
+
+        A page starts with a doctype declaration, `<!DOCTYPE html>`. It isn't an element,
+        and today its only job is to pick the document's rendering mode: an HTML5 doctype
+        means "no quirks", while a missing one, or one naming a pre-standards DTD, asks
+        for the quirks mode that very old pages were written for. The rules are the
+        [HTML spec's](https://html.spec.whatwg.org/multipage/parsing.html#the-initial-insertion-mode),
+        with its list of legacy public identifiers shortened to their common prefixes.
+        Keywords are case-insensitive, and the name is kept in lowercase.
+
+        e.g.
+            <!DOCTYPE html>                                          no quirks
+            <!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 4.01//EN">         no quirks
+            <!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 4.01 Transitional//EN" "http://...">
+                                                                     limited quirks
+            <!DOCTYPE HTML PUBLIC "-//W3C//DTD HTML 3.2 Final//EN">    quirks
+            (none)                                                   quirks
      */
 
-    /// Parse a text node.
-    fn parse_text(&mut self) -> dom::Node {
-        dom::text(self.consume_while(|c: char| c != '<'))
+    /// Parse a doctype declaration, which starts with `<!doctype` in any case. The tree
+    /// builder works out the rendering mode it asks for.
+    fn parse_doctype(&mut self) -> Result<Token<'input>, ParseError> {
+        self.position += "<!doctype".len();
+        self.consume_whitespace();
+        let name: String = self.consume(|rest: &str| scan::ascii_run(rest, |byte: u8| !byte.is_ascii_whitespace() && byte != b'>')).to_ascii_lowercase();
+        self.consume_whitespace();
+
+        // An optional `PUBLIC "public id" "system id"` or `SYSTEM "system id"`.
+        let keyword: String = self.parse_name().to_ascii_lowercase();
+        let (public_id, system_id) = match keyword.as_str() {
+            "public" => (self.parse_doctype_identifier()?, self.parse_doctype_identifier()?),
+            "system" => (None, self.parse_doctype_identifier()?),
+            _ => (None, None),
+        };
+        // Anything else in the declaration is ignored.
+        self.consume_until(b'>');
+        self.expect(">")?;
+        Ok(Token::Doctype { name: Some(name).filter(|name: &String| !name.is_empty()), public_id, system_id })
+    }
+
+    /// Parse a doctype's quoted public or system identifier, if one comes next.
+    fn parse_doctype_identifier(&mut self) -> Result<Option<String>, ParseError> {
+        self.consume_whitespace();
+        let quote: &str = match self.peek() {
+            Some('"') => "\"",
+            Some('\'') => "'",
+            _ => return Ok(None),
+        };
+        self.expect(quote)?;
+        let value: &str = self.consume_until(quote.as_bytes()[0]);
+        self.expect(quote)?;
+        Ok(Some(value.to_string()))
     }
 }
+
+
+/*
+    Implicit Elements
+
+    Browsers accept a document that leaves out `<html>`, `<head>`, or `<body>`, and put
+    them in for it, so every document has the same shape:
+
+        <title>Hi</title><h1>Hi</h1>
+
+    becomes
+
+        <html>
+            <head><title>Hi</title></head>
+            <body><h1>Hi</h1></body>
+        </html>
+
+    After parsing, `build_document` does the same, in a much lighter way than the HTML
+    spec's tree construction:
+        - If the top level has an `<html>` element, it's the root, and whatever else is at
+          the top level (besides whitespace), like content after `</html>`, is moved to the
+          start or end of its body. Otherwise a new `<html>` is made around everything.
+        - If the root has no `<head>`, one is made, and the metadata elements at the start
+          of the root (`<title>`, `<style>`, `<meta>`, ...) are moved into it.
+        - If the root has no `<body>`, one is made, and everything else in the root is
+          moved into it.
+    Elements that were written out are never moved, so a document that has all three
+    comes out as it went in. Unlike a real parser, it doesn't move metadata that comes
+    after other content into `<head>`, or content written inside `<head>` into `<body>`.
+ */
+/// Elements that belong in `<head>`.
+const HEAD_ELEMENTS: [&str; 8] = ["base", "link", "meta", "noscript", "script", "style", "template", "title"];
+
+/// Make the top-level `nodes` into a document with `<html>`, `<head>`, and `<body>`
+/// elements, creating whichever are missing, and return the `<html>` element. See
+/// "Implicit Elements", above.
+fn build_document(tree: &mut dom::Tree, nodes: Vec<dom::NodeId>) -> dom::NodeId {
+    let is_element = |tree: &dom::Tree, node: dom::NodeId, names: &[&str]| match tree[node].node_type {
+        dom::NodeType::Element(ref element) => names.contains(&element.tag_name.as_str()),
+        _ => false,
+    };
+    let is_whitespace = |tree: &dom::Tree, node: dom::NodeId| match tree[node].node_type {
+        dom::NodeType::Text(ref text) => text.chars().all(char::is_whitespace),
+        _ => false,
+    };
+
+    let significant: Vec<dom::NodeId> = nodes.iter().copied().filter(|&node: &dom::NodeId| !is_whitespace(tree, node)).collect();
+    let written: Option<dom::NodeId> = significant.iter().copied().find(|&node: &dom::NodeId| is_element(tree, node, &["html"]));
+    let html: dom::NodeId = match written {
+        Some(html) => html,
+        None => tree.element(Atom::from("html"), dom::AttributeMap::default(), nodes),
+    };
+
+    let children: Vec<dom::NodeId> = tree.children(html).collect();
+    let head: Option<dom::NodeId> = children.iter().copied().find(|&child: &dom::NodeId| is_element(tree, child, &["head"]));
+    let body: Option<dom::NodeId> = children.iter().copied().find(|&child: &dom::NodeId| is_element(tree, child, &["body"]));
+    let mut rest: &[dom::NodeId] = &children;
+    if head.is_none() {
+        let metadata: usize = children.iter()
+            .take_while(|&&child: &&dom::NodeId| Some(child) != body && (is_whitespace(tree, child) || is_element(tree, child, &HEAD_ELEMENTS)))
+            .count();
+        let head: dom::NodeId = tree.element(Atom::from("head"), dom::AttributeMap::default(), children[..metadata].to_vec());
+        let first: Option<dom::NodeId> = tree[html].first_child;
+        tree.insert_before(html, head, first);
+        rest = &children[metadata..];
+    }
+    if body.is_none() {
+        let contents: Vec<dom::NodeId> = rest.iter().copied().filter(|&child: &dom::NodeId| Some(child) != head).collect();
+        let body: dom::NodeId = tree.element(Atom::from("body"), dom::AttributeMap::default(), contents);
+        tree.append_child(html, body);
+    }
+
+    // Content before or after a written-out `<html>` goes at the start or end of its body.
+    if written.is_some() {
+        let body: dom::NodeId = tree.children(html).find(|&child: &dom::NodeId| is_element(tree, child, &["body"])).expect("the root has a body");
+        let first: Option<dom::NodeId> = tree[body].first_child;
+        let mut before: bool = true;
+        for node in significant {
+            if node == html {
+                before = false;
+            } else if before {
+                tree.insert_before(body, node, first);
+            } else {
+                tree.append_child(body, node);
+            }
+        }
+    }
+    html
+}
+
+
+/*
+    Finally, we can put this all together to parse an entire HTML document into a DOM tree.
+    The document always has `<html>`, `<head>`, and `<body>` elements, even if its
+    source doesn't (see "Implicit Elements", above); this is similar to what a real HTML
+    parser does.
+ */
+/// Parse an HTML document.
+pub fn parse(source: &str) -> Result<dom::Document, ParseError> {
+    let mut parser: Parser = Parser::new();
+    parser.feed(source)?;
+    parser.finish()
+}
+
+/// Parse an HTML document as far as it can be parsed. Never fails: misnested tags are
+/// fixed up, and if the markup is broken, the document holds everything before the
+/// error. The errors are returned alongside it. See "Best-Effort Parsing", above.
+pub fn parse_with_errors(source: &str) -> (dom::Document, Vec<ParseError>) {
+    let mut parser: Parser = Parser::best_effort();
+    match parser.feed(source).and_then(|()| parser.finish_document()) {
+        Ok(parsed) => parsed,
+        // Recovering parsers record their errors rather than returning them.
+        Err(err) => {
+            let mut tree: dom::Tree = dom::Tree::new();
+            let root: dom::NodeId = build_document(&mut tree, Vec::new());
+            (dom::Document::new(tree, root), vec![err])
+        }
+    }
+}
+
+/// Public identifiers that start with one of these put a document in quirks mode: DTDs
+/// from before CSS was standardized.
+const QUIRKS_PUBLIC_PREFIXES: [&str; 19] = [
+    "+//silmaril//dtd html pro v0r11 19970101//",
+    "-//as//dtd html 3.0 aswedit + extensions//",
+    "-//advasoft ltd//dtd html 3.0 aswedit + extensions//",
+    "-//ietf//dtd html",
+    "-//metrius//dtd metrius presentational//",
+    "-//microsoft//dtd internet explorer",
+    "-//netscape comm. corp.//dtd",
+    "-//o'reilly and associates//dtd html",
+    "-//softquad",
+    "-//spyglass//dtd html 2.0 extended//",
+    "-//sq//dtd html 2.0 hotmetal + extensions//",
+    "-//sun microsystems corp.//dtd hotjava",
+    "-//w3c//dtd html 3",
+    "-//w3c//dtd html 4.0 frameset//",
+    "-//w3c//dtd html 4.0 transitional//",
+    "-//w3c//dtd html experimental",
+    "-//w3c//dtd w3 html//",
+    "-//w3o//dtd w3 html 3.0//",
+    "-//webtechs//dtd mozilla html",
+];
+
+/// The rendering mode a doctype named `name`, with these identifiers, asks for.
+fn quirks_mode(name: &str, public_id: Option<&str>, system_id: Option<&str>) -> dom::QuirksMode {
+    let public_id: String = public_id.unwrap_or("").to_ascii_lowercase();
+    let system_id: Option<String> = system_id.map(str::to_ascii_lowercase);
+    let starts = |prefixes: &[&str]| prefixes.iter().any(|&prefix: &&str| public_id.starts_with(prefix));
+    let transitional: [&str; 2] = ["-//w3c//dtd html 4.01 frameset//", "-//w3c//dtd html 4.01 transitional//"];
+    let quirks: bool = name != "html"
+        || matches!(public_id.as_str(), "-//w3o//dtd w3 html strict 3.0//en//" | "-/w3c/dtd html 4.0 transitional/en" | "html")
+        || system_id.as_deref() == Some("http://www.ibm.com/data/dtd/v11/ibmxhtml1-transitional.dtd")
+        || starts(&QUIRKS_PUBLIC_PREFIXES)
+        || (system_id.is_none() && starts(&transitional));
+    if quirks {
+        dom::QuirksMode::Quirks
+    } else if starts(&["-//w3c//dtd xhtml 1.0 frameset//", "-//w3c//dtd xhtml 1.0 transitional//"]) || starts(&transitional) {
+        dom::QuirksMode::LimitedQuirks
+    } else {
+        dom::QuirksMode::NoQuirks
+    }
+}
+
+/// Parse an HTML document from raw bytes, e.g. as read from a file or a socket, in the
+/// encoding its byte order mark or `<meta charset>` names (see `encoding::detect`). Bytes
+/// that aren't valid in that encoding are replaced with U+FFFD rather than being an error.
+pub fn parse_bytes(source: &[u8]) -> Result<dom::Document, ParseError> {
+    parse(&encoding::decode_html(source, None).0)
+}
+
+/// Parse a snippet of HTML (like the value assigned to `innerHTML`) into a fragment.
+/*
+    Unlike `parse`, this doesn't wrap the nodes in an `<html>` element: the fragment's
+    children are the top-level nodes of the snippet, ready to be imported into a document.
+
+    e.g.
+        let (fragment_tree, fragment) = html::parse_fragment("<li>a</li><li>b</li>")?;
+        let fragment: NodeId = document.tree.import(&fragment_tree, fragment);
+        document.append_child(list, fragment);
+ */
+pub fn parse_fragment(source: &str) -> Result<(dom::Tree, dom::NodeId), ParseError> {
+    let mut parser: Parser = Parser::new();
+    // A fragment has no doctype.
+    parser.tokenizer.started = true;
+    parser.feed(source)?;
+    parser.finish_nodes()?;
+    let TreeBuilder { mut tree, top_level, .. } = parser.builder;
+    let fragment: dom::NodeId = tree.fragment(top_level);
+    Ok((tree, fragment))
+}
+
+
+/*
+    Serializing
+
+    Going the other way, `serialize` writes a node back out as markup, as a browser's
+    `outerHTML` does (and `serialize_children` as `innerHTML` does), so a tree can be
+    saved, diffed, or fed back to the parser:
+
+        <p title="a &quot;b&quot;">1 &lt; 2 &amp; <br>3</p>
+
+    What the parser reads as text has to come back as text, so:
+        - in text, `&`, `<`, and `>` are escaped as character references, and so is a
+          no-break space, which would otherwise be hard to tell from a space
+        - attribute values are always double-quoted, with `&`, `"`, `<`, `>`, and no-break
+          spaces escaped
+        - void elements (`VOID_ELEMENTS`) have no closing tag, and no contents to write
+        - the text of `<script>` and `<style>` is written as it is, since the parser
+          doesn't decode references in it (see "Raw Text", above); the text of `<title>`
+          and `<textarea>`, which it does decode, is escaped like any other
+    Tag and attribute names are written as they're stored, and a fragment is written as
+    its children. Parsing the result gives back the same tree, with two exceptions that
+    markup can't express: raw text containing its own closing tag, and text that the
+    parser would trim (see "Whitespace", above).
+ */
+/// Write `node` and its descendants as HTML. See "Serializing", above.
+pub fn serialize(tree: &dom::Tree, node: dom::NodeId) -> String {
+    let mut html: String = String::new();
+    write_node(tree, node, false, &mut html);
+    html
+}
+
+/// Write the children of `node` as HTML, leaving out `node` itself.
+pub fn serialize_children(tree: &dom::Tree, node: dom::NodeId) -> String {
+    let raw: bool = is_raw_text_parent(tree, node);
+    let mut html: String = String::new();
+    for child in tree.children(node) {
+        write_node(tree, child, raw, &mut html);
+    }
+    html
+}
+
+/// Write `node` and its descendants to `html`. `raw` says whether `node` is in an element
+/// whose text isn't escaped.
+fn write_node(tree: &dom::Tree, node: dom::NodeId, raw: bool, html: &mut String) {
+    match tree[node].node_type {
+        dom::NodeType::Element(ref element) => {
+            html.push('<');
+            html.push_str(&element.tag_name);
+            for (name, value) in &element.attributes {
+                html.push(' ');
+                html.push_str(name);
+                html.push_str("=\"");
+                escape(value, true, html);
+                html.push('"');
+            }
+            html.push('>');
+            if is_void_element(&element.tag_name) {
+                return;
+            }
+            let raw: bool = raw_text_kind(&element.tag_name) == Some(false);
+            for child in tree.children(node) {
+                write_node(tree, child, raw, html);
+            }
+            html.push_str("</");
+            html.push_str(&element.tag_name);
+            html.push('>');
+        }
+        dom::NodeType::Text(ref text) if raw => html.push_str(text),
+        dom::NodeType::Text(ref text) => escape(text, false, html),
+        dom::NodeType::DocumentFragment => {
+            for child in tree.children(node) {
+                write_node(tree, child, raw, html);
+            }
+        }
+    }
+}
+
+/// Whether the text in `node` is written without escaping.
+fn is_raw_text_parent(tree: &dom::Tree, node: dom::NodeId) -> bool {
+    matches!(tree[node].node_type, dom::NodeType::Element(ref element) if raw_text_kind(&element.tag_name) == Some(false))
+}
+
+/// Append `text` to `html`, with the characters that would be read as markup replaced by
+/// character references. `in_attribute` says whether it's a (double-quoted) attribute
+/// value.
+fn escape(text: &str, in_attribute: bool, html: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '\u{A0}' => html.push_str("&nbsp;"),
+            '"' if in_attribute => html.push_str("&quot;"),
+            c => html.push(c),
+        }
+    }
+}
+
+
+/*
+    Character References
+
+    Some characters can't be written as themselves in HTML: a `<` in text would start a
+    tag, and a `"` in a quoted attribute value would end it. Others are just hard to type.
+    Character references stand for them: by name, like `&lt;` and `&eacute;`, or by code
+    point, in decimal (`&#233;`) or hexadecimal (`&#xE9;`). They're decoded in text and in
+    attribute values.
+
+    Names are looked up in `ENTITIES`, a sorted table of the named references most pages
+    use (the full list has over two thousand). A `&` that doesn't start a reference we
+    know is just an ampersand. The rules for broken references are the
+    [HTML spec's](https://html.spec.whatwg.org/multipage/parsing.html#character-reference-state):
+        - The oldest names, like `&amp` and `&copy`, work without their `;`, and even run
+          into the text after them, so `&copy2024` is "©2024". In an attribute value, where
+          URLs like `?a=1&copy=2` are common, they're left alone if a letter, digit, or `=`
+          follows.
+        - A numeric reference may leave out its `;` too. One to a code point that can't be
+          a character (zero, a surrogate, or past U+10FFFF) stands for U+FFFD, and ones to
+          0x80-0x9F stand for what those bytes mean in Windows-1252, since that's what
+          pages that use them mean.
+ */
+
+/// Replace the character references in `text` with the characters they stand for.
+/// `in_attribute` says whether `text` is an attribute value.
+pub fn decode_entities(text: &str, in_attribute: bool) -> Cow<'_, str> {
+    if !text.contains('&') {
+        return Cow::Borrowed(text);
+    }
+    let mut decoded: String = String::with_capacity(text.len());
+    let mut rest: &str = text;
+    while let Some(ampersand) = rest.find('&') {
+        decoded.push_str(&rest[..ampersand]);
+        rest = &rest[ampersand + 1..];
+        match character_reference(rest, in_attribute) {
+            Some((c, length)) => {
+                decoded.push(c);
+                rest = &rest[length..];
+            }
+            None => decoded.push('&'),
+        }
+    }
+    decoded.push_str(rest);
+    Cow::Owned(decoded)
+}
+
+/// The character that the reference at the start of `text`, just after its `&`, stands
+/// for, and how long the rest of the reference is.
+fn character_reference(text: &str, in_attribute: bool) -> Option<(char, usize)> {
+    if let Some(number) = text.strip_prefix('#') {
+        let (digits, radix, prefix): (&str, u32, usize) = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16, 2),
+            None => (number, 10, 1),
+        };
+        let length: usize = scan::ascii_run(digits, |byte: u8| (byte as char).is_digit(radix));
+        if length == 0 {
+            return None;
+        }
+        let code: u32 = u32::from_str_radix(&digits[..length], radix).unwrap_or(u32::MAX);
+        let c: char = match code {
+            0 => char::REPLACEMENT_CHARACTER,
+            0x80..=0x9F => encoding::WINDOWS_1252[(code - 0x80) as usize],
+            code => char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER),
+        };
+        let semicolon: usize = usize::from(digits[length..].starts_with(';'));
+        return Some((c, prefix + length + semicolon));
+    }
+
+    let lookup = |name: &str| ENTITIES.binary_search_by_key(&name, |&(name, _, _): &(&str, char, bool)| name).ok().map(|i: usize| ENTITIES[i]);
+    let name: &str = &text[..scan::ascii_run(text, |byte: u8| byte.is_ascii_alphanumeric())];
+    if let (true, Some((_, c, _))) = (text[name.len()..].starts_with(';'), lookup(name)) {
+        return Some((c, name.len() + 1));
+    }
+    // Without a `;`, the longest legacy name that starts the text.
+    let (length, c): (usize, char) = (1..=name.len()).rev()
+        .find_map(|length: usize| lookup(&name[..length]).filter(|&(_, _, legacy): &(&str, char, bool)| legacy).map(|(_, c, _)| (length, c)))?;
+    let next: Option<u8> = text[length..].bytes().next();
+    if in_attribute && next.is_some_and(|byte: u8| byte.is_ascii_alphanumeric() || byte == b'=') {
+        return None;
+    }
+    Some((c, length))
+}
+
+/// Named character references, sorted by name: each name, its character, and whether
+/// it's a legacy name that works without its `;`.
+pub const ENTITIES: [(&str, char, bool); 253] = [
+    ("AElig", '\u{C6}', true),
+    ("AMP", '\u{26}', true),
+    ("Aacute", '\u{C1}', true),
+    ("Acirc", '\u{C2}', true),
+    ("Agrave", '\u{C0}', true),
+    ("Alpha", '\u{391}', false),
+    ("Aring", '\u{C5}', true),
+    ("Atilde", '\u{C3}', true),
+    ("Auml", '\u{C4}', true),
+    ("Beta", '\u{392}', false),
+    ("COPY", '\u{A9}', true),
+    ("Ccedil", '\u{C7}', true),
+    ("Chi", '\u{3A7}', false),
+    ("Dagger", '\u{2021}', false),
+    ("Delta", '\u{394}', false),
+    ("ETH", '\u{D0}', true),
+    ("Eacute", '\u{C9}', true),
+    ("Ecirc", '\u{CA}', true),
+    ("Egrave", '\u{C8}', true),
+    ("Epsilon", '\u{395}', false),
+    ("Eta", '\u{397}', false),
+    ("Euml", '\u{CB}', true),
+    ("GT", '\u{3E}', true),
+    ("Gamma", '\u{393}', false),
+    ("Iacute", '\u{CD}', true),
+    ("Icirc", '\u{CE}', true),
+    ("Igrave", '\u{CC}', true),
+    ("Iota", '\u{399}', false),
+    ("Iuml", '\u{CF}', true),
+    ("Kappa", '\u{39A}', false),
+    ("LT", '\u{3C}', true),
+    ("Lambda", '\u{39B}', false),
+    ("Mu", '\u{39C}', false),
+    ("Ntilde", '\u{D1}', true),
+    ("Nu", '\u{39D}', false),
+    ("OElig", '\u{152}', false),
+    ("Oacute", '\u{D3}', true),
+    ("Ocirc", '\u{D4}', true),
+    ("Ograve", '\u{D2}', true),
+    ("Omega", '\u{3A9}', false),
+    ("Omicron", '\u{39F}', false),
+    ("Oslash", '\u{D8}', true),
+    ("Otilde", '\u{D5}', true),
+    ("Ouml", '\u{D6}', true),
+    ("Phi", '\u{3A6}', false),
+    ("Pi", '\u{3A0}', false),
+    ("Prime", '\u{2033}', false),
+    ("Psi", '\u{3A8}', false),
+    ("QUOT", '\u{22}', true),
+    ("REG", '\u{AE}', true),
+    ("Rho", '\u{3A1}', false),
+    ("Scaron", '\u{160}', false),
+    ("Sigma", '\u{3A3}', false),
+    ("THORN", '\u{DE}', true),
+    ("Tau", '\u{3A4}', false),
+    ("Theta", '\u{398}', false),
+    ("Uacute", '\u{DA}', true),
+    ("Ucirc", '\u{DB}', true),
+    ("Ugrave", '\u{D9}', true),
+    ("Upsilon", '\u{3A5}', false),
+    ("Uuml", '\u{DC}', true),
+    ("Xi", '\u{39E}', false),
+    ("Yacute", '\u{DD}', true),
+    ("Yuml", '\u{178}', false),
+    ("Zeta", '\u{396}', false),
+    ("aacute", '\u{E1}', true),
+    ("acirc", '\u{E2}', true),
+    ("acute", '\u{B4}', true),
+    ("aelig", '\u{E6}', true),
+    ("agrave", '\u{E0}', true),
+    ("alefsym", '\u{2135}', false),
+    ("alpha", '\u{3B1}', false),
+    ("amp", '\u{26}', true),
+    ("and", '\u{2227}', false),
+    ("apos", '\u{27}', false),
+    ("aring", '\u{E5}', true),
+    ("asymp", '\u{2248}', false),
+    ("atilde", '\u{E3}', true),
+    ("auml", '\u{E4}', true),
+    ("bdquo", '\u{201E}', false),
+    ("beta", '\u{3B2}', false),
+    ("brvbar", '\u{A6}', true),
+    ("bull", '\u{2022}', false),
+    ("cap", '\u{2229}', false),
+    ("ccedil", '\u{E7}', true),
+    ("cedil", '\u{B8}', true),
+    ("cent", '\u{A2}', true),
+    ("check", '\u{2713}', false),
+    ("chi", '\u{3C7}', false),
+    ("circ", '\u{2C6}', false),
+    ("clubs", '\u{2663}', false),
+    ("copy", '\u{A9}', true),
+    ("crarr", '\u{21B5}', false),
+    ("cross", '\u{2717}', false),
+    ("cup", '\u{222A}', false),
+    ("curren", '\u{A4}', true),
+    ("dagger", '\u{2020}', false),
+    ("darr", '\u{2193}', false),
+    ("deg", '\u{B0}', true),
+    ("delta", '\u{3B4}', false),
+    ("diams", '\u{2666}', false),
+    ("divide", '\u{F7}', true),
+    ("eacute", '\u{E9}', true),
+    ("ecirc", '\u{EA}', true),
+    ("egrave", '\u{E8}', true),
+    ("empty", '\u{2205}', false),
+    ("emsp", '\u{2003}', false),
+    ("ensp", '\u{2002}', false),
+    ("epsilon", '\u{3B5}', false),
+    ("equiv", '\u{2261}', false),
+    ("eta", '\u{3B7}', false),
+    ("eth", '\u{F0}', true),
+    ("euml", '\u{EB}', true),
+    ("euro", '\u{20AC}', false),
+    ("exist", '\u{2203}', false),
+    ("female", '\u{2640}', false),
+    ("flat", '\u{266D}', false),
+    ("fnof", '\u{192}', false),
+    ("forall", '\u{2200}', false),
+    ("frac12", '\u{BD}', true),
+    ("frac14", '\u{BC}', true),
+    ("frac34", '\u{BE}', true),
+    ("frasl", '\u{2044}', false),
+    ("gamma", '\u{3B3}', false),
+    ("ge", '\u{2265}', false),
+    ("gt", '\u{3E}', true),
+    ("hArr", '\u{21D4}', false),
+    ("harr", '\u{2194}', false),
+    ("hearts", '\u{2665}', false),
+    ("hellip", '\u{2026}', false),
+    ("iacute", '\u{ED}', true),
+    ("icirc", '\u{EE}', true),
+    ("iexcl", '\u{A1}', true),
+    ("igrave", '\u{EC}', true),
+    ("image", '\u{2111}', false),
+    ("infin", '\u{221E}', false),
+    ("int", '\u{222B}', false),
+    ("iota", '\u{3B9}', false),
+    ("iquest", '\u{BF}', true),
+    ("isin", '\u{2208}', false),
+    ("iuml", '\u{EF}', true),
+    ("kappa", '\u{3BA}', false),
+    ("lArr", '\u{21D0}', false),
+    ("lambda", '\u{3BB}', false),
+    ("lang", '\u{27E8}', false),
+    ("laquo", '\u{AB}', true),
+    ("larr", '\u{2190}', false),
+    ("lceil", '\u{2308}', false),
+    ("ldquo", '\u{201C}', false),
+    ("le", '\u{2264}', false),
+    ("lfloor", '\u{230A}', false),
+    ("lowast", '\u{2217}', false),
+    ("loz", '\u{25CA}', false),
+    ("lrm", '\u{200E}', false),
+    ("lsaquo", '\u{2039}', false),
+    ("lsquo", '\u{2018}', false),
+    ("lt", '\u{3C}', true),
+    ("macr", '\u{AF}', true),
+    ("male", '\u{2642}', false),
+    ("mdash", '\u{2014}', false),
+    ("micro", '\u{B5}', true),
+    ("middot", '\u{B7}', true),
+    ("minus", '\u{2212}', false),
+    ("mu", '\u{3BC}', false),
+    ("nabla", '\u{2207}', false),
+    ("natural", '\u{266E}', false),
+    ("nbsp", '\u{A0}', true),
+    ("ndash", '\u{2013}', false),
+    ("ne", '\u{2260}', false),
+    ("not", '\u{AC}', true),
+    ("notin", '\u{2209}', false),
+    ("ntilde", '\u{F1}', true),
+    ("nu", '\u{3BD}', false),
+    ("oacute", '\u{F3}', true),
+    ("ocirc", '\u{F4}', true),
+    ("oelig", '\u{153}', false),
+    ("ograve", '\u{F2}', true),
+    ("oline", '\u{203E}', false),
+    ("omega", '\u{3C9}', false),
+    ("omicron", '\u{3BF}', false),
+    ("or", '\u{2228}', false),
+    ("ordf", '\u{AA}', true),
+    ("ordm", '\u{BA}', true),
+    ("oslash", '\u{F8}', true),
+    ("otilde", '\u{F5}', true),
+    ("ouml", '\u{F6}', true),
+    ("para", '\u{B6}', true),
+    ("part", '\u{2202}', false),
+    ("permil", '\u{2030}', false),
+    ("phi", '\u{3C6}', false),
+    ("phone", '\u{260E}', false),
+    ("pi", '\u{3C0}', false),
+    ("plusmn", '\u{B1}', true),
+    ("pound", '\u{A3}', true),
+    ("prime", '\u{2032}', false),
+    ("prod", '\u{220F}', false),
+    ("prop", '\u{221D}', false),
+    ("psi", '\u{3C8}', false),
+    ("quot", '\u{22}', true),
+    ("rArr", '\u{21D2}', false),
+    ("radic", '\u{221A}', false),
+    ("rang", '\u{27E9}', false),
+    ("raquo", '\u{BB}', true),
+    ("rarr", '\u{2192}', false),
+    ("rceil", '\u{2309}', false),
+    ("rdquo", '\u{201D}', false),
+    ("real", '\u{211C}', false),
+    ("reg", '\u{AE}', true),
+    ("rfloor", '\u{230B}', false),
+    ("rho", '\u{3C1}', false),
+    ("rlm", '\u{200F}', false),
+    ("rsaquo", '\u{203A}', false),
+    ("rsquo", '\u{2019}', false),
+    ("sbquo", '\u{201A}', false),
+    ("scaron", '\u{161}', false),
+    ("sdot", '\u{22C5}', false),
+    ("sect", '\u{A7}', true),
+    ("sharp", '\u{266F}', false),
+    ("shy", '\u{AD}', true),
+    ("sigma", '\u{3C3}', false),
+    ("sigmaf", '\u{3C2}', false),
+    ("sim", '\u{223C}', false),
+    ("spades", '\u{2660}', false),
+    ("star", '\u{2606}', false),
+    ("starf", '\u{2605}', false),
+    ("sum", '\u{2211}', false),
+    ("sup1", '\u{B9}', true),
+    ("sup2", '\u{B2}', true),
+    ("sup3", '\u{B3}', true),
+    ("szlig", '\u{DF}', true),
+    ("tau", '\u{3C4}', false),
+    ("there4", '\u{2234}', false),
+    ("theta", '\u{3B8}', false),
+    ("thinsp", '\u{2009}', false),
+    ("thorn", '\u{FE}', true),
+    ("tilde", '\u{2DC}', false),
+    ("times", '\u{D7}', true),
+    ("trade", '\u{2122}', false),
+    ("uacute", '\u{FA}', true),
+    ("uarr", '\u{2191}', false),
+    ("ucirc", '\u{FB}', true),
+    ("ugrave", '\u{F9}', true),
+    ("uml", '\u{A8}', true),
+    ("upsilon", '\u{3C5}', false),
+    ("uuml", '\u{FC}', true),
+    ("weierp", '\u{2118}', false),
+    ("xi", '\u{3BE}', false),
+    ("yacute", '\u{FD}', true),
+    ("yen", '\u{A5}', true),
+    ("yuml", '\u{FF}', true),
+    ("zeta", '\u{3B6}', false),
+    ("zwj", '\u{200D}', false),
+    ("zwnj", '\u{200C}', false),
+];
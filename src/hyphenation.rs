@@ -0,0 +1,171 @@
+//! Hyphenation: finding where a word may be broken across lines, from a dictionary of
+//! hyphenation patterns.
+
+use crate::collections::HashMap;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+/*
+    Hyphenation
+
+    With `hyphens: auto`, a line may end partway through a word, with a hyphen drawn at
+    the break. That keeps narrow columns from looking ragged, and keeps justified lines
+    (`text-align: justify`) from having huge gaps between their words. Where a word may
+    break depends on its language, so the engine has no built-in rules: the embedder adds
+    a dictionary for each language it wants hyphenated (`FontCollection::set_hyphenator`),
+    and text is only hyphenated when its `lang` attribute names one of them.
+
+    Dictionaries are in the format TeX uses, which is what most hyphenation dictionaries
+    are published in. Franklin Liang's algorithm (used by TeX, LibreOffice, and browsers)
+    describes a language with a list of patterns, letter sequences with digits between the
+    letters:
+
+        hy3ph   he2n   hena4   hen5at   1na   n2at   1tio   2io   o2n
+
+    To hyphenate a word, find every pattern that occurs in it (with `.` marking the start
+    and end of the word) and, between each pair of letters, take the highest digit any
+    pattern puts there. An odd number allows a break, and an even one forbids it, so
+    longer patterns can override shorter ones. The patterns above hyphenate "hyphenation"
+    as "hy-phen-ation". A dictionary also has a list of exceptions, words spelled out with
+    their hyphens, for the words the patterns get wrong.
+
+    A break is never allowed too close to either end of the word: by default two letters
+    must stay before it and three after, as in TeX's English hyphenation.
+
+    The other way to get hyphens is by hand: a soft hyphen (U+00AD, `&shy;`) in the text
+    marks a place where the word may break. It isn't drawn unless the line breaks there.
+    That works with `hyphens: manual` (the initial value) as well as `auto`, but not with
+    `hyphens: none`.
+ */
+/// The hyphenation patterns and exceptions for one language.
+#[derive(Clone, Debug, Default)]
+pub struct Hyphenator {
+    patterns: HashMap<String, Vec<u8>>,         // letters, and the digits before, between, and after them
+    exceptions: HashMap<String, Vec<usize>>,    // words, and how many letters come before each hyphen
+    longest: usize,                             // the most letters in a pattern
+    left_min: usize,
+    right_min: usize,
+}
+
+impl Hyphenator {
+    /// Read a dictionary in TeX's format: `\patterns{...}` and `\hyphenation{...}` groups,
+    /// with `%` comments. Text outside the groups is read as patterns, so a bare list of
+    /// patterns works too.
+    pub fn parse(dictionary: &str) -> Hyphenator {
+        let mut hyphenator: Hyphenator = Hyphenator { left_min: 2, right_min: 3, ..Hyphenator::default() };
+        let mut exceptions: bool = false;
+        for line in dictionary.lines() {
+            let line: &str = line.split('%').next().unwrap_or("");
+            for word in line.split_whitespace() {
+                let word: &str = if let Some(rest) = word.strip_prefix("\\patterns{") {
+                    exceptions = false;
+                    rest
+                } else if let Some(rest) = word.strip_prefix("\\hyphenation{") {
+                    exceptions = true;
+                    rest
+                } else {
+                    word
+                };
+                let word: &str = word.trim_end_matches('}');
+                if word.is_empty() || word.starts_with('\\') {
+                    continue;
+                }
+                if exceptions {
+                    hyphenator.add_exception(word);
+                } else {
+                    hyphenator.add_pattern(word);
+                }
+            }
+        }
+        hyphenator
+    }
+
+    /// Add one pattern, like `hen5at`.
+    pub fn add_pattern(&mut self, pattern: &str) {
+        let mut letters: String = String::new();
+        let mut digits: Vec<u8> = vec![0];
+        for c in pattern.chars() {
+            match c.to_digit(10) {
+                Some(digit) => *digits.last_mut().unwrap() = digit as u8,
+                None => {
+                    letters.extend(c.to_lowercase());
+                    digits.push(0);
+                }
+            }
+        }
+        self.longest = self.longest.max(letters.chars().count());
+        self.patterns.insert(letters, digits);
+    }
+
+    /// Add one exception: a word with its hyphens, like `ta-ble`.
+    pub fn add_exception(&mut self, word: &str) {
+        let mut letters: String = String::new();
+        let mut hyphens: Vec<usize> = Vec::new();
+        for c in word.chars() {
+            if c == '-' {
+                hyphens.push(letters.chars().count());
+            } else {
+                letters.extend(c.to_lowercase());
+            }
+        }
+        self.exceptions.insert(letters, hyphens);
+    }
+
+    /// Set how many letters must stay before and after a break.
+    pub fn set_min_lengths(&mut self, left: usize, right: usize) {
+        (self.left_min, self.right_min) = (left.max(1), right.max(1));
+    }
+
+    /// Where `word`, which is all letters, may be broken: the number of letters before
+    /// each break.
+    pub fn hyphenate(&self, word: &str) -> Vec<usize> {
+        let letters: Vec<char> = word.chars().flat_map(char::to_lowercase).collect();
+        let count: usize = letters.len();
+        if count < self.left_min + self.right_min || count != word.chars().count() {
+            return Vec::new();
+        }
+        let lowercase: String = letters.iter().collect();
+        if let Some(hyphens) = self.exceptions.get(&lowercase) {
+            return hyphens.clone();
+        }
+
+        // `points[i]` is the highest digit between the dotted word's letters i - 1 and i.
+        let dotted: Vec<char> = core::iter::once('.').chain(letters).chain(core::iter::once('.')).collect();
+        let mut points: Vec<u8> = vec![0; dotted.len() + 1];
+        let mut key: String = String::new();
+        for start in 0..dotted.len() {
+            key.clear();
+            for &c in dotted[start..].iter().take(self.longest) {
+                key.push(c);
+                if let Some(digits) = self.patterns.get(&key) {
+                    for (k, &digit) in digits.iter().enumerate() {
+                        points[start + k] = points[start + k].max(digit);
+                    }
+                }
+            }
+        }
+        // A break after `n` letters is between dotted letters n and n + 1.
+        (self.left_min..=count - self.right_min).filter(|&n: &usize| points[n + 1] % 2 == 1).collect()
+    }
+
+    /// Where `text` may be broken: the byte offsets of the breaks inside each of its words
+    /// (its runs of letters).
+    pub fn hyphenate_text(&self, text: &str) -> Vec<usize> {
+        let mut breaks: Vec<usize> = Vec::new();
+        let mut word_start: Option<usize> = None;
+        let ends = text.char_indices().map(|(i, c): (usize, char)| (i, Some(c))).chain(core::iter::once((text.len(), None)));
+        for (i, c) in ends {
+            match (c.is_some_and(char::is_alphabetic), word_start) {
+                (true, None) => word_start = Some(i),
+                (false, Some(start)) => {
+                    let word: &str = &text[start..i];
+                    let offsets: Vec<usize> = word.char_indices().map(|(offset, _): (usize, char)| offset).collect();
+                    breaks.extend(self.hyphenate(word).into_iter().map(|n: usize| start + offsets[n]));
+                    word_start = None;
+                }
+                _ => {}
+            }
+        }
+        breaks
+    }
+}
@@ -0,0 +1,79 @@
+//! Decoded images, and the cache layout and painting look them up in.
+
+use crate::collections::HashMap;
+use crate::css;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use alloc::sync::Arc;
+use core::fmt;
+
+
+/*
+    Images
+
+    Layout needs each image's size, and painting needs its pixels, but neither cares
+    where it came from. With `std`, `loader::ImageLoader` fetches and decodes images in
+    the background and fills in an `ImageCache` as they arrive. Without it, the caller
+    decodes them (or has them baked into its firmware) and inserts them itself:
+
+        let mut images: ImageCache = ImageCache::new();
+        images.insert("logo.png".to_string(), ImageState::Ready(Arc::new(logo)));
+ */
+
+/// A decoded image, stored as rows of pixels.
+pub struct Image {
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<css::Color>,
+}
+
+// Don't print every pixel when debugging.
+impl fmt::Debug for Image {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Image {{ width: {}, height: {} }}", self.width, self.height)
+    }
+}
+
+/// The loading state of one image source.
+#[derive(Clone, Debug)]
+pub enum ImageState {
+    Pending,
+    Ready(Arc<Image>),
+    Failed,
+}
+
+/// The state of every image a page has asked for, keyed by `src`.
+#[derive(Default, Debug)]
+pub struct ImageCache {
+    pub(crate) images: HashMap<String, ImageState>,
+}
+
+impl ImageCache {
+    /// Create an empty cache.
+    pub fn new() -> ImageCache {
+        Default::default()
+    }
+
+    /// Return the decoded image for `src`, if it has finished loading.
+    pub fn get(&self, src: &str) -> Option<Arc<Image>> {
+        match self.images.get(src) {
+            Some(ImageState::Ready(image)) => Some(image.clone()),
+            _ => None,
+        }
+    }
+
+    /// Return the loading state of `src`, or `None` if it was never requested.
+    pub fn state(&self, src: &str) -> Option<&ImageState> {
+        self.images.get(src)
+    }
+
+    /// Record the state of `src`.
+    pub fn insert(&mut self, src: String, state: ImageState) {
+        self.images.insert(src, state);
+    }
+
+    /// Forget `src`, so it is loaded again the next time it is requested.
+    pub fn remove(&mut self, src: &str) {
+        self.images.remove(src);
+    }
+}
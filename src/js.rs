@@ -0,0 +1,2960 @@
+//! A small JavaScript interpreter, for running the scripts in pages (behind the `js` feature).
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+
+
+/*
+    JavaScript
+
+    Pages are more than markup and styles: their scripts change the DOM after it is parsed.
+    Real engines like V8 or SpiderMonkey are huge, so this is a tree-walking interpreter
+    for the everyday part of the language, in three steps like the HTML and CSS parsers:
+
+        source -> Lexer -> tokens -> Parser -> statements -> Context (evaluation) -> values
+
+    Supported:
+        - `var`, `let`, and `const`; functions (declarations, expressions, and arrows) with
+          closures, default parameters, and `...rest` parameters
+        - `if`, `while`, `do`/`while`, `for`, `for`/`of`, `for`/`in`, `switch`, `break`,
+          `continue`, `return`, `throw`, and `try`/`catch`/`finally`
+        - numbers, strings (and `template ${literals}`), booleans, `null`, `undefined`,
+          arrays, and plain objects, with the usual operators and spread (`...xs`) in
+          array literals and calls
+        - the common methods of strings, arrays, and numbers, and the `Math`, `JSON`
+          (`stringify` only), `Object`, `Array`, `Number`, and `String` globals
+        - `console.log` and friends, which hand their output to the host
+
+    Not supported: classes, prototypes (`new` just calls the function with a fresh object
+    as `this`), getters and setters, destructuring, regular expressions, generators, and
+    `async`. Strings are indexed by `char`, not by UTF-16 code unit. `var` declarations
+    are scoped to their function but not hoisted; function declarations are hoisted to the
+    top of their block.
+
+    The interpreter knows nothing about documents. Whatever embeds it implements `Host`,
+    and exposes its own objects (elements, say) as `Value::Host` handles: reading,
+    writing, or calling a method on a handle goes to the host. See `script` for the DOM.
+
+    A runaway script (`while (true) {}`) would hang the page, so evaluation gives up with
+    an error after `STEP_LIMIT` statements, and recursion is limited to `MAX_CALL_DEPTH`.
+
+    e.g.
+        let mut runtime: Runtime = Runtime::new();
+        let value: Value = runtime.eval("[1, 2, 3].map(x => x * 2).join()", &mut NoHost)?;
+        assert_eq!(value.to_string(), "2,4,6");
+ */
+
+/// How many statements one call to `eval` or `call` may run before it is stopped.
+pub const STEP_LIMIT: usize = 5_000_000;
+
+/// How deeply functions may call each other.
+pub const MAX_CALL_DEPTH: usize = 128;
+
+
+// Values
+
+/// A JavaScript value.
+#[derive(Clone)]
+pub enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(Rc<str>),
+    Object(Object),
+    Host(HostObject),
+}
+
+/// A handle to an object owned by the host, e.g. an element. `class` says what kind of
+/// object it is, and `id` which one.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct HostObject {
+    pub class: &'static str,
+    pub id: usize,
+}
+
+/// A reference to an object, array, or function. Cloning it gives another reference to
+/// the same object.
+#[derive(Clone)]
+pub struct Object(Rc<RefCell<ObjectData>>);
+
+struct ObjectData {
+    properties: Vec<(Rc<str>, Value)>, // in insertion order
+    kind: ObjectKind,
+}
+
+enum ObjectKind {
+    Plain,
+    Array(Vec<Value>),
+    Function(Function),
+    Error,
+}
+
+#[derive(Clone)]
+enum Function {
+    Script { definition: Rc<FunctionDefinition>, env: Env },
+    Native { name: &'static str, function: NativeFunction },
+    Host { object: HostObject, method: Rc<str> },
+}
+
+/// A built-in function: called with `this` and the arguments, returns the result or a
+/// thrown value.
+type NativeFunction = fn(&mut Context, &Value, &[Value]) -> Result<Value, Value>;
+
+impl Value {
+    /// A string value.
+    pub fn string(s: &str) -> Value {
+        Value::String(Rc::from(s))
+    }
+
+    /// A new array holding `values`.
+    pub fn array(values: Vec<Value>) -> Value {
+        Value::Object(Object::new(ObjectKind::Array(values)))
+    }
+
+    /// A new plain object with `properties`.
+    pub fn object(properties: Vec<(&str, Value)>) -> Value {
+        let object: Object = Object::new(ObjectKind::Plain);
+        for (key, value) in properties {
+            object.set(key, value);
+        }
+        Value::Object(object)
+    }
+
+    /// A function that calls `method` on a host object, e.g. `window.setTimeout`.
+    pub fn host_method(object: HostObject, method: &str) -> Value {
+        Value::Object(Object::new(ObjectKind::Function(Function::Host { object, method: Rc::from(method) })))
+    }
+
+    /// Return true for values that count as true in conditions.
+    pub fn is_truthy(&self) -> bool {
+        match *self {
+            Value::Undefined | Value::Null => false,
+            Value::Bool(b) => b,
+            Value::Number(n) => n != 0.0 && !n.is_nan(),
+            Value::String(ref s) => !s.is_empty(),
+            Value::Object(_) | Value::Host(_) => true,
+        }
+    }
+
+    /// Return true for `null` and `undefined`.
+    pub fn is_nullish(&self) -> bool {
+        matches!(*self, Value::Undefined | Value::Null)
+    }
+
+    /// Return true if the value can be called.
+    pub fn is_function(&self) -> bool {
+        matches!(*self, Value::Object(ref object) if matches!(object.0.borrow().kind, ObjectKind::Function(_)))
+    }
+
+    /// Convert to a number, the way arithmetic does.
+    pub fn to_number(&self) -> f64 {
+        match *self {
+            Value::Undefined => f64::NAN,
+            Value::Null => 0.0,
+            Value::Bool(b) => if b { 1.0 } else { 0.0 },
+            Value::Number(n) => n,
+            Value::String(ref s) => string_to_number(s),
+            Value::Object(ref object) => match object.0.borrow().kind {
+                ObjectKind::Array(_) => string_to_number(&self.to_string()),
+                _ => f64::NAN,
+            },
+            Value::Host(_) => f64::NAN,
+        }
+    }
+
+    /// The elements of an array, or `None` for anything else.
+    pub fn as_array(&self) -> Option<Vec<Value>> {
+        match *self {
+            Value::Object(ref object) => match object.0.borrow().kind {
+                ObjectKind::Array(ref values) => Some(values.clone()),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// The host object this value is a handle to, if any.
+    pub fn as_host(&self) -> Option<HostObject> {
+        match *self {
+            Value::Host(object) => Some(object),
+            _ => None,
+        }
+    }
+
+    /// The result of the `typeof` operator.
+    pub fn type_of(&self) -> &'static str {
+        match *self {
+            Value::Undefined => "undefined",
+            Value::Null => "object",
+            Value::Bool(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::String(_) => "string",
+            Value::Object(_) if self.is_function() => "function",
+            Value::Object(_) | Value::Host(_) => "object",
+        }
+    }
+
+    /// `===`
+    pub fn strict_equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Undefined, Value::Undefined) | (Value::Null, Value::Null) => true,
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Number(a), Value::Number(b)) => a == b,
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::Object(a), Value::Object(b)) => Rc::ptr_eq(&a.0, &b.0),
+            (Value::Host(a), Value::Host(b)) => a == b,
+            _ => false,
+        }
+    }
+
+    /// `==`, which converts between types first.
+    pub fn loose_equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (a, b) if a.is_nullish() || b.is_nullish() => a.is_nullish() && b.is_nullish(),
+            (Value::Number(_), Value::String(_)) | (Value::String(_), Value::Number(_)) |
+            (Value::Bool(_), _) | (_, Value::Bool(_)) => self.to_number() == other.to_number(),
+            (Value::Object(_), Value::String(_) | Value::Number(_)) |
+            (Value::String(_) | Value::Number(_), Value::Object(_)) => self.to_string() == other.to_string(),
+            _ => self.strict_equals(other),
+        }
+    }
+}
+
+// `to_string` is JavaScript's `String(value)`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Undefined => write!(f, "undefined"),
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", number_to_string(n)),
+            Value::String(ref s) => write!(f, "{}", s),
+            Value::Host(object) => write!(f, "[object {}]", object.class),
+            Value::Object(ref object) => {
+                let data = object.0.borrow();
+                match data.kind {
+                    ObjectKind::Plain => write!(f, "[object Object]"),
+                    ObjectKind::Array(ref values) => {
+                        let parts: Vec<String> = values.iter()
+                            .map(|value: &Value| if value.is_nullish() { String::new() } else { value.to_string() })
+                            .collect();
+                        write!(f, "{}", parts.join(","))
+                    }
+                    ObjectKind::Function(ref function) => write!(f, "function {}() {{ [code] }}", function.name()),
+                    ObjectKind::Error => {
+                        let name: Value = data.get("name").unwrap_or(Value::string("Error"));
+                        match data.get("message") {
+                            Some(message) if !message.to_string().is_empty() => write!(f, "{}: {}", name, message),
+                            _ => write!(f, "{}", name),
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", inspect(self, true))
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::string(s)
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::String(Rc::from(s))
+    }
+}
+
+impl Object {
+    fn new(kind: ObjectKind) -> Object {
+        Object(Rc::new(RefCell::new(ObjectData { properties: Vec::new(), kind })))
+    }
+
+    /// Read an own property.
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.0.borrow().get(key)
+    }
+
+    /// Add or replace an own property.
+    pub fn set(&self, key: &str, value: Value) {
+        let mut data = self.0.borrow_mut();
+        if let ObjectKind::Array(ref mut values) = data.kind {
+            if let Some(index) = array_index(key) {
+                if index >= values.len() {
+                    values.resize(index + 1, Value::Undefined);
+                }
+                values[index] = value;
+                return;
+            }
+            if key == "length" {
+                let length: f64 = value.to_number();
+                if length >= 0.0 && length.is_finite() {
+                    values.resize(length as usize, Value::Undefined);
+                }
+                return;
+            }
+        }
+        match data.properties.iter_mut().find(|(name, _): &&mut (Rc<str>, Value)| &**name == key) {
+            Some((_, slot)) => *slot = value,
+            None => data.properties.push((Rc::from(key), value)),
+        }
+    }
+
+    /// The own property names, in order (array indices first).
+    pub fn keys(&self) -> Vec<String> {
+        let data = self.0.borrow();
+        let mut keys: Vec<String> = match data.kind {
+            ObjectKind::Array(ref values) => (0..values.len()).map(|i: usize| i.to_string()).collect(),
+            _ => Vec::new(),
+        };
+        keys.extend(data.properties.iter().map(|(name, _): &(Rc<str>, Value)| name.to_string()));
+        keys
+    }
+
+    fn delete(&self, key: &str) -> bool {
+        let mut data = self.0.borrow_mut();
+        if let ObjectKind::Array(ref mut values) = data.kind {
+            if let Some(index) = array_index(key) {
+                if index < values.len() {
+                    values[index] = Value::Undefined;
+                }
+                return true;
+            }
+        }
+        data.properties.retain(|(name, _): &(Rc<str>, Value)| &**name != key);
+        true
+    }
+}
+
+impl ObjectData {
+    fn get(&self, key: &str) -> Option<Value> {
+        match self.kind {
+            ObjectKind::Array(ref values) => {
+                if let Some(index) = array_index(key) {
+                    return Some(values.get(index).cloned().unwrap_or(Value::Undefined));
+                }
+                if key == "length" {
+                    return Some(Value::Number(values.len() as f64));
+                }
+            }
+            ObjectKind::Function(ref function) if key == "name" => return Some(Value::string(&function.name())),
+            _ => {}
+        }
+        self.properties.iter().find(|(name, _): &&(Rc<str>, Value)| &**name == key).map(|(_, value): &(Rc<str>, Value)| value.clone())
+    }
+}
+
+impl Function {
+    fn name(&self) -> String {
+        match *self {
+            Function::Script { ref definition, .. } => definition.name.as_deref().unwrap_or("").to_string(),
+            Function::Native { name, .. } => name.to_string(),
+            Function::Host { ref method, .. } => method.to_string(),
+        }
+    }
+}
+
+/// Parse an array index like "3" (but not "03" or "-1").
+fn array_index(key: &str) -> Option<usize> {
+    if key.is_empty() || (key.len() > 1 && key.starts_with('0')) || !key.bytes().all(|b: u8| b.is_ascii_digit()) {
+        return None;
+    }
+    key.parse().ok()
+}
+
+/// Format a number the way JavaScript does: no ".0" on integers, and `NaN` and `Infinity`.
+pub fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        "NaN".to_string()
+    } else if n.is_infinite() {
+        if n > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if n == 0.0 {
+        "0".to_string()
+    } else if n.fract() == 0.0 && n.abs() < 1e21 {
+        format!("{:.0}", n)
+    } else {
+        format!("{}", n)
+    }
+}
+
+/// Convert a string to a number: blank is 0, and anything that isn't a number is `NaN`.
+fn string_to_number(s: &str) -> f64 {
+    let s: &str = s.trim();
+    if s.is_empty() {
+        return 0.0;
+    }
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return u64::from_str_radix(hex, 16).map(|n: u64| n as f64).unwrap_or(f64::NAN);
+    }
+    match s {
+        "Infinity" | "+Infinity" => return f64::INFINITY,
+        "-Infinity" => return f64::NEG_INFINITY,
+        _ => {}
+    }
+    // Rust also accepts "inf" and "nan", which JavaScript doesn't.
+    if s.chars().any(|c: char| c.is_alphabetic() && c != 'e' && c != 'E') {
+        return f64::NAN;
+    }
+    s.parse().unwrap_or(f64::NAN)
+}
+
+/// Describe a value for the console: like `to_string`, but showing what is inside arrays
+/// and objects, with strings quoted when `quote` is set.
+pub fn inspect(value: &Value, quote: bool) -> String {
+    inspect_depth(value, quote, 0)
+}
+
+fn inspect_depth(value: &Value, quote: bool, depth: usize) -> String {
+    let object: &Object = match *value {
+        Value::String(ref s) if quote => return format!("'{}'", s),
+        Value::Object(ref object) => object,
+        _ => return value.to_string(),
+    };
+    if depth > 2 {
+        return if value.as_array().is_some() { "[Array]".to_string() } else { "[Object]".to_string() };
+    }
+    let data = object.0.borrow();
+    match data.kind {
+        ObjectKind::Array(ref values) => {
+            let parts: Vec<String> = values.iter().map(|value: &Value| inspect_depth(value, true, depth + 1)).collect();
+            format!("[{}]", parts.join(", "))
+        }
+        ObjectKind::Function(ref function) => format!("[Function: {}]", function.name()),
+        ObjectKind::Error => value.to_string(),
+        ObjectKind::Plain => {
+            if data.properties.is_empty() {
+                return "{}".to_string();
+            }
+            let parts: Vec<String> = data.properties.iter()
+                .map(|(key, value): &(Rc<str>, Value)| format!("{}: {}", key, inspect_depth(value, true, depth + 1)))
+                .collect();
+            format!("{{ {} }}", parts.join(", "))
+        }
+    }
+}
+
+
+// Errors
+
+/// An error that escaped a script: a syntax error, or an exception nobody caught.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScriptError {
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Uncaught {}", self.message)
+    }
+}
+
+impl Error for ScriptError {}
+
+/// Create an error object, e.g. a `TypeError`.
+fn make_error(name: &str, message: &str) -> Value {
+    let error: Object = Object::new(ObjectKind::Error);
+    error.set("name", Value::string(name));
+    error.set("message", Value::string(message));
+    Value::Object(error)
+}
+
+fn type_error(message: &str) -> Value {
+    make_error("TypeError", message)
+}
+
+
+// The host
+
+/// What the interpreter calls to reach objects that aren't its own.
+pub trait Host {
+    /// Read a property of a host object. Unknown properties are `undefined`.
+    fn get(&mut self, object: HostObject, name: &str) -> Result<Value, String>;
+
+    /// Write a property of a host object.
+    fn set(&mut self, object: HostObject, name: &str, value: Value) -> Result<(), String>;
+
+    /// Call a method of a host object.
+    fn call(&mut self, object: HostObject, method: &str, args: &[Value]) -> Result<Value, String>;
+
+    /// Show a `console` message. `level` is the method's name: "log", "warn", etc.
+    fn console(&mut self, level: &str, message: &str) {
+        match level {
+            "warn" | "error" => eprintln!("{}", message),
+            _ => println!("{}", message),
+        }
+    }
+}
+
+/// A host with no objects of its own, for running plain JavaScript.
+pub struct NoHost;
+
+impl Host for NoHost {
+    fn get(&mut self, _: HostObject, _: &str) -> Result<Value, String> {
+        Ok(Value::Undefined)
+    }
+
+    fn set(&mut self, _: HostObject, _: &str, _: Value) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn call(&mut self, object: HostObject, method: &str, _: &[Value]) -> Result<Value, String> {
+        Err(format!("{}.{} is not a function", object.class, method))
+    }
+}
+
+
+// The lexer
+
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+    Number(f64),
+    String(String),
+    Template(Vec<TemplatePart>),
+    Identifier(String), // including keywords
+    Punctuator(&'static str),
+    Eof,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+enum TemplatePart {
+    Text(String),
+    Expression(String), // source code, parsed later
+}
+
+/// Punctuators, longest first so that e.g. `===` isn't read as `==` and `=`.
+const PUNCTUATORS: [&str; 51] = [
+    ">>>=", "...", "===", "!==", "**=", "<<=", ">>=", ">>>", "&&=", "||=", "??=",
+    "=>", "==", "!=", "<=", ">=", "&&", "||", "??", "?.", "++", "--", "+=", "-=", "*=", "/=",
+    "%=", "&=", "|=", "^=", "**", "<<", ">>",
+    "{", "}", "(", ")", "[", "]", ";", ",", "<", ">", "+", "-", "*", "/", "%", "&", "|", "^",
+];
+
+const SINGLE_PUNCTUATORS: [&str; 7] = [".", "!", "~", "?", ":", "=", "@"];
+
+/// Words that can't be used as variable names.
+const KEYWORDS: [&str; 29] = [
+    "break", "case", "catch", "class", "const", "continue", "default", "delete", "do", "else",
+    "false", "finally", "for", "function", "if", "in", "instanceof", "let", "new", "null",
+    "return", "switch", "this", "throw", "true", "try", "typeof", "var", "while",
+];
+
+struct Lexer<'a> {
+    input: &'a str,
+    position: usize,
+    line: usize,
+}
+
+impl Lexer<'_> {
+    /// Split `input` into tokens, each with the line it starts on.
+    fn tokenize(input: &str) -> Result<Vec<(Token, usize)>, String> {
+        let mut lexer: Lexer = Lexer { input, position: 0, line: 1 };
+        let mut tokens: Vec<(Token, usize)> = Vec::new();
+        loop {
+            lexer.skip_whitespace_and_comments()?;
+            let line: usize = lexer.line;
+            let token: Token = lexer.next_token()?;
+            let eof: bool = token == Token::Eof;
+            tokens.push((token, line));
+            if eof {
+                return Ok(tokens);
+            }
+        }
+    }
+
+    fn rest(&self) -> &str {
+        &self.input[self.position..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn advance(&mut self) -> Option<char> {
+        let c: char = self.peek()?;
+        self.position += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), String> {
+        loop {
+            let rest: &str = self.rest();
+            if rest.starts_with("//") {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.advance();
+                }
+            } else if rest.starts_with("/*") {
+                let end: usize = rest.find("*/").ok_or(format!("Unterminated comment on line {}", self.line))?;
+                let end: usize = self.position + end + 2;
+                while self.position < end {
+                    self.advance();
+                }
+            } else if self.peek().is_some_and(char::is_whitespace) {
+                self.advance();
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token, String> {
+        let c: char = match self.peek() {
+            Some(c) => c,
+            None => return Ok(Token::Eof),
+        };
+        let next_is_digit: bool = self.rest()[c.len_utf8()..].starts_with(|c: char| c.is_ascii_digit());
+        if c.is_ascii_digit() || (c == '.' && next_is_digit) {
+            return self.number();
+        }
+        if c == '"' || c == '\'' {
+            return self.string(c);
+        }
+        if c == '`' {
+            return self.template();
+        }
+        if c.is_alphabetic() || c == '_' || c == '$' {
+            let start: usize = self.position;
+            while self.peek().is_some_and(|c: char| c.is_alphanumeric() || c == '_' || c == '$') {
+                self.advance();
+            }
+            return Ok(Token::Identifier(self.input[start..self.position].to_string()));
+        }
+        // `a?.5:1` is a conditional, not optional chaining.
+        if self.rest().starts_with("?.") && !next_is_digit_after(self.rest(), 2) {
+            self.position += 2;
+            return Ok(Token::Punctuator("?."));
+        }
+        for punctuator in PUNCTUATORS.iter().chain(SINGLE_PUNCTUATORS.iter()) {
+            if *punctuator != "?." && self.rest().starts_with(punctuator) {
+                self.position += punctuator.len();
+                return Ok(Token::Punctuator(punctuator));
+            }
+        }
+        Err(format!("Unexpected character {:?} on line {}", c, self.line))
+    }
+
+    fn number(&mut self) -> Result<Token, String> {
+        let start: usize = self.position;
+        let rest: &str = self.rest();
+        if rest.starts_with("0x") || rest.starts_with("0X") {
+            self.position += 2;
+            while self.peek().is_some_and(|c: char| c.is_ascii_hexdigit()) {
+                self.advance();
+            }
+            let digits: &str = &self.input[start + 2..self.position];
+            return u64::from_str_radix(digits, 16)
+                .map(|n: u64| Token::Number(n as f64))
+                .map_err(|_| format!("Invalid number on line {}", self.line));
+        }
+        while self.peek().is_some_and(|c: char| c.is_ascii_digit() || c == '.' || c == '_') {
+            self.advance();
+        }
+        if matches!(self.peek(), Some('e' | 'E')) {
+            self.advance();
+            if matches!(self.peek(), Some('+' | '-')) {
+                self.advance();
+            }
+            while self.peek().is_some_and(|c: char| c.is_ascii_digit()) {
+                self.advance();
+            }
+        }
+        let text: String = self.input[start..self.position].replace('_', "");
+        text.parse().map(Token::Number).map_err(|_| format!("Invalid number {} on line {}", text, self.line))
+    }
+
+    /// Read the character after a `\` in a string.
+    fn escape(&mut self) -> Result<char, String> {
+        let c: char = self.advance().ok_or("Unterminated string")?;
+        Ok(match c {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            'v' => '\u{b}',
+            '0' => '\0',
+            'u' | 'x' => {
+                let length: usize = if c == 'x' { 2 } else if self.peek() == Some('{') { 0 } else { 4 };
+                let digits: String = if length == 0 {
+                    self.advance();
+                    let digits: String = self.rest().chars().take_while(|&c: &char| c != '}').collect();
+                    self.position += digits.len() + 1;
+                    digits
+                } else {
+                    let digits: String = self.rest().chars().take(length).collect();
+                    self.position += digits.len();
+                    digits
+                };
+                u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32)
+                    .ok_or(format!("Invalid escape on line {}", self.line))?
+            }
+            c => c, // including \\, \', \", and a line continuation's \n
+        })
+    }
+
+    fn string(&mut self, quote: char) -> Result<Token, String> {
+        self.advance();
+        let mut value: String = String::new();
+        loop {
+            match self.advance() {
+                None | Some('\n') => return Err(format!("Unterminated string on line {}", self.line)),
+                Some(c) if c == quote => return Ok(Token::String(value)),
+                Some('\\') => value.push(self.escape()?),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn template(&mut self) -> Result<Token, String> {
+        self.advance();
+        let mut parts: Vec<TemplatePart> = Vec::new();
+        let mut text: String = String::new();
+        loop {
+            match self.advance() {
+                None => return Err(format!("Unterminated template on line {}", self.line)),
+                Some('`') => break,
+                Some('\\') => text.push(self.escape()?),
+                Some('$') if self.peek() == Some('{') => {
+                    self.advance();
+                    parts.push(TemplatePart::Text(std::mem::take(&mut text)));
+                    // Find the matching `}`, skipping over nested braces and strings.
+                    let start: usize = self.position;
+                    let mut depth: usize = 0;
+                    loop {
+                        match self.advance() {
+                            None => return Err(format!("Unterminated template on line {}", self.line)),
+                            Some('{') => depth += 1,
+                            Some('}') if depth == 0 => break,
+                            Some('}') => depth -= 1,
+                            Some(quote @ ('"' | '\'' | '`')) => {
+                                loop {
+                                    match self.advance() {
+                                        None => break,
+                                        Some(c) if c == quote => break,
+                                        Some('\\') => {
+                                            self.advance();
+                                        }
+                                        Some(_) => {}
+                                    }
+                                }
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                    parts.push(TemplatePart::Expression(self.input[start..self.position - 1].to_string()));
+                }
+                Some(c) => text.push(c),
+            }
+        }
+        parts.push(TemplatePart::Text(text));
+        Ok(Token::Template(parts))
+    }
+}
+
+fn next_is_digit_after(s: &str, offset: usize) -> bool {
+    s[offset..].starts_with(|c: char| c.is_ascii_digit())
+}
+
+
+// The syntax tree
+
+#[derive(Debug)]
+enum Statement {
+    Expression(Expression),
+    Declaration(DeclarationKind, Vec<(Rc<str>, Option<Expression>)>),
+    Function(Rc<FunctionDefinition>),
+    Return(Option<Expression>),
+    If(Expression, Box<Statement>, Option<Box<Statement>>),
+    While(Expression, Box<Statement>),
+    DoWhile(Box<Statement>, Expression),
+    For(Option<Box<Statement>>, Option<Expression>, Option<Expression>, Box<Statement>),
+    ForOf(Option<DeclarationKind>, Rc<str>, Expression, Box<Statement>),
+    ForIn(Option<DeclarationKind>, Rc<str>, Expression, Box<Statement>),
+    Switch(Expression, Vec<(Option<Expression>, Vec<Statement>)>),
+    Break,
+    Continue,
+    Throw(Expression),
+    Try(Vec<Statement>, Option<(Option<Rc<str>>, Vec<Statement>)>, Option<Vec<Statement>>),
+    Block(Vec<Statement>),
+    Empty,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum DeclarationKind {
+    Var,
+    Let,
+    Const,
+}
+
+#[derive(Debug)]
+enum Expression {
+    Number(f64),
+    String(Rc<str>),
+    Template(Vec<Expression>), // alternating text and expressions, starting with text
+    Bool(bool),
+    Null,
+    Identifier(Rc<str>),
+    This,
+    Array(Vec<Expression>),
+    Object(Vec<(Rc<str>, Expression)>),
+    Function(Rc<FunctionDefinition>),
+    Spread(Box<Expression>),
+    Unary(&'static str, Box<Expression>),
+    Update(&'static str, bool, Box<Expression>), // operator, prefix?, target
+    Binary(&'static str, Box<Expression>, Box<Expression>),
+    Logical(&'static str, Box<Expression>, Box<Expression>),
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+    Assign(&'static str, Box<Expression>, Box<Expression>),
+    Member(Box<Expression>, Rc<str>, bool), // object, property, optional (`?.`)?
+    Index(Box<Expression>, Box<Expression>, bool),
+    Call(Box<Expression>, Vec<Expression>),
+    New(Box<Expression>, Vec<Expression>),
+    Sequence(Vec<Expression>),
+}
+
+#[derive(Debug)]
+struct FunctionDefinition {
+    name: Option<Rc<str>>,
+    parameters: Vec<(Rc<str>, Option<Expression>)>, // name, default value
+    rest: Option<Rc<str>>,
+    body: FunctionBody,
+    arrow: bool,
+}
+
+#[derive(Debug)]
+enum FunctionBody {
+    Block(Vec<Statement>),
+    Expression(Expression),
+}
+
+
+// The parser
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    position: usize,
+}
+
+/// Binary operators and their precedence; higher binds tighter.
+fn binary_precedence(operator: &str) -> Option<u8> {
+    Some(match operator {
+        "??" | "||" => 1,
+        "&&" => 2,
+        "|" => 3,
+        "^" => 4,
+        "&" => 5,
+        "==" | "!=" | "===" | "!==" => 6,
+        "<" | ">" | "<=" | ">=" | "in" | "instanceof" => 7,
+        "<<" | ">>" | ">>>" => 8,
+        "+" | "-" => 9,
+        "*" | "/" | "%" => 10,
+        "**" => 11,
+        _ => return None,
+    })
+}
+
+const ASSIGNMENT_OPERATORS: [&str; 16] = [
+    "=", "+=", "-=", "*=", "/=", "%=", "**=", "<<=", ">>=", ">>>=", "&=", "|=", "^=", "&&=", "||=", "??=",
+];
+
+impl Parser {
+    fn parse_program(source: &str) -> Result<Vec<Statement>, String> {
+        let mut parser: Parser = Parser { tokens: Lexer::tokenize(source)?, position: 0 };
+        let mut statements: Vec<Statement> = Vec::new();
+        while !parser.at_eof() {
+            statements.push(parser.statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn parse_expression(source: &str) -> Result<Expression, String> {
+        let mut parser: Parser = Parser { tokens: Lexer::tokenize(source)?, position: 0 };
+        let expression: Expression = parser.expression()?;
+        if !parser.at_eof() {
+            return Err(parser.unexpected());
+        }
+        Ok(expression)
+    }
+
+    // Looking at tokens
+
+    fn peek(&self) -> &Token {
+        &self.tokens[self.position].0
+    }
+
+    fn peek_at(&self, offset: usize) -> &Token {
+        let index: usize = (self.position + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].0
+    }
+
+    fn line(&self) -> usize {
+        self.tokens[self.position].1
+    }
+
+    fn at_eof(&self) -> bool {
+        *self.peek() == Token::Eof
+    }
+
+    /// Return true if there is a line break before the current token.
+    fn newline_before(&self) -> bool {
+        self.position > 0 && self.tokens[self.position - 1].1 < self.line()
+    }
+
+    fn is(&self, punctuator: &str) -> bool {
+        matches!(*self.peek(), Token::Punctuator(p) if p == punctuator)
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        matches!(*self.peek(), Token::Identifier(ref name) if name == keyword)
+    }
+
+    fn advance(&mut self) -> Token {
+        let token: Token = self.tokens[self.position].0.clone();
+        if self.position < self.tokens.len() - 1 {
+            self.position += 1;
+        }
+        token
+    }
+
+    fn eat(&mut self, punctuator: &str) -> bool {
+        let found: bool = self.is(punctuator);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let found: bool = self.is_keyword(keyword);
+        if found {
+            self.advance();
+        }
+        found
+    }
+
+    fn expect(&mut self, punctuator: &str) -> Result<(), String> {
+        if self.eat(punctuator) { Ok(()) } else { Err(self.unexpected()) }
+    }
+
+    fn unexpected(&self) -> String {
+        let found: String = match *self.peek() {
+            Token::Eof => "end of input".to_string(),
+            Token::Number(n) => number_to_string(n),
+            Token::String(ref s) => format!("string {:?}", s),
+            Token::Template(_) => "template".to_string(),
+            Token::Identifier(ref name) => format!("'{}'", name),
+            Token::Punctuator(p) => format!("'{}'", p),
+        };
+        format!("Unexpected {} on line {}", found, self.line())
+    }
+
+    fn identifier(&mut self) -> Result<Rc<str>, String> {
+        match *self.peek() {
+            Token::Identifier(ref name) if !KEYWORDS.contains(&name.as_str()) => {
+                let name: Rc<str> = Rc::from(name.as_str());
+                self.advance();
+                Ok(name)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// A property name after `.`, where keywords are allowed.
+    fn property_name(&mut self) -> Result<Rc<str>, String> {
+        match self.advance() {
+            Token::Identifier(name) => Ok(Rc::from(name)),
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    /// End a statement: a `;`, or a line break, `}`, or the end of input before the next token.
+    fn semicolon(&mut self) -> Result<(), String> {
+        if self.eat(";") || self.is("}") || self.at_eof() || self.newline_before() {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    // Statements
+
+    fn statement(&mut self) -> Result<Statement, String> {
+        if self.eat("{") {
+            return Ok(Statement::Block(self.block_rest()?));
+        }
+        if self.eat(";") {
+            return Ok(Statement::Empty);
+        }
+        let keyword: String = match *self.peek() {
+            Token::Identifier(ref name) => name.clone(),
+            _ => String::new(),
+        };
+        match keyword.as_str() {
+            "var" | "let" | "const" => {
+                let statement: Statement = self.declaration()?;
+                self.semicolon()?;
+                Ok(statement)
+            }
+            "function" => {
+                self.advance();
+                let name: Rc<str> = self.identifier()?;
+                Ok(Statement::Function(self.function_rest(Some(name))?))
+            }
+            "return" => {
+                self.advance();
+                let value: Option<Expression> = if self.is(";") || self.is("}") || self.at_eof() || self.newline_before() {
+                    None
+                } else {
+                    Some(self.expression()?)
+                };
+                self.semicolon()?;
+                Ok(Statement::Return(value))
+            }
+            "if" => {
+                self.advance();
+                self.expect("(")?;
+                let test: Expression = self.expression()?;
+                self.expect(")")?;
+                let consequent: Statement = self.statement()?;
+                let alternate: Option<Box<Statement>> = if self.eat_keyword("else") { Some(Box::new(self.statement()?)) } else { None };
+                Ok(Statement::If(test, Box::new(consequent), alternate))
+            }
+            "while" => {
+                self.advance();
+                self.expect("(")?;
+                let test: Expression = self.expression()?;
+                self.expect(")")?;
+                Ok(Statement::While(test, Box::new(self.statement()?)))
+            }
+            "do" => {
+                self.advance();
+                let body: Statement = self.statement()?;
+                if !self.eat_keyword("while") {
+                    return Err(self.unexpected());
+                }
+                self.expect("(")?;
+                let test: Expression = self.expression()?;
+                self.expect(")")?;
+                self.eat(";");
+                Ok(Statement::DoWhile(Box::new(body), test))
+            }
+            "for" => self.for_statement(),
+            "switch" => self.switch_statement(),
+            "break" | "continue" => {
+                self.advance();
+                self.semicolon()?;
+                Ok(if keyword == "break" { Statement::Break } else { Statement::Continue })
+            }
+            "throw" => {
+                self.advance();
+                let value: Expression = self.expression()?;
+                self.semicolon()?;
+                Ok(Statement::Throw(value))
+            }
+            "try" => self.try_statement(),
+            "class" | "async" | "yield" | "import" | "export" => Err(format!("'{}' is not supported (line {})", keyword, self.line())),
+            _ => {
+                let expression: Expression = self.expression()?;
+                self.semicolon()?;
+                Ok(Statement::Expression(expression))
+            }
+        }
+    }
+
+    /// The statements of a block, after its `{`.
+    fn block_rest(&mut self) -> Result<Vec<Statement>, String> {
+        let mut statements: Vec<Statement> = Vec::new();
+        while !self.eat("}") {
+            if self.at_eof() {
+                return Err(self.unexpected());
+            }
+            statements.push(self.statement()?);
+        }
+        Ok(statements)
+    }
+
+    fn block(&mut self) -> Result<Vec<Statement>, String> {
+        self.expect("{")?;
+        self.block_rest()
+    }
+
+    fn declaration_kind(&mut self) -> Option<DeclarationKind> {
+        let kind: DeclarationKind = match *self.peek() {
+            Token::Identifier(ref name) if name == "var" => DeclarationKind::Var,
+            Token::Identifier(ref name) if name == "let" => DeclarationKind::Let,
+            Token::Identifier(ref name) if name == "const" => DeclarationKind::Const,
+            _ => return None,
+        };
+        self.advance();
+        Some(kind)
+    }
+
+    fn declaration(&mut self) -> Result<Statement, String> {
+        let kind: DeclarationKind = self.declaration_kind().ok_or_else(|| self.unexpected())?;
+        let mut declarations: Vec<(Rc<str>, Option<Expression>)> = Vec::new();
+        loop {
+            let name: Rc<str> = self.identifier()?;
+            let value: Option<Expression> = if self.eat("=") { Some(self.assignment()?) } else { None };
+            declarations.push((name, value));
+            if !self.eat(",") {
+                return Ok(Statement::Declaration(kind, declarations));
+            }
+        }
+    }
+
+    fn for_statement(&mut self) -> Result<Statement, String> {
+        self.advance();
+        self.expect("(")?;
+        let start: usize = self.position;
+
+        // `for (const x of xs)` and `for (key in object)`
+        let kind: Option<DeclarationKind> = self.declaration_kind();
+        if let Token::Identifier(_) = *self.peek() {
+            if matches!(*self.peek_at(1), Token::Identifier(ref word) if word == "of" || word == "in") {
+                let name: Rc<str> = self.identifier()?;
+                let of: bool = self.eat_keyword("of");
+                if !of {
+                    self.advance();
+                }
+                let iterable: Expression = self.expression()?;
+                self.expect(")")?;
+                let body: Box<Statement> = Box::new(self.statement()?);
+                return Ok(if of { Statement::ForOf(kind, name, iterable, body) } else { Statement::ForIn(kind, name, iterable, body) });
+            }
+        }
+        self.position = start;
+
+        let init: Option<Box<Statement>> = if self.is(";") {
+            None
+        } else if self.is_keyword("var") || self.is_keyword("let") || self.is_keyword("const") {
+            Some(Box::new(self.declaration()?))
+        } else {
+            Some(Box::new(Statement::Expression(self.expression()?)))
+        };
+        self.expect(";")?;
+        let test: Option<Expression> = if self.is(";") { None } else { Some(self.expression()?) };
+        self.expect(";")?;
+        let update: Option<Expression> = if self.is(")") { None } else { Some(self.expression()?) };
+        self.expect(")")?;
+        Ok(Statement::For(init, test, update, Box::new(self.statement()?)))
+    }
+
+    fn switch_statement(&mut self) -> Result<Statement, String> {
+        self.advance();
+        self.expect("(")?;
+        let discriminant: Expression = self.expression()?;
+        self.expect(")")?;
+        self.expect("{")?;
+        let mut cases: Vec<(Option<Expression>, Vec<Statement>)> = Vec::new();
+        while !self.eat("}") {
+            let test: Option<Expression> = if self.eat_keyword("case") {
+                Some(self.expression()?)
+            } else if self.eat_keyword("default") {
+                None
+            } else {
+                return Err(self.unexpected());
+            };
+            self.expect(":")?;
+            let mut body: Vec<Statement> = Vec::new();
+            while !self.is("}") && !self.is_keyword("case") && !self.is_keyword("default") {
+                if self.at_eof() {
+                    return Err(self.unexpected());
+                }
+                body.push(self.statement()?);
+            }
+            cases.push((test, body));
+        }
+        Ok(Statement::Switch(discriminant, cases))
+    }
+
+    fn try_statement(&mut self) -> Result<Statement, String> {
+        self.advance();
+        let body: Vec<Statement> = self.block()?;
+        let handler: Option<(Option<Rc<str>>, Vec<Statement>)> = if self.eat_keyword("catch") {
+            let parameter: Option<Rc<str>> = if self.eat("(") {
+                let name: Rc<str> = self.identifier()?;
+                self.expect(")")?;
+                Some(name)
+            } else {
+                None
+            };
+            Some((parameter, self.block()?))
+        } else {
+            None
+        };
+        let finalizer: Option<Vec<Statement>> = if self.eat_keyword("finally") { Some(self.block()?) } else { None };
+        if handler.is_none() && finalizer.is_none() {
+            return Err(self.unexpected());
+        }
+        Ok(Statement::Try(body, handler, finalizer))
+    }
+
+    /// The parameters and body of a function, after its name.
+    fn function_rest(&mut self, name: Option<Rc<str>>) -> Result<Rc<FunctionDefinition>, String> {
+        self.expect("(")?;
+        let (parameters, rest) = self.parameters()?;
+        let body: FunctionBody = FunctionBody::Block(self.block()?);
+        Ok(Rc::new(FunctionDefinition { name, parameters, rest, body, arrow: false }))
+    }
+
+    /// A parameter list, after its `(`.
+    #[allow(clippy::type_complexity)]
+    fn parameters(&mut self) -> Result<(Vec<(Rc<str>, Option<Expression>)>, Option<Rc<str>>), String> {
+        let mut parameters: Vec<(Rc<str>, Option<Expression>)> = Vec::new();
+        let mut rest: Option<Rc<str>> = None;
+        while !self.eat(")") {
+            if self.eat("...") {
+                rest = Some(self.identifier()?);
+                self.expect(")")?;
+                break;
+            }
+            let name: Rc<str> = self.identifier()?;
+            let default: Option<Expression> = if self.eat("=") { Some(self.assignment()?) } else { None };
+            parameters.push((name, default));
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        Ok((parameters, rest))
+    }
+
+    // Expressions
+
+    fn expression(&mut self) -> Result<Expression, String> {
+        let first: Expression = self.assignment()?;
+        if !self.is(",") {
+            return Ok(first);
+        }
+        let mut expressions: Vec<Expression> = vec![first];
+        while self.eat(",") {
+            expressions.push(self.assignment()?);
+        }
+        Ok(Expression::Sequence(expressions))
+    }
+
+    /// Return true if the tokens from here on start an arrow function.
+    fn at_arrow(&self) -> bool {
+        match *self.peek() {
+            Token::Identifier(_) => matches!(*self.peek_at(1), Token::Punctuator("=>")),
+            Token::Punctuator("(") => {
+                let mut depth: usize = 0;
+                for offset in 0.. {
+                    match *self.peek_at(offset) {
+                        Token::Punctuator("(") => depth += 1,
+                        Token::Punctuator(")") => {
+                            depth -= 1;
+                            if depth == 0 {
+                                return matches!(*self.peek_at(offset + 1), Token::Punctuator("=>"));
+                            }
+                        }
+                        Token::Eof => return false,
+                        _ => {}
+                    }
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    fn arrow_function(&mut self) -> Result<Expression, String> {
+        let (parameters, rest) = if self.eat("(") {
+            self.parameters()?
+        } else {
+            (vec![(self.identifier()?, None)], None)
+        };
+        self.expect("=>")?;
+        let body: FunctionBody = if self.eat("{") {
+            FunctionBody::Block(self.block_rest()?)
+        } else {
+            FunctionBody::Expression(self.assignment()?)
+        };
+        Ok(Expression::Function(Rc::new(FunctionDefinition { name: None, parameters, rest, body, arrow: true })))
+    }
+
+    fn assignment(&mut self) -> Result<Expression, String> {
+        if self.at_arrow() {
+            return self.arrow_function();
+        }
+        let target: Expression = self.conditional()?;
+        let operator: &'static str = match *self.peek() {
+            Token::Punctuator(p) if ASSIGNMENT_OPERATORS.contains(&p) => p,
+            _ => return Ok(target),
+        };
+        if !matches!(target, Expression::Identifier(_) | Expression::Member(..) | Expression::Index(..)) {
+            return Err(format!("Invalid assignment target on line {}", self.line()));
+        }
+        self.advance();
+        let value: Expression = self.assignment()?;
+        Ok(Expression::Assign(operator, Box::new(target), Box::new(value)))
+    }
+
+    fn conditional(&mut self) -> Result<Expression, String> {
+        let test: Expression = self.binary(1)?;
+        if !self.eat("?") {
+            return Ok(test);
+        }
+        let consequent: Expression = self.assignment()?;
+        self.expect(":")?;
+        let alternate: Expression = self.assignment()?;
+        Ok(Expression::Conditional(Box::new(test), Box::new(consequent), Box::new(alternate)))
+    }
+
+    /// Parse binary operators of at least `min_precedence`, by precedence climbing.
+    fn binary(&mut self, min_precedence: u8) -> Result<Expression, String> {
+        let mut left: Expression = self.unary()?;
+        loop {
+            let operator: &'static str = match *self.peek() {
+                Token::Punctuator(p) => p,
+                Token::Identifier(ref word) if word == "in" => "in",
+                Token::Identifier(ref word) if word == "instanceof" => "instanceof",
+                _ => return Ok(left),
+            };
+            let precedence: u8 = match binary_precedence(operator) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => return Ok(left),
+            };
+            self.advance();
+            // `**` is right-associative; everything else is left-associative.
+            let right: Expression = self.binary(if operator == "**" { precedence } else { precedence + 1 })?;
+            left = match operator {
+                "&&" | "||" | "??" => Expression::Logical(operator, Box::new(left), Box::new(right)),
+                _ => Expression::Binary(operator, Box::new(left), Box::new(right)),
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<Expression, String> {
+        let operator: &'static str = match *self.peek() {
+            Token::Punctuator(p @ ("!" | "-" | "+" | "~")) => p,
+            Token::Punctuator(p @ ("++" | "--")) => {
+                self.advance();
+                let target: Expression = self.unary()?;
+                return Ok(Expression::Update(p, true, Box::new(target)));
+            }
+            Token::Identifier(ref word) if word == "typeof" => "typeof",
+            Token::Identifier(ref word) if word == "void" => "void",
+            Token::Identifier(ref word) if word == "delete" => "delete",
+            _ => return self.postfix(),
+        };
+        self.advance();
+        Ok(Expression::Unary(operator, Box::new(self.unary()?)))
+    }
+
+    fn postfix(&mut self) -> Result<Expression, String> {
+        let expression: Expression = self.call()?;
+        if (self.is("++") || self.is("--")) && !self.newline_before() {
+            let operator: &'static str = if self.is("++") { "++" } else { "--" };
+            self.advance();
+            return Ok(Expression::Update(operator, false, Box::new(expression)));
+        }
+        Ok(expression)
+    }
+
+    fn arguments(&mut self) -> Result<Vec<Expression>, String> {
+        let mut arguments: Vec<Expression> = Vec::new();
+        while !self.eat(")") {
+            arguments.push(if self.eat("...") { Expression::Spread(Box::new(self.assignment()?)) } else { self.assignment()? });
+            if !self.eat(",") {
+                self.expect(")")?;
+                break;
+            }
+        }
+        Ok(arguments)
+    }
+
+    fn call(&mut self) -> Result<Expression, String> {
+        let mut expression: Expression = if self.eat_keyword("new") {
+            let callee: Expression = self.member_only()?;
+            let arguments: Vec<Expression> = if self.eat("(") { self.arguments()? } else { Vec::new() };
+            Expression::New(Box::new(callee), arguments)
+        } else {
+            self.primary()?
+        };
+        loop {
+            if self.eat(".") {
+                expression = Expression::Member(Box::new(expression), self.property_name()?, false);
+            } else if self.eat("?.") {
+                expression = if self.eat("[") {
+                    let index: Expression = self.expression()?;
+                    self.expect("]")?;
+                    Expression::Index(Box::new(expression), Box::new(index), true)
+                } else {
+                    Expression::Member(Box::new(expression), self.property_name()?, true)
+                };
+            } else if self.eat("[") {
+                let index: Expression = self.expression()?;
+                self.expect("]")?;
+                expression = Expression::Index(Box::new(expression), Box::new(index), false);
+            } else if self.eat("(") {
+                expression = Expression::Call(Box::new(expression), self.arguments()?);
+            } else if let Token::Template(_) = *self.peek() {
+                return Err(format!("Tagged templates are not supported (line {})", self.line()));
+            } else {
+                return Ok(expression);
+            }
+        }
+    }
+
+    /// The callee of `new`: a name and property accesses, but no calls.
+    fn member_only(&mut self) -> Result<Expression, String> {
+        let mut expression: Expression = self.primary()?;
+        while self.eat(".") {
+            expression = Expression::Member(Box::new(expression), self.property_name()?, false);
+        }
+        Ok(expression)
+    }
+
+    fn primary(&mut self) -> Result<Expression, String> {
+        let line: usize = self.line();
+        match self.advance() {
+            Token::Number(n) => Ok(Expression::Number(n)),
+            Token::String(s) => Ok(Expression::String(Rc::from(s))),
+            Token::Template(parts) => {
+                let mut pieces: Vec<Expression> = Vec::new();
+                for part in parts {
+                    pieces.push(match part {
+                        TemplatePart::Text(text) => Expression::String(Rc::from(text)),
+                        TemplatePart::Expression(source) => Parser::parse_expression(&source)
+                            .map_err(|error: String| format!("{} (in a template on line {})", error, line))?,
+                    });
+                }
+                Ok(Expression::Template(pieces))
+            }
+            Token::Punctuator("(") => {
+                let expression: Expression = self.expression()?;
+                self.expect(")")?;
+                Ok(expression)
+            }
+            Token::Punctuator("[") => {
+                let mut elements: Vec<Expression> = Vec::new();
+                while !self.eat("]") {
+                    elements.push(if self.eat("...") { Expression::Spread(Box::new(self.assignment()?)) } else { self.assignment()? });
+                    if !self.eat(",") {
+                        self.expect("]")?;
+                        break;
+                    }
+                }
+                Ok(Expression::Array(elements))
+            }
+            Token::Punctuator("{") => self.object_literal(),
+            Token::Identifier(word) => match word.as_str() {
+                "true" => Ok(Expression::Bool(true)),
+                "false" => Ok(Expression::Bool(false)),
+                "null" => Ok(Expression::Null),
+                "this" => Ok(Expression::This),
+                "function" => {
+                    let name: Option<Rc<str>> = if self.is("(") { None } else { Some(self.identifier()?) };
+                    Ok(Expression::Function(self.function_rest(name)?))
+                }
+                _ if KEYWORDS.contains(&word.as_str()) => {
+                    self.position -= 1;
+                    Err(self.unexpected())
+                }
+                _ => Ok(Expression::Identifier(Rc::from(word))),
+            },
+            _ => {
+                self.position -= 1;
+                Err(self.unexpected())
+            }
+        }
+    }
+
+    /// An object literal, after its `{`.
+    fn object_literal(&mut self) -> Result<Expression, String> {
+        let mut properties: Vec<(Rc<str>, Expression)> = Vec::new();
+        while !self.eat("}") {
+            let key: Rc<str> = match self.advance() {
+                Token::Identifier(name) => Rc::from(name),
+                Token::String(s) => Rc::from(s),
+                Token::Number(n) => Rc::from(number_to_string(n)),
+                _ => {
+                    self.position -= 1;
+                    return Err(self.unexpected());
+                }
+            };
+            let value: Expression = if self.eat(":") {
+                self.assignment()?
+            } else if self.is("(") {
+                Expression::Function(self.function_rest(Some(key.clone()))?)
+            } else {
+                Expression::Identifier(key.clone()) // shorthand `{ x }`
+            };
+            properties.push((key, value));
+            if !self.eat(",") {
+                self.expect("}")?;
+                break;
+            }
+        }
+        Ok(Expression::Object(properties))
+    }
+}
+
+
+// Evaluation
+
+/// Variables in scope: a function call, a block, or the global scope.
+type Env = Rc<RefCell<Environment>>;
+
+struct Environment {
+    bindings: HashMap<Rc<str>, Binding>,
+    this: Option<Value>,   // set in function scopes (except arrows') and the global scope
+    function_scope: bool,  // where `var` declarations go
+    parent: Option<Env>,
+}
+
+struct Binding {
+    value: Value,
+    mutable: bool,
+}
+
+fn new_env(parent: &Env) -> Env {
+    Rc::new(RefCell::new(Environment { bindings: HashMap::new(), this: None, function_scope: false, parent: Some(parent.clone()) }))
+}
+
+/// How a statement finished.
+enum Completion {
+    Normal,
+    Return(Value),
+    Break,
+    Continue,
+}
+
+/// A place to store a value: a variable or a property.
+enum Reference {
+    Variable(Rc<str>),
+    Property(Value, Rc<str>),
+}
+
+/// The interpreter's state that outlives a single script: globals and the random seed.
+pub struct Runtime {
+    globals: Env,
+    random_state: u64,
+}
+
+impl Default for Runtime {
+    fn default() -> Runtime {
+        Runtime::new()
+    }
+}
+
+impl Runtime {
+    /// Create a runtime with the built-in globals (`Math`, `JSON`, `console`, etc.).
+    pub fn new() -> Runtime {
+        let globals: Env = Rc::new(RefCell::new(Environment {
+            bindings: HashMap::new(),
+            this: Some(Value::Undefined),
+            function_scope: true,
+            parent: None,
+        }));
+        let mut runtime: Runtime = Runtime { globals, random_state: 0x2545_f491_4f6c_dd1d };
+        install_builtins(&mut runtime);
+        runtime
+    }
+
+    /// Define (or redefine) a global variable.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.globals.borrow_mut().bindings.insert(Rc::from(name), Binding { value, mutable: true });
+    }
+
+    /// Read a global variable.
+    pub fn global(&self, name: &str) -> Option<Value> {
+        self.globals.borrow().bindings.get(name).map(|binding: &Binding| binding.value.clone())
+    }
+
+    /// Run a script, returning the value of its last expression statement.
+    pub fn eval(&mut self, source: &str, host: &mut dyn Host) -> Result<Value, ScriptError> {
+        let program: Vec<Statement> = Parser::parse_program(source)
+            .map_err(|message: String| ScriptError { message: format!("SyntaxError: {}", message) })?;
+        let globals: Env = self.globals.clone();
+        let mut context: Context = Context { runtime: self, host, steps: 0, depth: 0, last_value: Value::Undefined };
+        context.hoist(&program, &globals);
+        for statement in &program {
+            match context.execute(statement, &globals) {
+                Ok(Completion::Normal) => {}
+                Ok(_) => return Err(ScriptError { message: "SyntaxError: Illegal break, continue, or return".to_string() }),
+                Err(thrown) => return Err(ScriptError { message: thrown.to_string() }),
+            }
+        }
+        Ok(context.last_value)
+    }
+
+    /// Call `function` with `this` and `args`, e.g. an event listener a script registered.
+    pub fn call(&mut self, function: &Value, this: Value, args: &[Value], host: &mut dyn Host) -> Result<Value, ScriptError> {
+        let mut context: Context = Context { runtime: self, host, steps: 0, depth: 0, last_value: Value::Undefined };
+        context.call_function(function, this, args).map_err(|thrown: Value| ScriptError { message: thrown.to_string() })
+    }
+}
+
+/// Everything evaluation needs: the runtime, the host, and the limits.
+struct Context<'a> {
+    runtime: &'a mut Runtime,
+    host: &'a mut dyn Host,
+    steps: usize,
+    depth: usize,
+    last_value: Value, // of the last expression statement, for `eval`
+}
+
+impl Context<'_> {
+    // Statements
+
+    /// Declare the functions in a block before running it, so they can be called early.
+    fn hoist(&mut self, statements: &[Statement], env: &Env) {
+        for statement in statements {
+            if let Statement::Function(ref definition) = *statement {
+                let function: Value = self.make_function(definition, env);
+                if let Some(ref name) = definition.name {
+                    declare(env, name, function, true);
+                }
+            }
+        }
+    }
+
+    fn execute_block(&mut self, statements: &[Statement], env: &Env) -> Result<Completion, Value> {
+        self.hoist(statements, env);
+        for statement in statements {
+            match self.execute(statement, env)? {
+                Completion::Normal => {}
+                completion => return Ok(completion),
+            }
+        }
+        Ok(Completion::Normal)
+    }
+
+    /// Count one step towards `STEP_LIMIT`: a statement, or a loop iteration.
+    fn tick(&mut self) -> Result<(), Value> {
+        self.steps += 1;
+        if self.steps > STEP_LIMIT {
+            return Err(make_error("RangeError", "Script ran for too long"));
+        }
+        Ok(())
+    }
+
+    fn execute(&mut self, statement: &Statement, env: &Env) -> Result<Completion, Value> {
+        self.tick()?;
+        match *statement {
+            Statement::Expression(ref expression) => {
+                self.last_value = self.evaluate(expression, env)?;
+            }
+            Statement::Declaration(kind, ref declarations) => {
+                for (name, value) in declarations {
+                    let value: Value = match value {
+                        Some(value) => self.evaluate(value, env)?,
+                        None if kind == DeclarationKind::Var && lookup(env, name).is_some() => continue,
+                        None => Value::Undefined,
+                    };
+                    if kind == DeclarationKind::Var {
+                        declare(&function_env(env), name, value, true);
+                    } else {
+                        declare(env, name, value, kind == DeclarationKind::Let);
+                    }
+                }
+            }
+            Statement::Function(_) => {} // hoisted
+            Statement::Return(ref value) => {
+                let value: Value = match value {
+                    Some(value) => self.evaluate(value, env)?,
+                    None => Value::Undefined,
+                };
+                return Ok(Completion::Return(value));
+            }
+            Statement::If(ref test, ref consequent, ref alternate) => {
+                if self.evaluate(test, env)?.is_truthy() {
+                    return self.execute_scoped(consequent, env);
+                } else if let Some(alternate) = alternate {
+                    return self.execute_scoped(alternate, env);
+                }
+            }
+            Statement::While(ref test, ref body) => {
+                while self.evaluate(test, env)?.is_truthy() {
+                    self.tick()?;
+                    match self.execute_scoped(body, env)? {
+                        Completion::Break => break,
+                        Completion::Return(value) => return Ok(Completion::Return(value)),
+                        Completion::Normal | Completion::Continue => {}
+                    }
+                }
+            }
+            Statement::DoWhile(ref body, ref test) => loop {
+                self.tick()?;
+                match self.execute_scoped(body, env)? {
+                    Completion::Break => break,
+                    Completion::Return(value) => return Ok(Completion::Return(value)),
+                    Completion::Normal | Completion::Continue => {}
+                }
+                if !self.evaluate(test, env)?.is_truthy() {
+                    break;
+                }
+            },
+            Statement::For(ref init, ref test, ref update, ref body) => {
+                return self.execute_for(init.as_deref(), test.as_ref(), update.as_ref(), body, env);
+            }
+            Statement::ForOf(kind, ref name, ref iterable, ref body) => {
+                let iterable: Value = self.evaluate(iterable, env)?;
+                let items: Vec<Value> = self.iterate(&iterable)?;
+                return self.execute_for_each(kind, name, items, body, env);
+            }
+            Statement::ForIn(kind, ref name, ref object, ref body) => {
+                let keys: Vec<Value> = match self.evaluate(object, env)? {
+                    Value::Object(object) => object.keys().into_iter().map(Value::from).collect(),
+                    Value::String(s) => (0..s.chars().count()).map(|i: usize| Value::from(i.to_string())).collect(),
+                    _ => Vec::new(),
+                };
+                return self.execute_for_each(kind, name, keys, body, env);
+            }
+            Statement::Switch(ref discriminant, ref cases) => {
+                let value: Value = self.evaluate(discriminant, env)?;
+                let block_env: Env = new_env(env);
+                let mut matched: Option<usize> = None;
+                for (i, (test, _)) in cases.iter().enumerate() {
+                    if let Some(test) = test {
+                        if self.evaluate(test, &block_env)?.strict_equals(&value) {
+                            matched = Some(i);
+                            break;
+                        }
+                    }
+                }
+                let start: Option<usize> = matched.or_else(|| cases.iter().position(|(test, _)| test.is_none()));
+                if let Some(start) = start {
+                    for (_, body) in &cases[start..] {
+                        match self.execute_block(body, &block_env)? {
+                            Completion::Normal => {}
+                            Completion::Break => break,
+                            completion => return Ok(completion),
+                        }
+                    }
+                }
+            }
+            Statement::Break => return Ok(Completion::Break),
+            Statement::Continue => return Ok(Completion::Continue),
+            Statement::Throw(ref value) => return Err(self.evaluate(value, env)?),
+            Statement::Try(ref body, ref handler, ref finalizer) => {
+                let mut result: Result<Completion, Value> = self.execute_block(body, &new_env(env));
+                if let (Err(thrown), Some((parameter, handler))) = (&result, handler) {
+                    let catch_env: Env = new_env(env);
+                    if let Some(parameter) = parameter {
+                        declare(&catch_env, parameter, thrown.clone(), true);
+                    }
+                    result = self.execute_block(handler, &catch_env);
+                }
+                if let Some(finalizer) = finalizer {
+                    match self.execute_block(finalizer, &new_env(env))? {
+                        Completion::Normal => {}
+                        completion => return Ok(completion),
+                    }
+                }
+                return result;
+            }
+            Statement::Block(ref statements) => return self.execute_block(statements, &new_env(env)),
+            Statement::Empty => {}
+        }
+        Ok(Completion::Normal)
+    }
+
+    /// Run the body of an `if` or a loop, in a scope of its own if it's a block.
+    fn execute_scoped(&mut self, statement: &Statement, env: &Env) -> Result<Completion, Value> {
+        match *statement {
+            Statement::Block(ref statements) => self.execute_block(statements, &new_env(env)),
+            _ => self.execute(statement, env),
+        }
+    }
+
+    fn execute_for(&mut self, init: Option<&Statement>, test: Option<&Expression>, update: Option<&Expression>, body: &Statement, env: &Env) -> Result<Completion, Value> {
+        let mut loop_env: Env = new_env(env);
+        if let Some(init) = init {
+            self.execute(init, &loop_env)?;
+        }
+        loop {
+            self.tick()?;
+            if let Some(test) = test {
+                if !self.evaluate(test, &loop_env)?.is_truthy() {
+                    break;
+                }
+            }
+            match self.execute_scoped(body, &loop_env)? {
+                Completion::Break => break,
+                Completion::Return(value) => return Ok(Completion::Return(value)),
+                Completion::Normal | Completion::Continue => {}
+            }
+            // Each iteration gets its own copy of the `let` variables, so closures created
+            // in the body see the value from their own iteration.
+            let copy: Env = new_env(env);
+            for (name, binding) in &loop_env.borrow().bindings {
+                copy.borrow_mut().bindings.insert(name.clone(), Binding { value: binding.value.clone(), mutable: binding.mutable });
+            }
+            loop_env = copy;
+            if let Some(update) = update {
+                self.evaluate(update, &loop_env)?;
+            }
+        }
+        Ok(Completion::Normal)
+    }
+
+    fn execute_for_each(&mut self, kind: Option<DeclarationKind>, name: &Rc<str>, items: Vec<Value>, body: &Statement, env: &Env) -> Result<Completion, Value> {
+        for item in items {
+            let iteration_env: Env = new_env(env);
+            match kind {
+                Some(DeclarationKind::Var) => declare(&function_env(env), name, item, true),
+                Some(kind) => declare(&iteration_env, name, item, kind == DeclarationKind::Let),
+                None => self.assign(&Reference::Variable(name.clone()), item, env)?,
+            }
+            match self.execute_scoped(body, &iteration_env)? {
+                Completion::Break => break,
+                Completion::Return(value) => return Ok(Completion::Return(value)),
+                Completion::Normal | Completion::Continue => {}
+            }
+        }
+        Ok(Completion::Normal)
+    }
+
+    /// The items `for`/`of` and spread go through.
+    fn iterate(&mut self, value: &Value) -> Result<Vec<Value>, Value> {
+        if let Some(items) = value.as_array() {
+            return Ok(items);
+        }
+        match *value {
+            Value::String(ref s) => Ok(s.chars().map(|c: char| Value::from(c.to_string())).collect()),
+            _ => Err(type_error(&format!("{} is not iterable", inspect(value, true)))),
+        }
+    }
+
+    // Expressions
+
+    fn evaluate(&mut self, expression: &Expression, env: &Env) -> Result<Value, Value> {
+        match *expression {
+            Expression::Number(n) => Ok(Value::Number(n)),
+            Expression::String(ref s) => Ok(Value::String(s.clone())),
+            Expression::Template(ref pieces) => {
+                let mut result: String = String::new();
+                for piece in pieces {
+                    result.push_str(&self.evaluate(piece, env)?.to_string());
+                }
+                Ok(Value::from(result))
+            }
+            Expression::Bool(b) => Ok(Value::Bool(b)),
+            Expression::Null => Ok(Value::Null),
+            Expression::Identifier(ref name) => match lookup(env, name) {
+                Some(value) => Ok(value),
+                None => Err(make_error("ReferenceError", &format!("{} is not defined", name))),
+            },
+            Expression::This => Ok(lookup_this(env)),
+            Expression::Array(ref elements) => Ok(Value::array(self.evaluate_list(elements, env)?)),
+            Expression::Object(ref properties) => {
+                let object: Object = Object::new(ObjectKind::Plain);
+                for (key, value) in properties {
+                    object.set(key, self.evaluate(value, env)?);
+                }
+                Ok(Value::Object(object))
+            }
+            Expression::Function(ref definition) => Ok(self.make_function(definition, env)),
+            Expression::Spread(_) => Err(make_error("SyntaxError", "Unexpected '...'")),
+            Expression::Unary(operator, ref operand) => self.evaluate_unary(operator, operand, env),
+            Expression::Update(operator, prefix, ref target) => {
+                let reference: Reference = self.reference(target, env)?;
+                let old: f64 = self.get_reference(&reference, env)?.to_number();
+                let new: f64 = if operator == "++" { old + 1.0 } else { old - 1.0 };
+                self.assign(&reference, Value::Number(new), env)?;
+                Ok(Value::Number(if prefix { new } else { old }))
+            }
+            Expression::Binary(operator, ref left, ref right) => {
+                let left: Value = self.evaluate(left, env)?;
+                let right: Value = self.evaluate(right, env)?;
+                self.binary(operator, &left, &right)
+            }
+            Expression::Logical(operator, ref left, ref right) => {
+                let left: Value = self.evaluate(left, env)?;
+                let short_circuit: bool = match operator {
+                    "&&" => !left.is_truthy(),
+                    "||" => left.is_truthy(),
+                    _ => !left.is_nullish(),
+                };
+                if short_circuit { Ok(left) } else { self.evaluate(right, env) }
+            }
+            Expression::Conditional(ref test, ref consequent, ref alternate) => {
+                if self.evaluate(test, env)?.is_truthy() {
+                    self.evaluate(consequent, env)
+                } else {
+                    self.evaluate(alternate, env)
+                }
+            }
+            Expression::Assign(operator, ref target, ref value) => {
+                let reference: Reference = self.reference(target, env)?;
+                let value: Value = match operator {
+                    "=" => self.evaluate(value, env)?,
+                    "&&=" | "||=" | "??=" => {
+                        let old: Value = self.get_reference(&reference, env)?;
+                        let keep: bool = match operator {
+                            "&&=" => !old.is_truthy(),
+                            "||=" => old.is_truthy(),
+                            _ => !old.is_nullish(),
+                        };
+                        if keep {
+                            return Ok(old);
+                        }
+                        self.evaluate(value, env)?
+                    }
+                    _ => {
+                        let old: Value = self.get_reference(&reference, env)?;
+                        let value: Value = self.evaluate(value, env)?;
+                        self.binary(&operator[..operator.len() - 1], &old, &value)?
+                    }
+                };
+                self.assign(&reference, value.clone(), env)?;
+                Ok(value)
+            }
+            Expression::Member(ref object, ref name, optional) => {
+                let object: Value = self.evaluate(object, env)?;
+                if optional && object.is_nullish() {
+                    return Ok(Value::Undefined);
+                }
+                self.get_property(&object, name)
+            }
+            Expression::Index(ref object, ref index, optional) => {
+                let object: Value = self.evaluate(object, env)?;
+                if optional && object.is_nullish() {
+                    return Ok(Value::Undefined);
+                }
+                let key: Value = self.evaluate(index, env)?;
+                self.get_property(&object, &key.to_string())
+            }
+            Expression::Call(ref callee, ref arguments) => self.evaluate_call(callee, arguments, env),
+            Expression::New(ref callee, ref arguments) => {
+                let function: Value = self.evaluate(callee, env)?;
+                let args: Vec<Value> = self.evaluate_list(arguments, env)?;
+                if !function.is_function() {
+                    return Err(type_error(&format!("{} is not a constructor", describe(callee))));
+                }
+                let this: Value = Value::Object(Object::new(ObjectKind::Plain));
+                let result: Value = self.call_function(&function, this.clone(), &args)?;
+                Ok(if matches!(result, Value::Object(_) | Value::Host(_)) { result } else { this })
+            }
+            Expression::Sequence(ref expressions) => {
+                let mut value: Value = Value::Undefined;
+                for expression in expressions {
+                    value = self.evaluate(expression, env)?;
+                }
+                Ok(value)
+            }
+        }
+    }
+
+    /// Evaluate array elements or call arguments, expanding `...spread`s.
+    fn evaluate_list(&mut self, expressions: &[Expression], env: &Env) -> Result<Vec<Value>, Value> {
+        let mut values: Vec<Value> = Vec::new();
+        for expression in expressions {
+            match *expression {
+                Expression::Spread(ref inner) => {
+                    let value: Value = self.evaluate(inner, env)?;
+                    values.extend(self.iterate(&value)?);
+                }
+                _ => values.push(self.evaluate(expression, env)?),
+            }
+        }
+        Ok(values)
+    }
+
+    fn evaluate_unary(&mut self, operator: &str, operand: &Expression, env: &Env) -> Result<Value, Value> {
+        match operator {
+            // `typeof undeclared` is "undefined", not an error.
+            "typeof" => Ok(Value::string(match *operand {
+                Expression::Identifier(ref name) => lookup(env, name).map_or("undefined", |value: Value| value.type_of()),
+                _ => self.evaluate(operand, env)?.type_of(),
+            })),
+            "delete" => {
+                if let Reference::Property(Value::Object(object), key) = self.reference(operand, env)? {
+                    object.delete(&key);
+                }
+                Ok(Value::Bool(true))
+            }
+            _ => {
+                let value: Value = self.evaluate(operand, env)?;
+                Ok(match operator {
+                    "!" => Value::Bool(!value.is_truthy()),
+                    "-" => Value::Number(-value.to_number()),
+                    "+" => Value::Number(value.to_number()),
+                    "~" => Value::Number(!to_int32(value.to_number()) as f64),
+                    _ => Value::Undefined, // void
+                })
+            }
+        }
+    }
+
+    fn binary(&mut self, operator: &str, left: &Value, right: &Value) -> Result<Value, Value> {
+        let number = |f: fn(f64, f64) -> f64| Ok(Value::Number(f(left.to_number(), right.to_number())));
+        let int32 = |f: fn(i32, u32) -> i32| Ok(Value::Number(f(to_int32(left.to_number()), to_int32(right.to_number()) as u32) as f64));
+        match operator {
+            "+" => {
+                let primitive = |value: &Value| if matches!(*value, Value::Object(_) | Value::Host(_)) { Value::from(value.to_string()) } else { value.clone() };
+                let (left, right) = (primitive(left), primitive(right));
+                if matches!(left, Value::String(_)) || matches!(right, Value::String(_)) {
+                    Ok(Value::from(format!("{}{}", left, right)))
+                } else {
+                    Ok(Value::Number(left.to_number() + right.to_number()))
+                }
+            }
+            "-" => number(|a: f64, b: f64| a - b),
+            "*" => number(|a: f64, b: f64| a * b),
+            "/" => number(|a: f64, b: f64| a / b),
+            "%" => number(|a: f64, b: f64| a % b),
+            "**" => number(f64::powf),
+            "&" => int32(|a: i32, b: u32| a & b as i32),
+            "|" => int32(|a: i32, b: u32| a | b as i32),
+            "^" => int32(|a: i32, b: u32| a ^ b as i32),
+            "<<" => int32(|a: i32, b: u32| a.wrapping_shl(b & 31)),
+            ">>" => int32(|a: i32, b: u32| a.wrapping_shr(b & 31)),
+            ">>>" => Ok(Value::Number((to_int32(left.to_number()) as u32).wrapping_shr(to_int32(right.to_number()) as u32 & 31) as f64)),
+            "==" => Ok(Value::Bool(left.loose_equals(right))),
+            "!=" => Ok(Value::Bool(!left.loose_equals(right))),
+            "===" => Ok(Value::Bool(left.strict_equals(right))),
+            "!==" => Ok(Value::Bool(!left.strict_equals(right))),
+            "<" | ">" | "<=" | ">=" => {
+                let ordering: Option<std::cmp::Ordering> = match (left, right) {
+                    (Value::String(a), Value::String(b)) => Some(a.cmp(b)),
+                    _ => left.to_number().partial_cmp(&right.to_number()),
+                };
+                Ok(Value::Bool(match ordering {
+                    Some(ordering) => match operator {
+                        "<" => ordering.is_lt(),
+                        ">" => ordering.is_gt(),
+                        "<=" => ordering.is_le(),
+                        _ => ordering.is_ge(),
+                    },
+                    None => false, // NaN
+                }))
+            }
+            "in" => match *right {
+                Value::Object(ref object) => Ok(Value::Bool(object.get(&left.to_string()).is_some())),
+                _ => Err(type_error("Cannot use 'in' on a non-object")),
+            },
+            _ => Err(make_error("SyntaxError", &format!("'{}' is not supported", operator))),
+        }
+    }
+
+    // References
+
+    fn reference(&mut self, target: &Expression, env: &Env) -> Result<Reference, Value> {
+        match *target {
+            Expression::Identifier(ref name) => Ok(Reference::Variable(name.clone())),
+            Expression::Member(ref object, ref name, _) => Ok(Reference::Property(self.evaluate(object, env)?, name.clone())),
+            Expression::Index(ref object, ref index, _) => {
+                let object: Value = self.evaluate(object, env)?;
+                let key: Value = self.evaluate(index, env)?;
+                Ok(Reference::Property(object, Rc::from(key.to_string())))
+            }
+            _ => Err(make_error("SyntaxError", "Invalid assignment target")),
+        }
+    }
+
+    fn get_reference(&mut self, reference: &Reference, env: &Env) -> Result<Value, Value> {
+        match *reference {
+            Reference::Variable(ref name) => lookup(env, name).ok_or_else(|| make_error("ReferenceError", &format!("{} is not defined", name))),
+            Reference::Property(ref object, ref name) => self.get_property(object, name),
+        }
+    }
+
+    fn assign(&mut self, reference: &Reference, value: Value, env: &Env) -> Result<(), Value> {
+        match *reference {
+            Reference::Variable(ref name) => {
+                let mut scope: Env = env.clone();
+                loop {
+                    let parent: Option<Env> = {
+                        let mut environment = scope.borrow_mut();
+                        if let Some(binding) = environment.bindings.get_mut(name) {
+                            if !binding.mutable {
+                                return Err(type_error("Assignment to constant variable."));
+                            }
+                            binding.value = value;
+                            return Ok(());
+                        }
+                        environment.parent.clone()
+                    };
+                    match parent {
+                        Some(parent) => scope = parent,
+                        None => break,
+                    }
+                }
+                // Assigning to an undeclared variable creates a global.
+                self.runtime.set_global(name, value);
+                Ok(())
+            }
+            Reference::Property(ref object, ref name) => self.set_property(object, name, value),
+        }
+    }
+
+    // Properties
+
+    fn get_property(&mut self, object: &Value, name: &str) -> Result<Value, Value> {
+        match *object {
+            Value::Undefined | Value::Null => Err(type_error(&format!("Cannot read properties of {} (reading '{}')", object, name))),
+            Value::Object(ref object) => Ok(object.get(name).unwrap_or(Value::Undefined)),
+            Value::String(ref s) => Ok(if name == "length" {
+                Value::Number(s.chars().count() as f64)
+            } else if let Some(index) = array_index(name) {
+                s.chars().nth(index).map_or(Value::Undefined, |c: char| Value::from(c.to_string()))
+            } else {
+                Value::Undefined
+            }),
+            Value::Host(host_object) => self.host.get(host_object, name).map_err(|message: String| type_error(&message)),
+            Value::Bool(_) | Value::Number(_) => Ok(Value::Undefined),
+        }
+    }
+
+    fn set_property(&mut self, object: &Value, name: &str, value: Value) -> Result<(), Value> {
+        match *object {
+            Value::Undefined | Value::Null => Err(type_error(&format!("Cannot set properties of {} (setting '{}')", object, name))),
+            Value::Object(ref object) => {
+                object.set(name, value);
+                Ok(())
+            }
+            Value::Host(host_object) => self.host.set(host_object, name, value).map_err(|message: String| type_error(&message)),
+            _ => Ok(()), // ignored, like in sloppy mode
+        }
+    }
+
+    // Calls
+
+    fn make_function(&self, definition: &Rc<FunctionDefinition>, env: &Env) -> Value {
+        Value::Object(Object::new(ObjectKind::Function(Function::Script { definition: definition.clone(), env: env.clone() })))
+    }
+
+    fn evaluate_call(&mut self, callee: &Expression, arguments: &[Expression], env: &Env) -> Result<Value, Value> {
+        // Method calls pass the object as `this`, and find built-in methods of strings,
+        // arrays, and host objects.
+        let (this, name) = match *callee {
+            Expression::Member(ref object, ref name, optional) => {
+                let object: Value = self.evaluate(object, env)?;
+                if optional && object.is_nullish() {
+                    return Ok(Value::Undefined);
+                }
+                (object, name.clone())
+            }
+            Expression::Index(ref object, ref index, _) => {
+                let object: Value = self.evaluate(object, env)?;
+                let key: Value = self.evaluate(index, env)?;
+                (object, Rc::from(key.to_string()))
+            }
+            _ => {
+                let function: Value = self.evaluate(callee, env)?;
+                let args: Vec<Value> = self.evaluate_list(arguments, env)?;
+                if !function.is_function() {
+                    return Err(type_error(&format!("{} is not a function", describe(callee))));
+                }
+                return self.call_function(&function, Value::Undefined, &args);
+            }
+        };
+        let args: Vec<Value> = self.evaluate_list(arguments, env)?;
+        self.call_method(&this, &name, &args).map_err(|error: Value| match error {
+            Value::Undefined => type_error(&format!("{} is not a function", describe(callee))),
+            error => error,
+        })
+    }
+
+    /// Call `object.name(...args)`. Fails with `undefined` if there's no such method.
+    fn call_method(&mut self, object: &Value, name: &str, args: &[Value]) -> Result<Value, Value> {
+        match *object {
+            Value::Host(host_object) => {
+                return self.host.call(host_object, name, args).map_err(|message: String| type_error(&message));
+            }
+            Value::String(ref s) => {
+                if let Some(result) = self.string_method(s, name, args) {
+                    return result;
+                }
+            }
+            Value::Number(n) => {
+                if let Some(result) = number_method(n, name, args) {
+                    return result;
+                }
+            }
+            Value::Object(ref object_ref) => {
+                let own: Option<Value> = object_ref.get(name);
+                match own {
+                    Some(function) if function.is_function() => return self.call_function(&function, object.clone(), args),
+                    Some(_) => return Err(Value::Undefined),
+                    None => {}
+                }
+                if object.as_array().is_some() {
+                    if let Some(result) = self.array_method(object_ref, name, args) {
+                        return result;
+                    }
+                }
+                match name {
+                    "hasOwnProperty" => return Ok(Value::Bool(object_ref.get(&arg(args, 0).to_string()).is_some())),
+                    "toString" => return Ok(Value::from(object.to_string())),
+                    "call" if object.is_function() => return self.call_function(object, arg(args, 0), args.get(1..).unwrap_or(&[])),
+                    "apply" if object.is_function() => {
+                        let args: Vec<Value> = arg(args, 1).as_array().unwrap_or_default();
+                        return self.call_function(object, arg(args.as_slice(), 0), &args);
+                    }
+                    _ => {}
+                }
+            }
+            Value::Undefined | Value::Null => return Err(type_error(&format!("Cannot read properties of {} (reading '{}')", object, name))),
+            Value::Bool(_) => {}
+        }
+        if name == "toString" {
+            return Ok(Value::from(object.to_string()));
+        }
+        Err(Value::Undefined)
+    }
+
+    /// Call a function value.
+    fn call_function(&mut self, function: &Value, this: Value, args: &[Value]) -> Result<Value, Value> {
+        let function: Function = match *function {
+            Value::Object(ref object) => match object.0.borrow().kind {
+                ObjectKind::Function(ref function) => function.clone(),
+                _ => return Err(type_error(&format!("{} is not a function", inspect(&Value::Object(object.clone()), true)))),
+            },
+            _ => return Err(type_error(&format!("{} is not a function", inspect(function, true)))),
+        };
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(make_error("RangeError", "Maximum call stack size exceeded"));
+        }
+        self.depth += 1;
+        let result: Result<Value, Value> = match function {
+            Function::Native { function, .. } => function(self, &this, args),
+            Function::Host { object, ref method } => self.host.call(object, method, args).map_err(|message: String| type_error(&message)),
+            Function::Script { ref definition, ref env } => self.call_script_function(definition, env, this, args),
+        };
+        self.depth -= 1;
+        result
+    }
+
+    fn call_script_function(&mut self, definition: &FunctionDefinition, env: &Env, this: Value, args: &[Value]) -> Result<Value, Value> {
+        let call_env: Env = new_env(env);
+        {
+            let mut environment = call_env.borrow_mut();
+            environment.function_scope = true;
+            if !definition.arrow {
+                environment.this = Some(this);
+            }
+        }
+        for (i, (name, default)) in definition.parameters.iter().enumerate() {
+            let value: Value = match (args.get(i), default) {
+                (Some(value), _) if !matches!(*value, Value::Undefined) => value.clone(),
+                (_, Some(default)) => self.evaluate(default, &call_env)?,
+                _ => Value::Undefined,
+            };
+            declare(&call_env, name, value, true);
+        }
+        if let Some(ref rest) = definition.rest {
+            let rest_values: Vec<Value> = args.get(definition.parameters.len()..).unwrap_or(&[]).to_vec();
+            declare(&call_env, rest, Value::array(rest_values), true);
+        }
+        match definition.body {
+            FunctionBody::Expression(ref expression) => self.evaluate(expression, &call_env),
+            FunctionBody::Block(ref statements) => match self.execute_block(statements, &call_env)? {
+                Completion::Return(value) => Ok(value),
+                _ => Ok(Value::Undefined),
+            },
+        }
+    }
+
+    // Built-in methods
+
+    fn string_method(&mut self, s: &str, name: &str, args: &[Value]) -> Option<Result<Value, Value>> {
+        let chars: Vec<char> = s.chars().collect();
+        let length: usize = chars.len();
+        let substring = |start: usize, end: usize| Value::from(chars[start.min(end)..end.max(start)].iter().collect::<String>());
+        let text = |i: usize| arg(args, i).to_string();
+        Some(Ok(match name {
+            "toUpperCase" | "toLocaleUpperCase" => Value::from(s.to_uppercase()),
+            "toLowerCase" | "toLocaleLowerCase" => Value::from(s.to_lowercase()),
+            "trim" => Value::string(s.trim()),
+            "trimStart" => Value::string(s.trim_start()),
+            "trimEnd" => Value::string(s.trim_end()),
+            "toString" | "valueOf" => Value::string(s),
+            "charAt" => substring(clamp_index(&arg(args, 0), 0, length), (clamp_index(&arg(args, 0), 0, length) + 1).min(length)),
+            "charCodeAt" => chars.get(arg(args, 0).to_number() as usize).map_or(Value::Number(f64::NAN), |&c: &char| Value::Number(c as u32 as f64)),
+            "at" => {
+                let index: f64 = arg(args, 0).to_number();
+                let index: f64 = if index < 0.0 { index + length as f64 } else { index };
+                chars.get(index as usize).filter(|_| index >= 0.0).map_or(Value::Undefined, |c: &char| Value::from(c.to_string()))
+            }
+            "indexOf" => Value::Number(char_position(s, s.find(&text(0)))),
+            "lastIndexOf" => Value::Number(char_position(s, s.rfind(&text(0)))),
+            "includes" => Value::Bool(s.contains(&text(0))),
+            "startsWith" => Value::Bool(s.starts_with(&text(0))),
+            "endsWith" => Value::Bool(s.ends_with(&text(0))),
+            "slice" => substring(relative_index(&arg(args, 0), 0, length), relative_index(&arg(args, 1), length, length)),
+            "substring" => substring(clamp_index(&arg(args, 0), 0, length), clamp_index(&arg(args, 1), length, length)),
+            "split" => {
+                let parts: Vec<Value> = match arg(args, 0) {
+                    Value::Undefined => vec![Value::string(s)],
+                    separator => match separator.to_string().as_str() {
+                        "" => chars.iter().map(|c: &char| Value::from(c.to_string())).collect(),
+                        separator => s.split(separator).map(Value::string).collect(),
+                    },
+                };
+                Value::array(parts)
+            }
+            "repeat" => Value::from(s.repeat(arg(args, 0).to_number().max(0.0) as usize)),
+            "padStart" | "padEnd" => {
+                let target: usize = arg(args, 0).to_number().max(0.0) as usize;
+                let fill: String = if args.len() > 1 { text(1) } else { " ".to_string() };
+                let padding: String = fill.chars().cycle().take(target.saturating_sub(length)).collect();
+                Value::from(if name == "padStart" { padding + s } else { s.to_string() + &padding })
+            }
+            "concat" => Value::from(args.iter().fold(s.to_string(), |mut result: String, value: &Value| {
+                result.push_str(&value.to_string());
+                result
+            })),
+            "replace" | "replaceAll" => {
+                let pattern: String = text(0);
+                let mut result: String = String::new();
+                let mut rest: &str = s;
+                while let Some(index) = rest.find(&pattern) {
+                    result.push_str(&rest[..index]);
+                    let replacement: Value = match arg(args, 1) {
+                        function if function.is_function() => match self.call_function(&function, Value::Undefined, &[Value::string(&pattern)]) {
+                            Ok(value) => value,
+                            Err(thrown) => return Some(Err(thrown)),
+                        },
+                        replacement => replacement,
+                    };
+                    result.push_str(&replacement.to_string());
+                    rest = &rest[index + pattern.len()..];
+                    if name == "replace" || pattern.is_empty() {
+                        break;
+                    }
+                }
+                result.push_str(rest);
+                Value::from(result)
+            }
+            _ => return None,
+        }))
+    }
+
+    fn array_method(&mut self, array: &Object, name: &str, args: &[Value]) -> Option<Result<Value, Value>> {
+        let items: Vec<Value> = Value::Object(array.clone()).as_array()?;
+        let length: usize = items.len();
+        let replace = |items: Vec<Value>| {
+            if let ObjectKind::Array(ref mut values) = array.0.borrow_mut().kind {
+                *values = items;
+            }
+        };
+        let result: Result<Value, Value> = match name {
+            "push" => {
+                let mut items: Vec<Value> = items;
+                items.extend_from_slice(args);
+                let length: usize = items.len();
+                replace(items);
+                Ok(Value::Number(length as f64))
+            }
+            "pop" => {
+                let mut items: Vec<Value> = items;
+                let last: Value = items.pop().unwrap_or(Value::Undefined);
+                replace(items);
+                Ok(last)
+            }
+            "shift" => {
+                let mut items: Vec<Value> = items;
+                let first: Value = if items.is_empty() { Value::Undefined } else { items.remove(0) };
+                replace(items);
+                Ok(first)
+            }
+            "unshift" => {
+                let mut new_items: Vec<Value> = args.to_vec();
+                new_items.extend(items);
+                let length: usize = new_items.len();
+                replace(new_items);
+                Ok(Value::Number(length as f64))
+            }
+            "splice" => {
+                let mut items: Vec<Value> = items;
+                let start: usize = relative_index(&arg(args, 0), 0, length);
+                let count: usize = if args.len() > 1 { clamp_index(&arg(args, 1), 0, length - start) } else { length - start };
+                let removed: Vec<Value> = items.splice(start..start + count, args.iter().skip(2).cloned()).collect();
+                replace(items);
+                Ok(Value::array(removed))
+            }
+            "reverse" => {
+                let mut items: Vec<Value> = items;
+                items.reverse();
+                replace(items);
+                Ok(Value::Object(array.clone()))
+            }
+            "sort" => self.sort(items, &arg(args, 0)).map(|items: Vec<Value>| {
+                replace(items);
+                Value::Object(array.clone())
+            }),
+            "slice" => {
+                let start: usize = relative_index(&arg(args, 0), 0, length);
+                let end: usize = relative_index(&arg(args, 1), length, length);
+                Ok(Value::array(items[start.min(end)..end].to_vec()))
+            }
+            "concat" => {
+                let mut items: Vec<Value> = items;
+                for value in args {
+                    match value.as_array() {
+                        Some(more) => items.extend(more),
+                        None => items.push(value.clone()),
+                    }
+                }
+                Ok(Value::array(items))
+            }
+            "join" => {
+                let separator: String = match arg(args, 0) {
+                    Value::Undefined => ",".to_string(),
+                    separator => separator.to_string(),
+                };
+                let parts: Vec<String> = items.iter().map(|value: &Value| if value.is_nullish() { String::new() } else { value.to_string() }).collect();
+                Ok(Value::from(parts.join(&separator)))
+            }
+            "indexOf" => Ok(Value::Number(items.iter().position(|value: &Value| value.strict_equals(&arg(args, 0))).map_or(-1.0, |i: usize| i as f64))),
+            "includes" => Ok(Value::Bool(items.iter().any(|value: &Value| value.strict_equals(&arg(args, 0)) || (value.to_number().is_nan() && arg(args, 0).to_number().is_nan() && matches!(*value, Value::Number(_)))))),
+            "at" => {
+                let index: f64 = arg(args, 0).to_number();
+                let index: f64 = if index < 0.0 { index + length as f64 } else { index };
+                Ok(items.get(index as usize).filter(|_| index >= 0.0).cloned().unwrap_or(Value::Undefined))
+            }
+            "flat" => {
+                let mut flat: Vec<Value> = Vec::new();
+                for item in items {
+                    match item.as_array() {
+                        Some(inner) => flat.extend(inner),
+                        None => flat.push(item),
+                    }
+                }
+                Ok(Value::array(flat))
+            }
+            "fill" => {
+                let items: Vec<Value> = vec![arg(args, 0); length];
+                replace(items);
+                Ok(Value::Object(array.clone()))
+            }
+            "forEach" | "map" | "filter" | "find" | "findIndex" | "some" | "every" | "reduce" => self.array_callback_method(array, items, name, args),
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    /// The array methods that call a function for each item.
+    fn array_callback_method(&mut self, array: &Object, items: Vec<Value>, name: &str, args: &[Value]) -> Result<Value, Value> {
+        let callback: Value = arg(args, 0);
+        if !callback.is_function() {
+            return Err(type_error(&format!("{} is not a function", inspect(&callback, true))));
+        }
+        let this_array: Value = Value::Object(array.clone());
+        if name == "reduce" {
+            let mut items = items.into_iter().enumerate();
+            let mut accumulator: Value = match args.get(1) {
+                Some(initial) => initial.clone(),
+                None => match items.next() {
+                    Some((_, first)) => first,
+                    None => return Err(type_error("Reduce of empty array with no initial value")),
+                },
+            };
+            for (i, item) in items {
+                accumulator = self.call_function(&callback, Value::Undefined, &[accumulator, item, Value::Number(i as f64), this_array.clone()])?;
+            }
+            return Ok(accumulator);
+        }
+        let mut results: Vec<Value> = Vec::new();
+        for (i, item) in items.into_iter().enumerate() {
+            let result: Value = self.call_function(&callback, Value::Undefined, &[item.clone(), Value::Number(i as f64), this_array.clone()])?;
+            match name {
+                "map" => results.push(result),
+                "filter" if result.is_truthy() => results.push(item),
+                "find" if result.is_truthy() => return Ok(item),
+                "findIndex" if result.is_truthy() => return Ok(Value::Number(i as f64)),
+                "some" if result.is_truthy() => return Ok(Value::Bool(true)),
+                "every" if !result.is_truthy() => return Ok(Value::Bool(false)),
+                _ => {}
+            }
+        }
+        Ok(match name {
+            "map" | "filter" => Value::array(results),
+            "find" => Value::Undefined,
+            "findIndex" => Value::Number(-1.0),
+            "some" => Value::Bool(false),
+            "every" => Value::Bool(true),
+            _ => Value::Undefined, // forEach
+        })
+    }
+
+    /// Sort with a comparison function, or by string value. An insertion sort, which is
+    /// stable and can stop when the comparison function throws.
+    fn sort(&mut self, mut items: Vec<Value>, compare: &Value) -> Result<Vec<Value>, Value> {
+        for i in 1..items.len() {
+            let mut j: usize = i;
+            while j > 0 {
+                let greater: bool = if compare.is_function() {
+                    self.call_function(compare, Value::Undefined, &[items[j - 1].clone(), items[j].clone()])?.to_number() > 0.0
+                } else {
+                    items[j - 1].to_string() > items[j].to_string()
+                };
+                if !greater {
+                    break;
+                }
+                items.swap(j - 1, j);
+                j -= 1;
+            }
+        }
+        Ok(items)
+    }
+}
+
+fn number_method(n: f64, name: &str, args: &[Value]) -> Option<Result<Value, Value>> {
+    Some(Ok(match name {
+        "toFixed" => Value::from(format!("{:.*}", arg(args, 0).to_number().clamp(0.0, 100.0) as usize, n)),
+        "toString" => match arg(args, 0).to_number() as u32 {
+            radix @ 2..=36 if radix != 10 && n.fract() == 0.0 => {
+                let mut digits: Vec<char> = Vec::new();
+                let mut rest: u64 = n.abs() as u64;
+                loop {
+                    digits.push(std::char::from_digit((rest % radix as u64) as u32, radix).unwrap());
+                    rest /= radix as u64;
+                    if rest == 0 {
+                        break;
+                    }
+                }
+                if n < 0.0 {
+                    digits.push('-');
+                }
+                Value::from(digits.iter().rev().collect::<String>())
+            }
+            _ => Value::from(number_to_string(n)),
+        },
+        "valueOf" => Value::Number(n),
+        _ => return None,
+    }))
+}
+
+/// The `i`th argument, or `undefined`.
+fn arg(args: &[Value], i: usize) -> Value {
+    args.get(i).cloned().unwrap_or(Value::Undefined)
+}
+
+/// An index argument to `slice` and friends: negative counts from the end.
+fn relative_index(value: &Value, default: usize, length: usize) -> usize {
+    if matches!(*value, Value::Undefined) {
+        return default;
+    }
+    let index: f64 = value.to_number();
+    let index: f64 = if index < 0.0 { index + length as f64 } else { index };
+    if index.is_nan() { 0 } else { index.clamp(0.0, length as f64) as usize }
+}
+
+/// An index argument to `substring` and friends: negative is 0.
+fn clamp_index(value: &Value, default: usize, length: usize) -> usize {
+    if matches!(*value, Value::Undefined) {
+        return default;
+    }
+    let index: f64 = value.to_number();
+    if index.is_nan() { 0 } else { index.clamp(0.0, length as f64) as usize }
+}
+
+/// Convert a byte position from `str::find` to a character index, or -1.
+fn char_position(s: &str, position: Option<usize>) -> f64 {
+    position.map_or(-1.0, |position: usize| s[..position].chars().count() as f64)
+}
+
+/// Convert to a 32-bit integer, as the bitwise operators do.
+fn to_int32(n: f64) -> i32 {
+    if n.is_finite() { n.trunc().rem_euclid(4294967296.0) as u32 as i32 } else { 0 }
+}
+
+/// A short description of a callee for error messages, e.g. "foo.bar".
+fn describe(expression: &Expression) -> String {
+    match *expression {
+        Expression::Identifier(ref name) => name.to_string(),
+        Expression::Member(ref object, ref name, _) => format!("{}.{}", describe(object), name),
+        Expression::Index(ref object, _, _) => format!("{}[...]", describe(object)),
+        Expression::Call(ref callee, _) => format!("{}(...)", describe(callee)),
+        Expression::This => "this".to_string(),
+        _ => "expression".to_string(),
+    }
+}
+
+fn declare(env: &Env, name: &Rc<str>, value: Value, mutable: bool) {
+    env.borrow_mut().bindings.insert(name.clone(), Binding { value, mutable });
+}
+
+fn lookup(env: &Env, name: &str) -> Option<Value> {
+    let environment = env.borrow();
+    match environment.bindings.get(name) {
+        Some(binding) => Some(binding.value.clone()),
+        None => environment.parent.as_ref().and_then(|parent: &Env| lookup(parent, name)),
+    }
+}
+
+fn lookup_this(env: &Env) -> Value {
+    let environment = env.borrow();
+    match (&environment.this, &environment.parent) {
+        (Some(this), _) => this.clone(),
+        (None, Some(parent)) => lookup_this(parent),
+        (None, None) => Value::Undefined,
+    }
+}
+
+/// The nearest function (or global) scope, where `var` declarations go.
+fn function_env(env: &Env) -> Env {
+    let environment = env.borrow();
+    match environment.parent {
+        Some(ref parent) if !environment.function_scope => function_env(parent),
+        _ => env.clone(),
+    }
+}
+
+
+// Built-in globals
+
+fn native(name: &'static str, function: NativeFunction) -> Value {
+    Value::Object(Object::new(ObjectKind::Function(Function::Native { name, function })))
+}
+
+fn native_object(functions: &[(&'static str, NativeFunction)]) -> Object {
+    let object: Object = Object::new(ObjectKind::Plain);
+    for &(name, function) in functions {
+        object.set(name, native(name, function));
+    }
+    object
+}
+
+fn install_builtins(runtime: &mut Runtime) {
+    runtime.set_global("undefined", Value::Undefined);
+    runtime.set_global("NaN", Value::Number(f64::NAN));
+    runtime.set_global("Infinity", Value::Number(f64::INFINITY));
+
+    runtime.set_global("console", Value::Object(native_object(&[
+        ("log", |cx: &mut Context, _: &Value, args: &[Value]| console(cx, "log", args)),
+        ("info", |cx: &mut Context, _: &Value, args: &[Value]| console(cx, "info", args)),
+        ("debug", |cx: &mut Context, _: &Value, args: &[Value]| console(cx, "debug", args)),
+        ("warn", |cx: &mut Context, _: &Value, args: &[Value]| console(cx, "warn", args)),
+        ("error", |cx: &mut Context, _: &Value, args: &[Value]| console(cx, "error", args)),
+    ])));
+
+    let math: Object = native_object(&[
+        ("abs", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().abs()))),
+        ("floor", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().floor()))),
+        ("ceil", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().ceil()))),
+        ("round", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number((arg(args, 0).to_number() + 0.5).floor()))),
+        ("trunc", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().trunc()))),
+        ("sign", |_: &mut Context, _: &Value, args: &[Value]| {
+            let n: f64 = arg(args, 0).to_number();
+            Ok(Value::Number(if n == 0.0 || n.is_nan() { n } else { n.signum() }))
+        }),
+        ("sqrt", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().sqrt()))),
+        ("pow", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().powf(arg(args, 1).to_number())))),
+        ("sin", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().sin()))),
+        ("cos", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().cos()))),
+        ("tan", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().tan()))),
+        ("atan2", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().atan2(arg(args, 1).to_number())))),
+        ("log", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(arg(args, 0).to_number().ln()))),
+        ("min", |_: &mut Context, _: &Value, args: &[Value]| {
+            Ok(Value::Number(args.iter().map(Value::to_number).fold(f64::INFINITY, |a: f64, b: f64| if a.is_nan() || b.is_nan() { f64::NAN } else { a.min(b) })))
+        }),
+        ("max", |_: &mut Context, _: &Value, args: &[Value]| {
+            Ok(Value::Number(args.iter().map(Value::to_number).fold(f64::NEG_INFINITY, |a: f64, b: f64| if a.is_nan() || b.is_nan() { f64::NAN } else { a.max(b) })))
+        }),
+        // xorshift64*: not random at all between runs, which keeps pages reproducible.
+        ("random", |cx: &mut Context, _: &Value, _: &[Value]| {
+            let state: &mut u64 = &mut cx.runtime.random_state;
+            *state ^= *state >> 12;
+            *state ^= *state << 25;
+            *state ^= *state >> 27;
+            let bits: u64 = state.wrapping_mul(0x2545_f491_4f6c_dd1d) >> 11;
+            Ok(Value::Number(bits as f64 / (1u64 << 53) as f64))
+        }),
+    ]);
+    math.set("PI", Value::Number(std::f64::consts::PI));
+    math.set("E", Value::Number(std::f64::consts::E));
+    runtime.set_global("Math", Value::Object(math));
+
+    runtime.set_global("JSON", Value::Object(native_object(&[
+        ("stringify", |_: &mut Context, _: &Value, args: &[Value]| {
+            let indent: String = match arg(args, 2) {
+                Value::Number(n) => " ".repeat(n.clamp(0.0, 10.0) as usize),
+                Value::String(s) => s.to_string(),
+                _ => String::new(),
+            };
+            let mut out: String = String::new();
+            Ok(if json_stringify(&arg(args, 0), &indent, 0, &mut out) { Value::from(out) } else { Value::Undefined })
+        }),
+    ])));
+
+    let object: Object = native_object(&[
+        ("keys", |_: &mut Context, _: &Value, args: &[Value]| Ok(match arg(args, 0) {
+            Value::Object(object) => Value::array(object.keys().into_iter().map(Value::from).collect()),
+            _ => Value::array(Vec::new()),
+        })),
+        ("values", |cx: &mut Context, _: &Value, args: &[Value]| {
+            let object: Value = arg(args, 0);
+            let keys: Vec<String> = match object {
+                Value::Object(ref object) => object.keys(),
+                _ => Vec::new(),
+            };
+            let values: Result<Vec<Value>, Value> = keys.iter().map(|key: &String| cx.get_property(&object, key)).collect();
+            Ok(Value::array(values?))
+        }),
+        ("entries", |cx: &mut Context, _: &Value, args: &[Value]| {
+            let object: Value = arg(args, 0);
+            let keys: Vec<String> = match object {
+                Value::Object(ref object) => object.keys(),
+                _ => Vec::new(),
+            };
+            let mut entries: Vec<Value> = Vec::new();
+            for key in keys {
+                let value: Value = cx.get_property(&object, &key)?;
+                entries.push(Value::array(vec![Value::from(key), value]));
+            }
+            Ok(Value::array(entries))
+        }),
+        ("assign", |cx: &mut Context, _: &Value, args: &[Value]| {
+            let target: Value = arg(args, 0);
+            for source in args.iter().skip(1) {
+                if let Value::Object(ref object) = *source {
+                    for key in object.keys() {
+                        let value: Value = cx.get_property(source, &key)?;
+                        cx.set_property(&target, &key, value)?;
+                    }
+                }
+            }
+            Ok(target)
+        }),
+        ("freeze", |_: &mut Context, _: &Value, args: &[Value]| Ok(arg(args, 0))),
+    ]);
+    runtime.set_global("Object", Value::Object(object));
+
+    let array: Object = native_object(&[
+        ("isArray", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Bool(arg(args, 0).as_array().is_some()))),
+        ("from", |cx: &mut Context, _: &Value, args: &[Value]| {
+            let items: Vec<Value> = match arg(args, 0) {
+                Value::Object(ref object) if object.get("length").is_some() && arg(args, 0).as_array().is_none() => {
+                    let length: usize = object.get("length").map_or(0.0, |length: Value| length.to_number()) as usize;
+                    (0..length).map(|i: usize| object.get(&i.to_string()).unwrap_or(Value::Undefined)).collect()
+                }
+                value => cx.iterate(&value)?,
+            };
+            match arg(args, 1) {
+                map if map.is_function() => {
+                    let mut mapped: Vec<Value> = Vec::new();
+                    for (i, item) in items.into_iter().enumerate() {
+                        mapped.push(cx.call_function(&map, Value::Undefined, &[item, Value::Number(i as f64)])?);
+                    }
+                    Ok(Value::array(mapped))
+                }
+                _ => Ok(Value::array(items)),
+            }
+        }),
+        ("of", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::array(args.to_vec()))),
+    ]);
+    runtime.set_global("Array", Value::Object(array));
+
+    let number: Object = native_object(&[
+        ("isInteger", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Bool(matches!(arg(args, 0), Value::Number(n) if n.is_finite() && n.fract() == 0.0)))),
+        ("isFinite", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Bool(matches!(arg(args, 0), Value::Number(n) if n.is_finite())))),
+        ("isNaN", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Bool(matches!(arg(args, 0), Value::Number(n) if n.is_nan())))),
+        ("parseFloat", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(parse_float(&arg(args, 0).to_string())))),
+        ("parseInt", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(parse_int(&arg(args, 0).to_string(), &arg(args, 1))))),
+    ]);
+    number.set("MAX_SAFE_INTEGER", Value::Number(9007199254740991.0));
+    number.set("EPSILON", Value::Number(f64::EPSILON));
+    runtime.set_global("Number", callable_namespace("Number", |_: &mut Context, _: &Value, args: &[Value]| {
+        Ok(Value::Number(if args.is_empty() { 0.0 } else { arg(args, 0).to_number() }))
+    }, &number));
+
+    let string: Object = native_object(&[
+        ("fromCharCode", |_: &mut Context, _: &Value, args: &[Value]| {
+            Ok(Value::from(args.iter().filter_map(|value: &Value| char::from_u32(value.to_number() as u32)).collect::<String>()))
+        }),
+    ]);
+    runtime.set_global("String", callable_namespace("String", |_: &mut Context, _: &Value, args: &[Value]| {
+        Ok(Value::from(if args.is_empty() { String::new() } else { arg(args, 0).to_string() }))
+    }, &string));
+    runtime.set_global("Boolean", native("Boolean", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Bool(arg(args, 0).is_truthy()))));
+
+    runtime.set_global("parseInt", native("parseInt", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(parse_int(&arg(args, 0).to_string(), &arg(args, 1))))));
+    runtime.set_global("parseFloat", native("parseFloat", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Number(parse_float(&arg(args, 0).to_string())))));
+    runtime.set_global("isNaN", native("isNaN", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Bool(arg(args, 0).to_number().is_nan()))));
+    runtime.set_global("isFinite", native("isFinite", |_: &mut Context, _: &Value, args: &[Value]| Ok(Value::Bool(arg(args, 0).to_number().is_finite()))));
+
+    runtime.set_global("Error", native("Error", |_: &mut Context, _: &Value, args: &[Value]| Ok(construct_error("Error", args))));
+    runtime.set_global("TypeError", native("TypeError", |_: &mut Context, _: &Value, args: &[Value]| Ok(construct_error("TypeError", args))));
+    runtime.set_global("RangeError", native("RangeError", |_: &mut Context, _: &Value, args: &[Value]| Ok(construct_error("RangeError", args))));
+    runtime.set_global("ReferenceError", native("ReferenceError", |_: &mut Context, _: &Value, args: &[Value]| Ok(construct_error("ReferenceError", args))));
+    runtime.set_global("SyntaxError", native("SyntaxError", |_: &mut Context, _: &Value, args: &[Value]| Ok(construct_error("SyntaxError", args))));
+}
+
+/// `Error(message)` and friends.
+fn construct_error(name: &str, args: &[Value]) -> Value {
+    make_error(name, &match arg(args, 0) {
+        Value::Undefined => String::new(),
+        message => message.to_string(),
+    })
+}
+
+/// A function that also has properties, like `Number` (with `Number.isInteger` and so on).
+fn callable_namespace(name: &'static str, function: NativeFunction, properties: &Object) -> Value {
+    let callable: Value = native(name, function);
+    if let Value::Object(ref object) = callable {
+        for key in properties.keys() {
+            object.set(&key, properties.get(&key).unwrap_or(Value::Undefined));
+        }
+    }
+    callable
+}
+
+/// Pass `console.log(...)` and friends to the host, with the arguments joined by spaces.
+fn console(cx: &mut Context, level: &str, args: &[Value]) -> Result<Value, Value> {
+    let parts: Vec<String> = args.iter().map(|value: &Value| inspect(value, false)).collect();
+    cx.host.console(level, &parts.join(" "));
+    Ok(Value::Undefined)
+}
+
+/// Parse the number at the start of `s`, ignoring anything after it.
+fn parse_float(s: &str) -> f64 {
+    let s: &str = s.trim_start();
+    if s.starts_with("Infinity") || s.starts_with("+Infinity") {
+        return f64::INFINITY;
+    }
+    if s.starts_with("-Infinity") {
+        return f64::NEG_INFINITY;
+    }
+    // Try the longest prefix that parses.
+    let end: usize = s.find(|c: char| !(c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '+' | '-'))).unwrap_or(s.len());
+    (1..=end).rev().find_map(|length: usize| s[..length].parse::<f64>().ok()).unwrap_or(f64::NAN)
+}
+
+/// Parse the integer at the start of `s` in base `radix` (10, or 16 for "0x...").
+fn parse_int(s: &str, radix: &Value) -> f64 {
+    let s: &str = s.trim();
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s.strip_prefix('+').unwrap_or(s)),
+    };
+    let mut radix: u32 = match *radix {
+        Value::Undefined => 0,
+        ref radix => radix.to_number() as u32,
+    };
+    let mut s: &str = s;
+    if matches!(radix, 0 | 16) && (s.starts_with("0x") || s.starts_with("0X")) {
+        s = &s[2..];
+        radix = 16;
+    }
+    if radix == 0 {
+        radix = 10;
+    }
+    if !(2..=36).contains(&radix) {
+        return f64::NAN;
+    }
+    let digits: Vec<u32> = s.chars().map_while(|c: char| c.to_digit(radix)).collect();
+    if digits.is_empty() {
+        return f64::NAN;
+    }
+    let n: f64 = digits.iter().fold(0.0, |n: f64, &digit: &u32| n * radix as f64 + digit as f64);
+    if negative { -n } else { n }
+}
+
+/// Write `value` as JSON to `out`. Returns false for values JSON can't represent
+/// (`undefined` and functions), which objects leave out.
+fn json_stringify(value: &Value, indent: &str, depth: usize, out: &mut String) -> bool {
+    let newline = |out: &mut String, depth: usize| {
+        if !indent.is_empty() {
+            out.push('\n');
+            out.push_str(&indent.repeat(depth));
+        }
+    };
+    match *value {
+        Value::Undefined => return false,
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&if n.is_finite() { number_to_string(n) } else { "null".to_string() }),
+        Value::String(ref s) => json_string(s, out),
+        Value::Host(_) => out.push_str("{}"),
+        Value::Object(ref object) => {
+            if value.is_function() {
+                return false;
+            }
+            if depth > 64 {
+                out.push_str("null"); // probably a cycle
+                return true;
+            }
+            if let Some(items) = value.as_array() {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    newline(out, depth + 1);
+                    if !json_stringify(item, indent, depth + 1, out) {
+                        out.push_str("null");
+                    }
+                }
+                if !items.is_empty() {
+                    newline(out, depth);
+                }
+                out.push(']');
+            } else {
+                out.push('{');
+                let mut first: bool = true;
+                for key in object.keys() {
+                    let mut member: String = String::new();
+                    json_string(&key, &mut member);
+                    member.push(':');
+                    if !indent.is_empty() {
+                        member.push(' ');
+                    }
+                    if json_stringify(&object.get(&key).unwrap_or(Value::Undefined), indent, depth + 1, &mut member) {
+                        if !first {
+                            out.push(',');
+                        }
+                        newline(out, depth + 1);
+                        out.push_str(&member);
+                        first = false;
+                    }
+                }
+                if !first {
+                    newline(out, depth);
+                }
+                out.push('}');
+            }
+        }
+    }
+    true
+}
+
+fn json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
@@ -1,8 +1,21 @@
-//! Basic CSS block layout.
-
-use crate::{css, style};
-
-/**
+//! Basic CSS block and inline layout.
+
+use crate::atom::Atom;
+use crate::collections::HashMap;
+use crate::image::{Image, ImageCache};
+use crate::fonts::{self, FontCollection};
+use crate::text::{self, Hyphens, TabSize, TextAlign, TextOverflow, TextStyle};
+use crate::trace::{stage, Stage};
+use crate::{css, dom, forms, style};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use alloc::sync::Arc;
+use core::error::Error;
+use core::fmt;
+
+pub use bumpalo::Bump;
+
+/*
  *  The layout module takes the style tree and translates it into a bunch of rectangles in
  *  a two-dimensional space.
  *
@@ -10,7 +23,7 @@ use crate::{css, style};
  *  "layout tree".
  */
 
-/**
+/*
  *  Layout is all about "boxes". A box is a rectangular section of a web page. It has a
  *  width, a height, and a position on the page. This rectangle is called the "content area"
  *  because it's where the box's content is drawn. The content may be text, image, video,
@@ -28,7 +41,8 @@ use crate::{css, style};
 // CSS box model. All sizes are in px.
 
 /// Position of the content area relative to the document origin:
-#[derive(Copy)]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Rect {
     pub x: f32,
     pub y: f32,
@@ -37,7 +51,8 @@ pub struct Rect {
 }
 
 /// Surrounding edges:
-#[derive(Copy)]
+#[derive(Clone, Copy, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct EdgeSizes {
     pub left: f32,
     pub right: f32,
@@ -45,7 +60,8 @@ pub struct EdgeSizes {
     pub bottom: f32,
 }
 
-#[derive(Copy)]
+#[derive(Clone, Copy, Default, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Dimensions {
     pub content: Rect,
     pub padding: EdgeSizes,
@@ -54,7 +70,7 @@ pub struct Dimensions {
 }
 
 
-/**
+/*
  *  Block and Inline Layout
  *
  *  The CSS display property determines which type of box an element generates. CSS
@@ -72,16 +88,25 @@ pub struct Dimensions {
 
 
 /**
- *  A box can be a block node, an inline node, or an anonymous block box. (This will
- *  need to change when I implement text layout, because line wrapping can cause a
- *  single inline node to split into multiple boxes. But it will do for now.)
+ *  A box can be a block node, an inline node, or an anonymous block box. Line wrapping
+ *  can spread a single inline node over several lines; its box is then the smallest
+ *  rectangle around all of them, and a text node's box keeps the pieces of its text on
+ *  each line as `TextFragment`s (see "Inline Layout" below).
  */
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum BoxType<'a> {
-    BlockNode(&'a style::StyledNode<'a>),
-    InlineNode(&'a style::StyledNode<'a>),
+    BlockNode(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_node_id"))] &'a style::StyledNode<'a>),
+    InlineNode(#[cfg_attr(feature = "serde", serde(serialize_with = "serialize_node_id"))] &'a style::StyledNode<'a>),
     AnonymousBlock,
 }
 
+/// Serialize a box's style node as just its DOM node id, rather than a copy of its subtree.
+#[cfg(feature = "serde")]
+fn serialize_node_id<S: serde::Serializer>(node: &&style::StyledNode, serializer: S) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&node.node_id, serializer)
+}
+
 
 /// A node in the layout tree.
 /**
@@ -89,15 +114,135 @@ pub enum BoxType<'a> {
  *
  *  The layout tree is a collection of boxes. A box has dimensions, and it may contain
  *  child boxes.
+ *
+ *  A large page has hundreds of thousands of boxes, and the whole tree is thrown away
+ *  after every layout. Giving each box's list of children its own heap allocation made
+ *  layout spend much of its time in the allocator, so the lists live in an arena instead
+ *  (a `bumpalo::Bump`, re-exported here). Allocating from it is just bumping a pointer,
+ *  and the tree's memory is all freed at once when the arena is dropped. The caller
+ *  creates the arena and passes it to `layout_tree`, and the tree borrows from it.
+ *
+ *  The DOM was already an arena of its own (see `dom::Tree`). `benches/arena.rs` counts
+ *  the allocations layout makes.
  */
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct LayoutBox<'a> {
     pub dimensions: Dimensions,
     pub box_type: BoxType<'a>,
-    pub children: Vec<LayoutBox<'a>>,
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_boxes"))]
+    pub children: BoxList<'a>,
+    pub replaced: Option<Replaced>,
+    pub text: Vec<TextFragment>, // for a text node's box, its text on each line
+    pub lines: Vec<Rect>,        // for an inline element's box, its content area on each line
+}
+
+/// A piece of a text node's text, placed on one line.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TextFragment {
+    pub rect: Rect, // from the font's ascent down to its descent
+    pub run: fonts::TextRun,
+    pub color: css::Color,
+}
+
+impl TextFragment {
+    /// The y coordinate of the fragment's baseline.
+    pub fn baseline(&self) -> f32 {
+        self.rect.y + self.run.metrics.ascent
+    }
+}
+
+/// The children of a layout box, allocated in the layout tree's arena. Unlike a growable
+/// `bumpalo::collections::Vec`, this doesn't hold on to the arena, so the tree can still
+/// be shared between threads once it's built.
+pub type BoxList<'a> = bumpalo::boxed::Box<'a, [LayoutBox<'a>]>;
+
+/// A list of boxes while it is being built.
+type BoxListBuilder<'a> = bumpalo::collections::Vec<'a, LayoutBox<'a>>;
+
+/// Serialize a box's children as a plain list.
+#[cfg(feature = "serde")]
+fn serialize_boxes<S: serde::Serializer>(boxes: &BoxList, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(boxes.iter())
 }
 
 
 /**
+ *  Replaced Elements
+ *
+ *  Some elements, like `<img>`, don't get their size from their children. Their content
+ *  comes from outside the document, and it has an "intrinsic" size of its own. Since images
+ *  load in the background, we may have to lay out the page before we know that size. The
+ *  `width` and `height` attributes let the page reserve space up front, so the layout
+ *  doesn't jump around when the image arrives.
+ *
+ *  Form controls are laid out the same way, with a size that comes from their attributes
+ *  (see `forms`), and so are `<iframe>`s, which show another document. A frame is 300x150
+ *  unless its attributes say otherwise, and its content is the other document painted at
+ *  that size, which the page puts in the image cache under the key `frame_key` gives.
+ */
+/// The content of a replaced element, sized in px.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Replaced {
+    pub width: f32,
+    pub height: f32,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pub image: Option<Arc<Image>>, // `None` while the image is still loading
+    pub control: Option<forms::Control>, // for form controls, instead of an image
+}
+
+/// What needs to be redone after some subresources finished loading.
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Invalidation {
+    None,
+    Repaint,
+    Relayout,
+}
+
+/// The image cache key for the painted document in the `<iframe>` at `node`.
+pub fn frame_key(node: dom::NodeId) -> String {
+    format!("about:frame#{}", node.index())
+}
+
+/// Work out the content of an `<img>` box, a form control, or an `<iframe>`, or return
+/// `None` for other nodes.
+/*
+    The width and height attributes win if they are both present. If only one is present,
+    the other one is derived from the image's aspect ratio (once we know it). Until the
+    image has loaded, a missing dimension is treated as zero.
+ */
+fn replaced_content(style_node: &style::StyledNode, images: &ImageCache) -> Option<Replaced> {
+    if let Some(control) = forms::control(style_node) {
+        let (width, height) = control.intrinsic_size();
+        return Some(Replaced { width, height, image: None, control: Some(control) });
+    }
+    let element: &dom::Element = match style_node.node.node_type {
+        dom::NodeType::Element(ref element) if element.tag_name == "img" || element.tag_name == "iframe" => element,
+        _ => return None,
+    };
+    let attribute = |name: &str| element.attr(name).and_then(|v: &str| v.trim().trim_end_matches("px").parse::<f32>().ok());
+    if element.tag_name == "iframe" {
+        let image: Option<Arc<Image>> = images.get(&frame_key(style_node.node_id));
+        return Some(Replaced { width: attribute("width").unwrap_or(300.0), height: attribute("height").unwrap_or(150.0), image, control: None });
+    }
+    let image: Option<Arc<Image>> = element.attr("src").and_then(|src: &str| images.get(src));
+
+    let (width, height) = match (attribute("width"), attribute("height"), &image) {
+        (Some(w), Some(h), _) => (w, h),
+        (Some(w), None, Some(img)) => (w, w * img.height as f32 / img.width.max(1) as f32),
+        (None, Some(h), Some(img)) => (h * img.width as f32 / img.height.max(1) as f32, h),
+        (None, None, Some(img)) => (img.width as f32, img.height as f32),
+        (w, h, None) => (w.unwrap_or(0.0), h.unwrap_or(0.0)),
+    };
+
+    Some(Replaced { width, height, image, control: None })
+}
+
+
+/*
  *  To build the layout tree, we need to look at the display property for each DOM node.
  *  I added some code to the style module to get the display value for a node. If there's
  *  no specified value it returns the initial value, "inline".
@@ -106,31 +251,141 @@ pub struct LayoutBox<'a> {
  *  see style::StyledNode
  */
 
-/**
+/*
  *  Now we can walk through the style tree, build a LayoutBox for each node, and then
  *  insert boxes for the node's children. If a node's display property is set to 'none'
  *  then it is not included in the layout tree.
  */
 
 /// Build the tree of LayoutBoxes, but don't perform any layout calculations yet.
-fn build_layout_tree<'a>(style_node: &'a style::StyledNode<'a>) -> LayoutBox<'a> {
-    // Create the root box.
-    let mut root: LayoutBox = LayoutBox::new(match style_node.display() {
-        style::Display::Block => BoxType::BlockNode(style_node),
-        style::Display::Inline => BoxType::InlineNode(style_node),
-        style::Display::None => panic!("Root node has display: none.")
-    });
-
-    // Create the descendant boxes.
-    for child in &style_node.children {
+fn build_layout_tree<'a>(style_node: &'a style::StyledNode<'a>, images: &ImageCache, arena: &'a Bump) -> Result<LayoutBox<'a>, LayoutError> {
+    // The root element is always a block, whatever its `display` says, so that its inline
+    // content has lines to go in.
+    let root: LayoutBox = match style_node.display() {
+        style::Display::None => return Err(LayoutError::RootNotDisplayed),
+        _ => build_box(BoxType::BlockNode(style_node), style_node, images, arena)?,
+    };
+    Ok(root)
+}
+
+/// Build the box for `style_node` and the boxes for its descendants.
+fn build_box<'a>(box_type: BoxType<'a>, style_node: &'a style::StyledNode<'a>, images: &ImageCache, arena: &'a Bump) -> Result<LayoutBox<'a>, LayoutError> {
+    let mut root: LayoutBox = LayoutBox::new(box_type, arena);
+    root.replaced = replaced_content(style_node, images);
+    if root.replaced.is_some() {
+        return Ok(root); // replaced elements draw their own contents instead of their children
+    }
+
+    /*
+     *  If a block node contains an inline child, create an anonymous block box to
+     *  contain it. If there are several inline children in a row, put them all in
+     *  the same anonymous container. An inline node with a block inside it is treated
+     *  the same way: its content is laid out like a block's, and the inline node sits
+     *  on a line of its own (see "Inline Layout").
+     */
+    let is_block: bool = matches!(root.box_type, BoxType::BlockNode(_))
+        || style_node.children.iter().any(|child: &style::StyledNode| child.display() == style::Display::Block);
+    let mut children: BoxListBuilder = BoxListBuilder::new_in(arena);
+    let mut inline_run: BoxListBuilder = BoxListBuilder::new_in(arena); // waiting for an anonymous block
+
+    // Create the descendant boxes, with a table's bottom captions last (see "Captions").
+    let bottom: Vec<bool> = style_node.children.iter().map(|child: &style::StyledNode| is_bottom_caption(style_node, child)).collect();
+    let ordered = style_node.children.iter().zip(&bottom).filter(|&(_, &bottom)| !bottom)
+        .chain(style_node.children.iter().zip(&bottom).filter(|&(_, &bottom)| bottom))
+        .map(|(child, _)| child);
+    for child in ordered {
         match child.display() {
-            style::Display::Block => root.children.push(build_layout_tree(child)),
-            style::Display::Inline => root.get_inline_container().children.push(build_layout_tree(child)),
+            style::Display::Block => {
+                end_inline_run(&mut children, &mut inline_run, arena);
+                children.push(build_box(BoxType::BlockNode(child), child, images, arena)?);
+            }
+            style::Display::Inline if is_block => inline_run.push(build_box(BoxType::InlineNode(child), child, images, arena)?),
+            style::Display::Inline => children.push(build_box(BoxType::InlineNode(child), child, images, arena)?),
             style::Display::None => {} // Don't lay out nodes with `display: none;`
         }
     }
+    end_inline_run(&mut children, &mut inline_run, arena);
 
-    root
+    root.children = children.into_boxed_slice();
+    Ok(root)
+}
+
+/*
+ *  Captions
+ *
+ *  This engine has no table layout: a table, its rows, and its cells are laid out as
+ *  whatever `display` their CSS gives them, usually blocks. A table's `<caption>` is
+ *  still put on the side that its `caption-side` property asks for: `top` (the initial
+ *  value) leaves it where the source has it, at the start of the table, and `bottom`
+ *  moves its box after the table's other contents. Like browsers, a caption without
+ *  `caption-side` of its own uses its table's.
+ */
+
+/// Whether `child` is a caption of the table `parent` that goes below the table.
+fn is_bottom_caption(parent: &style::StyledNode, child: &style::StyledNode) -> bool {
+    let is_element = |node: &style::StyledNode, tag_name: &str| match node.node.node_type {
+        dom::NodeType::Element(ref element) => element.tag_name == tag_name,
+        _ => false,
+    };
+    is_element(parent, "table") && is_element(child, "caption")
+        && matches!(child.value("caption-side").or_else(|| parent.value("caption-side")), Some(css::Value::Keyword(side)) if side == "bottom")
+}
+
+/// Wrap a run of inline boxes in an anonymous block, and add it to `children`.
+fn end_inline_run<'a>(children: &mut BoxListBuilder<'a>, inline_run: &mut BoxListBuilder<'a>, arena: &'a Bump) {
+    if inline_run.is_empty() {
+        return;
+    }
+    let mut anonymous: LayoutBox = LayoutBox::new(BoxType::AnonymousBlock, arena);
+    anonymous.children = core::mem::replace(inline_run, BoxListBuilder::new_in(arena)).into_boxed_slice();
+    children.push(anonymous);
+}
+
+
+/// Why a style tree couldn't be laid out.
+/*
+    Only the root can fail: a descendant with `display: none` is simply left out, but
+    without a root box there is no tree at all. Browsers show a blank page instead, which
+    is what `engine::Page` does with this error (see `LayoutBox::empty`).
+ */
+#[derive(Debug, Clone, PartialEq)]
+pub enum LayoutError {
+    RootNotDisplayed, // the root element has `display: none`
+}
+
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::RootNotDisplayed => write!(f, "the root element has display: none"),
+        }
+    }
+}
+
+impl Error for LayoutError {}
+
+
+/**
+ *  Putting it together: build the layout tree for a style tree and lay it out inside the
+ *  given containing block (usually the viewport, with a height of zero). Text is measured
+ *  with the faces in `fonts` (see `fonts`).
+ */
+/// Transform a style tree into a layout tree, allocated in `arena`.
+pub fn layout_tree<'a>(
+    node: &'a style::StyledNode<'a>,
+    mut containing_block: Dimensions,
+    images: &ImageCache,
+    fonts: &FontCollection,
+    arena: &'a Bump,
+) -> Result<LayoutBox<'a>, LayoutError> {
+    let stage: Stage = stage!("layout::layout_tree", boxes);
+
+    // The layout algorithm expects the container height to start at 0.
+    containing_block.content.height = 0.0;
+
+    let mut root_box: LayoutBox = build_layout_tree(node, images, arena)?;
+    root_box.layout(containing_block, &TextStyle::default(), fonts);
+    stage.record("boxes", || root_box.count_boxes());
+    Ok(root_box)
 }
 
 
@@ -143,58 +398,55 @@ fn build_layout_tree<'a>(style_node: &'a style::StyledNode<'a>) -> LayoutBox<'a>
  */
 impl<'a> LayoutBox<'a> {
     // Constructor function
-    fn new(box_type: BoxType) -> LayoutBox {
+    fn new(box_type: BoxType<'a>, arena: &'a Bump) -> LayoutBox<'a> {
         LayoutBox {
             box_type,
             dimensions: Default::default(), // initially set all fields to 0.0
-            children: Vec::new(),
+            children: BoxListBuilder::new_in(arena).into_boxed_slice(), // empty, so nothing is allocated
+            replaced: None,
+            text: Vec::new(),
+            lines: Vec::new(),
         }
     }
 
-    fn get_style_node(&self) -> &'a style::StyledNode<'a> {
-        match self.box_type {
-            BoxType::BlockNode(node) | BoxType::InlineNode(node) => node,
-            BoxType::AnonymousBlock => panic!("Anonymous block box has no style node")
-        }
+    /// A box with no content and no size, to stand in for a layout tree that couldn't be built.
+    pub fn empty(arena: &'a Bump) -> LayoutBox<'a> {
+        LayoutBox::new(BoxType::AnonymousBlock, arena)
     }
 
 
     /**
-     *  If a block node contains an inline child, create an anonymous block box to
-     *  contain it. If there are several inline children in a row, put them all in
-     *  the same anonymous container.
+     *  Traversing the Layout Tree
+     *
+     *  The entry point to this code is the layout function, which takes a LayoutBox
+     *  and calculates its dimensions. We’ll break this function into three cases,
+     *  and implement only one of them for now:
      */
-    /// Where a new inline child should go.
-    fn get_inline_container(&mut self) -> &mut LayoutBox {
+    /// Lay out a box and its descendants. `parent_style` is the text style the box inherits.
+    fn layout(&mut self, containing_block: Dimensions, parent_style: &TextStyle, fonts: &FontCollection) {
         match self.box_type {
-            BoxType::InlineNode(_) | BoxType::AnonymousBlock => self,
-            BoxType::BlockNode(_) => {
-                // If we've just generated an anonymous block box, keep using it.
-                // Otherwise, create a new one.
-                match self.children.last() {
-                    Some(&LayoutBox { box_type: AnonymousBlock, .. }) => {}
-                    _ => self.children.push(LayoutBox::new(BoxType::AnonymousBlock))
-                }
-                self.children.last_mut().unwrap()
+            // Inline boxes are normally laid out in lines by their anonymous block (see
+            // "Inline Layout"); an inline box that gets here is laid out like a block.
+            BoxType::BlockNode(style) | BoxType::InlineNode(style) => {
+                self.layout_block(style, containing_block, &parent_style.inherit(style), fonts)
             }
+            BoxType::AnonymousBlock => self.layout_anonymous_block(containing_block, parent_style, fonts),
         }
     }
 
 
     /**
-     *  Traversing the Layout Tree
-     *
-     *  The entry point to this code is the layout function, which takes a LayoutBox
-     *  and calculates its dimensions. We’ll break this function into three cases,
-     *  and implement only one of them for now:
+     *  An anonymous block has no style of its own, so it has no margins, borders, or
+     *  padding. It fills the width of its container, and is as tall as the lines of
+     *  inline content in it.
      */
-    /// Lay out a box and its descendants.
-    fn layout(&mut self, containing_block: Dimensions) {
-        match self.box_type {
-            BoxType::BlockNode(_) => self.layout(containing_block),
-            BoxType::InlineNode(_) => {} // TODO
-            BoxType::AnonymousBlock => {} // TODO
-        }
+    fn layout_anonymous_block(&mut self, containing_block: Dimensions, text_style: &TextStyle, fonts: &FontCollection) {
+        let d: &mut Dimensions = &mut self.dimensions;
+        d.content.x = containing_block.content.x;
+        d.content.y = containing_block.content.height + containing_block.content.y;
+        d.content.width = containing_block.content.width;
+
+        self.layout_inline_children(text_style, fonts);
     }
 
 
@@ -209,20 +461,20 @@ impl<'a> LayoutBox<'a> {
      *  parent's width is known, and traverse bottom-up to calculate heights, so that a parent's
      *  height is calculated after its children's.
      */
-    fn layout_block(&mut self, containing_block: Dimensions) {
+    fn layout_block(&mut self, style: &style::StyledNode, containing_block: Dimensions, text_style: &TextStyle, fonts: &FontCollection) {
         // Child width can depend on parent width, so we need to
         // calculate this box's width before laying out its children.
-        self.calculate_block_width(containing_block);
+        self.calculate_block_width(style, containing_block);
 
         // Determine where the box is located within its container.
-        self.calculate_block_position(containing_block);
+        self.calculate_block_position(style, containing_block);
 
         // Recursively lay out the children of this box.
-        self.layout_block_children();
+        self.layout_block_children(text_style, fonts);
 
         // Parent height can depend on child height, so `calculate_height`
         // must be called *after* the children are laid out.
-        self.calculate_block_height();
+        self.calculate_block_height(style);
     }
 
 
@@ -233,17 +485,21 @@ impl<'a> LayoutBox<'a> {
      *  most complicated. I'll walk through it step by step. To start, we need the values of
      *  the CSS width property and all the left and right edge sizes.
      */
-    fn calculate_block_width(&mut self, containing_block: Dimensions) {
-        let style: &style::StyledNode = self.get_style_node();
+    fn calculate_block_width(&mut self, style: &style::StyledNode, containing_block: Dimensions) {
 
         // `width` has initial value `auto`.
-        let auto: css::Value = css::Value::Keyword("auto".to_string());
+        let auto: css::Value = css::Value::Keyword(Atom::from("auto"));
         let mut width: css::Value = style.value("width").unwrap_or(auto.clone());
 
+        // A replaced element with `width: auto` uses its intrinsic width.
+        if let (true, Some(replaced)) = (width == auto, &self.replaced) {
+            width = css::Value::Length(replaced.width, css::Unit::Px);
+        }
+
         // margin, border, and padding have initial value 0.
         let zero: css::Value = css::Value::Length(0.0, css::Unit::Px);
 
-        /**
+        /*
          *  This uses a helper function called "style::StyledNode::lookup", which just
          *  tries a series of values in sequence. If the first property isn't set, it
          *  tries the second one. If that's not set either, it returns the given default
@@ -263,13 +519,13 @@ impl<'a> LayoutBox<'a> {
         let padding_left: css::Value = style.lookup("padding-left", "padding", &zero);
         let padding_right: css::Value = style.lookup("padding-right", "padding", &zero);
 
-        /**
+        /*
          *  Since a child can't change its parent's width, it needs to make sure its own
          *  width fits the parent's. The CSS spec expresses this as a set of [constraints](https://www.w3.org/TR/CSS2/visudet.html#blockwidth)
          *  and an algorithm for solving them. The following code implements that algorithm.
          */
 
-        /**
+        /*
          *  First we add up the margin, padding, border, and content widths.
          *  The "css::Value:to_px" helper method converts lengths to their numerical values.
          *  If a property is set to "auto", it returns 0 so it doesn't affect the sum.
@@ -281,18 +537,18 @@ impl<'a> LayoutBox<'a> {
             &width
         ].iter().map(|v: &&css::Value| v.to_px()).sum();
 
-        /**
+        /*
          *  This is the minimum horizontal space needed for the box. If this isn't equal
          *  to the container width, we'll need to adjust something to make it equal.
          */
 
-        /**
+        /*
          *  If the  width or margins are set to "auto", they can expand or contract to fit
          *  the available space. Following the spec, we first check if the box is too big.
          *  If so, we set any expandable margins to zero.
          */
-        /// If width is not auto and the total is wider than the container,
-        /// treat auto margins as 0.
+        // If width is not auto and the total is wider than the container,
+        // treat auto margins as 0.
         if width != auto && total > containing_block.content.width {
             if margin_left == auto {
                 margin_left = css::Value::Length(0.0, css::Unit::Px)
@@ -302,7 +558,7 @@ impl<'a> LayoutBox<'a> {
             }
         }
 
-        /**
+        /*
          *  If the box is too large for its container, it "overflows" the container.
          *  If it's too small, it will "underflow", leaving extra space. We'll calculate
          *  the underflow-the amount of extra space left in the container. (If this
@@ -310,7 +566,7 @@ impl<'a> LayoutBox<'a> {
          */
         let underflow: f32 = containing_block.content.width - total;
 
-        /**
+        /*
          *  We now follow the spec's [algorithm](https://www.w3.org/TR/CSS2/visudet.html#blockwidth)
          *  for eliminating any overflow or underflow by adjusting the expandable dimensions.
          *  If there are no "auto" dimensions, we adjust the right margin. (Yes, this means
@@ -357,11 +613,22 @@ impl<'a> LayoutBox<'a> {
             }
         }
 
-        /**
+        /*
          *  At this point, the constraints are met and any "auto" values have been
          *  converted to lengths. The results are the used values for the horizontal
          *  box dimensions, which we will store in the layout tree.
          */
+        let d: &mut Dimensions = &mut self.dimensions;
+        d.content.width = width.to_px();
+
+        d.padding.left = padding_left.to_px();
+        d.padding.right = padding_right.to_px();
+
+        d.border.left = border_left.to_px();
+        d.border.right = border_right.to_px();
+
+        d.margin.left = margin_left.to_px();
+        d.margin.right = margin_right.to_px();
     }
 
 
@@ -372,8 +639,7 @@ impl<'a> LayoutBox<'a> {
      *  along with the containing block dimensions to determine this block's position on
      *  the page.
      */
-    fn calculate_block_position(&mut self, containing_block: Dimensions) {
-        let style: &style::StyledNode = self.get_style_node();
+    fn calculate_block_position(&mut self, style: &style::StyledNode, containing_block: Dimensions) {
         let d: &mut Dimensions = &mut self.dimensions;
 
         // margin, border, and padding have initial value 0.
@@ -405,9 +671,9 @@ impl<'a> LayoutBox<'a> {
      *  the child boxes, it keeps track of the total content height. This is used by the
      *  positioning code (above) to find the vertical position of the next child.
      */
-    fn layout_block_children(&mut self) {
-        for child in &mut self.children {
-            child.layout(self.dimensions);
+    fn layout_block_children(&mut self, text_style: &TextStyle, fonts: &FontCollection) {
+        for child in self.children.iter_mut() {
+            child.layout(self.dimensions, text_style, fonts);
             // Increment the height so each child is laid out below the previous one.
             self.dimensions.content.height += child.dimensions.margin_box().height;
         }
@@ -419,24 +685,821 @@ impl<'a> LayoutBox<'a> {
      *  By default, the box's height is equal to the height of its contents. But if
      *  the "height" property is set to an explicit length, we'll use that instead:
      */
-    fn calculate_block_height(&mut self) {
-        /// If the height is set to an explicit length, use that exact length.
-        /// Otherwise, just keep the value set by `layout_block_children`.
-        if let Some(css::Value::Length(h, css::Unit::Px)) = self.get_style_node().value("height") {
+    fn calculate_block_height(&mut self, style: &style::StyledNode) {
+        // If the height is set to an explicit length, use that exact length.
+        // Otherwise, just keep the value set by `layout_block_children`.
+        if let Some(css::Value::Length(h, css::Unit::Px)) = style.value("height") {
             self.dimensions.content.height = h;
+        } else if let Some(ref replaced) = self.replaced {
+            // A replaced element has no children; use its intrinsic height.
+            self.dimensions.content.height = replaced.height;
+        }
+    }
+
+
+    /**
+     *  Images Arriving
+     *
+     *  When some images finish loading, we walk the layout tree and hand each waiting
+     *  `<img>` box its image. If an image has the size its placeholder reserved, the box
+     *  can simply be repainted. If not, everything after it may move, and the caller has
+     *  to run layout again (by calling `layout_tree` with the same image cache).
+     */
+    /// Attach newly loaded images to their boxes and report what needs to be redone.
+    pub fn update_images(&mut self, images: &ImageCache) -> Invalidation {
+        let mut invalidation: Invalidation = Invalidation::None;
+
+        if let (Some(old), BoxType::BlockNode(node) | BoxType::InlineNode(node)) = (&self.replaced, &self.box_type) {
+            if old.image.is_none() {
+                if let Some(new) = replaced_content(node, images).filter(|r: &Replaced| r.image.is_some()) {
+                    invalidation = if new.width != old.width || new.height != old.height {
+                        Invalidation::Relayout
+                    } else {
+                        Invalidation::Repaint
+                    };
+                    self.replaced = Some(new);
+                }
+            }
+        }
+
+        for child in self.children.iter_mut() {
+            invalidation = invalidation.max_with(child.update_images(images));
+        }
+        invalidation
+    }
+
+    /**
+     *  Translating
+     *
+     *  Boxes on a line are laid out before we know where the line goes, and moved there
+     *  afterwards, along with everything inside them.
+     */
+    fn translate(&mut self, dx: f32, dy: f32) {
+        self.dimensions.content = self.dimensions.content.translated(dx, dy);
+        for fragment in &mut self.text {
+            fragment.rect = fragment.rect.translated(dx, dy);
+        }
+        for line in &mut self.lines {
+            *line = line.translated(dx, dy);
+        }
+        for child in self.children.iter_mut() {
+            child.translate(dx, dy);
         }
     }
 }
 
 
 /**
+ *  Inline Layout
+ *
+ *  The children of an anonymous block are inline: text, and elements like `<em>` and
+ *  `<img>` that sit in the text. They form an "inline formatting context": their content
+ *  is laid out left to right in lines, and a line wraps when the next word doesn't fit.
+ *
+ *  Layout happens in three steps:
+ *    1. Flatten the inline boxes into a list of items, in document order: runs of text
+ *       (with their white space collapsed, and measured with their font, or with a
+ *       fallback font for the characters it doesn't have), "atomic" boxes like images
+ *       that can't be split, line breaks (`<br>`), and the start and end of each inline
+ *       element, which take up the element's left or right margin, border, and padding.
+ *    2. Cut the items into pieces (words, spaces, and whole boxes), and fill lines with
+ *       them greedily. A line may end after a space, or before or after a CJK character,
+ *       where the text's `white-space` allows wrapping, and around an atomic box. It may
+ *       also end inside a word where the word may be hyphenated (see `hyphenation`), with
+ *       a hyphen drawn at the end of the line, which needs room too.
+ *    3. Place each line below the previous one. Inside a line, everything sits on a
+ *       shared baseline; the line is tall enough for its tallest content, which for text
+ *       is its `line-height` (its ascent and descent plus half the extra space above and
+ *       half below). A justified line (`text-align: justify`) is stretched to the
+ *       block's width by widening its spaces, except for the last line and lines ending
+ *       in a line break. Then give each box its place: a text node's box gets a fragment
+ *       of text for each line it's on, and an inline element's box surrounds all of its
+ *       content, and remembers the part of it on each line (see "Inline Decorations").
+ *
+ *  Even a line with only small text is as tall as a line of the block's own text would
+ *  be (CSS calls this the "strut"). A line with nothing on it, like one made of the
+ *  white space between tags, takes up no space at all.
+ *
+ *  An inline element that contains a block is laid out like a block, as an atomic box
+ *  on a line of its own.
+ */
+/// One of the things an anonymous block's content is made of (see "Inline Layout").
+struct InlineItem {
+    box_index: usize, // the box it belongs to, counting the anonymous block's descendants in order
+    style: TextStyle,
+    kind: InlineItemKind,
+}
+
+enum InlineItemKind {
+    Text(fonts::TextRun, Option<Hyphenation>),
+    Atomic(f32, f32), // the width and height of its margin box
+    // An inline element starts, after this much left margin, border, and padding, or ends,
+    // before this much on the right. The metrics are those of its own font.
+    Start(f32, fonts::LineMetrics),
+    End(f32, fonts::LineMetrics),
+    Break, // `<br>`
+}
+
+/// Where a run of text may be hyphenated, and the hyphen drawn when a line ends there.
+struct Hyphenation {
+    breaks: Vec<usize>, // byte offsets into the run's text, in order
+    hyphen: fonts::TextRun,
+}
+
+/// A piece of an item that is never split across lines.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum PieceKind {
+    Word,
+    Space,
+    Break, // a line break (`<br>`, or a newline in preformatted text)
+    Atomic,
+    Start, // of an inline element
+    End,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Piece {
+    kind: PieceKind,
+    item: usize,
+    start: usize, // byte range of the item's text, for words and spaces
+    end: usize,
+    width: f32,
+    break_after: bool, // the line may end after this piece
+    hyphen: Option<f32>, // the width of the hyphen drawn if the line ends after it
+}
+
+/// Where layout put the parts of one box, by its box index.
+#[derive(Default)]
+struct Placement {
+    fragments: Vec<TextFragment>,
+    offset: Option<(f32, f32)>, // how far to move an atomic box
+    start: Option<Rect>,        // an inline element's content area where it starts, with no width
+    end: Option<Rect>,          // ... and where it ends
+    lines: Vec<Rect>,           // ... and on each line
+}
+
+/// Where layout put the parts of every box, and the inline elements still open at the end
+/// of the last line placed, each with its font's metrics.
+#[derive(Default)]
+struct Placements {
+    boxes: Vec<Placement>,
+    open: Vec<(usize, fonts::LineMetrics)>,
+}
+
+impl<'a> LayoutBox<'a> {
+    /// Lay out the inline children of an anonymous block in lines, and set its height.
+    fn layout_inline_children(&mut self, text_style: &TextStyle, fonts: &FontCollection) {
+        // 1. Flatten the boxes into items.
+        let mut items: Vec<InlineItem> = Vec::new();
+        let mut box_count: usize = 0;
+        let mut after_space: bool = true; // white space at the start of the block goes
+        let containing_block: Dimensions = self.dimensions;
+        for child in self.children.iter_mut() {
+            child.collect_inline_items(containing_block, text_style, fonts, &mut box_count, &mut after_space, &mut items);
+        }
+        expand_tabs(&mut items, fonts);
+
+        // 2. Cut them into pieces, and the pieces into lines.
+        let pieces: Vec<Piece> = inline_pieces(&items);
+        let lines: Vec<(usize, usize)> = break_lines(&pieces, self.dimensions.content.width);
+        let last_line: usize = lines.len().saturating_sub(1);
+
+        // 3. Place the lines.
+        let strut: (f32, f32) = line_extent(text_style, fonts.metrics(&text_style.font, text_style.font_size));
+        let mut placements: Placements = Placements { boxes: (0..box_count).map(|_| Placement::default()).collect(), open: Vec::new() };
+        let content: Rect = self.dimensions.content;
+        let clamp: Option<usize> = text_style.line_clamp.filter(|&clamp: &usize| clamp < lines.len());
+        let ellipsis: Option<fonts::TextRun> = (text_style.text_overflow == TextOverflow::Ellipsis || clamp.is_some())
+            .then(|| fonts.shape(ELLIPSIS, &text_style.font, text_style.font_size).remove(0));
+        let mut y: f32 = content.y;
+        let mut clamped_y: Option<f32> = None;
+        for (n, &(first, last)) in lines.iter().enumerate() {
+            // The last line of a justified paragraph, and a line ending in a line break,
+            // aren't stretched.
+            let align: TextAlign = match text_style.text_align {
+                TextAlign::Justify if n == last_line || pieces[first..last].iter().any(|piece: &Piece| piece.kind == PieceKind::Break) => TextAlign::Left,
+                align => align,
+            };
+            let truncated: Option<Vec<Piece>> = ellipsis.as_ref().and_then(|ellipsis: &fonts::TextRun| {
+                truncate_line(&mut items, &pieces[first..last], content.width, ellipsis, text_style, clamp == Some(n + 1))
+            });
+            y += place_line(&items, truncated.as_deref().unwrap_or(&pieces[first..last]), strut, content, y, align, &mut placements);
+            if clamp == Some(n + 1) {
+                clamped_y = Some(y);
+            }
+        }
+        self.dimensions.content.height = clamped_y.unwrap_or(y) - content.y;
+
+        let mut box_index: usize = 0;
+        for child in self.children.iter_mut() {
+            child.apply_placements(&mut placements.boxes, &mut box_index);
+        }
+    }
+
+    /// Add the items for this inline box and its descendants to `items`.
+    fn collect_inline_items(&mut self, containing_block: Dimensions, parent_style: &TextStyle, fonts: &FontCollection,
+                            box_count: &mut usize, after_space: &mut bool, items: &mut Vec<InlineItem>) {
+        let box_index: usize = *box_count;
+        *box_count += 1;
+        let node: &style::StyledNode = match self.box_type {
+            BoxType::BlockNode(node) | BoxType::InlineNode(node) => node,
+            BoxType::AnonymousBlock => return, // only inside atomic boxes, which don't get here
+        };
+
+        if let dom::NodeType::Text(ref text) = node.node.node_type {
+            let collapsed: String = text::collapse_white_space(text, parent_style.white_space, after_space);
+            // Words, for `text-transform: capitalize`, run on across elements.
+            let previous: Option<char> = items.iter().rev()
+                .find(|item: &&InlineItem| !matches!(item.kind, InlineItemKind::Start(..) | InlineItemKind::End(..)))
+                .and_then(|item: &InlineItem| match item.kind {
+                    InlineItemKind::Text(ref run, _) => run.text.chars().next_back(),
+                    _ => None,
+                });
+            let lang: Option<&str> = parent_style.lang.as_ref().map(Atom::as_str);
+            let transformed: String = text::transform_text(&collapsed, parent_style.text_transform, lang, previous);
+            // Each run is in one face, and the text node's box gets fragments of all of them.
+            for (text, small) in text::small_caps(&transformed, parent_style.font_variant, lang) {
+                let size: f32 = if small { parent_style.font_size * text::SMALL_CAPS_SCALE } else { parent_style.font_size };
+                for run in fonts.shape(&text, &parent_style.font, size) {
+                    let hyphenation: Option<Hyphenation> = hyphenate(&run, parent_style, fonts);
+                    items.push(InlineItem { box_index, style: parent_style.clone(), kind: InlineItemKind::Text(run, hyphenation) });
+                }
+            }
+            return;
+        }
+
+        let style: TextStyle = parent_style.inherit(node);
+        let is_atomic: bool = self.replaced.is_some() || self.children.iter().any(|child: &LayoutBox| !matches!(child.box_type, BoxType::InlineNode(_)));
+        if is_atomic {
+            // Lay the box out at the start of the block, to find its size; it moves onto
+            // its line later.
+            let mut origin: Dimensions = containing_block;
+            origin.content.height = 0.0;
+            self.layout_block(node, origin, &style, fonts);
+            // A block's right margin fills the rest of the line, but an atomic inline's
+            // stays as specified.
+            let zero: css::Value = css::Value::Length(0.0, css::Unit::Px);
+            self.dimensions.margin.right = node.lookup("margin-right", "margin", &zero).to_px();
+            let margin_box: Rect = self.dimensions.margin_box();
+            items.push(InlineItem { box_index, style, kind: InlineItemKind::Atomic(margin_box.width, margin_box.height) });
+            *after_space = false;
+            return;
+        }
+        if matches!(node.node.node_type, dom::NodeType::Element(ref element) if element.tag_name == "br") {
+            items.push(InlineItem { box_index, style, kind: InlineItemKind::Break });
+            *after_space = true;
+            return;
+        }
+
+        self.calculate_inline_edges(node);
+        let d: Dimensions = self.dimensions;
+        let metrics: fonts::LineMetrics = fonts.metrics(&style.font, style.font_size);
+        items.push(InlineItem { box_index, style: style.clone(), kind: InlineItemKind::Start(d.margin.left + d.border.left + d.padding.left, metrics) });
+        for child in self.children.iter_mut() {
+            child.collect_inline_items(containing_block, &style, fonts, box_count, after_space, items);
+        }
+        items.push(InlineItem { box_index, style, kind: InlineItemKind::End(d.margin.right + d.border.right + d.padding.right, metrics) });
+    }
+
+    /// Set an inline element's margins, borders, and padding. Its vertical ones don't
+    /// move anything, but backgrounds and borders still cover them.
+    fn calculate_inline_edges(&mut self, style: &style::StyledNode) {
+        let zero: css::Value = css::Value::Length(0.0, css::Unit::Px);
+        let edges = |name: &str, suffix: &str| -> EdgeSizes {
+            let side = |side: &str| style.lookup(&format!("{}-{}{}", name, side, suffix), &format!("{}{}", name, suffix), &zero).to_px();
+            EdgeSizes { left: side("left"), right: side("right"), top: side("top"), bottom: side("bottom") }
+        };
+        let d: &mut Dimensions = &mut self.dimensions;
+        d.margin = edges("margin", "");
+        d.padding = edges("padding", "");
+        d.border = edges("border", "-width");
+    }
+
+    /// Move this box and its descendants to where the lines put them. Returns the area
+    /// they cover, if they have any content.
+    fn apply_placements(&mut self, placements: &mut [Placement], box_index: &mut usize) -> Option<Rect> {
+        let placement: Placement = core::mem::take(&mut placements[*box_index]);
+        *box_index += 1;
+
+        if let Some((dx, dy)) = placement.offset {
+            self.translate(dx, dy);
+            return Some(self.dimensions.margin_box());
+        }
+        let (start, end) = match (placement.start, placement.end) {
+            (Some(start), Some(end)) => (start, end),
+            _ => {
+                // A text node's box covers its fragments.
+                self.text = placement.fragments;
+                let area: Option<Rect> = self.text.iter().map(|fragment: &TextFragment| fragment.rect).reduce(Rect::union);
+                self.dimensions.content = area.unwrap_or_default();
+                return area;
+            }
+        };
+
+        // An inline element's box is as tall as its own font, and reaches from its start to
+        // its end, or across all the lines in between.
+        let mut area: Option<Rect> = None;
+        for child in self.children.iter_mut() {
+            if let Some(child_area) = child.apply_placements(placements, box_index) {
+                area = Some(area.map_or(child_area, |area: Rect| area.union(child_area)));
+            }
+        }
+        let mut content: Rect = start.union(end);
+        if let (Some(area), true) = (area, start.y != end.y) {
+            let across: Rect = content.union(area);
+            (content.x, content.width) = (across.x, across.width);
+        }
+        self.dimensions.content = content;
+        self.lines = placement.lines;
+        Some(self.dimensions.margin_box())
+    }
+}
+
+/**
+ *  Inline Decorations
+ *
+ *  An inline element that wraps onto several lines is cut into one piece per line, and
+ *  each piece gets its own background and borders rather than the element's whole box
+ *  (which would cover the lines' other content too). Each line's piece is as tall as the
+ *  element's font, plus its padding and borders above and below.
+ *
+ *  `box-decoration-break` says what happens at the cuts:
+ *    - `slice` (the initial value): the element is drawn as if it were one long box cut
+ *      into pieces, so the left padding and border are only on the first piece, and the
+ *      right ones only on the last.
+ *    - `clone`: every piece is drawn with all four borders and its padding, as if it were
+ *      a box of its own. (The extra padding and borders only take up room where the
+ *      element really starts and ends; on the other lines they overlap their neighbors.)
+ */
+impl<'a> LayoutBox<'a> {
+    /// The box's pieces to decorate (see "Inline Decorations"): one per line for an inline
+    /// element on more than one line, each with the edges it is drawn with, or otherwise
+    /// just the box's own dimensions.
+    pub fn decoration_boxes(&self) -> Vec<Dimensions> {
+        let node: &style::StyledNode = match self.box_type {
+            BoxType::InlineNode(node) if self.lines.len() > 1 => node,
+            _ => return vec![self.dimensions],
+        };
+        let clone: bool = matches!(node.value("box-decoration-break"), Some(css::Value::Keyword(keyword)) if keyword == "clone");
+        let last: usize = self.lines.len() - 1;
+        self.lines.iter().enumerate().map(|(n, &content): (usize, &Rect)| {
+            let mut d: Dimensions = Dimensions { content, ..self.dimensions };
+            if n > 0 && !clone {
+                (d.margin.left, d.border.left, d.padding.left) = (0.0, 0.0, 0.0);
+            }
+            if n < last && !clone {
+                (d.margin.right, d.border.right, d.padding.right) = (0.0, 0.0, 0.0);
+            }
+            d
+        }).collect()
+    }
+}
+
+impl Rect {
+    /// The smallest rectangle around both rectangles.
+    pub fn union(self, other: Rect) -> Rect {
+        let x: f32 = self.x.min(other.x);
+        let y: f32 = self.y.min(other.y);
+        Rect {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// How far a line's content reaches above and below its baseline, for text in `style`
+/// with font metrics `metrics`: the font's ascent and descent, plus half of the rest of
+/// the `line-height` each.
+fn line_extent(style: &TextStyle, metrics: fonts::LineMetrics) -> (f32, f32) {
+    let line_height: f32 = style.line_height.unwrap_or_else(|| metrics.normal_line_height());
+    let half_leading: f32 = (line_height - metrics.ascent - metrics.descent) / 2.0;
+    (metrics.ascent + half_leading, metrics.descent + half_leading)
+}
+
+/// Stretch each tab in `items` to its tab stop (see "White Space" in `text`), measuring
+/// from the last line break.
+fn expand_tabs(items: &mut [InlineItem], fonts: &FontCollection) {
+    let mut x: f32 = 0.0; // from the start of the line
+    for item in items.iter_mut() {
+        match item.kind {
+            InlineItemKind::Text(ref mut run, _) => {
+                if run.text.contains('\t') {
+                    let interval: f32 = match item.style.tab_size {
+                        TabSize::Spaces(spaces) => spaces * fonts.shape(" ", &item.style.font, run.size)[0].width,
+                        TabSize::Px(size) => size,
+                    };
+                    *run = run.clone().with_tab_stops(x, interval);
+                }
+                x = match run.text.rfind('\n') {
+                    Some(newline) => run.width - run.x_at(newline + 1),
+                    None => x + run.width,
+                };
+            }
+            InlineItemKind::Atomic(width, _) | InlineItemKind::Start(width, _) | InlineItemKind::End(width, _) => x += width,
+            InlineItemKind::Break => x = 0.0,
+        }
+    }
+}
+
+/// Where `run`, in `style`, may be hyphenated: at its soft hyphens, and with `hyphens: auto`
+/// wherever the dictionary for its language says.
+fn hyphenate(run: &fonts::TextRun, style: &TextStyle, fonts: &FontCollection) -> Option<Hyphenation> {
+    if style.hyphens == Hyphens::None || !style.white_space.wraps() {
+        return None;
+    }
+    let mut breaks: Vec<usize> = run.text.match_indices(fonts::SOFT_HYPHEN)
+        .map(|(i, _): (usize, &str)| i + fonts::SOFT_HYPHEN.len_utf8())
+        .collect();
+    let hyphenator = style.lang.as_ref().filter(|_: &&Atom| style.hyphens == Hyphens::Auto).and_then(|lang: &Atom| fonts.hyphenator(lang.as_str()));
+    if let Some(hyphenator) = hyphenator {
+        breaks.extend(hyphenator.hyphenate_text(&run.text));
+        breaks.sort_unstable();
+        breaks.dedup();
+    }
+    if breaks.is_empty() {
+        return None;
+    }
+    let hyphen: fonts::TextRun = fonts.shape("-", &style.font, style.font_size).remove(0);
+    Some(Hyphenation { breaks, hyphen })
+}
+
+/// Cut the items into the pieces lines are made of.
+fn inline_pieces(items: &[InlineItem]) -> Vec<Piece> {
+    let mut pieces: Vec<Piece> = Vec::new();
+    for (item_index, item) in items.iter().enumerate() {
+        let wraps: bool = item.style.white_space.wraps();
+        let piece = |kind: PieceKind, width: f32, break_after: bool| Piece { kind, item: item_index, start: 0, end: 0, width, break_after, hyphen: None };
+        let (run, hyphenation) = match item.kind {
+            InlineItemKind::Text(ref run, ref hyphenation) => (run, hyphenation),
+            InlineItemKind::Atomic(width, _) => {
+                // Lines may break on either side of an atomic box.
+                if let Some(previous) = pieces.last_mut() {
+                    previous.break_after |= wraps;
+                }
+                pieces.push(piece(PieceKind::Atomic, width, wraps));
+                continue;
+            }
+            InlineItemKind::Start(width, _) => {
+                pieces.push(piece(PieceKind::Start, width, false));
+                continue;
+            }
+            InlineItemKind::End(width, _) => {
+                pieces.push(piece(PieceKind::End, width, false));
+                continue;
+            }
+            InlineItemKind::Break => {
+                pieces.push(piece(PieceKind::Break, 0.0, true));
+                continue;
+            }
+        };
+
+        let text: &str = &run.text;
+        let mut word_start: Option<usize> = None;
+        let end_word = |pieces: &mut Vec<Piece>, word_start: &mut Option<usize>, end: usize| {
+            if let Some(start) = word_start.take() {
+                pieces.push(Piece { kind: PieceKind::Word, item: item_index, start, end, width: run.x_at(end) - run.x_at(start), break_after: false, hyphen: None });
+            }
+        };
+        let breaks: &[usize] = hyphenation.as_ref().map_or(&[], |hyphenation: &Hyphenation| hyphenation.breaks.as_slice());
+        let mut next_break: usize = 0;
+        for (i, c) in text.char_indices() {
+            let end: usize = i + c.len_utf8();
+            // A word that may be hyphenated here is cut into two pieces.
+            while next_break < breaks.len() && breaks[next_break] < i {
+                next_break += 1;
+            }
+            let in_word: bool = !matches!(c, ' ' | '\t' | '\n') && !fonts::is_wide(c);
+            if let (true, Some(hyphenation), Some(start)) = (in_word && breaks.get(next_break) == Some(&i), hyphenation, word_start) {
+                let width: f32 = run.x_at(i) - run.x_at(start);
+                pieces.push(Piece { kind: PieceKind::Word, item: item_index, start, end: i, width, break_after: true, hyphen: Some(hyphenation.hyphen.width) });
+                word_start = Some(i);
+            }
+            let (kind, break_after) = match c {
+                '\n' => (PieceKind::Break, true),
+                ' ' | '\t' => (PieceKind::Space, wraps),
+                c if fonts::is_wide(c) => (PieceKind::Word, wraps),
+                _ => {
+                    word_start.get_or_insert(i);
+                    continue;
+                }
+            };
+            end_word(&mut pieces, &mut word_start, i);
+            if fonts::is_wide(c) {
+                // A line may break before a CJK character, as well as after it.
+                if let Some(previous) = pieces.last_mut() {
+                    previous.break_after |= wraps;
+                }
+            }
+            pieces.push(Piece { kind, item: item_index, start: i, end, width: run.x_at(end) - run.x_at(i), break_after, hyphen: None });
+        }
+        end_word(&mut pieces, &mut word_start, text.len());
+    }
+    pieces
+}
+
+/// Fill lines with pieces, and return each line's range of pieces.
+fn break_lines(pieces: &[Piece], available_width: f32) -> Vec<(usize, usize)> {
+    let mut lines: Vec<(usize, usize)> = Vec::new();
+    let mut line_start: usize = 0;
+    let mut line_width: f32 = 0.0;
+    let mut first: usize = 0;
+    while first < pieces.len() {
+        // The next chunk of pieces the line can't end inside.
+        let mut last: usize = first;
+        while !pieces[last].break_after && last + 1 < pieces.len() {
+            last += 1;
+        }
+        // Ends of inline elements stay with what comes before them.
+        while last + 1 < pieces.len() && pieces[last].kind != PieceKind::Break && pieces[last + 1].kind == PieceKind::End {
+            last += 1;
+        }
+        let chunk: &[Piece] = &pieces[first..=last];
+        let width: f32 = chunk.iter().map(|piece: &Piece| piece.width).sum();
+        // Spaces at the end of a line hang past its edge, so they don't count.
+        let trailing_spaces: f32 = chunk.iter().rev()
+            .take_while(|piece: &&Piece| piece.kind == PieceKind::Space)
+            .map(|piece: &Piece| piece.width).sum();
+        // A line that ends inside a hyphenated word needs room for the hyphen.
+        let hyphen: f32 = chunk.iter().rev()
+            .find(|piece: &&Piece| piece.kind != PieceKind::End)
+            .and_then(|piece: &Piece| piece.hyphen).unwrap_or(0.0);
+        if line_start < first && line_width + width - trailing_spaces + hyphen > available_width {
+            lines.push((line_start, first));
+            line_start = first;
+            line_width = 0.0;
+        }
+        line_width += width;
+        if chunk.iter().any(|piece: &Piece| piece.kind == PieceKind::Break) {
+            lines.push((line_start, last + 1));
+            line_start = last + 1;
+            line_width = 0.0;
+        }
+        first = last + 1;
+    }
+    if line_start < pieces.len() {
+        lines.push((line_start, pieces.len()));
+    }
+    lines
+}
+
+/// Place the pieces of one line, whose top is at `y`, and record where everything went
+/// in `placements`. Returns the line's height.
+fn place_line(items: &[InlineItem], pieces: &[Piece], strut: (f32, f32), content: Rect, y: f32, align: TextAlign,
+              placements: &mut Placements) -> f32 {
+    // Collapsible spaces at the start and end of the line go.
+    let mut visible: Vec<bool> = vec![true; pieces.len()];
+    hide_collapsible_spaces(items, pieces, 0..pieces.len(), &mut visible);
+    hide_collapsible_spaces(items, pieces, (0..pieces.len()).rev(), &mut visible);
+
+    // Lay the pieces out from left to right, and find how far the content reaches above
+    // and below the baseline.
+    let mut xs: Vec<f32> = Vec::with_capacity(pieces.len());
+    let mut x: f32 = 0.0;
+    let mut extent: Option<(f32, f32)> = None;
+    for (piece, &visible) in pieces.iter().zip(&visible) {
+        xs.push(x);
+        if !visible {
+            continue;
+        }
+        x += piece.width;
+        let item: &InlineItem = &items[piece.item];
+        let piece_extent: Option<(f32, f32)> = match (&item.kind, piece.kind) {
+            (InlineItemKind::Text(run, _), _) => Some(line_extent(&item.style, run.metrics)),
+            (InlineItemKind::Atomic(_, height), _) => Some((*height, 0.0)),
+            (InlineItemKind::Break, _) => Some((0.0, 0.0)),
+            (InlineItemKind::Start(..) | InlineItemKind::End(..), _) => None,
+        };
+        if let Some((above, below)) = piece_extent {
+            let (max_above, max_below) = extent.unwrap_or(strut);
+            extent = Some((max_above.max(above), max_below.max(below)));
+        }
+    }
+
+    // A line that ends inside a word ends with a hyphen.
+    let hyphenated: Option<usize> = (0..pieces.len()).rev()
+        .find(|&i: &usize| !matches!(pieces[i].kind, PieceKind::Start | PieceKind::End))
+        .filter(|&i: &usize| pieces[i].hyphen.is_some());
+    x += hyphenated.and_then(|i: usize| pieces[i].hyphen).unwrap_or(0.0);
+
+    // A justified line is stretched to fill the block by widening its spaces.
+    let spaces: usize = pieces.iter().zip(&visible).filter(|&(piece, &visible): &(&Piece, &bool)| visible && piece.kind == PieceKind::Space).count();
+    let word_spacing: f32 = if align == TextAlign::Justify && spaces > 0 { ((content.width - x) / spaces as f32).max(0.0) } else { 0.0 };
+    if word_spacing > 0.0 {
+        let mut shift: f32 = 0.0;
+        for (i, piece) in pieces.iter().enumerate() {
+            xs[i] += shift;
+            if visible[i] && piece.kind == PieceKind::Space {
+                shift += word_spacing;
+            }
+        }
+        x += shift;
+    }
+
+    let (above, below) = extent.unwrap_or((0.0, 0.0));
+    let baseline: f32 = y + above;
+    let left: f32 = content.x + match align {
+        TextAlign::Left | TextAlign::Justify => 0.0,
+        TextAlign::Right => content.width - x,
+        TextAlign::Center => (content.width - x) / 2.0,
+    };
+
+    // Record where each box's parts went. Neighboring words and spaces from the same text
+    // node make one fragment. Inline elements left open by the last line continue from
+    // this one's start.
+    let mut open: Vec<(usize, f32, fonts::LineMetrics)> = placements.open.drain(..)
+        .map(|(box_index, metrics): (usize, fonts::LineMetrics)| (box_index, left, metrics))
+        .collect();
+    let mut i: usize = 0;
+    while i < pieces.len() {
+        let piece: Piece = pieces[i];
+        let item: &InlineItem = &items[piece.item];
+        let placement: &mut Placement = &mut placements.boxes[item.box_index];
+        match item.kind {
+            InlineItemKind::Text(ref run, ref hyphenation) => {
+                let mut last: usize = i;
+                while last + 1 < pieces.len() && pieces[last + 1].item == piece.item && pieces[last + 1].kind != PieceKind::Break
+                    && visible[last + 1] && visible[i] {
+                    last += 1;
+                }
+                if visible[i] && piece.kind != PieceKind::Break {
+                    let fragment_run: fonts::TextRun = run.slice(piece.start, pieces[last].end).with_word_spacing(word_spacing);
+                    let rect: Rect = Rect {
+                        x: left + xs[i],
+                        y: baseline - run.metrics.ascent,
+                        width: fragment_run.width,
+                        height: run.metrics.ascent + run.metrics.descent,
+                    };
+                    placement.fragments.push(TextFragment { rect, run: fragment_run, color: item.style.color });
+                    if let (Some(hyphenation), true) = (hyphenation, hyphenated == Some(last)) {
+                        let hyphen: &fonts::TextRun = &hyphenation.hyphen;
+                        placement.fragments.push(TextFragment {
+                            rect: Rect {
+                                x: rect.x + rect.width,
+                                y: baseline - hyphen.metrics.ascent,
+                                width: hyphen.width,
+                                height: hyphen.metrics.ascent + hyphen.metrics.descent,
+                            },
+                            run: hyphen.clone(),
+                            color: item.style.color,
+                        });
+                    }
+                }
+                i = last;
+            }
+            InlineItemKind::Atomic(_, height) => placement.offset = Some((left + xs[i] - content.x, baseline - height - content.y)),
+            InlineItemKind::Start(width, metrics) => {
+                placement.start = Some(content_area(left + xs[i] + width, baseline, metrics));
+                open.push((item.box_index, left + xs[i] + width, metrics));
+            }
+            InlineItemKind::End(_, metrics) => {
+                placement.end = Some(content_area(left + xs[i], baseline, metrics));
+                if let Some(n) = open.iter().rposition(|&(box_index, _, _): &(usize, f32, fonts::LineMetrics)| box_index == item.box_index) {
+                    let (_, start, _) = open.remove(n);
+                    placement.lines.push(Rect { width: left + xs[i] - start, ..content_area(start, baseline, metrics) });
+                }
+            }
+            InlineItemKind::Break => {
+                let point: Rect = Rect { x: left + xs[i], y: baseline, width: 0.0, height: 0.0 };
+                (placement.start, placement.end) = (Some(point), Some(point));
+            }
+        }
+        i += 1;
+    }
+    // The elements still open reach to the end of the line, and go on to the next.
+    for (box_index, start, metrics) in open {
+        placements.boxes[box_index].lines.push(Rect { width: left + x - start, ..content_area(start, baseline, metrics) });
+        placements.open.push((box_index, metrics));
+    }
+    above + below
+}
+
+/*
+ *  Truncation
+ *
+ *  Two properties cut a block's text short, with an ellipsis ("…") to show that there's
+ *  more:
+ *    - `text-overflow: ellipsis`, on a block that hides its overflow, ends each line that's
+ *      too wide for the block with an ellipsis instead of letting it run past the edge.
+ *      It's mostly used with `white-space: nowrap`, to keep a label to one line.
+ *    - `line-clamp: 3` (or `-webkit-line-clamp`) makes the block only three lines tall,
+ *      and ends the third line with an ellipsis if there's more text. The lines after it
+ *      are still laid out, below the block's end, so the block should hide its overflow.
+ *  To make room for the ellipsis, pieces are dropped from the end of the line, and the
+ *  last word that only partly fits is cut after its last character that does. The
+ *  ellipsis is drawn in the block's own font and color, as part of the last text box
+ *  left on the line. The starts and ends of the inline elements cut off stay, with no
+ *  width, so that their boxes still have somewhere to be.
+ */
+/// The text that ends a truncated line.
+const ELLIPSIS: &str = "\u{2026}";
+
+/// Cut the end of a line short with `ellipsis` if it's wider than `available`, or always
+/// if `force` is true, adding an item for the ellipsis to `items`. Returns the new
+/// pieces, or `None` if the line stays as it is.
+fn truncate_line(items: &mut Vec<InlineItem>, pieces: &[Piece], available: f32, ellipsis: &fonts::TextRun, style: &TextStyle,
+                 force: bool) -> Option<Vec<Piece>> {
+    let mut width: f32 = pieces.iter().map(|piece: &Piece| piece.width).sum();
+    for piece in pieces.iter().rev().filter(|piece: &&Piece| !matches!(piece.kind, PieceKind::Start | PieceKind::End)) {
+        match piece.kind {
+            PieceKind::Space => width -= piece.width, // spaces at the end of a line hang past it
+            _ => {
+                width += piece.hyphen.unwrap_or(0.0);
+                break;
+            }
+        }
+    }
+    if !force && width <= available {
+        return None;
+    }
+
+    let room: f32 = available - ellipsis.width;
+    let mut kept: Vec<Piece> = Vec::with_capacity(pieces.len() + 1);
+    let mut x: f32 = 0.0;
+    let mut rest: usize = pieces.len();
+    for (i, piece) in pieces.iter().enumerate() {
+        if x + piece.width <= room {
+            kept.push(*piece);
+            x += piece.width;
+            continue;
+        }
+        if let (PieceKind::Word, InlineItemKind::Text(ref run, _)) = (piece.kind, &items[piece.item].kind) {
+            let x_at = |end: usize| run.x_at(end) - run.x_at(piece.start);
+            let end: Option<usize> = run.text[piece.start..piece.end].char_indices()
+                .map(|(i, c): (usize, char)| piece.start + i + c.len_utf8())
+                .take_while(|&end: &usize| x + x_at(end) <= room)
+                .last();
+            if let Some(end) = end {
+                kept.push(Piece { end, width: x_at(end), break_after: false, hyphen: None, ..*piece });
+            }
+        }
+        rest = i;
+        break;
+    }
+    while kept.last().is_some_and(|piece: &Piece| piece.kind == PieceKind::Space) {
+        kept.pop();
+    }
+    kept.extend(pieces[rest..].iter()
+        .filter(|piece: &&Piece| matches!(piece.kind, PieceKind::Start | PieceKind::End))
+        .map(|piece: &Piece| Piece { width: 0.0, ..*piece }));
+
+    // The ellipsis belongs to the last text box left on the line, or else the first one.
+    let is_text = |piece: &&Piece| piece.kind == PieceKind::Word && matches!(items[piece.item].kind, InlineItemKind::Text(..));
+    let text_piece: Option<&Piece> = kept.iter().rev().find(is_text).or_else(|| pieces.iter().find(is_text));
+    if let Some(box_index) = text_piece.map(|piece: &Piece| items[piece.item].box_index) {
+        items.push(InlineItem { box_index, style: style.clone(), kind: InlineItemKind::Text(ellipsis.clone(), None) });
+        kept.push(Piece {
+            kind: PieceKind::Word,
+            item: items.len() - 1,
+            start: 0,
+            end: ellipsis.text.len(),
+            width: ellipsis.width,
+            break_after: false,
+            hyphen: None,
+        });
+    }
+    Some(kept)
+}
+
+/// Hide the collapsible spaces at one end of a line, going through the pieces in `order`
+/// until something else is visible.
+fn hide_collapsible_spaces(items: &[InlineItem], pieces: &[Piece], order: impl Iterator<Item = usize>, visible: &mut [bool]) {
+    for i in order {
+        match pieces[i].kind {
+            PieceKind::Start | PieceKind::End => continue,
+            PieceKind::Space if items[pieces[i].item].style.white_space.collapses_spaces() => visible[i] = false,
+            _ => break,
+        }
+    }
+}
+
+/// The content area of an inline element at `x` on a line with its baseline at `baseline`,
+/// with no width.
+fn content_area(x: f32, baseline: f32, metrics: fonts::LineMetrics) -> Rect {
+    Rect { x, y: baseline - metrics.ascent, width: 0.0, height: metrics.ascent + metrics.descent }
+}
+
+impl Invalidation {
+    /// The more expensive of two invalidations.
+    fn max_with(self, other: Invalidation) -> Invalidation {
+        match (self, other) {
+            (Invalidation::Relayout, _) | (_, Invalidation::Relayout) => Invalidation::Relayout,
+            (Invalidation::Repaint, _) | (_, Invalidation::Repaint) => Invalidation::Repaint,
+            _ => Invalidation::None,
+        }
+    }
+}
+
+
+/*
  *  The total vertical space taken up by each child is the height of its "margin box",
  *  which we calculate like so:
  */
 
 impl Dimensions {
     // The area covered by the content area plus its padding.
-    fn padding_box(self) -> Rect {
+    pub fn padding_box(self) -> Rect {
         self.content.expanded_by(self.padding)
     }
 
@@ -446,7 +1509,7 @@ impl Dimensions {
     }
 
     // The area covered by the content area plus padding, borders, and margin.
-    fn margin_box(self) -> Rect {
+    pub fn margin_box(self) -> Rect {
         self.border_box().expanded_by(self.margin)
     }
 }
@@ -460,4 +1523,202 @@ impl Rect {
             height: self.height + edge.top + edge.bottom,
         }
     }
+
+    /// The same rectangle, moved by (dx, dy).
+    pub fn translated(self, dx: f32, dy: f32) -> Rect {
+        Rect { x: self.x + dx, y: self.y + dy, ..self }
+    }
+
+    /// The area covered by both rectangles (with zero size if they don't overlap).
+    pub fn intersection(self, other: Rect) -> Rect {
+        let x: f32 = self.x.max(other.x);
+        let y: f32 = self.y.max(other.y);
+        Rect {
+            x,
+            y,
+            width: ((self.x + self.width).min(other.x + other.width) - x).max(0.0),
+            height: ((self.y + self.height).min(other.y + other.height) - y).max(0.0),
+        }
+    }
+
+    /// The same rectangle with every coordinate multiplied by `factor`.
+    pub fn scaled(self, factor: f32) -> Rect {
+        Rect { x: self.x * factor, y: self.y * factor, width: self.width * factor, height: self.height * factor }
+    }
+
+    /// Return true if the point (x, y) is inside the rectangle.
+    pub fn contains(self, x: f32, y: f32) -> bool {
+        x >= self.x && x < self.x + self.width && y >= self.y && y < self.y + self.height
+    }
+}
+
+
+/**
+ *  Scrolling
+ *
+ *  A box with a fixed height can have more content than fits. The `overflow` property
+ *  says what happens to the rest: `visible` (the default) lets it spill out, `hidden`
+ *  clips it to the padding box, and `scroll` and `auto` clip it too but let the user
+ *  scroll to see it.
+ *
+ *  Layout doesn't change: children are laid out exactly as if nothing scrolled. Scrolling
+ *  a box by (sx, sy) just moves everything inside it by (-sx, -sy) when it is painted or
+ *  hit tested. Scroll positions belong to DOM nodes, so they survive re-layout.
+ */
+/// CSS's `overflow` property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Overflow {
+    Visible,
+    Hidden,
+    Scroll,
+    Auto,
+}
+
+/// The scroll position of each scrolled box, keyed by its DOM node.
+pub type ScrollOffsets = HashMap<dom::NodeId, (f32, f32)>;
+
+/// A box that can be hit by the mouse, flattened out of the layout tree.
+#[derive(Clone, Debug)]
+pub struct HitRegion {
+    pub node: dom::NodeId,
+    pub border_box: Rect, // after scrolling, in document coordinates
+    pub clip: Option<Rect>, // the part left visible by `overflow` ancestors
+    pub max_scroll: Option<(f32, f32)>, // set if the user can scroll this box
+}
+
+impl HitRegion {
+    /// Return true if the point is inside the visible part of this box.
+    pub fn contains(&self, x: f32, y: f32) -> bool {
+        self.border_box.contains(x, y) && self.clip.is_none_or(|clip: Rect| clip.contains(x, y))
+    }
+}
+
+impl LayoutBox<'_> {
+    /// The value of `overflow` for this box.
+    pub fn overflow(&self) -> Overflow {
+        let node: &style::StyledNode = match self.box_type {
+            BoxType::BlockNode(node) | BoxType::InlineNode(node) => node,
+            BoxType::AnonymousBlock => return Overflow::Visible,
+        };
+        match node.value("overflow") {
+            Some(css::Value::Keyword(ref keyword)) => match keyword.as_str() {
+                "hidden" => Overflow::Hidden,
+                "scroll" => Overflow::Scroll,
+                "auto" => Overflow::Auto,
+                _ => Overflow::Visible,
+            },
+            _ => Overflow::Visible,
+        }
+    }
+
+    /// How many boxes are in this subtree, including this one.
+    pub fn count_boxes(&self) -> usize {
+        1 + self.children.iter().map(|child: &LayoutBox| child.count_boxes()).sum::<usize>()
+    }
+
+    /// The DOM node this box was generated for, if any.
+    pub fn node_id(&self) -> Option<dom::NodeId> {
+        match self.box_type {
+            BoxType::BlockNode(node) | BoxType::InlineNode(node) => Some(node.node_id),
+            BoxType::AnonymousBlock => None,
+        }
+    }
+
+    /// How far this box's content can be scrolled: the amount by which its children's
+    /// margin boxes (plus its bottom and right padding) stick out of its padding box.
+    pub fn max_scroll(&self) -> (f32, f32) {
+        let padding_box: Rect = self.dimensions.padding_box();
+        let mut right: f32 = padding_box.x + padding_box.width;
+        let mut bottom: f32 = padding_box.y + padding_box.height;
+        for child in self.children.iter() {
+            let margin_box: Rect = child.dimensions.margin_box();
+            right = right.max(margin_box.x + margin_box.width + self.dimensions.padding.right);
+            bottom = bottom.max(margin_box.y + margin_box.height + self.dimensions.padding.bottom);
+        }
+        (right - (padding_box.x + padding_box.width), bottom - (padding_box.y + padding_box.height))
+    }
+
+    /// Flatten the tree into the regions the mouse can hit, in painting order (so the last
+    /// region containing a point is the one on top).
+    pub fn hit_regions(&self, offsets: &ScrollOffsets) -> Vec<HitRegion> {
+        let mut regions: Vec<HitRegion> = Vec::new();
+        self.collect_hit_regions(offsets, (0.0, 0.0), None, &mut regions);
+        regions
+    }
+
+    fn collect_hit_regions(&self, offsets: &ScrollOffsets, translate: (f32, f32), clip: Option<Rect>, regions: &mut Vec<HitRegion>) {
+        let overflow: Overflow = self.overflow();
+        if let Some(node) = self.node_id() {
+            regions.push(HitRegion {
+                node,
+                border_box: self.dimensions.border_box().translated(translate.0, translate.1),
+                clip,
+                max_scroll: match overflow {
+                    Overflow::Scroll | Overflow::Auto => Some(self.max_scroll()),
+                    _ => None,
+                },
+            });
+        }
+
+        let (translate, clip) = self.children_transform(offsets, translate, clip);
+        for child in self.children.iter() {
+            child.collect_hit_regions(offsets, translate, clip, regions);
+        }
+    }
+
+    /// Given the translation and clip that apply to this box, return the ones that apply to
+    /// its children, taking its `overflow` and scroll position into account.
+    pub fn children_transform(&self, offsets: &ScrollOffsets, translate: (f32, f32), clip: Option<Rect>) -> ((f32, f32), Option<Rect>) {
+        if self.overflow() == Overflow::Visible {
+            return (translate, clip);
+        }
+        let padding_box: Rect = self.dimensions.padding_box().translated(translate.0, translate.1);
+        let clip: Rect = match clip {
+            Some(clip) => clip.intersection(padding_box),
+            None => padding_box,
+        };
+        let (sx, sy) = self.node_id().and_then(|node: dom::NodeId| offsets.get(&node).copied()).unwrap_or((0.0, 0.0));
+        ((translate.0 - sx, translate.1 - sy), Some(clip))
+    }
+}
+
+/// Return the topmost region containing the point, if any.
+pub fn hit_test(regions: &[HitRegion], x: f32, y: f32) -> Option<&HitRegion> {
+    regions.iter().rev().find(|region: &&HitRegion| region.contains(x, y))
+}
+
+
+/// A layout tree, with a table from each DOM node to its first box: `style::StyledTree`
+/// (see "Looking Up Styled Nodes" in `style`) for layout boxes.
+pub struct BoxTable<'b, 'a> {
+    pub root: &'b LayoutBox<'a>,
+    boxes: Vec<Option<&'b LayoutBox<'a>>>, // indexed by `NodeId::index`
+}
+
+impl<'b, 'a> BoxTable<'b, 'a> {
+    /// Index the layout tree under `root`.
+    pub fn new(root: &'b LayoutBox<'a>) -> BoxTable<'b, 'a> {
+        let mut table: BoxTable = BoxTable { root, boxes: Vec::new() };
+        table.add(root);
+        table
+    }
+
+    fn add(&mut self, layout_box: &'b LayoutBox<'a>) {
+        if let Some(node) = layout_box.node_id() {
+            let index: usize = node.index();
+            if index >= self.boxes.len() {
+                self.boxes.resize(index + 1, None);
+            }
+            // A node's first box is its own; any after it are its generated content's.
+            self.boxes[index].get_or_insert(layout_box);
+        }
+        for child in layout_box.children.iter() {
+            self.add(child);
+        }
+    }
+
+    /// The first box generated by DOM node `node`, or `None` if it isn't rendered.
+    pub fn box_for(&self, node: dom::NodeId) -> Option<&'b LayoutBox<'a>> {
+        self.boxes.get(node.index()).copied().flatten()
+    }
 }
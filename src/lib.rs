@@ -0,0 +1,150 @@
+//! A toy browser engine, following the "Let's build a browser engine" series.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+pub mod atom;
+pub mod collections;
+pub mod dom;
+#[cfg(feature = "std")]
+pub mod engine;
+#[cfg(feature = "std")]
+pub mod error;
+#[cfg(feature = "std")]
+pub mod events;
+#[cfg(feature = "std")]
+pub mod event_loop;
+pub mod html;
+pub mod encoding;
+pub mod scan;
+pub mod css;
+pub mod lint;
+#[cfg(feature = "std")]
+pub mod debug;
+pub mod style;
+pub mod counters;
+pub mod quotes;
+pub mod fonts;
+pub mod shaping;
+pub mod text;
+pub mod hyphenation;
+pub mod layout;
+pub mod painting;
+pub mod image;
+pub mod math;
+pub mod trace;
+pub mod viewport;
+#[cfg(feature = "std")]
+pub mod net;
+#[cfg(feature = "std")]
+pub mod loader;
+#[cfg(feature = "std")]
+pub mod memory;
+#[cfg(feature = "std")]
+pub mod snapshot;
+#[cfg(feature = "std")]
+pub mod reftest;
+#[cfg(feature = "std")]
+pub mod wpt;
+#[cfg(feature = "std")]
+pub mod visit;
+#[cfg(feature = "std")]
+pub mod find;
+#[cfg(feature = "std")]
+pub mod accessibility;
+#[cfg(feature = "std")]
+pub mod readability;
+pub mod forms;
+#[cfg(feature = "viewer")]
+pub mod viewer;
+#[cfg(feature = "devtools")]
+pub mod devtools;
+#[cfg(feature = "js")]
+pub mod js;
+#[cfg(feature = "js")]
+pub mod script;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "capi")]
+pub mod capi;
+#[cfg(feature = "bench-fixtures")]
+pub mod bench_fixtures;
+
+
+/*
+    no_std
+
+    The core of the engine (parsing HTML and CSS, styling, layout, and painting into a
+    `Canvas`) needs nothing from an operating system, only an allocator. Without the
+    default `std` feature the crate is `#![no_std]`, and that core is all it has, so a
+    device with no OS can still render an HTML user interface into its framebuffer:
+
+        [dependencies]
+        build-a-browser-engine-in-rust = { version = "0.1", default-features = false }
+
+        let document: dom::Document = html::parse(source)?;
+        let stylesheet: css::Stylesheet = css::parse(style)?;
+        let style_root: style::StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+        let arena: layout::Bump = layout::Bump::new();
+        let layout_root: layout::LayoutBox = layout::layout_tree(&style_root, viewport, &image::ImageCache::new(), &fonts::FontCollection::new(), &arena)?;
+        let canvas: painting::Canvas = painting::paint(&layout_root, viewport.content);
+
+    Everything else (`Page`, networking, loading images from files, threads, and the
+    features built on them) needs `std`. A few things change without it:
+        - the core modules take `String`, `Vec`, and friends from `alloc` (see `prelude`)
+        - hash maps and sets come from `hashbrown`, with a fixed hasher (see `collections`)
+        - the atom table is behind a spin lock rather than a `Mutex` (see `atom`)
+        - floating point functions like `sqrt` come from `math`
+        - images have to be decoded by the caller and put in the `ImageCache` directly
+        - there are no fonts, so text is laid out with stand-in metrics and not drawn
+
+    The one supported way to build the core on its own is for such a target:
+
+        cargo build --lib --no-default-features --target thumbv7em-none-eabihf
+
+    (`tests/no_std_core.rs` runs exactly that.) On a host that has `std`, a plain
+    `cargo build --no-default-features` fails: the crate is also built as a `cdylib` (see
+    `Cargo.toml`), and linking one needs a panic handler, which only `std` provides.
+ */
+
+/// The parts of the standard prelude that come from `alloc`, for `no_std` builds.
+#[cfg(not(feature = "std"))]
+mod prelude {
+    pub use alloc::boxed::Box;
+    pub use alloc::format;
+    pub use alloc::string::{String, ToString};
+    pub use alloc::vec;
+    pub use alloc::vec::Vec;
+}
+
+
+/*
+    Thread Safety
+
+    Parallel styling and layout hand the same DOM, stylesheets, and style tree to several
+    worker threads at once, so those types must be `Send + Sync`. They are plain data (the
+    DOM is an arena of indices, not `Rc` pointers, and atoms are `&'static str`), and the
+    few shared pieces use thread-safe types: the atom table is behind a lock, and
+    decoded images are shared with `Arc`.
+
+    The assertions below fail to compile if a change ever makes one of them thread-unsafe,
+    e.g. by adding an `Rc` or a `RefCell` field.
+
+    Event listeners are the exception: they are closures owned by whoever dispatches
+    events, and are allowed to capture non-thread-safe state.
+ */
+fn assert_send_sync<T: Send + Sync>() {}
+
+const _: fn() = || {
+    assert_send_sync::<atom::Atom>();
+    assert_send_sync::<dom::Tree>();
+    assert_send_sync::<dom::Document>();
+    assert_send_sync::<css::Stylesheet>();
+    assert_send_sync::<style::StyledNode<'static>>();
+    assert_send_sync::<layout::LayoutBox<'static>>();
+    assert_send_sync::<image::ImageCache>();
+    assert_send_sync::<painting::Canvas>();
+    #[cfg(feature = "std")]
+    assert_send_sync::<net::Client>();
+};
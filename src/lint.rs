@@ -0,0 +1,146 @@
+//! A lint pass over stylesheets: declarations the engine won't use, and rules that match
+//! nothing.
+
+use crate::atom::Atom;
+use crate::{css, dom, style};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use core::fmt;
+
+
+/*
+    Linting Stylesheets
+
+    The CSS parser is forgiving: it takes any identifier as a property name and any value
+    for any property, because real CSS has hundreds of properties and the engine only knows
+    a few. That makes mistakes silent. `colour: red` and `display: blokc` parse fine, and
+    do nothing. `lint` looks for three kinds of problems:
+
+    - unknown properties: names that aren't in `PROPERTIES`, the properties the engine
+      actually implements (so a property that is real CSS, but not supported here, is
+      reported too)
+    - invalid values: a known property with a value of the wrong kind, e.g. a color for
+      `width`, or a keyword the property doesn't have
+    - unused rules: rules none of whose selectors match any element of the given
+      document. Pseudo-classes are ignored here, since `a:hover` matches as soon as a
+      link is hovered.
+
+    Rules are numbered from 0, in the order they appear in `stylesheet.rules`.
+
+    e.g.
+        a { colour: #ff0000; } p { display: blokc; } table { margin: 0px; }
+        on <html><body><p><a href="/">link</a></p></body></html>
+        => [
+               UnknownProperty { rule: 0, name: "colour" },
+               InvalidValue { rule: 1, name: "display", value: Keyword("blokc") },
+               UnusedRule { rule: 2 },
+           ]
+ */
+
+/// What values a property takes.
+#[derive(Clone, Copy, Debug)]
+enum Accepts {
+    Length,
+    LengthOrAuto,
+    Color,
+    Keywords(&'static [&'static str]),
+}
+
+/// The properties the engine implements, and the values each one takes.
+const PROPERTIES: [(&str, Accepts); 22] = [
+    ("display", Accepts::Keywords(&["block", "inline", "none"])),
+    ("overflow", Accepts::Keywords(&["visible", "hidden", "scroll", "auto"])),
+    ("width", Accepts::LengthOrAuto),
+    ("height", Accepts::LengthOrAuto),
+    ("margin", Accepts::LengthOrAuto),
+    ("margin-top", Accepts::LengthOrAuto),
+    ("margin-right", Accepts::LengthOrAuto),
+    ("margin-bottom", Accepts::LengthOrAuto),
+    ("margin-left", Accepts::LengthOrAuto),
+    ("padding", Accepts::Length),
+    ("padding-top", Accepts::Length),
+    ("padding-right", Accepts::Length),
+    ("padding-bottom", Accepts::Length),
+    ("padding-left", Accepts::Length),
+    ("border-width", Accepts::Length),
+    ("border-top-width", Accepts::Length),
+    ("border-right-width", Accepts::Length),
+    ("border-bottom-width", Accepts::Length),
+    ("border-left-width", Accepts::Length),
+    ("border-color", Accepts::Color),
+    ("background", Accepts::Color),
+    ("color", Accepts::Color),
+];
+
+impl Accepts {
+    fn accepts(self, value: &css::Value) -> bool {
+        match (self, value) {
+            (Accepts::Length | Accepts::LengthOrAuto, css::Value::Length(..)) => true,
+            (Accepts::LengthOrAuto, css::Value::Keyword(keyword)) => keyword.as_str() == "auto",
+            (Accepts::Color, css::Value::ColorValue(_)) => true,
+            (Accepts::Keywords(keywords), css::Value::Keyword(keyword)) => keywords.contains(&keyword.as_str()),
+            _ => false,
+        }
+    }
+}
+
+/// One problem `lint` found.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Lint {
+    UnknownProperty { rule: usize, name: Atom },
+    InvalidValue { rule: usize, name: Atom, value: css::Value },
+    UnusedRule { rule: usize },
+}
+
+impl Lint {
+    /// The index of the rule the problem is in.
+    pub fn rule(&self) -> usize {
+        match *self {
+            Lint::UnknownProperty { rule, .. } | Lint::InvalidValue { rule, .. } | Lint::UnusedRule { rule } => rule,
+        }
+    }
+}
+
+impl fmt::Display for Lint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Lint::UnknownProperty { rule, ref name } => write!(f, "rule {}: unknown property `{}`", rule, name),
+            Lint::InvalidValue { rule, ref name, ref value } => write!(f, "rule {}: invalid value `{}` for `{}`", rule, value, name),
+            Lint::UnusedRule { rule } => write!(f, "rule {}: matches no element", rule),
+        }
+    }
+}
+
+/// Check every declaration in `stylesheet`, and every rule against the elements of the
+/// tree at `root`. Problems are in rule order, each rule's declarations before the rule
+/// itself.
+pub fn lint(stylesheet: &css::Stylesheet, tree: &dom::Tree, root: dom::NodeId) -> Vec<Lint> {
+    let elements: Vec<&dom::Element> = tree.descendants(root)
+        .filter_map(|node: dom::NodeId| match tree[node].node_type {
+            dom::NodeType::Element(ref element) => Some(element),
+            _ => None,
+        })
+        .collect();
+
+    let mut lints: Vec<Lint> = Vec::new();
+    for (index, rule) in stylesheet.rules.iter().enumerate() {
+        for declaration in &rule.declarations {
+            match PROPERTIES.iter().find(|(name, _): &&(&str, Accepts)| *name == declaration.name.as_str()) {
+                None => lints.push(Lint::UnknownProperty { rule: index, name: declaration.name }),
+                Some(&(_, accepts)) if !accepts.accepts(&declaration.value) => {
+                    lints.push(Lint::InvalidValue { rule: index, name: declaration.name, value: declaration.value.clone() });
+                }
+                Some(_) => {}
+            }
+        }
+        let used: bool = rule.selectors.iter().any(|selector: &css::Selector| {
+            let css::Selector::Simple(ref simple) = *selector;
+            elements.iter().any(|element: &&dom::Element| style::matches_simple_selector(element, simple))
+        });
+        if !used {
+            lints.push(Lint::UnusedRule { rule: index });
+        }
+    }
+    lints
+}
@@ -0,0 +1,423 @@
+//! Loading subresources (images, stylesheets, and framed documents) in the background.
+
+pub use crate::image::{Image, ImageCache, ImageState};
+use crate::memory::HeapSize;
+use crate::{css, dom, encoding, html, net, trace};
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+use std::io::Cursor;
+use std::mem::size_of;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{num::NonZeroUsize, panic, thread};
+
+
+/*
+    Subresources
+
+    A page is more than its HTML: it refers to images, stylesheets, and so on, which have
+    to be fetched separately. We don't want layout to wait for every image to arrive, so
+    images are loaded on background threads while the page is laid out with placeholder
+    boxes. When an image finishes decoding, the page is updated: if the image turned out to
+    have a different size than the placeholder, we lay out again; otherwise we only need
+    to repaint.
+
+    Image sources can be `http://` URLs (fetched with the shared `net::Client`, so they get
+    the page's cookies) or paths on the local file system. Relative sources are resolved
+    against the document's base URL, if it has one, but images are still cached under
+    their `src` as written, which is how layout looks them up. Only PNG decoding is
+    supported.
+
+    The HTML of `<iframe>` documents is loaded the same way, by a `DocumentLoader`.
+
+    WebAssembly has no threads, file system, or sockets, so there each load runs (and
+    fails) on the calling thread, and its result is waiting for the next `poll`.
+
+    Elsewhere, a loader can be asked to do the same (`set_synchronous`), for a page in
+    deterministic mode: a screenshot taken after loading then never depends on which
+    thread happened to finish first.
+ */
+
+impl HeapSize for ImageCache {
+    fn heap_size(&self) -> usize {
+        // Count the pixels of each decoded image, and the table itself.
+        let images: usize = self.images.values().map(|state: &ImageState| match state {
+            ImageState::Ready(image) => image.pixels.heap_size(),
+            ImageState::Pending | ImageState::Failed => 0,
+        }).sum();
+        let table: usize = self.images.capacity() * (size_of::<(String, ImageState)>() + 1);
+        table + self.images.keys().map(|src: &String| src.heap_size()).sum::<usize>() + images
+    }
+}
+
+
+/// Fetches and decodes images on background threads.
+/*
+    e.g.
+        let mut loader = ImageLoader::new();
+        loader.request_images(&tree, root);
+        ... lay out and paint with placeholders ...
+        if !loader.poll().is_empty() {
+            match layout_root.update_images(&loader.cache) { ... }
+        }
+ */
+pub struct ImageLoader {
+    pub cache: ImageCache,
+    client: Arc<Mutex<net::Client>>,
+    base_url: Option<net::Url>, // what relative sources are resolved against
+    synchronous: bool,          // load on the calling thread instead
+    sender: Sender<(String, ImageState)>,
+    receiver: Receiver<(String, ImageState)>,
+}
+
+impl Default for ImageLoader {
+    fn default() -> ImageLoader {
+        ImageLoader::new()
+    }
+}
+
+impl ImageLoader {
+    /// Create a loader with its own HTTP client.
+    pub fn new() -> ImageLoader {
+        ImageLoader::with_client(Arc::new(Mutex::new(net::Client::new())))
+    }
+
+    /// Create a loader that shares cookies and headers with the page's client.
+    pub fn with_client(client: Arc<Mutex<net::Client>>) -> ImageLoader {
+        let (sender, receiver) = mpsc::channel();
+        ImageLoader { cache: ImageCache::new(), client, base_url: None, synchronous: cfg!(feature = "deterministic"), sender, receiver }
+    }
+
+    /// Resolve relative sources requested from now on against `base_url` (the document's
+    /// base URL), or not at all if it's `None` or isn't a URL.
+    pub fn set_base_url(&mut self, base_url: Option<&str>) {
+        self.base_url = base_url.and_then(|base_url: &str| net::Url::parse(base_url).ok());
+    }
+
+    /// The HTTP client images are fetched with.
+    pub fn client(&self) -> Arc<Mutex<net::Client>> {
+        self.client.clone()
+    }
+
+    /// Load images on the calling thread, so each `request` has finished (for the next
+    /// `poll`) by the time it returns. Loads already in flight carry on in the background.
+    pub fn set_synchronous(&mut self, synchronous: bool) {
+        self.synchronous = synchronous;
+    }
+
+    /// Return true if images load on the calling thread.
+    pub fn is_synchronous(&self) -> bool {
+        self.synchronous
+    }
+
+    /// Start loading `src` unless it has already been requested.
+    pub fn request(&mut self, src: &str) {
+        if self.cache.state(src).is_some() {
+            return;
+        }
+        self.cache.insert(src.to_string(), ImageState::Pending);
+
+        let url: String = match self.base_url {
+            Some(ref base_url) if !src.contains("://") => base_url.join(src).map_or_else(|_| src.to_string(), |url: net::Url| url.to_string()),
+            _ => src.to_string(),
+        };
+        let src: String = src.to_string();
+        let client: Arc<Mutex<net::Client>> = self.client.clone();
+        let sender: Sender<(String, ImageState)> = self.sender.clone();
+        let load = move || {
+            let state: ImageState = match fetch(&client, &url).and_then(|bytes: Vec<u8>| decode_png(&bytes)) {
+                Some(image) => ImageState::Ready(Arc::new(image)),
+                None => ImageState::Failed,
+            };
+            // The loader may have been dropped while we were working; that's fine.
+            let _ = sender.send((src, state));
+        };
+        if self.synchronous { load() } else { spawn(load) }
+    }
+
+    /// Load `src` again, e.g. because the file changed. Until it finishes, the old image
+    /// is gone and its boxes show placeholders.
+    pub fn reload(&mut self, src: &str) {
+        self.cache.remove(src);
+        self.request(src);
+    }
+
+    /// Start loading the `src` of every `<img>` element under `root`.
+    pub fn request_images(&mut self, tree: &dom::Tree, root: dom::NodeId) {
+        for node in tree.descendants(root) {
+            if let dom::NodeType::Element(ref element) = tree[node].node_type {
+                if element.tag_name == "img" {
+                    if let Some(src) = element.attr("src") {
+                        self.request(src);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Collect images that finished loading since the last call, returning their sources.
+    pub fn poll(&mut self) -> Vec<String> {
+        let mut finished: Vec<String> = Vec::new();
+        while let Ok((src, state)) = self.receiver.try_recv() {
+            self.cache.insert(src.clone(), state);
+            finished.push(src);
+        }
+        finished
+    }
+
+    /// Return true if any requested image is still loading.
+    pub fn is_loading(&self) -> bool {
+        self.cache.images.values().any(|state: &ImageState| matches!(state, ImageState::Pending))
+    }
+}
+
+
+/// Fetches the HTML of framed documents on background threads.
+/*
+    Unlike images, documents aren't cached: each request fetches the source again, since
+    every frame gets a document of its own, and the loader just hands back the text.
+
+    e.g.
+        let mut loader = DocumentLoader::with_client(client);
+        loader.request("frame.html");
+        ...
+        for (src, html) in loader.poll() { ... }
+ */
+pub struct DocumentLoader {
+    client: Arc<Mutex<net::Client>>,
+    pending: usize,
+    synchronous: bool, // load on the calling thread instead
+    sender: Sender<(String, Option<String>)>,
+    receiver: Receiver<(String, Option<String>)>,
+}
+
+impl DocumentLoader {
+    /// Create a loader that shares cookies and headers with the page's client.
+    pub fn with_client(client: Arc<Mutex<net::Client>>) -> DocumentLoader {
+        let (sender, receiver) = mpsc::channel();
+        DocumentLoader { client, pending: 0, synchronous: cfg!(feature = "deterministic"), sender, receiver }
+    }
+
+    /// Load documents on the calling thread, like `ImageLoader::set_synchronous`.
+    pub fn set_synchronous(&mut self, synchronous: bool) {
+        self.synchronous = synchronous;
+    }
+
+    /// Start loading the HTML at `src`.
+    pub fn request(&mut self, src: &str) {
+        self.pending += 1;
+        let src: String = src.to_string();
+        let client: Arc<Mutex<net::Client>> = self.client.clone();
+        let sender: Sender<(String, Option<String>)> = self.sender.clone();
+        let load = move || {
+            let html: Option<String> = fetch(&client, &src).map(|bytes: Vec<u8>| encoding::decode_html(&bytes, None).0.into_owned());
+            let _ = sender.send((src, html));
+        };
+        if self.synchronous { load() } else { spawn(load) }
+    }
+
+    /// Collect the documents that finished loading since the last call, as their sources
+    /// and HTML (or `None` if loading failed).
+    pub fn poll(&mut self) -> Vec<(String, Option<String>)> {
+        let finished: Vec<(String, Option<String>)> = self.receiver.try_iter().collect();
+        self.pending -= finished.len();
+        finished
+    }
+
+    /// Return true if any requested document is still loading.
+    pub fn is_loading(&self) -> bool {
+        self.pending > 0
+    }
+}
+
+
+
+/*
+    Stylesheets
+
+    A page's CSS comes from its `<style>` elements and the `<link rel="stylesheet">`s it
+    links to, applied in document order. Fetching a linked sheet means waiting on the
+    network, and parsing a big one can take as long as parsing the page's HTML, so there's
+    no reason for either to wait for the other. `with_stylesheets` finds the sheets by
+    tokenizing the HTML (see "Tokens" in `html`), without building a tree, and fetches and
+    parses them on a pool of worker threads, while the calling thread builds the document:
+
+        let ((document, errors), stylesheet) = loader::with_stylesheets(&source, &url, &client, || {
+            html::parse_with_errors(&source)
+        });
+
+    Each sheet is parsed on its own, and their rules are put together in document order
+    afterwards, so the stylesheet is the same as if they'd been parsed one after another,
+    whichever thread finishes first. Links are resolved against the page's `<base href>`,
+    if it has one, as they would be from the document. A sheet that can't be fetched is
+    left out, and one that doesn't parse is an error (the first, in document order). Like
+    the tree builder, the scan stops at the first error in the markup's syntax.
+
+    WebAssembly has no threads, so there the sheets are loaded one after another before
+    the document is built.
+ */
+/// One of a page's stylesheets, as found in its HTML.
+enum StylesheetSource {
+    Inline(String), // the text of a `<style>` element
+    Linked(String), // the `href` of a `<link rel="stylesheet">`, as written
+}
+
+/// Run `build` (which typically parses `html` into a document) while the stylesheets
+/// `html` contains and links to are loaded and parsed on other threads, and return its
+/// result with the page's stylesheet. `url` is where `html` came from. See "Stylesheets",
+/// above.
+///
+/// With tracing on, the last part is a "parse css" event for how long the stylesheets
+/// took to load and parse, linked ones' fetches included.
+pub fn with_stylesheets<T: Send>(html: &str, url: &net::Url, client: &Mutex<net::Client>, build: impl FnOnce() -> T + Send)
+                                 -> (T, Result<css::Stylesheet, css::ParseError>, Option<trace::TraceEvent>) {
+    let load = || -> (Result<css::Stylesheet, css::ParseError>, Option<trace::TraceEvent>) {
+        let start: Option<Instant> = trace::now();
+        let (sources, base) = find_stylesheets(html);
+        let base: net::Url = base.and_then(|href: String| url.join(&href).ok()).unwrap_or_else(|| url.clone());
+        let sheets: Vec<Option<Result<css::Stylesheet, css::ParseError>>> = load_all(&sources, &base, client);
+        let stylesheet: Result<css::Stylesheet, css::ParseError> = sheets.into_iter().flatten()
+            .try_fold(Vec::new(), |mut rules: Vec<css::Rule>, sheet: Result<css::Stylesheet, css::ParseError>| {
+                rules.extend(sheet?.rules);
+                Ok(rules)
+            })
+            .map(|rules: Vec<css::Rule>| css::Stylesheet { rules });
+        (stylesheet, trace::TraceEvent::since("parse css", "engine", start))
+    };
+    #[cfg(not(target_arch = "wasm32"))]
+    return thread::scope(|scope: &thread::Scope| {
+        let loading: thread::ScopedJoinHandle<_> = scope.spawn(load);
+        let built: T = build();
+        let (stylesheet, event) = loading.join().unwrap_or_else(|payload| panic::resume_unwind(payload));
+        (built, stylesheet, event)
+    });
+    #[cfg(target_arch = "wasm32")]
+    {
+        let (stylesheet, event) = load();
+        (build(), stylesheet, event)
+    }
+}
+
+/// The stylesheets in `html`, in document order, and the `href` of its `<base>`, if any.
+fn find_stylesheets(html: &str) -> (Vec<StylesheetSource>, Option<String>) {
+    let mut sources: Vec<StylesheetSource> = Vec::new();
+    let mut base: Option<String> = None;
+    let mut in_style: bool = false;
+    for token in html::tokenize(html).map_while(Result::ok) {
+        match token {
+            html::Token::StartTag { name, attributes, self_closing } => match name.as_str() {
+                "style" => in_style = !self_closing,
+                "link" if attributes.get("rel").is_some_and(|rel: &String| rel == "stylesheet") => {
+                    sources.extend(attributes.get("href").map(|href: &String| StylesheetSource::Linked(href.clone())));
+                }
+                "base" if base.is_none() => base = attributes.get("href").cloned(),
+                _ => {}
+            },
+            html::Token::Text(text) if in_style => sources.push(StylesheetSource::Inline(text.into_owned())),
+            html::Token::EndTag { name } if name == "style" => in_style = false,
+            _ => {}
+        }
+    }
+    (sources, base)
+}
+
+/// Fetch (if linked) and parse one stylesheet, or `None` if it can't be fetched.
+fn load_stylesheet(source: &StylesheetSource, base: &net::Url, client: &Mutex<net::Client>) -> Option<Result<css::Stylesheet, css::ParseError>> {
+    match source {
+        StylesheetSource::Inline(text) => Some(css::parse(text)),
+        StylesheetSource::Linked(href) => {
+            let bytes: Vec<u8> = fetch(client, &base.join(href).ok()?.to_string())?;
+            Some(css::parse_bytes(&bytes))
+        }
+    }
+}
+
+/// Load every one of `sources`, on as many threads as there are cores, returning them in
+/// the same order.
+#[cfg(not(target_arch = "wasm32"))]
+fn load_all(sources: &[StylesheetSource], base: &net::Url, client: &Mutex<net::Client>) -> Vec<Option<Result<css::Stylesheet, css::ParseError>>> {
+    let workers: usize = thread::available_parallelism().map_or(1, NonZeroUsize::get).min(sources.len());
+    // Each worker takes the next sheet nobody has started on, until there are none left.
+    let next: AtomicUsize = AtomicUsize::new(0);
+    let work = || -> Vec<(usize, Option<Result<css::Stylesheet, css::ParseError>>)> {
+        let mut loaded: Vec<(usize, Option<Result<css::Stylesheet, css::ParseError>>)> = Vec::new();
+        loop {
+            let index: usize = next.fetch_add(1, Ordering::Relaxed);
+            match sources.get(index) {
+                Some(source) => loaded.push((index, load_stylesheet(source, base, client))),
+                None => return loaded,
+            }
+        }
+    };
+    let mut loaded: Vec<(usize, Option<Result<css::Stylesheet, css::ParseError>>)> = thread::scope(|scope: &thread::Scope| {
+        let handles: Vec<thread::ScopedJoinHandle<_>> = (0..workers).map(|_| scope.spawn(work)).collect();
+        handles.into_iter().flat_map(|handle| handle.join().unwrap_or_else(|payload| panic::resume_unwind(payload))).collect()
+    });
+    loaded.sort_by_key(|&(index, _)| index);
+    loaded.into_iter().map(|(_, sheet)| sheet).collect()
+}
+
+/// Load every one of `sources`, in order, since WebAssembly has no threads.
+#[cfg(target_arch = "wasm32")]
+fn load_all(sources: &[StylesheetSource], base: &net::Url, client: &Mutex<net::Client>) -> Vec<Option<Result<css::Stylesheet, css::ParseError>>> {
+    sources.iter().map(|source: &StylesheetSource| load_stylesheet(source, base, client)).collect()
+}
+
+
+/// Run `job` on a background thread.
+#[cfg(not(target_arch = "wasm32"))]
+fn spawn(job: impl FnOnce() + Send + 'static) {
+    thread::spawn(job);
+}
+
+/// Run `job` right away, since WebAssembly has no threads.
+#[cfg(target_arch = "wasm32")]
+fn spawn(job: impl FnOnce() + Send + 'static) {
+    job();
+}
+
+/// Read the bytes of `src`, either over HTTP or from the file system.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn fetch(client: &Mutex<net::Client>, src: &str) -> Option<Vec<u8>> {
+    if src.contains("://") {
+        let response: net::Response = client.lock().ok()?.get(src).ok()?;
+        if response.status == 200 { Some(response.body) } else { None }
+    } else {
+        fs::read(src).ok()
+    }
+}
+
+/// WebAssembly can't open sockets or files, so there's nothing to read `src` from.
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn fetch(_client: &Mutex<net::Client>, _src: &str) -> Option<Vec<u8>> {
+    None
+}
+
+/// Decode a PNG file into RGBA pixels.
+fn decode_png(bytes: &[u8]) -> Option<Image> {
+    let mut decoder = png::Decoder::new(Cursor::new(bytes));
+    // Expand palettes and low bit depths, and reduce 16-bit channels to 8 bits.
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().ok()?;
+    let mut buffer: Vec<u8> = vec![0; reader.output_buffer_size()?];
+    let info: png::OutputInfo = reader.next_frame(&mut buffer).ok()?;
+    let data: &[u8] = &buffer[..info.buffer_size()];
+
+    let pixels: Vec<css::Color> = match info.color_type {
+        png::ColorType::Rgba => data.chunks_exact(4)
+            .map(|p: &[u8]| css::Color { r: p[0], g: p[1], b: p[2], a: p[3] }).collect(),
+        png::ColorType::Rgb => data.chunks_exact(3)
+            .map(|p: &[u8]| css::Color { r: p[0], g: p[1], b: p[2], a: 255 }).collect(),
+        png::ColorType::GrayscaleAlpha => data.chunks_exact(2)
+            .map(|p: &[u8]| css::Color { r: p[0], g: p[0], b: p[0], a: p[1] }).collect(),
+        png::ColorType::Grayscale => data.iter()
+            .map(|&v: &u8| css::Color { r: v, g: v, b: v, a: 255 }).collect(),
+        png::ColorType::Indexed => return None, // removed by `EXPAND`
+    };
+
+    Some(Image { width: info.width, height: info.height, pixels })
+}
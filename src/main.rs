@@ -1,10 +1,650 @@
-mod dom;
-mod html;
-mod css;
-mod style;
-mod layout;
-mod painting;
+//! Command-line front end: render a page to an image file, or dump its internals.
+
+use build_a_browser_engine_in_rust::debug::{self, DumpFormat};
+use build_a_browser_engine_in_rust::engine::{self, Page, ZOOM_RANGE};
+use build_a_browser_engine_in_rust::trace;
+use build_a_browser_engine_in_rust::wpt::{self, Outcome, Summary};
+use build_a_browser_engine_in_rust::{css, dom, html, layout, painting, style};
+use std::collections::HashMap;
+use std::env;
+use std::hint::black_box;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+
+const USAGE: &str = "\
+Usage: browser-engine <command> <page.html | http://url> [options]
+
+Commands:
+    render                Paint the page to an image file
+    watch                 Render the page again whenever its files change
+    bench                 Time each stage of rendering the page, over many runs
+    dump-dom              Print the DOM tree
+    dump-style            Print the style tree, with each node's specified values
+    dump-layout           Print the layout tree, with each box's dimensions
+    dump-display-list     Print the display list
+    report                Write an HTML report of the DOM, matched rules, styles, and
+                          boxes, over a picture of the page (-o, default report.html)
+    view                  Open the page in a window (needs the `viewer` feature)
+    devtools              Serve the page's DOM, styles, and layout as JSON on a local
+                          port, for an inspector (needs the `devtools` feature)
+    wpt                   Run the Web Platform Tests under a directory (instead of a
+                          page), and count how many pass
+
+Options:
+    --css <file>          Add a stylesheet (may be repeated)
+    --size <WxH>          Viewport size in px (default 800x600)
+    --viewport <WxH>      Same as --size
+    --scale <factor>      Zoom the page, e.g. 2 for a high-DPI screenshot twice the size
+    --media <screen|print>
+                          Which `@media` rules to style the page with (default screen)
+    --wait <ms>           How long to wait for images to load (default 2000)
+    -h, --help            Show this help
+
+Options for render and watch:
+    --format <png|ppm>    Output format (default: from the output file's extension, else png)
+    -o, --output <file>   Where to write the image (default out.png)
+    --trace <file>        Also write how long each stage of loading and rendering took,
+                          as a Chrome trace (JSON)
+
+Options for bench:
+    --iterations <n>      How many times to render the page (default 20)
+    --trace <file>        Also write each stage's timings as a Chrome trace (JSON), for
+                          chrome://tracing, Perfetto, or speedscope
+    (With the `tracing` feature, bench also prints how much work each stage did.)
+
+Options for dump-*:
+    --json                Print JSON instead of an indented tree
+
+Options for devtools:
+    --port <port>         Port to listen on, on 127.0.0.1 (default 9222)
+
+Options for wpt:
+    --list <file>         Run only the tests listed in this file, one path per line,
+                          relative to the directory
+";
+
+/// Command-line options shared by every command.
+struct Options {
+    input: String,
+    stylesheets: Vec<String>,
+    viewport: (usize, usize),
+    scale: f32,
+    media: css::Media,
+    format: Option<Format>,
+    wait: Duration,
+    output: String,
+    dump_format: DumpFormat,
+    port: u16,
+    iterations: usize,
+    trace: Option<String>,
+    list: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    Png,
+    Ppm,
+}
 
 fn main() {
-    println!("Hello, world!");
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command: fn(&Options) -> Result<(), String> = match args.first().map(String::as_str) {
+        Some("render") => render,
+        Some("watch") => watch,
+        Some("bench") => bench,
+        Some("dump-dom") => dump_dom,
+        Some("dump-style") => dump_style,
+        Some("dump-layout") => dump_layout,
+        Some("dump-display-list") => dump_display_list,
+        Some("report") => report,
+        Some("view") => view,
+        Some("devtools") => devtools,
+        Some("wpt") => run_wpt,
+        Some("-h") | Some("--help") => {
+            print!("{}", USAGE);
+            return;
+        }
+        Some(other) => fail(&format!("unknown command {:?}", other)),
+        None => fail("missing command"),
+    };
+    if let Err(message) = parse_options(&args[1..]).and_then(|options: Options| command(&options)) {
+        fail(&message);
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("browser-engine: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+
+fn parse_options(args: &[String]) -> Result<Options, String> {
+    let mut options: Options = Options {
+        input: String::new(),
+        stylesheets: Vec::new(),
+        viewport: (800, 600),
+        scale: 1.0,
+        media: css::Media::Screen,
+        format: None,
+        wait: Duration::from_millis(2000),
+        output: "out.png".to_string(),
+        dump_format: DumpFormat::Pretty,
+        port: 9222,
+        iterations: 20,
+        trace: None,
+        list: None,
+    };
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| -> Result<String, String> {
+            args.next().cloned().ok_or_else(|| format!("{} needs a value", name))
+        };
+        match arg.as_str() {
+            "--css" => options.stylesheets.push(value(arg)?),
+            "--size" | "--viewport" => options.viewport = parse_size(&value(arg)?)?,
+            "--scale" => {
+                options.scale = value(arg)?.parse().ok()
+                    .filter(|scale: &f32| (ZOOM_RANGE.0..=ZOOM_RANGE.1).contains(scale))
+                    .ok_or_else(|| format!("--scale must be a number from {} to {}", ZOOM_RANGE.0, ZOOM_RANGE.1))?;
+            }
+            "--media" => options.media = parse_media(&value(arg)?)?,
+            "--format" => options.format = Some(parse_format(&value(arg)?)?),
+            "--wait" => {
+                let ms: u64 = value(arg)?.parse().map_err(|_| "--wait must be a number of milliseconds")?;
+                options.wait = Duration::from_millis(ms);
+            }
+            "-o" | "--output" => options.output = value(arg)?,
+            "--json" => options.dump_format = DumpFormat::Json,
+            "--iterations" => {
+                options.iterations = value(arg)?.parse().ok().filter(|&n: &usize| n > 0)
+                    .ok_or("--iterations must be a positive number")?;
+            }
+            "--trace" => options.trace = Some(value(arg)?),
+            "--list" => options.list = Some(value(arg)?),
+            "--port" => options.port = value(arg)?.parse().map_err(|_| "--port must be a port number")?,
+            "-h" | "--help" => {
+                print!("{}", USAGE);
+                process::exit(0);
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown option {:?}", flag)),
+            input if options.input.is_empty() => options.input = input.to_string(),
+            extra => return Err(format!("unexpected argument {:?}", extra)),
+        }
+    }
+
+    if options.input.is_empty() {
+        return Err("missing input page".to_string());
+    }
+    Ok(options)
+}
+
+/// Parse a size like `800x600`.
+fn parse_size(size: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid size {:?} (expected WIDTHxHEIGHT)", size);
+    let (width, height) = size.split_once(['x', 'X']).ok_or_else(invalid)?;
+    Ok((width.parse().map_err(|_| invalid())?, height.parse().map_err(|_| invalid())?))
+}
+
+fn parse_format(format: &str) -> Result<Format, String> {
+    match format.to_ascii_lowercase().as_str() {
+        "png" => Ok(Format::Png),
+        "ppm" => Ok(Format::Ppm),
+        _ => Err(format!("unsupported format {:?} (expected png or ppm)", format)),
+    }
+}
+
+fn parse_media(media: &str) -> Result<css::Media, String> {
+    match media.to_ascii_lowercase().as_str() {
+        "screen" => Ok(css::Media::Screen),
+        "print" => Ok(css::Media::Print),
+        _ => Err(format!("unsupported media {:?} (expected screen or print)", media)),
+    }
+}
+
+/// Load the input page with the given stylesheets and viewport, and wait for its images.
+fn load_page(options: &Options) -> Result<Page, String> {
+    let mut css_source: String = read_stylesheets(options)?;
+
+    let mut page: Page = if options.input.contains("://") {
+        let mut page: Page = Page::load_url(&options.input).map_err(|err| err.to_string())?;
+        if !css_source.is_empty() {
+            css_source.insert_str(0, &page.stylesheet().to_string());
+            page.set_stylesheet(css::parse(&css_source).map_err(|err| err.to_string())?);
+        }
+        page
+    } else {
+        let html: String = fs::read_to_string(&options.input)
+            .map_err(|err| format!("can't read {}: {}", options.input, err))?;
+        Page::load(&html, &css_source).map_err(|err| format!("{}: {}", options.input, err))?
+    };
+
+    // The viewport size is in CSS px; zooming makes the page (and the image) bigger.
+    let (width, height) = options.viewport;
+    page.set_zoom(options.scale);
+    page.set_media(options.media);
+    page.set_viewport((width as f32 * options.scale).round() as usize, (height as f32 * options.scale).round() as usize);
+    if !page.wait_for_images(options.wait) {
+        eprintln!("browser-engine: some images were still loading; rendering without them");
+    }
+    // Show what the page's scripts logged. It goes to stderr, so it can't get mixed up
+    // with a dump on stdout.
+    #[cfg(feature = "js")]
+    for message in page.console_messages() {
+        eprintln!("console.{}: {}", message.level, message.text);
+    }
+    Ok(page)
+}
+
+fn render(options: &Options) -> Result<(), String> {
+    let mut page: Page = load_page(options)?;
+    write_image(&mut page, options)?;
+    if let Some(ref path) = options.trace {
+        page.export_trace(path).map_err(|err| format!("can't write {}: {}", path, err))?;
+    }
+    Ok(())
+}
+
+/// Paint the page and write it to the output file.
+fn write_image(page: &mut Page, options: &Options) -> Result<(), String> {
+    let format: Format = options.format.unwrap_or_else(|| {
+        if options.output.to_ascii_lowercase().ends_with(".ppm") { Format::Ppm } else { Format::Png }
+    });
+    let bytes: Vec<u8> = match format {
+        Format::Png => page.paint().to_png(),
+        Format::Ppm => page.paint().to_ppm(),
+    };
+    fs::write(&options.output, bytes).map_err(|err| format!("can't write {}: {}", options.output, err))
+}
+
+
+/*
+    Watching
+
+    `watch` renders the page, then polls its files (the HTML, each `--css` stylesheet,
+    and any images loaded from local files) for changes. Only the file that changed is
+    parsed again:
+        - new HTML is parsed into a new document; the stylesheet is kept, unless the
+          document's `<style>` elements changed
+        - a new stylesheet is parsed, and the document is kept
+        - a new image is decoded, and everything else is kept
+    Then the page is laid out, painted, and written out again, and the time each step
+    took is printed. Stop it with Ctrl-C.
+ */
+
+/// How often to check the files for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A file the page was loaded from.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Resource {
+    Html(String),
+    Css(String),
+    Image(String),
+}
+
+impl Resource {
+    fn path(&self) -> &str {
+        match self {
+            Resource::Html(path) | Resource::Css(path) | Resource::Image(path) => path,
+        }
+    }
+}
+
+fn watch(options: &Options) -> Result<(), String> {
+    if options.input.contains("://") {
+        return Err("watch only works on local files".to_string());
+    }
+    let mut page: Page = load_page(options)?;
+    write_image(&mut page, options)?;
+    println!("wrote {}; watching for changes (Ctrl-C to stop)", options.output);
+
+    let mut css_source: String = read_stylesheets(options)?;
+    let mut inline_source: String = engine::inline_css(page.document());
+    let mut modified: HashMap<Resource, Option<SystemTime>> = HashMap::new();
+    for resource in watched_resources(options, page.document()) {
+        modified.insert(resource.clone(), modified_time(resource.path()));
+    }
+
+    loop {
+        thread::sleep(WATCH_INTERVAL);
+        let changed: Vec<Resource> = modified.iter_mut()
+            .filter_map(|(resource, time): (&Resource, &mut Option<SystemTime>)| {
+                let new_time: Option<SystemTime> = modified_time(resource.path());
+                if new_time == *time {
+                    return None;
+                }
+                *time = new_time;
+                Some(resource.clone())
+            })
+            .collect();
+
+        for resource in changed {
+            let start: Instant = Instant::now();
+            let mut steps: Vec<(&str, Duration)> = Vec::new();
+            let result: Result<(), String> = (|| {
+                match resource {
+                    Resource::Html(ref path) => {
+                        let source: String = fs::read_to_string(path).map_err(|err| format!("can't read {}: {}", path, err))?;
+                        let document: dom::Document = html::parse(&source).map_err(|err| format!("{}: {}", path, err))?;
+                        steps.push(("parse html", start.elapsed()));
+                        let new_inline_source: String = engine::inline_css(&document);
+                        page.set_document(document);
+                        if new_inline_source != inline_source {
+                            let step: Instant = Instant::now();
+                            inline_source = new_inline_source;
+                            page.set_stylesheet(css::parse(&format!("{}{}", css_source, inline_source)).map_err(|err| err.to_string())?);
+                            steps.push(("parse css", step.elapsed()));
+                        }
+                    }
+                    Resource::Css(_) => {
+                        css_source = read_stylesheets(options)?;
+                        page.set_stylesheet(css::parse(&format!("{}{}", css_source, inline_source)).map_err(|err| err.to_string())?);
+                        steps.push(("parse css", start.elapsed()));
+                    }
+                    Resource::Image(ref src) => {
+                        page.reload_image(src);
+                        page.wait_for_images(options.wait);
+                        steps.push(("decode image", start.elapsed()));
+                    }
+                }
+
+                let step: Instant = Instant::now();
+                page.layout();
+                steps.push(("style+layout", step.elapsed()));
+                let step: Instant = Instant::now();
+                page.paint();
+                steps.push(("paint", step.elapsed()));
+                let step: Instant = Instant::now();
+                write_image(&mut page, options)?;
+                steps.push(("write", step.elapsed()));
+                Ok(())
+            })();
+
+            match result {
+                Ok(()) => {
+                    let timings: Vec<String> = steps.iter()
+                        .map(|&(name, time): &(&str, Duration)| format!("{} {:.1} ms", name, time.as_secs_f64() * 1000.0))
+                        .collect();
+                    println!("{} changed: {}; total {:.1} ms", resource.path(), timings.join(", "), start.elapsed().as_secs_f64() * 1000.0);
+                }
+                Err(message) => eprintln!("browser-engine: {}", message),
+            }
+        }
+
+        // New HTML may use different images.
+        for resource in watched_resources(options, page.document()) {
+            modified.entry(resource.clone()).or_insert_with(|| modified_time(resource.path()));
+        }
+    }
+}
+
+/// Read and concatenate the `--css` stylesheets.
+fn read_stylesheets(options: &Options) -> Result<String, String> {
+    let mut css_source: String = String::new();
+    for path in &options.stylesheets {
+        css_source.push_str(&fs::read_to_string(path).map_err(|err| format!("can't read {}: {}", path, err))?);
+        css_source.push('\n');
+    }
+    Ok(css_source)
+}
+
+/// The files a page loaded from local files depends on.
+fn watched_resources(options: &Options, document: &dom::Document) -> Vec<Resource> {
+    let mut resources: Vec<Resource> = vec![Resource::Html(options.input.clone())];
+    resources.extend(options.stylesheets.iter().cloned().map(Resource::Css));
+    for img in document.get_elements_by_tag_name("img") {
+        if let dom::NodeType::Element(ref element) = document.tree[img].node_type {
+            if let Some(src) = element.attr("src").filter(|src: &&str| !src.contains("://")) {
+                resources.push(Resource::Image(src.to_string()));
+            }
+        }
+    }
+    resources
+}
+
+fn modified_time(path: &str) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata: fs::Metadata| metadata.modified()).ok()
+}
+
+/*
+    Benchmarking
+
+    `bench` runs every stage of the pipeline on the page `--iterations` times and prints
+    how long each stage took: the fastest run, the median, the 90th and 99th percentiles,
+    and the slowest. The page's files are read, and its images loaded, once up front, so
+    only the engine's own work is timed. One extra run before the timed ones warms up the
+    caches (and the atom table) so the first iteration isn't an outlier.
+
+    With `--trace`, each stage of each run is also written as a "complete" event in the
+    Chrome trace format, which chrome://tracing, Perfetto, and speedscope can display as a
+    timeline or a flamegraph:
+        { "traceEvents": [{ "name": "layout", "ph": "X", "ts": us, "dur": us,
+                            "pid": 1, "tid": 1, "args": { "iteration": int } }, ...] }
+ */
+
+/// The stages `bench` times, in pipeline order.
+const BENCH_STAGES: [&str; 5] = ["parse html", "parse css", "style", "layout", "paint"];
+
+/// One timed stage of one run.
+struct Span {
+    stage: usize, // index into BENCH_STAGES
+    iteration: usize,
+    start: Duration, // since the benchmark started
+    duration: Duration,
+}
+
+fn bench(options: &Options) -> Result<(), String> {
+    if options.input.contains("://") {
+        return Err("bench only works on local files".to_string());
+    }
+    let page: Page = load_page(options)?;
+    let html_source: String = fs::read_to_string(&options.input)
+        .map_err(|err| format!("can't read {}: {}", options.input, err))?;
+    let css_source: String = read_stylesheets(options)?;
+    let (width, height) = options.viewport;
+    let mut viewport: layout::Dimensions = Default::default();
+    (viewport.content.width, viewport.content.height) = (width as f32, height as f32);
+    let bounds: layout::Rect = layout::Rect {
+        x: 0.0,
+        y: 0.0,
+        width: (width as f32 * options.scale).round(),
+        height: (height as f32 * options.scale).round(),
+    };
+    #[cfg(feature = "tracing")]
+    let recorder: trace::Recorder = Default::default();
+    #[cfg(feature = "tracing")]
+    let _guard = tracing::subscriber::set_default(recorder.clone());
+
+    let epoch: Instant = Instant::now();
+    let mut spans: Vec<Span> = Vec::new();
+    for iteration in 0..=options.iterations {
+        let mut times: Vec<(Instant, Duration)> = Vec::new();
+        let mut time = |start: Instant| times.push((start, start.elapsed()));
+
+        let start: Instant = Instant::now();
+        let document: dom::Document = html::parse(&html_source).map_err(|err| err.to_string())?;
+        time(start);
+        let start: Instant = Instant::now();
+        let stylesheet: css::Stylesheet = css::parse(&format!("{}{}", css_source, engine::inline_css(&document)))
+            .map_err(|err| err.to_string())?;
+        time(start);
+        let start: Instant = Instant::now();
+        let style_root: style::StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+        time(start);
+        let start: Instant = Instant::now();
+        let arena: layout::Bump = layout::Bump::new();
+        let layout_root: layout::LayoutBox = layout::layout_tree(&style_root, viewport, page.image_cache(), &page.fonts(), &arena)
+            .map_err(|err| err.to_string())?;
+        time(start);
+        let start: Instant = Instant::now();
+        let mut display_list: painting::DisplayList = painting::build_display_list(&layout_root);
+        if options.scale != 1.0 {
+            display_list = painting::scale_display_list(&display_list, options.scale);
+        }
+        black_box(painting::rasterize(&display_list, bounds));
+        time(start);
+
+        if iteration > 0 {
+            for (stage, (start, duration)) in times.into_iter().enumerate() {
+                spans.push(Span { stage, iteration, start: start - epoch, duration });
+            }
+        }
+    }
+
+    println!("{} iterations of {} ({} bytes)", options.iterations, options.input, html_source.len());
+    println!("{:<12} {:>9} {:>9} {:>9} {:>9} {:>9}", "stage (ms)", "min", "p50", "p90", "p99", "max");
+    let mut totals: Vec<Duration> = vec![Duration::ZERO; options.iterations];
+    for (stage, name) in BENCH_STAGES.iter().enumerate() {
+        let mut durations: Vec<Duration> = spans.iter()
+            .filter(|span: &&Span| span.stage == stage)
+            .map(|span: &Span| span.duration)
+            .collect();
+        for (total, duration) in totals.iter_mut().zip(&durations) {
+            *total += *duration;
+        }
+        print_percentiles(name, &mut durations);
+    }
+    print_percentiles("total", &mut totals);
+    #[cfg(feature = "tracing")]
+    print_counts(&recorder.spans());
+
+    if let Some(ref path) = options.trace {
+        let events: Vec<trace::TraceEvent> = spans.iter()
+            .map(|span: &Span| trace::TraceEvent {
+                name: BENCH_STAGES[span.stage].to_string(),
+                category: "bench".to_string(),
+                start: span.start,
+                duration: span.duration,
+                args: vec![("iteration".to_string(), span.iteration as u64)],
+            })
+            .collect();
+        fs::write(path, trace::chrome_trace(&events)).map_err(|err| format!("can't write {}: {}", path, err))?;
+        println!("wrote {}", path);
+    }
+    Ok(())
+}
+
+/// Print one row of the `bench` table.
+fn print_percentiles(name: &str, durations: &mut [Duration]) {
+    durations.sort();
+    let ms = |p: f64| -> f64 {
+        // The nearest-rank percentile.
+        let rank: usize = ((p / 100.0 * durations.len() as f64).ceil() as usize).max(1);
+        durations[rank - 1].as_secs_f64() * 1000.0
+    };
+    println!("{:<12} {:>9.3} {:>9.3} {:>9.3} {:>9.3} {:>9.3}", name, ms(0.0), ms(50.0), ms(90.0), ms(99.0), ms(100.0));
+}
+
+/// Print the counts recorded on the last span of each stage, e.g. how many boxes were laid out.
+#[cfg(feature = "tracing")]
+fn print_counts(spans: &[trace::RecordedSpan]) {
+    let mut names: Vec<&str> = Vec::new();
+    let mut counts: HashMap<&str, String> = HashMap::new();
+    for span in spans {
+        if !names.contains(&span.name) {
+            names.push(span.name);
+        }
+        let mut fields: Vec<String> = span.counts.iter().map(|(field, count): (&&str, &u64)| format!("{}={}", field, count)).collect();
+        fields.sort();
+        counts.insert(span.name, fields.join(" "));
+    }
+    println!("\nwork per run:");
+    for name in names {
+        println!("{:<20} {}", name, counts[name]);
+    }
+}
+
+fn report(options: &Options) -> Result<(), String> {
+    let page: Page = load_page(options)?;
+    // `-o` defaults to an image's name; a report is HTML.
+    let output: &str = if options.output == "out.png" { "report.html" } else { &options.output };
+    page.debug_report(output).map_err(|err| format!("can't write {}: {}", output, err))?;
+    println!("wrote {}", output);
+    Ok(())
+}
+
+fn dump_dom(options: &Options) -> Result<(), String> {
+    let page: Page = load_page(options)?;
+    let document = page.document();
+    print_dump(debug::dump_dom(&document.tree, document.root_element, options.dump_format));
+    Ok(())
+}
+
+fn dump_style(options: &Options) -> Result<(), String> {
+    let page: Page = load_page(options)?;
+    let document = page.document();
+    let style_root: style::StyledNode = style::style_tree(&document.tree, document.root_element, page.stylesheet());
+    print_dump(debug::dump_style(&style_root, options.dump_format));
+    Ok(())
+}
+
+fn dump_layout(options: &Options) -> Result<(), String> {
+    let page: Page = load_page(options)?;
+    print_dump(page.with_layout_tree(|root| debug::dump_layout(root, options.dump_format)));
+    Ok(())
+}
+
+fn dump_display_list(options: &Options) -> Result<(), String> {
+    let mut page: Page = load_page(options)?;
+    print_dump(debug::dump_display_list(page.layout(), options.dump_format));
+    Ok(())
+}
+
+/// Print a dump, ending it with a newline if it doesn't already have one.
+fn print_dump(dump: String) {
+    if dump.ends_with('\n') { print!("{}", dump) } else { println!("{}", dump) }
+}
+
+#[cfg(feature = "viewer")]
+fn view(options: &Options) -> Result<(), String> {
+    let page: Page = load_page(options)?;
+    build_a_browser_engine_in_rust::viewer::run(page).map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "viewer"))]
+fn view(_: &Options) -> Result<(), String> {
+    Err("this binary was built without the `viewer` feature".to_string())
+}
+
+#[cfg(feature = "devtools")]
+fn devtools(options: &Options) -> Result<(), String> {
+    use build_a_browser_engine_in_rust::devtools::DevtoolsServer;
+
+    let mut page: Page = load_page(options)?;
+    let server: DevtoolsServer = DevtoolsServer::bind(("127.0.0.1", options.port))
+        .map_err(|err| format!("can't listen on port {}: {}", options.port, err))?;
+    println!("devtools listening on http://{} (Ctrl-C to stop)", server.local_addr().map_err(|err| err.to_string())?);
+    server.serve(&mut page).map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "devtools"))]
+fn devtools(_: &Options) -> Result<(), String> {
+    Err("this binary was built without the `devtools` feature".to_string())
+}
+
+fn run_wpt(options: &Options) -> Result<(), String> {
+    let root: &Path = Path::new(&options.input);
+    let tests: Vec<PathBuf> = match options.list {
+        Some(ref list) => wpt::load_list(Path::new(list), root)?,
+        None => wpt::find_tests(root)?,
+    };
+    let mut summary: Summary = Default::default();
+    for test in &tests {
+        let outcome: Outcome = wpt::run_test(test);
+        let name: &Path = test.strip_prefix(root).unwrap_or(test);
+        match outcome {
+            Outcome::Pass => println!("pass  {}", name.display()),
+            Outcome::Fail(ref reason) => println!("fail  {}: {}", name.display(), reason),
+            Outcome::Error(ref reason) => println!("error {}: {}", name.display(), reason),
+            Outcome::Skip => println!("skip  {}", name.display()),
+        }
+        summary.add(&outcome);
+    }
+    println!("{}", summary);
+    Ok(())
 }
@@ -0,0 +1,55 @@
+//! Floating point functions that `core` doesn't have.
+
+
+/*
+    Without `std`, `f32` has no `floor`, `round`, or `sqrt`: they call into the C math
+    library, which a bare-metal target may not have. The few places the core modules need
+    them call these instead, which use the standard methods when `std` is there and small
+    versions of their own when it isn't.
+ */
+
+/// The largest integer less than or equal to `x`.
+pub fn floor(x: f32) -> f32 {
+    #[cfg(feature = "std")]
+    return x.floor();
+    #[cfg(not(feature = "std"))]
+    {
+        // Beyond 2^23 every f32 is already an integer.
+        if x.is_nan() || x.abs() >= 8_388_608.0 {
+            return x;
+        }
+        let truncated: f32 = x as i32 as f32;
+        if truncated > x { truncated - 1.0 } else { truncated }
+    }
+}
+
+/// The nearest integer to `x`, rounding half-way cases away from zero.
+pub fn round(x: f32) -> f32 {
+    #[cfg(feature = "std")]
+    return x.round();
+    #[cfg(not(feature = "std"))]
+    {
+        if x < 0.0 { -floor(-x + 0.5) } else { floor(x + 0.5) }
+    }
+}
+
+/// The square root of `x`, or NaN if `x` is negative.
+pub fn sqrt(x: f32) -> f32 {
+    #[cfg(feature = "std")]
+    return x.sqrt();
+    #[cfg(not(feature = "std"))]
+    {
+        if x < 0.0 {
+            return f32::NAN;
+        }
+        if x == 0.0 || x == f32::INFINITY {
+            return x;
+        }
+        // Halve the exponent for a first guess, then refine it with Newton's method.
+        let mut guess: f32 = f32::from_bits((x.to_bits() >> 1) + 0x1fc0_0000);
+        for _ in 0..4 {
+            guess = 0.5 * (guess + x / guess);
+        }
+        guess
+    }
+}
@@ -0,0 +1,231 @@
+//! Estimating how much memory a page uses.
+
+use crate::atom::Atom;
+use crate::{css, dom, find, layout, painting, style};
+use std::collections::HashMap;
+use std::fmt;
+use std::mem::size_of;
+
+
+/*
+    Memory Reports
+
+    An embedder on a small device needs to know where a page's memory goes, to decide when
+    to drop caches, discard background tabs, or refuse a page that is too big.
+    `Page::memory_report` adds up the heap memory owned by each part of the page:
+
+        dom            the DOM arena, its text and attributes, and its lookup indexes
+        stylesheet     the parsed rules
+        style_tree     the style tree, built for each layout and then dropped
+        layout_tree    the layout tree, ditto
+        display_list   the cached display list, hit regions, and find-in-page matches
+        canvas         the cached pixels
+        images         the decoded images in the image cache
+        frames         everything above, for the pages in `<iframe>`s
+
+    The style and layout trees aren't kept between layouts, so their numbers are what a
+    layout of the current document allocates while it runs: the peak to budget for, not
+    what is held now.
+
+    These are estimates. Each container counts its capacity times the size of its items
+    (plus a byte per slot for hash maps), and everything its items own in turn. The
+    allocator's own overhead isn't counted, and neither is anything shared through an
+    `Arc` (decoded images are counted once, in the cache), nor the JavaScript heap.
+
+    e.g.
+        let report: MemoryReport = page.memory_report();
+        if report.total() > 64 << 20 {
+            eprintln!("the page is using too much memory:\n{}", report);
+        }
+ */
+
+/// An estimate of the heap memory a value owns, in bytes, not counting the value itself.
+pub trait HeapSize {
+    fn heap_size(&self) -> usize;
+}
+
+/// Implement `HeapSize` for types that own nothing on the heap.
+macro_rules! no_heap {
+    ($($t:ty),*) => {
+        $(impl HeapSize for $t {
+            fn heap_size(&self) -> usize {
+                0
+            }
+        })*
+    };
+}
+
+no_heap!(u8, u32, usize, f32, Atom, dom::NodeId, css::Color, css::PseudoClass, layout::Rect);
+
+impl HeapSize for String {
+    fn heap_size(&self) -> usize {
+        self.capacity()
+    }
+}
+
+impl<T: HeapSize> HeapSize for Option<T> {
+    fn heap_size(&self) -> usize {
+        self.as_ref().map_or(0, |value: &T| value.heap_size())
+    }
+}
+
+impl<T: HeapSize> HeapSize for Vec<T> {
+    fn heap_size(&self) -> usize {
+        self.capacity() * size_of::<T>() + self.iter().map(|item: &T| item.heap_size()).sum::<usize>()
+    }
+}
+
+impl<K: HeapSize, V: HeapSize, S> HeapSize for HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        // Each slot holds a key and value, plus a control byte.
+        let table: usize = self.capacity() * (size_of::<(K, V)>() + 1);
+        table + self.iter().map(|(key, value): (&K, &V)| key.heap_size() + value.heap_size()).sum::<usize>()
+    }
+}
+
+// With the `deterministic` feature, the engine's maps are `hashbrown`'s (see `collections`).
+#[cfg(feature = "deterministic")]
+impl<K: HeapSize, V: HeapSize, S> HeapSize for hashbrown::HashMap<K, V, S> {
+    fn heap_size(&self) -> usize {
+        let table: usize = self.capacity() * (size_of::<(K, V)>() + 1);
+        table + self.iter().map(|(key, value): (&K, &V)| key.heap_size() + value.heap_size()).sum::<usize>()
+    }
+}
+
+
+impl HeapSize for dom::Element {
+    fn heap_size(&self) -> usize {
+        self.attributes.heap_size()
+    }
+}
+
+impl HeapSize for dom::NodeType {
+    fn heap_size(&self) -> usize {
+        match self {
+            dom::NodeType::Element(element) => element.heap_size(),
+            dom::NodeType::Text(text) => text.heap_size(),
+            dom::NodeType::DocumentFragment => 0,
+        }
+    }
+}
+
+impl HeapSize for dom::Node {
+    fn heap_size(&self) -> usize {
+        self.node_type.heap_size()
+    }
+}
+
+impl HeapSize for css::Value {
+    fn heap_size(&self) -> usize {
+        match self {
+            css::Value::StringValue(string) => string.heap_size(),
+            css::Value::List(values) | css::Value::Sequence(values) | css::Value::Function(_, values) => values.heap_size(),
+            css::Value::Keyword(_) | css::Value::Length(..) | css::Value::Number(_) | css::Value::ColorValue(_) => 0,
+        }
+    }
+}
+
+impl HeapSize for css::Declaration {
+    fn heap_size(&self) -> usize {
+        self.value.heap_size()
+    }
+}
+
+impl HeapSize for css::Selector {
+    fn heap_size(&self) -> usize {
+        match self {
+            css::Selector::Simple(simple) => simple.class.heap_size() + simple.pseudo_classes.heap_size(),
+        }
+    }
+}
+
+impl HeapSize for css::Rule {
+    fn heap_size(&self) -> usize {
+        self.selectors.heap_size() + self.declarations.heap_size() + self.media.heap_size()
+    }
+}
+
+impl HeapSize for css::Stylesheet {
+    fn heap_size(&self) -> usize {
+        self.rules.heap_size()
+    }
+}
+
+impl HeapSize for style::StyledNode<'_> {
+    fn heap_size(&self) -> usize {
+        self.specified_values.heap_size() + self.children.heap_size()
+    }
+}
+
+impl HeapSize for layout::LayoutBox<'_> {
+    fn heap_size(&self) -> usize {
+        // The children are in the layout tree's arena, but still take up room in it.
+        let children: usize = self.children.len() * size_of::<layout::LayoutBox>();
+        children + self.children.iter().map(|child: &layout::LayoutBox| child.heap_size()).sum::<usize>()
+    }
+}
+
+impl HeapSize for layout::HitRegion {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for find::Match {
+    fn heap_size(&self) -> usize {
+        0
+    }
+}
+
+impl HeapSize for painting::DisplayCommand {
+    fn heap_size(&self) -> usize {
+        0 // images are shared with the image cache
+    }
+}
+
+impl HeapSize for painting::Canvas {
+    fn heap_size(&self) -> usize {
+        self.pixels.heap_size()
+    }
+}
+
+
+/// Where a page's memory goes, in bytes. See `Page::memory_report`.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct MemoryReport {
+    pub dom: usize,
+    pub stylesheet: usize,
+    pub style_tree: usize,   // while laying out
+    pub layout_tree: usize,  // while laying out
+    pub display_list: usize, // including hit regions and find-in-page matches
+    pub canvas: usize,
+    pub images: usize,
+    pub frames: usize,       // the totals of the pages in `<iframe>`s
+}
+
+impl MemoryReport {
+    /// The sum of every part.
+    pub fn total(&self) -> usize {
+        self.dom + self.stylesheet + self.style_tree + self.layout_tree + self.display_list + self.canvas + self.images + self.frames
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let rows: [(&str, usize); 9] = [
+            ("dom", self.dom),
+            ("stylesheet", self.stylesheet),
+            ("style tree", self.style_tree),
+            ("layout tree", self.layout_tree),
+            ("display list", self.display_list),
+            ("canvas", self.canvas),
+            ("images", self.images),
+            ("frames", self.frames),
+            ("total", self.total()),
+        ];
+        for (name, bytes) in rows {
+            writeln!(f, "{:<14} {:>10.1} KiB", name, bytes as f64 / 1024.0)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,525 @@
+//! A minimal HTTP client for fetching pages.
+//!
+//! Supports plain `http://` URLs, a cookie jar, and per-origin request headers.
+//!
+//! Not yet supported:
+//!     - TLS (`https://`)
+//!     - Redirects
+//!     - Keep-alive connections
+//!     - Cookie expiry dates (only `Max-Age=0` deletion is honored)
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+
+
+/*
+    Fetching Pages
+
+    So far the engine has only rendered HTML and CSS that were handed to it as strings.
+    To load a real page we need to speak a little bit of [HTTP](https://www.rfc-editor.org/rfc/rfc9112).
+    Many demo pages also sit behind a login, which means the server hands us a cookie
+    with `Set-Cookie`, and expects it back on every following request to the same origin.
+
+    The client below keeps three pieces of state:
+        - a cookie jar, filled from `Set-Cookie` response headers
+        - default headers (User-Agent, Accept-Language, ...) sent with every request
+        - extra headers for a specific origin (e.g. an `Authorization` header)
+ */
+
+/// Default `User-Agent` sent by the client.
+pub const DEFAULT_USER_AGENT: &str = "build-a-browser-engine-in-rust/0.1";
+
+/// Default `Accept-Language` sent by the client.
+pub const DEFAULT_ACCEPT_LANGUAGE: &str = "en-US,en;q=0.9";
+
+
+/// Errors that can happen while fetching a resource.
+#[derive(Debug)]
+pub enum LoadError {
+    InvalidUrl(String),
+    UnsupportedScheme(String),
+    Io(io::Error),
+    InvalidResponse(String),
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::InvalidUrl(url) => write!(f, "invalid URL {:?}", url),
+            LoadError::UnsupportedScheme(scheme) => write!(f, "unsupported URL scheme {:?}", scheme),
+            LoadError::Io(err) => write!(f, "I/O error: {}", err),
+            LoadError::InvalidResponse(reason) => write!(f, "invalid HTTP response: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<io::Error> for LoadError {
+    fn from(err: io::Error) -> LoadError {
+        LoadError::Io(err)
+    }
+}
+
+
+/// A parsed absolute URL.
+/*
+    e.g.
+        "http://example.com:8080/login?next=/#form"
+        Url {
+            scheme: "http",
+            host: "example.com",
+            port: 8080,
+            path: "/login?next=/",
+            fragment: Some("form"),
+        }
+ */
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Url {
+    pub scheme: String,
+    pub host: String,
+    pub port: u16,
+    pub path: String, // path plus query, always starts with '/'
+    pub fragment: Option<String>, // never sent to the server
+}
+
+/// The (scheme, host, port) triple that cookies and per-origin headers are keyed on.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Origin {
+    pub scheme: String,
+    pub host: String,
+    pub port: u16,
+}
+
+impl Url {
+    /// Parse an absolute URL like `http://host[:port][/path]`.
+    pub fn parse(input: &str) -> Result<Url, LoadError> {
+        let (scheme, rest) = match input.split_once("://") {
+            Some(parts) => parts,
+            // `mailto:`, `data:`, and the like are URLs too, just not ones with a host.
+            None => return Err(match scheme(input) {
+                Some(scheme) => LoadError::UnsupportedScheme(scheme.to_ascii_lowercase()),
+                None => LoadError::InvalidUrl(input.to_string()),
+            }),
+        };
+        let scheme: String = scheme.to_ascii_lowercase();
+
+        let (rest, fragment) = match rest.split_once('#') {
+            Some((rest, fragment)) => (rest, Some(fragment.to_string())),
+            None => (rest, None),
+        };
+
+        let (authority, path) = match rest.find(['/', '?']) {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, "/"),
+        };
+        let path: String = if path.starts_with('?') { format!("/{}", path) } else { path.to_string() };
+
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port: u16 = port.parse().map_err(|_| LoadError::InvalidUrl(input.to_string()))?;
+                (host, port)
+            }
+            None => (authority, default_port(&scheme)),
+        };
+        if host.is_empty() {
+            return Err(LoadError::InvalidUrl(input.to_string()));
+        }
+
+        Ok(Url { scheme, host: host.to_ascii_lowercase(), port, path, fragment })
+    }
+
+    /// The origin this URL belongs to.
+    pub fn origin(&self) -> Origin {
+        Origin { scheme: self.scheme.clone(), host: self.host.clone(), port: self.port }
+    }
+
+    /// The host, and the port if it isn't the scheme's default: `example.com:8080`. This
+    /// is what the `Host` header holds.
+    pub fn authority(&self) -> String {
+        if self.port == default_port(&self.scheme) {
+            self.host.clone()
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+
+    /// Resolve a link found on this page (absolute, `//host/...`, `/path`, relative, or only
+    /// a `?query` or `#fragment`), as RFC 3986 does.
+    pub fn join(&self, reference: &str) -> Result<Url, LoadError> {
+        // A reference with a scheme is absolute, even one we can't load (`mailto:`).
+        if scheme(reference).is_some() {
+            return Url::parse(reference);
+        }
+        // The fragment comes from the reference alone: `#intro` keeps this URL's path and
+        // query, and anything else drops this URL's fragment.
+        let (reference, fragment) = match reference.split_once('#') {
+            Some((reference, fragment)) => (reference, Some(fragment.to_string())),
+            None => (reference, None),
+        };
+        let mut url: Url = self.join_path(reference)?;
+        url.fragment = fragment;
+        Ok(url)
+    }
+
+    /// `join` for a relative reference without a fragment.
+    fn join_path(&self, reference: &str) -> Result<Url, LoadError> {
+        if reference.is_empty() {
+            return Ok(Url { fragment: None, ..self.clone() });
+        }
+        if let Some(rest) = reference.strip_prefix("//") {
+            return Url::parse(&format!("{}://{}", self.scheme, rest));
+        }
+        let authority: String = format!("{}://{}:{}", self.scheme, self.host, self.port);
+        if reference.starts_with('/') {
+            return Url::parse(&format!("{}{}", authority, reference));
+        }
+        if reference.starts_with('?') {
+            let path: &str = self.path.split('?').next().unwrap_or("/");
+            return Url::parse(&format!("{}{}{}", authority, path, reference));
+        }
+
+        // Relative to the "directory" of the current path.
+        let path: &str = self.path.split('?').next().unwrap_or("/");
+        let directory: &str = &path[..path.rfind('/').map_or(0, |i: usize| i + 1)];
+        let mut segments: Vec<&str> = directory.split('/').filter(|s: &&str| !s.is_empty()).collect();
+        let (reference_path, query) = match reference.find('?') {
+            Some(i) => (&reference[..i], &reference[i..]),
+            None => (reference, ""),
+        };
+        let mut trailing_slash: bool = reference_path.is_empty();
+        for segment in reference_path.split('/') {
+            trailing_slash = matches!(segment, "" | "." | "..");
+            match segment {
+                "" | "." => {}
+                ".." => { segments.pop(); }
+                segment => segments.push(segment),
+            }
+        }
+        let mut joined: String = format!("/{}", segments.join("/"));
+        if trailing_slash && !segments.is_empty() {
+            joined.push('/');
+        }
+        Url::parse(&format!("{}{}{}", authority, joined, query))
+    }
+}
+
+impl fmt::Display for Url {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}://{}{}", self.scheme, self.authority(), self.path)?;
+        match self.fragment {
+            Some(ref fragment) => write!(f, "#{}", fragment),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The scheme `url` starts with (`http`, `mailto`, ...), if it has one. A URL with a
+/// scheme is absolute; one without is relative to some base URL.
+pub fn scheme(url: &str) -> Option<&str> {
+    let (scheme, _) = url.split_once(':')?;
+    let mut chars = scheme.chars();
+    let valid: bool = chars.next().is_some_and(|c: char| c.is_ascii_alphabetic())
+        && chars.all(|c: char| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    valid.then_some(scheme)
+}
+
+/// The port used when a URL doesn't name one.
+fn default_port(scheme: &str) -> u16 {
+    match scheme {
+        "https" => 443,
+        _ => 80,
+    }
+}
+
+
+/*
+    The Cookie Jar
+
+    A `Set-Cookie` header looks like this:
+        Set-Cookie: session=abc123; Path=/; HttpOnly
+
+    The first pair is the cookie itself, and the rest are attributes. This jar only looks
+    at `Path` (to decide which requests get the cookie) and `Max-Age=0` (which deletes it).
+    Cookies are stored per origin, so a cookie set by one server is never sent to another.
+ */
+
+/// A single stored cookie.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub path: String,
+}
+
+/// Cookies received from servers, keyed by origin.
+#[derive(Default)]
+pub struct CookieJar {
+    cookies: HashMap<Origin, Vec<Cookie>>,
+}
+
+impl CookieJar {
+    /// Create an empty cookie jar.
+    pub fn new() -> CookieJar {
+        Default::default()
+    }
+
+    /// Store (or replace, or delete) a cookie from a `Set-Cookie` header value.
+    pub fn set_from_header(&mut self, url: &Url, header: &str) {
+        let mut parts = header.split(';');
+        let (name, value) = match parts.next().and_then(|pair: &str| pair.split_once('=')) {
+            Some((name, value)) => (name.trim().to_string(), value.trim().to_string()),
+            None => return, // ignore malformed cookies
+        };
+        if name.is_empty() {
+            return;
+        }
+
+        let mut path: String = default_cookie_path(&url.path);
+        let mut expired: bool = false;
+        for attribute in parts {
+            let (key, val) = attribute.split_once('=').unwrap_or((attribute, ""));
+            match &*key.trim().to_ascii_lowercase() {
+                "path" if val.trim().starts_with('/') => path = val.trim().to_string(),
+                "max-age" => expired = val.trim().parse::<i64>().map(|age: i64| age <= 0).unwrap_or(false),
+                _ => {} // Domain, Expires, Secure, HttpOnly, SameSite are ignored
+            }
+        }
+
+        let cookies: &mut Vec<Cookie> = self.cookies.entry(url.origin()).or_default();
+        cookies.retain(|c: &Cookie| !(c.name == name && c.path == path));
+        if !expired {
+            cookies.push(Cookie { name, value, path });
+        }
+    }
+
+    /// Return the cookies that should be sent with a request to `url`.
+    pub fn cookies_for(&self, url: &Url) -> Vec<&Cookie> {
+        let request_path: &str = url.path.split('?').next().unwrap_or("/");
+        self.cookies.get(&url.origin())
+            .map(|cookies: &Vec<Cookie>| cookies.iter().filter(|c: &&Cookie| path_matches(request_path, &c.path)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Build the value of the `Cookie` request header for `url`, if any cookies apply.
+    pub fn header_for(&self, url: &Url) -> Option<String> {
+        let cookies: Vec<&Cookie> = self.cookies_for(url);
+        if cookies.is_empty() {
+            return None;
+        }
+        Some(cookies.iter().map(|c: &&Cookie| format!("{}={}", c.name, c.value)).collect::<Vec<String>>().join("; "))
+    }
+
+    /// Forget every cookie.
+    pub fn clear(&mut self) {
+        self.cookies.clear();
+    }
+}
+
+/// The default cookie path is the request path up to (not including) its last '/'.
+fn default_cookie_path(request_path: &str) -> String {
+    let path: &str = request_path.split('?').next().unwrap_or("/");
+    match path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+    }
+}
+
+/// Does a cookie with `cookie_path` apply to a request for `request_path`?
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+
+/*
+    The Client
+
+    The client owns the cookie jar and the header configuration, and performs requests.
+    Every request gets, in order: the default headers, the headers registered for its
+    origin (which can override defaults with the same name), and the `Cookie` header.
+ */
+
+/// A fetched resource.
+pub struct Response {
+    pub url: Url,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Return the first header named `name` (case-insensitive).
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The body decoded as UTF-8, replacing invalid sequences.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// An HTTP client with a cookie jar and configurable request headers.
+pub struct Client {
+    pub cookies: CookieJar,
+    default_headers: Vec<(String, String)>,
+    origin_headers: HashMap<Origin, Vec<(String, String)>>,
+}
+
+impl Default for Client {
+    fn default() -> Client {
+        Client::new()
+    }
+}
+
+impl Client {
+    /// Create a client with the default `User-Agent` and `Accept-Language` headers.
+    pub fn new() -> Client {
+        Client {
+            cookies: CookieJar::new(),
+            default_headers: vec![
+                ("User-Agent".to_string(), DEFAULT_USER_AGENT.to_string()),
+                ("Accept-Language".to_string(), DEFAULT_ACCEPT_LANGUAGE.to_string()),
+            ],
+            origin_headers: HashMap::new(),
+        }
+    }
+
+    /// Set a header sent with every request, replacing any previous value.
+    pub fn set_default_header(&mut self, name: &str, value: &str) {
+        set_header(&mut self.default_headers, name, value);
+    }
+
+    /// Stop sending a default header.
+    pub fn remove_default_header(&mut self, name: &str) {
+        self.default_headers.retain(|(key, _)| !key.eq_ignore_ascii_case(name));
+    }
+
+    /// Set a header sent only with requests to `origin`.
+    pub fn set_origin_header(&mut self, origin: &Origin, name: &str, value: &str) {
+        set_header(self.origin_headers.entry(origin.clone()).or_default(), name, value);
+    }
+
+    /// The headers that a request to `url` would carry, in the order they are sent.
+    pub fn request_headers(&self, url: &Url) -> Vec<(String, String)> {
+        let mut headers: Vec<(String, String)> = self.default_headers.clone();
+        if let Some(extra) = self.origin_headers.get(&url.origin()) {
+            for (name, value) in extra {
+                set_header(&mut headers, name, value);
+            }
+        }
+        if let Some(cookie) = self.cookies.header_for(url) {
+            set_header(&mut headers, "Cookie", &cookie);
+        }
+        headers
+    }
+
+    /// Fetch `url` with a GET request.
+    pub fn get(&mut self, url: &str) -> Result<Response, LoadError> {
+        self.request("GET", url, None)
+    }
+
+    /// Send a POST request with a form-encoded body (handy for demo login pages).
+    pub fn post_form(&mut self, url: &str, body: &str) -> Result<Response, LoadError> {
+        self.request("POST", url, Some(body.as_bytes()))
+    }
+
+    /// Send a request and store any cookies the server sets.
+    fn request(&mut self, method: &str, url: &str, body: Option<&[u8]>) -> Result<Response, LoadError> {
+        // The fragment is never sent to the server, so the response doesn't have one.
+        let url: Url = Url { fragment: None, ..Url::parse(url)? };
+        if url.scheme != "http" {
+            return Err(LoadError::UnsupportedScheme(url.scheme));
+        }
+
+        let mut request: String = format!("{} {} HTTP/1.1\r\n", method, url.path);
+        request.push_str(&format!("Host: {}\r\n", url.authority()));
+        for (name, value) in self.request_headers(&url) {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        if let Some(body) = body {
+            request.push_str("Content-Type: application/x-www-form-urlencoded\r\n");
+            request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+        }
+        request.push_str("Connection: close\r\n\r\n");
+
+        let mut stream: TcpStream = TcpStream::connect((url.host.as_str(), url.port))?;
+        stream.write_all(request.as_bytes())?;
+        if let Some(body) = body {
+            stream.write_all(body)?;
+        }
+        let mut raw: Vec<u8> = Vec::new();
+        stream.read_to_end(&mut raw)?;
+
+        let response: Response = parse_response(url, &raw)?;
+        for (name, value) in &response.headers {
+            if name.eq_ignore_ascii_case("Set-Cookie") {
+                self.cookies.set_from_header(&response.url, value);
+            }
+        }
+        Ok(response)
+    }
+}
+
+/// Replace the header named `name` in `headers`, or append it.
+fn set_header(headers: &mut Vec<(String, String)>, name: &str, value: &str) {
+    match headers.iter_mut().find(|(key, _)| key.eq_ignore_ascii_case(name)) {
+        Some(header) => header.1 = value.to_string(),
+        None => headers.push((name.to_string(), value.to_string())),
+    }
+}
+
+/// Split a raw HTTP/1.x response into status, headers and body.
+fn parse_response(url: Url, raw: &[u8]) -> Result<Response, LoadError> {
+    let split: usize = raw.windows(4).position(|w: &[u8]| w == b"\r\n\r\n")
+        .ok_or_else(|| LoadError::InvalidResponse("missing end of headers".to_string()))?;
+    let head: String = String::from_utf8_lossy(&raw[..split]).into_owned();
+    let mut lines = head.split("\r\n");
+
+    // e.g. "HTTP/1.1 200 OK"
+    let status_line: &str = lines.next().unwrap_or("");
+    let status: u16 = status_line.split_whitespace().nth(1)
+        .and_then(|code: &str| code.parse().ok())
+        .ok_or_else(|| LoadError::InvalidResponse(format!("bad status line {:?}", status_line)))?;
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line: &str| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_string(), value.trim().to_string()))
+        .collect();
+
+    let mut response = Response { url, status, headers, body: Vec::new() };
+    let body: &[u8] = &raw[split + 4..];
+    response.body = match response.header("Transfer-Encoding") {
+        Some(encoding) if encoding.eq_ignore_ascii_case("chunked") => decode_chunked(body)?,
+        _ => body.to_vec(),
+    };
+    Ok(response)
+}
+
+/// Decode a `Transfer-Encoding: chunked` body.
+fn decode_chunked(mut body: &[u8]) -> Result<Vec<u8>, LoadError> {
+    let mut result: Vec<u8> = Vec::new();
+    loop {
+        let line_end: usize = body.windows(2).position(|w: &[u8]| w == b"\r\n")
+            .ok_or_else(|| LoadError::InvalidResponse("truncated chunk".to_string()))?;
+        let size_line: String = String::from_utf8_lossy(&body[..line_end]).into_owned();
+        let size_hex: &str = size_line.split(';').next().unwrap_or("").trim();
+        let size: usize = usize::from_str_radix(size_hex, 16)
+            .map_err(|_| LoadError::InvalidResponse(format!("bad chunk size {:?}", size_hex)))?;
+        body = &body[line_end + 2..];
+        if size == 0 {
+            return Ok(result);
+        }
+        if body.len() < size {
+            return Err(LoadError::InvalidResponse("truncated chunk".to_string()));
+        }
+        result.extend_from_slice(&body[..size]);
+        body = body.get(size + 2..).unwrap_or(&[]);
+    }
+}
@@ -1,6 +1,10 @@
-use crate::{css, layout};
+use crate::trace::{stage, Stage};
+use crate::{css, fonts, forms, image, layout, math};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use alloc::sync::Arc;
 
-/**
+/*
  *  Painting 101
  *
  *  This code takes the tree of boxes from the layout module and turns them into an array
@@ -14,12 +18,9 @@ use crate::{css, layout};
  *
  *  Browser usually implement rasterization with the help of graphics APIs and libraries
  *  like Skia, Cairo, Direct2D, and so on. These APIs provide functions for painting
- *  polygons, lines, curves, gradients, and text. For now, I'm going to write my own
- *  rasterizer that can only paint one thing: rectangles.
- *
- *  Eventually I want to implement text rendering. At that point, I may throw away this
- *  toy painting code and switch to a "real" 2D graphics library. But for now, rectangles
- *  are sufficient to turn the output of my block layout algorithm into pictures.
+ *  polygons, lines, curves, gradients, and text. Instead, I'm going to write my own
+ *  rasterizer that paints rectangles, images, and text. The glyphs' outlines come from
+ *  the fonts (see `fonts`), which also turn them into pixels.
  */
 
 /**
@@ -35,41 +36,77 @@ use crate::{css, layout};
  *  modify and re-use the same display list to generate different types of output: for example,
  *  pixels for displaying on a screen, or vector graphics for sending to a printer.
  *
- *  The Engine's display list is a vector of DisplayCommands. For now there is only one
- *  type of DisplayCommand, a solid-color rectangle:
+ *  The Engine's display list is a vector of DisplayCommands: solid-color rectangles,
+ *  images, runs of text, and clips. A run of text's rectangle reaches from its font's
+ *  ascent down to its descent, so its baseline is the font's ascent below its top.
  */
-type DisplayList = Vec<DisplayCommand>;
+pub type DisplayList = Vec<DisplayCommand>;
 
-enum DisplayCommand {
-    SolidColor(css::Color, layout::Rect)
+#[derive(Clone, Debug)]
+pub enum DisplayCommand {
+    SolidColor(css::Color, layout::Rect),
+    Image(Arc<image::Image>, layout::Rect),
+    Text(fonts::TextRun, css::Color, layout::Rect),
+    PushClip(layout::Rect), // only draw inside this rect, until the matching PopClip
+    PopClip,
 }
 
 
-/**
+/*
  *  To build the display list, we walk through the layout tree and generate a series of
  *  commands for each box. First we draw the box's background, then we draw its borders
  *  and content on top of the background.
  */
 
-fn build_display_list(layout_root: &layout::LayoutBox) -> DisplayList {
+/// Walk a layout tree and list the drawing operations needed to paint it.
+pub fn build_display_list(layout_root: &layout::LayoutBox) -> DisplayList {
+    build_scrolled_display_list(layout_root, &layout::ScrollOffsets::default())
+}
+
+/// Like `build_display_list`, with the contents of scrolled boxes moved by their offsets.
+pub fn build_scrolled_display_list(layout_root: &layout::LayoutBox, offsets: &layout::ScrollOffsets) -> DisplayList {
+    let stage: Stage = stage!("painting::display", items);
     let mut list: Vec<DisplayCommand> = Vec::new();
-    render_layout_box(&mut list, layout_root);
+    render_layout_box(&mut list, layout_root, offsets, (0.0, 0.0), None);
+    stage.record("items", || list.len());
     list
 }
 
-fn render_layout_box(list: &mut DisplayList, layout_box: &layout::LayoutBox) {
+fn render_layout_box(list: &mut DisplayList, layout_box: &layout::LayoutBox, offsets: &layout::ScrollOffsets,
+                     translate: (f32, f32), clip: Option<layout::Rect>) {
+    let start: usize = list.len();
     render_background(list, layout_box);
     render_borders(list, layout_box);
+    render_image(list, layout_box);
+    render_control(list, layout_box);
+    render_text(list, layout_box);
 
-    // TODO: render text
+    // Boxes are rendered where layout put them; move them if an ancestor is scrolled.
+    if translate != (0.0, 0.0) {
+        for command in &mut list[start..] {
+            if let DisplayCommand::SolidColor(_, ref mut rect) | DisplayCommand::Image(_, ref mut rect)
+                | DisplayCommand::Text(_, _, ref mut rect) = *command {
+                *rect = rect.translated(translate.0, translate.1);
+            }
+        }
+    }
 
-    for child in &layout_box.children {
-        render_layout_box(list, child);
+    // Boxes with `overflow` other than `visible` clip (and maybe scroll) their children.
+    let (child_translate, child_clip) = layout_box.children_transform(offsets, translate, clip);
+    let clips: bool = child_clip != clip;
+    if clips {
+        list.push(DisplayCommand::PushClip(child_clip.unwrap()));
+    }
+    for child in layout_box.children.iter() {
+        render_layout_box(list, child, offsets, child_translate, child_clip);
+    }
+    if clips {
+        list.push(DisplayCommand::PopClip);
     }
 }
 
 
-/**
+/*
  *  By default, HTML elements are stacked in the order they appear: If two elements overlap,
  *  the later one is drawn on top of the earlier one. This is reflected in our display list,
  *  which will draw the elements in the same order they appear in the DOM tree. If this code
@@ -82,12 +119,11 @@ fn render_layout_box(list: &mut DisplayList, layout_box: &layout::LayoutBox) {
  */
 
 fn render_background(list: &mut DisplayList, layout_box: &layout::LayoutBox) {
-    get_color(layout_box, "background")
-        .map(
-            |color: css::Color| list.push(
-                DisplayCommand::SolidColor(color, layout_box.dimensions.border_box())
-            )
-        );
+    if let Some(color) = get_color(layout_box, "background") {
+        for d in layout_box.decoration_boxes() {
+            list.push(DisplayCommand::SolidColor(color, d.border_box()));
+        }
+    }
 }
 
 /// Return the specified color for CSS property `name`, or None if no color was specified.
@@ -105,14 +141,22 @@ fn get_color(layout_box: &layout::LayoutBox, name: &str) -> Option<css::Color> {
 /**
  *  The borders are similar, but instead of a single rectangle we draw four-one for
  *  each edge of the box.
+ *
+ *  An inline element that wraps onto several lines has a background and borders for
+ *  each line it's on (see "Inline Decorations" in `layout`).
  */
 fn render_borders(list: &mut DisplayList, layout_box: &layout::LayoutBox) {
     let color: css::Color = match get_color(layout_box, "border-color") {
         Some(color) => color,
         _ => return, // bail out if no border-color is specified
     };
+    for d in layout_box.decoration_boxes() {
+        render_border_edges(list, color, &d);
+    }
+}
 
-    let d: &layout::Dimensions = &layout_box.dimensions;
+/// Draw the four borders of a box with dimensions `d`.
+fn render_border_edges(list: &mut DisplayList, color: css::Color, d: &layout::Dimensions) {
     let border_box: layout::Rect = d.border_box();
 
     // Top border
@@ -127,7 +171,7 @@ fn render_borders(list: &mut DisplayList, layout_box: &layout::LayoutBox) {
     list.push(DisplayCommand::SolidColor(color, layout::Rect {
         x: border_box.x + border_box.width - d.border.right,
         y: border_box.y,
-        width: d.border.left,
+        width: d.border.right,
         height: border_box.height,
     }));
 
@@ -149,16 +193,50 @@ fn render_borders(list: &mut DisplayList, layout_box: &layout::LayoutBox) {
 }
 
 
+/**
+ *  Images are drawn into the content box. An image that hasn't loaded yet draws nothing,
+ *  but its placeholder box still takes up space in the layout.
+ */
+fn render_image(list: &mut DisplayList, layout_box: &layout::LayoutBox) {
+    if let Some(image) = layout_box.replaced.as_ref().and_then(|r: &layout::Replaced| r.image.clone()) {
+        list.push(DisplayCommand::Image(image, layout_box.dimensions.content));
+    }
+}
+
+
+/**
+ *  Form controls are drawn into the content box too, in a native look (see `forms`).
+ */
+fn render_control(list: &mut DisplayList, layout_box: &layout::LayoutBox) {
+    if let Some(control) = layout_box.replaced.as_ref().and_then(|r: &layout::Replaced| r.control) {
+        list.extend(forms::paint_control(control, layout_box.dimensions.content));
+    }
+}
+
+
+/**
+ *  Text is drawn last, on top of the box's background, as one command per line it's on.
+ */
+fn render_text(list: &mut DisplayList, layout_box: &layout::LayoutBox) {
+    for fragment in &layout_box.text {
+        if !fragment.run.glyphs.is_empty() {
+            list.push(DisplayCommand::Text(fragment.run.clone(), fragment.color, fragment.rect));
+        }
+    }
+}
+
+
 /**
  *  Rasterization
  *
  *  Now that we've built the display list, we need to turn it into pixels by executing
  *  each DisplayCommand. We'll store the pixels in a Canvas:
  */
-struct Canvas {
-    pixels: Vec<css::Color>,
-    width: usize,
-    height: usize,
+#[derive(Clone, Debug)]
+pub struct Canvas {
+    pub pixels: Vec<css::Color>,
+    pub width: usize,
+    pub height: usize,
 }
 
 impl Canvas {
@@ -177,28 +255,88 @@ impl Canvas {
      *  using a [helper method](https://doc.rust-lang.org/std/primitive.f32.html#method.clamp)
      *  to make sure we don't go outside the bounds of our canvas.
      *
-     *  Note that this code only works with opaque colors. If we added transparency (by
-     *  reading the opacity property, or adding support for `rgba()` values in the CSS
-     *  parser) then it would need to [blend](https://en.wikipedia.org/wiki/Alpha_compositing)
-     *  each new pixel with whatever it's drawn on top of.
+     *  Note that rectangles and images only work with opaque colors. If we added
+     *  transparency (by reading the opacity property, or adding support for `rgba()`
+     *  values in the CSS parser) then they would need to [blend](https://en.wikipedia.org/wiki/Alpha_compositing)
+     *  each new pixel with whatever it's drawn on top of, as text already does.
      */
-    fn paint_item(&mut self, item: &DisplayCommand) {
+    fn paint_item(&mut self, item: &DisplayCommand, origin: (f32, f32), clip: layout::Rect) {
         match item {
             &DisplayCommand::SolidColor(color, rect) => {
-                /// Clip the rectangle to the canvas boundaries.
-                let x0: usize = rect.x.clamp(0.0, self.width as f32) as usize;
-                let y0: usize = rect.y.clamp(0.0, self.height as f32) as usize;
-                let x1: usize = (rect.x + rect.width).clamp(0.0, self.width as f32) as usize;
-                let y1: usize = (rect.y + rect.height).clamp(0.0, self.height as f32) as usize;
-
-                for y in (y0..y1) {
-                    for x in (x0..x1) {
+                let rect: layout::Rect = rect.translated(-origin.0, -origin.1);
+                // Clip the rectangle to the canvas boundaries.
+                let x0: usize = rect.x.clamp(clip.x, clip.x + clip.width) as usize;
+                let y0: usize = rect.y.clamp(clip.y, clip.y + clip.height) as usize;
+                let x1: usize = (rect.x + rect.width).clamp(clip.x, clip.x + clip.width) as usize;
+                let y1: usize = (rect.y + rect.height).clamp(clip.y, clip.y + clip.height) as usize;
+
+                for y in y0..y1 {
+                    for x in x0..x1 {
                         // TODO: alpha compositing with existing pixel
                         self.pixels[x + y * self.width] = color;
                     }
                 }
             }
+            &DisplayCommand::Image(ref image, rect) => {
+                let rect: layout::Rect = rect.translated(-origin.0, -origin.1);
+                let x0: usize = rect.x.clamp(clip.x, clip.x + clip.width) as usize;
+                let y0: usize = rect.y.clamp(clip.y, clip.y + clip.height) as usize;
+                let x1: usize = (rect.x + rect.width).clamp(clip.x, clip.x + clip.width) as usize;
+                let y1: usize = (rect.y + rect.height).clamp(clip.y, clip.y + clip.height) as usize;
+
+                // Scale the image to the box using the nearest source pixel.
+                for y in y0..y1 {
+                    let src_y: usize = ((y as f32 - rect.y) * image.height as f32 / rect.height) as usize;
+                    for x in x0..x1 {
+                        let src_x: usize = ((x as f32 - rect.x) * image.width as f32 / rect.width) as usize;
+                        let src_index: usize = src_x.min(image.width as usize - 1)
+                            + src_y.min(image.height as usize - 1) * image.width as usize;
+                        let color: css::Color = image.pixels[src_index];
+                        // TODO: alpha compositing; for now only fully transparent pixels are skipped.
+                        if color.a > 0 {
+                            self.pixels[x + y * self.width] = color;
+                        }
+                    }
+                }
+            }
+            &DisplayCommand::Text(ref run, color, rect) => {
+                self.paint_text(run, color, rect.translated(-origin.0, -origin.1), clip);
+            }
+            // Clipping is tracked by `paint_region`.
+            DisplayCommand::PushClip(_) | DisplayCommand::PopClip => {}
+        }
+    }
+
+    /**
+     *  Glyphs have curved edges, so most of the pixels along an edge are only partly
+     *  covered. The font rasterizer says how much of each pixel a glyph covers, and the
+     *  text color is blended into the pixel in proportion, which smooths the edges
+     *  ("anti-aliasing"). Text in the stand-in font (see `fonts`) has no glyphs to draw.
+     */
+    fn paint_text(&mut self, run: &fonts::TextRun, color: css::Color, rect: layout::Rect, clip: layout::Rect) {
+        #[cfg(feature = "std")]
+        if let Some(ref face) = run.face {
+            let baseline: f32 = rect.y + run.metrics.ascent;
+            for glyph in &run.glyphs {
+                let (x, y) = run.glyph_origin(glyph);
+                face.draw_glyph(glyph.id, run.size, (rect.x + x, baseline + y), run.synthesis, clip, |x: i32, y: i32, coverage: f32| {
+                    if clip.contains(x as f32, y as f32) && x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+                        self.blend(x as usize, y as usize, color, coverage);
+                    }
+                });
+            }
         }
+        #[cfg(not(feature = "std"))]
+        let _ = (run, color, rect, clip);
+    }
+
+    /// Blend `color` into the pixel at (x, y), as if it covered `coverage` of the pixel.
+    #[cfg(feature = "std")]
+    fn blend(&mut self, x: usize, y: usize, color: css::Color, coverage: f32) {
+        let alpha: f32 = coverage.clamp(0.0, 1.0) * color.a as f32 / 255.0;
+        let pixel: &mut css::Color = &mut self.pixels[x + y * self.width];
+        let mix = |under: u8, over: u8| math::round(under as f32 + (over as f32 - under as f32) * alpha) as u8;
+        *pixel = css::Color { r: mix(pixel.r, color.r), g: mix(pixel.g, color.g), b: mix(pixel.b, color.b), a: pixel.a.max(color.a) };
     }
 }
 
@@ -208,11 +346,156 @@ impl Canvas {
  *  list and then rasterizes it to a canvas:
  */
 /// Paint a tree of LayoutBoxes to an array of pixels.
-fn paint(layout_root: &layout::LayoutBox, bounds: layout::Rect) -> Canvas {
+pub fn paint(layout_root: &layout::LayoutBox, bounds: layout::Rect) -> Canvas {
     let display_list = build_display_list(layout_root);
+    rasterize(&display_list, bounds)
+}
+
+/**
+ *  Zooming
+ *
+ *  Layout works in CSS px, but a screen has device pixels, and there may be more of them
+ *  than CSS px: a "2x" HiDPI screen has two device pixels across for every CSS px, and
+ *  zooming in on a page does the same thing. Scaling the finished canvas would make
+ *  everything blocky. Scaling the display list instead keeps edges sharp, since every
+ *  rectangle is then painted at full resolution.
+ */
+/// Return a copy of the display list with every rectangle multiplied by `factor`.
+pub fn scale_display_list(display_list: &DisplayList, factor: f32) -> DisplayList {
+    display_list.iter().map(|command: &DisplayCommand| match *command {
+        DisplayCommand::SolidColor(color, rect) => DisplayCommand::SolidColor(color, rect.scaled(factor)),
+        DisplayCommand::Image(ref image, rect) => DisplayCommand::Image(image.clone(), rect.scaled(factor)),
+        DisplayCommand::Text(ref run, color, rect) => DisplayCommand::Text(run.scaled(factor), color, rect.scaled(factor)),
+        DisplayCommand::PushClip(rect) => DisplayCommand::PushClip(rect.scaled(factor)),
+        DisplayCommand::PopClip => DisplayCommand::PopClip,
+    }).collect()
+}
+
+/// Execute a display list on a blank canvas the size of `bounds`, showing the part of the
+/// document at `bounds.x`, `bounds.y`.
+pub fn rasterize(display_list: &DisplayList, bounds: layout::Rect) -> Canvas {
+    let stage: Stage = stage!("painting::rasterize", items, pixels);
+    stage.record("items", || display_list.len());
     let mut canvas = Canvas::new(bounds.width as usize, bounds.height as usize);
-    for item in display_list {
-        canvas.paint_item(&item);
-    }
+    let region = layout::Rect { x: 0.0, y: 0.0, width: bounds.width, height: bounds.height };
+    canvas.paint_region(display_list, (bounds.x, bounds.y), region);
+    stage.record("pixels", || canvas.pixels.len());
     canvas
 }
+
+
+/**
+ *  Scrolling the Canvas
+ *
+ *  When the document scrolls, most of what's on the screen is still valid: it has just
+ *  moved. Instead of painting the whole canvas again, we shift the pixels we already have
+ *  and only paint the strips that scrolled into view.
+ */
+impl Canvas {
+    /// Repaint the part of the canvas inside `region` (in canvas pixels), where the
+    /// canvas's top-left corner shows the document point `origin`.
+    pub fn paint_region(&mut self, display_list: &DisplayList, origin: (f32, f32), region: layout::Rect) {
+        let region: layout::Rect = region.intersection(layout::Rect {
+            x: 0.0, y: 0.0, width: self.width as f32, height: self.height as f32,
+        });
+        let white = css::Color { r: 255, g: 255, b: 255, a: 255 };
+        self.paint_item(&DisplayCommand::SolidColor(white, region), (0.0, 0.0), region);
+
+        let mut clips: Vec<layout::Rect> = vec![region];
+        for item in display_list {
+            match *item {
+                DisplayCommand::PushClip(rect) => {
+                    let clip: layout::Rect = rect.translated(-origin.0, -origin.1).intersection(clips[clips.len() - 1]);
+                    clips.push(clip);
+                }
+                DisplayCommand::PopClip => {
+                    clips.pop();
+                }
+                _ => self.paint_item(item, origin, clips[clips.len() - 1]),
+            }
+        }
+    }
+
+    /// Move the pixels to show content (dx, dy) px further right and down, leaving the
+    /// newly exposed strips as they were. Returns those strips, which need repainting.
+    pub fn scroll(&mut self, dx: isize, dy: isize) -> Vec<layout::Rect> {
+        let (width, height) = (self.width as isize, self.height as isize);
+        if dx.abs() >= width || dy.abs() >= height {
+            return vec![layout::Rect { x: 0.0, y: 0.0, width: width as f32, height: height as f32 }];
+        }
+
+        // Copy each row from where its content used to be.
+        let rows: Vec<isize> = if dy > 0 { (0..height - dy).collect() } else { (-dy..height).rev().collect() };
+        for y in rows {
+            let src: usize = ((y + dy) * width + dx.max(0)) as usize;
+            let dst: usize = (y * width - dx.min(0)) as usize;
+            self.pixels.copy_within(src..src + (width - dx.abs()) as usize, dst);
+        }
+
+        let mut exposed: Vec<layout::Rect> = Vec::new();
+        if dy != 0 {
+            let y: isize = if dy > 0 { height - dy } else { 0 };
+            exposed.push(layout::Rect { x: 0.0, y: y as f32, width: width as f32, height: dy.abs() as f32 });
+        }
+        if dx != 0 {
+            let x: isize = if dx > 0 { width - dx } else { 0 };
+            exposed.push(layout::Rect { x: x as f32, y: 0.0, width: dx.abs() as f32, height: height as f32 });
+        }
+        exposed
+    }
+}
+
+
+/**
+ *  Saving the Pixels
+ *
+ *  To look at the result outside the program, we encode the canvas as a PNG image.
+ */
+impl Canvas {
+    /// Encode the canvas as an RGBA PNG file.
+    #[cfg(feature = "std")]
+    pub fn to_png(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut encoder = png::Encoder::new(&mut bytes, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let data: Vec<u8> = self.to_rgba();
+        // Writing to a `Vec` can't fail, and the header always matches the data.
+        let mut writer = encoder.write_header().expect("PNG header");
+        writer.write_image_data(&data).expect("PNG data");
+        writer.finish().expect("PNG end");
+        bytes
+    }
+
+    /// The pixels as RGBA bytes, row by row from the top left, as an HTML canvas's
+    /// `ImageData` or most graphics APIs expect them.
+    pub fn to_rgba(&self) -> Vec<u8> {
+        self.pixels.iter()
+            .flat_map(|color: &css::Color| [color.r, color.g, color.b, color.a])
+            .collect()
+    }
+
+    /// Encode the canvas as a binary PPM file (RGB, no alpha), which any image tool can read.
+    pub fn to_ppm(&self) -> Vec<u8> {
+        let mut bytes: Vec<u8> = format!("P6\n{} {}\n255\n", self.width, self.height).into_bytes();
+        for color in &self.pixels {
+            bytes.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+        bytes
+    }
+
+    /// Return a copy resized by `factor`, using the nearest source pixel.
+    pub fn scale(&self, factor: f32) -> Canvas {
+        let width: usize = math::round(self.width as f32 * factor) as usize;
+        let height: usize = math::round(self.height as f32 * factor) as usize;
+        let mut pixels: Vec<css::Color> = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let src_y: usize = ((y as f32 / factor) as usize).min(self.height.saturating_sub(1));
+            for x in 0..width {
+                let src_x: usize = ((x as f32 / factor) as usize).min(self.width.saturating_sub(1));
+                pixels.push(self.pixels[src_x + src_y * self.width]);
+            }
+        }
+        Canvas { pixels, width, height }
+    }
+}
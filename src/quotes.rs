@@ -0,0 +1,123 @@
+//! CSS quotes: the marks that `open-quote` and `close-quote` generate, nested and in the
+//! document's language.
+
+use crate::atom::Atom;
+use crate::css;
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+/*
+    Quotes
+
+    Generated content can quote: `content: open-quote` makes an opening quotation mark,
+    and `close-quote` the closing one. Quotes nest, so the marks depend on how many are
+    open at that point in the document: in English, the outer quote is “double” and a
+    quote inside it is ‘single’. `no-open-quote` and `no-close-quote` open and close a
+    quote without making a mark, for quotes that continue across paragraphs.
+
+    The `quotes` property says which marks to use, as pairs of strings, outermost first:
+
+        q { quotes: "«" "»" "‹" "›"; }
+
+    Quotes nested deeper than the pairs go use the last pair. `none` makes no marks, and
+    `auto` (the initial value) uses the marks of the element's language, from the `lang`
+    attribute of the nearest element that has one (see `marks_for`). Unlike most
+    properties in this engine, `quotes` is inherited: an element without one uses its
+    parent's.
+
+    The style tree is built in document order, so it keeps a `Quotes` as it goes, the way
+    it does `Counters` (see "Generated Content" in `style`): how many quotes are open, and
+    the `lang` and `quotes` of the elements it's inside. A closing quote with none open
+    makes no mark, and the depth never goes below zero.
+ */
+/// The quotes open at one point in the document, and the marks to quote with there.
+#[derive(Clone, Default, Debug)]
+pub struct Quotes {
+    depth: usize,                                    // how many quotes are open
+    scopes: Vec<(Option<Atom>, Option<css::Value>)>, // `lang` and `quotes` set by enclosing elements, outermost first
+}
+
+impl Quotes {
+    /// How many scopes there are. Passing this to `leave` later drops the ones entered
+    /// in between.
+    pub fn mark(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Drop the scopes entered since `mark`.
+    pub fn leave(&mut self, mark: usize) {
+        self.scopes.truncate(mark);
+    }
+
+    /// Enter an element with this `lang` attribute and `quotes` property, either of which
+    /// may be missing.
+    pub fn enter(&mut self, lang: Option<Atom>, quotes: Option<css::Value>) {
+        if lang.is_some() || quotes.is_some() {
+            self.scopes.push((lang, quotes));
+        }
+    }
+
+    /// Open a quote, returning its mark.
+    pub fn open(&mut self) -> String {
+        let mark: String = self.pair(self.depth).map(|(open, _)| open).unwrap_or_default();
+        self.depth += 1;
+        mark
+    }
+
+    /// Close the innermost quote, returning its mark, or nothing if no quote is open.
+    pub fn close(&mut self) -> String {
+        if self.depth == 0 {
+            return String::new();
+        }
+        self.depth -= 1;
+        self.pair(self.depth).map(|(_, close)| close).unwrap_or_default()
+    }
+
+    /// Open a quote without a mark.
+    pub fn open_silently(&mut self) {
+        self.depth += 1;
+    }
+
+    /// Close the innermost quote without a mark.
+    pub fn close_silently(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+
+    /// The marks for a quote nested `depth` quotes deep, or `None` for `quotes: none`.
+    fn pair(&self, depth: usize) -> Option<(String, String)> {
+        let quotes: Option<&css::Value> = self.scopes.iter().rev().find_map(|(_, quotes)| quotes.as_ref());
+        let strings: Vec<&str> = match quotes {
+            Some(css::Value::Keyword(keyword)) if *keyword == "none" => return None,
+            Some(css::Value::Sequence(values)) => values.iter().map_while(|value: &css::Value| match value {
+                css::Value::StringValue(string) => Some(string.as_str()),
+                _ => None,
+            }).collect(),
+            _ => Vec::new(),
+        };
+        let pairs: Vec<(&str, &str)> = strings.chunks_exact(2).map(|pair: &[&str]| (pair[0], pair[1])).collect();
+        // `auto`, or a value that isn't pairs of strings.
+        if pairs.is_empty() {
+            let lang: Option<&str> = self.scopes.iter().rev().find_map(|(lang, _)| lang.as_ref()).map(Atom::as_str);
+            let marks: [(&str, &str); 2] = marks_for(lang);
+            let (open, close) = marks[depth.min(1)];
+            return Some((open.to_string(), close.to_string()));
+        }
+        let (open, close) = pairs[depth.min(pairs.len() - 1)];
+        Some((open.to_string(), close.to_string()))
+    }
+}
+
+/// The quotation marks used in language `lang` (a language tag like `fr` or `de-CH`), for
+/// outer and inner quotes. Languages not listed, and unknown ones, get English marks.
+pub fn marks_for(lang: Option<&str>) -> [(&'static str, &'static str); 2] {
+    let primary: String = lang.and_then(|lang: &str| lang.split(['-', '_']).next()).unwrap_or("").to_ascii_lowercase();
+    match primary.as_str() {
+        "de" | "cs" | "sk" | "sl" => [("\u{201E}", "\u{201C}"), ("\u{201A}", "\u{2018}")], // „ “ ‚ ‘
+        "fr" | "es" | "it" | "pt" | "ca" => [("\u{AB}", "\u{BB}"), ("\u{201C}", "\u{201D}")], // « » “ ”
+        "ru" | "uk" | "be" => [("\u{AB}", "\u{BB}"), ("\u{201E}", "\u{201C}")], // « » „ “
+        "pl" | "hu" | "ro" => [("\u{201E}", "\u{201D}"), ("\u{AB}", "\u{BB}")], // „ ” « »
+        "sv" | "fi" => [("\u{201D}", "\u{201D}"), ("\u{2019}", "\u{2019}")], // ” ” ’ ’
+        "ja" => [("\u{300C}", "\u{300D}"), ("\u{300E}", "\u{300F}")], // 「 」 『 』
+        _ => [("\u{201C}", "\u{201D}"), ("\u{2018}", "\u{2019}")], // “ ” ‘ ’
+    }
+}
@@ -0,0 +1,285 @@
+//! Reader mode: find the article in a cluttered page and pull it out.
+
+use crate::atom::Atom;
+use crate::collections::HashMap;
+use crate::dom;
+
+
+/*
+    Reader Mode
+
+    Most pages wrap their actual content in navigation bars, sidebars, ads, comment
+    threads, and footers. Reader mode guesses which part of the page is the article, and
+    shows only that, with a plain stylesheet. The guessing works like the original
+    Readability bookmarklet:
+
+        1. Ignore elements that are unlikely to be content: <nav>, <aside>, <script>, and
+           anything whose class or id says "sidebar", "comment", "footer", and so on.
+        2. Score each paragraph by how much text it has: 1 point, plus 1 per comma, plus
+           1 per 100 characters (up to 3). Paragraphs under 25 characters score nothing.
+        3. Give each paragraph's score to its parent, and half of it to its grandparent.
+           Each parent starts with a bonus or penalty for its tag (a <div> is a likely
+           container; a <ul> less so) and for its class and id ("article" and "content"
+           are good signs; "sidebar" is not).
+        4. Scale each parent's score by how much of its text is *not* link text, so
+           blocks that are mostly links (menus, "related articles") lose out.
+        5. The highest scoring parent is the article. Its siblings join it if they score
+           well too, or if they are substantial paragraphs with few links.
+
+    The article is then copied into a new, clean document: unlikely elements and blocks
+    that are mostly links are dropped, and only a few attributes (like `href` and `src`)
+    are kept, so the page's own classes and ids can't affect the reader stylesheet.
+ */
+
+/// The main content of a page.
+#[derive(Debug)]
+pub struct Article {
+    pub title: Option<String>,
+    pub document: dom::Document, // <html><head><title/></head><body><article>...</article></body></html>
+}
+
+/// Elements that never contain article content.
+const UNLIKELY_TAGS: [&str; 17] = [
+    "aside", "button", "footer", "form", "head", "header", "iframe", "input", "link", "meta",
+    "nav", "noscript", "script", "select", "style", "template", "textarea",
+];
+
+/// Words in a class or id that suggest an element is (or isn't) content.
+const POSITIVE_WORDS: [&str; 9] = ["article", "blog", "body", "content", "entry", "main", "post", "story", "text"];
+const NEGATIVE_WORDS: [&str; 14] = [
+    "ad", "banner", "comment", "comments", "footer", "masthead", "menu", "nav", "promo", "related",
+    "share", "sidebar", "social", "sponsor",
+];
+
+/// Attributes kept when copying the article.
+const KEPT_ATTRIBUTES: [&str; 6] = ["alt", "height", "href", "src", "title", "width"];
+
+/// Find the main content of a document, or `None` if nothing looks like an article.
+pub fn extract(document: &dom::Document) -> Option<Article> {
+    let tree: &dom::Tree = &document.tree;
+    let scores: HashMap<dom::NodeId, f32> = score_candidates(tree, document.root_element);
+    let top: dom::NodeId = top_candidate(tree, &scores)?;
+
+    // Gather the top candidate and any siblings that belong with it, in document order.
+    let top_score: f32 = scores[&top];
+    let threshold: f32 = (top_score * 0.2).max(10.0);
+    let parts: Vec<dom::NodeId> = match tree[top].parent {
+        Some(parent) => tree.children(parent).filter(|&sibling: &dom::NodeId| {
+            sibling == top
+                || scores.get(&sibling).is_some_and(|&score: &f32| score >= threshold)
+                || is_substantial_paragraph(tree, sibling)
+        }).collect(),
+        None => vec![top],
+    };
+
+    let title: Option<String> = document.title().filter(|title: &String| !title.is_empty());
+
+    let mut clean: dom::Tree = dom::Tree::new();
+    let mut content: Vec<dom::NodeId> = Vec::new();
+    if let Some(ref title) = title {
+        // Start with the title as a heading, unless the article already does.
+        let first_heading: Option<String> = parts.iter()
+            .flat_map(|&part: &dom::NodeId| tree.descendants(part))
+            .find(|&node: &dom::NodeId| matches!(tag_name(tree, node), Some("h1") | Some("h2")))
+            .map(|heading: dom::NodeId| collapse_whitespace(&tree.text_content(heading)));
+        if first_heading.as_ref() != Some(title) {
+            let text: dom::NodeId = clean.text(title.clone());
+            content.push(clean.element(Atom::from("h1"), dom::AttributeMap::default(), vec![text]));
+        }
+    }
+    for part in parts {
+        content.extend(copy_clean(tree, part, &mut clean));
+    }
+
+    let article: dom::NodeId = clean.element(Atom::from("article"), dom::AttributeMap::default(), content);
+    let body: dom::NodeId = clean.element(Atom::from("body"), dom::AttributeMap::default(), vec![article]);
+    let title_text: dom::NodeId = clean.text(title.clone().unwrap_or_default());
+    let title_element: dom::NodeId = clean.element(Atom::from("title"), dom::AttributeMap::default(), vec![title_text]);
+    let head: dom::NodeId = clean.element(Atom::from("head"), dom::AttributeMap::default(), vec![title_element]);
+    let html: dom::NodeId = clean.element(Atom::from("html"), dom::AttributeMap::default(), vec![head, body]);
+
+    let mut result: dom::Document = dom::Document::new(clean, html);
+    result.url = document.url.clone();
+    result.base_url = document.base_url.clone();
+    result.doctype = Some("html".to_string());
+    Some(Article { title, document: result })
+}
+
+
+// Scoring
+
+/// The element most likely to contain the article.
+fn top_candidate(tree: &dom::Tree, scores: &HashMap<dom::NodeId, f32>) -> Option<dom::NodeId> {
+    scores.iter()
+        .map(|(&node, &score): (&dom::NodeId, &f32)| (node, score))
+        .filter(|&(_, score): &(dom::NodeId, f32)| score > 0.0)
+        // Break ties by document order, so the result doesn't depend on hash order.
+        .max_by(|a: &(dom::NodeId, f32), b: &(dom::NodeId, f32)| {
+            a.1.total_cmp(&b.1).then_with(|| tree.compare_document_order(b.0, a.0))
+        })
+        .map(|(node, _)| node)
+}
+
+/// Score every element that contains a paragraph (steps 2 to 4 above).
+fn score_candidates(tree: &dom::Tree, root: dom::NodeId) -> HashMap<dom::NodeId, f32> {
+    let mut scores: HashMap<dom::NodeId, f32> = HashMap::default();
+    for node in tree.descendants(root) {
+        if !is_paragraph(tree, node) || tree.ancestors(node).any(|ancestor: dom::NodeId| is_unlikely(tree, ancestor)) {
+            continue;
+        }
+        let text: String = collapse_whitespace(&tree.text_content(node));
+        if text.chars().count() < 25 {
+            continue;
+        }
+        let score: f32 = 1.0 + text.matches(',').count() as f32 + (text.chars().count() as f32 / 100.0).floor().min(3.0);
+
+        let parent: Option<dom::NodeId> = tree[node].parent;
+        let grandparent: Option<dom::NodeId> = parent.and_then(|parent: dom::NodeId| tree[parent].parent);
+        for (ancestor, share) in [(parent, 1.0), (grandparent, 0.5)] {
+            if let Some(ancestor) = ancestor.filter(|&ancestor: &dom::NodeId| tag_name(tree, ancestor).is_some()) {
+                *scores.entry(ancestor).or_insert_with(|| initial_score(tree, ancestor)) += score * share;
+            }
+        }
+    }
+
+    for (&node, score) in scores.iter_mut() {
+        *score *= 1.0 - link_density(tree, node);
+    }
+    scores
+}
+
+/// The bonus or penalty an element starts with, for its tag, class, and id.
+fn initial_score(tree: &dom::Tree, node: dom::NodeId) -> f32 {
+    let tag_score: f32 = match tag_name(tree, node) {
+        Some("article") => 10.0,
+        Some("div") => 5.0,
+        Some("pre") | Some("td") | Some("blockquote") => 3.0,
+        Some("address") | Some("ol") | Some("ul") | Some("dl") | Some("dd") | Some("dt") | Some("li") | Some("form") => -3.0,
+        Some("h1") | Some("h2") | Some("h3") | Some("h4") | Some("h5") | Some("h6") | Some("th") => -5.0,
+        _ => 0.0,
+    };
+    tag_score + class_weight(tree, node)
+}
+
+/// +25 if the element's class or id suggests content, -25 if it suggests clutter.
+fn class_weight(tree: &dom::Tree, node: dom::NodeId) -> f32 {
+    let element: &dom::Element = match tree[node].node_type {
+        dom::NodeType::Element(ref element) => element,
+        _ => return 0.0,
+    };
+    let names: String = format!("{} {}", element.attr("class").unwrap_or(""), element.id().unwrap_or("")).to_ascii_lowercase();
+    let words: Vec<&str> = names.split(|c: char| !c.is_ascii_alphanumeric()).filter(|word: &&str| !word.is_empty()).collect();
+    let mut weight: f32 = 0.0;
+    if words.iter().any(|word: &&str| POSITIVE_WORDS.contains(word)) {
+        weight += 25.0;
+    }
+    if words.iter().any(|word: &&str| NEGATIVE_WORDS.contains(word)) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+/// The fraction of an element's text that is inside links.
+fn link_density(tree: &dom::Tree, node: dom::NodeId) -> f32 {
+    let length: usize = collapse_whitespace(&tree.text_content(node)).len();
+    if length == 0 {
+        return 0.0;
+    }
+    let link_length: usize = tree.descendants(node)
+        .filter(|&descendant: &dom::NodeId| tag_name(tree, descendant) == Some("a"))
+        .map(|link: dom::NodeId| collapse_whitespace(&tree.text_content(link)).len())
+        .sum();
+    (link_length as f32 / length as f32).min(1.0)
+}
+
+/// Return true for elements that hold a paragraph of text: <p>, <pre>, <td>, <blockquote>,
+/// and <div>s used like paragraphs (with no block elements inside).
+fn is_paragraph(tree: &dom::Tree, node: dom::NodeId) -> bool {
+    match tag_name(tree, node) {
+        Some("p") | Some("pre") | Some("td") | Some("blockquote") => true,
+        Some("div") => !tree.descendants(node).skip(1).any(|descendant: dom::NodeId| {
+            matches!(tag_name(tree, descendant), Some("div") | Some("p") | Some("pre") | Some("table") | Some("ul") | Some("ol") | Some("blockquote"))
+        }),
+        _ => false,
+    }
+}
+
+/// A paragraph that belongs in the article even without a score: long, and few links.
+fn is_substantial_paragraph(tree: &dom::Tree, node: dom::NodeId) -> bool {
+    tag_name(tree, node) == Some("p")
+        && collapse_whitespace(&tree.text_content(node)).chars().count() > 80
+        && link_density(tree, node) < 0.25
+}
+
+/// Return true for elements that are unlikely to be content (step 1 above).
+fn is_unlikely(tree: &dom::Tree, node: dom::NodeId) -> bool {
+    match tag_name(tree, node) {
+        Some(tag_name) => UNLIKELY_TAGS.contains(&tag_name) || class_weight(tree, node) < 0.0,
+        None => false,
+    }
+}
+
+
+// Cleaning
+
+/// Copy `node` into `clean`, leaving out clutter. Returns `None` if it's all clutter.
+fn copy_clean(tree: &dom::Tree, node: dom::NodeId, clean: &mut dom::Tree) -> Option<dom::NodeId> {
+    let element: &dom::Element = match tree[node].node_type {
+        dom::NodeType::Text(ref text) => return Some(clean.text(text.clone())),
+        dom::NodeType::Element(ref element) => element,
+        dom::NodeType::DocumentFragment => return None,
+    };
+    if is_unlikely(tree, node) {
+        return None;
+    }
+    // Lists of links and the like.
+    if matches!(element.tag_name.as_str(), "div" | "ul" | "ol" | "table" | "section") && link_density(tree, node) > 0.5 {
+        return None;
+    }
+
+    let attributes: dom::AttributeMap = element.attributes.iter()
+        .filter(|&(name, _): &(&Atom, &String)| KEPT_ATTRIBUTES.contains(&name.as_str()))
+        .map(|(name, value): (&Atom, &String)| (*name, value.clone()))
+        .collect();
+    let children: Vec<dom::NodeId> = tree.children(node)
+        .filter_map(|child: dom::NodeId| copy_clean(tree, child, clean))
+        .collect();
+    Some(clean.element(element.tag_name, attributes, children))
+}
+
+
+// Helpers
+
+fn tag_name(tree: &dom::Tree, node: dom::NodeId) -> Option<&str> {
+    match tree[node].node_type {
+        dom::NodeType::Element(ref element) => Some(element.tag_name.as_str()),
+        _ => None,
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+
+/*
+    The Reader Stylesheet
+
+    A narrow, centered column with generous spacing, using only the properties this
+    engine supports. Since `extract` strips classes and ids, it only needs tag selectors.
+ */
+/// The stylesheet reader mode pages are shown with.
+pub const READER_CSS: &str = "
+html, body, article, h1, h2, h3, h4, h5, h6, p, div, pre, blockquote, ul, ol, li, table, figure, img, section {
+    display: block;
+}
+head { display: none; }
+html { background: #f7f3ea; }
+body { width: 640px; margin: auto; padding: 40px; }
+h1 { margin-bottom: 24px; }
+h2, h3, h4, h5, h6 { margin-top: 24px; margin-bottom: 12px; }
+p, pre, ul, ol, table, figure, img { margin-bottom: 16px; }
+li { margin-bottom: 4px; padding-left: 24px; }
+blockquote { margin-bottom: 16px; padding-left: 16px; border-left-width: 4px; border-color: #c8c0b0; }
+pre { padding: 12px; background: #ece6d8; }
+";
@@ -0,0 +1,215 @@
+//! Reference tests: pairs of pages that should (or shouldn't) paint the same pixels.
+
+use crate::engine::{self, Page};
+use crate::error::EngineError;
+use crate::painting::Canvas;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+
+/*
+    Reference Tests
+
+    The easiest way to check that the engine renders something correctly is to render it
+    a second way, using simpler features, and compare the pixels. Browsers call these
+    [reftests](https://web-platform-tests.org/writing-tests/reftests.html): the test page
+    uses the feature under test (say, `margin: auto`), and the reference page gets the
+    same picture without it (`margin-left: 300px`). If they match, the feature works, and
+    no one has to write down the expected pixels.
+
+    Tests are listed in a manifest, in the format of Mozilla's `reftest.list` files, one
+    per line. `==` means the pages must match, and `!=` that they must not:
+
+        # Comments start with '#'.
+        == auto-width.html auto-width-ref.html
+        != red.html green.html
+        fuzzy(0-2,0-100) == gradient.html gradient-ref.html
+
+    Painting is exact, but a reference sometimes can't be: `fuzzy(<difference>,<pixels>)`
+    lets up to `<pixels>` pixels differ, each by at most `<difference>` in any channel.
+    Like Mozilla, we accept a range for each, but only the maximum matters here.
+
+    Paths are relative to the manifest. Each page is loaded from its file with its own
+    `<style>` elements, and painted at `engine::DEFAULT_VIEWPORT`.
+
+    e.g.
+        let tests: Vec<Reftest> = reftest::load_manifest(Path::new("tests/reftests/reftest.list"))?;
+        for test in &tests {
+            println!("{}: {}", test, test.run());
+        }
+ */
+
+/// How far two renderings may differ and still match.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Fuzzy {
+    pub max_difference: u8, // in any channel of a pixel
+    pub max_pixels: usize,  // how many pixels may differ at all
+}
+
+/// How two renderings of the same size differ.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Difference {
+    pub max_difference: u8, // the largest difference in any channel of any pixel
+    pub pixels: usize,      // how many pixels differ at all
+}
+
+impl Difference {
+    /// Return true if the renderings are close enough to count as a match.
+    pub fn within(&self, fuzzy: &Fuzzy) -> bool {
+        self.max_difference <= fuzzy.max_difference && self.pixels <= fuzzy.max_pixels
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} pixels differ, by up to {}", self.pixels, self.max_difference)
+    }
+}
+
+/// Compare two canvases pixel by pixel, or return `None` if their sizes differ.
+pub fn compare(a: &Canvas, b: &Canvas) -> Option<Difference> {
+    if (a.width, a.height) != (b.width, b.height) {
+        return None;
+    }
+    let mut difference: Difference = Default::default();
+    for (p, q) in a.pixels.iter().zip(&b.pixels) {
+        let channels: [u8; 4] = [p.r.abs_diff(q.r), p.g.abs_diff(q.g), p.b.abs_diff(q.b), p.a.abs_diff(q.a)];
+        let max: u8 = channels.into_iter().max().unwrap_or(0);
+        if max > 0 {
+            difference.pixels += 1;
+            difference.max_difference = difference.max_difference.max(max);
+        }
+    }
+    Some(difference)
+}
+
+
+/// Whether the two pages of a reftest should match.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Relation {
+    Match,    // ==
+    Mismatch, // !=
+}
+
+/// One line of a manifest.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Reftest {
+    pub relation: Relation,
+    pub test: PathBuf,
+    pub reference: PathBuf,
+    pub fuzzy: Fuzzy,
+}
+
+/// What happened when a reftest ran.
+#[derive(Debug)]
+pub enum Outcome {
+    Pass,
+    Fail(Option<Difference>), // `None` if the canvases had different sizes
+    Error(PathBuf, EngineError),
+}
+
+impl Outcome {
+    /// Return true if the test passed.
+    pub fn passed(&self) -> bool {
+        matches!(self, Outcome::Pass)
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Pass => write!(f, "pass"),
+            Outcome::Fail(Some(difference)) => write!(f, "fail ({})", difference),
+            Outcome::Fail(None) => write!(f, "fail (different sizes)"),
+            Outcome::Error(path, err) => write!(f, "error in {}: {}", path.display(), err),
+        }
+    }
+}
+
+impl Reftest {
+    /// Render both pages and compare them.
+    pub fn run(&self) -> Outcome {
+        let test: Canvas = match render(&self.test) {
+            Ok(canvas) => canvas,
+            Err(err) => return Outcome::Error(self.test.clone(), err),
+        };
+        let reference: Canvas = match render(&self.reference) {
+            Ok(canvas) => canvas,
+            Err(err) => return Outcome::Error(self.reference.clone(), err),
+        };
+        let difference: Option<Difference> = compare(&test, &reference);
+        let matched: bool = difference.is_some_and(|d: Difference| d.within(&self.fuzzy));
+        if matched == (self.relation == Relation::Match) {
+            Outcome::Pass
+        } else {
+            Outcome::Fail(difference)
+        }
+    }
+}
+
+impl fmt::Display for Reftest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.fuzzy != Fuzzy::default() {
+            write!(f, "fuzzy(0-{},0-{}) ", self.fuzzy.max_difference, self.fuzzy.max_pixels)?;
+        }
+        let relation: &str = match self.relation {
+            Relation::Match => "==",
+            Relation::Mismatch => "!=",
+        };
+        write!(f, "{} {} {}", relation, self.test.display(), self.reference.display())
+    }
+}
+
+/// Load a page from a file and paint it at the default viewport size.
+pub fn render(path: &Path) -> Result<Canvas, EngineError> {
+    let mut page: Page = Page::load(&fs::read_to_string(path)?, "")?;
+    page.set_deterministic(true);
+    let (width, height) = engine::DEFAULT_VIEWPORT;
+    page.set_viewport(width, height);
+    Ok(page.paint().clone())
+}
+
+
+/// Read a manifest file, resolving the paths in it against the manifest's directory.
+pub fn load_manifest(path: &Path) -> Result<Vec<Reftest>, String> {
+    let source: String = fs::read_to_string(path).map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+    let base: &Path = path.parent().unwrap_or(Path::new(""));
+    parse_manifest(&source, base).map_err(|err: String| format!("{}: {}", path.display(), err))
+}
+
+/// Parse the lines of a manifest.
+pub fn parse_manifest(source: &str, base: &Path) -> Result<Vec<Reftest>, String> {
+    let mut tests: Vec<Reftest> = Vec::new();
+    for (number, line) in source.lines().enumerate() {
+        let line: &str = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        let fuzzy: Fuzzy = match words.first().and_then(|word: &&str| word.strip_prefix("fuzzy(")) {
+            Some(arguments) => {
+                let fuzzy: Fuzzy = parse_fuzzy(arguments).ok_or_else(|| error("expected fuzzy(<difference>,<pixels>)"))?;
+                words.remove(0);
+                fuzzy
+            }
+            None => Fuzzy::default(),
+        };
+        let (relation, test, reference) = match words[..] {
+            ["==", test, reference] => (Relation::Match, test, reference),
+            ["!=", test, reference] => (Relation::Mismatch, test, reference),
+            _ => return Err(error("expected == or != and two paths")),
+        };
+        tests.push(Reftest { relation, test: base.join(test), reference: base.join(reference), fuzzy });
+    }
+    Ok(tests)
+}
+
+/// Parse the arguments of `fuzzy(...)`, after the opening parenthesis. Each is a number or
+/// a range of numbers, of which we use the maximum.
+fn parse_fuzzy(arguments: &str) -> Option<Fuzzy> {
+    let (difference, pixels) = arguments.strip_suffix(')')?.split_once(',')?;
+    let maximum = |range: &str| range.rsplit('-').next().unwrap_or(range).trim().to_string();
+    Some(Fuzzy { max_difference: maximum(difference).parse().ok()?, max_pixels: maximum(pixels).parse().ok()? })
+}
@@ -0,0 +1,56 @@
+//! Fast scanning over the bytes of the parsers' input.
+
+
+/*
+    Scanning Bytes
+
+    Both parsers spend most of their time in a few loops: skipping whitespace, finding the
+    `<` that ends a run of text (or the `"` that ends an attribute value), and reading
+    names. Doing that a `char` at a time means decoding UTF-8 at every step, even though
+    every byte we're looking for is ASCII.
+
+    UTF-8 is designed so that an ASCII byte never appears inside a multi-byte character,
+    so we can search the bytes directly and still land on a character boundary:
+
+        until        finds a single byte with `memchr`, which checks 16 or 32 bytes per
+                     instruction where the CPU has SIMD, and a word at a time elsewhere
+        whitespace   skips ASCII whitespace a byte at a time, and only decodes a `char`
+                     when it meets a non-ASCII byte (which might be U+00A0 or U+3000)
+        ascii_run    skips bytes in a set of ASCII bytes, for names and identifiers
+
+    Each returns how many bytes to advance, so a parser's position stays a byte index into
+    its `&str`.
+
+    e.g.
+        scan::until("hello <b>", b'<')  == 6
+        scan::whitespace("  \n x")      == 4
+ */
+
+/// The length of the prefix of `s` that doesn't contain `byte` (all of `s` if none does).
+/// `byte` must be ASCII.
+pub fn until(s: &str, byte: u8) -> usize {
+    debug_assert!(byte.is_ascii());
+    memchr::memchr(byte, s.as_bytes()).unwrap_or(s.len())
+}
+
+/// The length of the whitespace at the start of `s`, as `char::is_whitespace` defines it.
+pub fn whitespace(s: &str) -> usize {
+    let bytes: &[u8] = s.as_bytes();
+    let mut i: usize = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b' ' | b'\t'..=b'\r' => i += 1,
+            byte if byte.is_ascii() => break,
+            _ => match s[i..].chars().next() {
+                Some(c) if c.is_whitespace() => i += c.len_utf8(),
+                _ => break,
+            },
+        }
+    }
+    i
+}
+
+/// The length of the prefix of `s` made of ASCII bytes for which `test` returns true.
+pub fn ascii_run(s: &str, test: impl Fn(u8) -> bool) -> usize {
+    s.bytes().position(|byte: u8| !(byte.is_ascii() && test(byte))).unwrap_or(s.len())
+}
@@ -0,0 +1,1081 @@
+//! Running a page's `<script>` elements, and the DOM objects scripts see (behind the `js` feature).
+
+use crate::js::{self, HostObject, Value};
+use crate::event_loop::{self, EventLoop, TaskId};
+use crate::{css, dom, events, forms, loader, net, style};
+use crate::atom::Atom;
+use crate::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::sync::Mutex;
+
+
+/*
+    Scripts
+
+    `js` runs JavaScript but knows nothing about documents; this module is the host that
+    connects the two. A `ScriptHost` owns the interpreter for one page, and runs the
+    page's `<script>` elements (inline, or fetched from their `src`) in document order.
+
+    A browser runs each script as soon as the parser reaches it, so scripts only see the
+    part of the document above them (and can `document.write` more). Here the whole
+    document is parsed first, as if every script were `defer`red, and `document.write`
+    isn't supported. An exception that escapes a script is reported on the console, and
+    the next script still runs.
+
+    Scripts see these globals:
+        document:  getElementById, querySelector(All), getElementsByTagName,
+                   getElementsByClassName, createElement, createTextNode,
+                   addEventListener, removeEventListener,
+                   documentElement, head, body, title, URL
+        window:    the global object's stand-in (`window.document`,
+                   addEventListener, removeEventListener, and the timer functions)
+        console:   log, info, debug, warn, and error (kept as `ConsoleMessage`s)
+        setTimeout, clearTimeout, setInterval, clearInterval,
+        requestAnimationFrame, cancelAnimationFrame
+        performance: now
+
+    Nodes are `Value::Host` handles whose `id` is the node's index in the arena, so the
+    same node is always the same handle (`a === b` works). Elements support:
+        tagName, nodeName, nodeType, id, className, textContent, innerText, value,
+        checked, parentNode, parentElement, children, childNodes, firstChild, lastChild,
+        firstElementChild, nextSibling, previousSibling, isConnected,
+        getAttribute, setAttribute, removeAttribute, hasAttribute, appendChild,
+        insertBefore, removeChild, remove, cloneNode, contains, hasChildNodes, matches,
+        closest, querySelector, querySelectorAll, getElementsByTagName,
+        getElementsByClassName, addEventListener, removeEventListener, style, classList
+    and text nodes have `data` and `nodeValue`. Lists of nodes are plain arrays, not
+    live collections. Selectors are whatever the CSS parser supports.
+
+    `element.style` edits the `style` attribute (getPropertyValue, setProperty,
+    removeProperty, cssText, and camelCase properties like `style.marginTop`), keeping
+    only declarations the inline-declaration parser accepts. `element.classList` edits
+    the `class` attribute (add, remove, toggle, replace, contains, length, value).
+
+    Event listeners are JavaScript functions, so they are kept here rather than in an
+    `events::EventListeners`, and `dispatch_event` walks the same capture, target, and
+    bubble phases with `events::dispatch_along`. The path ends at the document and then
+    the window, so listeners there see every event that bubbles. Listeners get an event
+    object with type, target, currentTarget, eventPhase, bubbles, cancelable,
+    defaultPrevented, preventDefault, stopPropagation, and stopImmediatePropagation,
+    which act on the `events::Event` being dispatched. The third argument of
+    `addEventListener` is `capture`, or an object with `capture` and `once`.
+
+    Timers and animation frame callbacks wait in an `event_loop::EventLoop`, whose clock
+    the page moves with `run_tasks`; `performance.now()` and the timestamps passed to
+    frame callbacks read that clock.
+
+    Console output isn't printed: the host keeps every message, with its level and the
+    time on the page's clock, for whoever runs the page to show or check. Errors the host
+    reports itself (uncaught exceptions, scripts that fail to load) go there too, as
+    `ConsoleLevel::Error` messages.
+
+    Changes go through the `Document` mutation methods, so its lookup indexes stay
+    correct. The host also records what scripts changed, for the page to decide how much
+    to redo: elements whose `id`, `class`, or `style` changed are kept as they were
+    before the first change, so the page can restyle just those; anything else (new
+    nodes, text, form values) means a new layout.
+ */
+
+/// The `document` global.
+pub const DOCUMENT: HostObject = HostObject { class: "Document", id: 0 };
+
+/// The `window` global.
+pub const WINDOW: HostObject = HostObject { class: "Window", id: 0 };
+
+/// The `performance` global.
+const PERFORMANCE: HostObject = HostObject { class: "Performance", id: 0 };
+
+/// The timer functions, which are globals as well as methods of `window`.
+const TIMER_FUNCTIONS: [&str; 6] = [
+    "setTimeout", "clearTimeout", "setInterval", "clearInterval", "requestAnimationFrame", "cancelAnimationFrame",
+];
+
+/// The event being dispatched, as listeners see it.
+const EVENT: HostObject = HostObject { class: "Event", id: 0 };
+
+/// The methods of elements, so that reading one (e.g. `typeof el.getAttribute`) gives a function.
+const ELEMENT_METHODS: [&str; 20] = [
+    "getAttribute", "setAttribute", "removeAttribute", "hasAttribute", "appendChild", "insertBefore",
+    "removeChild", "remove", "cloneNode", "contains", "hasChildNodes", "matches", "closest",
+    "querySelector", "querySelectorAll", "getElementsByTagName", "getElementsByClassName", "toString",
+    "addEventListener", "removeEventListener",
+];
+
+/// The methods of the event object.
+const EVENT_METHODS: [&str; 3] = ["preventDefault", "stopPropagation", "stopImmediatePropagation"];
+
+/// The methods of `element.style`.
+const STYLE_METHODS: [&str; 3] = ["getPropertyValue", "setProperty", "removeProperty"];
+
+/// The methods of `element.classList`.
+const CLASS_LIST_METHODS: [&str; 5] = ["add", "remove", "toggle", "replace", "contains"];
+
+/// What scripts changed in a document since the changes were last taken.
+#[derive(Default, Debug)]
+pub struct Changes {
+    /// Elements whose `id`, `class`, or `style` changed, as they were before the change.
+    pub restyled: HashMap<dom::NodeId, dom::Element>,
+    /// True if anything else changed, so the page needs a new layout.
+    pub other: bool,
+}
+
+impl Changes {
+    /// Return true if nothing changed.
+    pub fn is_empty(&self) -> bool {
+        self.restyled.is_empty() && !self.other
+    }
+}
+
+/// How serious a console message is: which `console` method wrote it.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ConsoleLevel {
+    Log,
+    Info,
+    Debug,
+    Warn,
+    Error,
+}
+
+impl ConsoleLevel {
+    /// The level of a `console` method, e.g. `Warn` for "warn". Unknown names log.
+    fn from_method(method: &str) -> ConsoleLevel {
+        match method {
+            "info" => ConsoleLevel::Info,
+            "debug" => ConsoleLevel::Debug,
+            "warn" => ConsoleLevel::Warn,
+            "error" => ConsoleLevel::Error,
+            _ => ConsoleLevel::Log,
+        }
+    }
+}
+
+impl fmt::Display for ConsoleLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name: &str = match self {
+            ConsoleLevel::Log => "log",
+            ConsoleLevel::Info => "info",
+            ConsoleLevel::Debug => "debug",
+            ConsoleLevel::Warn => "warn",
+            ConsoleLevel::Error => "error",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// One message written to the console.
+#[derive(Clone, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ConsoleMessage {
+    pub level: ConsoleLevel,
+    /// The arguments, formatted and joined by spaces.
+    pub text: String,
+    /// When it was written, on the page's clock (see `ScriptHost::now`).
+    pub time: f64,
+}
+
+/// Where a script's event listener is registered.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum Target {
+    Node(dom::NodeId),
+    Document,
+    Window,
+}
+
+/// A function a script registered with `addEventListener`.
+struct Listener {
+    event_type: String,
+    capture: bool,
+    once: bool,
+    function: Value,
+}
+
+/// A function to call from the event loop, and the arguments to call it with.
+type Callback = (Value, Vec<Value>);
+
+/// The JavaScript interpreter for one page, and the state of its DOM bindings.
+pub struct ScriptHost {
+    runtime: js::Runtime,
+    bindings: Bindings,
+}
+
+/// What the bindings remember between calls into scripts.
+#[derive(Default)]
+struct Bindings {
+    changes: Changes,
+    console: Vec<ConsoleMessage>,
+    listeners: HashMap<Target, Vec<Listener>>,
+    event_loop: EventLoop<Callback>,
+}
+
+impl Default for ScriptHost {
+    fn default() -> ScriptHost {
+        ScriptHost::new()
+    }
+}
+
+impl ScriptHost {
+    /// Create an interpreter with the `document` and `window` globals.
+    pub fn new() -> ScriptHost {
+        let mut runtime: js::Runtime = js::Runtime::new();
+        runtime.set_global("document", Value::Host(DOCUMENT));
+        runtime.set_global("window", Value::Host(WINDOW));
+        runtime.set_global("performance", Value::Host(PERFORMANCE));
+        for name in TIMER_FUNCTIONS {
+            runtime.set_global(name, Value::host_method(WINDOW, name));
+        }
+        ScriptHost { runtime, bindings: Bindings::default() }
+    }
+
+    /// Run every `<script>` in `document`, in document order. External scripts are fetched
+    /// with `client`, relative to the document's base URL. Returns the number of scripts run.
+    pub fn run_scripts(&mut self, document: &mut dom::Document, client: &Mutex<net::Client>) -> usize {
+        let mut sources: Vec<(String, String)> = Vec::new(); // name for errors, source
+        for node in document.get_elements_by_tag_name("script") {
+            let src: Option<String> = match document.tree[node].node_type {
+                dom::NodeType::Element(ref element) => {
+                    if !matches!(element.attr("type"), None | Some("") | Some("text/javascript") | Some("application/javascript")) {
+                        continue; // e.g. JSON data, or a template
+                    }
+                    element.attr("src").map(str::to_string)
+                }
+                _ => continue,
+            };
+            match src {
+                Some(src) => {
+                    let url: String = match document.base_url {
+                        Some(ref base) if !src.contains("://") => match net::Url::parse(base).and_then(|base: net::Url| base.join(&src)) {
+                            Ok(url) => url.to_string(),
+                            Err(_) => continue,
+                        },
+                        _ => src,
+                    };
+                    match loader::fetch(client, &url) {
+                        Some(bytes) => sources.push((url, String::from_utf8_lossy(&bytes).into_owned())),
+                        None => {
+                            let mut dom: Dom = Dom { document, bindings: &mut self.bindings, event: None };
+                            js::Host::console(&mut dom, "error", &format!("Failed to load script {}", url));
+                        }
+                    }
+                }
+                None => sources.push(("inline script".to_string(), document.tree.text_content(node))),
+            }
+        }
+
+        let count: usize = sources.len();
+        for (name, source) in sources {
+            let mut dom: Dom = Dom { document, bindings: &mut self.bindings, event: None };
+            if let Err(error) = self.runtime.eval(&source, &mut dom) {
+                js::Host::console(&mut dom, "error", &format!("{} (in {})", error, name));
+            }
+        }
+        count
+    }
+
+    /// Run `source` against `document`, returning the value of its last expression.
+    pub fn eval(&mut self, document: &mut dom::Document, source: &str) -> Result<Value, js::ScriptError> {
+        self.runtime.eval(source, &mut Dom { document, bindings: &mut self.bindings, event: None })
+    }
+
+    /// Dispatch `event` at `target`, calling the listeners scripts registered on its path
+    /// (and on the document and window, if `target` is in the document). Returns false if
+    /// a listener canceled the event.
+    pub fn dispatch_event(&mut self, document: &mut dom::Document, target: dom::NodeId, event: &mut events::Event) -> bool {
+        event.target = Some(target);
+        let mut path: Vec<Target> = document.tree.ancestors(target).map(Target::Node).collect();
+        if document.is_connected(target) {
+            path.extend([Target::Document, Target::Window]);
+        }
+        events::dispatch_along(&path, event, |current: Target, event: &mut events::Event, capture: bool| {
+            // Listeners added while the event is here wait for the next event.
+            let functions: Vec<Value> = match self.bindings.listeners.get_mut(&current) {
+                Some(listeners) => {
+                    let matching: Vec<Value> = listeners.iter()
+                        .filter(|listener: &&Listener| listener.event_type == event.event_type && listener.capture == capture)
+                        .map(|listener: &Listener| listener.function.clone())
+                        .collect();
+                    listeners.retain(|listener: &Listener| !(listener.once && listener.event_type == event.event_type && listener.capture == capture));
+                    matching
+                }
+                None => return,
+            };
+            event.current_target = match current {
+                Target::Node(node) => Some(node),
+                Target::Document | Target::Window => None,
+            };
+            let this: Value = match current {
+                Target::Node(node) => Value::Host(node_handle(&document.tree, node)),
+                Target::Document => Value::Host(DOCUMENT),
+                Target::Window => Value::Host(WINDOW),
+            };
+            let event_type: String = event.event_type.clone();
+            for function in functions {
+                let mut dom: Dom = Dom { document, bindings: &mut self.bindings, event: Some((event, current)) };
+                if let Err(error) = self.runtime.call(&function, this.clone(), &[Value::Host(EVENT)], &mut dom) {
+                    js::Host::console(&mut dom, "error", &format!("{} (in {} listener)", error, event_type));
+                }
+                if event.immediate_propagation_stopped() {
+                    return;
+                }
+            }
+        })
+    }
+
+    /// Run the timers and animation frame callbacks due up to `until` on the event loop's
+    /// clock (in ms), in order. Returns the number of tasks run.
+    pub fn run_tasks(&mut self, document: &mut dom::Document, until: f64) -> usize {
+        let mut count: usize = 0;
+        while let Some(task) = self.bindings.event_loop.next_task(until) {
+            let (callbacks, name): (Vec<Callback>, &str) = match task {
+                event_loop::Task::Timer(callback) => (vec![callback], "timer"),
+                event_loop::Task::AnimationFrame(functions) => {
+                    let now: Value = Value::Number(self.bindings.event_loop.now());
+                    let callbacks: Vec<Callback> = functions.into_iter()
+                        .map(|(function, _): Callback| (function, vec![now.clone()]))
+                        .collect();
+                    (callbacks, "animation frame")
+                }
+            };
+            for (function, args) in callbacks {
+                let mut dom: Dom = Dom { document, bindings: &mut self.bindings, event: None };
+                if let Err(error) = self.runtime.call(&function, Value::Host(WINDOW), &args, &mut dom) {
+                    js::Host::console(&mut dom, "error", &format!("{} (in {} callback)", error, name));
+                }
+            }
+            count += 1;
+        }
+        count
+    }
+
+    /// The time on the event loop's clock, in ms since the page was created.
+    pub fn now(&self) -> f64 {
+        self.bindings.event_loop.now()
+    }
+
+    /// When the next timer or animation frame is due on the event loop's clock, or `None`
+    /// if scripts aren't waiting for anything.
+    pub fn next_deadline(&self) -> Option<f64> {
+        self.bindings.event_loop.next_deadline()
+    }
+
+    /// Everything written to the console so far, oldest first.
+    pub fn console_messages(&self) -> &[ConsoleMessage] {
+        &self.bindings.console
+    }
+
+    /// Return what scripts changed since the last call.
+    pub fn take_changes(&mut self) -> Changes {
+        mem::take(&mut self.bindings.changes)
+    }
+
+    /// The interpreter, e.g. to define more globals.
+    pub fn runtime_mut(&mut self) -> &mut js::Runtime {
+        &mut self.runtime
+    }
+}
+
+
+/// The host side of the bindings: gives scripts access to one document while they run.
+struct Dom<'a> {
+    document: &'a mut dom::Document,
+    bindings: &'a mut Bindings,
+    event: Option<(&'a mut events::Event, Target)>, // the event being dispatched, and where it is
+}
+
+impl js::Host for Dom<'_> {
+    fn get(&mut self, object: HostObject, name: &str) -> Result<Value, String> {
+        match object.class {
+            "Window" => Ok(match name {
+                "document" => Value::Host(DOCUMENT),
+                "window" => Value::Host(WINDOW),
+                "addEventListener" | "removeEventListener" => Value::host_method(object, name),
+                _ if TIMER_FUNCTIONS.contains(&name) => Value::host_method(object, name),
+                _ => Value::Undefined,
+            }),
+            "Performance" => Ok(match name {
+                "now" => Value::host_method(object, name),
+                _ => Value::Undefined,
+            }),
+            "Document" => Ok(match name {
+                "documentElement" => self.node_value(self.document.root_element),
+                "head" => self.document.head().map_or(Value::Null, |node: dom::NodeId| self.node_value(node)),
+                "body" => self.document.body().map_or(Value::Null, |node: dom::NodeId| self.node_value(node)),
+                "title" => Value::from(self.document.title().unwrap_or_default()),
+                "URL" => Value::from(self.document.url.clone().unwrap_or_default()),
+                "getElementById" | "querySelector" | "querySelectorAll" | "getElementsByTagName" |
+                "getElementsByClassName" | "createElement" | "createTextNode" | "addEventListener" |
+                "removeEventListener" => Value::host_method(object, name),
+                _ => Value::Undefined,
+            }),
+            "Event" => Ok(self.get_event_property(object, name)),
+            "CSSStyleDeclaration" => {
+                let node: dom::NodeId = self.owner(object)?;
+                Ok(match name {
+                    "cssText" => Value::string(self.attribute(node, "style").unwrap_or("")),
+                    "length" => Value::Number(self.style_declarations(node).len() as f64),
+                    _ if STYLE_METHODS.contains(&name) => Value::host_method(object, name),
+                    _ => Value::from(self.property_value(node, &property_name(name))),
+                })
+            }
+            "DOMTokenList" => {
+                let node: dom::NodeId = self.owner(object)?;
+                Ok(match name {
+                    "length" => Value::Number(self.class_list(node).len() as f64),
+                    "value" => Value::string(self.attribute(node, "class").unwrap_or("")),
+                    _ if CLASS_LIST_METHODS.contains(&name) => Value::host_method(object, name),
+                    _ => match name.parse::<usize>() {
+                        Ok(i) => self.class_list(node).get(i).map_or(Value::Undefined, |class: &String| Value::string(class)),
+                        Err(_) => Value::Undefined,
+                    },
+                })
+            }
+            _ => {
+                let node: dom::NodeId = self.node(object)?;
+                Ok(self.get_node_property(object, node, name))
+            }
+        }
+    }
+
+    fn set(&mut self, object: HostObject, name: &str, value: Value) -> Result<(), String> {
+        match object.class {
+            "Window" | "Document" | "Event" | "Performance" => return Ok(()), // nothing to set yet
+            "CSSStyleDeclaration" => {
+                let node: dom::NodeId = self.owner(object)?;
+                match name {
+                    "cssText" => self.set_attribute(node, "style", &value.to_string()),
+                    _ => self.set_property(node, &property_name(name), &value.to_string()),
+                }
+                return Ok(());
+            }
+            "DOMTokenList" => {
+                let node: dom::NodeId = self.owner(object)?;
+                if name == "value" {
+                    self.set_attribute(node, "class", &value.to_string());
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        let node: dom::NodeId = self.node(object)?;
+        match name {
+            "id" | "className" => {
+                let attribute: &str = if name == "id" { "id" } else { "class" };
+                self.set_attribute(node, attribute, &value.to_string());
+            }
+            "textContent" | "innerText" => self.set_text_content(node, &value.to_string()),
+            "data" | "nodeValue" => {
+                if let dom::NodeType::Text(ref mut text) = self.document.tree[node].node_type {
+                    *text = value.to_string();
+                    self.bindings.changes.other = true;
+                }
+            }
+            "value" => {
+                forms::set_value(self.document, node, &value.to_string());
+                self.bindings.changes.other = true;
+            }
+            "checked" => {
+                forms::set_checked(self.document, node, value.is_truthy());
+                self.bindings.changes.other = true;
+            }
+            _ => {} // other properties can't be stored on nodes
+        }
+        Ok(())
+    }
+
+    fn call(&mut self, object: HostObject, method: &str, args: &[Value]) -> Result<Value, String> {
+        match (object.class, method) {
+            ("Window" | "Document" | "Element" | "Text" | "Node", "addEventListener" | "removeEventListener") => {
+                let target: Target = match object.class {
+                    "Window" => Target::Window,
+                    "Document" => Target::Document,
+                    _ => Target::Node(self.node(object)?),
+                };
+                self.call_listener_method(target, method, args);
+                Ok(Value::Undefined)
+            }
+            ("Event", _) => self.call_event_method(object, method),
+            ("Document", "getElementById") => {
+                let id: String = string_arg(args, 0);
+                Ok(self.document.get_element_by_id(&id).map_or(Value::Null, |node: dom::NodeId| self.node_value(node)))
+            }
+            ("Document", "createElement") => {
+                let tag_name: String = string_arg(args, 0).to_ascii_lowercase();
+                let node: dom::NodeId = self.document.tree.element(Atom::from(tag_name), dom::AttributeMap::default(), Vec::new());
+                Ok(self.node_value(node))
+            }
+            ("Document", "createTextNode") => {
+                let node: dom::NodeId = self.document.tree.text(string_arg(args, 0));
+                Ok(self.node_value(node))
+            }
+            ("Document", _) => {
+                let root: dom::NodeId = self.document.root_element;
+                // Searching the document includes its root element; searching an element doesn't.
+                self.call_search(root, true, method, args).unwrap_or_else(|| Err(not_a_function(object, method)))
+            }
+            ("Window", _) if TIMER_FUNCTIONS.contains(&method) => Ok(self.call_timer_function(method, args)),
+            ("Window", _) => Err(not_a_function(object, method)),
+            ("Performance", "now") => Ok(Value::Number(self.bindings.event_loop.now())),
+            ("Performance", _) => Err(not_a_function(object, method)),
+            ("CSSStyleDeclaration", _) => {
+                let node: dom::NodeId = self.owner(object)?;
+                self.call_style_method(object, node, method, args)
+            }
+            ("DOMTokenList", _) => {
+                let node: dom::NodeId = self.owner(object)?;
+                self.call_class_list_method(object, node, method, args)
+            }
+            _ => {
+                let node: dom::NodeId = self.node(object)?;
+                self.call_node_method(object, node, method, args)
+            }
+        }
+    }
+
+    fn console(&mut self, level: &str, message: &str) {
+        let time: f64 = self.bindings.event_loop.now();
+        self.bindings.console.push(ConsoleMessage { level: ConsoleLevel::from_method(level), text: message.to_string(), time });
+    }
+}
+
+impl Dom<'_> {
+    /// The handle for `node`.
+    fn node_value(&self, node: dom::NodeId) -> Value {
+        Value::Host(node_handle(&self.document.tree, node))
+    }
+
+    fn node_values(&self, nodes: impl IntoIterator<Item = dom::NodeId>) -> Value {
+        Value::array(nodes.into_iter().map(|node: dom::NodeId| self.node_value(node)).collect())
+    }
+
+    fn optional_node_value(&self, node: Option<dom::NodeId>) -> Value {
+        node.map_or(Value::Null, |node: dom::NodeId| self.node_value(node))
+    }
+
+    /// The node a handle refers to.
+    fn node(&self, object: HostObject) -> Result<dom::NodeId, String> {
+        match object.class {
+            "Element" | "Text" | "Node" => self.document.tree.node_id(object.id).ok_or_else(|| "The node no longer exists".to_string()),
+            class => Err(format!("Expected a node, got {}", class)),
+        }
+    }
+
+    /// The node passed as argument `i`.
+    fn node_arg(&self, args: &[Value], i: usize) -> Result<dom::NodeId, String> {
+        match args.get(i).and_then(Value::as_host) {
+            Some(object) => self.node(object),
+            None => Err(format!("Argument {} is not a node", i + 1)),
+        }
+    }
+
+    /// The element a `style` or `classList` handle belongs to.
+    fn owner(&self, object: HostObject) -> Result<dom::NodeId, String> {
+        self.document.tree.node_id(object.id).ok_or_else(|| "The element no longer exists".to_string())
+    }
+
+    fn element(&self, node: dom::NodeId) -> Option<&dom::Element> {
+        match self.document.tree[node].node_type {
+            dom::NodeType::Element(ref element) => Some(element),
+            _ => None,
+        }
+    }
+
+    fn is_element(&self, node: dom::NodeId) -> bool {
+        self.element(node).is_some()
+    }
+
+    fn get_node_property(&self, object: HostObject, node: dom::NodeId, name: &str) -> Value {
+        let tree: &dom::Tree = &self.document.tree;
+        let element: Option<&dom::Element> = self.element(node);
+        match name {
+            "tagName" => element.map_or(Value::Undefined, |element: &dom::Element| Value::from(element.tag_name.to_ascii_uppercase())),
+            "nodeName" => match tree[node].node_type {
+                dom::NodeType::Element(ref element) => Value::from(element.tag_name.to_ascii_uppercase()),
+                dom::NodeType::Text(_) => Value::string("#text"),
+                dom::NodeType::DocumentFragment => Value::string("#document-fragment"),
+            },
+            "nodeType" => Value::Number(match tree[node].node_type {
+                dom::NodeType::Element(_) => 1.0,
+                dom::NodeType::Text(_) => 3.0,
+                dom::NodeType::DocumentFragment => 11.0,
+            }),
+            "id" => Value::string(element.and_then(dom::Element::id).unwrap_or("")),
+            "className" => Value::string(element.and_then(|element: &dom::Element| element.attr("class")).unwrap_or("")),
+            "textContent" | "innerText" => Value::from(tree.text_content(node)),
+            "data" | "nodeValue" => match tree[node].node_type {
+                dom::NodeType::Text(ref text) => Value::string(text),
+                _ => Value::Null,
+            },
+            "value" => Value::from(forms::value(self.document, node)),
+            "checked" => Value::Bool(forms::is_checked(tree, node)),
+            "parentNode" => self.optional_node_value(tree[node].parent),
+            "parentElement" => self.optional_node_value(tree[node].parent.filter(|&parent: &dom::NodeId| self.is_element(parent))),
+            "children" => self.node_values(tree.children(node).filter(|&child: &dom::NodeId| self.is_element(child))),
+            "childNodes" => self.node_values(tree.children(node)),
+            "firstChild" => self.optional_node_value(tree[node].first_child),
+            "lastChild" => self.optional_node_value(tree[node].last_child),
+            "firstElementChild" => self.optional_node_value(tree.children(node).find(|&child: &dom::NodeId| self.is_element(child))),
+            "nextSibling" => self.optional_node_value(tree[node].next_sibling),
+            "previousSibling" => self.optional_node_value(tree[node].prev_sibling),
+            "isConnected" => Value::Bool(self.document.is_connected(node)),
+            "style" if element.is_some() => Value::Host(HostObject { class: "CSSStyleDeclaration", id: node.index() }),
+            "classList" if element.is_some() => Value::Host(HostObject { class: "DOMTokenList", id: node.index() }),
+            _ if ELEMENT_METHODS.contains(&name) => Value::host_method(object, name),
+            _ => Value::Undefined,
+        }
+    }
+
+    fn call_node_method(&mut self, object: HostObject, node: dom::NodeId, method: &str, args: &[Value]) -> Result<Value, String> {
+        if let Some(result) = self.call_search(node, false, method, args) {
+            return result;
+        }
+        match method {
+            "getAttribute" => {
+                let name: String = string_arg(args, 0).to_ascii_lowercase();
+                Ok(self.element(node).and_then(|element: &dom::Element| element.attr(&name)).map_or(Value::Null, Value::string))
+            }
+            "hasAttribute" => {
+                let name: String = string_arg(args, 0).to_ascii_lowercase();
+                Ok(Value::Bool(self.element(node).is_some_and(|element: &dom::Element| element.attr(&name).is_some())))
+            }
+            "setAttribute" => {
+                self.set_attribute(node, &string_arg(args, 0).to_ascii_lowercase(), &string_arg(args, 1));
+                Ok(Value::Undefined)
+            }
+            "removeAttribute" => {
+                self.remove_attribute(node, &string_arg(args, 0).to_ascii_lowercase());
+                Ok(Value::Undefined)
+            }
+            "appendChild" => {
+                let child: dom::NodeId = self.node_arg(args, 0)?;
+                self.insert_before(node, child, None)?;
+                Ok(self.node_value(child))
+            }
+            "insertBefore" => {
+                let child: dom::NodeId = self.node_arg(args, 0)?;
+                let reference: Option<dom::NodeId> = match args.get(1) {
+                    None | Some(Value::Null) | Some(Value::Undefined) => None,
+                    Some(_) => Some(self.node_arg(args, 1)?),
+                };
+                self.insert_before(node, child, reference)?;
+                Ok(self.node_value(child))
+            }
+            "removeChild" => {
+                let child: dom::NodeId = self.node_arg(args, 0)?;
+                if self.document.tree[child].parent != Some(node) {
+                    return Err("The node to be removed is not a child of this node".to_string());
+                }
+                self.document.remove_child(node, child);
+                self.bindings.changes.other = true;
+                Ok(self.node_value(child))
+            }
+            "remove" => {
+                if let Some(parent) = self.document.tree[node].parent {
+                    self.document.remove_child(parent, node);
+                    self.bindings.changes.other = true;
+                }
+                Ok(Value::Undefined)
+            }
+            "cloneNode" => {
+                let clone: dom::NodeId = if args.first().is_some_and(Value::is_truthy) {
+                    self.document.tree.deep_clone(node)
+                } else {
+                    let node_type: dom::NodeType = self.document.tree[node].node_type.clone();
+                    match node_type {
+                        dom::NodeType::Element(element) => self.document.tree.element(element.tag_name, element.attributes, Vec::new()),
+                        dom::NodeType::Text(text) => self.document.tree.text(text),
+                        dom::NodeType::DocumentFragment => self.document.tree.fragment(Vec::new()),
+                    }
+                };
+                Ok(self.node_value(clone))
+            }
+            "contains" => {
+                let other: dom::NodeId = self.node_arg(args, 0)?;
+                Ok(Value::Bool(self.document.tree.ancestors(other).any(|ancestor: dom::NodeId| ancestor == node)))
+            }
+            "hasChildNodes" => Ok(Value::Bool(self.document.tree[node].first_child.is_some())),
+            "matches" => {
+                let selectors: Vec<css::Selector> = css::parse_selector_list(&string_arg(args, 0))?;
+                Ok(Value::Bool(self.matches(node, &selectors)))
+            }
+            "closest" => {
+                let selectors: Vec<css::Selector> = css::parse_selector_list(&string_arg(args, 0))?;
+                let found: Option<dom::NodeId> = self.document.tree.ancestors(node).find(|&ancestor: &dom::NodeId| self.matches(ancestor, &selectors));
+                Ok(self.optional_node_value(found))
+            }
+            "toString" => Ok(Value::from(format!("[object {}]", object.class))),
+            _ => Err(not_a_function(object, method)),
+        }
+    }
+
+    /// The lookups documents and elements share. `None` if `method` isn't one of them.
+    fn call_search(&self, root: dom::NodeId, include_root: bool, method: &str, args: &[Value]) -> Option<Result<Value, String>> {
+        let candidates = || self.document.tree.descendants(root).filter(move |&node: &dom::NodeId| include_root || node != root);
+        let result: Result<Value, String> = match method {
+            "querySelector" | "querySelectorAll" => css::parse_selector_list(&string_arg(args, 0)).map(|selectors: Vec<css::Selector>| {
+                let mut found = candidates().filter(|&node: &dom::NodeId| self.matches(node, &selectors));
+                if method == "querySelector" { self.optional_node_value(found.next()) } else { self.node_values(found) }
+            }),
+            "getElementsByTagName" => {
+                let tag_name: String = string_arg(args, 0).to_ascii_lowercase();
+                Ok(self.node_values(candidates().filter(|&node: &dom::NodeId| {
+                    self.element(node).is_some_and(|element: &dom::Element| tag_name == "*" || element.tag_name == tag_name.as_str())
+                })))
+            }
+            "getElementsByClassName" => {
+                let class_name: String = string_arg(args, 0);
+                Ok(self.node_values(candidates().filter(|&node: &dom::NodeId| {
+                    self.element(node).is_some_and(|element: &dom::Element| element.has_class(&class_name))
+                })))
+            }
+            _ => return None,
+        };
+        Some(result)
+    }
+
+    /// Return true if `node` is an element matching any of `selectors`.
+    fn matches(&self, node: dom::NodeId, selectors: &[css::Selector]) -> bool {
+        let states: style::ElementStates = style::ElementStates::default();
+        match self.element(node) {
+            Some(element) => selectors.iter().any(|selector: &css::Selector| style::matches(element, node, selector, &states)),
+            None => false,
+        }
+    }
+
+    /// Insert a node, checking first for the cases the tree would panic on.
+    fn insert_before(&mut self, parent: dom::NodeId, child: dom::NodeId, reference: Option<dom::NodeId>) -> Result<(), String> {
+        if self.document.tree.ancestors(parent).any(|ancestor: dom::NodeId| ancestor == child) {
+            return Err("The new child element contains the parent".to_string());
+        }
+        if let Some(reference) = reference {
+            if self.document.tree[reference].parent != Some(parent) {
+                return Err("The node before which the new node is to be inserted is not a child of this node".to_string());
+            }
+        }
+        self.document.insert_before(parent, child, reference);
+        self.bindings.changes.other = true;
+        Ok(())
+    }
+
+    /// Replace the children of `node` with one text node.
+    fn set_text_content(&mut self, node: dom::NodeId, text: &str) {
+        self.bindings.changes.other = true;
+        if let dom::NodeType::Text(ref mut data) = self.document.tree[node].node_type {
+            *data = text.to_string();
+            return;
+        }
+        let children: Vec<dom::NodeId> = self.document.tree.children(node).collect();
+        for child in children {
+            self.document.remove_child(node, child);
+        }
+        if !text.is_empty() {
+            let text_node: dom::NodeId = self.document.tree.text(text.to_string());
+            self.document.append_child(node, text_node);
+        }
+    }
+}
+
+impl Dom<'_> {
+    // Attributes, and what changing them means for the page
+
+    fn attribute(&self, node: dom::NodeId, name: &str) -> Option<&str> {
+        self.element(node).and_then(|element: &dom::Element| element.attr(name))
+    }
+
+    /// Keep a copy of `node` before its first change to an attribute selectors or inline
+    /// styles can see, or note that the page needs a new layout for other attributes.
+    fn record_attribute_change(&mut self, node: dom::NodeId, name: &str) {
+        match self.document.tree[node].node_type {
+            dom::NodeType::Element(ref element) if matches!(name, "id" | "class" | "style") => {
+                self.bindings.changes.restyled.entry(node).or_insert_with(|| element.clone());
+            }
+            _ => self.bindings.changes.other = true,
+        }
+    }
+
+    fn set_attribute(&mut self, node: dom::NodeId, name: &str, value: &str) {
+        self.record_attribute_change(node, name);
+        self.document.set_attribute(node, name, value);
+    }
+
+    fn remove_attribute(&mut self, node: dom::NodeId, name: &str) {
+        self.record_attribute_change(node, name);
+        self.document.remove_attribute(node, name);
+    }
+
+
+    // element.style
+
+    /// The declarations in the `style` attribute of `node`, as names and value text.
+    fn style_declarations(&self, node: dom::NodeId) -> Vec<(String, String)> {
+        let style: &str = self.attribute(node, "style").unwrap_or("");
+        style.split(';')
+            .filter_map(|declaration: &str| declaration.split_once(':'))
+            .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+            .filter(|(name, _)| !name.is_empty())
+            .collect()
+    }
+
+    /// Write `declarations` back to the `style` attribute, removing it if there are none.
+    fn set_style_declarations(&mut self, node: dom::NodeId, declarations: &[(String, String)]) {
+        if declarations.is_empty() {
+            self.remove_attribute(node, "style");
+        } else {
+            let style: Vec<String> = declarations.iter().map(|(name, value)| format!("{}: {}", name, value)).collect();
+            self.set_attribute(node, "style", &style.join("; "));
+        }
+    }
+
+    /// The value text of the inline declaration of `name`, or "" if there is none.
+    fn property_value(&self, node: dom::NodeId, name: &str) -> String {
+        self.style_declarations(node).into_iter()
+            .rfind(|(declared, _)| declared == name)
+            .map_or(String::new(), |(_, value)| value)
+    }
+
+    /// Set the inline declaration of `name`. An empty value removes it, and one the
+    /// inline-declaration parser rejects is ignored, as in browsers.
+    fn set_property(&mut self, node: dom::NodeId, name: &str, value: &str) {
+        let value: &str = value.trim();
+        if value.is_empty() {
+            self.remove_property(node, name);
+            return;
+        }
+        if css::parse_inline(&format!("{}: {}", name, value)).is_empty() {
+            return;
+        }
+        let mut declarations: Vec<(String, String)> = self.style_declarations(node);
+        declarations.retain(|(declared, _)| declared != name);
+        declarations.push((name.to_string(), value.to_string()));
+        self.set_style_declarations(node, &declarations);
+    }
+
+    /// Remove the inline declaration of `name`, returning its old value text.
+    fn remove_property(&mut self, node: dom::NodeId, name: &str) -> String {
+        let old: String = self.property_value(node, name);
+        let mut declarations: Vec<(String, String)> = self.style_declarations(node);
+        let count: usize = declarations.len();
+        declarations.retain(|(declared, _)| declared != name);
+        if declarations.len() != count {
+            self.set_style_declarations(node, &declarations);
+        }
+        old
+    }
+
+    fn call_style_method(&mut self, object: HostObject, node: dom::NodeId, method: &str, args: &[Value]) -> Result<Value, String> {
+        let name: String = string_arg(args, 0).trim().to_ascii_lowercase();
+        match method {
+            "getPropertyValue" => Ok(Value::from(self.property_value(node, &name))),
+            "setProperty" => {
+                let value: String = args.get(1).map_or(String::new(), Value::to_string);
+                self.set_property(node, &name, &value);
+                Ok(Value::Undefined)
+            }
+            "removeProperty" => Ok(Value::from(self.remove_property(node, &name))),
+            _ => Err(not_a_function(object, method)),
+        }
+    }
+
+
+    // element.classList
+
+    /// The classes of `node`, in order and without duplicates.
+    fn class_list(&self, node: dom::NodeId) -> Vec<String> {
+        let mut classes: Vec<String> = Vec::new();
+        for class in self.attribute(node, "class").unwrap_or("").split_whitespace() {
+            if !classes.iter().any(|existing: &String| existing == class) {
+                classes.push(class.to_string());
+            }
+        }
+        classes
+    }
+
+    fn call_class_list_method(&mut self, object: HostObject, node: dom::NodeId, method: &str, args: &[Value]) -> Result<Value, String> {
+        // Every argument is a token, except the `force` flag of `toggle`.
+        let token_args: &[Value] = if method == "toggle" { &args[..args.len().min(1)] } else { args };
+        let tokens: Vec<String> = token_args.iter().map(Value::to_string).collect();
+        if let Some(token) = tokens.iter().find(|token: &&String| token.is_empty() || token.contains(char::is_whitespace)) {
+            return Err(format!("The token '{}' is empty or contains whitespace", token));
+        }
+        let mut classes: Vec<String> = self.class_list(node);
+        let result: Value = match method {
+            "contains" => return Ok(Value::Bool(classes.contains(&string_arg(args, 0)))),
+            "add" => {
+                for token in tokens {
+                    if !classes.contains(&token) {
+                        classes.push(token);
+                    }
+                }
+                Value::Undefined
+            }
+            "remove" => {
+                classes.retain(|class: &String| !tokens.contains(class));
+                Value::Undefined
+            }
+            "toggle" => {
+                let token: String = string_arg(args, 0);
+                let present: bool = classes.contains(&token);
+                let wanted: bool = match args.get(1) {
+                    Some(force) if !matches!(force, Value::Undefined) => force.is_truthy(),
+                    _ => !present,
+                };
+                if wanted && !present {
+                    classes.push(token);
+                } else if !wanted && present {
+                    classes.retain(|class: &String| *class != token);
+                }
+                Value::Bool(wanted)
+            }
+            "replace" => {
+                let (old, new): (String, String) = (string_arg(args, 0), string_arg(args, 1));
+                match classes.iter().position(|class: &String| *class == old) {
+                    Some(i) => {
+                        classes[i] = new.clone();
+                        // Keep the first occurrence if `new` was already there.
+                        let mut seen: bool = false;
+                        classes.retain(|class: &String| *class != new || !mem::replace(&mut seen, true));
+                        Value::Bool(true)
+                    }
+                    None => return Ok(Value::Bool(false)),
+                }
+            }
+            _ => return Err(not_a_function(object, method)),
+        };
+        if classes != self.class_list(node) {
+            self.set_attribute(node, "class", &classes.join(" "));
+        }
+        Ok(result)
+    }
+}
+
+impl Dom<'_> {
+    // Timers
+
+    fn call_timer_function(&mut self, method: &str, args: &[Value]) -> Value {
+        let event_loop: &mut EventLoop<Callback> = &mut self.bindings.event_loop;
+        let function: Option<Value> = args.first().filter(|function: &&Value| function.is_function()).cloned();
+        let id: TaskId = match (method, function) {
+            ("setTimeout" | "setInterval", Some(function)) => {
+                let delay: f64 = args.get(1).map_or(0.0, Value::to_number);
+                let extra: Vec<Value> = args.iter().skip(2).cloned().collect();
+                event_loop.set_timer((function, extra), delay, method == "setInterval")
+            }
+            ("requestAnimationFrame", Some(function)) => event_loop.request_animation_frame((function, Vec::new())),
+            ("setTimeout" | "setInterval" | "requestAnimationFrame", None) => return Value::Number(0.0), // e.g. a string of code
+            _ => {
+                // As in browsers, `clearTimeout` and `clearInterval` can clear either kind of timer.
+                let id: f64 = args.first().map_or(0.0, Value::to_number);
+                if id >= 1.0 && id <= TaskId::MAX as f64 {
+                    if method == "cancelAnimationFrame" {
+                        event_loop.cancel_animation_frame(id as TaskId);
+                    } else {
+                        event_loop.clear_timer(id as TaskId);
+                    }
+                }
+                return Value::Undefined;
+            }
+        };
+        Value::Number(id as f64)
+    }
+
+
+    // Events
+
+    /// Add or remove a listener, ignoring functions that are already (or not) registered.
+    fn call_listener_method(&mut self, target: Target, method: &str, args: &[Value]) {
+        let event_type: String = string_arg(args, 0);
+        let function: Value = match args.get(1) {
+            Some(function) if function.is_function() => function.clone(),
+            _ => return, // e.g. `null`, which browsers ignore too
+        };
+        let (capture, once) = match args.get(2) {
+            Some(Value::Object(options)) => (
+                options.get("capture").is_some_and(|capture: Value| capture.is_truthy()),
+                options.get("once").is_some_and(|once: Value| once.is_truthy()),
+            ),
+            Some(capture) => (capture.is_truthy(), false),
+            None => (false, false),
+        };
+        let listeners: &mut Vec<Listener> = self.bindings.listeners.entry(target).or_default();
+        let position: Option<usize> = listeners.iter().position(|listener: &Listener| {
+            listener.event_type == event_type && listener.capture == capture && listener.function.strict_equals(&function)
+        });
+        match (method, position) {
+            ("addEventListener", None) => listeners.push(Listener { event_type, capture, once, function }),
+            ("removeEventListener", Some(i)) => {
+                listeners.remove(i);
+            }
+            _ => {}
+        }
+    }
+
+    fn get_event_property(&self, object: HostObject, name: &str) -> Value {
+        let (event, current) = match self.event {
+            Some((ref event, current)) => (event, current),
+            None => return Value::Undefined, // kept after its dispatch ended
+        };
+        match name {
+            "type" => Value::string(&event.event_type),
+            "target" => self.optional_node_value(event.target),
+            "currentTarget" => match current {
+                Target::Node(node) => self.node_value(node),
+                Target::Document => Value::Host(DOCUMENT),
+                Target::Window => Value::Host(WINDOW),
+            },
+            "eventPhase" => Value::Number(match event.phase {
+                events::Phase::None => 0.0,
+                events::Phase::Capturing => 1.0,
+                events::Phase::AtTarget => 2.0,
+                events::Phase::Bubbling => 3.0,
+            }),
+            "bubbles" => Value::Bool(event.bubbles),
+            "cancelable" => Value::Bool(event.cancelable),
+            "defaultPrevented" => Value::Bool(event.default_prevented),
+            _ if EVENT_METHODS.contains(&name) => Value::host_method(object, name),
+            _ => Value::Undefined,
+        }
+    }
+
+    fn call_event_method(&mut self, object: HostObject, method: &str) -> Result<Value, String> {
+        let event: Option<&mut events::Event> = self.event.as_mut().map(|(event, _)| &mut **event);
+        match (method, event) {
+            ("preventDefault", Some(event)) => event.prevent_default(),
+            ("stopPropagation", Some(event)) => event.stop_propagation(),
+            ("stopImmediatePropagation", Some(event)) => event.stop_immediate_propagation(),
+            (_, _) if EVENT_METHODS.contains(&method) => {} // too late to change anything
+            _ => return Err(not_a_function(object, method)),
+        }
+        Ok(Value::Undefined)
+    }
+}
+
+/// The handle for `node`, whose class depends on the kind of node.
+pub fn node_handle(tree: &dom::Tree, node: dom::NodeId) -> HostObject {
+    let class: &'static str = match tree[node].node_type {
+        dom::NodeType::Element(_) => "Element",
+        dom::NodeType::Text(_) => "Text",
+        dom::NodeType::DocumentFragment => "Node",
+    };
+    HostObject { class, id: node.index() }
+}
+
+/// The CSS name of a `style` property, e.g. "margin-top" for `marginTop`.
+fn property_name(name: &str) -> String {
+    let mut css_name: String = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            css_name.push('-');
+        }
+        css_name.push(c.to_ascii_lowercase());
+    }
+    css_name
+}
+
+/// Argument `i` as a string (`undefined` if missing, as in browsers).
+fn string_arg(args: &[Value], i: usize) -> String {
+    args.get(i).map_or("undefined".to_string(), Value::to_string)
+}
+
+fn not_a_function(object: HostObject, method: &str) -> String {
+    format!("{}.{} is not a function", object.class.to_ascii_lowercase(), method)
+}
@@ -0,0 +1,895 @@
+//! Text shaping: choosing and placing the glyphs for a run of text with a font's
+//! OpenType tables.
+
+#[cfg(feature = "std")]
+use crate::fonts::{Glyph, SOFT_HYPHEN};
+#[cfg(feature = "std")]
+use ttf_parser::{gdef, gpos, gsub, opentype_layout as layout, GlyphId, Tag};
+
+/*
+    Shaping
+
+    Drawing text isn't just looking up a glyph for each character. In many writing systems
+    the glyph for a character depends on its neighbors:
+      - Arabic letters join up, and most have four shapes: on their own ("isolated"), and
+        at the start, in the middle, and at the end of a word.
+      - In Devanagari and the other Indic scripts, some vowel signs are written before the
+        consonant they follow in the text, and clusters of consonants merge into conjuncts.
+      - In Latin text, "fi" may be drawn as one ligature glyph, and accents sit over their
+        letters.
+
+    A font says how in its OpenType tables. GSUB substitutes glyphs: one for another, one
+    for many, many for one, or any of these only in some context. GPOS moves them: kerning
+    between pairs, and anchoring marks onto the glyphs they belong to. Each table lists its
+    rules as "lookups", grouped by script into named "features" (`liga`, `init`, `kern`,
+    ...). Shaping a run:
+        1. Find the features its script uses (see `Script`).
+        2. Map the characters to glyphs with the font's `cmap`, after moving Indic pre-base
+           vowel signs in front of their consonants, and work out the joining form of
+           each Arabic letter.
+        3. Apply the GSUB lookups of those features, in the font's order. The joining-form
+           features (`init`, `medi`, `fina`, `isol`) only apply to letters in that form.
+        4. Give each glyph its advance, then apply the GPOS lookups. A font without GPOS
+           kerning may still have an old `kern` table, which is used instead.
+
+    Arabic and Hebrew are written right to left. Glyphs stay in the order of their
+    characters, each placed along the run from its start, so line breaking treats them
+    like any other text; a right-to-left run is just drawn mirrored (see
+    `fonts::TextRun::glyph_origin`). Runs aren't reordered, so on a line that mixes
+    directions each run reads correctly, but the runs stay in text order.
+
+    Each glyph keeps the cluster (the byte offset of the first character) it came from.
+    When characters merge into one glyph, or are reordered, their glyphs share the
+    earliest cluster, so clusters stay in order and a line never breaks inside one.
+
+    This is much less than a full shaper like HarfBuzz. Indic syllables aren't analyzed, so
+    features meant for part of a syllable (like `half` and `rphf`) apply wherever the font
+    allows, the reph isn't moved, and two-part vowel signs aren't split. There's no
+    cursive attachment (for Nastaliq), no reverse chaining substitution, and nothing
+    specific to Thai, Khmer, or Myanmar. Fonts for the common cases still come out right,
+    because they keep most of the knowledge in their own lookups.
+ */
+
+/// The scripts that shaping treats differently.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Script {
+    Common, // spaces, digits, punctuation, symbols, and marks, which go with any script
+    Latin,
+    Greek,
+    Cyrillic,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Bengali,
+    Gurmukhi,
+    Gujarati,
+    Oriya,
+    Tamil,
+    Telugu,
+    Kannada,
+    Malayalam,
+    Thai,
+    Other, // letters of any other script
+}
+
+impl Script {
+    /// The script character `c` belongs to.
+    pub fn of(c: char) -> Script {
+        match c as u32 {
+            0x41..=0x5A | 0x61..=0x7A | 0xAA | 0xBA | 0xC0..=0xD6 | 0xD8..=0xF6 | 0xF8..=0x24F |
+            0x1E00..=0x1EFF | 0x2C60..=0x2C7F | 0xA720..=0xA7FF | 0xFB00..=0xFB06 => Script::Latin,
+            0x370..=0x3FF | 0x1F00..=0x1FFF => Script::Greek,
+            0x400..=0x52F | 0x1C80..=0x1C8F | 0x2DE0..=0x2DFF | 0xA640..=0xA69F => Script::Cyrillic,
+            0x591..=0x5F4 | 0xFB1D..=0xFB4F => Script::Hebrew,
+            0x600..=0x6FF | 0x750..=0x77F | 0x8A0..=0x8FF | 0xFB50..=0xFDFF | 0xFE70..=0xFEFC => Script::Arabic,
+            0x964 | 0x965 => Script::Common, // dandas, shared by the Indic scripts
+            0x900..=0x97F | 0xA8E0..=0xA8FF => Script::Devanagari,
+            0x980..=0x9FF => Script::Bengali,
+            0xA00..=0xA7F => Script::Gurmukhi,
+            0xA80..=0xAFF => Script::Gujarati,
+            0xB00..=0xB7F => Script::Oriya,
+            0xB80..=0xBFF => Script::Tamil,
+            0xC00..=0xC7F => Script::Telugu,
+            0xC80..=0xCFF => Script::Kannada,
+            0xD00..=0xD7F => Script::Malayalam,
+            0xE00..=0xE7F => Script::Thai,
+            _ if c.is_alphabetic() => Script::Other,
+            _ => Script::Common,
+        }
+    }
+
+    /// The script of the first letter in `text`, or `Common` if it has none.
+    pub fn of_text(text: &str) -> Script {
+        text.chars().map(Script::of).find(|&script: &Script| script != Script::Common).unwrap_or(Script::Common)
+    }
+
+    /// Return true if the script is written right to left.
+    pub fn is_rtl(self) -> bool {
+        matches!(self, Script::Arabic | Script::Hebrew)
+    }
+
+    /// Return true for the scripts with Indic shaping.
+    #[cfg(feature = "std")]
+    fn is_indic(self) -> bool {
+        matches!(self, Script::Devanagari | Script::Bengali | Script::Gurmukhi | Script::Gujarati | Script::Oriya |
+                       Script::Tamil | Script::Telugu | Script::Kannada | Script::Malayalam)
+    }
+
+    /// The script's OpenType tags, newest first. Fonts for Indic scripts may have the
+    /// lookups for either the new or the old way of shaping them.
+    #[cfg(feature = "std")]
+    fn tags(self) -> &'static [&'static [u8; 4]] {
+        match self {
+            Script::Latin => &[b"latn"],
+            Script::Greek => &[b"grek"],
+            Script::Cyrillic => &[b"cyrl"],
+            Script::Arabic => &[b"arab"],
+            Script::Hebrew => &[b"hebr"],
+            Script::Devanagari => &[b"dev2", b"deva"],
+            Script::Bengali => &[b"bng2", b"beng"],
+            Script::Gurmukhi => &[b"gur2", b"guru"],
+            Script::Gujarati => &[b"gjr2", b"gujr"],
+            Script::Oriya => &[b"ory2", b"orya"],
+            Script::Tamil => &[b"tml2", b"taml"],
+            Script::Telugu => &[b"tel2", b"telu"],
+            Script::Kannada => &[b"knd2", b"knda"],
+            Script::Malayalam => &[b"mlm2", b"mlym"],
+            Script::Thai => &[b"thai"],
+            Script::Common | Script::Other => &[],
+        }
+    }
+}
+
+
+/**
+ *  Features
+ *
+ *  The features each script uses, in the order a shaper applies them. The font decides
+ *  which it has; missing ones are skipped.
+ */
+#[cfg(feature = "std")]
+const COMMON_FEATURES: [&[u8; 4]; 2] = [b"ccmp", b"locl"];
+
+#[cfg(feature = "std")]
+const ARABIC_FEATURES: [&[u8; 4]; 5] = [b"rlig", b"calt", b"liga", b"clig", b"mset"];
+
+#[cfg(feature = "std")]
+const INDIC_FEATURES: [&[u8; 4]; 17] = [
+    b"nukt", b"akhn", b"rphf", b"rkrf", b"pref", b"blwf", b"abvf", b"half", b"pstf", b"vatu", b"cjct",
+    b"pres", b"abvs", b"blws", b"psts", b"haln", b"calt",
+];
+
+#[cfg(feature = "std")]
+const DEFAULT_FEATURES: [&[u8; 4]; 4] = [b"rlig", b"calt", b"liga", b"clig"];
+
+#[cfg(feature = "std")]
+const POSITIONING_FEATURES: [&[u8; 4]; 6] = [b"kern", b"dist", b"abvm", b"blwm", b"mark", b"mkmk"];
+
+/// How an Arabic letter joins its neighbors.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Form {
+    Isolated,
+    Initial,
+    Medial,
+    Final,
+}
+
+#[cfg(feature = "std")]
+impl Form {
+    /// The feature with the form's glyphs.
+    fn feature(self) -> &'static [u8; 4] {
+        match self {
+            Form::Isolated => b"isol",
+            Form::Initial => b"init",
+            Form::Medial => b"medi",
+            Form::Final => b"fina",
+        }
+    }
+
+    /// The mask bit for glyphs in this form; see `Info::mask`.
+    fn mask(self) -> u8 {
+        2 << self as u8
+    }
+}
+
+/// The mask bit every glyph has, for features that apply everywhere.
+#[cfg(feature = "std")]
+const ALL: u8 = 1;
+
+
+/**
+ *  The Buffer
+ */
+/// A glyph while it's being shaped. Positions are in font units.
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, Debug)]
+struct Info {
+    id: GlyphId,
+    cluster: usize,
+    mask: u8, // which features apply to it: `ALL`, plus its joining form's bit
+    advance: i32,
+    offset: (i32, i32), // right and up from where its advance puts it
+    attached: Option<(usize, i32, i32)>, // a mark's base glyph, and how far right and up from it it goes
+}
+
+/// Shape `text`, all in `script`, with the font `face`, at `size` px.
+#[cfg(feature = "std")]
+pub(crate) fn shape(face: &ttf_parser::Face, text: &str, script: Script, size: f32) -> Vec<Glyph> {
+    let mut shaper: Shaper = Shaper {
+        face,
+        gdef: face.tables().gdef,
+        glyphs: Vec::with_capacity(text.len()),
+    };
+    shaper.map_characters(text, script);
+
+    let gsub: Option<layout::LayoutTable> = face.tables().gsub;
+    if let Some(gsub) = gsub {
+        let mut features: Vec<(&[u8; 4], u8)> = COMMON_FEATURES.iter().map(|&tag: &&[u8; 4]| (tag, ALL)).collect();
+        let more: &[&[u8; 4]] = match script {
+            Script::Arabic => {
+                for form in [Form::Isolated, Form::Final, Form::Medial, Form::Initial] {
+                    features.push((form.feature(), form.mask()));
+                }
+                &ARABIC_FEATURES
+            }
+            script if script.is_indic() => &INDIC_FEATURES,
+            _ => &DEFAULT_FEATURES,
+        };
+        features.extend(more.iter().map(|&tag: &&[u8; 4]| (tag, ALL)));
+        for (index, mask) in lookups(&gsub, script, &features) {
+            shaper.substitute_all(&gsub, index, mask);
+        }
+    }
+
+    shaper.set_advances();
+    let gpos: Option<layout::LayoutTable> = face.tables().gpos;
+    let positioning: Vec<(u16, u8)> = gpos.map_or_else(Vec::new, |gpos: layout::LayoutTable| {
+        let features: Vec<(&[u8; 4], u8)> = POSITIONING_FEATURES.iter().map(|&tag: &&[u8; 4]| (tag, ALL)).collect();
+        lookups(&gpos, script, &features)
+    });
+    let has_kerning: bool = gpos.is_some_and(|gpos: layout::LayoutTable| has_feature(&gpos, script, b"kern"));
+    if let Some(gpos) = gpos {
+        for &(index, mask) in &positioning {
+            shaper.position_all(&gpos, index, mask);
+        }
+    }
+    if !has_kerning {
+        shaper.apply_kern_table();
+    }
+    shaper.glyphs(script.is_rtl(), size / face.units_per_em() as f32)
+}
+
+#[cfg(feature = "std")]
+struct Shaper<'a> {
+    face: &'a ttf_parser::Face<'a>,
+    gdef: Option<gdef::Table<'a>>,
+    glyphs: Vec<Info>,
+}
+
+#[cfg(feature = "std")]
+impl<'a> Shaper<'a> {
+    /// Fill the buffer with a glyph for each character of `text`.
+    fn map_characters(&mut self, text: &str, script: Script) {
+        let mut chars: Vec<(char, usize)> = text.char_indices().map(|(cluster, c): (usize, char)| (c, cluster)).collect();
+        if script.is_indic() {
+            reorder_indic(&mut chars);
+        }
+        let forms: Vec<Option<Form>> = if script == Script::Arabic {
+            joining_forms(&chars)
+        } else {
+            vec![None; chars.len()]
+        };
+        for (&(c, cluster), form) in chars.iter().zip(forms) {
+            if c == SOFT_HYPHEN {
+                continue; // drawn by layout, if the line breaks there (see `hyphenation`)
+            }
+            // A tab is drawn as a space, and layout stretches it to its tab stop.
+            let id: GlyphId = self.face.glyph_index(if c == '\t' { ' ' } else { c }).unwrap_or(GlyphId(0));
+            let mask: u8 = ALL | form.map_or(0, Form::mask);
+            self.glyphs.push(Info { id, cluster, mask, advance: 0, offset: (0, 0), attached: None });
+        }
+    }
+
+    /// Give each glyph its advance from the font. Marks don't advance.
+    fn set_advances(&mut self) {
+        for i in 0..self.glyphs.len() {
+            let id: GlyphId = self.glyphs[i].id;
+            self.glyphs[i].advance = if self.is_mark(id) { 0 } else { self.face.glyph_hor_advance(id).unwrap_or(0) as i32 };
+        }
+    }
+
+    fn is_mark(&self, id: GlyphId) -> bool {
+        self.gdef.is_some_and(|gdef: gdef::Table| gdef.glyph_class(id) == Some(gdef::GlyphClass::Mark))
+    }
+
+    /// Return true if `lookup` passes over glyph `id`, as its flags say.
+    fn skips(&self, lookup: &layout::Lookup, id: GlyphId) -> bool {
+        let gdef: gdef::Table = match self.gdef {
+            Some(gdef) => gdef,
+            None => return false,
+        };
+        let flags: layout::LookupFlags = lookup.flags;
+        match gdef.glyph_class(id) {
+            Some(gdef::GlyphClass::Base) => flags.ignore_base_glyphs(),
+            Some(gdef::GlyphClass::Ligature) => flags.ignore_ligatures(),
+            Some(gdef::GlyphClass::Mark) => {
+                flags.ignore_marks()
+                    || (flags.use_mark_filtering_set() && !gdef.is_mark_glyph(id, lookup.mark_filtering_set))
+                    || (flags.mark_attachment_type() != 0 && gdef.glyph_mark_attachment_class(id) != flags.mark_attachment_type() as u16)
+            }
+            _ => false,
+        }
+    }
+
+    /// The first glyph at or after `from` that `lookup` doesn't pass over.
+    fn next(&self, lookup: &layout::Lookup, from: usize) -> Option<usize> {
+        (from..self.glyphs.len()).find(|&j: &usize| !self.skips(lookup, self.glyphs[j].id))
+    }
+
+    /// The last glyph before `before` that `lookup` doesn't pass over.
+    fn previous(&self, lookup: &layout::Lookup, before: usize) -> Option<usize> {
+        (0..before).rev().find(|&j: &usize| !self.skips(lookup, self.glyphs[j].id))
+    }
+
+    /// The glyphs from `start` matching `count` more inputs after the first, as `matches`
+    /// says (with the input's index), or `None` if they don't match.
+    fn match_input(&self, lookup: &layout::Lookup, start: usize, count: u16, matches: impl Fn(u16, GlyphId) -> bool) -> Option<Vec<usize>> {
+        let mut positions: Vec<usize> = vec![start];
+        for k in 0..count {
+            let j: usize = self.next(lookup, positions[positions.len() - 1] + 1)?;
+            if !matches(k, self.glyphs[j].id) {
+                return None;
+            }
+            positions.push(j);
+        }
+        Some(positions)
+    }
+
+    /// Return true if the glyphs before the matched input `(start, end)` match `backtrack`
+    /// inputs (nearest first), and the glyphs after it match `lookahead` inputs.
+    fn match_around(&self, lookup: &layout::Lookup, (start, end): (usize, usize), backtrack: u16, lookahead: u16,
+                    matches_backtrack: impl Fn(u16, GlyphId) -> bool, matches_lookahead: impl Fn(u16, GlyphId) -> bool) -> bool {
+        let mut j: usize = start;
+        for k in 0..backtrack {
+            match self.previous(lookup, j) {
+                Some(previous) if matches_backtrack(k, self.glyphs[previous].id) => j = previous,
+                _ => return false,
+            }
+        }
+        let mut j: usize = end;
+        for k in 0..lookahead {
+            match self.next(lookup, j + 1) {
+                Some(next) if matches_lookahead(k, self.glyphs[next].id) => j = next,
+                _ => return false,
+            }
+        }
+        true
+    }
+
+    /// Match a contextual subtable at glyph `i`, returning the matched input glyphs and
+    /// the lookups to apply to them.
+    fn match_context(&self, lookup: &layout::Lookup, context: &layout::ContextLookup<'a>, i: usize)
+                     -> Option<(Vec<usize>, ttf_parser::LazyArray16<'a, layout::SequenceLookupRecord>)> {
+        let first: GlyphId = self.glyphs[i].id;
+        match *context {
+            layout::ContextLookup::Format1 { coverage, sets } => {
+                let set: layout::SequenceRuleSet = sets.get(coverage.get(first)?)?;
+                set.into_iter().find_map(|rule: layout::SequenceRule<'a>| {
+                    let positions: Vec<usize> = self.match_input(lookup, i, rule.input.len(), |k: u16, id: GlyphId| rule.input.get(k) == Some(id.0))?;
+                    Some((positions, rule.lookups))
+                })
+            }
+            layout::ContextLookup::Format2 { coverage, classes, sets } => {
+                coverage.get(first)?;
+                let set: layout::SequenceRuleSet = sets.get(classes.get(first))?;
+                set.into_iter().find_map(|rule: layout::SequenceRule<'a>| {
+                    let positions: Vec<usize> = self.match_input(lookup, i, rule.input.len(), |k: u16, id: GlyphId| rule.input.get(k) == Some(classes.get(id)))?;
+                    Some((positions, rule.lookups))
+                })
+            }
+            layout::ContextLookup::Format3 { coverage, coverages, lookups } => {
+                coverage.get(first)?;
+                let positions: Vec<usize> = self.match_input(lookup, i, coverages.len(), |k: u16, id: GlyphId| coverages.get(k).is_some_and(|c: layout::Coverage| c.contains(id)))?;
+                Some((positions, lookups))
+            }
+        }
+    }
+
+    /// Match a chained contextual subtable at glyph `i`, as `match_context` does.
+    fn match_chained_context(&self, lookup: &layout::Lookup, context: &layout::ChainedContextLookup<'a>, i: usize)
+                             -> Option<(Vec<usize>, ttf_parser::LazyArray16<'a, layout::SequenceLookupRecord>)> {
+        let first: GlyphId = self.glyphs[i].id;
+        match *context {
+            layout::ChainedContextLookup::Format1 { coverage, sets } => {
+                let set: layout::ChainedSequenceRuleSet = sets.get(coverage.get(first)?)?;
+                set.into_iter().find_map(|rule: layout::ChainedSequenceRule<'a>| {
+                    let glyph = |array: ttf_parser::LazyArray16<'a, u16>| move |k: u16, id: GlyphId| array.get(k) == Some(id.0);
+                    let positions: Vec<usize> = self.match_input(lookup, i, rule.input.len(), glyph(rule.input))?;
+                    let end: usize = positions[positions.len() - 1];
+                    self.match_around(lookup, (i, end), rule.backtrack.len(), rule.lookahead.len(), glyph(rule.backtrack), glyph(rule.lookahead))
+                        .then_some((positions, rule.lookups))
+                })
+            }
+            layout::ChainedContextLookup::Format2 { coverage, backtrack_classes, input_classes, lookahead_classes, sets } => {
+                coverage.get(first)?;
+                let set: layout::ChainedSequenceRuleSet = sets.get(input_classes.get(first))?;
+                set.into_iter().find_map(|rule: layout::ChainedSequenceRule<'a>| {
+                    let class = |array: ttf_parser::LazyArray16<'a, u16>, classes: layout::ClassDefinition<'a>| {
+                        move |k: u16, id: GlyphId| array.get(k) == Some(classes.get(id))
+                    };
+                    let positions: Vec<usize> = self.match_input(lookup, i, rule.input.len(), class(rule.input, input_classes))?;
+                    let end: usize = positions[positions.len() - 1];
+                    self.match_around(lookup, (i, end), rule.backtrack.len(), rule.lookahead.len(),
+                                      class(rule.backtrack, backtrack_classes), class(rule.lookahead, lookahead_classes))
+                        .then_some((positions, rule.lookups))
+                })
+            }
+            layout::ChainedContextLookup::Format3 { coverage, backtrack_coverages, input_coverages, lookahead_coverages, lookups } => {
+                coverage.get(first)?;
+                let input = |k: u16, id: GlyphId| input_coverages.get(k).is_some_and(|c: layout::Coverage| c.contains(id));
+                let backtrack = |k: u16, id: GlyphId| backtrack_coverages.get(k).is_some_and(|c: layout::Coverage| c.contains(id));
+                let lookahead = |k: u16, id: GlyphId| lookahead_coverages.get(k).is_some_and(|c: layout::Coverage| c.contains(id));
+                let positions: Vec<usize> = self.match_input(lookup, i, input_coverages.len(), input)?;
+                let end: usize = positions[positions.len() - 1];
+                self.match_around(lookup, (i, end), backtrack_coverages.len(), lookahead_coverages.len(), backtrack, lookahead)
+                    .then_some((positions, lookups))
+            }
+        }
+    }
+
+    /// Apply the nested `lookups` of a contextual match to its input glyphs, with `apply`.
+    /// Returns where to carry on.
+    fn apply_nested(&mut self, mut positions: Vec<usize>, records: ttf_parser::LazyArray16<layout::SequenceLookupRecord>,
+                    depth: u8, apply: fn(&mut Self, u16, usize, u8) -> Option<usize>) -> usize {
+        for record in records {
+            let k: usize = record.sequence_index as usize;
+            if k >= positions.len() || positions[k] >= self.glyphs.len() {
+                continue;
+            }
+            let before: usize = self.glyphs.len();
+            apply(self, record.lookup_list_index, positions[k], depth + 1);
+            // Substitutions that add or remove glyphs shift the input glyphs after them.
+            let change: isize = self.glyphs.len() as isize - before as isize;
+            for position in positions.iter_mut().skip(k + 1) {
+                *position = position.saturating_add_signed(change);
+            }
+        }
+        positions[positions.len() - 1] + 1
+    }
+}
+
+
+/**
+ *  Substitution
+ */
+/// How deeply contextual lookups may nest, so a font can't send shaping round in circles.
+#[cfg(feature = "std")]
+const MAX_NESTING: u8 = 8;
+
+#[cfg(feature = "std")]
+impl<'a> Shaper<'a> {
+    /// Apply GSUB lookup `index` to every glyph it applies to, by `mask`.
+    fn substitute_all(&mut self, gsub: &layout::LayoutTable<'a>, index: u16, mask: u8) {
+        let lookup: layout::Lookup = match gsub.lookups.get(index) {
+            Some(lookup) => lookup,
+            None => return,
+        };
+        let mut i: usize = 0;
+        while i < self.glyphs.len() {
+            let glyph: Info = self.glyphs[i];
+            i = match glyph.mask & mask != 0 && !self.skips(&lookup, glyph.id) {
+                true => self.substitute(&lookup, i, 0).unwrap_or(i + 1),
+                false => i + 1,
+            };
+        }
+    }
+
+    /// Apply GSUB lookup `index` at glyph `i` only, for a contextual lookup.
+    fn substitute_at(&mut self, index: u16, i: usize, depth: u8) -> Option<usize> {
+        let gsub: layout::LayoutTable<'a> = self.face.tables().gsub?;
+        let lookup: layout::Lookup = gsub.lookups.get(index)?;
+        if depth > MAX_NESTING || self.skips(&lookup, self.glyphs[i].id) {
+            return None;
+        }
+        self.substitute(&lookup, i, depth)
+    }
+
+    /// Apply the first subtable of `lookup` that matches at glyph `i`. Returns where to
+    /// carry on, if one did.
+    fn substitute(&mut self, lookup: &layout::Lookup<'a>, i: usize, depth: u8) -> Option<usize> {
+        let glyph: Info = self.glyphs[i];
+        for subtable in lookup.subtables.into_iter::<gsub::SubstitutionSubtable>() {
+            let next: Option<usize> = match subtable {
+                gsub::SubstitutionSubtable::Single(single) => single.coverage().get(glyph.id)
+                    .and_then(|index: u16| match single {
+                        gsub::SingleSubstitution::Format1 { delta, .. } => Some(GlyphId(glyph.id.0.wrapping_add(delta as u16))),
+                        gsub::SingleSubstitution::Format2 { substitutes, .. } => substitutes.get(index),
+                    })
+                    .map(|id: GlyphId| {
+                        self.glyphs[i].id = id;
+                        i + 1
+                    }),
+                gsub::SubstitutionSubtable::Multiple(multiple) => multiple.coverage.get(glyph.id)
+                    .and_then(|index: u16| multiple.sequences.get(index))
+                    .map(|sequence: gsub::Sequence| {
+                        let replacement: Vec<Info> = sequence.substitutes.into_iter()
+                            .map(|id: GlyphId| Info { id, ..glyph })
+                            .collect();
+                        let count: usize = replacement.len();
+                        self.glyphs.splice(i..i + 1, replacement);
+                        i + count
+                    }),
+                gsub::SubstitutionSubtable::Alternate(alternate) => alternate.coverage.get(glyph.id)
+                    .and_then(|index: u16| alternate.alternate_sets.get(index))
+                    .and_then(|set: gsub::AlternateSet| set.alternates.get(0))
+                    .map(|id: GlyphId| {
+                        self.glyphs[i].id = id;
+                        i + 1
+                    }),
+                gsub::SubstitutionSubtable::Ligature(ligature) => ligature.coverage.get(glyph.id)
+                    .and_then(|index: u16| ligature.ligature_sets.get(index))
+                    .and_then(|set: gsub::LigatureSet| set.into_iter().find_map(|ligature: gsub::Ligature| {
+                        let components: ttf_parser::LazyArray16<GlyphId> = ligature.components;
+                        let positions: Vec<usize> = self.match_input(lookup, i, components.len(), |k: u16, id: GlyphId| components.get(k) == Some(id))?;
+                        Some((ligature.glyph, positions))
+                    }))
+                    .map(|(id, positions): (GlyphId, Vec<usize>)| {
+                        // The ligature takes the first component's place; the others go,
+                        // and any marks between them stay.
+                        self.glyphs[i].id = id;
+                        for &position in positions[1..].iter().rev() {
+                            self.glyphs.remove(position);
+                        }
+                        i + 1
+                    }),
+                gsub::SubstitutionSubtable::Context(context) if depth < MAX_NESTING => self.match_context(lookup, &context, i)
+                    .map(|(positions, records)| self.apply_nested(positions, records, depth, Self::substitute_at)),
+                gsub::SubstitutionSubtable::ChainContext(context) if depth < MAX_NESTING => self.match_chained_context(lookup, &context, i)
+                    .map(|(positions, records)| self.apply_nested(positions, records, depth, Self::substitute_at)),
+                _ => None,
+            };
+            if next.is_some() {
+                return next;
+            }
+        }
+        None
+    }
+}
+
+
+/**
+ *  Positioning
+ */
+#[cfg(feature = "std")]
+impl<'a> Shaper<'a> {
+    /// Apply GPOS lookup `index` to every glyph it applies to, by `mask`.
+    fn position_all(&mut self, gpos: &layout::LayoutTable<'a>, index: u16, mask: u8) {
+        let lookup: layout::Lookup = match gpos.lookups.get(index) {
+            Some(lookup) => lookup,
+            None => return,
+        };
+        let mut i: usize = 0;
+        while i < self.glyphs.len() {
+            let glyph: Info = self.glyphs[i];
+            i = match glyph.mask & mask != 0 && !self.skips(&lookup, glyph.id) {
+                true => self.position(&lookup, i, 0).unwrap_or(i + 1),
+                false => i + 1,
+            };
+        }
+    }
+
+    /// Apply GPOS lookup `index` at glyph `i` only, for a contextual lookup.
+    fn position_at(&mut self, index: u16, i: usize, depth: u8) -> Option<usize> {
+        let gpos: layout::LayoutTable<'a> = self.face.tables().gpos?;
+        let lookup: layout::Lookup = gpos.lookups.get(index)?;
+        if depth > MAX_NESTING || self.skips(&lookup, self.glyphs[i].id) {
+            return None;
+        }
+        self.position(&lookup, i, depth)
+    }
+
+    /// Apply the first subtable of `lookup` that matches at glyph `i`. Returns where to
+    /// carry on, if one did.
+    fn position(&mut self, lookup: &layout::Lookup<'a>, i: usize, depth: u8) -> Option<usize> {
+        let id: GlyphId = self.glyphs[i].id;
+        for subtable in lookup.subtables.into_iter::<gpos::PositioningSubtable>() {
+            let next: Option<usize> = match subtable {
+                gpos::PositioningSubtable::Single(single) => {
+                    let value: Option<gpos::ValueRecord> = match single {
+                        gpos::SingleAdjustment::Format1 { coverage, value } => coverage.get(id).map(|_| value),
+                        gpos::SingleAdjustment::Format2 { coverage, values } => coverage.get(id).and_then(|index: u16| values.get(index)),
+                    };
+                    value.map(|value: gpos::ValueRecord| {
+                        self.adjust(i, &value);
+                        i + 1
+                    })
+                }
+                gpos::PositioningSubtable::Pair(pair) => self.next(lookup, i + 1).and_then(|j: usize| {
+                    let second: GlyphId = self.glyphs[j].id;
+                    let values: Option<(gpos::ValueRecord, gpos::ValueRecord)> = match pair {
+                        gpos::PairAdjustment::Format1 { coverage, sets } => coverage.get(id)
+                            .and_then(|index: u16| sets.get(index))
+                            .and_then(|set: gpos::PairSet| set.get(second)),
+                        gpos::PairAdjustment::Format2 { coverage, classes, matrix } => coverage.get(id)
+                            .and_then(|_| matrix.get((classes.0.get(id), classes.1.get(second)))),
+                    };
+                    values.map(|(first, second): (gpos::ValueRecord, gpos::ValueRecord)| {
+                        self.adjust(i, &first);
+                        self.adjust(j, &second);
+                        j
+                    })
+                }),
+                gpos::PositioningSubtable::MarkToBase(attachment) => {
+                    // The base is the glyph before the mark, passing over any other marks.
+                    let base: Option<usize> = (0..i).rev().find(|&j: &usize| !self.is_mark(self.glyphs[j].id));
+                    base.and_then(|j: usize| {
+                        let (class, mark_anchor) = attachment.marks.get(attachment.mark_coverage.get(id)?)?;
+                        let base_anchor: gpos::Anchor = attachment.anchors.get(attachment.base_coverage.get(self.glyphs[j].id)?, class)?;
+                        Some(self.attach(i, j, &base_anchor, &mark_anchor))
+                    })
+                }
+                gpos::PositioningSubtable::MarkToLigature(attachment) => {
+                    let base: Option<usize> = (0..i).rev().find(|&j: &usize| !self.is_mark(self.glyphs[j].id));
+                    base.and_then(|j: usize| {
+                        let (class, mark_anchor) = attachment.marks.get(attachment.mark_coverage.get(id)?)?;
+                        let anchors: gpos::AnchorMatrix = attachment.ligature_array.get(attachment.ligature_coverage.get(self.glyphs[j].id)?)?;
+                        // Marks go on the ligature's last component.
+                        let base_anchor: gpos::Anchor = anchors.get(anchors.rows.checked_sub(1)?, class)?;
+                        Some(self.attach(i, j, &base_anchor, &mark_anchor))
+                    })
+                }
+                gpos::PositioningSubtable::MarkToMark(attachment) => self.previous(lookup, i)
+                    .filter(|&j: &usize| self.is_mark(self.glyphs[j].id))
+                    .and_then(|j: usize| {
+                        let (class, mark_anchor) = attachment.marks.get(attachment.mark1_coverage.get(id)?)?;
+                        let base_anchor: gpos::Anchor = attachment.mark2_matrix.get(attachment.mark2_coverage.get(self.glyphs[j].id)?, class)?;
+                        Some(self.attach(i, j, &base_anchor, &mark_anchor))
+                    }),
+                gpos::PositioningSubtable::Context(context) if depth < MAX_NESTING => self.match_context(lookup, &context, i)
+                    .map(|(positions, records)| self.apply_nested(positions, records, depth, Self::position_at)),
+                gpos::PositioningSubtable::ChainContext(context) if depth < MAX_NESTING => self.match_chained_context(lookup, &context, i)
+                    .map(|(positions, records)| self.apply_nested(positions, records, depth, Self::position_at)),
+                _ => None,
+            };
+            if next.is_some() {
+                return next;
+            }
+        }
+        None
+    }
+
+    fn adjust(&mut self, i: usize, value: &gpos::ValueRecord) {
+        let glyph: &mut Info = &mut self.glyphs[i];
+        glyph.advance += value.x_advance as i32;
+        glyph.offset.0 += value.x_placement as i32;
+        glyph.offset.1 += value.y_placement as i32;
+    }
+
+    /// Attach mark `i` to glyph `j`, lining up their anchors.
+    fn attach(&mut self, i: usize, j: usize, base_anchor: &gpos::Anchor, mark_anchor: &gpos::Anchor) -> usize {
+        let dx: i32 = base_anchor.x as i32 - mark_anchor.x as i32;
+        let dy: i32 = base_anchor.y as i32 - mark_anchor.y as i32;
+        self.glyphs[i].attached = Some((j, dx, dy));
+        i + 1
+    }
+
+    /// Kern pairs of glyphs with the font's old `kern` table.
+    fn apply_kern_table(&mut self) {
+        let kern: ttf_parser::kern::Table = match self.face.tables().kern {
+            Some(kern) => kern,
+            None => return,
+        };
+        for i in 1..self.glyphs.len() {
+            let (left, right) = (self.glyphs[i - 1].id, self.glyphs[i].id);
+            let kerning: i32 = kern.subtables.into_iter()
+                .filter(|subtable: &ttf_parser::kern::Subtable| subtable.horizontal && !subtable.variable && !subtable.has_cross_stream)
+                .find_map(|subtable: ttf_parser::kern::Subtable| subtable.glyphs_kerning(left, right))
+                .unwrap_or(0) as i32;
+            self.glyphs[i - 1].advance += kerning;
+        }
+    }
+
+    /// The shaped glyphs, in px, with `unit` px per font unit. Clusters are made to go up
+    /// only, merging any that went back down.
+    fn glyphs(mut self, rtl: bool, unit: f32) -> Vec<Glyph> {
+        for i in (1..self.glyphs.len()).rev() {
+            self.glyphs[i - 1].cluster = self.glyphs[i - 1].cluster.min(self.glyphs[i].cluster);
+        }
+
+        // Where each glyph's advance starts, and where it's drawn from (left to right, and
+        // before offsets) once the run is mirrored if it's right to left.
+        let mut starts: Vec<i32> = Vec::with_capacity(self.glyphs.len());
+        let mut x: i32 = 0;
+        for glyph in &self.glyphs {
+            starts.push(x);
+            x += glyph.advance;
+        }
+        let width: i32 = x;
+        let origin = |i: usize, glyph: &Info| if rtl { width - starts[i] - glyph.advance } else { starts[i] };
+
+        // A mark goes where its anchor meets its base's, wherever that was drawn.
+        for i in 0..self.glyphs.len() {
+            if let Some((j, dx, dy)) = self.glyphs[i].attached {
+                let base: Info = self.glyphs[j];
+                let mark: Info = self.glyphs[i];
+                self.glyphs[i].offset = (origin(j, &base) + base.offset.0 + dx - origin(i, &mark), base.offset.1 + dy);
+            }
+        }
+
+        self.glyphs.iter().enumerate().map(|(i, glyph): (usize, &Info)| Glyph {
+            id: glyph.id.0,
+            x: starts[i] as f32 * unit,
+            advance: glyph.advance as f32 * unit,
+            offset: (glyph.offset.0 as f32 * unit, -glyph.offset.1 as f32 * unit),
+            cluster: glyph.cluster,
+        }).collect()
+    }
+}
+
+/// The lookups of the `features` a layout table has for `script`, in the order the font
+/// lists them, each with the mask of the glyphs it applies to.
+#[cfg(feature = "std")]
+fn lookups(table: &layout::LayoutTable, script: Script, features: &[(&[u8; 4], u8)]) -> Vec<(u16, u8)> {
+    let mut lookups: Vec<(u16, u8)> = Vec::new();
+    let language: layout::LanguageSystem = match language_system(table, script) {
+        Some(language) => language,
+        None => return lookups,
+    };
+    // The language's required feature always applies.
+    if let Some(required) = language.required_feature.and_then(|index: u16| table.features.get(index)) {
+        lookups.extend(required.lookup_indices.into_iter().map(|index: u16| (index, ALL)));
+    }
+    for feature in language.feature_indices.into_iter().filter_map(|index: u16| table.features.get(index)) {
+        for &(tag, mask) in features {
+            if feature.tag == Tag::from_bytes(tag) {
+                lookups.extend(feature.lookup_indices.into_iter().map(|index: u16| (index, mask)));
+            }
+        }
+    }
+    lookups.sort_by_key(|&(index, _): &(u16, u8)| index);
+    // A lookup in several features applies to the glyphs of any of them.
+    lookups.dedup_by(|next: &mut (u16, u8), kept: &mut (u16, u8)| {
+        let same: bool = next.0 == kept.0;
+        if same {
+            kept.1 |= next.1;
+        }
+        same
+    });
+    lookups
+}
+
+/// Return true if a layout table has `feature` for `script`.
+#[cfg(feature = "std")]
+fn has_feature(table: &layout::LayoutTable, script: Script, feature: &[u8; 4]) -> bool {
+    language_system(table, script).is_some_and(|language: layout::LanguageSystem| {
+        language.feature_indices.into_iter()
+            .filter_map(|index: u16| table.features.get(index))
+            .any(|f: layout::Feature| f.tag == Tag::from_bytes(feature))
+    })
+}
+
+/// The default language system of `script` in a layout table, or of the table's default
+/// script.
+#[cfg(feature = "std")]
+fn language_system<'a>(table: &layout::LayoutTable<'a>, script: Script) -> Option<layout::LanguageSystem<'a>> {
+    script.tags().iter().copied().chain([b"DFLT", b"latn"])
+        .find_map(|tag: &[u8; 4]| table.scripts.find(Tag::from_bytes(tag)))
+        .and_then(|script: layout::Script| script.default_language)
+}
+
+
+/**
+ *  Arabic Joining
+ *
+ *  Each Arabic letter joins on both sides ("dual-joining"), only to the letter before it
+ *  ("right-joining", like alef), or not at all. Marks are transparent: the letters either
+ *  side of them join as if they weren't there. A letter's form follows from whether it
+ *  joins the letter before it and the one after it.
+ */
+#[cfg(feature = "std")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Joining {
+    None,
+    Right,
+    Dual,
+    Causing, // joins on both sides but has no forms, like the tatweel and zero-width joiner
+    Transparent,
+}
+
+#[cfg(feature = "std")]
+fn joining(c: char) -> Joining {
+    match c as u32 {
+        0x0640 | 0x200D => Joining::Causing,
+        0x0610..=0x061A | 0x064B..=0x065F | 0x0670 | 0x06D6..=0x06DC | 0x06DF..=0x06E4 | 0x06E7 | 0x06E8 |
+        0x06EA..=0x06ED | 0x08D3..=0x08E1 | 0x08E3..=0x08FF | 0x0300..=0x036F => Joining::Transparent,
+        0x0622..=0x0625 | 0x0627 | 0x0629 | 0x062F..=0x0632 | 0x0648 | 0x0671..=0x0673 | 0x0675..=0x0677 |
+        0x0688..=0x0699 | 0x06C0 | 0x06C3..=0x06CB | 0x06CD | 0x06CF | 0x06D2 | 0x06D3 | 0x06D5 | 0x06EE | 0x06EF |
+        0x0759..=0x075B | 0x076B | 0x076C | 0x0771 | 0x0773 | 0x0774 | 0x0778 | 0x0779 => Joining::Right,
+        0x0620 | 0x0626 | 0x0628 | 0x062A..=0x062E | 0x0633..=0x063F | 0x0641..=0x0647 | 0x0649 | 0x064A |
+        0x066E | 0x066F | 0x0678..=0x0687 | 0x069A..=0x06BF | 0x06C1 | 0x06C2 | 0x06CC | 0x06CE | 0x06D0 | 0x06D1 |
+        0x06FA..=0x06FC | 0x06FF | 0x0750..=0x077F => Joining::Dual,
+        _ => Joining::None,
+    }
+}
+
+/// The joining form of each character that has one.
+#[cfg(feature = "std")]
+fn joining_forms(chars: &[(char, usize)]) -> Vec<Option<Form>> {
+    let types: Vec<Joining> = chars.iter().map(|&(c, _): &(char, usize)| joining(c)).collect();
+    let mut forms: Vec<Option<Form>> = vec![None; chars.len()];
+    let mut previous: Option<usize> = None; // the last letter that wasn't transparent
+    for i in 0..chars.len() {
+        if types[i] == Joining::Transparent {
+            continue;
+        }
+        let joins_previous: bool = matches!(types[i], Joining::Right | Joining::Dual | Joining::Causing)
+            && previous.is_some_and(|p: usize| matches!(types[p], Joining::Dual | Joining::Causing));
+        if joins_previous {
+            // The letter before joins this one, so it's initial or medial instead.
+            let p: usize = previous.unwrap();
+            forms[p] = match forms[p] {
+                Some(Form::Final) => Some(Form::Medial),
+                Some(Form::Isolated) => Some(Form::Initial),
+                form => form,
+            };
+        }
+        forms[i] = match types[i] {
+            Joining::Right | Joining::Dual if joins_previous => Some(Form::Final),
+            Joining::Right | Joining::Dual => Some(Form::Isolated),
+            _ => None,
+        };
+        previous = Some(i);
+    }
+    forms
+}
+
+
+/**
+ *  Indic Reordering
+ *
+ *  The Brahmi-derived scripts are laid out alike in Unicode, each in its own block of 128,
+ *  so a character's offset in its block says what it is. A vowel sign like Devanagari's
+ *  "i" (ि) comes after its consonant (or cluster of consonants joined by viramas) in the
+ *  text, but is written before it, so it moves there before shaping. The whole cluster
+ *  then counts as one, starting where the consonants did.
+ */
+#[cfg(feature = "std")]
+fn reorder_indic(chars: &mut [(char, usize)]) {
+    let offset = |c: char| (c as u32 & 0x7F, c as u32 & !0x7F);
+    let is_consonant = |c: char| matches!(offset(c).0, 0x15..=0x39 | 0x58..=0x5F);
+    let is_nukta = |c: char| offset(c).0 == 0x3C;
+    let is_virama = |c: char| offset(c).0 == 0x4D;
+    let is_pre_base = |c: char| matches!(offset(c),
+        (0x3F, 0x900 | 0x980 | 0xA00 | 0xA80) | (0x47 | 0x48, 0x980) | (0x47, 0xB00) | (0x46..=0x48, 0xB80 | 0xD00));
+
+    for i in 0..chars.len() {
+        if !is_pre_base(chars[i].0) {
+            continue;
+        }
+        // Find the start of the consonant cluster before it.
+        let mut start: usize = i;
+        if start > 0 && is_nukta(chars[start - 1].0) {
+            start -= 1;
+        }
+        if start == 0 || !is_consonant(chars[start - 1].0) {
+            continue;
+        }
+        start -= 1;
+        while start >= 2 && is_virama(chars[start - 1].0) {
+            let mut before: usize = start - 1;
+            if before > 0 && is_nukta(chars[before - 1].0) {
+                before -= 1;
+            }
+            if before == 0 || !is_consonant(chars[before - 1].0) {
+                break;
+            }
+            start = before - 1;
+        }
+        let cluster: usize = chars[start].1;
+        chars[start..=i].rotate_right(1);
+        for c in &mut chars[start..=i] {
+            c.1 = cluster;
+        }
+    }
+}
@@ -0,0 +1,389 @@
+//! A stable JSON format for DOM snapshots.
+
+use crate::atom::Atom;
+use crate::dom;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+
+/*
+    DOM Snapshots
+
+    The serde derives (behind the `serde` feature) mirror our Rust types, including the
+    arena's node ids and sibling links, so their output changes whenever the internals do.
+    Tools written in other languages need something they can rely on, so snapshots use a
+    small, documented schema of their own that only describes the tree's content:
+
+        snapshot = { "version": 1, "doctype": string | null, "root": node }
+        node     = { "type": "element", "tag": string,
+                     "attrs": { string: string, ... }, "children": [node, ...] }
+                 | { "type": "text", "text": string }
+                 | { "type": "fragment", "children": [node, ...] }
+
+    "attrs" and "children" may be left out when empty, and "doctype" when there is none.
+    `to_json` writes attributes in the order the element has them (see "Attributes" in
+    `dom`), so the same tree always produces the same text. Unknown keys are ignored, so
+    later versions can add fields.
+
+    e.g.
+        <p class="note">Hi</p>
+
+        {"version":1,"doctype":null,"root":{"type":"element","tag":"p","attrs":{"class":"note"},
+         "children":[{"type":"text","text":"Hi"}]}}
+ */
+
+/// The version of the snapshot schema written by `to_json`.
+pub const VERSION: u32 = 1;
+
+/// Why a snapshot couldn't be read.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SnapshotError {
+    pub message: String,
+    pub position: usize, // byte offset into the input
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.position)
+    }
+}
+
+impl Error for SnapshotError {}
+
+
+/// Write a document as a snapshot.
+pub fn to_json(document: &dom::Document) -> String {
+    let mut result: String = format!("{{\"version\":{},\"doctype\":", VERSION);
+    match document.doctype {
+        Some(ref doctype) => write_string(doctype, &mut result),
+        None => result.push_str("null"),
+    }
+    result.push_str(",\"root\":");
+    write_node(&document.tree, document.root_element, &mut result);
+    result.push('}');
+    result
+}
+
+/// Write the subtree at `node` as a snapshot `node` value.
+pub fn node_to_json(tree: &dom::Tree, node: dom::NodeId) -> String {
+    let mut result: String = String::new();
+    write_node(tree, node, &mut result);
+    result
+}
+
+fn write_node(tree: &dom::Tree, node: dom::NodeId, result: &mut String) {
+    match tree[node].node_type {
+        dom::NodeType::Text(ref text) => {
+            result.push_str("{\"type\":\"text\",\"text\":");
+            write_string(text, result);
+            result.push('}');
+            return;
+        }
+        dom::NodeType::Element(ref element) => {
+            result.push_str("{\"type\":\"element\",\"tag\":");
+            write_string(&element.tag_name, result);
+            result.push_str(",\"attrs\":{");
+            for (i, (name, value)) in element.attributes.iter().enumerate() {
+                if i > 0 {
+                    result.push(',');
+                }
+                write_string(name, result);
+                result.push(':');
+                write_string(value, result);
+            }
+            result.push('}');
+        }
+        dom::NodeType::DocumentFragment => result.push_str("{\"type\":\"fragment\""),
+    }
+
+    result.push_str(",\"children\":[");
+    for (i, child) in tree.children(node).enumerate() {
+        if i > 0 {
+            result.push(',');
+        }
+        write_node(tree, child, result);
+    }
+    result.push_str("]}");
+}
+
+/// Write a JSON string literal, escaping quotes, backslashes, and control characters.
+pub(crate) fn write_string(s: &str, result: &mut String) {
+    result.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            c if (c as u32) < 0x20 => result.push_str(&format!("\\u{:04x}", c as u32)),
+            c => result.push(c),
+        }
+    }
+    result.push('"');
+}
+
+
+/// Read a snapshot back into a document.
+pub fn from_json(source: &str) -> Result<dom::Document, SnapshotError> {
+    let mut parser: Parser = Parser { input: source, position: 0 };
+    let value: Json = parser.parse_document()?;
+    let snapshot: &BTreeMap<String, Json> = match value {
+        Json::Object(ref members) => members,
+        _ => return Err(parser.error("Expected a snapshot object")),
+    };
+
+    match snapshot.get("version") {
+        Some(&Json::Number(version)) if version == VERSION as f64 => {}
+        Some(_) => return Err(parser.error("Unsupported snapshot version")),
+        None => return Err(parser.error("Missing \"version\"")),
+    }
+    let root: &Json = snapshot.get("root").ok_or_else(|| parser.error("Missing \"root\""))?;
+
+    let mut tree: dom::Tree = dom::Tree::new();
+    let root: dom::NodeId = build_node(&mut tree, root).map_err(|message: String| parser.error(&message))?;
+    let mut document: dom::Document = dom::Document::new(tree, root);
+    document.doctype = match snapshot.get("doctype") {
+        Some(Json::String(doctype)) => Some(doctype.clone()),
+        _ => None,
+    };
+    Ok(document)
+}
+
+/// Add the node described by `value` (and its children) to `tree`.
+fn build_node(tree: &mut dom::Tree, value: &Json) -> Result<dom::NodeId, String> {
+    let members: &BTreeMap<String, Json> = match value {
+        Json::Object(members) => members,
+        _ => return Err("Expected a node object".to_string()),
+    };
+    let string = |key: &str| -> Result<&String, String> {
+        match members.get(key) {
+            Some(Json::String(s)) => Ok(s),
+            _ => Err(format!("Expected a string for {:?}", key)),
+        }
+    };
+
+    let node: dom::NodeId = match string("type")?.as_str() {
+        "text" => return Ok(tree.text(string("text")?.clone())),
+        "element" => {
+            let mut attributes: dom::AttributeMap = dom::AttributeMap::default();
+            match members.get("attrs") {
+                Some(Json::Object(attrs)) => {
+                    for (name, value) in attrs {
+                        match value {
+                            Json::String(value) => attributes.insert(Atom::from(name.as_str()), value.clone()),
+                            _ => return Err(format!("Expected a string value for attribute {:?}", name)),
+                        };
+                    }
+                }
+                None => {}
+                Some(_) => return Err("Expected an object for \"attrs\"".to_string()),
+            }
+            tree.element(Atom::from(string("tag")?.as_str()), attributes, Vec::new())
+        }
+        "fragment" => tree.fragment(Vec::new()),
+        other => return Err(format!("Unknown node type {:?}", other)),
+    };
+
+    match members.get("children") {
+        Some(Json::Array(children)) => {
+            for child in children {
+                let child: dom::NodeId = build_node(tree, child)?;
+                tree.append_child(node, child);
+            }
+        }
+        None => {}
+        Some(_) => return Err("Expected an array for \"children\"".to_string()),
+    }
+    Ok(node)
+}
+
+
+/*
+    Reading JSON
+
+    Snapshots only need a tiny JSON reader, so rather than pull in a dependency we parse it
+    the same way as HTML and CSS: a parser holding the input and a position, consuming one
+    value at a time. Other modules that read small JSON messages (e.g. `devtools`) use it
+    too, through `parse_json`.
+ */
+pub(crate) enum Json {
+    Literal, // true, false, or null; snapshots never need to know which
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+/// Parse a JSON document.
+#[cfg(feature = "devtools")]
+pub(crate) fn parse_json(source: &str) -> Result<Json, SnapshotError> {
+    Parser { input: source, position: 0 }.parse_document()
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, message: &str) -> SnapshotError {
+        SnapshotError { message: message.to_string(), position: self.position }
+    }
+
+    fn next_char(&self) -> Option<char> {
+        self.input[self.position..].chars().next()
+    }
+
+    fn consume_char(&mut self) -> Option<char> {
+        let c: char = self.next_char()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    fn consume_whitespace(&mut self) {
+        while let Some(c) = self.next_char() {
+            if !matches!(c, ' ' | '\t' | '\n' | '\r') {
+                break;
+            }
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<(), SnapshotError> {
+        if self.input[self.position..].starts_with(s) {
+            self.position += s.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected {:?}", s)))
+        }
+    }
+
+    /// Parse a single value that makes up the whole input.
+    fn parse_document(&mut self) -> Result<Json, SnapshotError> {
+        let value: Json = self.parse_value()?;
+        self.consume_whitespace();
+        if self.position < self.input.len() {
+            return Err(self.error("Unexpected text after the value"));
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<Json, SnapshotError> {
+        self.consume_whitespace();
+        match self.next_char() {
+            Some('{') => self.parse_object(),
+            Some('[') => self.parse_array(),
+            Some('"') => Ok(Json::String(self.parse_string()?)),
+            Some('t') => self.expect("true").map(|_| Json::Literal),
+            Some('f') => self.expect("false").map(|_| Json::Literal),
+            Some('n') => self.expect("null").map(|_| Json::Literal),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+            _ => Err(self.error("Expected a value")),
+        }
+    }
+
+    fn parse_object(&mut self) -> Result<Json, SnapshotError> {
+        self.expect("{")?;
+        let mut members: BTreeMap<String, Json> = BTreeMap::new();
+        self.consume_whitespace();
+        if self.next_char() == Some('}') {
+            self.position += 1;
+            return Ok(Json::Object(members));
+        }
+        loop {
+            self.consume_whitespace();
+            let key: String = self.parse_string()?;
+            self.consume_whitespace();
+            self.expect(":")?;
+            let value: Json = self.parse_value()?;
+            members.insert(key, value);
+            self.consume_whitespace();
+            match self.consume_char() {
+                Some(',') => continue,
+                Some('}') => return Ok(Json::Object(members)),
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Json, SnapshotError> {
+        self.expect("[")?;
+        let mut elements: Vec<Json> = Vec::new();
+        self.consume_whitespace();
+        if self.next_char() == Some(']') {
+            self.position += 1;
+            return Ok(Json::Array(elements));
+        }
+        loop {
+            elements.push(self.parse_value()?);
+            self.consume_whitespace();
+            match self.consume_char() {
+                Some(',') => continue,
+                Some(']') => return Ok(Json::Array(elements)),
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_number(&mut self) -> Result<Json, SnapshotError> {
+        let start: usize = self.position;
+        while let Some(c) = self.next_char() {
+            if !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E') {
+                break;
+            }
+            self.position += 1;
+        }
+        self.input[start..self.position].parse().map(Json::Number)
+            .map_err(|_| SnapshotError { message: "Invalid number".to_string(), position: start })
+    }
+
+    fn parse_string(&mut self) -> Result<String, SnapshotError> {
+        self.expect("\"")?;
+        let mut result: String = String::new();
+        loop {
+            match self.consume_char() {
+                Some('"') => return Ok(result),
+                Some('\\') => match self.consume_char() {
+                    Some('"') => result.push('"'),
+                    Some('\\') => result.push('\\'),
+                    Some('/') => result.push('/'),
+                    Some('b') => result.push('\u{8}'),
+                    Some('f') => result.push('\u{c}'),
+                    Some('n') => result.push('\n'),
+                    Some('r') => result.push('\r'),
+                    Some('t') => result.push('\t'),
+                    Some('u') => result.push(self.parse_unicode_escape()?),
+                    _ => return Err(self.error("Invalid escape")),
+                },
+                Some(c) => result.push(c),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    /// Parse the hex digits after `\u`, combining a surrogate pair if there is one.
+    fn parse_unicode_escape(&mut self) -> Result<char, SnapshotError> {
+        let high: u32 = self.parse_hex4()?;
+        let code: u32 = if (0xd800..0xdc00).contains(&high) {
+            self.expect("\\u")?;
+            let low: u32 = self.parse_hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("Invalid surrogate pair"));
+            }
+            0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+        } else {
+            high
+        };
+        char::from_u32(code).ok_or_else(|| self.error("Invalid character escape"))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, SnapshotError> {
+        let digits: &str = self.input.get(self.position..self.position + 4)
+            .ok_or_else(|| self.error("Expected four hex digits"))?;
+        let value: u32 = u32::from_str_radix(digits, 16).map_err(|_| self.error("Expected four hex digits"))?;
+        self.position += 4;
+        Ok(value)
+    }
+}
@@ -2,9 +2,17 @@
 //!
 //! I will call it "CSS Renderer"
 
+use crate::atom::Atom;
+use crate::collections::{HashMap, HashSet};
+use crate::counters::{self, Counters};
 use crate::css;
 use crate::dom;
-use std::collections::HashMap;
+use crate::layout::Invalidation;
+use crate::quotes::Quotes;
+use crate::trace::{stage, Stage};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+use alloc::borrow::Cow;
 
 
 /*
@@ -19,7 +27,7 @@ use std::collections::HashMap;
         }
  }
  */
-pub type PropertyMap = HashMap<String, css::Value>;
+pub type PropertyMap = HashMap<Atom, css::Value>;
 
 
 /// A node with associated style data.
@@ -28,16 +36,24 @@ pub type PropertyMap = HashMap<String, css::Value>;
     that pointers are memory-safe without requiring garbage collection. If you’re not
     working in Rust you can ignore them; they aren’t critical to the code’s meaning.
 
+    The node is borrowed from the DOM, except for generated content (see "Generated
+    Content"), whose nodes the style tree makes and owns itself.
+
     e.g.
         StyledNode<'a> {
-            node: &'a Node,
+            node_id: NodeId,
+            node: Cow<'a, Node>,
+            pseudo_element: None,
             specified_values: PropertyMap,
             children: Vec<StyledNode<'a>>,
         }
  */
-#[derive(Clone)]
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct StyledNode<'a> {
-    pub node: &'a dom::Node,
+    pub node_id: dom::NodeId, // for generated content, the element it was generated for
+    pub node: Cow<'a, dom::Node>,
+    pub pseudo_element: Option<css::PseudoElement>, // set on a pseudo-element's box and its text
     pub specified_values: PropertyMap,
     pub children: Vec<StyledNode<'a>>,
 }
@@ -48,6 +64,8 @@ pub struct StyledNode<'a> {
     e.g.
         Display::Inline, Display::Block, Display::None
  */
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Display {
     Inline,
     Block,
@@ -70,8 +88,9 @@ impl<'a> StyledNode<'a> {
 
     /// The value of the `display` property (defaults to inline).
     pub fn display(&self) -> Display {
-        match self.value("display") {
-            Some(css::Value::Keyword(s)) => match &*s {
+        // Called for every node in every layout, so look at the value without cloning it.
+        match self.specified_values.get("display") {
+            Some(css::Value::Keyword(s)) => match s.as_str() {
                 "block" => Display::Block,
                 "none" => Display::None,
                 _ => Display::Inline,
@@ -79,6 +98,181 @@ impl<'a> StyledNode<'a> {
             _ => Display::Inline,
         }
     }
+
+    /// The styled node for DOM node `node` in this subtree, or `None` if it isn't rendered.
+    pub fn find(&self, node: dom::NodeId) -> Option<&StyledNode<'a>> {
+        if self.node_id == node {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child: &StyledNode<'a>| child.find(node))
+    }
+
+    /// The text of this subtree as it would be rendered.
+    /*
+        This is a simplified [innerText](https://html.spec.whatwg.org/multipage/dom.html#the-innertext-idl-attribute).
+        Unlike `dom::Tree::text_content`, it looks at the style tree:
+            - nodes with `display: none` contribute nothing
+            - runs of whitespace collapse to a single space, and are trimmed at line edges
+            - block boxes start and end on their own lines, and `<br>` breaks the line
+            - paragraphs are separated by a blank line
+
+        e.g.
+            <div>Hello <em>big</em>
+                 world<p>Paragraph</p><p style="display: none">Hidden</p></div>
+            => "Hello big world\n\nParagraph"
+     */
+    pub fn inner_text(&self) -> String {
+        let mut pieces: Vec<TextPiece> = Vec::new();
+        self.collect_text_pieces(&mut pieces);
+
+        let mut result: String = String::new();
+        let mut line: String = String::new();
+        let mut pending_breaks: usize = 0;
+        for piece in pieces {
+            match piece {
+                TextPiece::Text(text) => line.push_str(text),
+                TextPiece::Breaks(count) => {
+                    flush_line(&mut result, &mut line, &mut pending_breaks);
+                    pending_breaks = pending_breaks.max(count);
+                }
+            }
+        }
+        flush_line(&mut result, &mut line, &mut pending_breaks);
+        result
+    }
+
+    /// Flatten the rendered text of this subtree into text runs and line breaks.
+    fn collect_text_pieces(&'a self, pieces: &mut Vec<TextPiece<'a>>) {
+        let display: Display = self.display();
+        if let Display::None = display {
+            return;
+        }
+        if self.pseudo_element.is_some() {
+            return; // generated content isn't part of the text, as in browsers
+        }
+
+        let element: &dom::Element = match self.node.node_type {
+            dom::NodeType::Text(ref text) => return pieces.push(TextPiece::Text(text)),
+            dom::NodeType::Element(ref element) => element,
+            dom::NodeType::DocumentFragment => {
+                for child in &self.children {
+                    child.collect_text_pieces(pieces);
+                }
+                return;
+            }
+        };
+        if element.tag_name == "br" {
+            return pieces.push(TextPiece::Breaks(1));
+        }
+
+        let breaks: usize = match (display, element.tag_name.as_str()) {
+            (Display::Block, "p") => 2,
+            (Display::Block, _) => 1,
+            _ => 0,
+        };
+        if breaks > 0 {
+            pieces.push(TextPiece::Breaks(breaks));
+        }
+        for child in &self.children {
+            child.collect_text_pieces(pieces);
+        }
+        if breaks > 0 {
+            pieces.push(TextPiece::Breaks(breaks));
+        }
+    }
+}
+
+
+/// Either a run of text, or a number of required line breaks.
+enum TextPiece<'a> {
+    Text(&'a str),
+    Breaks(usize),
+}
+
+/// Collapse whitespace in `line` and append it to `result`, preceded by any pending line
+/// breaks (which are dropped at the very start of the text).
+fn flush_line(result: &mut String, line: &mut String, pending_breaks: &mut usize) {
+    let collapsed: String = line.split_whitespace().collect::<Vec<&str>>().join(" ");
+    line.clear();
+    if collapsed.is_empty() {
+        return;
+    }
+    if !result.is_empty() {
+        result.push_str(&"\n".repeat(*pending_breaks));
+    }
+    result.push_str(&collapsed);
+    *pending_breaks = 0;
+}
+
+
+/// Which elements are in each dynamic pseudo-class state
+/*
+    Hovering over an element also hovers over all of its ancestors: with the mouse over
+    the <em> in <p><a><em>link</em></a></p>, `p:hover`, `a:hover`, and `em:hover` all match.
+    `hover_chain` builds that set from the node under the mouse.
+
+    Along with the states goes the medium the page is rendered for, which decides whether
+    the rules in `@media` blocks match (see "Media Types" in `css`), and the stylesheets
+    scoped to parts of the document (see "Scoped Styles", below).
+ */
+#[derive(Clone, Default, Debug)]
+pub struct ElementStates {
+    pub hover: HashSet<dom::NodeId>,
+    pub active: HashSet<dom::NodeId>,
+    pub focus: Option<dom::NodeId>,
+    pub media: css::Media,
+    pub scopes: HashMap<dom::NodeId, css::Stylesheet>, // the stylesheet for each of these elements' descendants
+}
+
+impl ElementStates {
+    /// Return true if `node` is in the state `pseudo_class` matches.
+    pub fn matches(&self, node: dom::NodeId, pseudo_class: css::PseudoClass) -> bool {
+        match pseudo_class {
+            css::PseudoClass::Hover => self.hover.contains(&node),
+            css::PseudoClass::Active => self.active.contains(&node),
+            css::PseudoClass::Focus => self.focus == Some(node),
+        }
+    }
+}
+
+/*
+    Scoped Styles
+
+    An embedder building a page out of widgets wants each widget's styles to stay inside
+    it: a `.title` rule written for one widget shouldn't restyle a `.title` in another,
+    or on the page around it, and the page's rules shouldn't reach into the widget. The
+    shadow DOM does this in browsers. This engine has a simpler version: an element can
+    be given a stylesheet of its own, in `ElementStates::scopes`, and then
+        - its descendants are styled by that stylesheet alone, and the document's rules
+          (or an outer scope's) don't match them
+        - its rules match only its descendants, so they can't leak out
+        - the element itself belongs to the scope it's in, so the page around it still
+          decides where the widget goes and how big it is, as for a shadow host
+    A scope inside another one replaces it, for its own descendants. Only rules are
+    scoped: inheritance, counters, and `style` attributes work across the boundary as
+    they do anywhere else.
+
+    e.g. with a scope on the <div>
+        <p class="title">Page</p>  <div class="card"><p class="title">Card</p></div>
+    the page's `.title` styles only the first <p>, and the scope's `.title` only the
+    second. The page's `.card` styles the <div>.
+ */
+impl ElementStates {
+    /// The stylesheet that styles `node`: the scope of its nearest ancestor that has one,
+    /// or else `stylesheet`, the document's. See "Scoped Styles", above.
+    pub fn stylesheet_for<'s>(&'s self, tree: &dom::Tree, node: dom::NodeId, stylesheet: &'s css::Stylesheet) -> &'s css::Stylesheet {
+        if self.scopes.is_empty() {
+            return stylesheet;
+        }
+        tree.ancestors(node).skip(1).find_map(|ancestor: dom::NodeId| self.scopes.get(&ancestor)).unwrap_or(stylesheet)
+    }
+}
+
+/// `node` and all of its element ancestors: everything hovered when the mouse is over `node`.
+pub fn hover_chain(tree: &dom::Tree, node: dom::NodeId) -> HashSet<dom::NodeId> {
+    tree.ancestors(node)
+        .filter(|&ancestor: &dom::NodeId| matches!(tree[ancestor].node_type, dom::NodeType::Element(_)))
+        .collect()
 }
 
 
@@ -90,9 +284,20 @@ impl<'a> StyledNode<'a> {
     the DOM tree to look at the element’s siblings, parents, etc.
  */
 /// Selector matching:
-fn matches(element: &dom::Element, selector: &css::Selector) -> bool {
+pub fn matches(element: &dom::Element, node: dom::NodeId, selector: &css::Selector, states: &ElementStates) -> bool {
+    matches_pseudo_element(element, node, selector, states, None)
+}
+
+/// Return true if `selector` matches `element`'s pseudo-element `pseudo_element`, or the
+/// element itself if that's `None`.
+fn matches_pseudo_element(element: &dom::Element, node: dom::NodeId, selector: &css::Selector, states: &ElementStates,
+                          pseudo_element: Option<css::PseudoElement>) -> bool {
     match selector {
-        css::Selector::Simple(s) => matches_simple_selector(element, s)
+        css::Selector::Simple(s) => {
+            s.pseudo_element == pseudo_element
+                && matches_simple_selector(element, s)
+                && s.pseudo_classes.iter().all(|&pseudo_class: &css::PseudoClass| states.matches(node, pseudo_class))
+        }
     }
 }
 
@@ -109,19 +314,19 @@ fn matches(element: &dom::Element, selector: &css::Selector) -> bool {
     or the [some](https://developer.mozilla.org/en-US/docs/Web/JavaScript/Reference/Global_Objects/Array/some)
     method in JavaScript.
  */
-fn matches_simple_selector(element: &dom::Element, selector: &css::SimpleSelector) -> bool {
+pub(crate) fn matches_simple_selector(element: &dom::Element, selector: &css::SimpleSelector) -> bool {
     // Check "tag" selector
-    if selector.tag_name.iter().any(|name: &String| element.tag_name != *name) {
+    if selector.tag_name.iter().any(|name: &Atom| element.tag_name != *name) {
         return false;
     }
 
     // Check "id" selector
-    if selector.id.iter().any(|id: &String| element.id() != Some(id)) {
+    if selector.id.iter().any(|id: &Atom| element.id() != Some(id.as_str())) {
         return false;
     }
 
     // Check "class" selectors
-    if selector.class.iter().any(|class: &String| !element.classes().contains(class.as_str())) {
+    if selector.class.iter().any(|class: &Atom| !element.has_class(class)) {
         return false;
     }
 
@@ -145,27 +350,94 @@ fn matches_simple_selector(element: &dom::Element, selector: &css::SimpleSelecto
 /// A single CSS rule and the specificity of its most specific matching selector.
 type MatchedRule<'a> = (css::Specificity, &'a css::Rule);
 
-/// If `rule` matches `element`, return a `MatchedRule`. Otherwise return `None`.
-fn match_rule<'a>(element: &dom::Element, rule: &'a css::Rule) -> Option<MatchedRule<'a>> {
+/// If `rule` matches `element` (or its `pseudo_element`), return a `MatchedRule`.
+/// Otherwise return `None`.
+fn match_rule<'a>(element: &dom::Element, node: dom::NodeId, key: &ElementKey, rule: &'a css::Rule, states: &ElementStates,
+                  pseudo_element: Option<css::PseudoElement>) -> Option<MatchedRule<'a>> {
     // Find the first (most specific) matching selector.
     rule.selectors
-        .iter().find(|selector: &&css::Selector| matches(element, selector))
+        .iter().find(|selector: &&css::Selector| key.may_match(selector) && matches_pseudo_element(element, node, selector, states, pseudo_element))
         .map(|selector: &css::Selector| (selector.specificity(), rule))
 }
 
 
+/*
+    Fast Rejection
+
+    Most elements match only a handful of the rules in a big stylesheet, so most of the
+    time spent matching goes into finding out that a rule doesn't match. And most rules in
+    a framework stylesheet are a single class or id: `.btn-primary`, `#sidebar`. Checking
+    one of those the general way means looking the `class` attribute up in the element's
+    attributes and splitting it on white space, again for every rule.
+
+    So before the general matcher, each selector gets a quick check of its most selective
+    part against an `ElementKey`: the element's id and classes, worked out once for the
+    element rather than once per rule. A selector with an id is checked by its id, one
+    with classes by its first class, and one with only a tag name by that. A selector that
+    fails the quick check can't match; one that passes goes on to the general matcher,
+    which checks the rest. `benches/selectors.rs` measures the difference on a
+    Bootstrap-sized stylesheet.
+ */
+/// What the quick check needs to know about an element. See "Fast Rejection", above.
+struct ElementKey<'a> {
+    tag_name: Atom,
+    id: Option<&'a str>,
+    classes: Vec<&'a str>,
+}
+
+impl<'a> ElementKey<'a> {
+    fn new(element: &'a dom::Element) -> ElementKey<'a> {
+        let classes: Vec<&str> = element.attr("class").map(|list: &str| list.split_whitespace().collect()).unwrap_or_default();
+        ElementKey { tag_name: element.tag_name, id: element.id(), classes }
+    }
+
+    /// Return false if `selector` can't match the element, judging by its most selective
+    /// part. True means it might.
+    fn may_match(&self, selector: &css::Selector) -> bool {
+        let css::Selector::Simple(ref simple) = *selector;
+        if let Some(ref id) = simple.id {
+            return self.id == Some(id.as_str());
+        }
+        if let Some(class) = simple.class.first() {
+            return self.classes.contains(&class.as_str());
+        }
+        simple.tag_name.is_none_or(|tag_name: Atom| tag_name == self.tag_name)
+    }
+}
+
+
 /*
     To find all the rules that match an element we call `filter_map`, which does a linear
     scan through the style sheet, checking every rule and throwing out ones that don't match.
     A real browser engine would speed this up by storing the rules in multiple hash tables
     based on tag name, id, class, etc.
  */
-/// Find all CSS rules that match the given element.
-fn matching_rules<'a>(element: &dom::Element, stylesheet: &'a css::Stylesheet) -> Vec<MatchedRule<'a>> {
+/// Find all CSS rules that match the given element (or its `pseudo_element`).
+fn matching_rules<'a>(element: &dom::Element, node: dom::NodeId, stylesheet: &'a css::Stylesheet, states: &ElementStates,
+                      pseudo_element: Option<css::PseudoElement>) -> Vec<MatchedRule<'a>> {
     // For now, we just do a linear scan of all the rules. For large documents,
     // it would be more efficient to store the rules in hash tables based on
     // tag name, id, class, etc.
-    stylesheet.rules.iter().filter_map(|rule: &css::Rule| match_rule(element, rule)).collect()
+    let key: ElementKey = ElementKey::new(element);
+    stylesheet.rules.iter()
+        .filter(|rule: &&css::Rule| rule.applies_to(states.media))
+        .filter_map(|rule: &css::Rule| match_rule(element, node, &key, rule, states, pseudo_element))
+        .collect()
+}
+
+/// The rules that match `element`, each with the specificity it matched with, in the
+/// order they are applied: least specific first, so later rules win.
+pub fn matched_rules<'a>(element: &dom::Element, node: dom::NodeId, stylesheet: &'a css::Stylesheet, states: &ElementStates) -> Vec<(css::Specificity, &'a css::Rule)> {
+    sorted_rules(element, node, stylesheet, states, None)
+}
+
+/// Like `matched_rules`, for the element's `pseudo_element`, or the element itself if
+/// that's `None`.
+fn sorted_rules<'a>(element: &dom::Element, node: dom::NodeId, stylesheet: &'a css::Stylesheet, states: &ElementStates,
+                    pseudo_element: Option<css::PseudoElement>) -> Vec<MatchedRule<'a>> {
+    let mut rules: Vec<MatchedRule<'a>> = matching_rules(element, node, stylesheet, states, pseudo_element);
+    rules.sort_by_key(|&(specificity, _): &MatchedRule<'a>| specificity);
+    rules
 }
 
 
@@ -173,18 +445,27 @@ fn matching_rules<'a>(element: &dom::Element, stylesheet: &'a css::Stylesheet) -
     Once we have the matching rules, we can find the "specified values" for the element.
     We insert each rule's property values into a HashMap. We sort the matches by specificity,
     so the more-specific rules are processed after the less-specific ones, and can overwrite
-    their values in the HashMap.
+    their values in the HashMap. Declarations in the element's `style` attribute go last,
+    overriding everything from the stylesheet. (They don't apply to its pseudo-elements,
+    which only rules can select.)
  */
-/// Apply styles to a single element, returning the specified values.
-fn specified_values(element: &dom::Element, stylesheet: &css::Stylesheet) -> PropertyMap {
-    let mut values = HashMap::new();
-    let mut rules = matching_rules(element, stylesheet);
+/// Apply styles to a single element (or its `pseudo_element`), returning the specified
+/// values.
+fn specified_values(element: &dom::Element, node: dom::NodeId, stylesheet: &css::Stylesheet, states: &ElementStates,
+                    pseudo_element: Option<css::PseudoElement>) -> PropertyMap {
+    let mut values = HashMap::default();
 
     // Go through the rules from lowest to highest specificity.
-    rules.sort_by(|&(a, _), &(b, _)| a.cmp(&b));
-    for (_, rule) in rules {
+    for (_, rule) in sorted_rules(element, node, stylesheet, states, pseudo_element) {
         for declaration in &rule.declarations {
-            values.insert(declaration.name.clone(), declaration.value.clone());
+            values.insert(declaration.name, declaration.value.clone());
+        }
+    }
+
+    // The `style` attribute beats every rule.
+    if let (Some(style), None) = (element.attr("style"), pseudo_element) {
+        for declaration in css::parse_inline(style) {
+            values.insert(declaration.name, declaration.value);
         }
     }
 
@@ -197,14 +478,343 @@ fn specified_values(element: &dom::Element, stylesheet: &css::Stylesheet) -> Pro
     Note that selector matching works only on elements, so the specified values for
     a text node are just and empty map.
  */
-/// Apply a stylesheet to an entire DOM tree, returning a `StyledNode` tree.
-pub fn style_tree<'a>(root: &'a dom::Node, stylesheet: &'a css::Stylesheet) -> StyledNode<'a> {
-    StyledNode {
-        node: root,
-        specified_values: match root.node_type {
-            dom::NodeType::Element(ref element) => specified_values(element, stylesheet),
-            dom::NodeType::Text(_) => HashMap::new(),
-        },
-        children: root.children.iter().map(|child: &dom::Node| style_tree(child, stylesheet)).collect(),
+/// Apply a stylesheet to the subtree of `tree` rooted at `root`, returning a `StyledNode` tree.
+pub fn style_tree<'a>(tree: &'a dom::Tree, root: dom::NodeId, stylesheet: &'a css::Stylesheet) -> StyledNode<'a> {
+    style_tree_with_states(tree, root, stylesheet, &ElementStates::default())
+}
+
+/// Like `style_tree`, with elements in the given pseudo-class states.
+pub fn style_tree_with_states<'a>(tree: &'a dom::Tree, root: dom::NodeId, stylesheet: &'a css::Stylesheet, states: &ElementStates) -> StyledNode<'a> {
+    let stage: Stage = stage!("style::style_tree", nodes);
+    let styled: StyledNode = style_node(tree, root, stylesheet, states, &mut Counters::default(), &mut Quotes::default());
+    stage.record("nodes", || tree.descendants(root).count());
+    styled
+}
+
+
+/*
+    Looking Up Styled Nodes
+
+    A style tree is a tree, and `StyledNode::find` searches it for a DOM node's styled
+    node, which is fine once. Devtools, scripts, and hit testing ask about many nodes in
+    a row, though, and searching the whole tree each time makes that quadratic. A
+    `StyledTree` walks the style tree once and keeps a table from each DOM node, by its
+    index in the arena, to its styled node, so each lookup after that is O(1):
+
+        let styled: StyledTree = StyledTree::new(&style_root);
+        let color: Option<&css::Value> = styled.style_for(node)?.get("color");
+
+    The table borrows the style tree, so it can't outlive it or see it change. Generated
+    content has its element's id (see "Generated Content"), but a node's entry is always
+    its own styled node, never its pseudo-elements'. Nodes that aren't rendered, because
+    they're inside an element with `display: none` or outside the styled subtree, have
+    no entry. `layout::BoxTable` does the same for layout boxes.
+ */
+/// A style tree, with a table from each DOM node to its styled node. See "Looking Up
+/// Styled Nodes", above.
+pub struct StyledTree<'t, 'a> {
+    pub root: &'t StyledNode<'a>,
+    nodes: Vec<Option<&'t StyledNode<'a>>>, // indexed by `NodeId::index`
+}
+
+impl<'t, 'a> StyledTree<'t, 'a> {
+    /// Index the style tree under `root`.
+    pub fn new(root: &'t StyledNode<'a>) -> StyledTree<'t, 'a> {
+        let mut styled: StyledTree = StyledTree { root, nodes: Vec::new() };
+        styled.add(root);
+        styled
+    }
+
+    fn add(&mut self, node: &'t StyledNode<'a>) {
+        if node.pseudo_element.is_none() {
+            let index: usize = node.node_id.index();
+            if index >= self.nodes.len() {
+                self.nodes.resize(index + 1, None);
+            }
+            self.nodes[index] = Some(node);
+        }
+        for child in &node.children {
+            self.add(child);
+        }
+    }
+
+    /// The styled node for DOM node `node`, or `None` if it isn't rendered.
+    pub fn node_for(&self, node: dom::NodeId) -> Option<&'t StyledNode<'a>> {
+        self.nodes.get(node.index()).copied().flatten()
+    }
+
+    /// The specified values of DOM node `node`, or `None` if it isn't rendered.
+    pub fn style_for(&self, node: dom::NodeId) -> Option<&'t PropertyMap> {
+        self.node_for(node).map(|styled: &StyledNode| &styled.specified_values)
+    }
+}
+
+/// Style one node and its descendants, updating `counters` and `quotes` as they go.
+fn style_node<'a>(tree: &'a dom::Tree, root: dom::NodeId, stylesheet: &css::Stylesheet, states: &ElementStates,
+                  counters: &mut Counters, quotes: &mut Quotes) -> StyledNode<'a> {
+    let node: &dom::Node = &tree[root];
+    let element: &dom::Element = match node.node_type {
+        dom::NodeType::Element(ref element) => element,
+        dom::NodeType::Text(_) | dom::NodeType::DocumentFragment => {
+            // A fragment's children are the document's, and their counters last past it.
+            let children: Vec<StyledNode<'a>> = tree.children(root)
+                .map(|child: dom::NodeId| style_node(tree, child, stylesheet, states, counters, quotes))
+                .collect();
+            return StyledNode { node_id: root, node: Cow::Borrowed(node), pseudo_element: None, specified_values: HashMap::default(), children };
+        }
+    };
+    let specified_values: PropertyMap = specified_values(element, root, stylesheet, states, None);
+    let displayed: bool = !matches!(specified_values.get("display"), Some(css::Value::Keyword(display)) if *display == "none");
+    if displayed {
+        update_counters(&specified_values, Some(element), counters);
+    }
+
+    // Counters made by the children last until the end of this element, and so do the
+    // element's `lang` and `quotes`.
+    let (mark, quotes_mark): (usize, usize) = (counters.mark(), quotes.mark());
+    quotes.enter(element.attr("lang").map(Atom::from), specified_values.get("quotes").cloned());
+    let mut children: Vec<StyledNode<'a>> = Vec::new();
+    children.extend(displayed.then(|| generate(element, root, stylesheet, states, css::PseudoElement::Before, counters, quotes)).flatten());
+    // The descendants of a scope's element are styled by its stylesheet (see "Scoped Styles").
+    let inner: &css::Stylesheet = states.scopes.get(&root).unwrap_or(stylesheet);
+    children.extend(tree.children(root).map(|child: dom::NodeId| style_node(tree, child, inner, states, counters, quotes)));
+    children.extend(displayed.then(|| generate(element, root, stylesheet, states, css::PseudoElement::After, counters, quotes)).flatten());
+    counters.leave(mark);
+    quotes.leave(quotes_mark);
+
+    StyledNode { node_id: root, node: Cow::Borrowed(node), pseudo_element: None, specified_values, children }
+}
+
+
+/*
+    Generated Content
+
+    The `::before` and `::after` pseudo-elements add a box at the start or end of an
+    element, holding the text their `content` property makes:
+        none, normal         no box (the initial value)
+        "text"               that text
+        counter(name)        a counter's value (see `counters`), optionally in a counter
+                             style: counter(name, upper-roman)
+        counters(name, ".")  the values of all the counters with that name, outermost
+                             first, joined with the string; optionally in a counter style
+        attr(name)           the value of one of the element's attributes
+        open-quote           an opening or closing quotation mark (see `quotes`);
+        close-quote          `no-open-quote` and `no-close-quote` count the quote
+                             without making a mark
+    or a sequence of those, as in `content: "Figure " counter(figure) ": "`.
+
+    Browsers quote `<q>` elements from their user-agent stylesheet, with
+    `q::before { content: open-quote }` and `q::after { content: close-quote }`. This
+    engine has no such stylesheet, so that's built in: a `<q>`'s pseudo-elements quote
+    unless the page gives them some other `content`.
+
+    The box is an inline element (unless its `display` says otherwise) with one text
+    child, so layout treats it like any other, but neither is in the DOM: the style tree
+    makes their nodes, and gives them the id of the element they belong to, so clicking
+    generated content clicks the element. A pseudo-element can change counters too, just
+    before its content is made.
+ */
+/// The box for `element`'s `pseudo_element`, if its `content` makes one.
+fn generate<'a>(element: &dom::Element, node: dom::NodeId, stylesheet: &css::Stylesheet, states: &ElementStates,
+                pseudo_element: css::PseudoElement, counters: &mut Counters, quotes: &mut Quotes) -> Option<StyledNode<'a>> {
+    let mut specified_values: PropertyMap = specified_values(element, node, stylesheet, states, Some(pseudo_element));
+    if element.tag_name == "q" && !specified_values.contains_key("content") {
+        let quote: &str = match pseudo_element {
+            css::PseudoElement::Before => "open-quote",
+            css::PseudoElement::After => "close-quote",
+        };
+        specified_values.insert(Atom::from("content"), css::Value::Keyword(Atom::from(quote)));
+    }
+    let content: &css::Value = specified_values.get("content")?;
+    if matches!(content, css::Value::Keyword(keyword) if *keyword == "none" || *keyword == "normal")
+        || matches!(specified_values.get("display"), Some(css::Value::Keyword(display)) if *display == "none") {
+        return None;
+    }
+    update_counters(&specified_values, None, counters);
+    let text: String = content_text(content, element, counters, quotes);
+
+    let generated_node = |node_type: dom::NodeType| -> Cow<'a, dom::Node> {
+        Cow::Owned(dom::Node { node_type, parent: None, first_child: None, last_child: None, prev_sibling: None, next_sibling: None, source: None })
+    };
+    let tag_name: Atom = Atom::from(match pseudo_element {
+        css::PseudoElement::Before => "::before",
+        css::PseudoElement::After => "::after",
+    });
+    let text: StyledNode<'a> = StyledNode {
+        node_id: node,
+        node: generated_node(dom::NodeType::Text(text)),
+        pseudo_element: Some(pseudo_element),
+        specified_values: HashMap::default(),
+        children: Vec::new(),
+    };
+    Some(StyledNode {
+        node_id: node,
+        node: generated_node(dom::NodeType::Element(dom::Element { tag_name, attributes: dom::AttributeMap::default() })),
+        pseudo_element: Some(pseudo_element),
+        specified_values,
+        children: vec![text],
+    })
+}
+
+/// Apply the counter properties in `values` to `counters`, for `element` (or for a
+/// pseudo-element, if that's `None`): resets first, then increments, then sets.
+fn update_counters(values: &PropertyMap, element: Option<&dom::Element>, counters: &mut Counters) {
+    let tag_name: &str = element.map_or("", |element: &dom::Element| element.tag_name.as_str());
+    let list_item: Atom = Atom::from("list-item");
+
+    let mut resets: Vec<(Atom, i32)> = values.get("counter-reset").map(|value: &css::Value| counters::changes(value, 0)).unwrap_or_default();
+    if values.get("counter-reset").is_none() && matches!(tag_name, "ol" | "ul" | "menu") {
+        let start: i32 = element.and_then(|element: &dom::Element| element.attr("start")?.trim().parse().ok()).unwrap_or(1);
+        resets.push((list_item, start.saturating_sub(1)));
+    }
+    let mut increments: Vec<(Atom, i32)> = values.get("counter-increment").map(|value: &css::Value| counters::changes(value, 1)).unwrap_or_default();
+    let mut sets: Vec<(Atom, i32)> = values.get("counter-set").map(|value: &css::Value| counters::changes(value, 0)).unwrap_or_default();
+    if tag_name == "li" {
+        if !increments.iter().any(|&(name, _): &(Atom, i32)| name == list_item) {
+            increments.push((list_item, 1));
+        }
+        if let Some(value) = element.and_then(|element: &dom::Element| element.attr("value")?.trim().parse().ok()) {
+            sets.push((list_item, value));
+        }
+    }
+
+    for (name, value) in resets {
+        counters.reset(name, value);
+    }
+    for (name, by) in increments {
+        counters.increment(name, by);
+    }
+    for (name, value) in sets {
+        counters.set(name, value);
+    }
+}
+
+/// The text that a `content` value makes for `element`, opening and closing `quotes` as
+/// it says.
+fn content_text(content: &css::Value, element: &dom::Element, counters: &Counters, quotes: &mut Quotes) -> String {
+    let parts: &[css::Value] = match content {
+        css::Value::Sequence(parts) => parts,
+        part => core::slice::from_ref(part),
+    };
+    let mut text: String = String::new();
+    for part in parts {
+        match part {
+            css::Value::StringValue(string) => text.push_str(string),
+            css::Value::Keyword(keyword) => match keyword.as_str() {
+                "open-quote" => text.push_str(&quotes.open()),
+                "close-quote" => text.push_str(&quotes.close()),
+                "no-open-quote" => quotes.open_silently(),
+                "no-close-quote" => quotes.close_silently(),
+                _ => {}
+            },
+            css::Value::Function(function, arguments) => {
+                let keyword = |i: usize| match arguments.get(i) {
+                    Some(css::Value::Keyword(keyword)) => Some(*keyword),
+                    _ => None,
+                };
+                match (function.as_str(), keyword(0)) {
+                    ("counter", Some(name)) => {
+                        let style: &str = keyword(1).map_or("decimal", |style: Atom| style.as_str());
+                        text.push_str(&counters::format(counters.value(name), style));
+                    }
+                    ("counters", Some(name)) => {
+                        let separator: &str = match arguments.get(1) {
+                            Some(css::Value::StringValue(separator)) => separator,
+                            _ => "",
+                        };
+                        let style: &str = keyword(2).map_or("decimal", |style: Atom| style.as_str());
+                        let values: Vec<String> = counters.values(name).into_iter()
+                            .map(|value: i32| counters::format(value, style))
+                            .collect();
+                        text.push_str(&if values.is_empty() { counters::format(0, style) } else { values.join(separator) });
+                    }
+                    ("attr", Some(name)) => text.push_str(element.attr(name.as_str()).unwrap_or("")),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+
+/*
+    Restyling After a State Change
+
+    When the mouse moves from one element to another, a few elements leave or enter the
+    `:hover` state. Most of the time no rule cares: the stylesheet has no `:hover` rules
+    for them, and nothing needs to be redone. When one does, what it changes decides the
+    cost. A new `color` or `background` only needs the element repainted, but a new
+    `width` or `display` can move everything after it.
+
+    Properties don't inherit in this engine, so a state change can only restyle the
+    elements whose state changed.
+ */
+/// Properties that affect how a box is painted, but not its size or position.
+const PAINT_ONLY_PROPERTIES: [&str; 3] = ["background", "border-color", "color"];
+
+/// Work out what has to be redone after the elements in `changed` enter or leave a
+/// pseudo-class state.
+pub fn state_change_invalidation(tree: &dom::Tree, stylesheet: &css::Stylesheet, states: &ElementStates, changed: &HashSet<dom::NodeId>) -> Invalidation {
+    let mut invalidation: Invalidation = Invalidation::None;
+    for &node in changed {
+        let element: &dom::Element = match tree[node].node_type {
+            dom::NodeType::Element(ref element) => element,
+            _ => continue,
+        };
+        for rule in &states.stylesheet_for(tree, node, stylesheet).rules {
+            let affected: bool = rule.selectors.iter().any(|selector: &css::Selector| {
+                let css::Selector::Simple(ref simple) = *selector;
+                !simple.pseudo_classes.is_empty() && matches_simple_selector(element, simple)
+            });
+            if !affected {
+                continue;
+            }
+            let paint_only: bool = rule.declarations.iter()
+                .all(|declaration: &css::Declaration| PAINT_ONLY_PROPERTIES.contains(&declaration.name.as_str()));
+            if !paint_only {
+                return Invalidation::Relayout;
+            }
+            invalidation = Invalidation::Repaint;
+        }
+    }
+    invalidation
+}
+
+/*
+    Restyling After a Script Change
+
+    Scripts restyle elements by editing their `class` and `style` attributes. The script
+    host keeps a copy of each element from before its first edit, so we can compare the
+    element's specified values before and after, and apply the same rule as for state
+    changes: nothing to do if they're equal, a repaint if only paint properties differ,
+    and a new layout otherwise.
+ */
+/// Work out what has to be redone after scripts changed the elements in `old`, which
+/// maps each node to a copy of its element from before the change.
+pub fn restyle_invalidation(tree: &dom::Tree, stylesheet: &css::Stylesheet, states: &ElementStates, old: &HashMap<dom::NodeId, dom::Element>) -> Invalidation {
+    let mut invalidation: Invalidation = Invalidation::None;
+    for (&node, old_element) in old {
+        let element: &dom::Element = match tree[node].node_type {
+            dom::NodeType::Element(ref element) => element,
+            _ => continue,
+        };
+        let stylesheet: &css::Stylesheet = states.stylesheet_for(tree, node, stylesheet);
+        // The element's pseudo-elements may have changed too.
+        for pseudo_element in [None, Some(css::PseudoElement::Before), Some(css::PseudoElement::After)] {
+            let before: PropertyMap = specified_values(old_element, node, stylesheet, states, pseudo_element);
+            let after: PropertyMap = specified_values(element, node, stylesheet, states, pseudo_element);
+            let changed: HashSet<&Atom> = before.keys().chain(after.keys())
+                .filter(|&name: &&Atom| before.get(name) != after.get(name))
+                .collect();
+            if changed.iter().any(|name: &&Atom| !PAINT_ONLY_PROPERTIES.contains(&name.as_str())) {
+                return Invalidation::Relayout;
+            }
+            if !changed.is_empty() {
+                invalidation = Invalidation::Repaint;
+            }
+        }
     }
+    invalidation
 }
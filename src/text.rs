@@ -0,0 +1,499 @@
+//! Text styles: the inherited properties that say how text looks and wraps.
+
+use crate::atom::Atom;
+use crate::fonts::{self, FontDescriptor, FontStyle};
+use crate::{css, dom, style};
+#[cfg(not(feature = "std"))]
+use crate::prelude::*;
+
+/*
+    Text Styles
+
+    The box properties (`width`, `margin`, ...) apply to one box. The properties that
+    describe text are inherited instead: a `<p>` with `color: #cc0000` makes all the text
+    inside it red, including the text in its `<em>`, unless something closer to the text
+    says otherwise. The style tree only has the values that rules set on each node (see
+    `style`), so layout works out each box's text style from its parent's as it walks down
+    the tree, with `TextStyle::inherit`.
+
+    The inherited properties supported are:
+        font-family    keywords (including generic families) and strings, e.g.
+                       `"DejaVu Sans", Arial, sans-serif`
+        font-size      a length, or `xx-small` ... `xxx-large`, `smaller`, `larger`
+        font-weight    `normal`, `bold`, `bolder`, `lighter`
+        font-style     `normal`, `italic`, `oblique`
+        font-variant   `normal`, `small-caps`, `all-small-caps` (also as
+                       `font-variant-caps`)
+        line-height    a length, or `normal` (the font's own line spacing)
+        color          a color
+        white-space    `normal`, `nowrap`, `pre`, `pre-wrap`, `pre-line`
+        text-align     `left`, `right`, `center`, `justify` (and `start` and `end`, for
+                       left-to-right text)
+        hyphens        `none`, `manual`, `auto` (see `hyphenation`)
+        text-transform `none`, `uppercase`, `lowercase`, `capitalize`
+        tab-size       a number of spaces, or a length (also as `-moz-tab-size`)
+
+    A box's language, which says how its text is hyphenated, is inherited too: it's the
+    `lang` attribute of the nearest element that has one.
+
+    Two properties that aren't inherited live here too, since they only change how a
+    block's lines are laid out (see "Truncation" in `layout`), and `inherit` resets them
+    for each element:
+        text-overflow  `clip`, `ellipsis` (only on a box whose `overflow` isn't `visible`)
+        line-clamp     a number of lines, or `none` (also as `-webkit-line-clamp`)
+
+    Browsers style some elements from their user-agent stylesheet. This engine has none,
+    so the few text defaults that matter most are built in: `<b>` and `<strong>` are bold,
+    `<i>` and `<em>` italic, code is monospace, headings are big and bold, and `<pre>`
+    keeps its white space. The page's own styles override them.
+ */
+
+/// The inherited text properties of a box.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TextStyle {
+    pub font: FontDescriptor,
+    pub font_size: f32,           // px
+    pub line_height: Option<f32>, // px, or `None` for `normal`
+    pub color: css::Color,
+    pub white_space: WhiteSpace,
+    pub text_align: TextAlign,
+    pub hyphens: Hyphens,
+    pub text_transform: TextTransform,
+    pub tab_size: TabSize,
+    pub font_variant: FontVariant,
+    pub lang: Option<Atom>, // from the nearest `lang` attribute
+    pub text_overflow: TextOverflow, // not inherited
+    pub line_clamp: Option<usize>,   // not inherited; `None` for `none`
+}
+
+/// CSS's `white-space` property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WhiteSpace {
+    Normal,
+    NoWrap,
+    Pre,
+    PreWrap,
+    PreLine,
+}
+
+/// CSS's `text-align` property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextAlign {
+    Left,
+    Right,
+    Center,
+    Justify,
+}
+
+/// CSS's `text-overflow` property: what the end of a line too long for its box shows.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextOverflow {
+    Clip,
+    Ellipsis,
+}
+
+/// CSS's `hyphens` property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Hyphens {
+    None,   // words only break at hyphens that are drawn anyway
+    Manual, // ... and at soft hyphens
+    Auto,   // ... and where the language's hyphenation dictionary allows
+}
+
+/// CSS's `text-transform` property.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TextTransform {
+    None,
+    Uppercase,
+    Lowercase,
+    Capitalize, // the first letter of each word
+}
+
+/// CSS's `tab-size` property: how far apart tab stops are.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum TabSize {
+    Spaces(f32), // times the width of a space
+    Px(f32),
+}
+
+/// CSS's `font-variant` property (just its `font-variant-caps` part).
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FontVariant {
+    Normal,
+    SmallCaps,    // lowercase letters drawn as small capitals
+    AllSmallCaps, // ... and capitals too
+}
+
+/// `font-size: medium`, the initial font size.
+pub const MEDIUM: f32 = 16.0;
+
+impl Default for TextStyle {
+    /// The initial values of the text properties.
+    fn default() -> TextStyle {
+        TextStyle {
+            font: FontDescriptor::default(),
+            font_size: MEDIUM,
+            line_height: None,
+            color: css::Color { r: 0, g: 0, b: 0, a: 255 },
+            white_space: WhiteSpace::Normal,
+            text_align: TextAlign::Left,
+            hyphens: Hyphens::Manual,
+            text_transform: TextTransform::None,
+            tab_size: TabSize::Spaces(8.0),
+            font_variant: FontVariant::Normal,
+            lang: None,
+            text_overflow: TextOverflow::Clip,
+            line_clamp: None,
+        }
+    }
+}
+
+impl TextStyle {
+    /// The text style of `node`, whose parent's text style is `self`.
+    pub fn inherit(&self, node: &style::StyledNode) -> TextStyle {
+        let mut style: TextStyle = self.clone();
+        (style.text_overflow, style.line_clamp) = (TextOverflow::Clip, None);
+        if let dom::NodeType::Element(ref element) = node.node.node_type {
+            style.apply_element_defaults(element.tag_name.as_str());
+            if let Some(lang) = element.attr("lang") {
+                // `lang=""` means the language is unknown.
+                style.lang = (!lang.is_empty()).then(|| Atom::from(lang));
+            }
+        }
+        for (name, value) in node.specified_values.iter() {
+            style.apply(name.as_str(), value);
+        }
+        // Text only overflows with an ellipsis where the overflow is hidden.
+        if !matches!(node.value("overflow"), Some(css::Value::Keyword(ref overflow)) if *overflow != "visible") {
+            style.text_overflow = TextOverflow::Clip;
+        }
+        style
+    }
+
+    /// Apply the built-in defaults for elements with tag name `tag`.
+    fn apply_element_defaults(&mut self, tag: &str) {
+        match tag {
+            "b" | "strong" | "th" => self.font.weight = fonts::BOLD_WEIGHT,
+            "i" | "em" | "cite" | "var" | "dfn" | "address" => self.font.style = FontStyle::Italic,
+            "code" | "kbd" | "samp" | "tt" => self.font.families = vec![Atom::from("monospace")],
+            "pre" | "listing" | "xmp" => {
+                self.font.families = vec![Atom::from("monospace")];
+                self.white_space = WhiteSpace::Pre;
+            }
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let scale: f32 = match tag {
+                    "h1" => 2.0,
+                    "h2" => 1.5,
+                    "h3" => 1.17,
+                    "h4" => 1.0,
+                    "h5" => 0.83,
+                    _ => 0.67,
+                };
+                self.font_size *= scale;
+                self.font.weight = fonts::BOLD_WEIGHT;
+            }
+            "small" => self.font_size /= 1.2,
+            "big" => self.font_size *= 1.2,
+            "center" => self.text_align = TextAlign::Center,
+            _ => {}
+        }
+    }
+
+    /// Apply one specified value, if it's a text property this understands.
+    fn apply(&mut self, name: &str, value: &css::Value) {
+        match (name, value) {
+            ("font-family", value) => {
+                let families: Vec<Atom> = font_families(value);
+                if !families.is_empty() {
+                    self.font.families = families;
+                }
+            }
+            ("font-size", &css::Value::Length(size, css::Unit::Px)) => self.font_size = size,
+            ("font-size", css::Value::Keyword(keyword)) => {
+                self.font_size = match keyword.as_str() {
+                    "xx-small" => 9.0,
+                    "x-small" => 10.0,
+                    "small" => 13.0,
+                    "medium" => MEDIUM,
+                    "large" => 18.0,
+                    "x-large" => 24.0,
+                    "xx-large" => 32.0,
+                    "xxx-large" => 48.0,
+                    "smaller" => self.font_size / 1.2,
+                    "larger" => self.font_size * 1.2,
+                    _ => self.font_size,
+                }
+            }
+            ("font-weight", css::Value::Keyword(keyword)) => {
+                let weight: u16 = self.font.weight;
+                // `bolder` and `lighter` follow CSS's table of relative weights.
+                self.font.weight = match keyword.as_str() {
+                    "normal" => fonts::NORMAL_WEIGHT,
+                    "bold" => fonts::BOLD_WEIGHT,
+                    "bolder" if weight < 350 => 400,
+                    "bolder" if weight < 550 => 700,
+                    "bolder" => weight.max(900),
+                    "lighter" if weight < 100 => weight,
+                    "lighter" if weight < 550 => 100,
+                    "lighter" if weight < 750 => 400,
+                    "lighter" => 700,
+                    _ => weight,
+                }
+            }
+            ("font-style", css::Value::Keyword(keyword)) => {
+                self.font.style = match keyword.as_str() {
+                    "normal" => FontStyle::Normal,
+                    "italic" => FontStyle::Italic,
+                    "oblique" => FontStyle::Oblique,
+                    _ => self.font.style,
+                }
+            }
+            ("font-variant" | "font-variant-caps", css::Value::Keyword(keyword)) => {
+                self.font_variant = match keyword.as_str() {
+                    "normal" => FontVariant::Normal,
+                    "small-caps" => FontVariant::SmallCaps,
+                    "all-small-caps" => FontVariant::AllSmallCaps,
+                    _ => self.font_variant,
+                }
+            }
+            ("line-height", &css::Value::Length(height, css::Unit::Px)) => self.line_height = Some(height),
+            ("line-height", css::Value::Keyword(keyword)) if *keyword == "normal" => self.line_height = None,
+            ("color", &css::Value::ColorValue(color)) => self.color = color,
+            ("white-space", css::Value::Keyword(keyword)) => {
+                self.white_space = match keyword.as_str() {
+                    "normal" => WhiteSpace::Normal,
+                    "nowrap" => WhiteSpace::NoWrap,
+                    "pre" => WhiteSpace::Pre,
+                    "pre-wrap" => WhiteSpace::PreWrap,
+                    "pre-line" => WhiteSpace::PreLine,
+                    _ => self.white_space,
+                }
+            }
+            ("text-align", css::Value::Keyword(keyword)) => {
+                self.text_align = match keyword.as_str() {
+                    "left" | "start" => TextAlign::Left,
+                    "right" | "end" => TextAlign::Right,
+                    "center" => TextAlign::Center,
+                    "justify" => TextAlign::Justify,
+                    _ => self.text_align,
+                }
+            }
+            ("text-overflow", css::Value::Keyword(keyword)) => {
+                self.text_overflow = match keyword.as_str() {
+                    "clip" => TextOverflow::Clip,
+                    "ellipsis" => TextOverflow::Ellipsis,
+                    _ => self.text_overflow,
+                }
+            }
+            ("line-clamp" | "-webkit-line-clamp", &css::Value::Number(lines)) if lines >= 1.0 => self.line_clamp = Some(lines as usize),
+            ("line-clamp" | "-webkit-line-clamp", css::Value::Keyword(keyword)) if *keyword == "none" => self.line_clamp = None,
+            ("text-transform", css::Value::Keyword(keyword)) => {
+                self.text_transform = match keyword.as_str() {
+                    "none" => TextTransform::None,
+                    "uppercase" => TextTransform::Uppercase,
+                    "lowercase" => TextTransform::Lowercase,
+                    "capitalize" => TextTransform::Capitalize,
+                    _ => self.text_transform,
+                }
+            }
+            ("tab-size" | "-moz-tab-size", &css::Value::Number(spaces)) if spaces >= 0.0 => self.tab_size = TabSize::Spaces(spaces),
+            ("tab-size" | "-moz-tab-size", &css::Value::Length(size, css::Unit::Px)) if size >= 0.0 => self.tab_size = TabSize::Px(size),
+            ("hyphens", css::Value::Keyword(keyword)) => {
+                self.hyphens = match keyword.as_str() {
+                    "none" => Hyphens::None,
+                    "manual" => Hyphens::Manual,
+                    "auto" => Hyphens::Auto,
+                    _ => self.hyphens,
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The families in a `font-family` value.
+fn font_families(value: &css::Value) -> Vec<Atom> {
+    match value {
+        css::Value::Keyword(family) => vec![*family],
+        css::Value::StringValue(family) => vec![Atom::from(family.as_str())],
+        css::Value::List(values) => values.iter().flat_map(font_families).collect(),
+        _ => Vec::new(),
+    }
+}
+
+
+/*
+    White Space
+
+    HTML source is full of white space that's only there to make it readable: newlines
+    between tags, and indentation. By default (`white-space: normal`) each run of spaces,
+    tabs, and newlines collapses to a single space, and a space at the start or end of a
+    line disappears. `pre` and `pre-wrap` keep all of it, and `pre-line` keeps the
+    newlines but collapses the rest. `normal`, `pre-wrap`, and `pre-line` let lines wrap
+    at spaces; `nowrap` and `pre` don't.
+
+    A tab that's kept reaches to the next tab stop. Tab stops are `tab-size` apart: a
+    number of spaces (8 by default), or a length. They're measured from the start of the
+    line, as the text has it: in `pre-wrap` text whose lines wrap, a tab after the wrap
+    keeps the width it has on the line as written (see `expand_tabs` in `layout`).
+
+    Runs of white space collapse across elements, so `a <b> b</b>` has one space, not two.
+    The caller keeps track of whether the text so far ended in a collapsible space.
+ */
+impl WhiteSpace {
+    /// Return true if runs of spaces and tabs collapse to one space.
+    pub fn collapses_spaces(self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::NoWrap | WhiteSpace::PreLine)
+    }
+
+    /// Return true if newlines break the line, rather than counting as spaces.
+    pub fn preserves_newlines(self) -> bool {
+        matches!(self, WhiteSpace::Pre | WhiteSpace::PreWrap | WhiteSpace::PreLine)
+    }
+
+    /// Return true if lines may wrap.
+    pub fn wraps(self) -> bool {
+        matches!(self, WhiteSpace::Normal | WhiteSpace::PreWrap | WhiteSpace::PreLine)
+    }
+}
+
+/// Collapse the white space in `text` as `white_space` says. `after_space` says whether
+/// the text before it ended in a collapsible space (or the start of a line), and is
+/// updated for the text after it.
+pub fn collapse_white_space(text: &str, white_space: WhiteSpace, after_space: &mut bool) -> String {
+    let mut result: String = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\r' => {}
+            '\n' if white_space.preserves_newlines() => {
+                // With `pre-line`, spaces around a newline go too.
+                if white_space.collapses_spaces() && result.ends_with(' ') {
+                    result.pop();
+                }
+                result.push('\n');
+                *after_space = white_space.collapses_spaces();
+            }
+            ' ' | '\t' | '\n' | '\x0c' if white_space.collapses_spaces() => {
+                if !*after_space {
+                    result.push(' ');
+                }
+                *after_space = true;
+            }
+            '\t' => {
+                result.push('\t');
+                *after_space = false;
+            }
+            c => {
+                result.push(c);
+                *after_space = false;
+            }
+        }
+    }
+    result
+}
+
+
+/*
+    Case
+
+    `text-transform` changes the case of the text as it's laid out, leaving the DOM as it
+    is: a heading written "Chapter one" can be drawn as "CHAPTER ONE" or "Chapter One".
+    The mappings are Unicode's, so "straße" becomes "STRASSE", and Turkish and Azeri text
+    (by its `lang`) get their dotted and dotless i's right: "i" uppercases to "İ", and "I"
+    lowercases to "ı". `capitalize` makes the first letter of each word a capital (in
+    title case, for the few letters like "ǆ" that have one) and leaves the rest alone. A
+    word continues through letters, digits, and apostrophes, and across elements, so the
+    caller passes the character before the text.
+
+    `font-variant: small-caps` draws lowercase letters as capitals the height of the
+    lowercase ones. Few fonts have real small capitals, so they're synthesized, the way
+    browsers do when the font lacks them: the letters are uppercased and drawn at
+    `SMALL_CAPS_SCALE` times the font size. `all-small-caps` shrinks the capitals too.
+ */
+
+/// How big synthesized small capitals are, relative to the font size.
+pub const SMALL_CAPS_SCALE: f32 = 0.7;
+
+/// Whether `lang` is a language whose i's have and lack dots in both cases.
+fn has_dotted_i(lang: Option<&str>) -> bool {
+    lang.is_some_and(|lang: &str| {
+        let primary: &str = lang.split(['-', '_']).next().unwrap_or("");
+        primary.eq_ignore_ascii_case("tr") || primary.eq_ignore_ascii_case("az")
+    })
+}
+
+/// Add `c` in uppercase to `out`.
+fn push_uppercase(out: &mut String, c: char, dotted_i: bool) {
+    match c {
+        'i' if dotted_i => out.push('\u{130}'),
+        c => out.extend(c.to_uppercase()),
+    }
+}
+
+/// Add `c` in title case to `out`: uppercase, except for the digraphs that have a title
+/// case of their own.
+fn push_titlecase(out: &mut String, c: char, dotted_i: bool) {
+    match c {
+        '\u{1C4}'..='\u{1C6}' => out.push('\u{1C5}'), // Ǆ ǅ ǆ
+        '\u{1C7}'..='\u{1C9}' => out.push('\u{1C8}'), // Ǉ ǈ ǉ
+        '\u{1CA}'..='\u{1CC}' => out.push('\u{1CB}'), // Ǌ ǋ ǌ
+        '\u{1F1}'..='\u{1F3}' => out.push('\u{1F2}'), // Ǳ ǲ ǳ
+        c => push_uppercase(out, c, dotted_i),
+    }
+}
+
+/// Whether `c`, coming after a letter, is still part of the same word.
+fn continues_word(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '\'' | '\u{2019}' | fonts::SOFT_HYPHEN)
+}
+
+/// Change the case of `text`, in language `lang`, as `transform` says. `previous` is the
+/// character before it, if it continues a line of text.
+pub fn transform_text(text: &str, transform: TextTransform, lang: Option<&str>, previous: Option<char>) -> String {
+    let dotted_i: bool = has_dotted_i(lang);
+    if transform == TextTransform::Lowercase {
+        // Lowercasing looks at the letters around each one, for the Greek final sigma.
+        if dotted_i {
+            return text.replace('I', "\u{131}").replace('\u{130}', "i").to_lowercase();
+        }
+        return text.to_lowercase();
+    }
+    let mut out: String = String::with_capacity(text.len());
+    let mut in_word: bool = previous.is_some_and(continues_word);
+    for c in text.chars() {
+        match transform {
+            TextTransform::None | TextTransform::Lowercase => out.push(c),
+            TextTransform::Uppercase => push_uppercase(&mut out, c, dotted_i),
+            TextTransform::Capitalize if !in_word && c.is_alphabetic() => push_titlecase(&mut out, c, dotted_i),
+            TextTransform::Capitalize => out.push(c),
+        }
+        in_word = if in_word { continues_word(c) } else { c.is_alphanumeric() };
+    }
+    out
+}
+
+/// Split `text`, in language `lang`, into runs for `font-variant`: each is the text to
+/// draw, and whether it's drawn as small capitals. Characters without case join the run
+/// before them.
+pub fn small_caps(text: &str, variant: FontVariant, lang: Option<&str>) -> Vec<(String, bool)> {
+    if variant == FontVariant::Normal || text.is_empty() {
+        return vec![(text.to_string(), false)];
+    }
+    let dotted_i: bool = has_dotted_i(lang);
+    let mut runs: Vec<(String, bool)> = Vec::new();
+    for c in text.chars() {
+        let cased: bool = c.is_lowercase() || c.is_uppercase();
+        let small: bool = c.is_lowercase() || (cased && variant == FontVariant::AllSmallCaps);
+        match runs.last_mut() {
+            Some((_, last_small)) if *last_small == small || !cased => {}
+            _ => runs.push((String::new(), small)),
+        }
+        let (run, _) = runs.last_mut().unwrap();
+        if small {
+            push_uppercase(run, c, dotted_i);
+        } else {
+            run.push(c);
+        }
+    }
+    runs
+}
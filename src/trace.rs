@@ -0,0 +1,305 @@
+//! Spans around each stage of the rendering pipeline, for the `tracing` crate, and
+//! timelines of them in the Chrome trace format.
+
+#[cfg(feature = "std")]
+use crate::debug::json_string;
+#[cfg(feature = "tracing")]
+use std::collections::HashMap;
+#[cfg(feature = "tracing")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "std")]
+use std::collections::VecDeque;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+
+/*
+    Tracing
+
+    With the `tracing` feature, every stage of the pipeline runs inside a
+    [tracing](https://docs.rs/tracing) span, at the INFO level, with the target
+    `build_a_browser_engine_in_rust::trace`:
+
+        span                  fields
+        html::parse           bytes, nodes
+        css::parse            bytes, rules
+        style::style_tree     nodes          (nodes styled)
+        layout::layout_tree   boxes          (boxes laid out)
+        painting::display     items          (display items built)
+        painting::rasterize   items, pixels
+
+    The counts are recorded when the stage finishes. An embedder sees the spans in
+    whatever subscriber it installs (`tracing-subscriber`, `tracing-chrome`, or its own),
+    and the `bench` command uses `Recorder` below to print the counts next to its timings.
+
+    Without the feature, `stage!` expands to a guard with nothing in it, and the counts are
+    never computed, so the pipeline costs exactly what it did before.
+
+    e.g.
+        let _stage = stage!("style::style_tree", nodes);
+        let root: StyledNode = ...;
+        _stage.record("nodes", || count(&root));
+ */
+
+/// A stage of the pipeline in progress. The span ends when this is dropped.
+pub(crate) struct Stage {
+    #[cfg(feature = "tracing")]
+    pub(crate) span: tracing::span::EnteredSpan,
+}
+
+impl Stage {
+    /// Record a count on the stage's span. `count` is only called if someone is listening.
+    #[allow(unused_variables)]
+    pub(crate) fn record(&self, field: &str, count: impl FnOnce() -> usize) {
+        #[cfg(feature = "tracing")]
+        if !self.span.is_disabled() && self.span.has_field(field) {
+            self.span.record(field, count() as u64);
+        }
+    }
+}
+
+/// Start a stage: `stage!("name", field, ...)` opens a span named "name" with empty
+/// fields, to be filled in with `Stage::record`.
+macro_rules! stage {
+    ($name:literal $(, $field:ident)*) => {{
+        #[cfg(feature = "tracing")]
+        let stage: $crate::trace::Stage = $crate::trace::Stage {
+            span: tracing::info_span!(target: "build_a_browser_engine_in_rust::trace", $name, $($field = tracing::field::Empty),*).entered(),
+        };
+        #[cfg(not(feature = "tracing"))]
+        let stage: $crate::trace::Stage = $crate::trace::Stage {};
+        stage
+    }};
+}
+pub(crate) use stage;
+
+
+/*
+    Recording Spans
+
+    The `tracing` crate only produces spans; something else has to collect them. A full
+    subscriber crate is more than the `bench` command needs, so `Recorder` is a minimal
+    one: it keeps every span it sees, with its duration and the numbers recorded on it.
+
+    e.g.
+        let recorder: Recorder = Recorder::default();
+        tracing::subscriber::with_default(recorder.clone(), || page.paint());
+        for span in recorder.spans() {
+            println!("{} took {:?}: {:?}", span.name, span.duration, span.counts);
+        }
+ */
+
+/// A span, as seen by a `Recorder`.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Debug)]
+pub struct RecordedSpan {
+    pub name: &'static str,
+    pub duration: Duration,                 // total time spent inside the span
+    pub counts: HashMap<&'static str, u64>, // the numbers recorded on it
+}
+
+/// A recorded span, with when it was last entered if it's running now.
+#[cfg(feature = "tracing")]
+type Entry = (RecordedSpan, Option<Instant>);
+
+/// A `tracing` subscriber that keeps the spans it sees. Clones share the same spans.
+#[cfg(feature = "tracing")]
+#[derive(Clone, Default)]
+pub struct Recorder {
+    spans: Arc<Mutex<Vec<Entry>>>,
+}
+
+#[cfg(feature = "tracing")]
+impl Recorder {
+    /// The spans recorded so far, in the order they started.
+    pub fn spans(&self) -> Vec<RecordedSpan> {
+        let spans = self.spans.lock().unwrap_or_else(|err| err.into_inner());
+        spans.iter().map(|(span, _)| span.clone()).collect()
+    }
+
+    /// Run `f` on the span with the given id.
+    fn with_span(&self, id: &tracing::span::Id, f: impl FnOnce(&mut Entry)) {
+        let mut spans = self.spans.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(span) = spans.get_mut(id.into_u64() as usize - 1) {
+            f(span);
+        }
+    }
+}
+
+/// Collects the numbers recorded on a span.
+#[cfg(feature = "tracing")]
+struct Counts<'a>(&'a mut HashMap<&'static str, u64>);
+
+#[cfg(feature = "tracing")]
+impl tracing::field::Visit for Counts<'_> {
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        self.0.insert(field.name(), value);
+    }
+
+    fn record_i64(&mut self, field: &tracing::field::Field, value: i64) {
+        self.0.insert(field.name(), value.max(0) as u64);
+    }
+
+    fn record_debug(&mut self, _: &tracing::field::Field, _: &dyn std::fmt::Debug) {}
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for Recorder {
+    fn enabled(&self, metadata: &tracing::Metadata) -> bool {
+        metadata.is_span()
+    }
+
+    fn new_span(&self, attributes: &tracing::span::Attributes) -> tracing::span::Id {
+        let mut span: RecordedSpan = RecordedSpan { name: attributes.metadata().name(), duration: Duration::ZERO, counts: HashMap::new() };
+        attributes.record(&mut Counts(&mut span.counts));
+        let mut spans = self.spans.lock().unwrap_or_else(|err| err.into_inner());
+        spans.push((span, None));
+        tracing::span::Id::from_u64(spans.len() as u64) // ids start at 1
+    }
+
+    fn record(&self, id: &tracing::span::Id, values: &tracing::span::Record) {
+        self.with_span(id, |(span, _)| values.record(&mut Counts(&mut span.counts)));
+    }
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, _: &tracing::Event) {}
+
+    fn enter(&self, id: &tracing::span::Id) {
+        self.with_span(id, |(_, entered)| *entered = Some(Instant::now()));
+    }
+
+    fn exit(&self, id: &tracing::span::Id) {
+        self.with_span(id, |(span, entered)| {
+            if let Some(start) = entered.take() {
+                span.duration += start.elapsed();
+            }
+        });
+    }
+}
+
+
+/*
+    Timelines
+
+    Spans need a subscriber and a feature flag. A `Page` also keeps a plain timeline of
+    the pipeline stages it ran (parsing, styling, layout, building the display list, and
+    painting), always, since a stage costs two reads of the clock more. The timeline
+    keeps the last `MAX_TRACE_EVENTS` events, so a page that repaints forever doesn't
+    grow forever.
+
+    `Page::export_trace` writes the timeline in the Chrome trace event format, which
+    chrome://tracing, Perfetto, and speedscope display as a timeline. Each stage is a
+    "complete" event, with its start and duration in microseconds since the process first
+    asked for the time, so events from every page (and from the embedder, who can add
+    its own with `Page::add_trace_event`) line up on one timeline:
+
+        { "traceEvents": [{ "name": "layout", "cat": "engine", "ph": "X", "ts": us,
+                            "dur": us, "pid": 1, "tid": 1, "args": {} }, ...] }
+
+    WebAssembly has no clock to read, so there, pages record nothing.
+ */
+
+/// How many events a `Timeline` keeps.
+#[cfg(feature = "std")]
+pub const MAX_TRACE_EVENTS: usize = 10_000;
+
+/// The time every event's start is measured from.
+#[cfg(feature = "std")]
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// The time a stage starts, or `None` where there is no clock (WebAssembly).
+#[cfg(feature = "std")]
+pub fn now() -> Option<Instant> {
+    if cfg!(target_arch = "wasm32") {
+        return None;
+    }
+    epoch();
+    Some(Instant::now())
+}
+
+/// Something that happened over a span of time, e.g. a stage of the pipeline.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug)]
+pub struct TraceEvent {
+    pub name: String,
+    pub category: String,             // "engine" for the engine's own stages
+    pub start: Duration,              // since the epoch
+    pub duration: Duration,
+    pub args: Vec<(String, u64)>,     // shown next to the event, e.g. counts
+}
+
+#[cfg(feature = "std")]
+impl TraceEvent {
+    /// An event that started at `start` (from `now`) and ends now, or `None` if there
+    /// is no clock.
+    pub fn since(name: &str, category: &str, start: Option<Instant>) -> Option<TraceEvent> {
+        let start: Instant = start?;
+        Some(TraceEvent {
+            name: name.to_string(),
+            category: category.to_string(),
+            start: start.saturating_duration_since(epoch()),
+            duration: start.elapsed(),
+            args: Vec::new(),
+        })
+    }
+}
+
+/// The most recent events, oldest first.
+#[cfg(feature = "std")]
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    events: VecDeque<TraceEvent>,
+}
+
+#[cfg(feature = "std")]
+impl Timeline {
+    /// Add an event, forgetting the oldest one if there are too many.
+    pub fn push(&mut self, event: TraceEvent) {
+        if self.events.len() == MAX_TRACE_EVENTS {
+            self.events.pop_front();
+        }
+        self.events.push_back(event);
+    }
+
+    /// The events, oldest first.
+    pub fn events(&self) -> impl Iterator<Item = &TraceEvent> {
+        self.events.iter()
+    }
+
+    /// Forget every event.
+    pub fn clear(&mut self) {
+        self.events.clear();
+    }
+}
+
+#[cfg(feature = "std")]
+impl Extend<TraceEvent> for Timeline {
+    fn extend<I: IntoIterator<Item = TraceEvent>>(&mut self, events: I) {
+        for event in events {
+            self.push(event);
+        }
+    }
+}
+
+/// Write events in the Chrome trace event format.
+#[cfg(feature = "std")]
+pub fn chrome_trace<'a>(events: impl IntoIterator<Item = &'a TraceEvent>) -> String {
+    let events: Vec<String> = events.into_iter()
+        .map(|event: &TraceEvent| {
+            let args: Vec<String> = event.args.iter().map(|(name, value): &(String, u64)| format!("{}:{}", json_string(name), value)).collect();
+            format!(
+                "{{\"name\":{},\"cat\":{},\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\"pid\":1,\"tid\":1,\"args\":{{{}}}}}",
+                json_string(&event.name), json_string(&event.category),
+                event.start.as_secs_f64() * 1e6, event.duration.as_secs_f64() * 1e6, args.join(","),
+            )
+        })
+        .collect();
+    format!("{{\"traceEvents\":[\n{}\n],\"displayTimeUnit\":\"ms\"}}\n", events.join(",\n"))
+}
+
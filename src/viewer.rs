@@ -0,0 +1,309 @@
+//! An interactive window that shows a page (behind the `viewer` feature).
+
+use crate::engine::Page;
+use std::error::Error;
+use std::num::NonZeroU32;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use winit::application::ApplicationHandler;
+use winit::dpi::{LogicalSize, PhysicalPosition, PhysicalSize};
+use winit::event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{Key, ModifiersState, NamedKey};
+use winit::window::{Window, WindowId};
+
+
+/*
+    Viewing Pages
+
+    Writing PNG files is fine for tests, but while working on the engine it's nicer to see
+    the page in a window and resize it to watch the layout respond. The window is created
+    with [winit](https://docs.rs/winit), and the canvas is copied into it with
+    [softbuffer](https://docs.rs/softbuffer), which needs no GPU.
+
+    Resizing goes through `Page::set_viewport`, so only a change of width lays the page
+    out again; a change of height just repaints from the cached display list. Images that
+    finish loading while the window is open are picked up on the next frame. With the `js`
+    feature, the page's clock follows the wall clock, so script timers and animations run
+    in real time.
+
+    The mouse wheel scrolls whatever is under the cursor (an `overflow: auto` box, or else
+    the whole document), and the arrow keys, Page Up/Down, Space, Home, and End scroll the
+    document.
+
+    Moving the mouse hit tests the page to find the element under the cursor, so `:hover`
+    rules apply; scrolling moves content under a still cursor, so it hit tests again.
+
+    Clicking a form control focuses it (and ticks or unticks checkboxes), after giving the
+    page's scripts the `click` event. While a text field has focus, typing goes into it:
+    printable keys add to the end of its value, Backspace deletes, and Escape takes the
+    focus away again.
+
+    Ctrl + and Ctrl - (Cmd on macOS) zoom in and out through the usual browser zoom levels,
+    and Ctrl 0 goes back to 100%. The window starts at the page's viewport size in CSS px,
+    so on a HiDPI screen it has more device pixels than that; the page is told the screen's
+    scale factor, and told again if the window moves to a different screen.
+ */
+
+/// How far one notch of the mouse wheel, or one arrow key press, scrolls, in px.
+const LINE_HEIGHT: f32 = 40.0;
+
+/// The zoom levels Ctrl + and Ctrl - step through.
+const ZOOM_LEVELS: [f32; 13] = [0.5, 0.67, 0.75, 0.8, 0.9, 1.0, 1.1, 1.25, 1.5, 1.75, 2.0, 2.5, 3.0];
+
+/// Open a window showing `page`, and run until it is closed.
+pub fn run(page: Page) -> Result<(), Box<dyn Error>> {
+    let event_loop: EventLoop<()> = EventLoop::new()?;
+    let mut viewer: Viewer = Viewer {
+        page,
+        window: None,
+        cursor: (0.0, 0.0),
+        modifiers: ModifiersState::empty(),
+        #[cfg(feature = "js")]
+        clock: Instant::now(),
+        error: None,
+    };
+    event_loop.run_app(&mut viewer)?;
+    match viewer.error {
+        Some(error) => Err(error),
+        None => Ok(()),
+    }
+}
+
+/// The window and the surface we draw into.
+struct ViewerWindow {
+    window: Rc<Window>,
+    surface: softbuffer::Surface<Rc<Window>, Rc<Window>>,
+}
+
+struct Viewer {
+    page: Page,
+    window: Option<ViewerWindow>, // created once the event loop starts
+    cursor: (f32, f32),             // where the mouse is, in viewport (device) px
+    modifiers: ModifiersState,      // which of Ctrl, Shift, etc. are held down
+    #[cfg(feature = "js")]
+    clock: Instant,                 // when the page's clock was last moved forward
+    error: Option<Box<dyn Error>>,  // why we had to give up, reported by `run`
+}
+
+impl Viewer {
+    fn open_window(&mut self, event_loop: &ActiveEventLoop) -> Result<ViewerWindow, Box<dyn Error>> {
+        let (width, height) = self.page.viewport();
+        let scale: f32 = self.page.scale();
+        let attributes = Window::default_attributes()
+            .with_title(self.title())
+            .with_inner_size(LogicalSize::new(width as f32 / scale, height as f32 / scale));
+        let window: Rc<Window> = Rc::new(event_loop.create_window(attributes)?);
+        self.page.set_device_scale(window.scale_factor() as f32);
+        let context: softbuffer::Context<Rc<Window>> = softbuffer::Context::new(window.clone())?;
+        let surface: softbuffer::Surface<Rc<Window>, Rc<Window>> = softbuffer::Surface::new(&context, window.clone())?;
+        Ok(ViewerWindow { window, surface })
+    }
+
+    /// The text of the page's `<title>`, or a default.
+    fn title(&self) -> String {
+        self.page.document().title().unwrap_or_else(|| "browser-engine".to_string())
+    }
+
+    /// Paint the page and copy the pixels into the window.
+    fn redraw(&mut self) -> Result<(), Box<dyn Error>> {
+        let viewer_window: &mut ViewerWindow = match self.window {
+            Some(ref mut viewer_window) => viewer_window,
+            None => return Ok(()),
+        };
+        let size: PhysicalSize<u32> = viewer_window.window.inner_size();
+        let (width, height) = match (NonZeroU32::new(size.width), NonZeroU32::new(size.height)) {
+            (Some(width), Some(height)) => (width, height),
+            _ => return Ok(()), // minimized
+        };
+        self.page.set_viewport(size.width as usize, size.height as usize);
+        let canvas = self.page.paint();
+
+        viewer_window.surface.resize(width, height)?;
+        let mut buffer = viewer_window.surface.buffer_mut()?;
+        // softbuffer wants 0RGB pixels, one u32 each.
+        for (pixel, color) in buffer.iter_mut().zip(&canvas.pixels) {
+            *pixel = (color.r as u32) << 16 | (color.g as u32) << 8 | color.b as u32;
+        }
+        buffer.present()?;
+        Ok(())
+    }
+
+    /// Zoom, edit the focused text field, or scroll the document in response to a key
+    /// press. `text` is what the key types, if anything.
+    fn key_pressed(&mut self, key: &Key, text: Option<&str>) {
+        if self.modifiers.control_key() || self.modifiers.super_key() {
+            return self.zoom_key_pressed(key);
+        }
+        if self.page.focused_node().is_some() && self.edit_key_pressed(key, text) {
+            return self.request_redraw();
+        }
+        let (x, y) = self.page.scroll_position();
+        let page_height: f32 = self.page.viewport().1 as f32 / self.page.scale() * 0.9;
+        match key {
+            Key::Named(NamedKey::ArrowUp) => self.page.scroll_by(0.0, -LINE_HEIGHT),
+            Key::Named(NamedKey::ArrowDown) => self.page.scroll_by(0.0, LINE_HEIGHT),
+            Key::Named(NamedKey::ArrowLeft) => self.page.scroll_by(-LINE_HEIGHT, 0.0),
+            Key::Named(NamedKey::ArrowRight) => self.page.scroll_by(LINE_HEIGHT, 0.0),
+            Key::Named(NamedKey::PageUp) => self.page.scroll_by(0.0, -page_height),
+            Key::Named(NamedKey::PageDown) | Key::Named(NamedKey::Space) => self.page.scroll_by(0.0, page_height),
+            Key::Named(NamedKey::Home) => self.page.scroll_to(x, 0.0),
+            Key::Named(NamedKey::End) => self.page.scroll_to(x, f32::INFINITY),
+            _ => return,
+        }
+        if (x, y) != self.page.scroll_position() {
+            self.update_hover();
+            self.request_redraw();
+        }
+    }
+
+    /// Type into the focused element. Returns false if the key isn't for it.
+    fn edit_key_pressed(&mut self, key: &Key, text: Option<&str>) -> bool {
+        match key {
+            Key::Named(NamedKey::Backspace) => self.page.delete_backward(),
+            Key::Named(NamedKey::Enter) => self.page.type_text("\n"),
+            Key::Named(NamedKey::Escape) => {
+                self.page.set_focused_node(None);
+                true
+            }
+            _ => text.is_some_and(|text: &str| self.page.type_text(text)),
+        }
+    }
+
+    /// Step to the next or previous zoom level for Ctrl +, Ctrl -, or Ctrl 0.
+    fn zoom_key_pressed(&mut self, key: &Key) {
+        let zoom: f32 = self.page.zoom();
+        let new_zoom: Option<f32> = match key.as_ref() {
+            Key::Character("+") | Key::Character("=") => ZOOM_LEVELS.iter().copied().find(|&level: &f32| level > zoom + 0.001),
+            Key::Character("-") => ZOOM_LEVELS.iter().copied().rev().find(|&level: &f32| level < zoom - 0.001),
+            Key::Character("0") => Some(1.0),
+            _ => None,
+        };
+        if let Some(new_zoom) = new_zoom.filter(|&new_zoom: &f32| new_zoom != zoom) {
+            self.page.set_zoom(new_zoom);
+            self.update_hover();
+            self.request_redraw();
+        }
+    }
+
+    /// Scroll whatever is under the cursor.
+    fn mouse_wheel(&mut self, delta: MouseScrollDelta) {
+        // Wheel deltas point the way the content should move, the opposite of scrolling.
+        let scale: f32 = self.page.scale();
+        let (dx, dy) = match delta {
+            MouseScrollDelta::LineDelta(x, y) => (-x * LINE_HEIGHT, -y * LINE_HEIGHT),
+            MouseScrollDelta::PixelDelta(position) => (-position.x as f32 / scale, -position.y as f32 / scale),
+        };
+        if self.page.scroll_at(self.cursor.0, self.cursor.1, dx, dy) {
+            self.update_hover();
+            self.request_redraw();
+        }
+    }
+
+    /// Click whatever is under the cursor.
+    fn mouse_pressed(&mut self) {
+        if self.page.click_at(self.cursor.0, self.cursor.1) {
+            self.request_redraw();
+        }
+    }
+
+    /// Nothing is hovered while the cursor is outside the window.
+    fn mouse_left(&mut self) {
+        if self.page.set_hovered_node(None) {
+            self.request_redraw();
+        }
+    }
+
+    /// Hover over whatever is under the cursor.
+    fn update_hover(&mut self) {
+        if self.page.hover_at(self.cursor.0, self.cursor.1) {
+            self.request_redraw();
+        }
+    }
+
+    /// Run the page's timers and animation frames up to `now`, and return when the next
+    /// one is due.
+    #[cfg(feature = "js")]
+    fn advance_clock(&mut self, now: Instant) -> Option<Instant> {
+        let elapsed: Duration = now - self.clock;
+        self.clock = now;
+        if self.page.advance_time(elapsed.as_secs_f64() * 1000.0) {
+            self.request_redraw();
+        }
+        self.page.next_task_delay().map(|delay: f64| now + Duration::from_secs_f64(delay / 1000.0))
+    }
+
+    fn request_redraw(&self) {
+        if let Some(ref viewer_window) = self.window {
+            viewer_window.window.request_redraw();
+        }
+    }
+
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: Box<dyn Error>) {
+        self.error = Some(error);
+        event_loop.exit();
+    }
+}
+
+impl ApplicationHandler for Viewer {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if self.window.is_some() {
+            return;
+        }
+        match self.open_window(event_loop) {
+            Ok(viewer_window) => self.window = Some(viewer_window),
+            Err(error) => self.fail(event_loop, error),
+        }
+    }
+
+    fn window_event(&mut self, event_loop: &ActiveEventLoop, _: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::CloseRequested => event_loop.exit(),
+            WindowEvent::Resized(_) => self.request_redraw(),
+            WindowEvent::CursorMoved { position, .. } => {
+                let position: PhysicalPosition<f64> = position;
+                self.cursor = (position.x as f32, position.y as f32);
+                self.update_hover();
+            }
+            WindowEvent::CursorLeft { .. } => self.mouse_left(),
+            WindowEvent::MouseWheel { delta, .. } => self.mouse_wheel(delta),
+            WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers.state(),
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.page.set_device_scale(scale_factor as f32);
+                self.request_redraw();
+            }
+            WindowEvent::MouseInput { state: ElementState::Pressed, button: MouseButton::Left, .. } => self.mouse_pressed(),
+            WindowEvent::KeyboardInput { event: KeyEvent { ref logical_key, ref text, state: ElementState::Pressed, .. }, .. } => {
+                self.key_pressed(logical_key, text.as_deref());
+            }
+            WindowEvent::RedrawRequested => {
+                if let Err(error) = self.redraw() {
+                    self.fail(event_loop, error);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        if self.page.poll_images() {
+            self.request_redraw();
+        }
+        let now: Instant = Instant::now();
+        #[cfg(feature = "js")]
+        let mut wake: Option<Instant> = self.advance_clock(now);
+        #[cfg(not(feature = "js"))]
+        let mut wake: Option<Instant> = None;
+
+        // Keep checking for images while any are loading, and wake up for the page's
+        // timers and animation frames; otherwise sleep until an event.
+        if self.page.is_loading_images() {
+            let poll: Instant = now + Duration::from_millis(50);
+            wake = Some(wake.map_or(poll, |wake: Instant| wake.min(poll)));
+        }
+        match wake {
+            Some(wake) => event_loop.set_control_flow(ControlFlow::WaitUntil(wake)),
+            None => event_loop.set_control_flow(ControlFlow::Wait),
+        }
+    }
+}
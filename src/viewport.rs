@@ -0,0 +1,113 @@
+//! The `<meta name="viewport">` tag: how wide a page asks to be laid out, and how far
+//! zoomed in.
+
+use crate::dom;
+
+
+/*
+    Viewport Meta Tags
+
+    Phones are narrow, and most pages were written for wider screens, so mobile browsers
+    lay pages out wider than the screen and zoom out to fit. A page that works on a narrow
+    screen says so with a meta tag:
+
+        <meta name="viewport" content="width=device-width, initial-scale=1">
+
+    The content is a list of `key=value` pairs, separated by commas (or semicolons, or
+    spaces). The keys this engine understands are:
+        width           the width to lay the page out at: `device-width`, or a number of
+                        CSS px
+        initial-scale   how far to zoom in: 2 makes every CSS px two screen px wide
+        minimum-scale   the least and most the scale can be, which limit `initial-scale`
+        maximum-scale
+    Other keys (`height`, `user-scalable`, ...) and values that don't parse are ignored.
+
+    Here the "device width" is the width of the viewport in CSS px at the page's default
+    scale, i.e. before the meta tag has a say. `resolve` turns the tag into the layout
+    width and the scale the page asked for:
+        - with only a width, the scale fits that width to the screen
+        - with only a scale, the width is whatever fits on the screen at that scale
+        - with both, the layout is at least as wide as the screen at that scale
+    so `width=device-width, initial-scale=1` changes nothing, and `width=1200` on an
+    800px screen lays out 1200px wide at a scale of 2/3.
+
+    Pages without a meta tag are laid out at the width of the screen, as desktop browsers
+    do; this engine doesn't pretend to be a phone.
+ */
+/// What a page's `<meta name="viewport">` asked for.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct ViewportMeta {
+    pub width: Option<ViewportWidth>,
+    pub initial_scale: Option<f32>,
+    pub minimum_scale: Option<f32>,
+    pub maximum_scale: Option<f32>,
+}
+
+/// The `width` of a viewport meta tag.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ViewportWidth {
+    DeviceWidth,
+    Px(f32),
+}
+
+/// The scales a meta tag can ask for.
+pub const SCALE_RANGE: (f32, f32) = (0.1, 10.0);
+
+/// The widths a meta tag can ask for, in CSS px.
+pub const WIDTH_RANGE: (f32, f32) = (1.0, 10000.0);
+
+impl ViewportMeta {
+    /// Parse the `content` attribute of a viewport meta tag.
+    pub fn parse(content: &str) -> ViewportMeta {
+        let mut meta: ViewportMeta = ViewportMeta::default();
+        let scale = |value: &str| -> Option<f32> {
+            value.parse().ok().filter(|scale: &f32| scale.is_finite() && *scale > 0.0)
+                .map(|scale: f32| scale.clamp(SCALE_RANGE.0, SCALE_RANGE.1))
+        };
+        for pair in content.split([',', ';']).flat_map(str::split_whitespace) {
+            let (key, value) = match pair.split_once('=') {
+                Some((key, value)) => (key.to_ascii_lowercase(), value.to_ascii_lowercase()),
+                None => continue,
+            };
+            match key.as_str() {
+                "width" => meta.width = match value.as_str() {
+                    "device-width" => Some(ViewportWidth::DeviceWidth),
+                    px => px.trim_end_matches("px").parse().ok().filter(|px: &f32| px.is_finite() && *px > 0.0)
+                        .map(|px: f32| ViewportWidth::Px(px.clamp(WIDTH_RANGE.0, WIDTH_RANGE.1))),
+                },
+                "initial-scale" => meta.initial_scale = scale(&value),
+                "minimum-scale" => meta.minimum_scale = scale(&value),
+                "maximum-scale" => meta.maximum_scale = scale(&value),
+                _ => {}
+            }
+        }
+        meta
+    }
+
+    /// The viewport meta tag of `document`: the last `<meta name="viewport">` with a
+    /// `content` attribute, if there is one.
+    pub fn from_document(document: &dom::Document) -> Option<ViewportMeta> {
+        document.tree.descendants(document.root_element).filter_map(|node: dom::NodeId| match document.tree[node].node_type {
+            dom::NodeType::Element(ref element) if element.tag_name == "meta"
+                && element.attr("name").is_some_and(|name: &str| name.eq_ignore_ascii_case("viewport")) => element.attr("content"),
+            _ => None,
+        }).last().map(ViewportMeta::parse)
+    }
+
+    /// The width to lay the page out at, in CSS px, and the scale to show it at, on a
+    /// screen `device_width` CSS px wide. See "Viewport Meta Tags", above.
+    pub fn resolve(&self, device_width: f32) -> (f32, f32) {
+        let minimum: f32 = self.minimum_scale.unwrap_or(SCALE_RANGE.0);
+        let maximum: f32 = self.maximum_scale.unwrap_or(SCALE_RANGE.1).max(minimum);
+        let width: Option<f32> = self.width.map(|width: ViewportWidth| match width {
+            ViewportWidth::DeviceWidth => device_width,
+            ViewportWidth::Px(px) => px,
+        });
+        let scale: f32 = match (width, self.initial_scale) {
+            (_, Some(scale)) => scale,
+            (Some(width), None) => device_width / width,
+            (None, None) => 1.0,
+        }.clamp(minimum, maximum);
+        (width.unwrap_or(0.0).max(device_width / scale), scale)
+    }
+}
@@ -0,0 +1,153 @@
+//! A visitor trait for walking the DOM, style tree, and layout tree.
+
+use crate::{dom, layout, style};
+
+
+/*
+    Visitors
+
+    Linting, collecting metrics, and exporting all need to walk a tree and do something at
+    each node. Rather than have every pass write its own recursion, a pass implements
+    `NodeVisitor`, overriding only the callbacks it cares about, and hands itself to one of
+    the drivers:
+        - `dom::Tree::visit` walks the DOM
+        - `style::StyledNode::visit` walks the style tree
+        - `layout::LayoutBox::visit` walks the layout tree
+
+    Each node gets an `enter_` call before its children and an `exit_` call after them.
+    The `enter_` call returns a `Flow`, which can skip the node's children or stop the walk.
+
+    By default, entering a styled node enters its DOM node, so a visitor written for the
+    DOM also works on the style tree (where it only sees nodes that were styled). Layout
+    boxes have no such default, since anonymous boxes don't belong to any DOM node.
+
+    e.g. counting elements by tag name:
+        struct TagCounter { counts: HashMap<Atom, usize> }
+
+        impl NodeVisitor for TagCounter {
+            fn enter_element(&mut self, _: dom::NodeId, element: &dom::Element) -> Flow {
+                *self.counts.entry(element.tag_name).or_default() += 1;
+                Flow::Continue
+            }
+        }
+
+        tree.visit(root, &mut TagCounter { counts: HashMap::new() });
+ */
+
+/// What a visitor wants to do after entering a node.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Flow {
+    Continue,
+    SkipChildren,
+    Stop,
+}
+
+/// Callbacks for each kind of node. Every method has a default that does nothing.
+#[allow(unused_variables)]
+pub trait NodeVisitor {
+    fn enter_element(&mut self, node: dom::NodeId, element: &dom::Element) -> Flow {
+        Flow::Continue
+    }
+    fn exit_element(&mut self, node: dom::NodeId, element: &dom::Element) {}
+
+    fn enter_text(&mut self, node: dom::NodeId, text: &str) -> Flow {
+        Flow::Continue
+    }
+    fn exit_text(&mut self, node: dom::NodeId, text: &str) {}
+
+    fn enter_fragment(&mut self, node: dom::NodeId) -> Flow {
+        Flow::Continue
+    }
+    fn exit_fragment(&mut self, node: dom::NodeId) {}
+
+    fn enter_styled_node(&mut self, styled_node: &style::StyledNode) -> Flow {
+        enter_dom_node(self, styled_node.node_id, &styled_node.node)
+    }
+    fn exit_styled_node(&mut self, styled_node: &style::StyledNode) {
+        exit_dom_node(self, styled_node.node_id, &styled_node.node)
+    }
+
+    fn enter_layout_box(&mut self, layout_box: &layout::LayoutBox) -> Flow {
+        Flow::Continue
+    }
+    fn exit_layout_box(&mut self, layout_box: &layout::LayoutBox) {}
+}
+
+/// Call the `enter_` method for this kind of DOM node.
+fn enter_dom_node<V: NodeVisitor + ?Sized>(visitor: &mut V, id: dom::NodeId, node: &dom::Node) -> Flow {
+    match node.node_type {
+        dom::NodeType::Element(ref element) => visitor.enter_element(id, element),
+        dom::NodeType::Text(ref text) => visitor.enter_text(id, text),
+        dom::NodeType::DocumentFragment => visitor.enter_fragment(id),
+    }
+}
+
+/// Call the `exit_` method for this kind of DOM node.
+fn exit_dom_node<V: NodeVisitor + ?Sized>(visitor: &mut V, id: dom::NodeId, node: &dom::Node) {
+    match node.node_type {
+        dom::NodeType::Element(ref element) => visitor.exit_element(id, element),
+        dom::NodeType::Text(ref text) => visitor.exit_text(id, text),
+        dom::NodeType::DocumentFragment => visitor.exit_fragment(id),
+    }
+}
+
+
+impl dom::Tree {
+    /// Walk the subtree at `node` in document order. Returns `Flow::Stop` if the visitor
+    /// stopped the walk.
+    pub fn visit<V: NodeVisitor + ?Sized>(&self, node: dom::NodeId, visitor: &mut V) -> Flow {
+        match enter_dom_node(visitor, node, &self[node]) {
+            Flow::Stop => return Flow::Stop,
+            Flow::SkipChildren => {}
+            Flow::Continue => {
+                for child in self.children(node) {
+                    if self.visit(child, visitor) == Flow::Stop {
+                        return Flow::Stop;
+                    }
+                }
+            }
+        }
+        exit_dom_node(visitor, node, &self[node]);
+        Flow::Continue
+    }
+}
+
+impl style::StyledNode<'_> {
+    /// Walk this style tree in document order. Returns `Flow::Stop` if the visitor stopped
+    /// the walk.
+    pub fn visit<V: NodeVisitor + ?Sized>(&self, visitor: &mut V) -> Flow {
+        match visitor.enter_styled_node(self) {
+            Flow::Stop => return Flow::Stop,
+            Flow::SkipChildren => {}
+            Flow::Continue => {
+                for child in &self.children {
+                    if child.visit(visitor) == Flow::Stop {
+                        return Flow::Stop;
+                    }
+                }
+            }
+        }
+        visitor.exit_styled_node(self);
+        Flow::Continue
+    }
+}
+
+impl layout::LayoutBox<'_> {
+    /// Walk this layout tree, parents before children. Returns `Flow::Stop` if the visitor
+    /// stopped the walk.
+    pub fn visit<V: NodeVisitor + ?Sized>(&self, visitor: &mut V) -> Flow {
+        match visitor.enter_layout_box(self) {
+            Flow::Stop => return Flow::Stop,
+            Flow::SkipChildren => {}
+            Flow::Continue => {
+                for child in self.children.iter() {
+                    if child.visit(visitor) == Flow::Stop {
+                        return Flow::Stop;
+                    }
+                }
+            }
+        }
+        visitor.exit_layout_box(self);
+        Flow::Continue
+    }
+}
@@ -0,0 +1,87 @@
+//! A JavaScript API for running the engine in WebAssembly.
+
+use crate::engine::Page;
+use wasm_bindgen::prelude::*;
+
+
+/*
+    WebAssembly
+
+    With the `wasm` feature, the crate builds for `wasm32-unknown-unknown` and exports an
+    `Engine` class to JavaScript. It renders into an RGBA buffer that a page can copy
+    straight into an HTML `<canvas>`: a browser engine running inside a browser.
+
+        wasm-pack build --target web --out-dir examples/wasm/pkg -- --features wasm
+
+    `examples/wasm/index.html` loads the result, and redraws whenever the HTML or CSS in
+    its text boxes changes, or the canvas is clicked.
+
+    WebAssembly has no file system, sockets, or threads. Subresources are loaded on the
+    calling thread instead of in the background (see `loader`), and anything that isn't
+    an inline part of the page fails to load, so images show their placeholders.
+
+    e.g. (JavaScript)
+        let engine = new Engine("<p>Hello</p>", "p { display: block; }");
+        engine.set_viewport(canvas.width, canvas.height);
+        let pixels = new ImageData(new Uint8ClampedArray(engine.render()), canvas.width);
+        canvas.getContext("2d").putImageData(pixels, 0, 0);
+ */
+
+/// A page, as seen from JavaScript.
+#[wasm_bindgen]
+pub struct Engine {
+    page: Page,
+}
+
+#[wasm_bindgen]
+impl Engine {
+    /// Parse `html` and `css` into a new page.
+    #[wasm_bindgen(constructor)]
+    pub fn new(html: &str, css: &str) -> Result<Engine, JsError> {
+        let page: Page = Page::load(html, css).map_err(|err| JsError::new(&err.to_string()))?;
+        Ok(Engine { page })
+    }
+
+    /// The width of the viewport, in pixels.
+    pub fn width(&self) -> usize {
+        self.page.viewport().0
+    }
+
+    /// The height of the viewport, in pixels.
+    pub fn height(&self) -> usize {
+        self.page.viewport().1
+    }
+
+    /// Resize the viewport.
+    pub fn set_viewport(&mut self, width: usize, height: usize) {
+        self.page.set_viewport(width, height);
+    }
+
+    /// Paint the page, returning `width * height * 4` bytes of RGBA pixels.
+    pub fn render(&mut self) -> Vec<u8> {
+        self.page.paint().to_rgba()
+    }
+
+    /// Click at a point in the viewport. Returns true if the page needs to be drawn again.
+    pub fn click_at(&mut self, x: f32, y: f32) -> bool {
+        self.page.click_at(x, y)
+    }
+
+    /// Move the mouse to a point in the viewport. Returns true if the page needs to be
+    /// drawn again.
+    pub fn hover_at(&mut self, x: f32, y: f32) -> bool {
+        self.page.hover_at(x, y)
+    }
+
+    /// Scroll the viewport by a distance in pixels.
+    pub fn scroll_by(&mut self, dx: f32, dy: f32) {
+        self.page.scroll_by(dx, dy);
+    }
+
+    /// Move the page's clock forward by `ms`, running timers and animation frames. Returns
+    /// true if the page needs to be drawn again.
+    #[cfg(feature = "js")]
+    pub fn advance_time(&mut self, ms: f64) -> bool {
+        self.page.advance_time(ms)
+    }
+}
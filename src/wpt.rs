@@ -0,0 +1,272 @@
+//! Running tests written for the Web Platform Tests, to measure how much of CSS we get right.
+
+use crate::engine::{self, Page};
+use crate::layout::{BoxTable, LayoutBox, Rect};
+use crate::reftest::{self, Fuzzy, Reftest, Relation};
+use crate::{dom, html};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+
+/*
+    Web Platform Tests
+
+    The [Web Platform Tests](https://web-platform-tests.org/) are the test suite browsers
+    share. Most of it needs JavaScript and the `testharness.js` library, but two kinds of
+    test don't, and those are what this runner handles:
+
+      - Reftests name their reference page in the test itself, with
+        `<link rel="match" href="...">` (or `rel="mismatch"`), and may allow some pixels
+        to differ with `<meta name="fuzzy" content="maxDifference=0-2;totalPixels=0-100">`.
+        They're run like our own reftests (see `reftest`).
+      - Layout tests put the expected geometry of elements in attributes, as
+        `data-expected-width`, `-height`, `-offset-x`, and `-offset-y`, in CSS px. Browsers
+        check them with a script (`check-layout-th.js`); we check them against the layout
+        tree directly. Sizes are of the border box, and offsets are from the origin of the
+        page, which is where `offsetLeft` measures from in a page with nothing positioned.
+
+    Anything else (including the reference pages themselves) is skipped. So are tests
+    the parser rejects, but those count as errors, since they are a gap too: WPT files
+    rely on plenty of HTML that we don't support yet, like `<!DOCTYPE html>` and void
+    elements.
+
+    A checkout of WPT is large, and most of CSS 2.1 is beyond this engine, so the runner
+    takes a curated list of test paths, relative to the root of the checkout, one per
+    line, with `#` comments. The totals show how the engine is doing on that subset.
+
+    e.g.
+        $ browser-engine wpt ~/wpt --list tests/wpt/css21.list
+        pass  css/CSS2/margin-padding-clear/margin-left-001.xht
+        fail  css/CSS2/box-display/display-change-001.xht: 400 pixels differ, by up to 255
+        ...
+        31 passed, 4 failed, 6 errors, 2 skipped (76% of 41 run)
+ */
+
+/// What happened when one test ran.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Pass,
+    Fail(String),  // why
+    Error(String), // the test couldn't be run, e.g. because it didn't parse
+    Skip,          // not a reftest or a layout test
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Pass => write!(f, "pass"),
+            Outcome::Fail(reason) => write!(f, "fail: {}", reason),
+            Outcome::Error(reason) => write!(f, "error: {}", reason),
+            Outcome::Skip => write!(f, "skip"),
+        }
+    }
+}
+
+/// The totals of a run.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Summary {
+    pub passed: usize,
+    pub failed: usize,
+    pub errors: usize,
+    pub skipped: usize,
+}
+
+impl Summary {
+    /// Count one more outcome.
+    pub fn add(&mut self, outcome: &Outcome) {
+        match outcome {
+            Outcome::Pass => self.passed += 1,
+            Outcome::Fail(_) => self.failed += 1,
+            Outcome::Error(_) => self.errors += 1,
+            Outcome::Skip => self.skipped += 1,
+        }
+    }
+
+    /// How many tests ran, passing or not.
+    pub fn run(&self) -> usize {
+        self.passed + self.failed + self.errors
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} passed, {} failed, {} errors, {} skipped", self.passed, self.failed, self.errors, self.skipped)?;
+        if self.run() > 0 {
+            write!(f, " ({}% of {} run)", self.passed * 100 / self.run(), self.run())?;
+        }
+        Ok(())
+    }
+}
+
+
+/// Read a list of test paths, resolving them against `root`.
+pub fn load_list(path: &Path, root: &Path) -> Result<Vec<PathBuf>, String> {
+    let source: String = fs::read_to_string(path).map_err(|err| format!("can't read {}: {}", path.display(), err))?;
+    Ok(source.lines()
+        .map(|line: &str| line.split('#').next().unwrap_or("").trim())
+        .filter(|line: &&str| !line.is_empty())
+        .map(|line: &str| root.join(line))
+        .collect())
+}
+
+/// Find every test under `root`, in order, leaving out reference pages and support files.
+pub fn find_tests(root: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut tests: Vec<PathBuf> = Vec::new();
+    let mut directories: Vec<PathBuf> = vec![root.to_path_buf()];
+    while let Some(directory) = directories.pop() {
+        let entries = fs::read_dir(&directory).map_err(|err| format!("can't read {}: {}", directory.display(), err))?;
+        for entry in entries.flatten() {
+            let path: PathBuf = entry.path();
+            let name: String = entry.file_name().to_string_lossy().into_owned();
+            if path.is_dir() {
+                if !matches!(name.as_str(), "reference" | "support" | "resources") {
+                    directories.push(path);
+                }
+            } else if is_test_file(&name) {
+                tests.push(path);
+            }
+        }
+    }
+    tests.sort();
+    Ok(tests)
+}
+
+/// Is this the name of a test page, rather than a reference or something else?
+fn is_test_file(name: &str) -> bool {
+    let stem: &str = match name.rsplit_once('.') {
+        Some((stem, "html" | "htm" | "xht" | "xhtml")) => stem,
+        _ => return false,
+    };
+    !(stem.ends_with("-ref") || stem.ends_with("-notref") || stem.starts_with("ref-"))
+}
+
+
+/// Run the test in `path`.
+pub fn run_test(path: &Path) -> Outcome {
+    let source: String = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => return Outcome::Error(err.to_string()),
+    };
+    let document: dom::Document = match html::parse(&source) {
+        Ok(document) => document,
+        Err(err) => return Outcome::Error(err.to_string()),
+    };
+
+    if let Some(test) = reftest_for(&document, path) {
+        return match test.run() {
+            reftest::Outcome::Pass => Outcome::Pass,
+            reftest::Outcome::Fail(Some(difference)) => Outcome::Fail(difference.to_string()),
+            reftest::Outcome::Fail(None) => Outcome::Fail("the pages have different sizes".to_string()),
+            reftest::Outcome::Error(path, err) => Outcome::Error(format!("{}: {}", path.display(), err)),
+        };
+    }
+
+    let expectations: Vec<(dom::NodeId, Expected)> = expectations(&document);
+    if expectations.is_empty() {
+        return Outcome::Skip;
+    }
+    let mut page: Page = match Page::load(&source, "") {
+        Ok(page) => page,
+        Err(err) => return Outcome::Error(err.to_string()),
+    };
+    page.set_deterministic(true);
+    let (width, height) = engine::DEFAULT_VIEWPORT;
+    page.set_viewport(width, height);
+    let failures: Vec<String> = page.with_layout_tree(|root: &LayoutBox| {
+        let boxes: BoxTable = BoxTable::new(root);
+        expectations.iter().filter_map(|(node, expected): &(dom::NodeId, Expected)| {
+            let border_box: Option<Rect> = boxes.box_for(*node).map(|b: &LayoutBox| b.dimensions.border_box());
+            expected.check(border_box).err().map(|reason: String| format!("node {}: {}", node.index(), reason))
+        }).collect()
+    });
+    if failures.is_empty() { Outcome::Pass } else { Outcome::Fail(failures.join("; ")) }
+}
+
+/// The reftest described by a test's `<link rel="match">` and `<meta name="fuzzy">`, if any.
+fn reftest_for(document: &dom::Document, path: &Path) -> Option<Reftest> {
+    let base: &Path = path.parent().unwrap_or(Path::new(""));
+    let (relation, reference) = document.get_elements_by_tag_name("link").into_iter().find_map(|link: dom::NodeId| {
+        let element: &dom::Element = element(document, link)?;
+        let relation: Relation = match element.attr("rel")? {
+            "match" => Relation::Match,
+            "mismatch" => Relation::Mismatch,
+            _ => return None,
+        };
+        Some((relation, base.join(element.attr("href")?)))
+    })?;
+    let fuzzy: Fuzzy = document.get_elements_by_tag_name("meta").into_iter()
+        .filter_map(|meta: dom::NodeId| element(document, meta))
+        .filter(|element: &&dom::Element| element.attr("name") == Some("fuzzy"))
+        .find_map(|element: &dom::Element| parse_fuzzy(element.attr("content")?))
+        .unwrap_or_default();
+    Some(Reftest { relation, test: path.to_path_buf(), reference, fuzzy })
+}
+
+/// Parse the content of `<meta name="fuzzy">`: `maxDifference=0-2;totalPixels=0-100`,
+/// or just `0-2;0-100`. As in `reftest`, only the maximum of each range matters.
+fn parse_fuzzy(content: &str) -> Option<Fuzzy> {
+    // A reference may be named first ("ref.html:0-2;0-100"); we only have one reference.
+    let content: &str = content.rsplit(':').next()?;
+    let (difference, pixels) = content.split_once(';')?;
+    let maximum = |part: &str| {
+        let range: &str = part.rsplit('=').next().unwrap_or(part);
+        range.rsplit('-').next().unwrap_or(range).trim().to_string()
+    };
+    Some(Fuzzy { max_difference: maximum(difference).parse().ok()?, max_pixels: maximum(pixels).parse().ok()? })
+}
+
+
+/// The geometry a layout test expects of one element, in CSS px.
+#[derive(Default, Debug)]
+struct Expected {
+    width: Option<f32>,
+    height: Option<f32>,
+    offset_x: Option<f32>,
+    offset_y: Option<f32>,
+}
+
+impl Expected {
+    /// Compare with the element's border box, or `None` if it has no box.
+    fn check(&self, border_box: Option<Rect>) -> Result<(), String> {
+        let border_box: Rect = border_box.ok_or("no layout box")?;
+        let checks: [(&str, Option<f32>, f32); 4] = [
+            ("width", self.width, border_box.width),
+            ("height", self.height, border_box.height),
+            ("offset-x", self.offset_x, border_box.x),
+            ("offset-y", self.offset_y, border_box.y),
+        ];
+        let wrong: Vec<String> = checks.iter()
+            .filter_map(|&(name, expected, actual): &(&str, Option<f32>, f32)| {
+                // Allow for rounding, as `check-layout-th.js` does.
+                let expected: f32 = expected?;
+                ((expected - actual).abs() >= 1.0).then(|| format!("{} is {}, expected {}", name, actual, expected))
+            })
+            .collect();
+        if wrong.is_empty() { Ok(()) } else { Err(wrong.join(", ")) }
+    }
+}
+
+/// The elements with `data-expected-*` attributes, and what they expect.
+fn expectations(document: &dom::Document) -> Vec<(dom::NodeId, Expected)> {
+    document.tree.descendants(document.root_element).filter_map(|node: dom::NodeId| {
+        let element: &dom::Element = element(document, node)?;
+        let number = |name: &str| element.attr(name).and_then(|value: &str| value.trim().parse::<f32>().ok());
+        let expected: Expected = Expected {
+            width: number("data-expected-width"),
+            height: number("data-expected-height"),
+            offset_x: number("data-offset-x").or_else(|| number("data-expected-offset-x")),
+            offset_y: number("data-offset-y").or_else(|| number("data-expected-offset-y")),
+        };
+        let any: bool = expected.width.is_some() || expected.height.is_some() || expected.offset_x.is_some() || expected.offset_y.is_some();
+        any.then_some((node, expected))
+    }).collect()
+}
+
+/// The element `node`, if it is one.
+fn element(document: &dom::Document, node: dom::NodeId) -> Option<&dom::Element> {
+    match document.tree[node].node_type {
+        dom::NodeType::Element(ref element) => Some(element),
+        _ => None,
+    }
+}
@@ -0,0 +1,73 @@
+//! Checks the table semantics in the accessibility tree, and where captions are laid out.
+
+use build_a_browser_engine_in_rust::accessibility::{AccessibleNode, Role};
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::Page;
+
+const CSS: &str = "head { display: none; } html, body, table, caption, thead, tbody, tr, td, th, p { display: block; }";
+
+/// Every node in the tree, in order.
+fn flatten<'a>(node: &'a AccessibleNode, out: &mut Vec<&'a AccessibleNode>) {
+    out.push(node);
+    for child in &node.children {
+        flatten(child, out);
+    }
+}
+
+#[test]
+fn gives_rows_and_cells_their_indices() {
+    let html: &str = "<table><caption>Scores</caption>\
+        <thead><tr><th>Name</th><th colspan=\"2\">Rounds</th></tr></thead>\
+        <tbody><tr><th scope=\"row\" rowspan=\"2\">Ann</th><td>1</td><td>2</td></tr>\
+        <tr><td>3</td><td>4</td></tr>\
+        <tr><td colspan=\"3\"><table><tr><td>inner</td></tr></table></td></tr></tbody></table>";
+    let page: Page = Page::load(html, CSS).unwrap();
+    let tree: AccessibleNode = page.accessibility_tree();
+    let mut nodes: Vec<&AccessibleNode> = Vec::new();
+    flatten(&tree, &mut nodes);
+    let summary: Vec<(Role, &str, Option<usize>, Option<usize>)> = nodes.iter()
+        .filter(|node: &&&AccessibleNode| node.role != Role::Text && node.role != Role::Document)
+        .map(|node: &&AccessibleNode| (node.role, node.name.as_str(), node.row_index, node.column_index))
+        .collect();
+    assert_eq!(summary, [
+        (Role::Table, "Scores", None, None),
+        (Role::Caption, "Scores", None, None),
+        (Role::Row, "", Some(1), None),
+        (Role::ColumnHeader, "Name", Some(1), Some(1)),
+        (Role::ColumnHeader, "Rounds", Some(1), Some(2)),
+        (Role::Row, "", Some(2), None),
+        (Role::RowHeader, "Ann", Some(2), Some(1)),
+        (Role::Cell, "1", Some(2), Some(2)),
+        (Role::Cell, "2", Some(2), Some(3)),
+        // "Ann" still covers the first column.
+        (Role::Row, "", Some(3), None),
+        (Role::Cell, "3", Some(3), Some(2)),
+        (Role::Cell, "4", Some(3), Some(3)),
+        (Role::Row, "", Some(4), None),
+        (Role::Cell, "inner", Some(4), Some(1)),
+        // A nested table counts its own rows and columns.
+        (Role::Table, "", None, None),
+        (Role::Row, "", Some(1), None),
+        (Role::Cell, "inner", Some(1), Some(1)),
+    ]);
+    let tables: Vec<Option<(usize, usize)>> = nodes.iter().filter(|node: &&&AccessibleNode| node.role == Role::Table).map(|node: &&AccessibleNode| node.table_size).collect();
+    assert_eq!(tables, [Some((4, 3)), Some((1, 1))]);
+    assert!(tree.to_string().contains("cell \"2\" row 2 column 3 at "));
+    assert!(tree.to_string().contains("table \"Scores\" 4 rows 3 columns at "));
+}
+
+#[test]
+fn puts_captions_on_their_caption_side() {
+    let html: &str = "<table id=\"t\"><caption id=\"c\">Caption</caption><tr><td>Cell</td></tr></table>";
+    let position = |css: &str| -> (f32, f32) {
+        let mut page: Page = Page::load(html, &format!("{} td {{ height: 40px; }} caption {{ height: 20px; }} {}", CSS, css)).unwrap();
+        let (table, caption): (NodeId, NodeId) = (page.query_selector("#t").unwrap(), page.query_selector("#c").unwrap());
+        let (table, caption) = (page.element_rect(table).unwrap(), page.element_rect(caption).unwrap());
+        (caption.y - table.y, table.height)
+    };
+    assert_eq!(position(""), (0.0, 60.0));
+    assert_eq!(position("caption { caption-side: top; }"), (0.0, 60.0));
+    assert_eq!(position("caption { caption-side: bottom; }"), (40.0, 60.0));
+    // Captions use their table's `caption-side` when they have none of their own.
+    assert_eq!(position("table { caption-side: bottom; }"), (40.0, 60.0));
+}
@@ -0,0 +1,73 @@
+//! Checks the roles, names, and bounds in the accessibility tree, and what it leaves out.
+
+use build_a_browser_engine_in_rust::accessibility::{AccessibleNode, Role};
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::layout::Rect;
+
+const CSS: &str = "head, .gone { display: none; } html, body, nav, div, h1, h2, p, ul, li, section { display: block; } \
+                   body, h1, p { margin: 0; } h1 { height: 30px; } p { height: 20px; }";
+
+/// The page's accessibility tree, printed without bounds.
+fn outline(html: &str) -> String {
+    let page: Page = Page::load(html, CSS).unwrap();
+    page.accessibility_tree().to_string().lines()
+        .map(|line: &str| line.split(" at ").next().unwrap())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[test]
+fn gives_elements_roles_and_names() {
+    assert_eq!(outline("<nav><a href=\"/\">Home</a></nav><div><h1>Title</h1><img alt=\"A cat\"></div>"), "\
+document
+  navigation
+    link \"Home\"
+      text \"Home\"
+  heading \"Title\" level 1
+    text \"Title\"
+  image \"A cat\"");
+
+    assert_eq!(outline("<ul><li>One\n   item</li></ul><p title=\"Tip\">x</p><input type=\"submit\" value=\"Go\"><input type=\"checkbox\">"), "\
+document
+  list
+    listitem \"One item\"
+      text \"One item\"
+  paragraph \"Tip\"
+    text \"x\"
+  button \"Go\"
+  checkbox");
+}
+
+#[test]
+fn prefers_labels_and_explicit_roles() {
+    assert_eq!(outline("<a href=\"#\" aria-label=\"Close\">X</a><div role=\"button\">Press</div>\
+                        <h2 role=\"none\">Plain</h2><div role=\"heading\" aria-level=\"3\">Sub</div><a>no href</a>"), "\
+document
+  link \"Close\"
+    text \"X\"
+  button \"Press\"
+    text \"Press\"
+  text \"Plain\"
+  heading \"Sub\" level 3
+    text \"Sub\"
+  text \"no href\"");
+}
+
+#[test]
+fn leaves_out_what_is_not_perceivable() {
+    assert_eq!(outline("<p class=\"gone\">hidden</p><section aria-hidden=\"true\"><p>also hidden</p></section>\
+                        <img src=\"decor.png\" alt=\"\"><script>var x;</script><div><div><p>kept</p></div></div>"), "\
+document
+  paragraph
+    text \"kept\"");
+}
+
+#[test]
+fn gives_bounds_in_document_coordinates() {
+    let page: Page = Page::load("<h1>Title</h1><p>Text</p>", CSS).unwrap();
+    let tree: AccessibleNode = page.accessibility_tree();
+    let nodes: Vec<(Role, Rect)> = tree.children.iter().map(|node: &AccessibleNode| (node.role, node.bounds)).collect();
+    assert_eq!(nodes, [(Role::Heading, Rect { x: 0.0, y: 0.0, width: 800.0, height: 30.0 }),
+                       (Role::Paragraph, Rect { x: 0.0, y: 30.0, width: 800.0, height: 20.0 })]);
+    assert_eq!(tree.node, page.document().root_element);
+}
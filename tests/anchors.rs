@@ -0,0 +1,67 @@
+//! Checks scrolling to the element a URL fragment or a selector names.
+
+use build_a_browser_engine_in_rust::engine::{Page, Session};
+use std::fs;
+use std::path::PathBuf;
+
+/// A document of five 100px-tall sections, with ids `s0` to `s4`.
+const SECTIONS: &str = "<html><body>\
+    <div id=\"s0\">a</div><div id=\"s1\">b</div><div id=\"s2\">c</div>\
+    <div id=\"s3\">d</div><a name=\"old\"></a><div id=\"s4\">e</div></body></html>";
+const CSS: &str = "head { display: none; } html, body, div { display: block; } body { margin: 0px; } div { height: 100px; }";
+
+fn page() -> Page {
+    let mut page: Page = Page::load(SECTIONS, CSS).unwrap();
+    page.set_deterministic(true);
+    page.set_viewport(200, 150);
+    page
+}
+
+#[test]
+fn scrolls_to_fragments_and_selectors() {
+    let mut page: Page = page();
+    assert!(page.scroll_to_fragment("s2"));
+    assert_eq!(page.scroll_position(), (0.0, 200.0));
+
+    // The last section can't reach the top of the viewport: the document ends first.
+    assert!(page.scroll_to_fragment("s4"));
+    assert_eq!(page.scroll_position(), (0.0, 350.0));
+
+    // Unknown fragments leave the page where it is; `top` goes back to the top.
+    assert!(!page.scroll_to_fragment("nowhere"));
+    assert_eq!(page.scroll_position(), (0.0, 350.0));
+    assert!(page.scroll_to_fragment("top"));
+    assert_eq!(page.scroll_position(), (0.0, 0.0));
+
+    // Old-style anchors are found by name.
+    assert!(page.scroll_to_fragment("old"));
+    assert_eq!(page.scroll_position(), (0.0, 350.0));
+
+    page.scroll_to_selector("div#s1").unwrap();
+    assert_eq!(page.scroll_position(), (0.0, 100.0));
+    assert!(page.scroll_to_selector("p").is_err());
+}
+
+#[test]
+fn follows_links_to_fragments_in_a_session() {
+    let dir: PathBuf = std::env::temp_dir().join(format!("anchors-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let html: String = SECTIONS.replace("<body>", &format!("<head><style>{}</style></head><body>", CSS));
+    let path: PathBuf = dir.join("sections.html");
+    fs::write(&path, html).unwrap();
+    let url: String = path.to_str().unwrap().to_string();
+
+    let mut session: Session = Session::new();
+    session.set_viewport(200, 150);
+    session.open_tab(&format!("{}#s1", url)).unwrap();
+    assert_eq!(session.page().unwrap().scroll_position(), (0.0, 100.0));
+
+    // A link within the document scrolls and adds a history entry.
+    session.navigate("#s3").unwrap();
+    assert_eq!(session.tabs()[0].url(), format!("{}#s3", url));
+    assert_eq!(session.page().unwrap().scroll_position(), (0.0, 300.0));
+    assert!(session.back().unwrap());
+    assert_eq!(session.page().unwrap().scroll_position(), (0.0, 100.0));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,59 @@
+//! Checks that names are interned: equal names share one copy, from any thread, and
+//! atoms compare, hash, and sort like the strings they hold.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::dom::{Document, NodeId, NodeType};
+use build_a_browser_engine_in_rust::html;
+use std::collections::HashMap;
+use std::thread;
+
+#[test]
+fn equal_names_share_one_copy() {
+    let div: Atom = Atom::new("div");
+    assert_eq!(div, Atom::from(String::from("div")));
+    assert!(std::ptr::eq(div.as_str(), Atom::from("div").as_str()));
+    assert_ne!(div, Atom::new("DIV"));
+
+    // Threads intern into the same table.
+    let name: &str = "interned-on-another-thread";
+    let others: Vec<Atom> = (0..4).map(|_| thread::spawn(move || Atom::new(name))).map(|handle: thread::JoinHandle<Atom>| handle.join().unwrap()).collect();
+    assert!(others.iter().all(|other: &Atom| std::ptr::eq(other.as_str(), Atom::new(name).as_str())));
+}
+
+#[test]
+fn atoms_act_like_their_strings() {
+    let class: Atom = Atom::new("class");
+    assert_eq!(class, "class");
+    assert_eq!(class, *"class");
+    assert_eq!(class, "class".to_string());
+    assert_eq!(class.len(), 5);
+    assert_eq!(format!("{} {:?}", class, class), "class \"class\"");
+
+    // Maps keyed by atoms can be queried with a plain `&str`.
+    let mut counts: HashMap<Atom, usize> = HashMap::new();
+    counts.insert(class, 1);
+    assert_eq!(counts.get("class"), Some(&1));
+
+    let mut names: Vec<Atom> = ["span", "a", "div"].into_iter().map(Atom::new).collect();
+    names.sort();
+    assert_eq!(names, ["a", "div", "span"]);
+}
+
+#[test]
+fn parsers_intern_names() {
+    let document: Document = html::parse("<p class=\"x\">a</p><p class=\"y\">b</p>").unwrap();
+    let names: Vec<&'static str> = document.get_elements_by_tag_name("p").into_iter().map(|node: NodeId| match document.tree[node].node_type {
+        NodeType::Element(ref element) => element.tag_name.as_str(),
+        _ => unreachable!(),
+    }).collect();
+    assert!(std::ptr::eq(names[0], names[1]));
+    assert!(std::ptr::eq(names[0], Atom::new("p").as_str()));
+
+    let stylesheet: css::Stylesheet = css::parse("p { display: block; } div { display: block; }").unwrap();
+    let values: Vec<&css::Value> = stylesheet.rules.iter().map(|rule: &css::Rule| &rule.declarations[0].value).collect();
+    match (values[0], values[1]) {
+        (css::Value::Keyword(first), css::Value::Keyword(second)) => assert!(std::ptr::eq(first.as_str(), second.as_str())),
+        other => panic!("expected keywords, got {:?}", other),
+    }
+}
@@ -0,0 +1,96 @@
+//! Checks the automation API: clicking and typing by selector, waiting for elements,
+//! evaluating scripts, and taking screenshots, as an end-to-end test would.
+//!
+//! Run the tests that need scripts with `cargo test --features js --test automation`.
+
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::{AutomationError, Page};
+use build_a_browser_engine_in_rust::forms;
+use std::time::Duration;
+
+const CSS: &str = "head, script { display: none; } html, body, div, form { display: block; } body { margin: 0; } \
+                   .done { height: 10px; background: #00ff00; }";
+
+const FORM: &str = "<form><input id=\"name\" value=\"A\"><input type=\"checkbox\" id=\"agree\"><button class=\"submit\">Send</button></form>";
+
+#[test]
+fn clicks_and_types_by_selector() {
+    let mut page: Page = Page::load(FORM, CSS).unwrap();
+    page.type_into("#name", "da").unwrap();
+    page.click("#agree").unwrap();
+    let name: NodeId = page.query_selector("#name").unwrap();
+    let agree: NodeId = page.query_selector("#agree").unwrap();
+    assert_eq!(forms::value(page.document(), name), "Ada");
+    assert!(forms::is_checked(&page.document().tree, agree));
+    assert_eq!(page.focused_node(), Some(agree));
+}
+
+#[test]
+fn explains_what_went_wrong() {
+    let mut page: Page = Page::load(FORM, CSS).unwrap();
+    let error: AutomationError = page.click("#missing").unwrap_err();
+    assert!(matches!(error, AutomationError::NoSuchElement(_)));
+    assert_eq!(error.to_string(), "no element matches \"#missing\"");
+    assert_eq!(page.type_into("#agree", "x").unwrap_err().to_string(), "\"#agree\" is not a text field");
+    assert!(matches!(page.click("!!"), Err(AutomationError::InvalidSelector(_))));
+
+    // Without anything scheduled to add it, waiting gives up at once.
+    assert_eq!(page.wait_for_selector("form", Duration::ZERO).unwrap(), page.query_selector("form").unwrap());
+    assert_eq!(page.wait_for_selector(".done", Duration::from_secs(5)).unwrap_err().to_string(), "no element matched \".done\" within 5s");
+}
+
+#[test]
+fn takes_screenshots_as_png_files() {
+    let mut page: Page = Page::load("<div class=\"done\"></div>", CSS).unwrap();
+    page.set_viewport(16, 8);
+    let png: Vec<u8> = page.screenshot();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    // The IHDR chunk holds the width and height.
+    assert_eq!((&png[16..20], &png[20..24]), (&16u32.to_be_bytes()[..], &8u32.to_be_bytes()[..]));
+    assert_eq!(page.screenshot(), png);
+}
+
+/// Scripts that respond to the automation API.
+#[cfg(feature = "js")]
+mod scripts {
+    use super::{CSS, FORM};
+    use build_a_browser_engine_in_rust::dom::NodeId;
+    use build_a_browser_engine_in_rust::engine::{AutomationError, Page};
+    use std::time::Duration;
+
+    /// The form, with a script that adds `.done` 300ms after the button is clicked, and
+    /// echoes what is typed into `#name`.
+    fn load() -> Page {
+        Page::load(&format!("{}<script>\
+            var typed = [];
+            document.querySelector('#name').addEventListener('input', event => typed.push(event.target.value));
+            document.querySelector('.submit').addEventListener('click', () => setTimeout(() => {{
+                const done = document.createElement('div');
+                done.className = 'done';
+                document.body.appendChild(done);
+            }}, 300));
+        </script>", FORM), CSS).unwrap()
+    }
+
+    #[test]
+    fn waits_on_the_page_clock() {
+        let mut page: Page = load();
+        page.click("button.submit").unwrap();
+        // Too short a wait leaves the clock where the timer hasn't fired yet.
+        assert!(matches!(page.wait_for_selector(".done", Duration::from_millis(299)), Err(AutomationError::Timeout(..))));
+        let done: NodeId = page.wait_for_selector(".done", Duration::from_secs(1)).unwrap();
+        assert_eq!(page.query_selector(".done"), Some(done));
+        assert_eq!(page.evaluate("performance.now()").unwrap().to_string(), "300");
+    }
+
+    #[test]
+    fn evaluates_scripts_and_sends_input_events() {
+        let mut page: Page = load();
+        page.type_into("#name", "b").unwrap();
+        page.type_into("#name", "c").unwrap();
+        assert_eq!(page.evaluate("typed.join()").unwrap().to_string(), "Ab,Abc");
+        assert_eq!(page.evaluate("document.querySelector('#name').value").unwrap().to_string(), "Abc");
+        let error: AutomationError = page.evaluate("nope()").unwrap_err();
+        assert_eq!(error.to_string(), "Uncaught ReferenceError: nope is not defined");
+    }
+}
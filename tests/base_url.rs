@@ -0,0 +1,38 @@
+//! Checks that relative URLs are resolved against a document's `<base href>`.
+
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+
+#[test]
+fn resolves_links_against_the_base_element() {
+    let html: &str = "<html><head><base href=\"http://example.com/docs/\"><base href=\"http://example.org/\"></head>\
+        <body><a id=\"next\" href=\"guide/next.html\">Next</a></body></html>";
+    let css: &str = "head { display: none; } html, body { display: block; }";
+    let mut page: Page = Page::load(html, css).unwrap();
+    page.set_deterministic(true);
+    // The first `<base>` counts.
+    assert_eq!(page.document().base_href(), Some("http://example.com/docs/"));
+    assert_eq!(page.document().base_url.as_deref(), Some("http://example.com/docs/"));
+
+    assert_eq!(page.resolve_url("a.png"), "http://example.com/docs/a.png");
+    assert_eq!(page.resolve_url("../index.html"), "http://example.com/index.html");
+    assert_eq!(page.resolve_url("/style.css"), "http://example.com/style.css");
+    assert_eq!(page.resolve_url("#top"), "http://example.com/docs/#top");
+    assert_eq!(page.resolve_url("https://other.example/"), "https://other.example/");
+    // Fragments are kept, whatever comes before them.
+    assert_eq!(page.resolve_url("guide/next.html#intro"), "http://example.com/docs/guide/next.html#intro");
+    assert_eq!(page.resolve_url("?q=1#intro"), "http://example.com/docs/?q=1#intro");
+    // URLs with a scheme are absolute, even ones that aren't for loading pages.
+    for url in ["mailto:me@example.com", "javascript:void(0)", "data:text/plain,a#b"] {
+        assert_eq!(page.resolve_url(url), url);
+    }
+
+    page.click("#next").unwrap();
+    assert_eq!(page.take_followed_link().as_deref(), Some("http://example.com/docs/guide/next.html"));
+
+    // Without a URL to resolve against, a relative `<base>` means nothing.
+    let page: Page = Page::load("<base href=\"docs/\"><a href=\"a.html\">a</a>", css).unwrap();
+    assert_eq!(page.document().base_url, None);
+    assert_eq!(page.resolve_url("a.html"), "a.html");
+    assert_eq!(html::parse("<p>x</p>").unwrap().base_href(), None);
+}
@@ -0,0 +1,51 @@
+//! Checks that `browser-engine bench` times every stage over the requested number of runs,
+//! and writes each run's stages as a Chrome trace.
+
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Run `browser-engine` with `args`, returning what it printed.
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_browser-engine")).args(args).output().unwrap()
+}
+
+#[test]
+fn prints_percentiles_and_writes_a_trace() {
+    let dir: PathBuf = std::env::temp_dir();
+    let (html, trace) = (dir.join(format!("bench-{}.html", std::process::id())), dir.join(format!("bench-{}.json", std::process::id())));
+    fs::write(&html, "<style>p { display: block; }</style><p>Hello, <em>bench</em></p>").unwrap();
+    let output: Output = run(&["bench", html.to_str().unwrap(), "--iterations", "3", "--size", "100x100", "--trace", trace.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout: String = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with(&format!("3 iterations of {} (", html.display())), "{}", stdout);
+    let header: Vec<&str> = lines[1].split_whitespace().collect();
+    assert_eq!(header, ["stage", "(ms)", "min", "p50", "p90", "p99", "max"]);
+    let stages: Vec<&str> = lines[2..8].iter().map(|line: &&str| line.split_whitespace().next().unwrap()).collect();
+    assert_eq!(stages, ["parse", "parse", "style", "layout", "paint", "total"]);
+    for line in &lines[2..8] {
+        // Five times in ms, in increasing order.
+        let times: Vec<f64> = line.split_whitespace().rev().take(5).map(|time: &str| time.parse().unwrap()).collect();
+        assert!(times.windows(2).all(|pair: &[f64]| pair[0] >= pair[1]), "{}", line);
+    }
+    assert!(stdout.contains(&format!("wrote {}", trace.display())));
+
+    // Five stages for each timed run; the warm-up run isn't recorded.
+    let trace: Value = serde_json::from_str(&fs::read_to_string(&trace).unwrap()).unwrap();
+    let events: &Vec<Value> = trace["traceEvents"].as_array().unwrap();
+    assert_eq!(events.len(), 15);
+    assert_eq!((&events[0]["name"], &events[0]["ph"], &events[0]["args"]["iteration"]), (&Value::from("parse html"), &Value::from("X"), &Value::from(1)));
+    assert_eq!(events[14]["name"], "paint");
+}
+
+#[test]
+fn rejects_bad_iteration_counts_and_urls() {
+    let output: Output = run(&["bench", "page.html", "--iterations", "0"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("browser-engine: --iterations must be a positive number"));
+    let output: Output = run(&["bench", "http://127.0.0.1:1/"]);
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("browser-engine: bench only works on local files"));
+}
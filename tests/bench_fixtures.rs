@@ -0,0 +1,68 @@
+//! Checks that the generated benchmark pages are the sizes they claim, come out the same
+//! every time, and run through the whole pipeline.
+//!
+//! Run with `cargo test --features bench-fixtures --test bench_fixtures`.
+
+#![cfg(feature = "bench-fixtures")]
+
+use build_a_browser_engine_in_rust::bench_fixtures;
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::dom::{Document, NodeId};
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::layout::Rect;
+
+#[test]
+fn generates_pages_of_the_sizes_asked_for() {
+    // Each section of an article is about 5 KB, and each rule about 60 bytes.
+    let (one, ten): (usize, usize) = (bench_fixtures::article(1).len(), bench_fixtures::article(11).len());
+    assert!((4_000..7_000).contains(&((ten - one) / 10)), "{} bytes per section", (ten - one) / 10);
+    let (one, many): (usize, usize) = (bench_fixtures::stylesheet(0).len(), bench_fixtures::stylesheet(1000).len());
+    assert!((40..80).contains(&((many - one) / 1000)), "{} bytes per rule", (many - one) / 1000);
+
+    let article: usize = bench_fixtures::wikipedia_sized_article().len();
+    assert!((800_000..1_300_000).contains(&article), "{} bytes", article);
+    let stylesheet: usize = bench_fixtures::bootstrap_sized_stylesheet().len();
+    assert!((150_000..260_000).contains(&stylesheet), "{} bytes", stylesheet);
+
+    // The same size gives the same bytes, so every run times the same input.
+    assert_eq!(bench_fixtures::article(3), bench_fixtures::article(3));
+    assert_eq!(bench_fixtures::stylesheet(50), bench_fixtures::stylesheet(50));
+}
+
+#[test]
+fn article_and_stylesheet_parse_and_match() {
+    let (html, css): (String, String) = (bench_fixtures::article(4), bench_fixtures::stylesheet(200));
+    let stylesheet: css::Stylesheet = css::parse(&css).unwrap();
+    assert_eq!(stylesheet.rules.len(), 200 + 2);
+
+    let mut page: Page = Page::load(&html, &css).unwrap();
+    assert_eq!(page.query_selector_all("div.section").len(), 4);
+    assert_eq!(page.query_selector_all("p.para").len(), 4 * 8);
+    assert_eq!(page.query_selector_all("li.item").len(), 4 * 6);
+    // Every section is a block with the generated margins, so the sections stack up.
+    let section: NodeId = page.query_selector("#s3").unwrap();
+    assert_eq!(page.computed_value(section, "display").unwrap().to_string(), "block");
+    assert!(page.computed_value(section, "margin-bottom").is_some());
+    page.set_viewport(800, 600);
+    let first: Rect = page.element_rect(page.query_selector("#s0").unwrap()).unwrap();
+    let last: Rect = page.element_rect(section).unwrap();
+    assert!(first.height > 0.0 && last.y > first.y + 2.0 * first.height, "{:?} {:?}", first, last);
+}
+
+#[test]
+fn deep_trees_nest_as_deep_as_the_parser_allows() {
+    let depth: usize = html::MAX_DEPTH - 2;
+    let document: Document = html::parse(&bench_fixtures::deep_tree(depth)).unwrap();
+    let mut deepest: NodeId = document.root_element;
+    let mut levels: usize = 0;
+    while let Some(div) = document.tree.children(deepest).find(|&child: &NodeId| document.tree.children(child).next().is_some()) {
+        deepest = div;
+        levels += 1;
+    }
+    // <body>, then the divs.
+    assert_eq!(levels, 1 + depth);
+    assert_eq!(document.tree.text_content(deepest), "At the bottom.");
+
+    assert!(html::parse(&bench_fixtures::deep_tree(depth + 1)).is_err());
+}
@@ -0,0 +1,73 @@
+//! Checks that the parsers read borrowed `&str` input, copying text only where decoding
+//! changes it, and that CSS keywords are interned.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::css::{self, Value};
+use build_a_browser_engine_in_rust::dom::Document;
+use build_a_browser_engine_in_rust::html::{self, ParseError, Token};
+use std::borrow::Cow;
+
+/// Return true if `slice` points into `source`.
+fn borrowed_from(slice: &str, source: &str) -> bool {
+    source.as_bytes().as_ptr_range().contains(&slice.as_ptr())
+}
+
+#[test]
+fn decodes_entities_only_when_there_are_some() {
+    let plain: &str = "no references here";
+    assert!(matches!(html::decode_entities(plain, false), Cow::Borrowed(text) if text.as_ptr() == plain.as_ptr()));
+    assert!(matches!(html::decode_entities("a &lt; b", false), Cow::Owned(ref text) if text == "a < b"));
+    // An ampersand that starts no reference is kept, but the text is still rewritten.
+    assert_eq!(html::decode_entities("fish & chips", false), "fish & chips");
+    // In attribute values, old names followed by `=` are left alone, as in URLs.
+    assert_eq!(html::decode_entities("?a=1&copy=2", true), "?a=1&copy=2");
+    assert_eq!(html::decode_entities("?a=1&copy=2", false), "?a=1\u{a9}=2");
+}
+
+#[test]
+fn borrows_text_tokens_from_the_source() {
+    let source: &str = "<p title=\"a&amp;b\">Hello<!-- note -->x &lt; y</p>";
+    let tokens: Vec<Token> = html::tokenize(source).collect::<Result<Vec<Token>, ParseError>>().unwrap();
+    match &tokens[0] {
+        Token::StartTag { name, attributes, .. } => {
+            assert_eq!(*name, "p");
+            assert_eq!(attributes.get("title").map(String::as_str), Some("a&b"));
+        }
+        token => panic!("expected <p>, got {:?}", token),
+    }
+    assert!(matches!(&tokens[1], Token::Text(Cow::Borrowed(text)) if *text == "Hello" && borrowed_from(text, source)));
+    assert!(matches!(&tokens[2], Token::Comment(text) if *text == " note " && borrowed_from(text, source)));
+    assert!(matches!(&tokens[3], Token::Text(Cow::Owned(text)) if text == "x < y"));
+    assert!(matches!(&tokens[4], Token::EndTag { name } if *name == "p"));
+    assert_eq!(tokens[5], Token::Eof);
+}
+
+#[test]
+fn parses_a_slice_of_a_larger_buffer() {
+    let buffer: String = String::from("junk before<p class=\"x\">caf&eacute;</p>junk after");
+    let start: usize = buffer.find('<').unwrap();
+    let end: usize = buffer.rfind('>').unwrap() + 1;
+    let document: Document = html::parse(&buffer[start..end]).unwrap();
+    // The tree owns its strings, so it outlives the buffer it was parsed from.
+    drop(buffer);
+    assert_eq!(html::serialize(&document.tree, document.root_element), "<html><head></head><body><p class=\"x\">caf\u{e9}</p></body></html>");
+}
+
+#[test]
+fn interns_css_names_and_keywords() {
+    let mut source: String = String::from("p { display: block; } div { display: BLOCK; float: block; }");
+    let stylesheet: css::Stylesheet = css::parse(&source).unwrap();
+    // Nothing in the stylesheet borrows its source.
+    source.clear();
+    let keywords: Vec<Atom> = stylesheet.rules.iter()
+        .flat_map(|rule: &css::Rule| rule.declarations.iter())
+        .map(|declaration: &css::Declaration| match &declaration.value {
+            Value::Keyword(keyword) => *keyword,
+            value => panic!("expected a keyword, got {:?}", value),
+        })
+        .collect();
+    assert_eq!(keywords, [Atom::from("block"), Atom::from("BLOCK"), Atom::from("block")]);
+    // Equal keywords are the same interned string.
+    assert_eq!(keywords[0].as_str().as_ptr(), keywords[2].as_str().as_ptr());
+    assert_eq!(stylesheet.rules[0].declarations[0].name.as_str().as_ptr(), stylesheet.rules[1].declarations[0].name.as_str().as_ptr());
+}
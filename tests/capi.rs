@@ -0,0 +1,156 @@
+//! Generates `include/browser_engine.h` from the functions in `src/capi.rs`, and checks
+//! that the saved header is up to date. After changing the C API, save the new header
+//! with `BLESS=1`:
+//!
+//!     BLESS=1 cargo test --test capi
+//!
+//! With the `capi` feature, also drives a page through the API the way a C program would.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const PRELUDE: &str = "\
+/*
+ * The engine's C API. Build the shared library with
+ *
+ *     cargo build --release --features capi
+ *
+ * and link against target/release/libbuild_a_browser_engine_in_rust.so (or .dylib, or
+ * .dll). See src/capi.rs for how pages, strings, and errors are handled.
+ *
+ * Generated from src/capi.rs by tests/capi.rs; don't edit it by hand.
+ */
+
+#ifndef BROWSER_ENGINE_H
+#define BROWSER_ENGINE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern \"C\" {
+#endif
+
+/* A page, owned by the caller until it is passed to engine_free. */
+typedef struct EnginePage EnginePage;
+";
+
+const POSTLUDE: &str = "
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* BROWSER_ENGINE_H */
+";
+
+/// The C spelling of a Rust type in the API.
+fn c_type(rust: &str) -> &'static str {
+    match rust {
+        "*const c_char" => "const char *",
+        "*mut c_char" => "char *",
+        "*mut i64" => "int64_t *",
+        "*mut EnginePage" => "EnginePage *",
+        "*mut u8" => "uint8_t *",
+        "usize" => "size_t ",
+        "i64" => "int64_t ",
+        "f32" => "float ",
+        "bool" => "bool ",
+        "" => "void ",
+        _ => panic!("no C type for {:?}; add one to c_type", rust),
+    }
+}
+
+/// Declare an `extern "C"` function, given the Rust signature after `fn`.
+fn declaration(signature: &str) -> String {
+    let (name, rest) = signature.split_once('(').unwrap();
+    let (arguments, rest) = rest.split_once(')').unwrap();
+    let result: &str = rest.trim().trim_end_matches('{').trim().trim_start_matches("->").trim();
+    let arguments: Vec<String> = arguments.split(", ")
+        .filter(|argument: &&str| !argument.is_empty())
+        .map(|argument: &str| {
+            let (name, rust) = argument.split_once(": ").unwrap();
+            format!("{}{}", c_type(rust), name)
+        })
+        .collect();
+    let arguments: String = if arguments.is_empty() { "void".to_string() } else { arguments.join(", ") };
+    format!("{}{}({});", c_type(result), name, arguments)
+}
+
+/// A C comment holding the doc comment `lines`.
+fn comment(lines: &[String]) -> String {
+    let text: String = lines.join("\n * ").replace('`', "");
+    format!("/* {} */", text)
+}
+
+/// The header for the API in `source` (the text of `src/capi.rs`).
+fn header(source: &str) -> String {
+    let mut header: String = PRELUDE.to_string();
+    let mut docs: Vec<String> = Vec::new();
+    let mut in_safety: bool = false;
+    for line in source.lines() {
+        if let Some(doc) = line.strip_prefix("///") {
+            let doc: &str = doc.trim();
+            in_safety |= doc == "# Safety";
+            if !in_safety && !doc.is_empty() {
+                docs.push(doc.to_string());
+            }
+        } else if let Some((_, signature)) = line.split_once("extern \"C\" fn ") {
+            header.push_str(&format!("\n{}\n{}\n", comment(&docs), declaration(signature)));
+            docs.clear();
+            in_safety = false;
+        } else if !line.starts_with("#[") {
+            docs.clear();
+            in_safety = false;
+        }
+    }
+    header.push_str(POSTLUDE);
+    header
+}
+
+#[test]
+fn header_is_up_to_date() {
+    let root: &Path = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let source: String = fs::read_to_string(root.join("src/capi.rs")).unwrap();
+    let path: PathBuf = root.join("include/browser_engine.h");
+    let generated: String = header(&source);
+    if env::var_os("BLESS").is_some() {
+        fs::write(&path, &generated).unwrap();
+        return;
+    }
+    let saved: String = fs::read_to_string(&path).unwrap_or_default();
+    assert!(saved == generated, "{} is out of date with src/capi.rs (run with BLESS=1 to save it)", path.display());
+}
+
+#[cfg(feature = "capi")]
+#[test]
+fn render_and_hit_test_through_the_c_api() {
+    use build_a_browser_engine_in_rust::capi::*;
+    use std::ffi::{CStr, CString};
+
+    let html: CString = CString::new("<html><body><div id=\"box\"></div></body></html>").unwrap();
+    let css: CString = CString::new("html, body, div { display: block; } body { margin: 0px; } #box { width: 10px; height: 10px; background: #ff0000; }").unwrap();
+    unsafe {
+        let page: *mut EnginePage = engine_load_html(html.as_ptr(), css.as_ptr());
+        assert!(!page.is_null(), "{:?}", CStr::from_ptr(engine_last_error()));
+        engine_set_viewport(page, 20, 20);
+
+        let size: usize = engine_render_rgba(page, std::ptr::null_mut(), 0);
+        assert_eq!(size, 20 * 20 * 4);
+        let mut pixels: Vec<u8> = vec![0; size];
+        engine_render_rgba(page, pixels.as_mut_ptr(), pixels.len());
+        assert_eq!(&pixels[..4], &[255, 0, 0, 255]);
+        assert_eq!(&pixels[(20 * 15 + 15) * 4..][..4], &[255, 255, 255, 255]);
+
+        let selector: CString = CString::new("#box").unwrap();
+        let node: i64 = engine_query_selector(page, selector.as_ptr());
+        assert!(node >= 0);
+        assert_eq!(engine_hit_test(page, 5.0, 5.0), node);
+        engine_free(page);
+
+        let bad: CString = CString::new("<div>").unwrap();
+        assert!(engine_load_html(bad.as_ptr(), css.as_ptr()).is_null());
+        assert!(!CStr::from_ptr(engine_last_error()).to_str().unwrap().is_empty());
+    }
+}
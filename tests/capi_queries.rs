@@ -0,0 +1,128 @@
+//! Checks the C API calls the Python bindings are built on: finding elements, reading
+//! their text, attributes, and computed styles, and taking screenshots, with the strings
+//! and errors the bindings turn into Python values and exceptions.
+//!
+//! Run with `cargo test --features capi --test capi_queries`.
+
+#![cfg(feature = "capi")]
+
+use build_a_browser_engine_in_rust::capi::*;
+use build_a_browser_engine_in_rust::css::Value;
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::Page;
+use std::ffi::{c_char, CStr, CString};
+use std::fs;
+use std::path::PathBuf;
+use std::ptr;
+
+const HTML: &str = "<html><body><div id=\"main\"><p class=\"note\">First</p><p class=\"note\">Sec<b>ond</b></p></div>\
+                    <a href=\"/next\">Next</a></body></html>";
+const CSS: &str = "html, body, div, p { display: block; } body { margin: 0px; } \
+                   #main { background: #ff0000; height: 40px; } .note { color: #0000ff; } a:hover { color: #00ff00; }";
+
+/// Take a string returned by the API, freeing it. `None` if it's NULL.
+unsafe fn take(s: *mut c_char) -> Option<String> {
+    if s.is_null() {
+        return None;
+    }
+    let string: String = CStr::from_ptr(s).to_str().unwrap().to_string();
+    engine_free_string(s);
+    Some(string)
+}
+
+unsafe fn load() -> *mut EnginePage {
+    let (html, css): (CString, CString) = (CString::new(HTML).unwrap(), CString::new(CSS).unwrap());
+    let page: *mut EnginePage = engine_load_html(html.as_ptr(), css.as_ptr());
+    assert!(!page.is_null());
+    page
+}
+
+#[test]
+fn finds_elements_and_reads_them() {
+    unsafe {
+        let page: *mut EnginePage = load();
+        let notes: CString = CString::new(".note").unwrap();
+        // Ask for the count first, then fill a buffer that big.
+        let count: usize = engine_query_selector_all(page, notes.as_ptr(), ptr::null_mut(), 0);
+        assert_eq!(count, 2);
+        let mut nodes: Vec<i64> = vec![-1; count];
+        assert_eq!(engine_query_selector_all(page, notes.as_ptr(), nodes.as_mut_ptr(), 1), 2);
+        assert_eq!(nodes, [-1, -1]);
+        engine_query_selector_all(page, notes.as_ptr(), nodes.as_mut_ptr(), nodes.len());
+        assert_eq!(nodes[0], engine_query_selector(page, notes.as_ptr()));
+        assert_eq!(take(engine_text_content(page, nodes[0])).as_deref(), Some("First"));
+        assert_eq!(take(engine_text_content(page, nodes[1])).as_deref(), Some("Second"));
+
+        let link: i64 = engine_query_selector(page, CString::new("a").unwrap().as_ptr());
+        let (href, title): (CString, CString) = (CString::new("href").unwrap(), CString::new("title").unwrap());
+        assert_eq!(take(engine_attribute(page, link, href.as_ptr())).as_deref(), Some("/next"));
+        assert_eq!(take(engine_attribute(page, link, title.as_ptr())), None);
+
+        let missing: CString = CString::new(".missing").unwrap();
+        assert_eq!(engine_query_selector_all(page, missing.as_ptr(), ptr::null_mut(), 0), 0);
+        engine_free(page);
+    }
+}
+
+#[test]
+fn reads_computed_styles() {
+    unsafe {
+        let page: *mut EnginePage = load();
+        let main: i64 = engine_query_selector(page, CString::new("#main").unwrap().as_ptr());
+        let style = |node: i64, property: &str| -> Option<String> {
+            let property: CString = CString::new(property).unwrap();
+            take(engine_computed_style(page, node, property.as_ptr()))
+        };
+        assert_eq!(style(main, "display").as_deref(), Some("block"));
+        assert_eq!(style(main, "background").as_deref(), Some("#ff0000"));
+        assert_eq!(style(main, "height").as_deref(), Some("40px"));
+        assert_eq!(style(main, "border-color"), None);
+        engine_free(page);
+    }
+
+    // The Rust side follows the page's `:hover` state.
+    let mut page: Page = Page::load(HTML, CSS).unwrap();
+    page.set_viewport(100, 60);
+    let link: NodeId = page.query_selector("a").unwrap();
+    assert_eq!(page.computed_value(link, "color"), None);
+    page.hover_at(2.0, 45.0);
+    assert_eq!(page.computed_value(link, "color").map(|value: Value| value.to_string()).as_deref(), Some("#00ff00"));
+    // Elements that aren't rendered have no computed style.
+    let head: NodeId = page.query_selector("head").unwrap();
+    assert_eq!(page.computed_value(head, "display"), None);
+}
+
+#[test]
+fn takes_screenshots_and_loads_files() {
+    let path: PathBuf = std::env::temp_dir().join(format!("capi-queries-{}.html", std::process::id()));
+    fs::write(&path, format!("<style>{}</style>{}", CSS, HTML)).unwrap();
+    unsafe {
+        let url: CString = CString::new(path.to_str().unwrap()).unwrap();
+        let page: *mut EnginePage = engine_load_url(url.as_ptr());
+        assert!(!page.is_null(), "{:?}", CStr::from_ptr(engine_last_error()));
+        engine_set_viewport(page, 16, 8);
+        let size: usize = engine_screenshot_png(page, ptr::null_mut(), 0);
+        let mut png: Vec<u8> = vec![0; size];
+        assert_eq!(engine_screenshot_png(page, png.as_mut_ptr(), png.len()), size);
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        assert_eq!((&png[16..20], &png[20..24]), (&16u32.to_be_bytes()[..], &8u32.to_be_bytes()[..]));
+        engine_free(page);
+    }
+}
+
+#[test]
+fn reports_errors_for_missing_nodes_and_pages() {
+    unsafe {
+        let page: *mut EnginePage = load();
+        assert_eq!(take(engine_text_content(page, 9999)), None);
+        assert_eq!(CStr::from_ptr(engine_last_error()).to_str().unwrap(), "No node 9999");
+        assert_eq!(take(engine_text_content(page, -1)), None);
+        assert_eq!(CStr::from_ptr(engine_last_error()).to_str().unwrap(), "No node -1");
+        engine_free(page);
+
+        let url: CString = CString::new("ftp://example.com/").unwrap();
+        assert!(engine_load_url(url.as_ptr()).is_null());
+        assert!(!CStr::from_ptr(engine_last_error()).to_str().unwrap().is_empty());
+        engine_free_string(ptr::null_mut());
+    }
+}
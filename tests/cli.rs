@@ -0,0 +1,78 @@
+//! Checks that `browser-engine render` paints a page from files on disk to an image, and
+//! that bad command lines fail with a usage message.
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Write `contents` to a file of its own in the temp directory, returning its path.
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path: PathBuf = std::env::temp_dir().join(format!("cli-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Run the binary with `args`.
+fn run(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_browser-engine")).args(args).output().unwrap()
+}
+
+/// The size and the first pixel's RGB of a binary PPM image.
+fn read_ppm(bytes: &[u8]) -> ((usize, usize), [u8; 3]) {
+    let header: String = String::from_utf8_lossy(&bytes[..bytes.len().min(32)]).into_owned();
+    let fields: Vec<&str> = header.split_whitespace().take(4).collect();
+    assert_eq!((fields[0], fields[3]), ("P6", "255"));
+    let header_len: usize = fields.iter().map(|field: &&str| field.len() + 1).sum();
+    let pixel: [u8; 3] = [bytes[header_len], bytes[header_len + 1], bytes[header_len + 2]];
+    ((fields[1].parse().unwrap(), fields[2].parse().unwrap()), pixel)
+}
+
+const PAGE: &str = "<html><head><style>div { height: 20px; }</style></head><body><div class=\"box\"></div></body></html>";
+const CSS: &str = "head { display: none; } html, body, div { display: block; } body { margin: 0; } .box { background: #ff0000; }";
+
+#[test]
+fn renders_pages_to_images() {
+    let html: PathBuf = temp_file("page.html", PAGE);
+    let css: PathBuf = temp_file("page.css", CSS);
+    let ppm: PathBuf = std::env::temp_dir().join(format!("cli-{}-out.ppm", std::process::id()));
+    let output: Output = run(&["render", html.to_str().unwrap(), "--css", css.to_str().unwrap(),
+                               "--size", "40x30", "-o", ppm.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    // The format comes from the output file's extension.
+    assert_eq!(read_ppm(&fs::read(&ppm).unwrap()), ((40, 30), [255, 0, 0]));
+
+    // Zooming makes a bigger image, and `--format` overrides the extension.
+    let png: PathBuf = std::env::temp_dir().join(format!("cli-{}-out.img", std::process::id()));
+    let output: Output = run(&["render", html.to_str().unwrap(), "--size", "40x30", "--scale", "2",
+                               "--format", "png", "-o", png.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let bytes: Vec<u8> = fs::read(&png).unwrap();
+    assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1a\n");
+    let size: (u32, u32) = (u32::from_be_bytes(bytes[16..20].try_into().unwrap()), u32::from_be_bytes(bytes[20..24].try_into().unwrap()));
+    assert_eq!(size, (80, 60));
+}
+
+#[test]
+fn fails_with_usage_on_bad_command_lines() {
+    let html: PathBuf = temp_file("usage.html", PAGE);
+    let html: &str = html.to_str().unwrap();
+    for (args, message) in [
+        (&["frobnicate", html][..], "unknown command \"frobnicate\""),
+        (&["render"][..], "missing input page"),
+        (&["render", html, "--size", "big"][..], "invalid size \"big\" (expected WIDTHxHEIGHT)"),
+        (&["render", html, "--format", "gif"][..], "unsupported format \"gif\" (expected png or ppm)"),
+        (&["render", html, "--bogus"][..], "unknown option \"--bogus\""),
+        (&["render", html, "--css"][..], "--css needs a value"),
+        (&["render", "/no/such/page.html"][..], "can't read /no/such/page.html"),
+    ] {
+        let output: Output = run(args);
+        let stderr: String = String::from_utf8_lossy(&output.stderr).into_owned();
+        assert_eq!(output.status.code(), Some(2), "{:?}", args);
+        assert!(stderr.starts_with(&format!("browser-engine: {}", message)), "{:?}: {}", args, stderr);
+        assert!(stderr.contains("Usage: browser-engine <command>"));
+    }
+
+    let output: Output = run(&["--help"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).starts_with("Usage: browser-engine"));
+}
@@ -0,0 +1,91 @@
+//! Checks that clicks reach the listeners scripts added, through the capture and bubble
+//! phases, and that `preventDefault` stops what the click would have done.
+//!
+//! Run with `cargo test --features js --test click_listeners`.
+
+#![cfg(feature = "js")]
+
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::forms;
+
+const CSS: &str = "head { display: none; } html, body, div, a { display: block; } body { margin: 0; } a, #box { height: 10px; }";
+
+/// Load `html`, then run `script` to add listeners.
+fn load(html: &str, script: &str) -> Page {
+    let mut page: Page = Page::load(html, CSS).unwrap();
+    page.execute_script(script).unwrap();
+    page
+}
+
+/// The value of the global `log`, joined by spaces.
+fn log(page: &mut Page) -> String {
+    page.evaluate("log.join(' ')").unwrap().to_string()
+}
+
+#[test]
+fn runs_capture_target_and_bubble_listeners_in_order() {
+    let mut page: Page = load("<div id=\"outer\"><div id=\"box\"></div></div>", "\
+        var log = [];
+        const outer = document.getElementById('outer'), box = document.getElementById('box');
+        const note = name => event => log.push(name + ':' + event.eventPhase + ':' + event.currentTarget.id + '>' + event.target.id);
+        window.addEventListener('click', () => log.push('window'));
+        document.addEventListener('click', () => log.push('document'), true);
+        outer.addEventListener('click', note('capture'), true);
+        outer.addEventListener('click', note('bubble'));
+        box.addEventListener('click', note('target'));
+        box.addEventListener('click', () => log.push('once'), { once: true });");
+
+    // The click lands on `#box`, the first thing on the page.
+    page.click_at(5.0, 5.0);
+    assert_eq!(log(&mut page), "document capture:1:outer>box target:2:box>box once bubble:3:outer>box window");
+    page.execute_script("log = [];").unwrap();
+    page.click("#box").unwrap();
+    assert_eq!(log(&mut page), "document capture:1:outer>box target:2:box>box bubble:3:outer>box window");
+}
+
+#[test]
+fn stops_propagation_and_reports_listener_errors() {
+    let mut page: Page = load("<div id=\"outer\"><div id=\"box\"></div></div>", "\
+        var log = [];
+        document.getElementById('box').addEventListener('click', event => { log.push('box'); event.stopPropagation(); });
+        document.getElementById('box').addEventListener('click', () => { log.push('second'); missing(); });
+        document.getElementById('outer').addEventListener('click', () => log.push('outer'));");
+    page.click("#box").unwrap();
+    // Listeners at the same node still run, and a throwing one doesn't stop the page.
+    assert_eq!(log(&mut page), "box second");
+    assert_eq!(page.console_messages()[0].text, "Uncaught ReferenceError: missing is not defined (in click listener)");
+}
+
+#[test]
+fn prevent_default_cancels_links_and_checkboxes() {
+    let html: &str = "<a id=\"link\" href=\"next.html\">Next</a><input type=\"checkbox\" id=\"check\">";
+    let mut page: Page = load(html, "");
+    page.click("#link").unwrap();
+    assert_eq!(page.take_followed_link().as_deref(), Some("next.html"));
+
+    let mut page: Page = load(html, "\
+        document.addEventListener('click', event => event.preventDefault());
+        var prevented = [];
+        window.addEventListener('click', event => prevented.push(event.defaultPrevented));");
+    page.click("#link").unwrap();
+    assert_eq!(page.take_followed_link(), None);
+    page.click("#check").unwrap();
+    let check: NodeId = page.document().get_element_by_id("check").unwrap();
+    assert!(!forms::is_checked(&page.document().tree, check));
+    // Focus moves anyway.
+    assert_eq!(page.focused_node(), Some(check));
+    assert_eq!(page.evaluate("prevented.join()").unwrap().to_string(), "true,true");
+}
+
+#[test]
+fn listeners_see_the_checkbox_before_it_changes() {
+    let mut page: Page = load("<input type=\"checkbox\" id=\"check\">", "\
+        var seen = [];
+        document.getElementById('check').addEventListener('click', event => seen.push(event.target.checked));");
+    page.click("#check").unwrap();
+    page.click("#check").unwrap();
+    assert_eq!(page.evaluate("seen.join()").unwrap().to_string(), "false,true");
+    let check: NodeId = page.document().get_element_by_id("check").unwrap();
+    assert!(!forms::is_checked(&page.document().tree, check));
+}
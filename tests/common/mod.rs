@@ -0,0 +1,19 @@
+//! Helpers shared by several test files, which each include them with `mod common;`. Not
+//! every file uses every helper, hence the `allow`.
+
+#![allow(dead_code)]
+
+use build_a_browser_engine_in_rust::layout::{LayoutBox, TextFragment};
+
+/// Every text fragment in a layout tree, in tree order.
+pub fn fragments(layout_box: &LayoutBox, out: &mut Vec<TextFragment>) {
+    out.extend(layout_box.text.iter().cloned());
+    for child in layout_box.children.iter() {
+        fragments(child, out);
+    }
+}
+
+/// The text of each fragment.
+pub fn texts(lines: &[TextFragment]) -> Vec<&str> {
+    lines.iter().map(|fragment: &TextFragment| fragment.run.text.as_str()).collect()
+}
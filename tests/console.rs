@@ -0,0 +1,65 @@
+//! Checks that what scripts write to the console is kept as structured messages, and
+//! that `browser-engine` prints them.
+//!
+//! Run with `cargo test --features js --test console`.
+
+#![cfg(feature = "js")]
+
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::script::{ConsoleLevel, ConsoleMessage};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+fn message(level: ConsoleLevel, text: &str, time: f64) -> ConsoleMessage {
+    ConsoleMessage { level, text: text.to_string(), time }
+}
+
+#[test]
+fn keeps_messages_with_their_level_and_time() {
+    let mut page: Page = Page::load("<script>\
+        console.log('hello', 42, [1, 'two'], { a: null });
+        console.info('info'); console.debug('debug'); console.warn('careful'); console.error(new Error('bad'));
+        setTimeout(() => console.log('later'), 250);
+    </script>", "").unwrap();
+    assert_eq!(page.console_messages(), [message(ConsoleLevel::Log, "hello 42 [1, 'two'] { a: null }", 0.0),
+                                         message(ConsoleLevel::Info, "info", 0.0),
+                                         message(ConsoleLevel::Debug, "debug", 0.0),
+                                         message(ConsoleLevel::Warn, "careful", 0.0),
+                                         message(ConsoleLevel::Error, "Error: bad", 0.0)]);
+
+    // Later messages are stamped with the page's clock.
+    page.advance_time(1000.0);
+    assert_eq!(page.console_messages().last(), Some(&message(ConsoleLevel::Log, "later", 250.0)));
+    page.execute_script("console.log(`${1 + 1}`, undefined, 'a' + 'b');").unwrap();
+    assert_eq!(page.console_messages().last().unwrap().text, "2 undefined ab");
+    assert_eq!(page.console_messages().len(), 7);
+}
+
+#[test]
+fn names_levels_like_the_console_methods() {
+    let names: Vec<String> = [ConsoleLevel::Log, ConsoleLevel::Info, ConsoleLevel::Debug, ConsoleLevel::Warn, ConsoleLevel::Error]
+        .iter()
+        .map(ConsoleLevel::to_string)
+        .collect();
+    assert_eq!(names, ["log", "info", "debug", "warn", "error"]);
+}
+
+#[test]
+fn the_command_line_prints_messages_to_stderr() {
+    let dir: PathBuf = std::env::temp_dir();
+    let html: PathBuf = dir.join(format!("console-{}.html", std::process::id()));
+    let ppm: PathBuf = dir.join(format!("console-{}.ppm", std::process::id()));
+    fs::write(&html, "<script>console.log('from the page'); console.warn('watch out'); oops();</script>").unwrap();
+    let output: Output = Command::new(env!("CARGO_BIN_EXE_browser-engine"))
+        .args(["render", html.to_str().unwrap(), "--size", "10x10", "-o", ppm.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "\
+console.log: from the page
+console.warn: watch out
+console.error: Uncaught ReferenceError: oops is not defined (in inline script)
+");
+}
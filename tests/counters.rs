@@ -0,0 +1,143 @@
+//! Checks CSS counters, counter styles, quotes, and `::before`/`::after` generated content.
+
+mod common;
+
+use build_a_browser_engine_in_rust::counters;
+use build_a_browser_engine_in_rust::css::{self, PseudoElement, Selector, Value};
+use build_a_browser_engine_in_rust::dom::{self, NodeType};
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::layout::{LayoutBox, TextFragment};
+use build_a_browser_engine_in_rust::style::{self, StyledNode};
+use common::fragments;
+
+#[test]
+fn formats_counter_styles() {
+    let formatted: Vec<String> = [1, 2, 4, 9, 14, 1994].iter().map(|&value: &i32| counters::format(value, "lower-roman")).collect();
+    assert_eq!(formatted, ["i", "ii", "iv", "ix", "xiv", "mcmxciv"]);
+    assert_eq!(counters::format(1994, "upper-roman"), "MCMXCIV");
+    assert_eq!(counters::format(4000, "upper-roman"), "4000");
+    assert_eq!(counters::format(1, "lower-alpha"), "a");
+    assert_eq!(counters::format(27, "upper-latin"), "AA");
+    assert_eq!(counters::format(0, "lower-alpha"), "0");
+    assert_eq!(counters::format(3, "lower-greek"), "γ");
+    assert_eq!(counters::format(7, "decimal-leading-zero"), "07");
+    assert_eq!(counters::format(-3, "decimal-leading-zero"), "-03");
+    assert_eq!(counters::format(12, "decimal"), "12");
+    assert_eq!(counters::format(5, "disc"), "\u{2022}");
+    assert_eq!(counters::format(5, "none"), "");
+    assert_eq!(counters::format(5, "no-such-style"), "5");
+}
+
+#[test]
+fn parses_pseudo_elements_and_functions() {
+    let stylesheet: css::Stylesheet = css::parse(r#"h2::before, p:after { content: "Chapter " counter(chapter, upper-roman) ". "; }"#).unwrap();
+    let rule: &css::Rule = &stylesheet.rules[0];
+    let Selector::Simple(ref before) = rule.selectors[0];
+    assert_eq!(before.pseudo_element, Some(PseudoElement::Before));
+    let Selector::Simple(ref after) = rule.selectors[1];
+    assert_eq!(after.pseudo_element, Some(PseudoElement::After));
+    assert_eq!(rule.selectors[0].to_string(), "h2::before");
+    assert_eq!(rule.declarations[0].value, Value::Sequence(vec![
+        Value::StringValue("Chapter ".into()),
+        Value::Function("counter".into(), vec![Value::Keyword("chapter".into()), Value::Keyword("upper-roman".into())]),
+        Value::StringValue(". ".into()),
+    ]));
+
+    // Nothing may follow a pseudo-element.
+    assert!(css::parse_selector_list("p::before.note").is_err());
+}
+
+/// The text of every `::before` and `::after` in a styled tree, in document order.
+fn generated(node: &StyledNode, out: &mut Vec<String>) {
+    if let (Some(_), NodeType::Text(ref text)) = (node.pseudo_element, &node.node.node_type) {
+        out.push(text.clone());
+    }
+    for child in node.children.iter() {
+        generated(child, out);
+    }
+}
+
+fn generate(html: &str, css: &str) -> Vec<String> {
+    let document: dom::Document = html::parse(html).unwrap();
+    let stylesheet: css::Stylesheet = css::parse(css).unwrap();
+    let root: StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+    let mut out: Vec<String> = Vec::new();
+    generated(&root, &mut out);
+    out
+}
+
+#[test]
+fn numbers_nested_sections() {
+    let html: &str = "<html><ol><li>a</li><li>b<ol><li>c</li><li>d</li></ol></li><li>e</li></ol></html>";
+    let css: &str = r#"li::before { content: counters(list-item, ".") " "; }"#;
+    assert_eq!(generate(html, css), ["1 ", "2 ", "2.1 ", "2.2 ", "3 "]);
+
+    // `start` and `value` change where a list counts from.
+    let html: &str = r#"<html><ol start="5"><li>a</li><li value="10">b</li><li>c</li></ol></html>"#;
+    assert_eq!(generate(html, "li::before { content: counter(list-item, lower-roman); }"), ["v", "x", "xi"]);
+
+    // A reset lasts to the end of its parent, so later siblings see it.
+    let html: &str = "<html><h1>A</h1><h2>a</h2><h2>b</h2><h1>B</h1><h2>c</h2></html>";
+    let css: &str = r#"
+        html { counter-reset: chapter; }
+        h1 { counter-increment: chapter; counter-reset: section; }
+        h2 { counter-increment: section; }
+        h1::before { content: counter(chapter) ". "; }
+        h2::before { content: counter(chapter) "." counter(section) " "; }
+        h2::after { content: " [" attr(id) "]"; }
+    "#;
+    assert_eq!(generate(html, css), ["1. ", "1.1 ", " []", "1.2 ", " []", "2. ", "2.1 ", " []"]);
+}
+
+#[test]
+fn quotes_nest_in_the_document_language() {
+    // `<q>` quotes by default, with marks for its language.
+    let html: &str = r#"<html><p><q>a <q>b</q></q></p><p lang="fr"><q>c <q>d</q></q></p><p lang="de-CH"><q>e</q></p></html>"#;
+    assert_eq!(generate(html, ""), ["“", "‘", "’", "”", "«", "“", "”", "»", "„", "“"]);
+
+    // `quotes` is inherited, and nesting past its pairs reuses the last one.
+    let html: &str = "<html><div><q>a<q>b<q>c</q></q></q></div></html>";
+    assert_eq!(generate(html, r#"div { quotes: "<" ">" "[" "]"; }"#), ["<", "[", "[", "]", "]", ">"]);
+    assert_eq!(generate(html, "div { quotes: none; }"), ["", "", "", "", "", ""]);
+
+    // `no-close-quote` leaves a quote open without a mark, so the next one opens at the
+    // same depth, and extra closing quotes do nothing.
+    let css: &str = r#"
+        p::before { content: open-quote; }
+        p::after { content: no-close-quote; }
+        p.last::after { content: close-quote close-quote; }
+    "#;
+    let html: &str = r#"<html><p>a</p><p>b</p><p class="last">c</p></html>"#;
+    assert_eq!(generate(html, css), ["“", "", "“", "", "“", "”"]);
+}
+
+#[test]
+fn skips_hidden_elements_and_empty_content() {
+    let html: &str = r#"<html><p>a</p><p style="display: none">b</p><p class="none">c</p><p>d</p></html>"#;
+    let css: &str = r#"
+        p { counter-increment: n; }
+        p::before { content: counter(n) ":"; }
+        p.none::before { content: none; }
+    "#;
+    assert_eq!(generate(html, css), ["1:", "3:"]);
+}
+
+#[test]
+fn lays_out_generated_content_inline() {
+    let css: &str = r#"html, h2 { display: block; } body { margin: 0px; } h2 { counter-increment: h; } h2::before { content: counter(h) ". "; }"#;
+    let mut page: Page = Page::load("<html><h2>Intro</h2><h2>Usage</h2></html>", css).unwrap();
+    page.set_deterministic(true);
+    page.set_viewport(400, 100);
+    let texts: Vec<String> = page.with_layout_tree(|root: &LayoutBox| {
+        let mut out: Vec<TextFragment> = Vec::new();
+        fragments(root, &mut out);
+        out.into_iter().map(|fragment: TextFragment| fragment.run.text).collect()
+    });
+    assert_eq!(texts.concat(), "1. Intro2. Usage");
+
+    // Generated content isn't part of the text.
+    let document: dom::Document = html::parse("<html><h2>Intro</h2></html>").unwrap();
+    let stylesheet: css::Stylesheet = css::parse(css).unwrap();
+    assert_eq!(style::style_tree(&document.tree, document.root_element, &stylesheet).inner_text(), "Intro");
+}
@@ -0,0 +1,55 @@
+//! Checks reading and writing `data-*` attributes through an element's dataset.
+
+use build_a_browser_engine_in_rust::dom::{Dataset, DatasetMut, Document, Element, NodeId, NodeType};
+use build_a_browser_engine_in_rust::html;
+
+/// The element `node` is, for changing.
+fn element_mut(document: &mut Document, node: NodeId) -> &mut Element {
+    match document.tree[node].node_type {
+        NodeType::Element(ref mut element) => element,
+        _ => panic!("node {} isn't an element", node.index()),
+    }
+}
+
+#[test]
+fn reads_data_attributes_by_camel_cased_key() {
+    let mut document: Document = html::parse("<div id=\"x\" data-max-count=\"3\" data-label=\"Items\" data-x-y-z=\"1\"></div>").unwrap();
+    let div: NodeId = document.get_element_by_id("x").unwrap();
+    let dataset: Dataset = element_mut(&mut document, div).dataset();
+    assert_eq!(dataset.get("label"), Some("Items"));
+    assert_eq!(dataset.get("maxCount"), Some("3"));
+    assert_eq!(dataset.parse::<u32>("maxCount"), Some(3));
+    assert_eq!(dataset.parse::<u32>("label"), None);
+    assert_eq!(dataset.get("xYZ"), Some("1"));
+    // Keys that no attribute name maps to find nothing.
+    assert_eq!(dataset.get("max-count"), None);
+    assert_eq!(dataset.get("id"), None);
+
+    let entries: Vec<(String, &str)> = dataset.iter().collect();
+    assert_eq!(entries, [("maxCount".to_string(), "3"), ("label".to_string(), "Items"), ("xYZ".to_string(), "1")]);
+}
+
+#[test]
+fn sets_and_removes_data_attributes() {
+    let mut document: Document = html::parse("<p data-count=\"1\">x</p>").unwrap();
+    let p: NodeId = document.get_elements_by_tag_name("p")[0];
+    let element: &mut Element = element_mut(&mut document, p);
+    let mut dataset: DatasetMut = element.dataset_mut();
+    dataset.set("count", dataset.parse::<u32>("count").unwrap() + 1);
+    dataset.set("userName", "ann");
+    assert_eq!(dataset.remove("missing"), None);
+    assert_eq!(dataset.get("userName"), Some("ann"));
+    assert_eq!(document.tree.to_html(p), "<p data-count=\"2\" data-user-name=\"ann\">x</p>");
+
+    let mut dataset: DatasetMut = element_mut(&mut document, p).dataset_mut();
+    assert_eq!(dataset.remove("count"), Some("2".to_string()));
+    assert_eq!(document.tree.to_html(p), "<p data-user-name=\"ann\">x</p>");
+}
+
+#[test]
+#[should_panic(expected = "Invalid dataset key")]
+fn refuses_keys_with_a_hyphen_before_a_lowercase_letter() {
+    let mut document: Document = html::parse("<p>x</p>").unwrap();
+    let p: NodeId = document.get_elements_by_tag_name("p")[0];
+    element_mut(&mut document, p).dataset_mut().set("max-count", 1);
+}
@@ -0,0 +1,37 @@
+//! Checks the HTML report `Page::debug_report` writes.
+
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::Page;
+use std::fs;
+use std::path::PathBuf;
+
+const HTML: &str = "<html><body><p id=\"intro\" class=\"note\" style=\"padding: 2px\">Fish & chips</p><div class=\"hidden\">gone</div></body></html>";
+const CSS: &str = "html, body, p, div { display: block; } p { margin: 4px; } p.note { background: #ff0000; } .hidden { display: none; }";
+
+#[test]
+fn report_shows_rules_values_and_boxes() {
+    let page: Page = Page::load(HTML, CSS).unwrap();
+    let path: PathBuf = std::env::temp_dir().join(format!("debug-report-{}.html", std::process::id()));
+    page.debug_report(&path).unwrap();
+    let report: String = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    let p: NodeId = page.query_selector("#intro").unwrap();
+    let hidden: NodeId = page.query_selector(".hidden").unwrap();
+    assert!(report.starts_with("<!DOCTYPE html>"));
+    // The picture is a PNG, inlined.
+    assert!(report.contains("src=\"data:image/png;base64,iVBORw0KGgo"));
+    // The paragraph's box links to its node, and its node to its box.
+    assert!(report.contains(&format!("id=\"box-{}\" href=\"#node-{}\"", p.index(), p.index())));
+    assert!(report.contains(&format!("<li id=\"node-{}\"><details open>\n<summary><a href=\"#box-{}\">", p.index(), p.index())));
+    // Its rules, in cascade order, then its style attribute.
+    let tag_rule: usize = report.find("<tr><td>(0, 0, 1)</td><td>p { margin: 4px; }</td></tr>").unwrap();
+    let class_rule: usize = report.find("<tr><td>(0, 1, 1)</td><td>p.note { background: #ff0000; }</td></tr>").unwrap();
+    let style: usize = report.find("<tr><td>style</td><td>padding: 2px</td></tr>").unwrap();
+    assert!(tag_rule < class_rule && class_rule < style);
+    assert!(report.contains("<div class=\"values\">background: #ff0000; display: block; margin: 4px; padding: 2px;</div>"));
+    assert!(report.contains("<div class=\"boxes\">block content "));
+    // Text is escaped, and elements without boxes say so.
+    assert!(report.contains("&quot;Fish &amp; chips&quot;"));
+    assert!(report.contains(&format!("<a href=\"#node-{}\">&lt;div class=&quot;hidden&quot;&gt;</a> (no box)", hidden.index())));
+}
@@ -0,0 +1,49 @@
+//! Checks that a page in deterministic mode has loaded its images by the time it is
+//! painted, without waiting on a clock.
+
+use build_a_browser_engine_in_rust::css::Color;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::painting::Canvas;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+
+/// Save a small red PNG file in the temporary directory, returning its path.
+fn red_png(name: &str) -> PathBuf {
+    let canvas: Canvas = Canvas { pixels: vec![RED; 4 * 4], width: 4, height: 4 };
+    let path: PathBuf = std::env::temp_dir().join(format!("deterministic-{}-{}.png", std::process::id(), name));
+    fs::write(&path, canvas.to_png()).unwrap();
+    path
+}
+
+fn page_html(src: &Path) -> String {
+    format!("<html><body><img src=\"{}\"></img></body></html>", src.display())
+}
+
+const CSS: &str = "html, body, img { display: block; } body { margin: 0px; }";
+
+#[test]
+fn images_load_before_the_first_paint() {
+    let first: PathBuf = red_png("first");
+    let second: PathBuf = red_png("second");
+
+    let mut page: Page = Page::load(&page_html(&first), CSS).unwrap();
+    page.set_deterministic(true);
+    assert!(page.is_deterministic());
+    page.set_viewport(8, 8);
+    assert!(page.wait_for_images(Duration::ZERO));
+    assert_eq!(page.paint().pixels[0], RED);
+
+    // Images requested later load on the spot too.
+    page.set_document(html::parse(&page_html(&second)).unwrap());
+    assert!(page.wait_for_images(Duration::ZERO));
+    let screenshot: Vec<u8> = page.screenshot();
+    assert_eq!(page.paint().pixels[0], RED);
+    assert_eq!(page.screenshot(), screenshot);
+
+    fs::remove_file(first).unwrap();
+    fs::remove_file(second).unwrap();
+}
@@ -0,0 +1,101 @@
+//! Checks the devtools protocol: reading the DOM, styles, and boxes as JSON, editing the
+//! page with commands, and serving it all over HTTP.
+//!
+//! Run with `cargo test --features devtools --test devtools`.
+
+#![cfg(feature = "devtools")]
+
+use build_a_browser_engine_in_rust::devtools::{self, DevtoolsServer};
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::Page;
+use serde_json::Value;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::thread::{self, JoinHandle};
+
+fn load() -> Page {
+    Page::load("<div id=\"a\" class=\"x\">Hi</div><p>bye</p>", "head { display: none; } html, body, div, p { display: block; } div { height: 10px; }").unwrap()
+}
+
+/// Send a request to `handle`, returning the status and the parsed JSON body.
+fn request(page: &mut Page, method: &str, path: &str, body: &str) -> (u16, Value) {
+    let (status, json) = devtools::handle(page, method, path, body);
+    (status, serde_json::from_str(&json).unwrap())
+}
+
+#[test]
+fn reads_the_dom_with_ids() {
+    let mut page: Page = load();
+    let div: NodeId = page.document().get_element_by_id("a").unwrap();
+    let (status, dom) = request(&mut page, "GET", "/dom", "");
+    assert_eq!(status, 200);
+    assert_eq!(dom["tag"], "html");
+    let found: &Value = &dom["children"][1]["children"][0];
+    assert_eq!((&found["id"], &found["attrs"]["class"]), (&Value::from(div.index()), &Value::from("x")));
+
+    let (_, node) = request(&mut page, "GET", &format!("/node/{}", div.index()), "");
+    assert_eq!(node["children"][0], serde_json::json!({ "id": node["children"][0]["id"], "type": "text", "text": "Hi" }));
+    let (_, style) = request(&mut page, "GET", &format!("/node/{}/style", div.index()), "");
+    assert_eq!(style["values"]["height"], "10px");
+
+    let (_, stylesheet) = request(&mut page, "GET", "/stylesheet", "");
+    assert!(stylesheet["css"].as_str().unwrap().contains("height: 10px"));
+    let (_, layout) = request(&mut page, "GET", "/layout", "");
+    assert_eq!(layout["box"], "block");
+}
+
+#[test]
+fn applies_mutation_commands() {
+    let mut page: Page = load();
+    let div: NodeId = page.document().get_element_by_id("a").unwrap();
+    let commands: String = format!("[{{\"op\":\"set-attribute\",\"node\":{0},\"name\":\"class\",\"value\":\"y\"}},\
+                                     {{\"op\":\"set-text\",\"node\":\"{0}\",\"text\":\"New\"}},\
+                                     {{\"op\":\"set-stylesheet\",\"css\":\"div {{ height: 20px; }}\"}},\
+                                     {{\"op\":\"hover\",\"node\":{0}}}]", div.index());
+    assert_eq!(request(&mut page, "POST", "/mutate", &commands), (200, serde_json::json!({ "applied": 4 })));
+    assert_eq!(page.document().tree.to_html(div), "<div id=\"a\" class=\"y\">New</div>");
+    assert_eq!(page.document().get_elements_by_class_name("y"), [div]);
+    assert_eq!(page.hovered_node(), Some(div));
+
+    // A failing command stops the list, keeping the ones before it.
+    let commands: String = format!("[{{\"op\":\"remove-attribute\",\"node\":{},\"name\":\"class\"}},{{\"op\":\"explode\"}}]", div.index());
+    assert_eq!(request(&mut page, "POST", "/mutate", &commands), (400, serde_json::json!({ "error": "Command 1: Unknown op \"explode\"" })));
+    assert_eq!(page.document().tree.to_html(div), "<div id=\"a\">New</div>");
+    assert_eq!(request(&mut page, "POST", "/mutate", &format!("{{\"op\":\"remove\",\"node\":{}}}", div.index())).0, 200);
+    assert!(page.document().get_element_by_id("a").is_none());
+}
+
+#[test]
+fn reports_bad_requests() {
+    let mut page: Page = load();
+    assert_eq!(request(&mut page, "GET", "/node/9999", ""), (404, serde_json::json!({ "error": "No node 9999" })));
+    assert_eq!(request(&mut page, "GET", "/node/abc", "").0, 400);
+    assert_eq!(request(&mut page, "DELETE", "/dom", ""), (405, serde_json::json!({ "error": "DELETE is not allowed on /dom" })));
+    assert_eq!(request(&mut page, "GET", "/nothing", "").0, 404);
+    assert_eq!(request(&mut page, "POST", "/mutate", "{not json").0, 400);
+    assert_eq!(devtools::handle(&mut page, "OPTIONS", "/dom", ""), (204, String::new()));
+}
+
+#[test]
+fn serves_requests_over_http() {
+    let mut page: Page = load();
+    let server: DevtoolsServer = DevtoolsServer::bind("127.0.0.1:0").unwrap();
+    let address: SocketAddr = server.local_addr().unwrap();
+    let client: JoinHandle<String> = thread::spawn(move || {
+        let mut stream: TcpStream = TcpStream::connect(address).unwrap();
+        let body: &str = "{\"op\":\"set-stylesheet\",\"css\":\"p { color: red; }\"}";
+        write!(stream, "POST /mutate HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        let mut response: String = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    });
+    while !client.is_finished() {
+        server.poll(&mut page).unwrap();
+        thread::yield_now();
+    }
+    let response: String = client.join().unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{}", response);
+    assert!(response.contains("Access-Control-Allow-Origin: *\r\n"));
+    assert!(response.ends_with("\r\n\r\n{\"applied\":1}"));
+    assert_eq!(page.stylesheet().rules.len(), 1);
+}
@@ -0,0 +1,78 @@
+//! Checks that `dom::diff` finds the changes between two trees, and that applying them
+//! with `Document::apply_patches` turns the old document into the new one.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::dom::{self, Document, NodeId, Patch};
+use build_a_browser_engine_in_rust::html;
+
+/// The body's first child, the element each test page is about.
+fn first(document: &Document) -> NodeId {
+    document.tree.children(document.body().unwrap()).next().unwrap()
+}
+
+/// Diff `old` against `new`, apply the patches to `old`, and check it then matches `new`.
+fn patch(old: &str, new: &str) -> (Document, Vec<Patch>) {
+    let mut old: Document = html::parse(old).unwrap();
+    let new: Document = html::parse(new).unwrap();
+    old.build_indexes();
+    let patches: Vec<Patch> = dom::diff(&old.tree, old.root_element, &new.tree, new.root_element);
+    old.apply_patches(&new.tree, &patches);
+    assert!(old.tree.tree_eq(old.root_element, &new.tree, new.root_element), "patched {}, wanted {}", old.to_html(), new.to_html());
+    (old, patches)
+}
+
+#[test]
+fn finds_attribute_text_and_insertion_patches() {
+    let old: Document = html::parse("<ul><li>a</li><li>b</li></ul>").unwrap();
+    let new: Document = html::parse("<ul class=\"x\"><li>a</li><li>c</li><p>d</p></ul>").unwrap();
+    let (ul, new_ul) = (first(&old), first(&new));
+    let b: NodeId = old.tree.children(old.tree.children(ul).nth(1).unwrap()).next().unwrap();
+    let p: NodeId = new.tree.children(new_ul).nth(2).unwrap();
+
+    let patches: Vec<Patch> = dom::diff(&old.tree, ul, &new.tree, new_ul);
+    assert_eq!(patches, [
+        Patch::SetAttribute { node: ul, name: Atom::from("class"), value: "x".to_string() },
+        Patch::SetText { node: b, text: "c".to_string() },
+        Patch::Insert { parent: ul, before: None, new_node: p },
+    ]);
+    // Identical trees need no patches.
+    assert!(dom::diff(&old.tree, ul, &old.tree, ul).is_empty());
+}
+
+#[test]
+fn removes_and_inserts_unmatched_children() {
+    let (_, patches) = patch("<div><p>a</p><span>b</span><p>c</p></div>", "<div><em>x</em><p>a</p><p>c</p></div>");
+    let kinds: Vec<&str> = patches.iter().map(|patch: &Patch| match patch {
+        Patch::Insert { before: Some(_), .. } => "insert before",
+        Patch::Insert { before: None, .. } => "append",
+        Patch::Remove { .. } => "remove",
+        _ => "other",
+    }).collect();
+    assert_eq!(kinds, ["insert before", "remove"]);
+}
+
+#[test]
+fn replaces_nodes_of_a_different_kind() {
+    let (document, patches) = patch("<div><p id=\"a\">x</p>text</div>", "<div><p id=\"b\">x</p><b>bold</b></div>");
+    assert!(patches.iter().any(|patch: &Patch| matches!(patch, Patch::Remove { .. })));
+    assert_eq!(document.tree.to_html(first(&document)), "<div><p id=\"b\">x</p><b>bold</b></div>");
+
+    // A root that can't be patched is replaced whole.
+    let old: Document = html::parse("<p>x</p>").unwrap();
+    let new: Document = html::parse("<div>x</div>").unwrap();
+    let (p, div) = (first(&old), first(&new));
+    assert_eq!(dom::diff(&old.tree, p, &new.tree, div), [Patch::Replace { node: p, new_node: div }]);
+}
+
+#[test]
+fn keeps_indexes_in_sync() {
+    let (document, _) = patch(
+        "<div id=\"old\" class=\"a\"><p class=\"a\">x</p></div>",
+        "<div id=\"old\" class=\"b\"><p class=\"a\">x</p><section id=\"new\" class=\"b\"></section></div>",
+    );
+    let div: NodeId = document.get_element_by_id("old").unwrap();
+    let section: NodeId = document.get_element_by_id("new").unwrap();
+    assert_eq!(document.get_elements_by_class_name("b"), [div, section]);
+    assert_eq!(document.get_elements_by_class_name("a").len(), 1);
+    assert_eq!(document.get_elements_by_tag_name("section"), [section]);
+}
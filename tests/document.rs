@@ -0,0 +1,34 @@
+//! Checks the `Document` the parser returns: its root, head, and body, and what it
+//! starts out knowing about where it came from.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::dom::{AttributeMap, Document, NodeId, QuirksMode, Tree};
+use build_a_browser_engine_in_rust::html;
+
+#[test]
+fn finds_the_root_head_and_body() {
+    let document: Document = html::parse("<!DOCTYPE html><html><head><title>T</title></head><body><p>Hi</p></body></html>").unwrap();
+    let root: NodeId = document.document_element();
+    assert_eq!(root, document.root_element);
+    let children: Vec<NodeId> = document.tree.children(root).collect();
+    assert_eq!((document.head(), document.body()), (Some(children[0]), Some(children[1])));
+    assert_eq!(document.tree.text_content(document.body().unwrap()), "Hi");
+    assert_eq!(document.to_html(), "<!DOCTYPE html><html><head><title>T</title></head><body><p>Hi</p></body></html>");
+
+    // The parser doesn't know where the source came from; its loader fills that in.
+    assert_eq!((document.url.as_deref(), document.base_url.as_deref()), (None, None));
+}
+
+#[test]
+fn a_document_built_by_hand_has_no_head_or_body() {
+    let mut tree: Tree = Tree::new();
+    let text: NodeId = tree.text("Hello".to_string());
+    let paragraph: NodeId = tree.element(Atom::new("p"), AttributeMap::new(), vec![text]);
+    let document: Document = Document::new(tree, paragraph);
+
+    assert_eq!(document.document_element(), paragraph);
+    assert_eq!((document.head(), document.body()), (None, None));
+    assert_eq!((document.doctype.as_deref(), document.quirks_mode), (None, QuirksMode::NoQuirks));
+    assert_eq!(document.to_html(), "<p>Hello</p>");
+    assert!(document.is_connected(text));
+}
@@ -0,0 +1,59 @@
+//! Checks copying subtrees, within a tree and between trees, and comparing them by
+//! structure rather than by node id.
+
+use build_a_browser_engine_in_rust::dom::{Document, NodeId, Tree};
+use build_a_browser_engine_in_rust::html;
+
+#[test]
+fn clones_subtrees_detached() {
+    let mut document: Document = html::parse("<ul id=\"list\"><li class=\"a\">One</li><li>Two <b>!</b></li></ul>").unwrap();
+    let list: NodeId = document.get_element_by_id("list").unwrap();
+    let copy: NodeId = document.tree.deep_clone(list);
+    assert_ne!(copy, list);
+    assert_eq!(document.tree[copy].parent, None);
+    assert!(document.tree.tree_eq(list, &document.tree, copy));
+    assert_eq!(document.tree.to_html(copy), document.tree.to_html(list));
+    // The copy shares no nodes with the original.
+    assert!(document.tree.descendants(copy).all(|node: NodeId| !document.is_connected(node)));
+
+    // Changing the copy leaves the original alone, and makes them unequal.
+    let li: NodeId = document.tree.children(copy).next().unwrap();
+    document.tree.detach(li);
+    assert!(!document.tree.tree_eq(list, &document.tree, copy));
+    assert_eq!(document.tree.children(list).count(), 2);
+}
+
+#[test]
+fn compares_structure_attributes_and_text() {
+    let parse = |source: &str| -> (Tree, NodeId) {
+        let document: Document = html::parse(source).unwrap();
+        let body: NodeId = document.body().unwrap();
+        (document.tree, body)
+    };
+    let (tree, body) = parse("<p id=\"a\" class=\"b\">x<i>y</i></p>");
+    let same = |source: &str| -> bool {
+        let (other, other_body) = parse(source);
+        tree.tree_eq(body, &other, other_body)
+    };
+    // Attribute order doesn't matter; everything else does.
+    assert!(same("<p class=\"b\" id=\"a\">x<i>y</i></p>"));
+    assert!(!same("<p id=\"a\" class=\"c\">x<i>y</i></p>"));
+    assert!(!same("<p id=\"a\">x<i>y</i></p>"));
+    assert!(!same("<p id=\"a\" class=\"b\">x<i>z</i></p>"));
+    assert!(!same("<p id=\"a\" class=\"b\">x<b>y</b></p>"));
+    assert!(!same("<p id=\"a\" class=\"b\">x<i>y</i>z</p>"));
+}
+
+#[test]
+fn imports_subtrees_from_other_trees() {
+    let source: Document = html::parse("<div><p>Hi <em>there</em></p></div>").unwrap();
+    let div: NodeId = source.get_elements_by_tag_name("div")[0];
+    let mut target: Document = html::parse("<main></main>").unwrap();
+    let main: NodeId = target.get_elements_by_tag_name("main")[0];
+
+    let imported: NodeId = target.tree.import(&source.tree, div);
+    target.append_child(main, imported);
+    assert!(target.tree.tree_eq(imported, &source.tree, div));
+    assert_eq!(target.tree.to_html(main), "<main><div><p>Hi <em>there</em></p></div></main>");
+    assert_eq!(target.get_elements_by_tag_name("em").len(), 1);
+}
@@ -0,0 +1,42 @@
+//! Checks how nodes print: one line per node in a dump, and `Display` and `Debug` for
+//! elements, text, and fragments.
+
+use build_a_browser_engine_in_rust::dom::{self, Document, NodeId, NodeType, Tree};
+use build_a_browser_engine_in_rust::html;
+
+#[test]
+fn dumps_one_node_per_line() {
+    let document: Document = html::parse("<p class=\"note\" id=\"x\">Hello <em title='a \"b\"'>world</em></p>").unwrap();
+    assert_eq!(dom::dump(&document.tree, document.root_element), "\
+<html>
+  <head>
+  <body>
+    <p class=\"note\" id=\"x\">
+      \"Hello \"
+      <em title=\"a \\\"b\\\"\">
+        \"world\"
+");
+    let em: NodeId = document.get_elements_by_tag_name("em")[0];
+    assert_eq!(dom::dump(&document.tree, em), "<em title=\"a \\\"b\\\"\">\n  \"world\"\n");
+}
+
+#[test]
+fn displays_and_debugs_nodes() {
+    let document: Document = html::parse("<a href=\"/\">Line\nbreak</a>").unwrap();
+    let a: NodeId = document.get_elements_by_tag_name("a")[0];
+    let text: NodeId = document.tree.children(a).next().unwrap();
+    assert_eq!(document.tree[a].node_type.to_string(), "<a href=\"/\">");
+    assert_eq!(document.tree[text].node_type.to_string(), "\"Line\\nbreak\"");
+    match document.tree[a].node_type {
+        NodeType::Element(ref element) => {
+            assert_eq!(element.to_string(), "<a href=\"/\">");
+            assert_eq!(format!("{:?}", element.attributes), "{\"href\": \"/\"}");
+        }
+        _ => unreachable!(),
+    }
+
+    let mut tree: Tree = Tree::new();
+    let fragment: NodeId = tree.fragment(Vec::new());
+    assert_eq!(tree[fragment].node_type.to_string(), "#document-fragment");
+    assert_eq!(format!("{:?}", tree[fragment].node_type), "DocumentFragment");
+}
@@ -0,0 +1,106 @@
+//! Checks that a document's id, class, and tag indexes give the same answers as
+//! searching the tree, through every kind of mutation.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::dom::{AttributeMap, Document, NodeId};
+use build_a_browser_engine_in_rust::html;
+
+const IDS: &[&str] = &["main", "a", "b", "new", "moved"];
+const CLASSES: &[&str] = &["item", "first", "extra", "fresh"];
+const TAGS: &[&str] = &["div", "p", "span", "em", "li"];
+
+/// Every lookup the test makes, in one list.
+fn lookups(document: &Document) -> Vec<Vec<NodeId>> {
+    let ids = IDS.iter().map(|id: &&str| document.get_element_by_id(id).into_iter().collect());
+    let classes = CLASSES.iter().map(|class: &&str| document.get_elements_by_class_name(class));
+    let tags = TAGS.iter().map(|tag: &&str| document.get_elements_by_tag_name(tag));
+    ids.chain(classes).chain(tags).collect()
+}
+
+/// Check that the indexes answer every lookup the way a search of the tree does.
+fn check(document: &mut Document) {
+    assert!(document.has_indexes());
+    let indexed: Vec<Vec<NodeId>> = lookups(document);
+    document.drop_indexes();
+    assert_eq!(indexed, lookups(document), "\n{}", document.to_html());
+    document.build_indexes();
+}
+
+/// A new, detached element with the given attributes.
+fn element(document: &mut Document, tag_name: &str, attributes: &[(&str, &str)]) -> NodeId {
+    let mut map: AttributeMap = AttributeMap::new();
+    for &(name, value) in attributes {
+        map.insert(Atom::new(name), value.to_string());
+    }
+    document.tree.element(Atom::new(tag_name), map, Vec::new())
+}
+
+#[test]
+fn keeps_indexes_in_sync_through_mutations() {
+    let mut document: Document = html::parse("<div id=\"main\"><p class=\"item first\" id=\"a\">One</p>\
+        <p class=\"item\" id=\"b\">Two <em>three</em></p></div>").unwrap();
+    document.build_indexes();
+    check(&mut document);
+    let main: NodeId = document.get_element_by_id("main").unwrap();
+    let (a, b) = (document.get_element_by_id("a").unwrap(), document.get_element_by_id("b").unwrap());
+
+    // Detached nodes aren't indexed, nor is anything built under them, until they're
+    // connected, subtree and all.
+    let span: NodeId = element(&mut document, "span", &[("id", "new"), ("class", "item fresh")]);
+    let em: NodeId = element(&mut document, "em", &[("class", "extra")]);
+    document.append_child(span, em);
+    assert_eq!(document.get_element_by_id("new"), None);
+    document.insert_before(main, span, Some(b));
+    assert_eq!(document.get_elements_by_class_name("item"), [a, span, b]);
+    check(&mut document);
+
+    // Lists stay in document order when a node moves.
+    document.append_child(main, a);
+    assert_eq!(document.get_elements_by_class_name("item"), [span, b, a]);
+    check(&mut document);
+
+    // Attribute changes move an element between lists.
+    document.set_attribute(a, "id", "moved");
+    document.set_attribute(b, "class", "extra");
+    document.remove_attribute(span, "class");
+    assert_eq!(document.get_element_by_id("a"), None);
+    assert_eq!(document.get_elements_by_class_name("extra"), [em, b]);
+    check(&mut document);
+
+    // A fragment's children are indexed, not the fragment.
+    let (li, other) = (element(&mut document, "li", &[("class", "fresh")]), element(&mut document, "li", &[("id", "a")]));
+    let fragment: NodeId = document.tree.fragment(vec![li, other]);
+    document.append_child(b, fragment);
+    assert_eq!(document.get_elements_by_tag_name("li"), [li, other]);
+    assert_eq!(document.get_element_by_id("a"), Some(other));
+    check(&mut document);
+
+    // Removing a node takes its whole subtree out.
+    document.remove_child(main, b);
+    assert!(document.get_elements_by_tag_name("li").is_empty());
+    assert_eq!(document.get_elements_by_class_name("extra"), [em]);
+    check(&mut document);
+
+    // Changes to a detached node are never indexed.
+    document.set_attribute(b, "id", "main");
+    assert_eq!(document.get_element_by_id("main"), Some(main));
+    check(&mut document);
+}
+
+#[test]
+fn finds_the_first_of_several_elements_with_an_id() {
+    let mut document: Document = html::parse("<p id=\"a\">1</p><div><span id=\"a\">2</span></div>").unwrap();
+    let p: NodeId = document.get_elements_by_tag_name("p")[0];
+    let span: NodeId = document.get_elements_by_tag_name("span")[0];
+    assert!(!document.has_indexes());
+    assert_eq!(document.get_element_by_id("a"), Some(p));
+
+    document.build_indexes();
+    assert_eq!(document.get_element_by_id("a"), Some(p));
+    document.remove_attribute(p, "id");
+    assert_eq!(document.get_element_by_id("a"), Some(span));
+    // Putting it back before the other one makes it first again.
+    document.set_attribute(p, "id", "a");
+    assert_eq!(document.get_element_by_id("a"), Some(p));
+    check(&mut document);
+}
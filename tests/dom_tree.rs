@@ -0,0 +1,143 @@
+//! Checks that the arena DOM keeps its parent and sibling links consistent through
+//! inserts, moves, and removals, and that its walks follow them.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::dom::{AttributeMap, NodeId, NodeType, Tree};
+use std::cmp::Ordering;
+
+/// A new, empty element.
+fn element(tree: &mut Tree, tag_name: &str) -> NodeId {
+    tree.element(Atom::new(tag_name), AttributeMap::new(), Vec::new())
+}
+
+/// The tag names (or text) of `node`'s children, first to last.
+fn names(tree: &Tree, node: NodeId) -> Vec<String> {
+    tree.children(node).map(|child: NodeId| match tree[child].node_type {
+        NodeType::Element(ref element) => element.tag_name.to_string(),
+        NodeType::Text(ref text) => text.clone(),
+        NodeType::DocumentFragment => "#fragment".to_string(),
+    }).collect()
+}
+
+/// Check that every link under `root` has a matching link back.
+fn check_links(tree: &Tree, root: NodeId) {
+    for node in tree.descendants(root) {
+        let children: Vec<NodeId> = tree.children(node).collect();
+        assert_eq!(tree[node].first_child, children.first().copied());
+        assert_eq!(tree[node].last_child, children.last().copied());
+        for (i, &child) in children.iter().enumerate() {
+            assert_eq!(tree[child].parent, Some(node));
+            assert_eq!(tree[child].prev_sibling, i.checked_sub(1).map(|prev: usize| children[prev]));
+            assert_eq!(tree[child].next_sibling, children.get(i + 1).copied());
+        }
+    }
+}
+
+#[test]
+fn keeps_links_consistent_through_mutations() {
+    let mut tree: Tree = Tree::new();
+    assert!(tree.is_empty());
+    let (a, b, c) = (element(&mut tree, "a"), element(&mut tree, "b"), element(&mut tree, "c"));
+    let text: NodeId = tree.text("hi".to_string());
+    let root: NodeId = tree.element(Atom::new("div"), AttributeMap::new(), vec![a, b]);
+    assert_eq!(tree.len(), 5);
+    assert_eq!(root.index(), 4);
+    assert_eq!(tree.node_id(4), Some(root));
+    assert_eq!(tree.node_id(5), None);
+
+    tree.append_child(root, c);
+    tree.insert_before(root, text, Some(b));
+    assert_eq!(names(&tree, root), ["a", "hi", "b", "c"]);
+    check_links(&tree, root);
+
+    // Inserting a node that's already in the tree moves it.
+    tree.insert_before(root, c, Some(a));
+    assert_eq!(names(&tree, root), ["c", "a", "hi", "b"]);
+    tree.append_child(a, b);
+    assert_eq!(names(&tree, root), ["c", "a", "hi"]);
+    assert_eq!(names(&tree, a), ["b"]);
+    check_links(&tree, root);
+
+    // Inserting a node before itself changes nothing.
+    tree.insert_before(root, a, Some(a));
+    assert_eq!(names(&tree, root), ["c", "a", "hi"]);
+
+    // A removed node keeps its children, and can go back in.
+    tree.remove_child(root, a);
+    assert_eq!(names(&tree, root), ["c", "hi"]);
+    assert_eq!((tree[a].parent, tree[a].prev_sibling, tree[a].next_sibling), (None, None, None));
+    assert_eq!(names(&tree, a), ["b"]);
+    tree.append_child(root, a);
+    assert_eq!(names(&tree, root), ["c", "hi", "a"]);
+    check_links(&tree, root);
+
+    tree.detach(c);
+    tree.detach(text);
+    tree.detach(a);
+    assert_eq!(tree.children(root).count(), 0);
+    check_links(&tree, root);
+    // Nodes are never freed.
+    assert_eq!(tree.len(), 5);
+}
+
+#[test]
+#[should_panic(expected = "Cannot insert a node into itself or its own descendant")]
+fn refuses_to_insert_a_node_into_its_descendant() {
+    let mut tree: Tree = Tree::new();
+    let inner: NodeId = element(&mut tree, "span");
+    let outer: NodeId = tree.element(Atom::new("div"), AttributeMap::new(), vec![inner]);
+    tree.append_child(inner, outer);
+}
+
+#[test]
+#[should_panic(expected = "Reference node is not a child of the parent node")]
+fn refuses_a_reference_node_from_elsewhere() {
+    let mut tree: Tree = Tree::new();
+    let (a, b, c) = (element(&mut tree, "a"), element(&mut tree, "b"), element(&mut tree, "c"));
+    tree.insert_before(a, b, Some(c));
+}
+
+#[test]
+#[should_panic(expected = "Node is not a child of the parent node")]
+fn refuses_to_remove_another_nodes_child() {
+    let mut tree: Tree = Tree::new();
+    let child: NodeId = element(&mut tree, "b");
+    let parent: NodeId = tree.element(Atom::new("a"), AttributeMap::new(), vec![child]);
+    let other: NodeId = element(&mut tree, "c");
+    tree.append_child(parent, other);
+    tree.remove_child(other, child);
+}
+
+#[test]
+fn walks_the_tree_in_every_direction() {
+    //  root
+    //  ├── a
+    //  │   ├── a1
+    //  │   └── a2
+    //  │       └── a2x
+    //  └── b
+    let mut tree: Tree = Tree::new();
+    let a2x: NodeId = element(&mut tree, "a2x");
+    let a1: NodeId = element(&mut tree, "a1");
+    let a2: NodeId = tree.element(Atom::new("a2"), AttributeMap::new(), vec![a2x]);
+    let a: NodeId = tree.element(Atom::new("a"), AttributeMap::new(), vec![a1, a2]);
+    let b: NodeId = element(&mut tree, "b");
+    let root: NodeId = tree.element(Atom::new("root"), AttributeMap::new(), vec![a, b]);
+    let stray: NodeId = element(&mut tree, "stray");
+
+    assert_eq!(tree.descendants(root).collect::<Vec<NodeId>>(), [root, a, a1, a2, a2x, b]);
+    // A walk over a subtree doesn't climb out of it.
+    assert_eq!(tree.descendants(a2).collect::<Vec<NodeId>>(), [a2, a2x]);
+    assert_eq!(tree.ancestors(a2x).collect::<Vec<NodeId>>(), [a2x, a2, a, root]);
+    assert_eq!(tree.following_siblings(a1).collect::<Vec<NodeId>>(), [a2]);
+    assert_eq!(tree.preceding_siblings(a2).collect::<Vec<NodeId>>(), [a1]);
+    assert_eq!(tree.following_siblings(b).count(), 0);
+
+    assert_eq!(tree.compare_document_order(a2x, b), Ordering::Less);
+    assert_eq!(tree.compare_document_order(b, a1), Ordering::Greater);
+    assert_eq!(tree.compare_document_order(a, a2x), Ordering::Less);
+    assert_eq!(tree.compare_document_order(a2x, a), Ordering::Greater);
+    assert_eq!(tree.compare_document_order(a2, a2), Ordering::Equal);
+    // Nodes in different trees are still ordered, one way round.
+    assert_eq!(tree.compare_document_order(stray, root), tree.compare_document_order(root, stray).reverse());
+}
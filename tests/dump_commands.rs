@@ -0,0 +1,73 @@
+//! Checks that the `dump-*` commands print each stage's tree, as an indented outline or
+//! as JSON.
+
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const PAGE: &str = "<p class=\"a\">Hi <em>there</em></p>";
+const CSS: &str = "head { display: none; } html, body, p { display: block; } body { margin: 0; } p { height: 10px; background: #ff0000; }";
+
+/// Run `browser-engine <command>` on the test page and stylesheet, returning what it printed.
+fn dump(command: &str, json: bool) -> String {
+    let dir: PathBuf = std::env::temp_dir();
+    let name: String = format!("{}-{}-{}", command, json, std::process::id());
+    let (html, css) = (dir.join(format!("{}.html", name)), dir.join(format!("{}.css", name)));
+    fs::write(&html, PAGE).unwrap();
+    fs::write(&css, CSS).unwrap();
+    let mut args: Vec<&str> = vec![command, html.to_str().unwrap(), "--css", css.to_str().unwrap(), "--size", "100x50"];
+    if json {
+        args.push("--json");
+    }
+    let output: Output = Command::new(env!("CARGO_BIN_EXE_browser-engine")).args(&args).output().unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn dumps_the_dom() {
+    assert_eq!(dump("dump-dom", false), "<html>\n  <head>\n  <body>\n    <p class=\"a\">\n      \"Hi \"\n      <em>\n        \"there\"\n");
+    // The JSON is a snapshot node (see `snapshot`).
+    let json: Value = serde_json::from_str(&dump("dump-dom", true)).unwrap();
+    assert_eq!(json["tag"], "html");
+    assert_eq!(json["children"][1]["children"][0]["attrs"]["class"], "a");
+}
+
+#[test]
+fn dumps_specified_values() {
+    let dump_text: String = dump("dump-style", false);
+    let lines: Vec<&str> = dump_text.lines().collect();
+    assert_eq!(lines[..4], ["<html> { display: block; }", "  <head> { display: none; }", "  <body> { display: block; margin: 0; }",
+                            "    <p class=\"a\"> { background: #ff0000; display: block; height: 10px; }"]);
+    let json: Value = serde_json::from_str(&dump("dump-style", true)).unwrap();
+    assert_eq!(json["children"][1]["values"]["margin"], "0");
+    assert_eq!(json["children"][1]["children"][0]["label"], "<p class=\"a\">");
+}
+
+#[test]
+fn dumps_boxes() {
+    let dump_text: String = dump("dump-layout", false);
+    let lines: Vec<&str> = dump_text.lines().collect();
+    assert_eq!(lines[..3], ["block <html> content 0,0 100x10", "  block <body> content 0,0 100x10", "    block <p class=\"a\"> content 0,0 100x10"]);
+    // How tall the line is depends on the font's metrics.
+    assert!(lines[3].starts_with("      anonymous content 0,0 100x"));
+    assert!(lines[4].starts_with("        inline \"Hi \" content 0,"));
+
+    let json: Value = serde_json::from_str(&dump("dump-layout", true)).unwrap();
+    let p: &Value = &json["children"][0]["children"][0];
+    assert_eq!((&p["box"], &p["content"]["width"], &p["margin"]["top"]), (&Value::from("block"), &Value::from(100), &Value::from(0)));
+}
+
+#[test]
+fn dumps_the_display_list() {
+    let dump_text: String = dump("dump-display-list", false);
+    let lines: Vec<&str> = dump_text.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "solid-color #ff0000 0,0 100x10");
+    assert!(lines[1].starts_with("text \"Hi \" #000000 16px 0,"));
+
+    let json: Value = serde_json::from_str(&dump("dump-display-list", true)).unwrap();
+    assert_eq!(json[0]["type"], "solid-color");
+    assert_eq!(json[2]["text"], "there");
+}
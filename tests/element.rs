@@ -0,0 +1,43 @@
+//! Checks the accessors for an element's attributes, id, and classes.
+
+use build_a_browser_engine_in_rust::dom::{Document, Element, NodeId, NodeType};
+use build_a_browser_engine_in_rust::html;
+
+/// The element `node` is.
+fn element(document: &Document, node: NodeId) -> &Element {
+    match document.tree[node].node_type {
+        NodeType::Element(ref element) => element,
+        _ => panic!("node {} isn't an element", node.index()),
+    }
+}
+
+#[test]
+fn reads_attributes_ids_and_classes() {
+    let document: Document = html::parse("<p id=\"intro\" class=\"  lead  note lead\n wide\" title=\"\">x</p><br>").unwrap();
+    let p: &Element = element(&document, document.get_elements_by_tag_name("p")[0]);
+    assert_eq!(p.attr("title"), Some(""));
+    assert_eq!(p.attr("lang"), None);
+    assert_eq!(p.id(), Some("intro"));
+    // Classes are split on any white space, and each one is listed once.
+    assert_eq!(p.classes().collect::<Vec<&str>>(), ["lead", "note", "wide"]);
+    assert!(p.has_class("note"));
+    assert!(!p.has_class("lea"));
+    assert!(!p.has_class("lead note"));
+
+    let br: &Element = element(&document, document.get_elements_by_tag_name("br")[0]);
+    assert_eq!(br.id(), None);
+    assert_eq!(br.classes().count(), 0);
+    assert!(!br.has_class(""));
+}
+
+#[test]
+fn sees_attribute_changes() {
+    let mut document: Document = html::parse("<div class=\"a\"></div>").unwrap();
+    let div: NodeId = document.get_elements_by_tag_name("div")[0];
+    document.set_attribute(div, "class", "b c");
+    document.set_attribute(div, "id", "box");
+    assert_eq!(element(&document, div).classes().collect::<Vec<&str>>(), ["b", "c"]);
+    assert_eq!(element(&document, div).id(), Some("box"));
+    document.remove_attribute(div, "id");
+    assert_eq!(element(&document, div).id(), None);
+}
@@ -0,0 +1,68 @@
+//! Checks that pages are decoded from the encoding their byte order mark, `Content-Type`
+//! header, or `<meta>` tag names.
+
+use build_a_browser_engine_in_rust::dom::Document;
+use build_a_browser_engine_in_rust::encoding::{self, Encoding};
+use build_a_browser_engine_in_rust::html;
+
+#[test]
+fn decodes_each_encoding() {
+    assert_eq!(Encoding::Windows1252.decode(b"caf\xE9 \x80 \x93x\x94 \x81"), "café € “x” \u{81}");
+    assert_eq!(Encoding::Utf16Le.decode(b"h\x00\xE9\x00=\xD8\x00\xDE"), "hé\u{1F600}");
+    assert_eq!(Encoding::Utf16Be.decode(b"\x00h\x00\xE9\x00"), "hé\u{FFFD}");
+    assert_eq!(Encoding::Utf8.decode(b"caf\xC3\xA9 \xFF"), "café \u{FFFD}");
+
+    assert_eq!(Encoding::for_label(" Latin1\t"), Some(Encoding::Windows1252));
+    assert_eq!(Encoding::for_label("UTF8"), Some(Encoding::Utf8));
+    assert_eq!(Encoding::for_label("utf-16"), Some(Encoding::Utf16Le));
+    assert_eq!(Encoding::for_label("shift_jis"), None);
+    assert_eq!(Encoding::Windows1252.name(), "windows-1252");
+}
+
+#[test]
+fn detects_the_encoding_in_order() {
+    let latin1: &[u8] = b"<p>caf\xE9</p>";
+    // Without anything to go on, bytes that aren't UTF-8 are Windows-1252.
+    assert_eq!(encoding::detect(latin1, None), Encoding::Windows1252);
+    assert_eq!(encoding::detect("<p>café</p>".as_bytes(), None), Encoding::Utf8);
+
+    // A `Content-Type` header beats a `<meta>` tag, and a byte order mark beats both.
+    let meta: &[u8] = b"<meta charset='iso-8859-1'><p>caf\xC3\xA9</p>";
+    assert_eq!(encoding::detect(meta, None), Encoding::Windows1252);
+    assert_eq!(encoding::detect(meta, Some("text/html; Charset=\"UTF-8\"")), Encoding::Utf8);
+    assert_eq!(encoding::detect(meta, Some("text/html")), Encoding::Windows1252);
+    let bom: Vec<u8> = [&b"\xEF\xBB\xBF"[..], meta].concat();
+    assert_eq!(encoding::detect(&bom, Some("text/html; charset=latin1")), Encoding::Utf8);
+
+    assert_eq!(encoding::from_content_type("text/html;charset=windows-1252"), Some(Encoding::Windows1252));
+    assert_eq!(encoding::from_content_type("text/html; format=charset; charset=utf-8"), Some(Encoding::Utf8));
+    assert_eq!(encoding::from_content_type("text/html"), None);
+}
+
+#[test]
+fn prescans_meta_tags() {
+    assert_eq!(encoding::prescan(b"<html><head><META Charset=latin1>"), Some(Encoding::Windows1252));
+    assert_eq!(encoding::prescan(b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=utf-8\">"), Some(Encoding::Utf8));
+    // Commented-out tags, and tags that don't name an encoding, don't count.
+    assert_eq!(encoding::prescan(b"<!-- <meta charset=latin1> --><meta name=x><meta charset=\"utf-8\">"), Some(Encoding::Utf8));
+    assert_eq!(encoding::prescan(b"<metadata charset=latin1>"), None);
+    // A page that declares UTF-16 in ASCII is really UTF-8.
+    assert_eq!(encoding::prescan(b"<meta charset=utf-16>"), Some(Encoding::Utf8));
+    // Only the start of the document is scanned.
+    let late: Vec<u8> = [&[b' '; encoding::PRESCAN_LENGTH][..], b"<meta charset=latin1>"].concat();
+    assert_eq!(encoding::prescan(&late), None);
+}
+
+#[test]
+fn parses_documents_from_bytes() {
+    let document: Document = html::parse_bytes(b"<meta charset=windows-1252><p>caf\xE9 \x93ok\x94</p>").unwrap();
+    assert_eq!(document.tree.text_content(document.body().unwrap()), "café “ok”");
+
+    let utf16: Vec<u8> = "\u{FEFF}<p>café</p>".encode_utf16().flat_map(u16::to_le_bytes).collect();
+    let document: Document = html::parse_bytes(&utf16).unwrap();
+    assert_eq!(document.tree.text_content(document.body().unwrap()), "café");
+
+    // The byte order mark isn't part of the text.
+    let (text, encoding) = encoding::decode_html(b"\xEF\xBB\xBFhi", None);
+    assert_eq!((text.as_ref(), encoding), ("hi", Encoding::Utf8));
+}
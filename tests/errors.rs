@@ -0,0 +1,69 @@
+//! Checks that every stage reports bad input as an `EngineError` saying what went wrong and
+//! where, and that no malformed page makes the engine panic.
+
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::dom::Document;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::error::EngineError;
+use build_a_browser_engine_in_rust::fonts::FontCollection;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::image::ImageCache;
+use build_a_browser_engine_in_rust::layout::{self, LayoutError};
+use build_a_browser_engine_in_rust::net::LoadError;
+use build_a_browser_engine_in_rust::style::{self, StyledNode};
+use std::error::Error;
+
+#[test]
+fn wraps_parse_errors_with_their_position() {
+    let error: EngineError = Page::load("<p>a</q>", "").err().unwrap();
+    assert!(matches!(error, EngineError::Html(ref err) if err.position == 4));
+    assert_eq!(error.to_string(), "HTML parse error: Expected \"</p>\" at byte 4");
+
+    let error: EngineError = Page::load("<p>a</p>", "p { color: #zz; }").err().unwrap();
+    assert!(matches!(error, EngineError::Css(ref err) if err.position == 12));
+    assert_eq!(error.to_string(), "CSS parse error: Expected two hexadecimal digits at byte 12");
+    // The wrapped error is the source, for error reporters that walk the chain.
+    assert_eq!(error.source().unwrap().to_string(), "Expected two hexadecimal digits at byte 12");
+
+    let error: EngineError = html::parse("<div").unwrap_err().into();
+    assert_eq!(error.to_string(), "HTML parse error: Unexpected end of input at byte 4");
+}
+
+#[test]
+fn reports_layout_and_load_errors() {
+    let document: Document = html::parse("<p>hidden</p>").unwrap();
+    let stylesheet: css::Stylesheet = css::parse("html { display: none; }").unwrap();
+    let style_root: StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+    let arena: layout::Bump = layout::Bump::new();
+    let error: LayoutError = layout::layout_tree(&style_root, Default::default(), &ImageCache::new(), &FontCollection::new(), &arena).err().unwrap();
+    assert_eq!(EngineError::from(error).to_string(), "layout error: the root element has display: none");
+    // A page with nothing to lay out just paints nothing.
+    let mut page: Page = Page::load("<p>hidden</p>", "html { display: none; }").unwrap();
+    assert!(page.layout().is_empty());
+
+    let error: EngineError = Page::load_url("ftp://example.com/").err().unwrap();
+    assert!(matches!(error, EngineError::Load(LoadError::UnsupportedScheme(ref scheme)) if scheme == "ftp"));
+    assert_eq!(Page::load_url("not a url").err().unwrap().to_string(), "load error: invalid URL \"not a url\"");
+    let error: EngineError = std::fs::read("/no/such/file").unwrap_err().into();
+    assert!(matches!(error, EngineError::Load(LoadError::Io(_))));
+}
+
+#[test]
+fn survives_malformed_input() {
+    let html_inputs: [&str; 12] = ["", "<", "</", "<div", "<1>", "<!-- x", "<a b='c>", "<p>a</q>", "</p>", "<<<>>>",
+                                   "<div><p>unclosed", "<p>\u{e9}\u{1F600}\0</p>"];
+    let css_inputs: [&str; 12] = ["", "a {", "{}", "@media", "#", "a b c", "} }", "p { color: #zz; }", "\u{e9} {",
+                                  "p { height: -1e999px; }", "p { width: 10%%; }", "@media screen { p {"];
+    for html in html_inputs {
+        for css in css_inputs {
+            // Either it loads and renders, or it says why it can't.
+            match Page::load(html, css) {
+                Ok(mut page) => {
+                    page.set_viewport(50, 50);
+                    page.paint();
+                }
+                Err(error) => assert!(!error.to_string().is_empty()),
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! Checks the order timers and animation frames run in on the event loop's clock, and
+//! (with the `js` feature) a page's scripts animating as its clock is moved.
+//!
+//! Run the page tests with `cargo test --features js --test event_loop`.
+
+use build_a_browser_engine_in_rust::event_loop::{EventLoop, Task, TaskId, FRAME_INTERVAL, MIN_NESTED_DELAY};
+
+/// Run every task due by `until`, returning each with the time it ran.
+fn run_until(event_loop: &mut EventLoop<&'static str>, until: f64) -> Vec<(f64, Task<&'static str>)> {
+    let mut tasks: Vec<(f64, Task<&'static str>)> = Vec::new();
+    while let Some(task) = event_loop.next_task(until) {
+        tasks.push((event_loop.now(), task));
+    }
+    tasks
+}
+
+#[test]
+fn runs_timers_and_frames_in_time_order() {
+    let mut event_loop: EventLoop<&str> = EventLoop::new();
+    event_loop.set_timer("late", 30.0, false);
+    event_loop.set_timer("first", 10.0, false);
+    event_loop.set_timer("second", 10.0, false);
+    event_loop.request_animation_frame("draw");
+    event_loop.request_animation_frame("draw again");
+    assert_eq!(event_loop.next_deadline(), Some(10.0));
+
+    assert_eq!(run_until(&mut event_loop, 100.0), [(10.0, Task::Timer("first")), (10.0, Task::Timer("second")),
+                                                   (FRAME_INTERVAL, Task::AnimationFrame(vec!["draw", "draw again"])),
+                                                   (30.0, Task::Timer("late"))]);
+    // With nothing due, the clock moves to the end.
+    assert_eq!(event_loop.now(), 100.0);
+    assert!(event_loop.is_idle());
+}
+
+#[test]
+fn repeats_and_cancels() {
+    let mut event_loop: EventLoop<&str> = EventLoop::new();
+    let tick: TaskId = event_loop.set_timer("tick", 0.0, true);
+    let canceled: TaskId = event_loop.set_timer("canceled", 1.0, false);
+    event_loop.clear_timer(canceled);
+    let frame: TaskId = event_loop.request_animation_frame("frame");
+    event_loop.cancel_animation_frame(frame);
+
+    // Repeating timers wait at least `MIN_NESTED_DELAY`.
+    let times: Vec<f64> = run_until(&mut event_loop, 10.0).into_iter().map(|(time, _): (f64, Task<&str>)| time).collect();
+    assert_eq!(times, [MIN_NESTED_DELAY, 2.0 * MIN_NESTED_DELAY]);
+    event_loop.clear_timer(tick);
+    assert_eq!(event_loop.next_deadline(), None);
+}
+
+#[test]
+fn clamps_deeply_nested_timers() {
+    // Each timer sets the next with no delay, like a script that keeps calling `setTimeout(f, 0)`.
+    let mut event_loop: EventLoop<&str> = EventLoop::new();
+    event_loop.set_timer("again", 0.0, false);
+    let mut times: Vec<f64> = Vec::new();
+    while times.len() < 7 {
+        event_loop.next_task(f64::INFINITY).unwrap();
+        times.push(event_loop.now());
+        event_loop.set_timer("again", 0.0, false);
+    }
+    assert_eq!(times, [0.0, 0.0, 0.0, 0.0, 0.0, 4.0, 8.0]);
+}
+
+#[test]
+fn frames_come_only_while_callbacks_wait() {
+    let mut event_loop: EventLoop<&str> = EventLoop::new();
+    event_loop.request_animation_frame("one");
+    assert_eq!(run_until(&mut event_loop, 20.0), [(FRAME_INTERVAL, Task::AnimationFrame(vec!["one"]))]);
+    // A frame requested between boundaries waits for the next one, after the last frame.
+    event_loop.request_animation_frame("two");
+    assert_eq!(event_loop.next_deadline(), Some(2.0 * FRAME_INTERVAL));
+    event_loop.next_task(100.0);
+    event_loop.request_animation_frame("three");
+    let (time, _) = run_until(&mut event_loop, 100.0).remove(0);
+    assert!((time - 50.0).abs() < 1e-9, "{}", time);
+}
+
+/// Scripts on a page, run as `advance_time` moves its clock.
+#[cfg(feature = "js")]
+mod page {
+    use build_a_browser_engine_in_rust::css::Color;
+    use build_a_browser_engine_in_rust::engine::Page;
+    use build_a_browser_engine_in_rust::event_loop::FRAME_INTERVAL;
+
+    const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+    const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+
+    /// A 40x10 page with an empty red box, and `script`.
+    fn load(script: &str) -> Page {
+        let mut page: Page = Page::load(&format!("<div id=\"box\"></div><script>{}</script>", script),
+                                        "head, script { display: none; } html, body, div { display: block; } body { margin: 0; } \
+                                         #box { height: 10px; width: 0px; background: #ff0000; }").unwrap();
+        page.set_viewport(40, 10);
+        page
+    }
+
+    fn pixel(page: &mut Page, x: usize) -> Color {
+        page.paint().pixels[x]
+    }
+
+    #[test]
+    fn runs_timers_as_the_clock_moves() {
+        let mut page: Page = load("setTimeout(() => document.getElementById('box').style.width = '20px', 100);");
+        assert_eq!(page.next_task_delay(), Some(100.0));
+        assert!(!page.advance_time(99.0));
+        assert_eq!(pixel(&mut page, 5), WHITE);
+        assert!(page.advance_time(1.0));
+        assert_eq!((pixel(&mut page, 5), pixel(&mut page, 25)), (RED, WHITE));
+        assert_eq!(page.next_task_delay(), None);
+    }
+
+    #[test]
+    fn plays_animations_frame_by_frame() {
+        let mut page: Page = load("\
+            var stamps = [];
+            function step(time) {
+                stamps.push(Math.round(time));
+                document.getElementById('box').style.width = (stamps.length * 10) + 'px';
+                if (stamps.length < 3) requestAnimationFrame(step);
+            }
+            requestAnimationFrame(step);");
+        assert!(page.advance_time(FRAME_INTERVAL));
+        assert_eq!((pixel(&mut page, 5), pixel(&mut page, 15)), (RED, WHITE));
+        page.advance_time(100.0);
+        assert_eq!((pixel(&mut page, 25), pixel(&mut page, 35)), (RED, WHITE));
+        assert_eq!(page.evaluate("stamps.join() + ' ' + performance.now()").unwrap().to_string(), "17,33,50 116.66666666666667");
+    }
+}
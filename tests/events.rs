@@ -0,0 +1,103 @@
+//! Checks that events run capturing listeners on the way down, every listener at the
+//! target, and bubbling listeners on the way back up, and that listeners can stop them.
+
+use build_a_browser_engine_in_rust::dom::{Document, NodeId};
+use build_a_browser_engine_in_rust::events::{self, Event, EventListeners, ListenerId, Phase};
+use build_a_browser_engine_in_rust::html;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type Log = Rc<RefCell<Vec<String>>>;
+
+/// A listener that logs `name` and the phase the event is in.
+fn logger(log: &Log, name: &'static str) -> Box<dyn FnMut(&mut Event)> {
+    let log: Log = log.clone();
+    Box::new(move |event: &mut Event| {
+        assert_eq!(event.event_type, "click");
+        log.borrow_mut().push(format!("{} {:?}", name, event.phase));
+    })
+}
+
+/// A page with `<div><p><em>Hi</em></p></div>`, and its three elements.
+fn page() -> (Document, NodeId, NodeId, NodeId) {
+    let document: Document = html::parse("<div><p><em>Hi</em></p></div>").unwrap();
+    let [div, p, em] = ["div", "p", "em"].map(|tag: &str| document.get_elements_by_tag_name(tag)[0]);
+    (document, div, p, em)
+}
+
+#[test]
+fn captures_targets_then_bubbles() {
+    let (document, div, p, em) = page();
+    let log: Log = Log::default();
+    let mut listeners: EventListeners = EventListeners::new();
+    listeners.add_event_listener(div, "click", false, logger(&log, "div"));
+    listeners.add_event_listener(div, "click", true, logger(&log, "div capture"));
+    listeners.add_event_listener(p, "click", false, logger(&log, "p"));
+    listeners.add_event_listener(em, "click", false, logger(&log, "em"));
+    listeners.add_event_listener(em, "click", true, logger(&log, "em capture"));
+    listeners.add_event_listener(em, "keydown", false, Box::new(|_: &mut Event| panic!("wrong event type")));
+    assert!(listeners.has_listener(em, "keydown"));
+    assert!(!listeners.has_listener(p, "keydown"));
+
+    let mut event: Event = Event::bubbling("click");
+    assert!(listeners.dispatch_event(&document.tree, em, &mut event));
+    assert_eq!(*log.borrow(), ["div capture Capturing", "em capture AtTarget", "em AtTarget", "p Bubbling", "div Bubbling"]);
+    assert_eq!((event.target, event.current_target, event.phase), (Some(em), None, Phase::None));
+
+    // An event that doesn't bubble stops at its target.
+    log.borrow_mut().clear();
+    assert!(listeners.dispatch_event(&document.tree, em, &mut Event::new("click")));
+    assert_eq!(*log.borrow(), ["div capture Capturing", "em capture AtTarget", "em AtTarget"]);
+}
+
+#[test]
+fn listeners_can_stop_and_cancel_events() {
+    let (document, div, p, em) = page();
+    let log: Log = Log::default();
+    let mut listeners: EventListeners = EventListeners::new();
+    listeners.add_event_listener(div, "click", false, logger(&log, "div"));
+    let stop: ListenerId = listeners.add_event_listener(p, "click", false, Box::new(|event: &mut Event| {
+        event.stop_propagation();
+        event.prevent_default();
+    }));
+    listeners.add_event_listener(p, "click", false, logger(&log, "p after stop"));
+
+    // `stop_propagation` lets the node's other listeners run, but no further nodes.
+    let mut event: Event = Event::bubbling("click");
+    assert!(!listeners.dispatch_event(&document.tree, em, &mut event));
+    assert!(event.default_prevented);
+    assert_eq!(*log.borrow(), ["p after stop Bubbling"]);
+
+    // Only cancelable events can be canceled.
+    let mut event: Event = Event::new("click");
+    event.bubbles = true;
+    assert!(listeners.dispatch_event(&document.tree, em, &mut event));
+
+    // `stop_immediate_propagation` stops the rest of the node's listeners too.
+    log.borrow_mut().clear();
+    listeners.remove_event_listener(stop);
+    listeners.remove_event_listener(stop);
+    let first: ListenerId = listeners.add_event_listener(em, "click", false, Box::new(|event: &mut Event| event.stop_immediate_propagation()));
+    listeners.add_event_listener(em, "click", false, logger(&log, "em"));
+    let mut event: Event = Event::bubbling("click");
+    assert!(listeners.dispatch_event(&document.tree, em, &mut event));
+    assert!(event.immediate_propagation_stopped());
+    assert!(log.borrow().is_empty());
+
+    listeners.remove_event_listener(first);
+    assert!(listeners.dispatch_event(&document.tree, em, &mut Event::bubbling("click")));
+    assert_eq!(*log.borrow(), ["em AtTarget", "p after stop Bubbling", "div Bubbling"]);
+}
+
+#[test]
+fn dispatches_along_any_path() {
+    // A path of anything `Copy`, such as the names of the nodes, target first.
+    let mut calls: Vec<String> = Vec::new();
+    let mut event: Event = Event::bubbling("custom");
+    let path: [&str; 3] = ["target", "parent", "document"];
+    assert!(events::dispatch_along(&path, &mut event, |node: &str, event: &mut Event, capture: bool| {
+        calls.push(format!("{} {:?} {}", node, event.phase, capture));
+    }));
+    assert_eq!(calls, ["document Capturing true", "parent Capturing true", "target AtTarget true", "target AtTarget false",
+                       "parent Bubbling false", "document Bubbling false"]);
+}
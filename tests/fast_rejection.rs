@@ -0,0 +1,53 @@
+//! Checks that the quick check run before full selector matching (see "Fast Rejection" in
+//! `style`) never rules out a selector that matches: `matched_rules`, which runs it, finds
+//! the same rules as `matches`, which doesn't.
+
+use build_a_browser_engine_in_rust::css::{self, Selector};
+use build_a_browser_engine_in_rust::dom::{self, NodeId, NodeType};
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::style::{self, ElementStates};
+
+const HTML: &str = "<html><body><div id=\"main\" class=\"card  wide\"><p class=\"note\">a</p><p class=\"Note big\">b</p>\
+    <P id=\"Shout\" class=\"LOUD\">c</P></div><span class=\"wide card note card\"></span><section id=\"main-2\"></section></body></html>";
+
+/// Selectors by tag, id, one or more classes, and nothing at all, some in a different case
+/// from the markup.
+const SELECTORS: [&str; 26] = [
+    "*", "div", "p", "P", "span", "section",
+    "#main", "#Main", "#Shout", "#shout", "#main-2",
+    ".card", ".wide", ".card.wide", ".wide.card", ".note", ".Note", ".Note.big", ".big.note", ".LOUD", ".loud",
+    "div.card", "div#main.card.wide", "p#Shout.LOUD", "span.note.card", "*#main.nope",
+];
+
+#[test]
+fn quick_check_never_rejects_a_matching_selector() {
+    let document: dom::Document = html::parse(HTML).unwrap();
+    let states: ElementStates = ElementStates::default();
+    let mut matches: usize = 0;
+    for text in SELECTORS {
+        let stylesheet: css::Stylesheet = css::parse(&format!("{} {{ color: #000000; }}", text)).unwrap();
+        let selector: &Selector = &stylesheet.rules[0].selectors[0];
+        for node in document.tree.descendants(document.root_element) {
+            let NodeType::Element(ref element) = document.tree[node].node_type else { continue };
+            let matched: bool = style::matches(element, node, selector, &states);
+            let found: bool = !style::matched_rules(element, node, &stylesheet, &states).is_empty();
+            assert_eq!(found, matched, "{} on <{}>", text, element.tag_name);
+            matches += usize::from(matched);
+        }
+    }
+    // The selectors match plenty, so agreeing isn't just both sides finding nothing.
+    assert!(matches >= 20, "{} matches", matches);
+
+    // Ids and classes are case-sensitive.
+    let count = |text: &str| -> usize {
+        let stylesheet: css::Stylesheet = css::parse(&format!("{} {{ color: #000000; }}", text)).unwrap();
+        document.tree.descendants(document.root_element)
+            .filter(|&node: &NodeId| match document.tree[node].node_type {
+                NodeType::Element(ref element) => !style::matched_rules(element, node, &stylesheet, &states).is_empty(),
+                _ => false,
+            })
+            .count()
+    };
+    assert_eq!((count(".note"), count(".Note"), count(".card.wide")), (2, 1, 2));
+    assert_eq!((count("#Main"), count("#Shout"), count("#shout")), (0, 1, 0));
+}
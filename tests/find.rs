@@ -0,0 +1,80 @@
+//! Checks finding text in a page: only rendered text is searched, case and runs of white
+//! space don't matter, and stepping through the matches wraps around and scrolls to them.
+
+use build_a_browser_engine_in_rust::css::Color;
+use build_a_browser_engine_in_rust::dom::{NodeId, NodeType};
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::find::Match;
+use build_a_browser_engine_in_rust::painting::DisplayCommand;
+
+const ORANGE: Color = Color { r: 255, g: 140, b: 0, a: 255 };
+const YELLOW: Color = Color { r: 255, g: 220, b: 0, a: 255 };
+
+fn load(html: &str) -> Page {
+    let mut page: Page = Page::load(html, "head, .hidden { display: none; } html, body, p { display: block; } \
+                                           body, p { margin: 0; } p { height: 100px; }").unwrap();
+    page.set_deterministic(true);
+    page.set_viewport(200, 150);
+    page
+}
+
+/// Search for `query`, returning the text of the node each match is in, and the part of
+/// it that matched.
+fn find(page: &mut Page, query: &str) -> Vec<(String, String)> {
+    let matches: Vec<Match> = page.find(query).to_vec();
+    matches.iter().map(|found: &Match| match page.document().tree[found.node].node_type {
+        NodeType::Text(ref text) => (text.clone(), text[found.range.clone()].to_string()),
+        _ => panic!("a match outside a text node"),
+    }).collect()
+}
+
+/// The colors of the highlight outlines drawn over the page.
+fn highlights(page: &mut Page) -> Vec<Color> {
+    page.layout().iter().filter_map(|command: &DisplayCommand| match *command {
+        DisplayCommand::SolidColor(color, _) if color == ORANGE || color == YELLOW => Some(color),
+        _ => None,
+    }).collect()
+}
+
+#[test]
+fn finds_rendered_text_ignoring_case_and_white_space() {
+    let mut page: Page = load("<p>Hello   world, <em>WORLD</em></p><p class=\"hidden\">world</p><p>big <b>world</b></p>");
+    assert_eq!(find(&mut page, "World"), [("Hello   world, ".to_string(), "world".to_string()),
+                                         ("WORLD".to_string(), "WORLD".to_string()),
+                                         ("world".to_string(), "world".to_string())]);
+    // White space in the query matches any run of it, but matches stay in one text node.
+    assert_eq!(find(&mut page, "hello world"), [("Hello   world, ".to_string(), "Hello   world".to_string())]);
+    assert!(find(&mut page, "big world").is_empty());
+    assert!(page.find("   ").is_empty());
+}
+
+#[test]
+fn steps_through_matches_and_scrolls_to_them() {
+    let mut page: Page = load("<p>one cat</p><p>two</p><p>three cat</p><p>four cat</p>");
+    assert_eq!(page.find("cat").len(), 3);
+    let texts: Vec<NodeId> = page.find_matches().iter().map(|found: &Match| found.node).collect();
+    assert_eq!(page.current_match().map(|found: &Match| found.node), Some(texts[0]));
+    assert_eq!(page.scroll_position(), (0.0, 0.0));
+
+    // The selected match is centered when it comes into view.
+    assert_eq!(page.find_next().map(|found: &Match| found.node), Some(texts[1]));
+    let rect_y: f32 = page.current_match().unwrap().rect.y;
+    assert!(page.scroll_position().1 > 0.0 && page.scroll_position().1 <= rect_y);
+    assert_eq!(page.find_next().map(|found: &Match| found.node), Some(texts[2]));
+    assert_eq!(page.find_next().map(|found: &Match| found.node), Some(texts[0]));
+    assert_eq!(page.find_previous().map(|found: &Match| found.node), Some(texts[2]));
+}
+
+#[test]
+fn highlights_matches_until_cleared() {
+    let mut page: Page = load("<p>a cat</p><p>a cat</p>");
+    page.find("cat");
+    // The current match is drawn last, in its own color.
+    assert_eq!(highlights(&mut page), [YELLOW; 4].into_iter().chain([ORANGE; 4]).collect::<Vec<Color>>());
+    page.clear_find();
+    assert!(highlights(&mut page).is_empty());
+    assert_eq!(page.current_match(), None);
+
+    assert!(page.find("dog").is_empty());
+    assert_eq!(page.find_next(), None);
+}
@@ -0,0 +1,210 @@
+//! Checks font matching against real font files (when the system has DejaVu), and text
+//! layout with the stand-in font, whose advances are easy to work out by hand.
+
+mod common;
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::fonts::{self, Face, FontCollection, FontDescriptor, FontStyle, Glyph, Synthesis, TextRun};
+use build_a_browser_engine_in_rust::layout::{LayoutBox, Rect, TextFragment};
+use build_a_browser_engine_in_rust::painting::Canvas;
+use common::{fragments, texts};
+use std::path::Path;
+use std::sync::Arc;
+
+const DEJAVU: &str = "/usr/share/fonts/truetype/dejavu";
+
+/// The text fragments of a page, laid out 100px wide.
+fn lay_out(page: &mut Page) -> Vec<TextFragment> {
+    page.set_deterministic(true);
+    page.set_viewport(100, 100);
+    page.with_layout_tree(|root: &LayoutBox| {
+        let mut out: Vec<TextFragment> = Vec::new();
+        fragments(root, &mut out);
+        out
+    })
+}
+
+#[test]
+fn stand_in_text_wraps_and_keeps_preformatted_white_space() {
+    // Each character of the stand-in font is 0.5em (8px) wide, and lines are 1.2em apart.
+    let css: &str = "html, body, p, pre { display: block; } body { margin: 0px; }";
+    let lines: Vec<TextFragment> = lay_out(&mut Page::load("<html><body><p>aaaa   bbbb cccc dddd</p></body></html>", css).unwrap());
+    assert_eq!(texts(&lines), ["aaaa bbbb", "cccc dddd"]);
+    assert_eq!(lines[1].rect.x, 0.0);
+    assert!((lines[1].rect.y - lines[0].rect.y - 19.2).abs() < 0.001);
+
+    let lines: Vec<TextFragment> = lay_out(&mut Page::load("<html><body><pre>a  b\nc</pre></body></html>", css).unwrap());
+    assert_eq!(texts(&lines), ["a  b", "c"]);
+
+    let css: String = format!("{} p {{ white-space: nowrap; text-align: right; }}", css);
+    let lines: Vec<TextFragment> = lay_out(&mut Page::load("<html><body><p>aaaa bbbb cccc dddd</p></body></html>", &css).unwrap());
+    assert_eq!(texts(&lines), ["aaaa bbbb cccc dddd"]);
+    assert_eq!(lines[0].rect.x, 100.0 - 19.0 * 8.0);
+}
+
+/// The DejaVu fonts, if the system has them.
+fn dejavu() -> Option<FontCollection> {
+    if !Path::new(DEJAVU).is_dir() {
+        eprintln!("skipping: no fonts in {}", DEJAVU);
+        return None;
+    }
+    let mut collection: FontCollection = FontCollection::new();
+    assert!(collection.add_directory(Path::new(DEJAVU)) > 0);
+    Some(collection)
+}
+
+#[test]
+fn matches_faces_by_family_weight_and_style() {
+    let Some(mut collection) = dejavu() else { return };
+    collection.set_generic_family("sans-serif", &["DejaVu Sans"]);
+    collection.set_fallback(&["DejaVu Sans"]);
+
+    let face = |families: &[&str], weight: u16, style: FontStyle| -> Arc<Face> {
+        let font: FontDescriptor = FontDescriptor {
+            families: families.iter().map(|&family: &&str| Atom::from(family)).collect(),
+            weight,
+            style,
+        };
+        collection.face_for(&font).unwrap()
+    };
+    let regular: Arc<Face> = face(&["sans-serif"], fonts::NORMAL_WEIGHT, FontStyle::Normal);
+    assert_eq!((regular.family.as_str(), regular.weight, regular.style), ("DejaVu Sans", 400, FontStyle::Normal));
+    let bold: Arc<Face> = face(&["No Such Font", "DejaVu Sans"], 800, FontStyle::Italic);
+    // DejaVu Sans's slanted faces are obliques, but they're marked as italic.
+    assert_eq!((bold.family.as_str(), bold.weight, bold.style), ("DejaVu Sans", 700, FontStyle::Italic));
+    let fallback: Arc<Face> = face(&["No Such Font"], fonts::NORMAL_WEIGHT, FontStyle::Normal);
+    assert_eq!(fallback.family, "DejaVu Sans");
+
+    // Real advances differ from the stand-in font's, and the page uses them once it's told to.
+    let collection: Arc<FontCollection> = Arc::new(collection);
+    let sans: FontDescriptor = FontDescriptor { families: vec![Atom::from("sans-serif")], ..FontDescriptor::default() };
+    let width: f32 = collection.shape("iiii", &sans, 16.0)[0].width;
+    assert!(width > 0.0 && width < 4.0 * 8.0);
+    let mut page: Page = Page::load("<html><p>iiii</p></html>", "html, p { display: block; } p { font-family: sans-serif; }").unwrap();
+    page.set_fonts(collection);
+    let lines: Vec<TextFragment> = lay_out(&mut page);
+    assert_eq!(texts(&lines), ["iiii"]);
+    assert_eq!(lines[0].rect.width, width);
+}
+
+#[test]
+fn falls_back_to_other_faces_for_missing_characters() {
+    let Some(collection) = dejavu() else { return };
+    let mono: FontDescriptor = FontDescriptor { families: vec![Atom::from("DejaVu Sans Mono")], ..FontDescriptor::default() };
+    let runs = |text: &str| -> Vec<(String, String)> {
+        collection.shape(text, &mono, 16.0).iter()
+            .map(|run: &TextRun| (run.text.clone(), run.face.as_ref().unwrap().family.clone()))
+            .collect()
+    };
+    let run = |text: &str, family: &str| (text.to_string(), family.to_string());
+
+    // DejaVu Sans Mono has no Braille, but DejaVu Sans does; spaces stay with the text
+    // before them.
+    assert_eq!(runs("ab \u{2801}\u{2803} c"), [
+        run("ab ", "DejaVu Sans Mono"),
+        run("\u{2801}\u{2803} ", "DejaVu Sans"),
+        run("c", "DejaVu Sans Mono"),
+    ]);
+    // A combining mark stays with its letter.
+    assert_eq!(runs("e\u{0301}"), [run("e\u{0301}", "DejaVu Sans Mono")]);
+    // Characters no face has are left in the primary face, which draws them as boxes.
+    assert_eq!(runs("\u{65E5}\u{672C}"), [run("\u{65E5}\u{672C}", "DejaVu Sans Mono")]);
+    // Runs are also cut where the script changes, since scripts are shaped differently.
+    assert_eq!(runs("ab \u{5D0}\u{5D1}"), [run("ab ", "DejaVu Sans Mono"), run("\u{5D0}\u{5D1}", "DejaVu Sans")]);
+}
+
+#[test]
+fn synthesizes_bold_and_oblique_when_the_family_lacks_them() {
+    let Some(dejavu) = dejavu() else { return };
+    let mut regular_only: FontCollection = FontCollection::new();
+    regular_only.add_file(&Path::new(DEJAVU).join("DejaVuSans.ttf")).unwrap();
+    let font = |weight: u16, style: FontStyle| FontDescriptor { families: vec![Atom::from("DejaVu Sans")], weight, style };
+    let shape = |collection: &FontCollection, font: &FontDescriptor| -> TextRun { collection.shape("iiii", font, 16.0).remove(0) };
+
+    // With only a regular face, bold is faked, and each glyph is a px wider at 16px.
+    let regular: TextRun = shape(&regular_only, &font(fonts::NORMAL_WEIGHT, FontStyle::Normal));
+    let bold: TextRun = shape(&regular_only, &font(fonts::BOLD_WEIGHT, FontStyle::Normal));
+    assert_eq!(regular.synthesis, Synthesis::default());
+    assert_eq!(bold.synthesis, Synthesis { bold: true, oblique: false });
+    assert_eq!(bold.width, regular.width + 4.0);
+    let italic: TextRun = shape(&regular_only, &font(fonts::NORMAL_WEIGHT, FontStyle::Italic));
+    assert_eq!(italic.synthesis, Synthesis { bold: false, oblique: true });
+    assert_eq!(italic.width, regular.width);
+    // The real faces need no faking.
+    assert_eq!(shape(&dejavu, &font(fonts::BOLD_WEIGHT, FontStyle::Italic)).synthesis, Synthesis::default());
+
+    // Emboldened glyphs cover more, and slanted ones lean right: their tops move right
+    // and their bottoms stay put.
+    let face: &Face = regular.face.as_deref().unwrap();
+    let id: u16 = regular_only.shape("l", &font(fonts::NORMAL_WEIGHT, FontStyle::Normal), 32.0)[0].glyphs[0].id;
+    let visible: Rect = Rect { x: 0.0, y: 0.0, width: 100.0, height: 100.0 };
+    let draw = |synthesis: Synthesis| -> Vec<(i32, i32, f32)> {
+        let mut pixels: Vec<(i32, i32, f32)> = Vec::new();
+        face.draw_glyph(id, 32.0, (10.0, 40.0), synthesis, visible, |x: i32, y: i32, coverage: f32| pixels.push((x, y, coverage)));
+        pixels
+    };
+    let coverage = |pixels: &[(i32, i32, f32)]| -> f32 { pixels.iter().map(|&(_, _, c): &(i32, i32, f32)| c).sum() };
+    let leftmost = |pixels: &[(i32, i32, f32)], row: i32| -> i32 {
+        pixels.iter().filter(|&&(_, y, c): &&(i32, i32, f32)| y == row && c > 0.5).map(|&(x, _, _): &(i32, i32, f32)| x).min().unwrap()
+    };
+    let plain: Vec<(i32, i32, f32)> = draw(Synthesis::default());
+    let bold: Vec<(i32, i32, f32)> = draw(Synthesis { bold: true, oblique: false });
+    let oblique: Vec<(i32, i32, f32)> = draw(Synthesis { bold: false, oblique: true });
+    assert!(coverage(&bold) > coverage(&plain) * 1.2);
+    assert!((coverage(&oblique) - coverage(&plain)).abs() < coverage(&plain) * 0.05);
+    assert!(leftmost(&oblique, 38) - leftmost(&plain, 38) <= 1);
+    assert!(leftmost(&oblique, 18) - leftmost(&plain, 18) >= 4);
+
+    // Glyphs outside the visible area aren't drawn at all, and nor are huge ones.
+    let mut plotted: usize = 0;
+    face.draw_glyph(id, 32.0, (150.0, 40.0), Synthesis::default(), visible, |_: i32, _: i32, _: f32| plotted += 1);
+    face.draw_glyph(id, 32.0, (-20.0, 40.0), Synthesis { bold: true, oblique: true }, visible, |_: i32, _: i32, _: f32| plotted += 1);
+    face.draw_glyph(id, 100_000.0, (10.0, 40.0), Synthesis::default(), visible, |_: i32, _: i32, _: f32| plotted += 1);
+    assert_eq!(plotted, 0);
+}
+
+#[test]
+fn paints_huge_text_without_rasterizing_it() {
+    // At this size each glyph is tens of thousands of px across, far too big to draw.
+    let Some(collection) = dejavu() else { return };
+    let mut page: Page = Page::load("<html><p>Huge</p></html>", "html, p { display: block; } p { font-size: 100000px; }").unwrap();
+    page.set_fonts(Arc::new(collection));
+    page.set_viewport(100, 100);
+    let canvas: &Canvas = page.paint();
+    assert_eq!((canvas.width, canvas.height), (100, 100));
+}
+
+#[test]
+fn shapes_ligatures_joining_letters_and_marks() {
+    let Some(collection) = dejavu() else { return };
+    let sans: FontDescriptor = FontDescriptor { families: vec![Atom::from("DejaVu Sans")], ..FontDescriptor::default() };
+    let shape = |text: &str| -> TextRun {
+        let mut runs: Vec<TextRun> = collection.shape(text, &sans, 16.0);
+        assert_eq!(runs.len(), 1, "{:?} should shape as one run", text);
+        runs.remove(0)
+    };
+    let ids = |run: &TextRun| -> Vec<u16> { run.glyphs.iter().map(|glyph: &Glyph| glyph.id).collect() };
+    let clusters = |run: &TextRun| -> Vec<usize> { run.glyphs.iter().map(|glyph: &Glyph| glyph.cluster).collect() };
+
+    // "fi" is one glyph, and a line can't break inside it.
+    let office: TextRun = shape("office");
+    assert_eq!(clusters(&office), [0, 1, 4, 5]);
+    assert_eq!(office.x_at(2), office.x_at(4));
+
+    // An Arabic letter has different glyphs at the start and end of a word than on its
+    // own, and the run is drawn right to left.
+    let beh: TextRun = shape("\u{628}");
+    let beh_beh: TextRun = shape("\u{628}\u{628}");
+    assert!(beh_beh.rtl);
+    assert_eq!(beh_beh.glyphs.len(), 2);
+    assert!(!ids(&beh_beh).contains(&beh.glyphs[0].id));
+    let first: &Glyph = &beh_beh.glyphs[0];
+    assert_eq!(beh_beh.glyph_origin(first).0, beh_beh.width - first.advance);
+
+    // A combining accent doesn't advance, and is moved onto its letter.
+    let accented: TextRun = shape("e\u{301}");
+    assert_eq!(clusters(&accented), [0, 1]);
+    assert_eq!(accented.glyphs[1].advance, 0.0);
+    assert!(accented.glyph_origin(&accented.glyphs[1]).0 < accented.glyphs[0].advance);
+}
@@ -0,0 +1,103 @@
+//! Checks that form controls are sized from their attributes, and that clicking and typing
+//! change their state in the DOM.
+
+use build_a_browser_engine_in_rust::dom::{Document, NodeId};
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::forms::{self, Control};
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::layout::{LayoutBox, Rect, Replaced};
+
+const CSS: &str = "head { display: none; } html, body, div { display: block; } body { margin: 0; }";
+
+/// Each form control's kind and content box, in document order.
+fn controls(root: &LayoutBox, out: &mut Vec<(Control, Rect)>) {
+    if let Some(control) = root.replaced.as_ref().and_then(|replaced: &Replaced| replaced.control) {
+        out.push((control, root.dimensions.content));
+    }
+    for child in root.children.iter() {
+        controls(child, out);
+    }
+}
+
+#[test]
+fn sizes_controls_from_their_attributes() {
+    let page: Page = Page::load("<div><input size=\"10\"><input type=\"checkbox\" checked><input type=\"hidden\" value=\"x\"></div>\
+                                 <div><button> Go   now </button><input type=\"submit\"></div>\
+                                 <div><textarea cols=\"5\" rows=\"3\"></textarea><select><option>One</option><option>Three</option></select></div>", CSS).unwrap();
+    let found: Vec<(Control, (f32, f32))> = page.with_layout_tree(|root: &LayoutBox| {
+        let mut out: Vec<(Control, Rect)> = Vec::new();
+        controls(root, &mut out);
+        out.into_iter().map(|(control, rect): (Control, Rect)| (control, (rect.width, rect.height))).collect()
+    });
+    // 7px per character, 3px of padding, and a 16px line.
+    assert_eq!(found, [(Control::TextInput { size: 10 }, (76.0, 22.0)),
+                       (Control::CheckBox { checked: true }, (13.0, 13.0)),
+                       (Control::Button { label_len: 6 }, (54.0, 22.0)),
+                       (Control::Button { label_len: 6 }, (54.0, 22.0)),
+                       (Control::TextArea { cols: 5, rows: 3 }, (41.0, 54.0)),
+                       (Control::Select { label_len: 5 }, (63.0, 22.0))]);
+}
+
+#[test]
+fn ticks_one_radio_button_per_group() {
+    let mut document: Document = html::parse("<input type=\"radio\" name=\"a\" id=\"1\" checked><input type=\"radio\" name=\"a\" id=\"2\">\
+                                              <input type=\"radio\" name=\"b\" id=\"3\" checked><input type=\"checkbox\" id=\"4\">").unwrap();
+    let ids: Vec<NodeId> = ["1", "2", "3", "4"].iter().map(|id: &&str| document.get_element_by_id(id).unwrap()).collect();
+    let ticked = |document: &Document| -> Vec<bool> { ids.iter().map(|&id: &NodeId| forms::is_checked(&document.tree, id)).collect() };
+
+    assert!(forms::toggle_checked(&mut document, ids[1]));
+    assert_eq!(ticked(&document), [false, true, true, false]);
+    // A ticked radio button stays ticked; a checkbox flips.
+    assert!(!forms::toggle_checked(&mut document, ids[1]));
+    assert!(forms::toggle_checked(&mut document, ids[3]));
+    assert!(forms::toggle_checked(&mut document, ids[3]));
+    assert_eq!(ticked(&document), [false, true, true, false]);
+    forms::set_checked(&mut document, ids[0], true);
+    assert_eq!(ticked(&document), [true, false, true, false]);
+}
+
+#[test]
+fn keeps_values_in_the_dom() {
+    let mut document: Document = html::parse("<input id=\"i\" value=\"old\"><textarea id=\"t\">old <b>text</b></textarea>").unwrap();
+    let (input, textarea) = (document.get_element_by_id("i").unwrap(), document.get_element_by_id("t").unwrap());
+    assert_eq!((forms::value(&document, input), forms::value(&document, textarea)), ("old".to_string(), "old <b>text</b>".to_string()));
+    forms::set_value(&mut document, input, "new");
+    forms::set_value(&mut document, textarea, "one\ntwo");
+    assert_eq!(document.tree.to_html(input), "<input id=\"i\" value=\"new\">");
+    assert_eq!(forms::value(&document, textarea), "one\ntwo");
+}
+
+#[test]
+fn clicking_and_typing_change_controls() {
+    let mut page: Page = Page::load("<div><input id=\"name\"></div><div><label><input type=\"checkbox\" id=\"ok\"> OK</label></div>\
+                                     <textarea id=\"notes\"></textarea><input id=\"off\" disabled>", CSS).unwrap();
+    let name: NodeId = page.document().get_element_by_id("name").unwrap();
+    let ok: NodeId = page.document().get_element_by_id("ok").unwrap();
+    let notes: NodeId = page.document().get_element_by_id("notes").unwrap();
+    let off: NodeId = page.document().get_element_by_id("off").unwrap();
+
+    // The text input is the first thing on the page.
+    assert!(page.click_at(5.0, 5.0));
+    assert_eq!(page.focused_node(), Some(name));
+    assert!(page.type_text("ab\ncd"));
+    assert!(page.delete_backward());
+    assert_eq!(forms::value(page.document(), name), "abc");
+
+    assert!(page.click_node(ok));
+    assert!(forms::is_checked(&page.document().tree, ok));
+    assert_eq!(page.focused_node(), Some(ok));
+    assert!(!page.type_text("x"));
+
+    // Only a `<textarea>` keeps newlines.
+    page.click_node(notes);
+    page.type_text("a\nb");
+    assert_eq!(forms::value(page.document(), notes), "a\nb");
+
+    // Disabled controls can't be focused, and clicking nowhere blurs.
+    page.click_node(off);
+    assert_eq!(page.focused_node(), None);
+    page.set_focused_node(Some(name));
+    assert!(page.click_at(700.0, 500.0));
+    assert_eq!(page.focused_node(), None);
+    assert!(!page.delete_backward());
+}
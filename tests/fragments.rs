@@ -0,0 +1,61 @@
+//! Checks document fragments: parsing snippets into them, inserting them (which moves
+//! their children in), and stamping out the contents of `<template>` elements.
+
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::dom::{Document, NodeId, NodeType, Tree};
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::style::{self, StyledNode};
+
+#[test]
+fn parses_snippets_into_fragments() {
+    let (tree, fragment) = html::parse_fragment("<li>a</li>text<li class=\"x\">b</li>").unwrap();
+    assert_eq!(tree[fragment].node_type, NodeType::DocumentFragment);
+    assert_eq!(tree.children(fragment).count(), 3);
+    assert_eq!(tree.text_content(fragment), "atextb");
+    // There's no `<html>` around a fragment, and no place for a doctype in one.
+    let (tree, fragment) = html::parse_fragment("<p>x</p>").unwrap();
+    assert_eq!(tree.to_html(tree.children(fragment).next().unwrap()), "<p>x</p>");
+    assert!(html::parse_fragment("<!DOCTYPE html><p>x</p>").is_err());
+}
+
+#[test]
+fn inserting_a_fragment_moves_its_children() {
+    let mut document: Document = html::parse("<ul><li>first</li><li id=\"last\">last</li></ul>").unwrap();
+    document.build_indexes();
+    let ul: NodeId = document.get_elements_by_tag_name("ul")[0];
+    let last: NodeId = document.get_element_by_id("last").unwrap();
+
+    let (snippet, fragment) = html::parse_fragment("<li class=\"new\">a</li><li class=\"new\">b</li>").unwrap();
+    let fragment: NodeId = document.tree.import(&snippet, fragment);
+    document.insert_before(ul, fragment, Some(last));
+    assert_eq!(document.tree.to_html(ul), "<ul><li>first</li><li class=\"new\">a</li><li class=\"new\">b</li><li id=\"last\">last</li></ul>");
+    // The fragment is left empty, and never becomes part of the document.
+    assert_eq!(document.tree.children(fragment).count(), 0);
+    assert!(!document.is_connected(fragment));
+    assert_eq!(document.get_elements_by_class_name("new").len(), 2);
+}
+
+#[test]
+fn stamps_out_template_contents() {
+    let mut document: Document = html::parse("<template id=\"row\"><tr><td>cell</td></tr></template><table></table>").unwrap();
+    let template: NodeId = document.get_element_by_id("row").unwrap();
+    let table: NodeId = document.get_elements_by_tag_name("table")[0];
+    for _ in 0..2 {
+        let contents: NodeId = document.tree.template_contents(template);
+        document.append_child(table, contents);
+    }
+    assert_eq!(document.tree.to_html(table), "<table><tr><td>cell</td></tr><tr><td>cell</td></tr></table>");
+    // The template keeps its own copy.
+    assert_eq!(document.tree.children(template).count(), 1);
+}
+
+#[test]
+fn fragments_style_their_children() {
+    let mut tree: Tree = Tree::new();
+    let (snippet, fragment) = html::parse_fragment("<p>a</p><p>b</p>").unwrap();
+    let fragment: NodeId = tree.import(&snippet, fragment);
+    let stylesheet: css::Stylesheet = css::parse("p { display: block; }").unwrap();
+    let root: StyledNode = style::style_tree(&tree, fragment, &stylesheet);
+    assert_eq!(root.children.len(), 2);
+    assert_eq!(root.inner_text(), "a\n\nb");
+}
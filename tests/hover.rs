@@ -0,0 +1,72 @@
+//! Checks that moving the mouse over elements matches `:hover` on them and their
+//! ancestors, and repaints (or lays out again) only when a rule depends on it.
+
+use build_a_browser_engine_in_rust::css::Color;
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::Page;
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const GREEN: Color = Color { r: 0, g: 255, b: 0, a: 255 };
+
+/// A 40x20 page with a red `#a` 10px tall above an `#b` holding a 10px `#c`.
+fn load(css: &str) -> Page {
+    let mut page: Page = Page::load("<div id=\"a\"></div><div id=\"b\"><div id=\"c\"></div></div>",
+                                    &format!("head {{ display: none; }} html, body, div {{ display: block; }} body {{ margin: 0; }} \
+                                              #a, #c {{ height: 10px; }} #a {{ background: #ff0000; }} {}", css)).unwrap();
+    page.set_viewport(40, 20);
+    page
+}
+
+fn id(page: &Page, id: &str) -> NodeId {
+    page.document().get_element_by_id(id).unwrap()
+}
+
+/// The color at (x, y) in the page's canvas.
+fn pixel(page: &mut Page, x: usize, y: usize) -> Color {
+    let width: usize = page.paint().width;
+    page.paint().pixels[y * width + x]
+}
+
+#[test]
+fn hovers_the_element_under_the_mouse() {
+    let mut page: Page = load("#a:hover { background: #00ff00; }");
+    assert_eq!(pixel(&mut page, 5, 5), RED);
+
+    assert!(page.hover_at(5.0, 5.0));
+    assert_eq!(page.hovered_node(), Some(id(&page, "a")));
+    assert_eq!(pixel(&mut page, 5, 5), GREEN);
+    // Moving within it changes nothing.
+    assert!(!page.hover_at(6.0, 6.0));
+
+    // Hovering the other element needs a repaint, since `#a` loses its `:hover`.
+    assert!(page.hover_at(5.0, 15.0));
+    assert_eq!(page.hovered_node(), Some(id(&page, "c")));
+    assert_eq!(pixel(&mut page, 5, 5), RED);
+    assert!(page.set_hovered_node(Some(id(&page, "a"))));
+    assert!(page.set_hovered_node(None));
+    assert_eq!(page.hovered_node(), None);
+}
+
+#[test]
+fn ancestors_of_the_hovered_element_match_hover_too() {
+    let mut page: Page = load("#b:hover { background: #00ff00; }");
+    assert!(page.hover_at(5.0, 15.0));
+    assert_eq!(pixel(&mut page, 5, 15), GREEN);
+    assert_eq!(page.hovered_node(), Some(id(&page, "c")));
+    assert!(page.hover_at(5.0, 5.0));
+    assert_ne!(pixel(&mut page, 5, 15), GREEN);
+}
+
+#[test]
+fn only_invalidates_for_hover_rules() {
+    // Without any `:hover` rules, hovering never needs painting.
+    let mut page: Page = load("");
+    assert!(!page.hover_at(5.0, 15.0));
+    assert_eq!(page.hovered_node(), Some(id(&page, "c")));
+
+    // A rule that changes size lays the page out again.
+    let mut page: Page = load("#a:hover { height: 15px; }");
+    assert_eq!(page.element_rect(id(&page, "c")).unwrap().y, 10.0);
+    assert!(page.hover_at(5.0, 5.0));
+    assert_eq!(page.element_rect(id(&page, "c")).unwrap().y, 15.0);
+}
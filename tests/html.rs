@@ -0,0 +1,462 @@
+//! Checks the HTML parser on the parts of real pages that the round-trip tests don't
+//! generate.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::dom::{AttributeMap, Document, NodeId, NodeType, QuirksMode, SourceSpan};
+use build_a_browser_engine_in_rust::html::{self, Token};
+
+/// The tag name of the document's root element.
+fn root_tag(document: &Document) -> &str {
+    match document.tree[document.root_element].node_type {
+        NodeType::Element(ref element) => element.tag_name.as_str(),
+        _ => panic!("the root isn't an element"),
+    }
+}
+
+#[test]
+fn parses_doctypes_and_picks_the_rendering_mode() {
+    let document: Document = html::parse("<!DOCTYPE html>\n<html><body>hi</body></html>").unwrap();
+    assert_eq!((document.doctype.as_deref(), document.quirks_mode), (Some("html"), QuirksMode::NoQuirks));
+    assert_eq!(root_tag(&document), "html");
+
+    let mode = |source: &str| -> (Option<String>, QuirksMode) {
+        let document: Document = html::parse(source).unwrap();
+        (document.doctype, document.quirks_mode)
+    };
+    let html: Option<String> = Some("html".to_string());
+    assert_eq!(mode("  <!doctype HTML ><p>x</p>"), (html.clone(), QuirksMode::NoQuirks));
+    assert_eq!(mode("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01//EN\" \"http://www.w3.org/TR/html4/strict.dtd\"><p>x</p>"),
+               (html.clone(), QuirksMode::NoQuirks));
+    assert_eq!(mode("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\" \"http://www.w3.org/TR/html4/loose.dtd\"><p>x</p>"),
+               (html.clone(), QuirksMode::LimitedQuirks));
+    assert_eq!(mode("<!DOCTYPE HTML PUBLIC \"-//W3C//DTD HTML 4.01 Transitional//EN\"><p>x</p>"), (html.clone(), QuirksMode::Quirks));
+    assert_eq!(mode("<!DOCTYPE html PUBLIC '-//W3C//DTD XHTML 1.0 Transitional//EN' 'http://www.w3.org/TR/xhtml1/DTD/xhtml1-transitional.dtd'><p>x</p>"),
+               (html.clone(), QuirksMode::LimitedQuirks));
+    assert_eq!(mode("<!DOCTYPE HTML PUBLIC \"-//IETF//DTD HTML 2.0//EN\"><p>x</p>"), (html, QuirksMode::Quirks));
+    assert_eq!(mode("<!DOCTYPE svg><p>x</p>"), (Some("svg".to_string()), QuirksMode::Quirks));
+    assert_eq!(mode("<p>x</p>"), (None, QuirksMode::Quirks));
+
+    // A doctype that never ends is an error.
+    assert!(html::parse("<!DOCTYPE html").is_err());
+}
+
+/// The first node in the document's `<body>`.
+fn first_in_body(document: &Document) -> NodeId {
+    document.tree.children(document.body().unwrap()).next().unwrap()
+}
+
+/// The tag names of `node`'s descendants, with `/` after each element's children.
+fn outline(document: &Document, node: NodeId, out: &mut String) {
+    for child in document.tree.children(node) {
+        if let NodeType::Element(ref element) = document.tree[child].node_type {
+            out.push_str(&element.tag_name);
+            out.push(' ');
+            outline(document, child, out);
+            out.push_str("/ ");
+        }
+    }
+}
+
+#[test]
+fn void_and_self_closing_elements_have_no_contents() {
+    let shape = |source: &str| -> String {
+        let document: Document = html::parse(source).unwrap();
+        let mut out: String = String::new();
+        outline(&document, document.root_element, &mut out);
+        out
+    };
+    assert_eq!(shape("<html><head><meta charset=\"utf-8\"></head><body><p>a<br>b<img src=\"x\"></p></body></html>"),
+               "head meta / / body p br / img / / / ");
+    assert_eq!(shape("<html><p><input type=\"text\" /><BR/><x-icon name=\"close\"/></p></html>"), "head / body p input / BR / x-icon / / / ");
+    assert_eq!(shape("<html><p><img></IMG>a<br></br></p></html>"), "head / body p img / br / / / ");
+
+    let document: Document = html::parse("<html><img src=\"a.png\" alt=\"a/b\"/></html>").unwrap();
+    let img: NodeId = first_in_body(&document);
+    match document.tree[img].node_type {
+        NodeType::Element(ref element) => assert_eq!(element.attributes.get("alt").map(String::as_str), Some("a/b")),
+        _ => panic!("not an element"),
+    }
+
+    // A slash that isn't followed by `>` is still an error.
+    assert!(html::parse("<html><br / ></html>").is_err());
+}
+
+#[test]
+fn decodes_character_references() {
+    let document: Document = html::parse("<p title=\"&quot;R&amp;D&quot; &#x27;24\">Caf&eacute; &lt;&#233;&#xE9;&gt; &copy2024 &bogus; &amp &#x1F642; &#128; &#0; &#xD800;</p>").unwrap();
+    let p: NodeId = first_in_body(&document);
+    assert_eq!(document.tree.text_content(p), "Café <éé> ©2024 &bogus; & 🙂 € \u{FFFD} \u{FFFD}");
+    match document.tree[p].node_type {
+        NodeType::Element(ref element) => assert_eq!(element.attributes.get("title").map(String::as_str), Some("\"R&D\" '24")),
+        _ => panic!("not an element"),
+    }
+
+    // Legacy names without their `;` aren't decoded in attributes before a letter, digit,
+    // or `=`.
+    assert_eq!(html::decode_entities("?a=1&copy=2&amp;b&lt3 &lt 3", true), "?a=1&copy=2&b&lt3 < 3");
+    assert_eq!(html::decode_entities("&notin; &notit &noti", false), "∉ ¬it ¬i");
+    assert!(matches!(html::decode_entities("no references", false), std::borrow::Cow::Borrowed(_)));
+}
+
+#[test]
+fn accepts_every_way_of_writing_attributes() {
+    let document: Document = html::parse("<input type=checkbox checked title = 'say \"hi\"' value=\"it's\" data-x=a&amp;b/ name=café disabled type=radio>").unwrap();
+    let input: NodeId = first_in_body(&document);
+    let attribute = |name: &str| -> Option<String> {
+        match document.tree[input].node_type {
+            NodeType::Element(ref element) => element.attributes.get(name).cloned(),
+            _ => None,
+        }
+    };
+    assert_eq!(attribute("type").as_deref(), Some("checkbox")); // the first one counts
+    assert_eq!(attribute("checked").as_deref(), Some(""));
+    assert_eq!(attribute("disabled").as_deref(), Some(""));
+    assert_eq!(attribute("title").as_deref(), Some("say \"hi\""));
+    assert_eq!(attribute("value").as_deref(), Some("it's"));
+    assert_eq!(attribute("data-x").as_deref(), Some("a&b/"));
+    assert_eq!(attribute("name").as_deref(), Some("café"));
+
+    // A boolean attribute right before the end of a self-closing tag.
+    let document: Document = html::parse("<p><input disabled/>x</p>").unwrap();
+    assert_eq!(document.tree.children(first_in_body(&document)).count(), 2);
+
+    assert!(html::parse("<p title='unclosed>x</p>").is_err());
+    assert!(html::parse("<p \"oops\">x</p>").is_err());
+}
+
+#[test]
+fn keeps_attributes_in_document_order() {
+    let document: Document = html::parse("<p title=x id=a class=note data-n=1 id=c title=y>x</p>").unwrap();
+    let element = match document.tree[first_in_body(&document)].node_type {
+        NodeType::Element(ref element) => element,
+        _ => panic!("not an element"),
+    };
+    // Repeated attributes are dropped: the first one counts.
+    let attributes: Vec<(&str, &str)> = element.attributes.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+    assert_eq!(attributes, [("title", "x"), ("id", "a"), ("class", "note"), ("data-n", "1")]);
+    assert_eq!(element.to_string(), "<p title=\"x\" id=\"a\" class=\"note\" data-n=\"1\">");
+
+    // Changing a value keeps its place; new attributes go at the end.
+    let mut attributes: AttributeMap = element.attributes.clone();
+    assert_eq!(attributes.insert(Atom::from("id"), "z".to_string()), Some("a".to_string()));
+    attributes.insert(Atom::from("lang"), "en".to_string());
+    assert_eq!(attributes.remove("class"), Some("note".to_string()));
+    let names: Vec<&str> = attributes.keys().map(|name: &Atom| name.as_str()).collect();
+    assert_eq!(names, ["title", "id", "data-n", "lang"]);
+    assert_eq!(attributes.get("id").map(String::as_str), Some("z"));
+
+    // Order doesn't matter for equality.
+    let reversed: AttributeMap = element.attributes.clone().into_iter().rev().collect();
+    assert_eq!(reversed, element.attributes);
+}
+
+#[test]
+fn best_effort_parsing_keeps_what_came_before_an_error() {
+    let source: &str = "<div><p>One</p><p>Two <b id=\">Three</p></div>";
+    assert!(html::parse(source).is_err());
+    let (document, errors) = html::parse_with_errors(source);
+    let mut out: String = String::new();
+    outline(&document, document.root_element, &mut out);
+    assert_eq!(out, "head / body div p / p / / / ");
+    assert_eq!(document.tree.text_content(document.root_element), "OneTwo ");
+    assert_eq!(errors, vec![html::ParseError { message: "Expected \"\\\"\"".to_string(), position: source.len() }]);
+
+    // Unclosed elements are closed at the end of the input, with one error for the first.
+    let (document, errors) = html::parse_with_errors("<html><body><p>Hi");
+    let mut out: String = String::new();
+    outline(&document, document.root_element, &mut out);
+    assert_eq!(out, "head / body p / / ");
+    assert_eq!(errors.len(), 1);
+
+    // Well-formed documents have no errors.
+    let (document, errors) = html::parse_with_errors("<!DOCTYPE html><p>Hi</p>");
+    assert_eq!((document.quirks_mode, errors), (QuirksMode::NoQuirks, Vec::new()));
+}
+
+/// The markup of what's in `node`, with attributes left out.
+fn markup(document: &Document, node: NodeId, out: &mut String) {
+    for child in document.tree.children(node) {
+        match document.tree[child].node_type {
+            NodeType::Element(ref element) => {
+                out.push_str(&format!("<{}>", element.tag_name));
+                markup(document, child, out);
+                out.push_str(&format!("</{}>", element.tag_name));
+            }
+            NodeType::Text(ref text) => out.push_str(text),
+            NodeType::DocumentFragment => {}
+        }
+    }
+}
+
+#[test]
+fn recovers_from_misnested_tags() {
+    let fix = |source: &str| -> (String, Vec<String>) {
+        assert!(html::parse(source).is_err(), "{:?}", source);
+        let (document, errors) = html::parse_with_errors(source);
+        let mut out: String = String::new();
+        markup(&document, document.body().unwrap(), &mut out);
+        (out, errors.into_iter().map(|err: html::ParseError| err.message).collect())
+    };
+    // A closing tag closes what's inside it, and formatting elements carry on after.
+    assert_eq!(fix("<p><b>bold</p>plain</b>"), ("<p><b>bold</b></p><b>plain</b>".to_string(), vec!["Expected \"</b>\"".to_string()]));
+    assert_eq!(fix("<div><span>text</div>after"), ("<div><span>text</span></div>after".to_string(), vec!["Expected \"</span>\"".to_string()]));
+    assert_eq!(fix("<ul><li><i>one</li><li>two</li></ul>").0, "<ul><li><i>one</i></li><li><i>two</i></li></ul>");
+    // A formatting element's closing tag moves a block out of it.
+    assert_eq!(fix("<b><p>bold</b>plain</p>").0, "<b></b><p><b>bold</b>plain</p>");
+    assert_eq!(fix("<div><a href=x><div>link</a>text</div></div>").0, "<div><a></a><div><a>link</a>text</div></div>");
+    // Closing tags for elements that aren't open are ignored.
+    assert_eq!(fix("<div>a</span>b</div>").0, "<div>ab</div>");
+    assert_eq!(fix("<p><b>x</p></b>y").0, "<p><b>x</b></p>y");
+    // Even with nothing open: the rest of the document is still read.
+    assert_eq!(fix("<div>a</div></div><p>b</p>"), ("<div>a</div><p>b</p>".to_string(), vec!["Unexpected \"</div>\"".to_string()]));
+    // Every tag fixed up is an error of its own, and so is the one still open at the end.
+    assert_eq!(fix("<P>x</p>").1.len(), 2);
+    // A missing closing tag is one error at the end of the input.
+    let (out, errors) = fix("<div><p>one<div>two</div>");
+    assert_eq!((out.as_str(), errors.len()), ("<div><p>one<div>two</div></p></div>", 1));
+}
+
+#[test]
+fn puts_in_missing_html_head_and_body_elements() {
+    let shape = |source: &str| -> String {
+        let document: Document = html::parse(source).unwrap();
+        let mut out: String = root_tag(&document).to_string() + " ";
+        outline(&document, document.root_element, &mut out);
+        out
+    };
+    assert_eq!(shape("<h1>Hi</h1>"), "html head / body h1 / / ");
+    assert_eq!(shape("<!DOCTYPE html>\n<title>Hi</title> <meta charset=\"utf-8\"><h1>Hi</h1><p>x</p>"),
+               "html head title / meta / / body h1 / p / / ");
+    assert_eq!(shape("<html lang=\"en\"><style></style><body><p>x</p></body></html>"), "html head style / / body p / / ");
+    assert_eq!(shape("<html><head><title>Hi</title></head>Hello</html>"), "html head title / / body / ");
+    assert_eq!(shape("<body><p>x</p></body>"), "html head / body p / / ");
+    assert_eq!(shape("Just text"), "html head / body / ");
+    // What comes after `</html>` (or before `<html>`) goes in its body.
+    assert_eq!(shape("<html><body>x</body></html><p>after</p>"), "html head / body p / / ");
+    assert_eq!(shape("<p>before</p><html><head></head><body><h1>x</h1></body></html>"), "html head / body p / h1 / / ");
+    let document: Document = html::parse("<html><body><h1>x</h1></body></html><p>after</p>").unwrap();
+    let mut out: String = String::new();
+    markup(&document, document.root_element, &mut out);
+    assert_eq!(out, "<head></head><body><h1>x</h1><p>after</p></body>");
+
+    // The elements that were written out are kept, attributes and all.
+    let document: Document = html::parse("<html lang=\"en\"><p>x</p></html>").unwrap();
+    match document.tree[document.root_element].node_type {
+        NodeType::Element(ref element) => assert_eq!(element.attr("lang"), Some("en")),
+        _ => panic!("not an element"),
+    }
+    assert_eq!(document.tree.text_content(document.body().unwrap()), "x");
+}
+
+#[test]
+fn takes_script_and_style_contents_verbatim() {
+    let source: &str = "<html><head><title>Q&amp;A</title><style> a > b { color: red; } </style></head>\
+        <script>if (a < b && c) { x = \"</p><b>\"; }</SCRIPT ><textarea>&lt;b&gt; <i>is</i> bold</textarea><script></script></html>";
+    let document: Document = html::parse(source).unwrap();
+    let texts: Vec<String> = document.tree.descendants(document.root_element)
+        .filter(|&node: &NodeId| matches!(document.tree[node].node_type, NodeType::Text(_)))
+        .map(|node: NodeId| document.tree.text_content(node))
+        .collect();
+    assert_eq!(texts, ["Q&A", " a > b { color: red; } ", "if (a < b && c) { x = \"</p><b>\"; }", "<b> <i>is</i> bold"]);
+    let mut out: String = String::new();
+    outline(&document, document.root_element, &mut out);
+    assert_eq!(out, "head title / style / / body script / textarea / script / / ");
+
+    // A script that never ends is an error.
+    assert!(html::parse("<html><script>let a = 1;</html>").is_err());
+}
+
+#[test]
+fn splits_source_into_tokens() {
+    let tokens: Vec<Token> = html::tokenize("<!DOCTYPE html>\n<p class=x>Caf&eacute; <!-- <b> --><br/></p></P >\
+        <style>p > b {}</style><script></script>").collect::<Result<_, _>>().unwrap();
+    let attributes: AttributeMap = [(Atom::from("class"), "x".to_string())].into_iter().collect();
+    assert_eq!(tokens, [
+        Token::Doctype { name: Some("html".to_string()), public_id: None, system_id: None },
+        Token::StartTag { name: Atom::from("p"), attributes, self_closing: false },
+        Token::Text("Café ".into()),
+        Token::Comment(" <b> "),
+        Token::StartTag { name: Atom::from("br"), attributes: AttributeMap::default(), self_closing: true },
+        Token::EndTag { name: Atom::from("p") },
+        // The tokenizer doesn't check that closing tags match.
+        Token::EndTag { name: Atom::from("P") },
+        Token::StartTag { name: Atom::from("style"), attributes: AttributeMap::default(), self_closing: false },
+        Token::Text("p > b {}".into()),
+        Token::EndTag { name: Atom::from("style") },
+        Token::StartTag { name: Atom::from("script"), attributes: AttributeMap::default(), self_closing: false },
+        Token::EndTag { name: Atom::from("script") },
+        Token::Eof,
+    ]);
+
+    // An error ends the tokens.
+    let tokens: Vec<Result<Token, html::ParseError>> = html::tokenize("<p>x</p><!-- unclosed").collect();
+    assert_eq!(tokens.len(), 4);
+    assert!(tokens[3].is_err());
+}
+
+#[test]
+fn skips_comments() {
+    let document: Document = html::parse("<!-- header --><ul><!-- <li>old</li> --><li>new</li></ul>").unwrap();
+    let mut out: String = String::new();
+    outline(&document, document.root_element, &mut out);
+    assert_eq!(out, "head / body ul li / / / ");
+    assert_eq!(document.tree.text_content(document.root_element), "new");
+}
+
+/// Parse `source` fed in chunks, split at each of `splits` (byte offsets, in order).
+fn parse_in_chunks(source: &str, splits: &[usize]) -> Result<Document, html::ParseError> {
+    let mut parser: html::Parser = html::Parser::new();
+    let mut start: usize = 0;
+    for &split in splits.iter().chain([source.len()].iter()) {
+        parser.feed(&source[start..split])?;
+        start = split;
+    }
+    parser.finish()
+}
+
+#[test]
+fn parses_input_fed_in_chunks() {
+    let sources: [&str; 7] = [
+        "<!DOCTYPE html PUBLIC \"-//W3C//DTD HTML 4.01//EN\">\n<html><head><title>A &amp; B</title><style>p > b {}</style></head>\
+         <body><p class=\"x\" title='&lt;y&gt;' disabled>Caf&eacute; <b>bold</b><br>text<img src=a.png></img></p>\
+         <script>if (a </b) {}</script ><x-icon/><input disabled/></body></html>",
+        "  <!doctype html><p>x</p>",
+        "<p>no doctype</p>",
+        "<div><p>unclosed",
+        "<p title='unclosed>x</p>",
+        "<div></span></div>",
+        "<!-- a <p> --><p>x<!-- b --></p><!-- unclosed",
+    ];
+    for source in sources {
+        let whole: Result<Document, html::ParseError> = html::parse(source);
+        let boundaries: Vec<usize> = (1..source.len()).filter(|&i: &usize| source.is_char_boundary(i)).collect();
+        // Split once at every point, and into single characters.
+        let mut splits: Vec<Vec<usize>> = boundaries.iter().map(|&i: &usize| vec![i]).collect();
+        splits.push(boundaries);
+        for splits in splits {
+            let chunked: Result<Document, html::ParseError> = parse_in_chunks(source, &splits);
+            match (&whole, &chunked) {
+                (Ok(whole), Ok(chunked)) => {
+                    assert!(chunked.tree.tree_eq(chunked.root_element, &whole.tree, whole.root_element), "{:?} split at {:?}", source, splits);
+                    assert_eq!((&chunked.doctype, chunked.quirks_mode), (&whole.doctype, whole.quirks_mode));
+                }
+                (Err(whole), Err(chunked)) => assert_eq!(whole, chunked, "{:?} split at {:?}", source, splits),
+                _ => panic!("{:?} split at {:?}: {:?} in one piece, {:?} in chunks", source, splits, whole.is_ok(), chunked.is_ok()),
+            }
+        }
+    }
+
+    // The document grows as its input arrives.
+    let mut parser: html::Parser = html::Parser::new();
+    parser.feed("<ul><li>one</li><li>tw").unwrap();
+    parser.feed("o</li></ul>").unwrap();
+    let document: Document = parser.finish().unwrap();
+    assert_eq!(document.tree.text_content(document.root_element), "onetwo");
+
+    // Errors are reported at their position in the whole input.
+    let mut parser: html::Parser = html::Parser::new();
+    parser.feed("<p>ok</p>").unwrap();
+    assert_eq!(parser.feed("<p \"oops\">").unwrap_err().position, 12);
+    assert!(parser.finish().is_err());
+}
+
+/// Where a node's source starts and ends, as (byte offset, line, column).
+type Span = [(usize, usize, usize); 2];
+
+/// Each node under the body of `document`, with its span.
+fn spans(document: &Document) -> Vec<(String, Option<Span>)> {
+    let body: NodeId = document.body().unwrap();
+    document.tree.descendants(body).skip(1).map(|node: NodeId| {
+        let name: String = match document.tree[node].node_type {
+            NodeType::Element(ref element) => element.tag_name.to_string(),
+            NodeType::Text(ref text) => format!("{:?}", text),
+            NodeType::DocumentFragment => String::new(),
+        };
+        let span: Option<Span> = document.tree[node].source.map(|span: SourceSpan| {
+            [(span.start.offset, span.start.line, span.start.column), (span.end.offset, span.end.line, span.end.column)]
+        });
+        (name, span)
+    }).collect()
+}
+
+#[test]
+fn records_source_positions() {
+    let source: &str = "<p>Hello\n<em>world</em></p>\n  <img src=a.png><div>café <br/>x</div>";
+    let mut parser: html::Parser = html::Parser::new().with_source_positions();
+    parser.feed(source).unwrap();
+    let document: Document = parser.finish().unwrap();
+    let expected = [
+        ("p", [(0, 1, 1), (27, 2, 19)]),
+        ("\"Hello\\n\"", [(3, 1, 4), (9, 2, 1)]),
+        ("em", [(9, 2, 1), (23, 2, 15)]),
+        ("\"world\"", [(13, 2, 5), (18, 2, 10)]),
+        // A void element spans its tag, and the white space before it isn't part of it.
+        ("img", [(30, 3, 3), (45, 3, 18)]),
+        ("div", [(45, 3, 18), (68, 3, 40)]),
+        // Columns count characters, not bytes.
+        ("\"café \"", [(50, 3, 23), (56, 3, 28)]),
+        ("br", [(56, 3, 28), (61, 3, 33)]),
+        ("\"x\"", [(61, 3, 33), (62, 3, 34)]),
+    ];
+    let expected: Vec<(String, Option<Span>)> = expected.iter()
+        .map(|&(name, span): &(&str, Span)| (name.to_string(), Some(span)))
+        .collect();
+    assert_eq!(spans(&document), expected);
+    // The implicit elements weren't in the source.
+    assert_eq!(document.tree[document.root_element].source, None);
+
+    // The spans are the same however the input arrives.
+    let mut parser: html::Parser = html::Parser::new().with_source_positions();
+    for chunk in source.split_inclusive(['<', '\n', 'é']) {
+        parser.feed(chunk).unwrap();
+    }
+    assert_eq!(spans(&parser.finish().unwrap()), expected);
+
+    // They're only recorded when asked for.
+    assert!(spans(&html::parse(source).unwrap()).iter().all(|(_, span)| span.is_none()));
+}
+
+#[test]
+fn trims_preserves_or_collapses_whitespace() {
+    let source: &str = "<html>\n<head>\n  <title>A  title</title>\n</head>\n<body>\n  <h1>Hello,\n    world</h1>\n  \
+        <p>One <b>two</b> <i>three</i></p>\n  <pre>  kept\n  as is</pre>\n</body>\n</html>\n";
+    let parse = |source: &str, whitespace: html::Whitespace| -> String {
+        let mut parser: html::Parser = html::Parser::new().with_whitespace(whitespace);
+        parser.feed(source).unwrap();
+        let document: Document = parser.finish().unwrap();
+        let mut out: String = String::new();
+        markup(&document, document.root_element, &mut out);
+        out
+    };
+
+    // By default the white space before each token is skipped, even where it shows.
+    assert_eq!(parse(source, html::Whitespace::Trim), "<head><title>A  title</title></head>\
+        <body><h1>Hello,\n    world</h1><p>One <b>two</b><i>three</i></p><pre>kept\n  as is</pre></body>");
+    assert_eq!(parse(source, html::Whitespace::Preserve), "\n<head>\n  <title>A  title</title>\n</head>\n\
+        <body>\n  <h1>Hello,\n    world</h1>\n  <p>One <b>two</b> <i>three</i></p>\n  <pre>  kept\n  as is</pre>\n</body>\n");
+    assert_eq!(parse(source, html::Whitespace::Collapse), "<head><title>A title</title></head>\
+        <body><h1>Hello, world</h1><p>One <b>two</b> <i>three</i></p><pre>  kept\n  as is</pre></body>");
+    // Next to inline elements, white space is kept, as one space.
+    assert_eq!(parse("<p>\n<b>a</b>\n\n<i>b</i>\n</p>", html::Whitespace::Collapse), "<head></head><body><p> <b>a</b> <i>b</i> </p></body>");
+}
+
+#[test]
+fn serializes_nodes_as_markup() {
+    let source: &str = "<!DOCTYPE html><html><head><title>A &amp; B</title><style>p > b { color: red; }</style></head>\
+        <body><p title='a \"b\" &lt;c&gt;'>1 &lt; 2 &amp;&nbsp;<br>3<img src=x.png></p><textarea>&lt;/textarea&gt;</textarea></body></html>";
+    let document: Document = html::parse(source).unwrap();
+    let html: String = document.to_html();
+    assert_eq!(html, "<!DOCTYPE html><html><head><title>A &amp; B</title><style>p > b { color: red; }</style></head>\
+        <body><p title=\"a &quot;b&quot; &lt;c&gt;\">1 &lt; 2 &amp;&nbsp;<br>3<img src=\"x.png\"></p>\
+        <textarea>&lt;/textarea&gt;</textarea></body></html>");
+    assert!(html::parse(&html).unwrap().tree.tree_eq(document.root_element, &document.tree, document.root_element));
+
+    // The inner markup leaves the node itself out.
+    let p: NodeId = document.get_elements_by_tag_name("p")[0];
+    assert_eq!(html::serialize_children(&document.tree, p), "1 &lt; 2 &amp;&nbsp;<br>3<img src=\"x.png\">");
+    assert_eq!(document.tree.to_html(p), html::serialize(&document.tree, p));
+
+    // A fragment is written as its children.
+    let (tree, fragment) = html::parse_fragment("<li>a</li><li>b &gt; c</li>").unwrap();
+    assert_eq!(tree.to_html(fragment), "<li>a</li><li>b &gt; c</li>");
+}
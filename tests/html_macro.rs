@@ -0,0 +1,39 @@
+//! Checks that the `html!` macro builds the same trees the parser does.
+
+use build_a_browser_engine_in_rust::dom::{Document, NodeId};
+use build_a_browser_engine_in_rust::html;
+
+/// Assert that the tree `built` has is the one the parser makes of `source`, an element
+/// in a page's body.
+fn assert_same(built: Document, source: &str) {
+    let mut parser: html::Parser = html::Parser::new().with_whitespace(html::Whitespace::Preserve);
+    parser.feed(source).unwrap();
+    let parsed: Document = parser.finish().unwrap();
+    let element: NodeId = parsed.tree.children(parsed.body().unwrap()).next().unwrap();
+    assert!(built.tree.tree_eq(built.root_element, &parsed.tree, element), "built {}, parsed {}", built.to_html(), parsed.to_html());
+}
+
+#[test]
+fn builds_elements_attributes_and_text() {
+    let count: usize = 3;
+    let document: Document = html! {
+        div(class = "note", "data-count" = count, id = "n") {
+            p { "Hello " em { "world" } "!" }
+            br
+            img(src = "a.png")
+            span {}
+        }
+    };
+    assert_eq!(document.to_html(), "<div class=\"note\" data-count=\"3\" id=\"n\"><p>Hello <em>world</em>!</p><br><img src=\"a.png\"><span></span></div>");
+    assert_same(document, "<div class=\"note\" data-count=\"3\" id=\"n\"><p>Hello <em>world</em>!</p><br><img src=\"a.png\"><span></span></div>");
+}
+
+#[test]
+fn wraps_several_top_level_nodes_in_html() {
+    let document: Document = html! { head { title { "T" } } body { "text" } };
+    assert_eq!(document.to_html(), "<html><head><title>T</title></head><body>text</body></html>");
+    assert_eq!(document.tree.children(document.body().unwrap()).count(), 1);
+
+    let text: Document = html! { "just text" };
+    assert_eq!(text.tree.text_content(text.root_element), "just text");
+}
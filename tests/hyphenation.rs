@@ -0,0 +1,88 @@
+//! Checks the hyphenation algorithm on Liang's example patterns, and hyphenated and
+//! justified lines laid out with the stand-in font (every character 8px wide).
+
+mod common;
+
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::fonts::FontCollection;
+use build_a_browser_engine_in_rust::hyphenation::Hyphenator;
+use build_a_browser_engine_in_rust::layout::{LayoutBox, TextFragment};
+use common::{fragments, texts};
+use std::sync::Arc;
+
+/// The patterns from Liang's thesis that hyphenate "hyphenation".
+const PATTERNS: &str = r"
+    % From Liang, Word Hy-phen-a-tion by Com-put-er (1983).
+    \patterns{
+        hy3ph he2n hena4 hen5at 1na n2at 1tio 2io o2n
+    }
+    \hyphenation{ ta-ble }
+";
+
+#[test]
+fn hyphenates_words_with_patterns_and_exceptions() {
+    let hyphenator: Hyphenator = Hyphenator::parse(PATTERNS);
+    assert_eq!(hyphenator.hyphenate("hyphenation"), [2, 6]);
+    assert_eq!(hyphenator.hyphenate("Hyphenation"), [2, 6]);
+    assert_eq!(hyphenator.hyphenate("table"), [2]);
+    // Too short to break, with two letters kept before a break and three after.
+    assert_eq!(hyphenator.hyphenate("hyph"), Vec::<usize>::new());
+    assert_eq!(hyphenator.hyphenate_text("(hyphenation, or table)"), [3, 7, 19]);
+
+    let mut hyphenator: Hyphenator = hyphenator;
+    hyphenator.set_min_lengths(3, 3);
+    assert_eq!(hyphenator.hyphenate("hyphenation"), [6]);
+}
+
+/// The text fragments of `html`, laid out 100px wide with the stand-in font and an
+/// English dictionary.
+fn lay_out(html: &str, css: &str) -> Vec<TextFragment> {
+    let mut fonts: FontCollection = FontCollection::new();
+    fonts.set_hyphenator("en", Arc::new(Hyphenator::parse(PATTERNS)));
+    let css: String = format!("html, body, p {{ display: block; }} body {{ margin: 0px; }} {}", css);
+    let mut page: Page = Page::load(html, &css).unwrap();
+    page.set_deterministic(true);
+    page.set_fonts(Arc::new(fonts));
+    page.set_viewport(100, 100);
+    page.with_layout_tree(|root: &LayoutBox| {
+        let mut out: Vec<TextFragment> = Vec::new();
+        fragments(root, &mut out);
+        out
+    })
+}
+
+#[test]
+fn breaks_lines_inside_hyphenated_words() {
+    // "hy-phen-" (64px with its hyphen) fits in 70px, but "hyphenation" (88px) doesn't.
+    let css: &str = "p { width: 70px; hyphens: auto; }";
+    let lines: Vec<TextFragment> = lay_out("<html lang=\"en-US\"><p>hyphenation</p></html>", css);
+    assert_eq!(texts(&lines), ["hyphen", "-", "ation"]);
+    assert_eq!(lines[1].rect.x, 48.0);
+    assert!((lines[2].rect.y - lines[0].rect.y - 19.2).abs() < 0.001);
+
+    // Only with `hyphens: auto`, and only in a language with a dictionary.
+    let lines: Vec<TextFragment> = lay_out("<html lang=\"en\"><p>hyphenation</p></html>", "p { width: 70px; }");
+    assert_eq!(texts(&lines), ["hyphenation"]);
+    let lines: Vec<TextFragment> = lay_out("<html lang=\"fr\"><p>hyphenation</p></html>", css);
+    assert_eq!(texts(&lines), ["hyphenation"]);
+
+    // Soft hyphens work without a dictionary, and take up no space unless a line breaks at
+    // one.
+    let lines: Vec<TextFragment> = lay_out("<html><p>ab\u{AD}cdefgh\u{AD}ijkl</p></html>", "p { width: 90px; }");
+    assert_eq!(texts(&lines), ["ab\u{AD}cdefgh\u{AD}", "-", "ijkl"]);
+    assert_eq!(lines[0].rect.width, 64.0);
+    let lines: Vec<TextFragment> = lay_out("<html><p>ab\u{AD}cdefgh\u{AD}ijkl</p></html>", "p { width: 90px; hyphens: none; }");
+    assert_eq!(texts(&lines), ["ab\u{AD}cdefgh\u{AD}ijkl"]);
+}
+
+#[test]
+fn justified_lines_fill_the_block() {
+    // "aaaa bb cc" is 80px, so each of its two spaces grows by 10px. The last line isn't
+    // stretched.
+    let lines: Vec<TextFragment> = lay_out("<html><p>aaaa bb cc dddd</p></html>", "p { text-align: justify; }");
+    assert_eq!(texts(&lines), ["aaaa bb cc", "dddd"]);
+    assert_eq!(lines[0].rect.width, 100.0);
+    assert_eq!(lines[0].run.x_at(5), 50.0);
+    assert_eq!(lines[0].run.x_at(8), 84.0);
+    assert_eq!((lines[1].rect.x, lines[1].rect.width), (0.0, 32.0));
+}
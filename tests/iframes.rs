@@ -0,0 +1,87 @@
+//! Checks that an `<iframe>` loads its document into a page of its own, painted into the
+//! frame's box at the frame's size.
+
+use build_a_browser_engine_in_rust::css::{self, Color};
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::{Page, MAX_FRAME_DEPTH};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const GREEN: Color = Color { r: 0, g: 128, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+
+const CSS: &str = "head { display: none; } html, body, iframe { display: block; } body { margin: 0; }";
+
+/// Save `html` in the temporary directory, returning its path.
+fn save(name: &str, html: &str) -> PathBuf {
+    let path: PathBuf = std::env::temp_dir().join(format!("iframes-{}-{}.html", std::process::id(), name));
+    fs::write(&path, html).unwrap();
+    path
+}
+
+/// A document that fills its frame with `color`, using only its own styles.
+fn filled(color: &str) -> String {
+    format!("<html><head><style>head {{ display: none; }} html, body, div {{ display: block; }} body {{ margin: 0; }} \
+             div {{ height: 200px; background: {}; }}</style></head><body><div></div></body></html>", color)
+}
+
+#[test]
+fn paints_the_frame_document_into_its_box() {
+    let src: PathBuf = save("green", &filled("#008000"));
+    let mut page: Page = Page::load(&format!("<iframe id=\"f\" src=\"{}\" width=\"40\" height=\"30\"></iframe>", src.display()), CSS).unwrap();
+    page.set_viewport(100, 100);
+    let frame: NodeId = page.document().get_element_by_id("f").unwrap();
+    assert!(page.wait_for_images(Duration::from_secs(10)));
+    assert!(page.frame(frame).is_some());
+
+    assert_eq!(page.paint().pixels[5 * 100 + 5], GREEN);
+    assert_eq!(page.paint().pixels[5 * 100 + 50], WHITE);
+    assert_eq!(page.paint().pixels[40 * 100 + 5], WHITE);
+    // The frame's page is laid out at the size of the frame.
+    assert_eq!(page.frame(frame).unwrap().viewport(), (40, 30));
+}
+
+#[test]
+fn repaints_after_changes_to_the_frame() {
+    let src: PathBuf = save("change", &filled("#008000"));
+    let mut page: Page = Page::load(&format!("<iframe id=\"f\" src=\"{}\"></iframe>", src.display()), CSS).unwrap();
+    let frame: NodeId = page.document().get_element_by_id("f").unwrap();
+    assert!(page.wait_for_images(Duration::from_secs(10)));
+    assert_eq!(page.paint().pixels[0], GREEN);
+
+    let stylesheet: css::Stylesheet = css::parse("head { display: none; } html, body, div { display: block; } body { margin: 0; } div { height: 200px; background: #0000ff; }").unwrap();
+    page.frame_mut(frame).unwrap().set_stylesheet(stylesheet);
+    assert_eq!(page.paint().pixels[0], BLUE);
+    // The default size is 300x150.
+    assert_eq!(page.frame(frame).unwrap().viewport(), (300, 150));
+}
+
+#[test]
+fn stops_nesting_at_the_maximum_depth() {
+    // The document frames itself.
+    let path: PathBuf = std::env::temp_dir().join(format!("iframes-{}-self.html", std::process::id()));
+    fs::write(&path, format!("<iframe src=\"{}\"></iframe>", path.display())).unwrap();
+    let mut page: Page = Page::load(&fs::read_to_string(&path).unwrap(), CSS).unwrap();
+    assert!(page.wait_for_images(Duration::from_secs(10)));
+
+    let mut depth: usize = 0;
+    let mut current: &Page = &page;
+    while let Some(frame) = current.frame(current.document().get_elements_by_tag_name("iframe")[0]) {
+        current = frame;
+        depth += 1;
+    }
+    assert_eq!(depth, MAX_FRAME_DEPTH);
+}
+
+#[test]
+fn leaves_frames_empty_when_their_document_is_missing() {
+    let mut page: Page = Page::load("<iframe id=\"f\" src=\"/no/such/frame.html\"></iframe><iframe id=\"g\"></iframe>", CSS).unwrap();
+    assert!(page.wait_for_images(Duration::from_secs(10)));
+    for id in ["f", "g"] {
+        let frame: NodeId = page.document().get_element_by_id(id).unwrap();
+        assert!(page.frame(frame).is_none());
+    }
+    assert_eq!(page.paint().pixels[0], WHITE);
+}
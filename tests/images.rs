@@ -0,0 +1,118 @@
+//! Checks that images load in the background (or on the spot, when asked), and that
+//! their boxes hold placeholders until they arrive.
+
+use build_a_browser_engine_in_rust::css::{self, Color};
+use build_a_browser_engine_in_rust::dom::{self, NodeId};
+use build_a_browser_engine_in_rust::fonts::FontCollection;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::image::{Image, ImageCache, ImageState};
+use build_a_browser_engine_in_rust::layout::{self, Invalidation, LayoutBox};
+use build_a_browser_engine_in_rust::loader::ImageLoader;
+use build_a_browser_engine_in_rust::painting::Canvas;
+use build_a_browser_engine_in_rust::style::{self, StyledNode};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+
+/// Save a `width` x `height` blue PNG file in the temporary directory, returning its path.
+fn blue_png(name: &str, width: usize, height: usize) -> PathBuf {
+    let canvas: Canvas = Canvas { pixels: vec![BLUE; width * height], width, height };
+    let path: PathBuf = std::env::temp_dir().join(format!("images-{}-{}.png", std::process::id(), name));
+    fs::write(&path, canvas.to_png()).unwrap();
+    path
+}
+
+/// Poll `loader` until nothing is loading, returning every source that finished.
+fn wait(loader: &mut ImageLoader) -> Vec<String> {
+    let deadline: Instant = Instant::now() + Duration::from_secs(10);
+    let mut finished: Vec<String> = Vec::new();
+    while loader.is_loading() && Instant::now() < deadline {
+        finished.extend(loader.poll());
+        thread::sleep(Duration::from_millis(5));
+    }
+    finished.extend(loader.poll());
+    finished.sort();
+    finished
+}
+
+#[test]
+fn loads_images_in_the_background() {
+    let path: PathBuf = blue_png("background", 3, 2);
+    let src: String = path.display().to_string();
+    let missing: String = std::env::temp_dir().join("images-missing.png").display().to_string();
+    let document: dom::Document = html::parse(&format!("<p><img src=\"{}\"><img src=\"{}\"></p><img src=\"{}\">", src, missing, src)).unwrap();
+
+    let mut loader: ImageLoader = ImageLoader::new();
+    loader.request_images(&document.tree, document.root_element);
+    assert!(matches!(loader.cache.state(&src), Some(ImageState::Pending)));
+    assert_eq!(wait(&mut loader), [src.clone(), missing.clone()]);
+    let image: Arc<Image> = loader.cache.get(&src).unwrap();
+    assert_eq!((image.width, image.height, image.pixels[5]), (3, 2, BLUE));
+    assert!(matches!(loader.cache.state(&missing), Some(ImageState::Failed)));
+
+    // Each source is loaded once, until it's reloaded.
+    loader.request(&src);
+    assert!(!loader.is_loading());
+    loader.reload(&src);
+    assert!(loader.cache.get(&src).is_none());
+    assert_eq!(wait(&mut loader), [src.as_str()]);
+    assert!(loader.cache.get(&src).is_some());
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn loads_images_on_the_spot_when_synchronous() {
+    let path: PathBuf = blue_png("synchronous", 1, 1);
+    let mut loader: ImageLoader = ImageLoader::new();
+    loader.set_synchronous(true);
+    assert!(loader.is_synchronous());
+    loader.request(&path.display().to_string());
+    // Relative sources resolve against the base URL, but are cached as written.
+    loader.set_base_url(Some("http://127.0.0.1:1/"));
+    loader.request("relative.png");
+    let mut finished: Vec<String> = loader.poll();
+    finished.sort();
+    assert_eq!(finished, [path.display().to_string(), "relative.png".to_string()]);
+    assert!(matches!(loader.cache.state("relative.png"), Some(ImageState::Failed)));
+    assert!(loader.cache.get(&path.display().to_string()).is_some());
+    fs::remove_file(path).unwrap();
+}
+
+/// The content size of the box `node` generated.
+fn size(root: &LayoutBox, node: NodeId) -> (f32, f32) {
+    let found: &LayoutBox = layout::BoxTable::new(root).box_for(node).unwrap();
+    (found.dimensions.content.width, found.dimensions.content.height)
+}
+
+#[test]
+fn lays_out_placeholders_until_images_arrive() {
+    let document: dom::Document = html::parse("<div><img src=\"a.png\" width=\"20\"><img src=\"b.png\" width=\"10\" height=\"10\"></div>").unwrap();
+    let stylesheet: css::Stylesheet = css::parse("html, body, div, img { display: block; }").unwrap();
+    let images: Vec<NodeId> = document.get_elements_by_tag_name("img");
+    let style_root: StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+    let mut viewport: layout::Dimensions = Default::default();
+    viewport.content.width = 100.0;
+    let image = |width: u32, height: u32| ImageState::Ready(Arc::new(Image { width, height, pixels: vec![BLUE; (width * height) as usize] }));
+
+    // A missing dimension is zero until the image tells us its aspect ratio.
+    let mut cache: ImageCache = ImageCache::new();
+    let arena: layout::Bump = layout::Bump::new();
+    let mut root: LayoutBox = layout::layout_tree(&style_root, viewport, &cache, &FontCollection::new(), &arena).unwrap();
+    assert_eq!((size(&root, images[0]), size(&root, images[1])), ((20.0, 0.0), (10.0, 10.0)));
+    assert_eq!(root.update_images(&cache), Invalidation::None);
+
+    // An image that fits its placeholder only needs a repaint.
+    cache.insert("b.png".to_string(), image(5, 2));
+    assert_eq!(root.update_images(&cache), Invalidation::Repaint);
+    assert_eq!(root.update_images(&cache), Invalidation::None);
+
+    // One that doesn't needs layout again, which then gives it its size.
+    cache.insert("a.png".to_string(), image(4, 2));
+    assert_eq!(root.update_images(&cache), Invalidation::Relayout);
+    let root: LayoutBox = layout::layout_tree(&style_root, viewport, &cache, &FontCollection::new(), &arena).unwrap();
+    assert_eq!((size(&root, images[0]), size(&root, images[1])), ((20.0, 10.0), (10.0, 10.0)));
+}
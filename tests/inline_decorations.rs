@@ -0,0 +1,71 @@
+//! Checks the backgrounds and borders of inline elements that wrap onto several lines,
+//! with the stand-in font (every character 8px wide).
+
+use build_a_browser_engine_in_rust::dom::NodeType;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::layout::{BoxType, Dimensions, LayoutBox, Rect};
+use build_a_browser_engine_in_rust::painting::{self, DisplayCommand};
+
+/// The box of the first `<span>` in a layout tree.
+fn find_span<'a>(layout_box: &'a LayoutBox<'a>) -> Option<&'a LayoutBox<'a>> {
+    if let BoxType::InlineNode(node) = layout_box.box_type {
+        if matches!(node.node.node_type, NodeType::Element(ref element) if element.tag_name == "span") {
+            return Some(layout_box);
+        }
+    }
+    layout_box.children.iter().find_map(find_span)
+}
+
+/// The decoration boxes of the `span` in `html`, laid out 100px wide, and the rectangles
+/// painted in its background color.
+fn decorate(css: &str) -> (Vec<Dimensions>, Vec<Rect>) {
+    let html: &str = "<html><p>aa <span>bbbb cccc dddd eeee gggg hhhh</span> ff</p></html>";
+    let css: String = format!("html, body, p {{ display: block; }} body {{ margin: 0px; }} \
+        span {{ padding: 2px; border-width: 1px; background: #ffff00; border-color: #0000ff; }} {}", css);
+    let mut page: Page = Page::load(html, &css).unwrap();
+    page.set_deterministic(true);
+    page.set_viewport(100, 100);
+    page.with_layout_tree(|root: &LayoutBox| {
+        let span: &LayoutBox = find_span(root).unwrap();
+        let backgrounds: Vec<Rect> = painting::build_display_list(root).into_iter()
+            .filter_map(|command: DisplayCommand| match command {
+                DisplayCommand::SolidColor(color, rect) if color.r == 255 && color.b == 0 => Some(rect),
+                _ => None,
+            })
+            .collect();
+        (span.decoration_boxes(), backgrounds)
+    })
+}
+
+#[test]
+fn slices_decorations_at_line_breaks() {
+    let (pieces, backgrounds) = decorate("");
+    assert_eq!(pieces.len(), 3);
+    let edges: Vec<(f32, f32)> = pieces.iter().map(|d: &Dimensions| (d.border.left + d.padding.left, d.border.right + d.padding.right)).collect();
+    assert_eq!(edges, [(3.0, 0.0), (0.0, 0.0), (0.0, 3.0)]);
+
+    // One background per line, each on its own line, and none reaching past the text.
+    assert_eq!(backgrounds.len(), 3);
+    assert!(backgrounds[0].y < backgrounds[1].y && backgrounds[1].y < backgrounds[2].y);
+    assert_eq!((backgrounds[0].x, backgrounds[1].x, backgrounds[2].x), (24.0, 0.0, 0.0));
+    for (background, piece) in backgrounds.iter().zip(&pieces) {
+        assert_eq!(*background, piece.border_box());
+        assert!(background.x + background.width <= 100.0);
+    }
+}
+
+#[test]
+fn clones_decorations_onto_every_line() {
+    let (pieces, _) = decorate("span { box-decoration-break: clone; }");
+    let edges: Vec<(f32, f32)> = pieces.iter().map(|d: &Dimensions| (d.border.left + d.padding.left, d.border.right + d.padding.right)).collect();
+    assert_eq!(edges, [(3.0, 3.0), (3.0, 3.0), (3.0, 3.0)]);
+
+    // An element on one line is decorated as one box.
+    let mut page: Page = Page::load("<html><p><span>aa</span></p></html>", "html, p { display: block; } span { padding: 2px; }").unwrap();
+    page.set_deterministic(true);
+    page.with_layout_tree(|root: &LayoutBox| {
+        let span: &LayoutBox = find_span(root).unwrap();
+        assert_eq!(span.decoration_boxes().len(), 1);
+        assert_eq!(span.decoration_boxes()[0].content, span.dimensions.content);
+    });
+}
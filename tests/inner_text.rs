@@ -0,0 +1,47 @@
+//! Checks `text_content`, which is every bit of text in a subtree, against `inner_text`,
+//! which is the text as it would be rendered.
+
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::dom::{Document, NodeId};
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::style::{self, StyledNode};
+
+const CSS: &str = "div, p, li, ul { display: block; } .hidden, head { display: none; }";
+
+/// Parse `source`, keeping its white space as written.
+fn parse(source: &str) -> Document {
+    let mut parser: html::Parser = html::Parser::new().with_whitespace(html::Whitespace::Preserve);
+    parser.feed(source).unwrap();
+    parser.finish().unwrap()
+}
+
+/// The rendered text of the first element named `tag_name` in `source`.
+fn inner_text(source: &str, tag_name: &str) -> String {
+    let document: Document = parse(source);
+    let node: NodeId = document.get_elements_by_tag_name(tag_name)[0];
+    let stylesheet: css::Stylesheet = css::parse(CSS).unwrap();
+    let root: StyledNode = style::style_tree(&document.tree, node, &stylesheet);
+    root.inner_text()
+}
+
+#[test]
+fn text_content_includes_everything() {
+    let document: Document = parse("<div>Hello <em>big</em>\n  world<p class=\"hidden\">Hidden</p></div>");
+    let div: NodeId = document.get_elements_by_tag_name("div")[0];
+    assert_eq!(document.tree.text_content(div), "Hello big\n  worldHidden");
+    let em: NodeId = document.get_elements_by_tag_name("em")[0];
+    assert_eq!(document.tree.text_content(em), "big");
+}
+
+#[test]
+fn inner_text_follows_the_rendering() {
+    // White space collapses, hidden elements drop out, and paragraphs get a blank line.
+    assert_eq!(inner_text("<div>Hello <em>big</em>\n  world<p>Paragraph</p><p class=\"hidden\">Hidden</p></div>", "div"),
+               "Hello big world\n\nParagraph");
+    // Blocks start new lines, and `<br>` breaks one.
+    assert_eq!(inner_text("<div>  <ul><li>One</li><li> Two<br>lines </li></ul>after</div>", "div"), "One\nTwo\nlines\nafter");
+    // Breaks at the very start or end are dropped, and don't pile up between blocks.
+    assert_eq!(inner_text("<div><p>A</p><div><p>B</p></div></div>", "div"), "A\n\nB");
+    assert_eq!(inner_text("<div class=\"hidden\">gone</div>", "div"), "");
+    assert_eq!(inner_text("<p>x <span>y</span></p>", "span"), "y");
+}
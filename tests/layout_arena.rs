@@ -0,0 +1,68 @@
+//! Checks that the layout tree keeps its boxes in the arena it was given, so a whole tree
+//! is freed (or the arena reused) at once.
+
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::dom::Document;
+use build_a_browser_engine_in_rust::fonts::FontCollection;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::image::ImageCache;
+use build_a_browser_engine_in_rust::layout::{self, Bump, Dimensions, LayoutBox, Rect};
+use build_a_browser_engine_in_rust::style::{self, StyledNode};
+use std::mem::size_of;
+
+const CSS: &str = "head { display: none; } html, body, div, p { display: block; } body { margin: 0; } p { height: 10px; }";
+
+fn viewport() -> Dimensions {
+    let mut viewport: Dimensions = Default::default();
+    viewport.content.width = 200.0;
+    viewport
+}
+
+/// Every box's margin box, in tree order.
+fn rects(layout_box: &LayoutBox, out: &mut Vec<Rect>) {
+    out.push(layout_box.dimensions.margin_box());
+    for child in layout_box.children.iter() {
+        rects(child, out);
+    }
+}
+
+#[test]
+fn allocates_child_lists_in_the_arena() {
+    let document: Document = html::parse(&format!("<div>{}</div>", "<p>text</p>".repeat(50))).unwrap();
+    let stylesheet: css::Stylesheet = css::parse(CSS).unwrap();
+    let style_root: StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+    let arena: Bump = Bump::new();
+    assert_eq!(arena.allocated_bytes(), 0);
+    let root: LayoutBox = layout::layout_tree(&style_root, viewport(), &ImageCache::new(), &FontCollection::new(), &arena).unwrap();
+
+    // Every box but the root is in some box's list of children.
+    let boxes: usize = root.count_boxes();
+    assert!(boxes > 100);
+    assert!(arena.allocated_bytes() >= (boxes - 1) * size_of::<LayoutBox>(), "{} bytes for {} boxes", arena.allocated_bytes(), boxes);
+}
+
+#[test]
+fn empty_boxes_allocate_nothing() {
+    let arena: Bump = Bump::new();
+    let empty: LayoutBox = LayoutBox::empty(&arena);
+    assert!(empty.children.is_empty());
+    assert_eq!(empty.count_boxes(), 1);
+    assert_eq!(arena.allocated_bytes(), 0);
+}
+
+#[test]
+fn reusing_an_arena_lays_out_the_same_tree() {
+    let document: Document = html::parse("<div><p>one</p><p>two</p></div><p>three</p>").unwrap();
+    let stylesheet: css::Stylesheet = css::parse(CSS).unwrap();
+    let style_root: StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+
+    let mut arena: Bump = Bump::new();
+    let mut first: Vec<Rect> = Vec::new();
+    rects(&layout::layout_tree(&style_root, viewport(), &ImageCache::new(), &FontCollection::new(), &arena).unwrap(), &mut first);
+
+    // Once the first tree is gone, the arena can be reset and used for the next one.
+    arena.reset();
+    let mut second: Vec<Rect> = Vec::new();
+    rects(&layout::layout_tree(&style_root, viewport(), &ImageCache::new(), &FontCollection::new(), &arena).unwrap(), &mut second);
+    assert_eq!(first, second);
+}
@@ -0,0 +1,73 @@
+//! Lays out each page in `tests/layout/` and compares its JSON layout dump with the saved
+//! one next to it (`box-model.html` -> `box-model.json`).
+//!
+//! A change that moves boxes on purpose changes the saved dumps too, so the diff shows
+//! exactly which boxes moved. To save the new dumps, run with `BLESS=1`:
+//!
+//!     BLESS=1 cargo test --test layout_snapshots
+
+use build_a_browser_engine_in_rust::debug::{self, DumpFormat};
+use build_a_browser_engine_in_rust::engine::{self, Page};
+use build_a_browser_engine_in_rust::layout::LayoutBox;
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Lay out the page in `path` at the default viewport size and dump its layout tree.
+fn dump(path: &Path) -> Result<String, String> {
+    let html: String = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut page: Page = Page::load(&html, "").map_err(|err| err.to_string())?;
+    page.set_deterministic(true);
+    let (width, height) = engine::DEFAULT_VIEWPORT;
+    page.set_viewport(width, height);
+    Ok(page.with_layout_tree(|root: &LayoutBox| debug::dump_layout(root, DumpFormat::Json)) + "\n")
+}
+
+/// Describe the first line where the dumps differ.
+fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => continue,
+            (e, a) => return format!("line {}:\n  expected: {}\n  actual:   {}", line, e.unwrap_or("(end)"), a.unwrap_or("(end)")),
+        }
+    }
+    unreachable!()
+}
+
+#[test]
+fn layout_snapshots() {
+    let dir: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/layout");
+    let bless: bool = env::var_os("BLESS").is_some();
+
+    let mut pages: Vec<PathBuf> = fs::read_dir(&dir).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path: &PathBuf| path.extension().is_some_and(|ext| ext == "html"))
+        .collect();
+    pages.sort();
+    assert!(!pages.is_empty(), "no pages in {}", dir.display());
+
+    let mut failures: Vec<String> = Vec::new();
+    for page in &pages {
+        let saved: PathBuf = page.with_extension("json");
+        let actual: String = match dump(page) {
+            Ok(actual) => actual,
+            Err(err) => {
+                failures.push(format!("{}: {}", page.display(), err));
+                continue;
+            }
+        };
+        if bless {
+            fs::write(&saved, &actual).unwrap();
+            continue;
+        }
+        match fs::read_to_string(&saved) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!("{}: {}", saved.display(), first_difference(&expected, &actual))),
+            Err(_) => failures.push(format!("{}: no saved layout", saved.display())),
+        }
+    }
+    assert!(failures.is_empty(), "{} of {} layouts changed (run with BLESS=1 to save them):\n{}",
+        failures.len(), pages.len(), failures.join("\n"));
+}
@@ -0,0 +1,40 @@
+//! Checks stylesheet statistics and the lint pass.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::css::{self, Color, Stylesheet, StylesheetStats, Value};
+use build_a_browser_engine_in_rust::dom;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::lint::{self, Lint};
+
+#[test]
+fn stats_count_rules_selectors_and_specificity() {
+    let stylesheet: Stylesheet = css::parse("h1, h2 { margin: auto; } #a.b { color: #cc0000; padding: 1px; } p { }").unwrap();
+    assert_eq!(stylesheet.stats(), StylesheetStats {
+        rules: 3,
+        selectors: 4,
+        declarations: 3,
+        specificity: vec![((0, 0, 1), 3), ((1, 1, 0), 1)],
+    });
+    assert_eq!(css::parse("").unwrap().stats(), StylesheetStats::default());
+}
+
+#[test]
+fn lint_reports_unknown_properties_invalid_values_and_unused_rules() {
+    let document: dom::Document = html::parse("<html><body><p class=\"note\"><a href=\"/\">link</a></p></body></html>").unwrap();
+    let stylesheet: Stylesheet = css::parse("
+        a { colour: #ff0000; }
+        p.note { display: blokc; width: #ffffff; margin: auto; padding: auto; }
+        table, .missing { margin: 0px; }
+        a:hover { background: #eeeeee; overflow: scroll; }
+    ").unwrap();
+    let lints: Vec<Lint> = lint::lint(&stylesheet, &document.tree, document.root_element);
+    assert_eq!(lints, vec![
+        Lint::UnknownProperty { rule: 0, name: Atom::from("colour") },
+        Lint::InvalidValue { rule: 1, name: Atom::from("display"), value: Value::Keyword(Atom::from("blokc")) },
+        Lint::InvalidValue { rule: 1, name: Atom::from("width"), value: Value::ColorValue(Color { r: 255, g: 255, b: 255, a: 255 }) },
+        Lint::InvalidValue { rule: 1, name: Atom::from("padding"), value: Value::Keyword(Atom::from("auto")) },
+        Lint::UnusedRule { rule: 2 },
+    ]);
+    assert_eq!(lints[0].to_string(), "rule 0: unknown property `colour`");
+    assert_eq!(lints[4].rule(), 2);
+}
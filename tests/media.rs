@@ -0,0 +1,72 @@
+//! Checks that `@media` rules parse, print, and apply only to the medium they're for.
+
+use build_a_browser_engine_in_rust::css::{self, Media, Stylesheet};
+use build_a_browser_engine_in_rust::dom::NodeType;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::layout::{BoxType, LayoutBox};
+
+const HTML: &str = "<html><head><title>Hi</title></head><body><nav>Menu</nav><p>Text</p></body></html>";
+
+const CSS: &str = "\
+    head { display: none; }
+    html, body, nav, p { display: block; }
+    @media print { nav { display: none; } }
+    @media only screen, tv { p { display: none; } }";
+
+/// The tag names of the boxes in the body of `page`.
+fn body_boxes(page: &mut Page) -> Vec<String> {
+    page.with_layout_tree(|root: &LayoutBox| root.children[0].children.iter().map(|child: &LayoutBox| match child.box_type {
+        BoxType::BlockNode(styled) => match styled.node.node_type {
+            NodeType::Element(ref element) => element.tag_name.to_string(),
+            _ => String::new(),
+        },
+        _ => String::new(),
+    }).collect())
+}
+
+#[test]
+fn parses_media_blocks() {
+    let stylesheet: Stylesheet = css::parse(CSS).unwrap();
+    let media: Vec<Option<Vec<&str>>> = stylesheet.rules.iter()
+        .map(|rule: &css::Rule| rule.media.as_ref().map(|types| types.iter().map(|media_type| media_type.as_str()).collect()))
+        .collect();
+    assert_eq!(media, [None, None, Some(vec!["print"]), Some(vec!["screen", "tv"])]);
+
+    assert!(stylesheet.rules[2].applies_to(Media::Print));
+    assert!(!stylesheet.rules[2].applies_to(Media::Screen));
+    assert!(stylesheet.rules[0].applies_to(Media::Print));
+    assert!(css::parse("@media ALL { p { color: red; } }").unwrap().rules[0].applies_to(Media::Screen));
+
+    // Each rule prints in a block of its own, and reads back the same.
+    let printed: String = stylesheet.to_string();
+    assert!(printed.contains("@media screen, tv { p { display: none; } }"), "{}", printed);
+    assert_eq!(css::parse(&printed).unwrap(), stylesheet);
+}
+
+#[test]
+fn rejects_what_it_doesnt_support() {
+    let message = |source: &str| -> String { css::parse(source).unwrap_err().message };
+    assert_eq!(message("@import url(a.css);"), "Unsupported at-rule @import");
+    assert_eq!(message("@media { p { color: red; } }"), "Expected a media type");
+    assert_eq!(message("@media not print { p { color: red; } }"), "Expected a media type");
+    assert!(css::parse("@media print and (color) { p { color: red; } }").is_err());
+    assert!(css::parse("@media print { @media screen { p { color: red; } } }").is_err());
+}
+
+#[test]
+fn renders_for_screen_or_print() {
+    let mut page: Page = Page::load(HTML, CSS).unwrap();
+    page.set_deterministic(true);
+    assert_eq!(page.media(), Media::Screen);
+    // On screen the `p` is hidden.
+    assert_eq!(body_boxes(&mut page), ["nav"]);
+
+    // For print the `nav` is hidden instead.
+    page.set_media(Media::Print);
+    assert_eq!(body_boxes(&mut page), ["p"]);
+
+    // The medium outlives a new document.
+    page.set_document(html::parse(HTML).unwrap());
+    assert_eq!(page.media(), Media::Print);
+}
@@ -0,0 +1,83 @@
+//! Checks that a page's memory report counts what each part of the page owns, and grows
+//! with the page.
+
+use build_a_browser_engine_in_rust::css::Color;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::memory::{HeapSize, MemoryReport};
+use build_a_browser_engine_in_rust::painting::Canvas;
+use std::fs;
+use std::mem::size_of;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const CSS: &str = "head { display: none; } html, body, p, img, iframe { display: block; } body { margin: 0; }";
+
+/// The report for `html`, painted at 100x50.
+fn report(html: &str) -> MemoryReport {
+    let mut page: Page = Page::load(html, CSS).unwrap();
+    page.set_viewport(100, 50);
+    assert!(page.wait_for_images(Duration::from_secs(10)));
+    page.paint();
+    page.memory_report()
+}
+
+#[test]
+fn counts_capacities_and_what_items_own() {
+    let mut words: Vec<String> = Vec::with_capacity(4);
+    words.push(String::with_capacity(10));
+    words.push("abc".to_string());
+    assert_eq!(words.heap_size(), 4 * size_of::<String>() + 10 + 3);
+    assert_eq!(Some(String::with_capacity(7)).heap_size(), 7);
+    assert_eq!(None::<String>.heap_size(), 0);
+}
+
+#[test]
+fn reports_each_part_of_the_page() {
+    let mut page: Page = Page::load("<p>Hello</p>", CSS).unwrap();
+    let before: MemoryReport = page.memory_report();
+    assert!(before.dom > 0 && before.stylesheet > 0 && before.style_tree > 0 && before.layout_tree > 0);
+    // Nothing is cached until the page is painted.
+    assert_eq!((before.display_list, before.canvas, before.images, before.frames), (0, 0, 0, 0));
+
+    page.set_viewport(100, 50);
+    page.paint();
+    let after: MemoryReport = page.memory_report();
+    assert!(after.display_list > 0);
+    assert_eq!(after.canvas, 100 * 50 * size_of::<Color>());
+    assert_eq!(after.total(), after.dom + after.stylesheet + after.style_tree + after.layout_tree + after.display_list + after.canvas);
+}
+
+#[test]
+fn grows_with_the_document() {
+    let small: MemoryReport = report("<p>Hello</p>");
+    let large: MemoryReport = report(&"<p class=\"para\">Hello, world</p>".repeat(100));
+    assert!(large.dom > small.dom * 10, "{} vs {}", large.dom, small.dom);
+    assert!(large.style_tree > small.style_tree * 10);
+    assert!(large.layout_tree > small.layout_tree * 10);
+    assert_eq!(large.canvas, small.canvas);
+}
+
+#[test]
+fn counts_images_and_frames() {
+    let dir: PathBuf = std::env::temp_dir();
+    let png: PathBuf = dir.join(format!("memory-report-{}.png", std::process::id()));
+    let canvas: Canvas = Canvas { pixels: vec![Color { r: 255, g: 0, b: 0, a: 255 }; 8 * 8], width: 8, height: 8 };
+    fs::write(&png, canvas.to_png()).unwrap();
+    let with_image: MemoryReport = report(&format!("<img src=\"{}\">", png.display()));
+    assert!(with_image.images >= 8 * 8 * size_of::<Color>(), "{}", with_image.images);
+
+    let frame: PathBuf = dir.join(format!("memory-report-{}.html", std::process::id()));
+    fs::write(&frame, "<p>Inside</p>").unwrap();
+    let with_frame: MemoryReport = report(&format!("<iframe src=\"{}\" width=\"20\" height=\"10\"></iframe>", frame.display()));
+    // The frame's own page, and its painted pixels in the parent's image cache.
+    assert!(with_frame.frames >= 20 * 10 * size_of::<Color>(), "{}", with_frame.frames);
+    assert!(with_frame.images >= 20 * 10 * size_of::<Color>());
+}
+
+#[test]
+fn prints_a_table_in_kib() {
+    let report: MemoryReport = MemoryReport { dom: 2048, stylesheet: 512, style_tree: 0, layout_tree: 0, display_list: 0, canvas: 1024, images: 0, frames: 0 };
+    let lines: Vec<String> = report.to_string().lines().map(|line: &str| line.split_whitespace().collect::<Vec<&str>>().join(" ")).collect();
+    assert_eq!(lines, ["dom 2.0 KiB", "stylesheet 0.5 KiB", "style tree 0.0 KiB", "layout tree 0.0 KiB", "display list 0.0 KiB",
+                       "canvas 1.0 KiB", "images 0.0 KiB", "frames 0.0 KiB", "total 3.5 KiB"]);
+}
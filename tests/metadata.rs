@@ -0,0 +1,47 @@
+//! Checks the metadata helpers on `Document`: title, meta tags, canonical URL, and Open
+//! Graph properties.
+
+use build_a_browser_engine_in_rust::dom::Document;
+use build_a_browser_engine_in_rust::html;
+
+#[test]
+fn reads_metadata_from_the_head() {
+    let document: Document = html::parse("<html><head>\
+        <title>\n  Build a   Browser\tEngine </title><title>Second</title>\
+        <meta name=\"Description\" content=\"A toy engine.\">\
+        <meta name=\"description\" content=\"Later.\">\
+        <meta name=\"author\">\
+        <link rel=\"stylesheet\" href=\"style.css\">\
+        <link rel=\"Canonical alternate\" href=\"/engine\">\
+        <meta property=\"og:title\" content=\"Let's build a browser engine!\">\
+        <meta property=\"og:image\" content=\"cover.png\">\
+        <meta property=\"OG:image\" content=\"back.png\">\
+        <meta property=\"twitter:card\" content=\"summary\">\
+        </head><body><p>Hello</p></body></html>").unwrap();
+
+    assert_eq!(document.title().as_deref(), Some("Build a Browser Engine"));
+    assert_eq!(document.meta("description"), Some("A toy engine."));
+    assert_eq!(document.meta("DESCRIPTION"), Some("A toy engine."));
+    assert_eq!(document.meta("author"), None);
+    assert_eq!(document.meta("keywords"), None);
+    assert_eq!(document.canonical_url(), Some("/engine"));
+    assert_eq!(document.open_graph(), vec![
+        ("title", "Let's build a browser engine!"),
+        ("image", "cover.png"),
+        ("image", "back.png"),
+    ]);
+    assert_eq!(document.open_graph_property("image"), Some("cover.png"));
+    assert_eq!(document.open_graph_property("card"), None);
+}
+
+#[test]
+fn has_no_metadata_without_a_head() {
+    let document: Document = html::parse("<p>Hello</p>").unwrap();
+    assert_eq!(document.title(), None);
+    assert_eq!(document.meta("description"), None);
+    assert_eq!(document.canonical_url(), None);
+    assert!(document.open_graph().is_empty());
+
+    let document: Document = html::parse("<title></title>").unwrap();
+    assert_eq!(document.title().as_deref(), Some(""));
+}
@@ -0,0 +1,148 @@
+//! Checks the HTTP client: parsing and resolving URLs, the cookie jar, and the headers
+//! sent with each request, against a server on a port of its own.
+
+use build_a_browser_engine_in_rust::net::{self, Client, CookieJar, Origin, Response, Url};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+
+/// Answer each request with `responses`, in turn, sending the raw requests back over the
+/// returned channel. Returns the server's address.
+fn serve(responses: &'static [&'static str]) -> (String, Receiver<String>) {
+    let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address: String = format!("http://{}", listener.local_addr().unwrap());
+    let (sender, receiver): (Sender<String>, Receiver<String>) = mpsc::channel();
+    thread::spawn(move || {
+        for (mut stream, response) in listener.incoming().map_while(Result::ok).zip(responses) {
+            let mut request: Vec<u8> = Vec::new();
+            let mut buffer: [u8; 1024] = [0; 1024];
+            // The headers end with a blank line; a POST's body may come in the same read.
+            while !request.windows(4).any(|window: &[u8]| window == b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => request.extend_from_slice(&buffer[..read]),
+                }
+            }
+            let _ = sender.send(String::from_utf8_lossy(&request).into_owned());
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    (address, receiver)
+}
+
+/// The value of the header named `name` in a raw request.
+fn header<'r>(request: &'r str, name: &str) -> Option<&'r str> {
+    request.lines()
+        .filter_map(|line: &str| line.split_once(": "))
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value)
+}
+
+#[test]
+fn parses_and_resolves_urls() {
+    let url: Url = Url::parse("HTTP://Example.com:8080/a/b/page.html?q=1#top").unwrap();
+    assert_eq!(url, Url { scheme: "http".into(), host: "example.com".into(), port: 8080, path: "/a/b/page.html?q=1".into(),
+                          fragment: Some("top".into()) });
+    assert_eq!(url.to_string(), "http://example.com:8080/a/b/page.html?q=1#top");
+    assert_eq!(Url::parse("http://example.com").unwrap().to_string(), "http://example.com/");
+    assert_eq!(Url::parse("https://example.com?x").unwrap().port, 443);
+    assert!(matches!(Url::parse("example.com"), Err(net::LoadError::InvalidUrl(_))));
+    assert!(Url::parse("http://:80/").is_err());
+    assert!(matches!(Url::parse("mailto:me@example.com"), Err(net::LoadError::UnsupportedScheme(ref scheme)) if scheme == "mailto"));
+
+    let join = |reference: &str| url.join(reference).unwrap().to_string();
+    assert_eq!(join("c.html"), "http://example.com:8080/a/b/c.html");
+    assert_eq!(join("../c.html?x=2"), "http://example.com:8080/a/c.html?x=2");
+    assert_eq!(join("./"), "http://example.com:8080/a/b/");
+    assert_eq!(join("/root"), "http://example.com:8080/root");
+    assert_eq!(join("?q=2"), "http://example.com:8080/a/b/page.html?q=2");
+    assert_eq!(join("//other.org/x"), "http://other.org/x");
+    assert_eq!(join("http://third.net"), "http://third.net/");
+    assert_eq!(join("go?to=http://third.net"), "http://example.com:8080/a/b/go?to=http://third.net");
+    // The fragment comes from the reference, even when it is all there is.
+    assert_eq!(join(""), "http://example.com:8080/a/b/page.html?q=1");
+    assert_eq!(join("#end"), "http://example.com:8080/a/b/page.html?q=1#end");
+    assert_eq!(join("?q=2#end"), "http://example.com:8080/a/b/page.html?q=2#end");
+    assert_eq!(join("../c.html#end"), "http://example.com:8080/a/c.html#end");
+    assert_eq!(join("HTTP://third.net/#end"), "http://third.net/#end");
+    // A reference with a scheme is absolute, even one there's no fetching.
+    for reference in ["mailto:me@example.com", "javascript:void(0)", "data:text/plain,hi"] {
+        assert!(matches!(url.join(reference), Err(net::LoadError::UnsupportedScheme(_))), "{}", reference);
+    }
+    assert_eq!(net::scheme("data:text/plain,hi"), Some("data"));
+    assert_eq!(net::scheme("a/b:c"), None);
+    assert_eq!(net::scheme("?next=http://third.net"), None);
+}
+
+#[test]
+fn sends_cookies_back_to_their_origin_and_path() {
+    let mut jar: CookieJar = CookieJar::new();
+    let login: Url = Url::parse("http://example.com/account/login").unwrap();
+    jar.set_from_header(&login, "session=abc; HttpOnly");
+    jar.set_from_header(&login, "theme=dark; Path=/");
+    jar.set_from_header(&login, "malformed");
+
+    let url = |path: &str| Url::parse(&format!("http://example.com{}", path)).unwrap();
+    // A cookie's default path is the directory of the page that set it.
+    assert_eq!(jar.header_for(&url("/account/settings?tab=1")).as_deref(), Some("session=abc; theme=dark"));
+    assert_eq!(jar.header_for(&url("/account")).as_deref(), Some("session=abc; theme=dark"));
+    assert_eq!(jar.header_for(&url("/accounts")).as_deref(), Some("theme=dark"));
+    assert_eq!(jar.header_for(&Url::parse("http://example.com:81/account/").unwrap()), None);
+    assert_eq!(jar.header_for(&Url::parse("http://other.com/").unwrap()), None);
+
+    // Setting a cookie again replaces it, and `Max-Age=0` deletes it.
+    jar.set_from_header(&login, "session=def");
+    assert_eq!(jar.cookies_for(&url("/account/")).len(), 2);
+    assert_eq!(jar.header_for(&url("/account/")).as_deref(), Some("theme=dark; session=def"));
+    jar.set_from_header(&login, "session=; Max-Age=0");
+    assert_eq!(jar.header_for(&url("/account/")).as_deref(), Some("theme=dark"));
+    jar.clear();
+    assert_eq!(jar.header_for(&url("/")), None);
+}
+
+#[test]
+fn puts_origin_headers_over_default_ones() {
+    let mut client: Client = Client::new();
+    let api: Url = Url::parse("http://api.example.com/v1").unwrap();
+    let origin: Origin = api.origin();
+    client.set_default_header("X-Trace", "on");
+    client.set_origin_header(&origin, "Authorization", "Bearer t");
+    client.set_origin_header(&origin, "accept-language", "fr");
+    client.remove_default_header("User-Agent");
+    client.cookies.set_from_header(&api, "id=7");
+
+    let names = |headers: Vec<(String, String)>| headers.into_iter().map(|(name, value)| format!("{}: {}", name, value)).collect::<Vec<String>>();
+    assert_eq!(names(client.request_headers(&api)), ["Accept-Language: fr", "X-Trace: on", "Authorization: Bearer t", "Cookie: id=7"]);
+    let elsewhere: Url = Url::parse("http://example.com/").unwrap();
+    assert_eq!(names(client.request_headers(&elsewhere)), [format!("Accept-Language: {}", net::DEFAULT_ACCEPT_LANGUAGE), "X-Trace: on".to_string()]);
+}
+
+#[test]
+fn keeps_cookies_set_by_the_server() {
+    static RESPONSES: &[&str] = &[
+        "HTTP/1.1 200 OK\r\nSet-Cookie: session=abc; Path=/\r\nContent-Length: 2\r\n\r\nhi",
+        "HTTP/1.1 404 Not Found\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nnot \r\n5\r\nfound\r\n0\r\n\r\n",
+    ];
+    let (address, requests) = serve(RESPONSES);
+    let mut client: Client = Client::new();
+
+    let response: Response = client.post_form(&format!("{}/login", address), "user=me").unwrap();
+    assert_eq!((response.status, response.text()), (200, "hi".to_string()));
+    assert_eq!(response.header("set-cookie"), Some("session=abc; Path=/"));
+    let request: String = requests.recv().unwrap();
+    assert!(request.starts_with("POST /login HTTP/1.1\r\n"));
+    // The server isn't on port 80, so the Host header names its port.
+    assert_eq!(header(&request, "Host"), address.strip_prefix("http://"));
+    assert_eq!(header(&request, "User-Agent"), Some(net::DEFAULT_USER_AGENT));
+    assert_eq!(header(&request, "Cookie"), None);
+
+    let response: Response = client.get(&format!("{}/home?x=1#top", address)).unwrap();
+    assert_eq!((response.status, response.text()), (404, "not found".to_string()));
+    assert_eq!(response.url.fragment, None);
+    let request: String = requests.recv().unwrap();
+    assert!(request.starts_with("GET /home?x=1 HTTP/1.1\r\n"));
+    assert_eq!(header(&request, "Cookie"), Some("session=abc"));
+
+    assert!(matches!(client.get("https://example.com/"), Err(net::LoadError::UnsupportedScheme(scheme)) if scheme == "https"));
+}
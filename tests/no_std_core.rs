@@ -0,0 +1,118 @@
+//! Checks the core that builds without `std`: parsing, styling, layout, and painting into a
+//! `Canvas`, with images the caller decoded itself, and the pieces that stand in for `std`
+//! (the math functions, hash maps, and atom table), and that the core builds for a target
+//! with no `std` at all.
+//!
+//! These tests use only the core, so they run with or without `std`. Run them as a
+//! `no_std` build of the crate would with
+//! `cargo test --no-default-features --test no_std_core`.
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::collections::HashMap;
+use build_a_browser_engine_in_rust::css::{self, Color};
+use build_a_browser_engine_in_rust::dom::Document;
+use build_a_browser_engine_in_rust::fonts::FontCollection;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::image::{Image, ImageCache, ImageState};
+use build_a_browser_engine_in_rust::layout::{self, Bump, Dimensions, LayoutBox};
+use build_a_browser_engine_in_rust::math;
+use build_a_browser_engine_in_rust::painting::{self, Canvas};
+use build_a_browser_engine_in_rust::style::{self, StyledNode};
+use std::borrow::Cow;
+use std::env;
+use std::path::Path;
+use std::process::{Command, Output};
+use std::sync::Arc;
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+
+/// Render `html` and `css` into a 20x20 canvas, with `images` as the only images there
+/// are. Also returns the height of the laid out document.
+fn render(html: &str, css: &str, images: &ImageCache) -> (Canvas, f32) {
+    let document: Document = html::parse(html).unwrap();
+    let stylesheet: css::Stylesheet = css::parse(css).unwrap();
+    let style_root: StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+    let mut viewport: Dimensions = Default::default();
+    (viewport.content.width, viewport.content.height) = (20.0, 20.0);
+    let arena: Bump = Bump::new();
+    let layout_root: LayoutBox = layout::layout_tree(&style_root, viewport, images, &FontCollection::new(), &arena).unwrap();
+    (painting::paint(&layout_root, viewport.content), layout_root.dimensions.margin_box().height)
+}
+
+#[test]
+fn renders_into_a_canvas_with_images_from_the_caller() {
+    let mut images: ImageCache = ImageCache::new();
+    let logo: Image = Image { width: 4, height: 2, pixels: vec![BLUE; 4 * 2] };
+    images.insert("logo.raw".to_string(), ImageState::Ready(Arc::new(logo)));
+    let css: &str = "html, body, div, img { display: block; } body { margin: 0; } div { height: 5px; background: #ff0000; }";
+    let (canvas, _): (Canvas, f32) = render("<div></div><img src=\"logo.raw\"><img src=\"missing.raw\">", css, &images);
+
+    assert_eq!((canvas.width, canvas.height), (20, 20));
+    assert_eq!(canvas.pixels[0], RED);
+    assert_eq!(canvas.pixels[4 * 20 + 19], RED);
+    // The image is drawn at its own size, under the div.
+    assert_eq!(canvas.pixels[5 * 20], BLUE);
+    assert_eq!(canvas.pixels[6 * 20 + 3], BLUE);
+    assert_ne!(canvas.pixels[6 * 20 + 4], BLUE);
+    assert!(images.get("missing.raw").is_none());
+}
+
+#[test]
+fn lays_out_text_without_fonts() {
+    let css: &str = "head { display: none; } html, body, p { display: block; } body { margin: 0; }";
+    let (_, one_line): (Canvas, f32) = render("<p>one</p>", css, &ImageCache::new());
+    let (_, wrapped): (Canvas, f32) = render("<p>one two three four five six</p>", css, &ImageCache::new());
+    // The stand-in metrics still give lines a height, and wrap long text onto more of them.
+    assert!(one_line > 0.0);
+    assert!(wrapped > one_line, "{} vs {}", wrapped, one_line);
+}
+
+#[test]
+fn math_matches_the_standard_functions() {
+    for x in [-2.5f32, -1.5, -1.0, -0.4, 0.0, 0.4, 0.5, 1.5, 2.49, 7.0, 1e6, 9e6, -9e6] {
+        assert_eq!(math::floor(x), x.floor(), "floor({})", x);
+        assert_eq!(math::round(x), x.round(), "round({})", x);
+    }
+    for x in [0.0f32, 1e-6, 0.25, 1.0, 2.0, 10.0, 12345.678] {
+        let (ours, theirs): (f32, f32) = (math::sqrt(x), x.sqrt());
+        assert!((ours - theirs).abs() <= theirs * 1e-6, "sqrt({}) = {}, not {}", x, ours, theirs);
+    }
+    assert_eq!(math::sqrt(f32::INFINITY), f32::INFINITY);
+    assert!(math::sqrt(-1.0).is_nan());
+    assert!(math::floor(f32::NAN).is_nan());
+}
+
+#[test]
+fn interns_atoms_and_hashes_keys() {
+    let first: Atom = Atom::new("no-std-core-test");
+    let again: Atom = Atom::from(String::from("no-std-core-test"));
+    assert_eq!(first, again);
+    assert!(std::ptr::eq(first.as_str(), again.as_str()));
+
+    let mut counts: HashMap<Atom, usize> = HashMap::default();
+    for word in ["div", "p", "div", "span", "div"] {
+        *counts.entry(Atom::from(word)).or_insert(0) += 1;
+    }
+    assert_eq!((counts[&Atom::from("div")], counts[&Atom::from("p")], counts.len()), (3, 1, 3));
+}
+
+/// Building without `std` is only supported for a target that has none (see "no_std" in
+/// `src/lib.rs`), so this runs the documented command. It builds into a directory of its
+/// own, so as not to wait on the build running the tests.
+#[test]
+fn builds_for_a_target_without_std() {
+    let cargo: String = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let output: Output = Command::new(cargo)
+        .args(["build", "--lib", "--no-default-features", "--target", "thumbv7em-none-eabihf"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("CARGO_TARGET_DIR", Path::new(env!("CARGO_TARGET_TMPDIR")).join("no_std"))
+        .output()
+        .unwrap();
+    let errors: Cow<str> = String::from_utf8_lossy(&output.stderr);
+    if errors.contains("target may not be installed") {
+        eprintln!("skipping: no thumbv7em-none-eabihf target (rustup target add thumbv7em-none-eabihf)");
+        return;
+    }
+    assert!(output.status.success(), "{}", errors);
+}
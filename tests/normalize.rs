@@ -0,0 +1,67 @@
+//! Checks merging adjacent text nodes, and stripping the white space between tags.
+
+use build_a_browser_engine_in_rust::dom::{Document, NodeId, NodeType, Tree};
+use build_a_browser_engine_in_rust::html;
+
+/// What each child of `node` is: its tag name, or its text in quotes.
+fn children(tree: &Tree, node: NodeId) -> Vec<String> {
+    tree.children(node).map(|child: NodeId| match tree[child].node_type {
+        NodeType::Element(ref element) => element.tag_name.to_string(),
+        NodeType::Text(ref text) => format!("{:?}", text),
+        NodeType::DocumentFragment => "#fragment".to_string(),
+    }).collect()
+}
+
+#[test]
+fn merges_adjacent_text_and_drops_empty_text() {
+    let mut document: Document = html::parse("<p>a<b>x</b>c</p>").unwrap();
+    let p: NodeId = document.get_elements_by_tag_name("p")[0];
+    let b: NodeId = document.get_elements_by_tag_name("b")[0];
+    let pieces: Vec<NodeId> = ["1", "", "2"].iter().map(|text: &&str| document.tree.text(text.to_string())).collect();
+    for &piece in &pieces {
+        document.tree.insert_before(p, piece, Some(b));
+    }
+    let empty: NodeId = document.tree.text(String::new());
+    document.tree.append_child(b, empty);
+    let last: NodeId = document.tree.text("d".to_string());
+    document.tree.append_child(p, last);
+    assert_eq!(children(&document.tree, p), ["\"a\"", "\"1\"", "\"\"", "\"2\"", "b", "\"c\"", "\"d\""]);
+
+    document.tree.normalize(p);
+    assert_eq!(children(&document.tree, p), ["\"a12\"", "b", "\"cd\""]);
+    assert_eq!(children(&document.tree, b), ["\"x\""]);
+    assert_eq!(document.tree.text_content(p), "a12xcd");
+
+    // Normalizing again changes nothing.
+    document.tree.normalize(document.root_element);
+    assert_eq!(document.tree.to_html(p), "<p>a12<b>x</b>cd</p>");
+}
+
+#[test]
+fn only_touches_the_subtree_it_is_given() {
+    let mut document: Document = html::parse("<div><p>x</p></div>").unwrap();
+    let div: NodeId = document.get_elements_by_tag_name("div")[0];
+    let p: NodeId = document.get_elements_by_tag_name("p")[0];
+    let (one, two) = (document.tree.text("1".to_string()), document.tree.text("2".to_string()));
+    document.tree.append_child(div, one);
+    document.tree.append_child(div, two);
+    let more: NodeId = document.tree.text("y".to_string());
+    document.tree.append_child(p, more);
+
+    document.tree.normalize(p);
+    assert_eq!(children(&document.tree, p), ["\"xy\""]);
+    assert_eq!(children(&document.tree, div), ["p", "\"1\"", "\"2\""]);
+}
+
+#[test]
+fn strips_whitespace_only_text() {
+    let mut parser: html::Parser = html::Parser::new().with_whitespace(html::Whitespace::Preserve);
+    parser.feed("<ul>\n  <li> One </li>\n  <li>\t</li>\n</ul>").unwrap();
+    let mut document: Document = parser.finish().unwrap();
+    let ul: NodeId = document.get_elements_by_tag_name("ul")[0];
+    assert_eq!(children(&document.tree, ul).len(), 5);
+
+    document.tree.strip_whitespace_text(ul);
+    // Text with anything else in it is kept as it is.
+    assert_eq!(document.tree.to_html(ul), "<ul><li> One </li><li></li></ul>");
+}
@@ -0,0 +1,87 @@
+//! Checks that a `Page` runs the whole pipeline from source to pixels, and only runs the
+//! stages whose inputs changed.
+
+use build_a_browser_engine_in_rust::css::{self, Color};
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::{self, Page};
+use build_a_browser_engine_in_rust::error::EngineError;
+use build_a_browser_engine_in_rust::layout::{BoxType, LayoutBox};
+use build_a_browser_engine_in_rust::painting::{Canvas, DisplayCommand};
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+
+/// The color at (x, y) in the page's canvas.
+fn pixel(page: &mut Page, x: usize, y: usize) -> Color {
+    let canvas: &Canvas = page.paint();
+    canvas.pixels[y * canvas.width + x]
+}
+
+#[test]
+fn renders_html_and_css_to_pixels() {
+    let mut page: Page = Page::load("<div class=\"a\"></div><div class=\"b\"></div>",
+                                    "html, body, div { display: block; } div { height: 10px; } .a { background: #ff0000; } .b { background: #0000ff; }").unwrap();
+    assert_eq!(page.viewport(), engine::DEFAULT_VIEWPORT);
+    page.set_viewport(20, 30);
+    assert_eq!((page.paint().width, page.paint().height), (20, 30));
+    assert_eq!((pixel(&mut page, 0, 0), pixel(&mut page, 19, 10)), (RED, BLUE));
+    assert_eq!(pixel(&mut page, 0, 25), Color { r: 255, g: 255, b: 255, a: 255 });
+
+    let png: Vec<u8> = page.screenshot();
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+}
+
+#[test]
+fn applies_style_elements_after_the_given_css() {
+    let page: Page = Page::load("<style>p { color: #00ff00; }</style><p>x</p>", "p { color: #ff0000; margin: 2px; }").unwrap();
+    assert_eq!(page.stylesheet().rules.len(), 2);
+    let p: NodeId = page.document().get_elements_by_tag_name("p")[0];
+    assert_eq!(page.computed_value(p, "color"), Some(css::Value::ColorValue(Color { r: 0, g: 255, b: 0, a: 255 })));
+    assert_eq!(page.computed_value(p, "margin"), Some(css::Value::Length(2.0, css::Unit::Px)));
+}
+
+#[test]
+fn only_reruns_stages_whose_inputs_changed() {
+    let mut page: Page = Page::load("<div></div>", "html, body, div { display: block; } div { height: 10px; background: #ff0000; }").unwrap();
+    page.set_viewport(10, 10);
+    let first: *const DisplayCommand = page.layout().as_ptr();
+    assert_eq!(page.layout().as_ptr(), first);
+
+    // A taller viewport needs a new canvas, but not a new layout.
+    page.set_viewport(10, 20);
+    assert_eq!(page.paint().height, 20);
+    assert_eq!(page.layout().as_ptr(), first);
+
+    // A wider one, a new stylesheet, or a changed document lays the page out again.
+    page.set_viewport(5, 20);
+    assert!(page.layout().iter().any(|command: &DisplayCommand| matches!(command, DisplayCommand::SolidColor(_, rect) if rect.width == 5.0)));
+    page.set_stylesheet(css::parse("html, body, div { display: block; } div { height: 10px; background: #0000ff; }").unwrap());
+    assert_eq!(pixel(&mut page, 0, 0), BLUE);
+    let div: NodeId = page.document().get_elements_by_tag_name("div")[0];
+    page.document_mut().set_attribute(div, "style", "background: #ff0000");
+    assert_eq!(pixel(&mut page, 0, 0), RED);
+}
+
+#[test]
+fn lends_out_the_layout_tree() {
+    let page: Page = Page::load("<p>a</p>", "html, body, p { display: block; } p { height: 7px; }").unwrap();
+    let p: NodeId = page.document().get_elements_by_tag_name("p")[0];
+    let height: f32 = page.with_layout_tree(|root: &LayoutBox| {
+        let mut boxes: Vec<&LayoutBox> = vec![root];
+        while let Some(layout_box) = boxes.pop() {
+            if let BoxType::BlockNode(styled) = layout_box.box_type {
+                if styled.node_id == p {
+                    return layout_box.dimensions.content.height;
+                }
+            }
+            boxes.extend(layout_box.children.iter());
+        }
+        panic!("no box for the p");
+    });
+    assert_eq!(height, 7.0);
+}
+
+#[test]
+fn reports_css_that_does_not_parse() {
+    assert!(matches!(Page::load("<p>x</p>", "p { color: red"), Err(EngineError::Css(_))));
+}
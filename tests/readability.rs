@@ -0,0 +1,52 @@
+//! Checks that reader mode finds a page's article among its clutter, and copies it into a
+//! clean document.
+
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::readability::{self, Article};
+
+/// A paragraph long enough to count as content.
+const PARAGRAPH: &str = "This is a long paragraph of article text, with commas, clauses, and enough words to score well.";
+
+/// A news page with the article in a `div.content` among navigation, a sidebar, and comments.
+fn cluttered_page() -> String {
+    format!("<html><head><title>The News</title><style>p {{ color: red; }}</style></head><body>\
+             <nav><a href=\"/\">Home</a> <a href=\"/about\">About</a></nav>\
+             <div class=\"sidebar\"><p>{0}</p></div>\
+             <div class=\"content\" id=\"main\"><h1>Headline</h1><p class=\"lead\">{0}</p><p>{0}</p>\
+             <img src=\"a.png\" alt=\"A picture\" onclick=\"x()\"><script>track();</script>\
+             <ul><li><a href=\"/1\">Related one</a></li><li><a href=\"/2\">Related two</a></li></ul></div>\
+             <div id=\"comments\"><p>{0}</p></div><footer>Copyright</footer></body></html>", PARAGRAPH)
+}
+
+#[test]
+fn extracts_the_article() {
+    let article: Article = readability::extract(&html::parse(&cluttered_page()).unwrap()).unwrap();
+    assert_eq!(article.title.as_deref(), Some("The News"));
+    let body: String = article.document.tree.to_html(article.document.body().unwrap());
+    // The title heads the article. Scripts, mostly-link lists, and the page's own classes,
+    // ids, and handlers are gone.
+    assert_eq!(body, format!("<body><article><h1>The News</h1><div><h1>Headline</h1><p>{0}</p><p>{0}</p>\
+                              <img src=\"a.png\" alt=\"A picture\"></div></article></body>", PARAGRAPH));
+    assert_eq!(article.document.tree.text_content(article.document.head().unwrap()), "The News");
+}
+
+#[test]
+fn finds_nothing_in_pages_without_articles() {
+    assert!(readability::extract(&html::parse("<nav><a href=\"/\">Home</a></nav><p>Short.</p>").unwrap()).is_none());
+}
+
+#[test]
+fn opens_articles_in_reader_mode() {
+    let mut page: Page = Page::load(&cluttered_page(), "").unwrap();
+    page.set_viewport(640, 480);
+    page.set_zoom(1.5);
+    let reader: Page = page.reader_mode().unwrap();
+    assert_eq!((reader.viewport(), reader.zoom()), ((640, 480), 1.5));
+    assert_eq!(reader.stylesheet().to_string(), css::parse(readability::READER_CSS).unwrap().to_string());
+    assert!(reader.document().get_elements_by_class_name("sidebar").is_empty());
+    assert_eq!(reader.document().get_elements_by_tag_name("p").len(), 2);
+
+    assert!(Page::load("<p>Nothing to read.</p>", "").unwrap().reader_mode().is_none());
+}
@@ -0,0 +1,18 @@
+//! Runs the reference tests listed in `tests/reftests/reftest.list`.
+
+use build_a_browser_engine_in_rust::reftest::{self, Outcome, Reftest};
+use std::path::Path;
+
+#[test]
+fn reftests() {
+    let manifest: &Path = &Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/reftests/reftest.list");
+    let tests: Vec<Reftest> = reftest::load_manifest(manifest).unwrap_or_else(|err: String| panic!("{}", err));
+    assert!(!tests.is_empty(), "{} lists no tests", manifest.display());
+
+    let failures: Vec<String> = tests.iter()
+        .map(|test: &Reftest| (test, test.run()))
+        .filter(|(_, outcome): &(&Reftest, Outcome)| !outcome.passed())
+        .map(|(test, outcome): (&Reftest, Outcome)| format!("{}: {}", test, outcome))
+        .collect();
+    assert!(failures.is_empty(), "{} of {} reftests failed:\n{}", failures.len(), tests.len(), failures.join("\n"));
+}
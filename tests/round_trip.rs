@@ -0,0 +1,256 @@
+//! Property tests: random stylesheets and DOM trees survive being written out and parsed
+//! back, i.e. `parse(serialize(x)) == x`.
+//!
+//! The cases come from a fixed seed, so every run tries the same ones. A failure prints
+//! the case number and the source that didn't round-trip; to try more cases while
+//! working on a parser, set `ROUND_TRIP_CASES`:
+//!
+//!     ROUND_TRIP_CASES=100000 cargo test --release --test round_trip
+//!
+//! The generators only make what the parsers are meant to handle, so each limitation of
+//! the parsers shows up as a rule here: text never starts with whitespace (the HTML parser
+//! skips whitespace before each node), and CSS identifiers never start with a digit
+//! (a value starting with one is a length).
+
+use build_a_browser_engine_in_rust::atom::Atom;
+use build_a_browser_engine_in_rust::css::{self, Color, Declaration, PseudoClass, PseudoElement, Rule, Selector, SimpleSelector, Stylesheet, Unit, Value};
+use build_a_browser_engine_in_rust::dom::{self, NodeId, NodeType, Tree};
+use build_a_browser_engine_in_rust::html;
+use std::env;
+
+/// How many cases each test tries, unless `ROUND_TRIP_CASES` says otherwise.
+const CASES: usize = 500;
+
+fn cases() -> usize {
+    env::var("ROUND_TRIP_CASES").ok().and_then(|n: String| n.parse().ok()).unwrap_or(CASES)
+}
+
+/// A xorshift64* generator: fast, and the same numbers on every machine.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// A number in `0..n`.
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    /// True `percent`% of the time.
+    fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+
+    fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.below(items.len())]
+    }
+
+    /// A string of `min..=max` characters from `chars`.
+    fn string(&mut self, chars: &[char], min: usize, max: usize) -> String {
+        let length: usize = min + self.below(max - min + 1);
+        (0..length).map(|_| *self.pick(chars)).collect()
+    }
+}
+
+fn chars(s: &str) -> Vec<char> {
+    s.chars().collect()
+}
+
+
+// Stylesheets
+
+/// A CSS identifier: letters, digits, `-`, and `_`, but not starting with a digit.
+fn identifier(rng: &mut Rng) -> String {
+    let first: String = rng.string(&chars("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ-_"), 1, 1);
+    first + &rng.string(&chars("abcdefghijklmnopqrstuvwxyz0123456789-_"), 0, 8)
+}
+
+fn value(rng: &mut Rng) -> Value {
+    match rng.below(7) {
+        // Lists hold two or more items.
+        5 => Value::List((0..2 + rng.below(3)).map(|_| item(rng)).collect()),
+        6 => sequence(rng),
+        _ => single_value(rng),
+    }
+}
+
+/// An item of a list, or an argument of a function: a single value or a sequence.
+fn item(rng: &mut Rng) -> Value {
+    if rng.chance(25) { sequence(rng) } else { single_value(rng) }
+}
+
+/// Two or more single values.
+fn sequence(rng: &mut Rng) -> Value {
+    Value::Sequence((0..2 + rng.below(3)).map(|_| single_value(rng)).collect())
+}
+
+fn single_value(rng: &mut Rng) -> Value {
+    match rng.below(7) {
+        6 => Value::Function(Atom::from(identifier(rng)), (0..rng.below(3)).map(|_| item(rng)).collect()),
+        0 => Value::Keyword(Atom::from(identifier(rng))),
+        // Round numbers, fractions, and any finite non-negative float at all (the parser
+        // has no minus sign), including subnormals and `f32::MAX`.
+        1 => Value::Length((rng.below(2000) as f32) / (1 << rng.below(6)) as f32, Unit::Px),
+        2 => Value::Length(f32::from_bits(rng.next() as u32 & 0x7f7f_ffff), Unit::Px),
+        5 => Value::Number((rng.below(2000) as f32) / (1 << rng.below(6)) as f32),
+        // Strings, with quotes and backslashes to escape.
+        3 => Value::StringValue(rng.string(&chars("ab Z0-;{}:,'\"\\é"), 0, 10)),
+        _ => {
+            let a: u8 = if rng.chance(70) { 255 } else { rng.below(256) as u8 };
+            Value::ColorValue(Color { r: rng.below(256) as u8, g: rng.below(256) as u8, b: rng.below(256) as u8, a })
+        }
+    }
+}
+
+fn selector(rng: &mut Rng) -> Selector {
+    let tag_name: Option<Atom> = if rng.chance(60) { Some(Atom::from(identifier(rng))) } else { None };
+    let id: Option<Atom> = if rng.chance(25) { Some(Atom::from(identifier(rng))) } else { None };
+    let class: Vec<Atom> = (0..rng.below(4)).map(|_| Atom::from(identifier(rng))).collect();
+    let pseudo_classes: Vec<PseudoClass> = (0..rng.below(3)).map(|_| *rng.pick(&[PseudoClass::Hover, PseudoClass::Active, PseudoClass::Focus])).collect();
+    let pseudo_element: Option<PseudoElement> = rng.chance(20).then(|| *rng.pick(&[PseudoElement::Before, PseudoElement::After]));
+    Selector::Simple(SimpleSelector { tag_name, id, class, pseudo_classes, pseudo_element })
+}
+
+fn stylesheet(rng: &mut Rng) -> Stylesheet {
+    let rules: Vec<Rule> = (0..rng.below(8)).map(|_| {
+        let mut selectors: Vec<Selector> = (0..1 + rng.below(4)).map(|_| selector(rng)).collect();
+        // The parser orders each rule's selectors by specificity.
+        selectors.sort_by_key(Selector::specificity);
+        let declarations: Vec<Declaration> = (0..rng.below(6))
+            .map(|_| Declaration { name: Atom::from(identifier(rng)), value: value(rng) })
+            .collect();
+        let media: Option<Vec<Atom>> = rng.chance(20)
+            .then(|| (0..1 + rng.below(2)).map(|_| Atom::from(*rng.pick(&["screen", "print", "all"]))).collect());
+        Rule { selectors, declarations, media }
+    }).collect();
+    Stylesheet { rules }
+}
+
+#[test]
+fn stylesheets_round_trip() {
+    let mut rng: Rng = Rng(0x5eed_c55e);
+    for case in 0..cases() {
+        let stylesheet: Stylesheet = stylesheet(&mut rng);
+        let source: String = stylesheet.to_string();
+        let parsed: Stylesheet = css::parse(&source).unwrap_or_else(|err| panic!("case {}: {}\n{}", case, err, source));
+        assert!(parsed == stylesheet, "case {} didn't round-trip:\n{}\nparsed as:\n{}", case, source, parsed);
+    }
+}
+
+
+// DOM trees
+
+const TAGS: [&str; 12] = ["div", "p", "span", "ul", "li", "em", "b", "section", "a", "x-widget", "br", "img"];
+
+/// Text for a text node: anything but `<`, starting with something other than whitespace.
+fn text(rng: &mut Rng) -> String {
+    let first: String = rng.string(&chars("abcXYZ019.,;:!?'\"&>=-é√🙂"), 1, 1);
+    first + &rng.string(&chars("abc XYZ 019.,;:!?'\"&>=-\n\t é√🙂"), 0, 20)
+}
+
+/// An element with random attributes and up to `depth` levels of children. Text nodes
+/// are never next to each other, since the parser would read them as one.
+fn element(rng: &mut Rng, tree: &mut Tree, depth: usize) -> NodeId {
+    let mut attributes: dom::AttributeMap = dom::AttributeMap::default();
+    for _ in 0..rng.below(4) {
+        let name: String = rng.string(&chars("abcdefghijklmnopqrstuvwxyz"), 1, 1) + &rng.string(&chars("abcxyz0123-"), 0, 6);
+        // Anything but the closing quote, even markup.
+        attributes.insert(Atom::from(name), rng.string(&chars("abc XYZ 019<>='&/é\n"), 0, 12));
+    }
+    let tag_name: Atom = Atom::from(*rng.pick(&TAGS));
+    let mut children: Vec<NodeId> = Vec::new();
+    if depth > 0 && !html::is_void_element(&tag_name) {
+        for _ in 0..rng.below(5) {
+            let after_text: bool = children.last().is_some_and(|&child: &NodeId| matches!(tree[child].node_type, NodeType::Text(_)));
+            let child: NodeId = if after_text || rng.chance(50) {
+                element(rng, tree, depth - 1)
+            } else {
+                let text: String = text(rng);
+                tree.text(text)
+            };
+            children.push(child);
+        }
+    }
+    tree.element(tag_name, attributes, children)
+}
+
+/// Write `node` and its descendants as HTML, the way the parser reads it back.
+fn to_html(tree: &Tree, node: NodeId, html: &mut String) {
+    match tree[node].node_type {
+        NodeType::Element(ref element) => {
+            html.push('<');
+            html.push_str(&element.tag_name);
+            for (name, value) in &element.attributes {
+                // Each of the ways to write a value that can hold this one.
+                let value: String = value.replace('&', "&amp;");
+                if value.is_empty() {
+                    html.push_str(&format!(" {}", name));
+                } else if !value.contains(|c: char| c.is_ascii_whitespace() || "\"'<>=`".contains(c)) {
+                    html.push_str(&format!(" {}={}", name, value));
+                } else if !value.contains('\'') {
+                    html.push_str(&format!(" {}='{}'", name, value));
+                } else {
+                    html.push_str(&format!(" {}=\"{}\"", name, value));
+                }
+            }
+            html.push('>');
+            if html::is_void_element(&element.tag_name) {
+                return;
+            }
+            for child in tree.children(node) {
+                to_html(tree, child, html);
+            }
+            html.push_str(&format!("</{}>", element.tag_name));
+        }
+        NodeType::Text(ref text) => html.push_str(&text.replace('&', "&amp;")),
+        NodeType::DocumentFragment => {}
+    }
+}
+
+#[test]
+fn dom_trees_round_trip() {
+    let mut rng: Rng = Rng(0xd0c7_7ee5);
+    for case in 0..cases() {
+        let mut tree: Tree = Tree::new();
+        let root: NodeId = element(&mut rng, &mut tree, 4);
+        let mut source: String = String::new();
+        to_html(&tree, root, &mut source);
+        let parsed: dom::Document = html::parse(&source).unwrap_or_else(|err| panic!("case {}: {}\n{}", case, err, source));
+        // The parser puts the tree in the `<body>` it makes for it.
+        let body: Vec<NodeId> = parsed.body().map(|body: NodeId| parsed.tree.children(body).collect()).unwrap_or_default();
+        assert!(
+            body.len() == 1 && parsed.tree.tree_eq(body[0], &tree, root),
+            "case {} didn't round-trip:\n{}\nparsed as:\n{}", case, source, dom::dump(&parsed.tree, parsed.root_element)
+        );
+    }
+}
+
+#[test]
+fn serialized_trees_round_trip() {
+    let mut rng: Rng = Rng(0x5e71_a112);
+    for case in 0..cases() {
+        let mut tree: Tree = Tree::new();
+        let root: NodeId = element(&mut rng, &mut tree, 4);
+        // Markup in text, which only the serializer escapes.
+        let texts: Vec<NodeId> = tree.descendants(root).filter(|&node: &NodeId| matches!(tree[node].node_type, NodeType::Text(_))).collect();
+        for node in texts {
+            let extra: String = rng.string(&chars("<>&\u{A0}\"ab </p>"), 0, 8);
+            if let NodeType::Text(ref mut text) = tree[node].node_type {
+                text.push_str(&extra);
+            }
+        }
+        let source: String = html::serialize(&tree, root);
+        let parsed: dom::Document = html::parse(&source).unwrap_or_else(|err| panic!("case {}: {}\n{}", case, err, source));
+        let body: Vec<NodeId> = parsed.body().map(|body: NodeId| parsed.tree.children(body).collect()).unwrap_or_default();
+        assert!(
+            body.len() == 1 && parsed.tree.tree_eq(body[0], &tree, root),
+            "case {} didn't round-trip:\n{}\nparsed as:\n{}", case, source, dom::dump(&parsed.tree, parsed.root_element)
+        );
+        assert_eq!(parsed.tree.to_html(body[0]), source, "case {}", case);
+    }
+}
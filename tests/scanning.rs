@@ -0,0 +1,53 @@
+//! Checks the byte scanners the parsers use, and that scanning by bytes keeps the parsers
+//! on character boundaries in text that isn't ASCII.
+
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::dom::{Document, NodeId};
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::scan;
+
+#[test]
+fn finds_bytes_and_skips_runs() {
+    assert_eq!(scan::until("hello <b>", b'<'), 6);
+    assert_eq!(scan::until("no tags", b'<'), 7);
+    assert_eq!(scan::until("", b'<'), 0);
+    // Multi-byte characters before the byte count by their length in bytes.
+    assert_eq!(scan::until("caf\u{e9} \u{1F600}<", b'<'), "caf\u{e9} \u{1F600}".len());
+
+    assert_eq!(scan::whitespace("  \n x"), 4);
+    assert_eq!(scan::whitespace("\t\r\x0c"), 3);
+    assert_eq!(scan::whitespace("x "), 0);
+    // Non-ASCII whitespace is whitespace too; other non-ASCII characters aren't.
+    assert_eq!(scan::whitespace(" \u{a0}\u{3000}x"), 1 + 2 + 3);
+    assert_eq!(scan::whitespace("\u{e9} "), 0);
+
+    assert_eq!(scan::ascii_run("font-size: 12px", |byte: u8| byte.is_ascii_alphabetic() || byte == b'-'), 9);
+    assert_eq!(scan::ascii_run("abc", |byte: u8| byte.is_ascii_alphabetic()), 3);
+    // A run stops at the first non-ASCII byte, whatever the test says.
+    assert_eq!(scan::ascii_run("ab\u{e9}c", |_: u8| true), 2);
+}
+
+#[test]
+fn parses_text_that_is_not_ascii() {
+    let document: Document = html::parse("<p title=\"\u{1F600}\">\u{65e5}\u{672c}\u{8a9e}<b>\u{e9}</b>\u{2014}</p>\u{3000}<p>x</p>").unwrap();
+    assert_eq!(html::serialize(&document.tree, document.root_element),
+               "<html><head></head><body><p title=\"\u{1F600}\">\u{65e5}\u{672c}\u{8a9e}<b>\u{e9}</b>\u{2014}</p><p>x</p></body></html>");
+
+    let stylesheet: css::Stylesheet = css::parse("p\u{3000}{\u{a0}color:\u{2003}#ff0000; }").unwrap();
+    assert_eq!(stylesheet.rules.len(), 1);
+    assert_eq!(stylesheet.rules[0].declarations[0].name, "color");
+}
+
+#[test]
+fn parses_multi_megabyte_documents() {
+    let paragraph: &str = "<p class=\"para\" id=\"p\">Lorem ipsum dolor sit amet, consectetur \u{e9}l\u{ee}t.</p>\n";
+    let source: String = paragraph.repeat(2 * 1024 * 1024 / paragraph.len());
+    assert!(source.len() > 2_000_000);
+    let document: Document = html::parse(&source).unwrap();
+    let body: NodeId = document.tree.children(document.root_element).nth(1).unwrap();
+    assert_eq!(document.tree.children(body).count(), source.len() / paragraph.len());
+
+    let rule: &str = ".para { color: #336699; margin: 4px; }\n";
+    let stylesheet: css::Stylesheet = css::parse(&rule.repeat(1024 * 1024 / rule.len())).unwrap();
+    assert_eq!(stylesheet.rules.len(), 1024 * 1024 / rule.len());
+}
@@ -0,0 +1,55 @@
+//! Checks that a stylesheet scoped to an element styles only its descendants, and that
+//! the document's rules stay out of them.
+
+use build_a_browser_engine_in_rust::css::{self, Color, Value};
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+
+const HTML: &str = "<html><body><p class=\"title\">Page</p>\
+    <div class=\"card\"><p class=\"title\">Card</p><span>Text</span>\
+    <div class=\"inner\"><p class=\"title\">Inner</p></div></div></body></html>";
+
+const CSS: &str = ".title { color: #ff0000; } .card { width: 200px; } span { color: #00ff00; }";
+
+/// The color of `node`, as the page styles it.
+fn color(page: &Page, node: NodeId) -> Option<Value> {
+    page.computed_value(node, "color")
+}
+
+fn rgb(r: u8, g: u8, b: u8) -> Option<Value> {
+    Some(Value::ColorValue(Color { r, g, b, a: 255 }))
+}
+
+#[test]
+fn scoped_rules_match_only_inside_their_scope() {
+    let mut page: Page = Page::load(HTML, CSS).unwrap();
+    let titles: Vec<NodeId> = page.document().get_elements_by_class_name("title");
+    let card: NodeId = page.document().get_elements_by_class_name("card")[0];
+    let span: NodeId = page.document().get_elements_by_tag_name("span")[0];
+    assert_eq!(color(&page, titles[1]), rgb(255, 0, 0));
+
+    page.attach_scoped_stylesheet(card, css::parse(".title { color: #0000ff; } .card { width: 10px; }").unwrap());
+    // Outside the scope, and on its element, the page's rules apply; the scope's don't.
+    assert_eq!(color(&page, titles[0]), rgb(255, 0, 0));
+    assert_eq!(page.computed_value(card, "width"), Some(Value::Length(200.0, css::Unit::Px)));
+    // Inside, only the scope's rules do.
+    assert_eq!(color(&page, titles[1]), rgb(0, 0, 255));
+    assert_eq!(color(&page, titles[2]), rgb(0, 0, 255));
+    assert_eq!(color(&page, span), None);
+
+    // A scope inside another replaces it.
+    let inner: NodeId = page.document().get_elements_by_class_name("inner")[0];
+    page.attach_scoped_stylesheet(inner, css::parse("p { color: #000000; }").unwrap());
+    assert_eq!(color(&page, titles[1]), rgb(0, 0, 255));
+    assert_eq!(color(&page, titles[2]), rgb(0, 0, 0));
+
+    assert!(page.detach_scoped_stylesheet(card).is_some());
+    assert!(page.detach_scoped_stylesheet(card).is_none());
+    assert_eq!(color(&page, titles[1]), rgb(255, 0, 0));
+    assert_eq!(color(&page, span), rgb(0, 255, 0));
+
+    // Scopes belong to the document's nodes, so a new document has none.
+    page.set_document(html::parse(HTML).unwrap());
+    assert_eq!(color(&page, titles[2]), rgb(255, 0, 0));
+}
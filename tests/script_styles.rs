@@ -0,0 +1,95 @@
+//! Checks that scripts edit inline styles through `element.style` and classes through
+//! `element.classList`, and that the page restyles what they changed.
+//!
+//! Run with `cargo test --features js --test script_styles`.
+
+#![cfg(feature = "js")]
+
+use build_a_browser_engine_in_rust::css::Color;
+use build_a_browser_engine_in_rust::dom::{self, Document, NodeId};
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::script::{Changes, ScriptHost};
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+
+/// A 20x40 page with two 10px boxes, `#a` above `#b`.
+fn load() -> Page {
+    let mut page: Page = Page::load("<div id=\"a\"></div><div id=\"b\"></div>",
+                                    "head { display: none; } html, body, div { display: block; } body { margin: 0; } \
+                                     div { height: 10px; } #b { background: #ff0000; } #b.blue { background: #0000ff; }").unwrap();
+    page.set_viewport(20, 40);
+    page
+}
+
+/// Run `source` on the page, returning its value as a string.
+fn run(page: &mut Page, source: &str) -> String {
+    page.execute_script(source).unwrap().to_string()
+}
+
+fn pixel(page: &mut Page, x: usize, y: usize) -> Color {
+    let width: usize = page.paint().width;
+    page.paint().pixels[y * width + x]
+}
+
+#[test]
+fn edits_inline_styles() {
+    let mut page: Page = load();
+    let a: NodeId = page.document().get_element_by_id("a").unwrap();
+    run(&mut page, "const a = document.getElementById('a'); a.style.setProperty('color', 'red'); a.style.marginTop = '4px';");
+    assert_eq!(page.document().tree.to_html(a), "<div id=\"a\" style=\"color: red; margin-top: 4px\"></div>");
+    assert_eq!(run(&mut page, "a.style.getPropertyValue('margin-top') + ' ' + a.style.color"), "4px red");
+
+    // Declarations the inline parser rejects are dropped.
+    run(&mut page, "a.style.removeProperty('color'); a.style.setProperty('width', '!!');");
+    assert_eq!(run(&mut page, "a.style.cssText"), "margin-top: 4px");
+    // `cssText` replaces the whole attribute, and only the valid declarations count.
+    run(&mut page, "a.style.cssText = 'height: 30px; bogus';");
+    assert_eq!(run(&mut page, "a.getAttribute('style') + ' / ' + a.style.length + ' / ' + a.style.marginTop"), "height: 30px; bogus / 1 / ");
+}
+
+#[test]
+fn edits_class_lists() {
+    let mut page: Page = load();
+    assert_eq!(run(&mut page, "\
+        const list = document.getElementById('a').classList;
+        list.add('x', 'y');
+        list.remove('x');
+        const added = list.toggle('z');
+        const removed = list.toggle('y');
+        list.replace('z', 'w');
+        [added, removed, list.contains('w'), list.length, list.value].join()"), "true,false,true,1,w");
+    assert_eq!(page.document().get_elements_by_class_name("w"), [page.document().get_element_by_id("a").unwrap()]);
+}
+
+#[test]
+fn repaints_and_lays_out_what_scripts_change() {
+    let mut page: Page = load();
+    assert_eq!((pixel(&mut page, 5, 15), pixel(&mut page, 5, 25)), (RED, WHITE));
+
+    // A class that only changes the color.
+    run(&mut page, "document.getElementById('b').classList.add('blue');");
+    assert_eq!(pixel(&mut page, 5, 15), BLUE);
+
+    // A style that makes `#a` taller moves `#b` down.
+    run(&mut page, "document.getElementById('a').style.height = '20px';");
+    assert_eq!((pixel(&mut page, 5, 15), pixel(&mut page, 5, 25)), (WHITE, BLUE));
+}
+
+#[test]
+fn records_restyled_elements_as_they_were() {
+    let mut document: Document = html::parse("<p id=\"p\" class=\"old\">text</p>").unwrap();
+    let p: NodeId = document.get_element_by_id("p").unwrap();
+    let mut host: ScriptHost = ScriptHost::new();
+    host.eval(&mut document, "const p = document.getElementById('p'); p.classList.add('new'); p.style.color = 'red';").unwrap();
+    let changes: Changes = host.take_changes();
+    assert!(!changes.other);
+    let before: &dom::Element = &changes.restyled[&p];
+    assert_eq!((before.attr("class"), before.attr("style")), (Some("old"), None));
+    assert!(host.take_changes().is_empty());
+
+    host.eval(&mut document, "p.textContent = 'new text';").unwrap();
+    assert!(host.take_changes().other);
+}
@@ -0,0 +1,89 @@
+//! Checks the JavaScript interpreter on its own, and a page running its `<script>`
+//! elements in document order against the DOM.
+//!
+//! Run with `cargo test --features js --test scripts`.
+
+#![cfg(feature = "js")]
+
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::js::{NoHost, Runtime, ScriptError, Value};
+use build_a_browser_engine_in_rust::script::{ConsoleLevel, ConsoleMessage};
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+/// Run `source` without a document, returning its value as `String(value)`, or the error.
+fn eval(source: &str) -> Result<String, String> {
+    Runtime::new().eval(source, &mut NoHost).map(|value: Value| value.to_string()).map_err(|error: ScriptError| error.to_string())
+}
+
+/// The text of every console message a page wrote, with its level.
+fn console(page: &Page) -> Vec<(ConsoleLevel, &str)> {
+    page.console_messages().iter().map(|message: &ConsoleMessage| (message.level, message.text.as_str())).collect()
+}
+
+#[test]
+fn runs_everyday_javascript() {
+    assert_eq!(eval("[1, 2, 3].map(x => x * 2).join()").unwrap(), "2,4,6");
+    assert_eq!(eval("function counter() { let n = 0; return () => ++n; } const next = counter(); next(); next()").unwrap(), "2");
+    assert_eq!(eval("const who = 'world'; `hello ${who.toUpperCase()}!`").unwrap(), "hello WORLD!");
+    assert_eq!(eval("let out = []; for (const k in { a: 1, b: 2 }) out.push(k); out.join('')").unwrap(), "ab");
+    assert_eq!(eval("function sum(...xs) { return xs.reduce((a, b) => a + b, 0); } sum(...[1, 2], 3)").unwrap(), "6");
+    assert_eq!(eval("try { null.x } catch (e) { e.name }").unwrap(), "TypeError");
+    assert_eq!(eval("JSON.stringify({ a: [1, 'b', null], c: true })").unwrap(), "{\"a\":[1,\"b\",null],\"c\":true}");
+}
+
+#[test]
+fn reports_errors_and_runaway_scripts() {
+    assert_eq!(eval("throw new Error('boom')").unwrap_err(), "Uncaught Error: boom");
+    assert!(eval("let = ;").unwrap_err().starts_with("Uncaught SyntaxError: "));
+    assert_eq!(eval("undefinedFunction()").unwrap_err(), "Uncaught ReferenceError: undefinedFunction is not defined");
+    assert_eq!(eval("while (true) {}").unwrap_err(), "Uncaught RangeError: Script ran for too long");
+    // Each JavaScript call takes several Rust frames, and unoptimized ones are big, so give
+    // the deepest recursion as much stack as a main thread gets.
+    let deep: Result<String, String> = thread::Builder::new().stack_size(8 << 20)
+        .spawn(|| eval("function f() { return f(); } f()"))
+        .unwrap()
+        .join()
+        .unwrap();
+    assert_eq!(deep.unwrap_err(), "Uncaught RangeError: Maximum call stack size exceeded");
+}
+
+#[test]
+fn runs_page_scripts_in_document_order() {
+    let path: PathBuf = std::env::temp_dir().join(format!("scripts-{}.js", std::process::id()));
+    fs::write(&path, "order.push('external'); document.getElementById('out').textContent = order.join(' ');").unwrap();
+    let html: String = format!("<p id=\"out\"></p><script>var order = ['first'];</script>\
+                                <script type=\"application/json\">{{\"not\": \"run\"}}</script>\
+                                <script>order.push('second'); throw new Error('oops');</script>\
+                                <script>order.push('third');</script><script src=\"{}\"></script>\
+                                <script src=\"/no/such/script.js\"></script>", path.display());
+    let page: Page = Page::load(&html, "").unwrap();
+    let out: &str = &page.document().tree.text_content(page.document().get_element_by_id("out").unwrap());
+    assert_eq!(out, "first second third external");
+    // An uncaught exception doesn't stop the next script. Scripts are all fetched before
+    // the first one runs, so the failed load is reported first.
+    assert_eq!(console(&page), [(ConsoleLevel::Error, "Failed to load script /no/such/script.js"),
+                                (ConsoleLevel::Error, "Uncaught Error: oops (in inline script)")]);
+}
+
+#[test]
+fn scripts_build_and_read_the_dom() {
+    let mut page: Page = Page::load("<ul id=\"list\"><li>a</li></ul>", "").unwrap();
+    let value: Value = page.execute_script("\
+        const list = document.getElementById('list');
+        for (const text of ['b', 'c']) {
+            const item = document.createElement('li');
+            item.appendChild(document.createTextNode(text));
+            list.appendChild(item);
+        }
+        list.children.map(item => item.textContent).join() + ' ' + (list.firstElementChild.parentNode === list)").unwrap();
+    assert_eq!(value.to_string(), "a,b,c true");
+    let list: NodeId = page.document().get_element_by_id("list").unwrap();
+    assert_eq!(page.document().tree.to_html(list), "<ul id=\"list\"><li>a</li><li>b</li><li>c</li></ul>");
+    assert_eq!(page.query_selector_all("li").len(), 3);
+
+    assert_eq!(page.execute_script("document.querySelector('li').tagName").unwrap().to_string(), "LI");
+    assert_eq!(page.execute_script("document.body.nope.nope").unwrap_err().to_string(), "Uncaught TypeError: Cannot read properties of undefined (reading 'nope')");
+}
@@ -0,0 +1,90 @@
+//! Checks scrolling the document and `overflow` elements: positions are clamped to what
+//! can scroll, and a scrolled canvas, repainted only where it moved, matches one painted
+//! from scratch.
+
+use build_a_browser_engine_in_rust::css::Color;
+use build_a_browser_engine_in_rust::dom::NodeId;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::layout::Rect;
+use build_a_browser_engine_in_rust::painting::Canvas;
+
+/// Stripes 10px tall, alternating red and blue, 200px in all, in a 50x40 viewport.
+fn striped_page() -> Page {
+    let stripes: String = (0..20).map(|i: usize| format!("<div class=\"{}\"></div>", ["r", "b"][i % 2])).collect();
+    let mut page: Page = Page::load(&stripes, "head { display: none; } html, body, div { display: block; } body { margin: 0; } \
+                                               div { height: 10px; width: 80px; } .r { background: #ff0000; } .b { background: #0000ff; }").unwrap();
+    page.set_viewport(50, 40);
+    page
+}
+
+/// The color at (x, y) in `canvas`.
+fn pixel(canvas: &Canvas, x: usize, y: usize) -> Color {
+    canvas.pixels[y * canvas.width + x]
+}
+
+#[test]
+fn clamps_the_document_scroll_position() {
+    let mut page: Page = striped_page();
+    // The document is as wide as the viewport; the stripes overflowing it don't count.
+    assert_eq!(page.max_scroll(), (0.0, 160.0));
+    page.scroll_to(10.0, 25.0);
+    assert_eq!(page.scroll_position(), (0.0, 25.0));
+    page.scroll_by(0.0, 1000.0);
+    assert_eq!(page.scroll_position(), (0.0, 160.0));
+    page.scroll_to(-5.0, -5.0);
+    assert_eq!(page.scroll_position(), (0.0, 0.0));
+}
+
+#[test]
+fn repaints_only_what_scrolls_into_view() {
+    let mut page: Page = striped_page();
+    page.paint();
+    for (x, y) in [(0.0, 15.0), (0.0, 37.0), (0.0, 160.0), (0.0, 20.0), (0.0, 2.0)] {
+        page.scroll_to(x, y);
+        let mut fresh: Page = striped_page();
+        fresh.scroll_to(x, y);
+        assert!(page.paint().pixels == fresh.paint().pixels, "scrolled to {:?}", (x, y));
+    }
+    // Scrolled down 2px, the first red stripe has 8px left showing.
+    assert_eq!((pixel(page.paint(), 0, 7), pixel(page.paint(), 0, 8)), (Color { r: 255, g: 0, b: 0, a: 255 }, Color { r: 0, g: 0, b: 255, a: 255 }));
+}
+
+#[test]
+fn scrolls_overflow_elements() {
+    let mut page: Page = Page::load(
+        "<div id=\"box\"><p></p><p></p><p></p></div><div id=\"after\"></div>",
+        "head { display: none; } html, body, div, p { display: block; } body, p { margin: 0; } \
+         #box { height: 20px; overflow: auto; } p { height: 15px; } #after { height: 100px; }").unwrap();
+    page.set_viewport(50, 40);
+    let scroller: NodeId = page.document().get_element_by_id("box").unwrap();
+    let after: NodeId = page.document().get_element_by_id("after").unwrap();
+
+    page.set_element_scroll_position(scroller, 0.0, 100.0);
+    assert_eq!(page.element_scroll_position(scroller), (0.0, 25.0));
+    // Elements that don't scroll ignore it.
+    page.set_element_scroll_position(after, 0.0, 10.0);
+    assert_eq!(page.element_scroll_position(after), (0.0, 0.0));
+
+    // The wheel scrolls the innermost thing under the pointer that can still move, and
+    // the document once the element can't.
+    page.set_element_scroll_position(scroller, 0.0, 20.0);
+    assert!(page.scroll_at(5.0, 5.0, 0.0, 10.0));
+    assert_eq!((page.element_scroll_position(scroller), page.scroll_position()), ((0.0, 25.0), (0.0, 0.0)));
+    assert!(page.scroll_at(5.0, 5.0, 0.0, 10.0));
+    assert_eq!((page.element_scroll_position(scroller), page.scroll_position()), ((0.0, 25.0), (0.0, 10.0)));
+    assert!(page.scroll_at(5.0, 30.0, 0.0, 1000.0));
+    assert_eq!(page.scroll_position(), (0.0, 80.0));
+    assert!(!page.scroll_at(5.0, 30.0, 0.0, 10.0));
+}
+
+#[test]
+fn scrolls_elements_into_view() {
+    let mut page: Page = striped_page();
+    let divs: Vec<NodeId> = page.document().get_elements_by_tag_name("div");
+    assert_eq!(page.element_rect(divs[5]).map(|rect: Rect| (rect.y, rect.height)), Some((50.0, 10.0)));
+    assert!(page.scroll_node_into_view(divs[5]));
+    assert_eq!(page.scroll_position(), (0.0, 50.0));
+    // Near the end, it scrolls as far as it can.
+    assert!(page.scroll_node_into_view(divs[19]));
+    assert_eq!(page.scroll_position(), (0.0, 160.0));
+}
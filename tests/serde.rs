@@ -0,0 +1,66 @@
+//! Checks that documents and stylesheets survive a trip through JSON, and what the style
+//! and layout trees look like written out. Needs the `serde` feature:
+//!
+//!     cargo test --features serde --test serde
+
+#![cfg(feature = "serde")]
+
+use build_a_browser_engine_in_rust::css::{self, Stylesheet};
+use build_a_browser_engine_in_rust::dom::{Document, NodeId};
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::layout::LayoutBox;
+use build_a_browser_engine_in_rust::style::StyledNode;
+use serde_json::{json, Value};
+
+#[test]
+fn documents_round_trip_through_json() {
+    let mut document: Document = html::parse("<!DOCTYPE html><p id=\"a\" class=\"b c\">Hi <em>there</em></p>").unwrap();
+    document.url = Some("http://example.com/".to_string());
+    document.build_indexes();
+    let json: String = serde_json::to_string(&document).unwrap();
+    let mut copy: Document = serde_json::from_str(&json).unwrap();
+
+    assert!(copy.tree.tree_eq(copy.root_element, &document.tree, document.root_element));
+    assert_eq!((copy.doctype.as_deref(), copy.url.as_deref(), copy.quirks_mode), (Some("html"), Some("http://example.com/"), document.quirks_mode));
+    // Node ids are kept, but the indexes aren't written out; they can be built again.
+    let p: NodeId = document.get_element_by_id("a").unwrap();
+    assert!(!copy.has_indexes());
+    assert_eq!(copy.get_element_by_id("a"), Some(p));
+    copy.build_indexes();
+    assert_eq!(copy.get_elements_by_class_name("c"), [p]);
+
+    // Attributes are written as a map, in document order.
+    let value: Value = serde_json::to_value(&document.tree[p].node_type).unwrap();
+    assert_eq!(value, json!({ "Element": { "tag_name": "p", "attributes": { "id": "a", "class": "b c" } } }));
+}
+
+#[test]
+fn stylesheets_round_trip_through_json() {
+    let stylesheet: Stylesheet = css::parse("h1, div.note::before, a#top:hover { color: #ff000080; margin: 1.5px auto; content: \"x\"; }").unwrap();
+    let json: String = serde_json::to_string(&stylesheet).unwrap();
+    assert_eq!(serde_json::from_str::<Stylesheet>(&json).unwrap(), stylesheet);
+}
+
+#[test]
+fn writes_style_and_layout_trees() {
+    let page: Page = Page::load("<div><p>Hi</p></div>", "div, p { display: block; } p { height: 10px; }").unwrap();
+    let p: NodeId = page.document().get_elements_by_tag_name("p")[0];
+
+    let style: Value = page.with_style_tree(|root: &StyledNode| serde_json::to_value(root.find(p).unwrap()).unwrap());
+    assert_eq!(style["node_id"], json!(p.index()));
+    assert_eq!(style["specified_values"]["display"], json!({ "Keyword": "block" }));
+    assert_eq!(style["children"].as_array().unwrap().len(), 1);
+
+    // A box refers to its node by id, instead of repeating the style tree.
+    let layout: Value = page.with_layout_tree(|root: &LayoutBox| serde_json::to_value(root).unwrap());
+    let mut boxes: Vec<&Value> = vec![&layout];
+    while let Some(layout_box) = boxes.pop() {
+        if layout_box["box_type"] == json!({ "BlockNode": p.index() }) {
+            assert_eq!(layout_box["dimensions"]["content"]["height"], json!(10.0));
+            return;
+        }
+        boxes.extend(layout_box["children"].as_array().unwrap());
+    }
+    panic!("no box for the <p> in {}", layout);
+}
@@ -0,0 +1,125 @@
+//! Checks that a session's tabs navigate, go back and forward through their history, and
+//! keep the pages near the current one as they were left.
+
+use build_a_browser_engine_in_rust::dom::{Document, NodeId};
+use build_a_browser_engine_in_rust::engine::{Session, MAX_CACHED_PAGES};
+use std::fs;
+use std::path::PathBuf;
+
+const STYLE: &str = "<style>head { display: none; } html, body, a { display: block; } body { margin: 0; } a { height: 20px; }</style>";
+
+/// A directory with pages `0.html` to `count - 1.html`, each linking to the next.
+fn site(name: &str, count: usize) -> PathBuf {
+    let dir: PathBuf = std::env::temp_dir().join(format!("session-{}-{}", std::process::id(), name));
+    fs::create_dir_all(&dir).unwrap();
+    for i in 0..count {
+        fs::write(dir.join(format!("{}.html", i)), format!("<html><head>{}</head><body><a href=\"{}.html\">Page {}</a></body></html>", STYLE, i + 1, i)).unwrap();
+    }
+    dir
+}
+
+/// Mark the page showing, so we can tell it apart from a fresh load of the same URL.
+fn mark(session: &mut Session) {
+    let document: &mut Document = session.page_mut().unwrap().document_mut();
+    let root: NodeId = document.root_element;
+    document.set_attribute(root, "class", "visited");
+}
+
+fn is_marked(session: &Session) -> bool {
+    !session.page().unwrap().document().get_elements_by_class_name("visited").is_empty()
+}
+
+#[test]
+fn goes_back_and_forward_through_history() {
+    let dir: PathBuf = site("history", 3);
+    let first: String = dir.join("0.html").to_str().unwrap().to_string();
+    let mut session: Session = Session::new();
+    assert!(!session.back().unwrap());
+    session.open_tab(&first).unwrap();
+    mark(&mut session);
+
+    // Relative URLs are resolved against the page showing.
+    session.navigate("1.html").unwrap();
+    session.navigate("2.html").unwrap();
+    let urls: Vec<String> = (0..3).map(|i: usize| dir.join(format!("{}.html", i)).to_str().unwrap().to_string()).collect();
+    assert_eq!(session.tabs()[0].history(), (urls.iter().map(String::as_str).collect(), 2));
+    assert!(!session.forward().unwrap());
+
+    // Going back shows the page as it was left.
+    assert!(session.back().unwrap() && session.back().unwrap());
+    assert!(is_marked(&session));
+    assert!(!session.tabs()[0].can_go_back() && session.tabs()[0].can_go_forward());
+    assert!(session.forward().unwrap());
+    assert_eq!(session.tabs()[0].url(), urls[1]);
+
+    // Navigating drops the entries after the current one.
+    session.back().unwrap();
+    session.navigate("2.html").unwrap();
+    assert_eq!(session.tabs()[0].history().0, [urls[0].as_str(), urls[2].as_str()]);
+
+    // Reloading starts over.
+    session.back().unwrap();
+    assert!(session.reload().unwrap());
+    assert!(!is_marked(&session));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn keeps_only_the_nearest_pages() {
+    let dir: PathBuf = site("cache", MAX_CACHED_PAGES + 3);
+    let mut session: Session = Session::new();
+    session.open_tab(dir.join("0.html").to_str().unwrap()).unwrap();
+    mark(&mut session);
+    for i in 1..MAX_CACHED_PAGES + 3 {
+        session.navigate(&format!("{}.html", i)).unwrap();
+        mark(&mut session);
+    }
+    // The `MAX_CACHED_PAGES` pages before the current one are kept; older ones load again.
+    for _ in 0..MAX_CACHED_PAGES {
+        session.back().unwrap();
+        assert!(is_marked(&session));
+    }
+    session.back().unwrap();
+    assert!(!is_marked(&session));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn manages_tabs() {
+    let dir: PathBuf = site("tabs", 3);
+    let mut session: Session = Session::new();
+    assert_eq!(session.active_tab(), None);
+    // Navigating with no tabs opens one.
+    session.navigate(dir.join("0.html").to_str().unwrap()).unwrap();
+    assert_eq!(session.open_tab(dir.join("1.html").to_str().unwrap()).unwrap(), 1);
+    assert_eq!(session.open_tab(dir.join("2.html").to_str().unwrap()).unwrap(), 2);
+    assert_eq!(session.active_tab(), Some(2));
+
+    session.set_active_tab(1);
+    session.close_tab(1);
+    assert_eq!(session.active_tab(), Some(1));
+    assert!(session.tabs()[1].url().ends_with("2.html"));
+    session.close_tab(1);
+    assert_eq!(session.active_tab(), Some(0));
+    session.close_tab(0);
+    assert_eq!(session.active_tab(), None);
+    assert!(session.page().is_none());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn follows_clicked_links_and_survives_failed_loads() {
+    let dir: PathBuf = site("links", 2);
+    let mut session: Session = Session::new();
+    session.set_viewport(200, 100);
+    session.open_tab(dir.join("0.html").to_str().unwrap()).unwrap();
+    assert!(session.click_at(5.0, 5.0).unwrap());
+    assert!(session.tabs()[0].url().ends_with("1.html"));
+    assert_eq!(session.page().unwrap().viewport(), (200, 100));
+
+    // 1.html links to a page that doesn't exist, so the tab stays where it was.
+    assert!(session.click_at(5.0, 5.0).is_err());
+    assert!(session.tabs()[0].url().ends_with("1.html"));
+    assert_eq!(session.tabs()[0].history().0.len(), 2);
+    fs::remove_dir_all(&dir).unwrap();
+}
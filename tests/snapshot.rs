@@ -0,0 +1,61 @@
+//! Checks writing documents as JSON snapshots, and reading them back into the same tree.
+
+use build_a_browser_engine_in_rust::dom::{Document, NodeId};
+use build_a_browser_engine_in_rust::{html, snapshot};
+
+#[test]
+fn writes_the_documented_schema() {
+    let document: Document = html::parse("<p class=\"note\">Hi</p>").unwrap();
+    let p: NodeId = document.get_elements_by_tag_name("p")[0];
+    assert_eq!(snapshot::node_to_json(&document.tree, p),
+               "{\"type\":\"element\",\"tag\":\"p\",\"attrs\":{\"class\":\"note\"},\"children\":[{\"type\":\"text\",\"text\":\"Hi\"}]}");
+
+    let json: String = snapshot::to_json(&html::parse("<!DOCTYPE html><p>x</p>").unwrap());
+    assert!(json.starts_with("{\"version\":1,\"doctype\":\"html\",\"root\":{\"type\":\"element\",\"tag\":\"html\""), "{}", json);
+}
+
+#[test]
+fn round_trips_documents() {
+    let source: &str = "<!DOCTYPE html><html><head><title>T</title></head>\
+                        <body><div id=\"a\" data-x=\"1\"><p>say \"hi\"\\\tnow</p><br></div></body></html>";
+    let document: Document = html::parse(source).unwrap();
+    let json: String = snapshot::to_json(&document);
+    let read: Document = snapshot::from_json(&json).unwrap();
+    assert_eq!(read.doctype, document.doctype);
+    assert!(read.tree.tree_eq(read.root_element, &document.tree, document.root_element), "{}", read.to_html());
+    // Reading and writing again is stable.
+    let again: String = snapshot::to_json(&read);
+    assert_eq!(snapshot::to_json(&snapshot::from_json(&again).unwrap()), again);
+}
+
+#[test]
+fn reads_optional_and_unknown_keys() {
+    let json: &str = r#"{ "version": 1, "generator": "tool",
+        "root": { "type": "element", "tag": "ul", "extra": [1, true, null],
+                  "children": [ { "type": "element", "tag": "li" },
+                                { "type": "text", "text": "café \"x\"" },
+                                { "type": "fragment" } ] } }"#;
+    let document: Document = snapshot::from_json(json).unwrap();
+    assert_eq!(document.doctype, None);
+    assert_eq!(document.tree.to_html(document.root_element), "<ul><li></li>café \"x\"</ul>");
+    // Appending a fragment moves its children in, and this one has none.
+    assert_eq!(document.tree.children(document.root_element).count(), 2);
+}
+
+#[test]
+fn reports_bad_snapshots() {
+    let error = |json: &str| -> String { snapshot::from_json(json).unwrap_err().message };
+    assert_eq!(error("{\"root\":{\"type\":\"text\",\"text\":\"x\"}}"), "Missing \"version\"");
+    assert_eq!(error("{\"version\":2,\"root\":{\"type\":\"text\",\"text\":\"x\"}}"), "Unsupported snapshot version");
+    assert_eq!(error("{\"version\":1}"), "Missing \"root\"");
+    assert_eq!(error("[1]"), "Expected a snapshot object");
+    assert_eq!(error("{\"version\":1,\"root\":{\"type\":\"comment\"}}"), "Unknown node type \"comment\"");
+    assert_eq!(error("{\"version\":1,\"root\":{\"type\":\"element\",\"tag\":\"p\",\"attrs\":{\"n\":1}}}"),
+               "Expected a string value for attribute \"n\"");
+    assert_eq!(error("{\"version\":1,\"root\":{\"type\":\"text\"}}"), "Expected a string for \"text\"");
+
+    // Syntax errors say where they are.
+    let bad: snapshot::SnapshotError = snapshot::from_json("{\"version\" 1}").unwrap_err();
+    assert_eq!(bad.position, 11);
+    assert_eq!(bad.to_string(), format!("{} at byte 11", bad.message));
+}
@@ -0,0 +1,65 @@
+//! Checks that the tables from DOM nodes to styled nodes and layout boxes find the same
+//! nodes and boxes as searching the trees does.
+
+use build_a_browser_engine_in_rust::css::{self, Value};
+use build_a_browser_engine_in_rust::dom::{self, NodeId};
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::layout::{BoxTable, LayoutBox};
+use build_a_browser_engine_in_rust::style::{self, StyledNode, StyledTree};
+
+const HTML: &str = "<html><head><title>Hi</title></head><body><div id=\"a\">One <b>two</b></div>\
+    <p class=\"note\">Three</p><div id=\"hidden\"><i>four</i></div></body></html>";
+
+const CSS: &str = "head, #hidden { display: none; } html, body, div, p { display: block; }
+    b { color: #ff0000; } .note::before { content: \"Note: \"; color: #0000ff; }";
+
+/// The box generated by `node`, found by searching the layout tree.
+fn find_box<'b, 'a>(layout_box: &'b LayoutBox<'a>, node: NodeId) -> Option<&'b LayoutBox<'a>> {
+    if layout_box.node_id() == Some(node) {
+        return Some(layout_box);
+    }
+    layout_box.children.iter().find_map(|child: &LayoutBox| find_box(child, node))
+}
+
+#[test]
+fn finds_styled_nodes_by_dom_node() {
+    let document: dom::Document = html::parse(HTML).unwrap();
+    let stylesheet: css::Stylesheet = css::parse(CSS).unwrap();
+    let root: StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+    let styled: StyledTree = StyledTree::new(&root);
+
+    for node in document.tree.descendants(document.root_element) {
+        let indexed: Option<*const StyledNode> = styled.node_for(node).map(|styled: &StyledNode| styled as *const StyledNode);
+        assert_eq!(indexed, root.find(node).map(|found: &StyledNode| found as *const StyledNode), "node {}", node.index());
+    }
+    let b: NodeId = document.get_elements_by_tag_name("b")[0];
+    assert_eq!(styled.style_for(b).and_then(|values| values.get("color")), Some(&Value::ColorValue(css::Color { r: 255, g: 0, b: 0, a: 255 })));
+
+    // A node's entry is its own, not its `::before`'s.
+    let note: NodeId = document.get_elements_by_tag_name("p")[0];
+    assert_eq!(styled.node_for(note).unwrap().pseudo_element, None);
+    assert!(styled.style_for(note).unwrap().get("color").is_none());
+
+    // Nodes outside the styled subtree have no entry.
+    let body: NodeId = document.body().unwrap();
+    let subtree: StyledNode = style::style_tree(&document.tree, body, &stylesheet);
+    assert!(StyledTree::new(&subtree).node_for(document.root_element).is_none());
+}
+
+#[test]
+fn finds_layout_boxes_by_dom_node() {
+    let page: Page = Page::load(HTML, CSS).unwrap();
+    let document: &dom::Document = page.document();
+    page.with_layout_tree(|root: &LayoutBox| {
+        let boxes: BoxTable = BoxTable::new(root);
+        for node in document.tree.descendants(document.root_element) {
+            let indexed: Option<*const LayoutBox> = boxes.box_for(node).map(|found: &LayoutBox| found as *const LayoutBox);
+            assert_eq!(indexed, find_box(root, node).map(|found: &LayoutBox| found as *const LayoutBox), "node {}", node.index());
+        }
+        // Hidden elements have no boxes.
+        let hidden: NodeId = document.get_element_by_id("hidden").unwrap();
+        assert!(boxes.box_for(hidden).is_none());
+        assert!(boxes.box_for(document.get_elements_by_tag_name("div")[0]).is_some());
+    });
+}
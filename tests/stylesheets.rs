@@ -0,0 +1,83 @@
+//! Checks that a page's inline and linked stylesheets, loaded alongside its document, end
+//! up in document order.
+
+use build_a_browser_engine_in_rust::css::{self, Stylesheet};
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::{html, loader, net};
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+/// Serve `files` (path, body) over HTTP on a port of its own, returning its address.
+fn serve(files: &'static [(&'static str, &'static str)]) -> String {
+    let listener: TcpListener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address: String = format!("http://{}", listener.local_addr().unwrap());
+    thread::spawn(move || {
+        for mut stream in listener.incoming().map_while(Result::ok) {
+            let mut request: Vec<u8> = Vec::new();
+            let mut buffer: [u8; 1024] = [0; 1024];
+            while !request.ends_with(b"\r\n\r\n") {
+                match stream.read(&mut buffer) {
+                    Ok(0) | Err(_) => break,
+                    Ok(read) => request.extend_from_slice(&buffer[..read]),
+                }
+            }
+            let request: String = String::from_utf8_lossy(&request).into_owned();
+            let path: &str = request.split_whitespace().nth(1).unwrap_or("/");
+            let response: String = match files.iter().find(|&&(name, _)| name == path) {
+                Some((_, body)) => format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{}", body.len(), body),
+                None => "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string(),
+            };
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+    address
+}
+
+/// The selectors of `stylesheet`'s rules, in order.
+fn selectors(stylesheet: &Stylesheet) -> Vec<String> {
+    stylesheet.rules.iter().map(|rule: &css::Rule| rule.selectors[0].to_string()).collect()
+}
+
+const FILES: &[(&str, &str)] = &[
+    ("/", "<html><head><style>h1 { color: red; }</style>\
+           <link rel=\"stylesheet\" href=\"/css/a.css\">\
+           <base href=\"/css/\">\
+           <style>h2 { color: red; }</style>\
+           <link rel=\"stylesheet\" href=\"b.css\">\
+           <link rel=\"stylesheet\" href=\"missing.css\">\
+           <link rel=\"icon\" href=\"c.css\">\
+           </head><body><h3>Hi</h3><style>h3 { color: red; }</style></body></html>"),
+    ("/css/a.css", "p { color: blue; } a { color: blue; }"),
+    ("/css/b.css", "ul { color: green; }"),
+    ("/css/c.css", "ol { color: green; }"),
+];
+
+#[test]
+fn merges_stylesheets_in_document_order() {
+    let address: String = serve(FILES);
+    let client: Arc<Mutex<net::Client>> = Arc::new(Mutex::new(net::Client::new()));
+    let page: Page = Page::load_url_with_client(&format!("{}/", address), client).unwrap();
+    // Links resolve against the `<base>`, even one after them; missing sheets are left out.
+    assert_eq!(selectors(page.stylesheet()), ["h1", "p", "a", "h2", "ul", "h3"]);
+    assert_eq!(page.document().base_url, Some(format!("{}/css/", address)));
+}
+
+#[test]
+fn reports_the_first_error_in_document_order() {
+    static FILES: &[(&str, &str)] = &[("/broken.css", "p { color: red; } } broken")];
+    let address: String = serve(FILES);
+    let url: net::Url = net::Url::parse(&format!("{}/", address)).unwrap();
+    let client: Mutex<net::Client> = Mutex::new(net::Client::new());
+    let html: &str = "<link rel=\"stylesheet\" href=\"broken.css\"><style>p { color: }</style><p>Hi</p>";
+
+    let (document, stylesheet, _) = loader::with_stylesheets(html, &url, &client, || html::parse(html).unwrap());
+    assert!(document.tree.text_content(document.root_element).ends_with("Hi"));
+    let error: css::ParseError = stylesheet.unwrap_err();
+    assert_eq!(error, css::parse(FILES[0].1).unwrap_err());
+
+    // With nothing to load, the stylesheet is empty.
+    let (_, stylesheet, _) = loader::with_stylesheets("<p>Hi</p>", &url, &client, || ());
+    assert!(stylesheet.unwrap().rules.is_empty());
+}
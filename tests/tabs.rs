@@ -0,0 +1,41 @@
+//! Checks tab stops in preformatted text, with the stand-in font (every character,
+//! spaces and tabs included, half as wide as the font size).
+
+mod common;
+
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::layout::{LayoutBox, TextFragment};
+use common::fragments;
+
+/// The text fragments of `html`, laid out 400px wide, in 16px monospace text.
+fn lay_out(html: &str, css: &str) -> Vec<TextFragment> {
+    let css: String = format!("html, body, pre {{ display: block; }} body {{ margin: 0px; }} {}", css);
+    let mut page: Page = Page::load(html, &css).unwrap();
+    page.set_deterministic(true);
+    page.set_viewport(400, 100);
+    page.with_layout_tree(|root: &LayoutBox| {
+        let mut out: Vec<TextFragment> = Vec::new();
+        fragments(root, &mut out);
+        out
+    })
+}
+
+#[test]
+fn tabs_reach_the_next_tab_stop() {
+    // Spaces are 8px, so tab stops are 64px apart by default.
+    let lines: Vec<TextFragment> = lay_out("<html><pre>a\tbcd\tef\n\tg</pre></html>", "");
+    assert_eq!(lines[0].run.text, "a\tbcd\tef");
+    assert_eq!((lines[0].run.x_at(2), lines[0].run.x_at(6)), (64.0, 128.0));
+    assert_eq!((lines[1].run.text.as_str(), lines[1].run.x_at(1)), ("\tg", 64.0));
+
+    // `tab-size` is a number of spaces, or a length, and stops are measured from the start
+    // of the line, across elements.
+    let lines: Vec<TextFragment> = lay_out("<html><pre>ab<b>c\td</b></pre></html>", "pre { tab-size: 4; }");
+    assert_eq!((lines[1].run.text.as_str(), lines[1].run.x_at(2), lines[1].rect.x), ("c\td", 16.0, 16.0));
+    let lines: Vec<TextFragment> = lay_out("<html><pre>abcde\tf</pre></html>", "pre { tab-size: 20px; }");
+    assert_eq!(lines[0].run.x_at(6), 60.0);
+
+    // Tabs that aren't preserved collapse like any other white space.
+    let lines: Vec<TextFragment> = lay_out("<html><pre>a\t\tb</pre></html>", "pre { white-space: normal; }");
+    assert_eq!(lines[0].run.text, "a b");
+}
@@ -0,0 +1,55 @@
+//! Checks `text-transform` and synthesized small capitals, with the stand-in font (every
+//! character half as wide as the font size).
+
+mod common;
+
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::layout::{LayoutBox, TextFragment};
+use build_a_browser_engine_in_rust::text::{self, FontVariant, TextTransform};
+use common::fragments;
+
+#[test]
+fn transforms_case_the_unicode_way() {
+    let transform = |text: &str, transform: TextTransform| text::transform_text(text, transform, None, None);
+    assert_eq!(transform("Straße café", TextTransform::Uppercase), "STRASSE CAFÉ");
+    assert_eq!(transform("ΣΊΣΥΦΟΣ Ünïcode", TextTransform::Lowercase), "σίσυφος ünïcode");
+    assert_eq!(transform("the don't-stop ǆungla 2nd éclair", TextTransform::Capitalize), "The Don't-Stop ǅungla 2nd Éclair");
+
+    // Turkish has a dotted capital I and a dotless small one.
+    assert_eq!(text::transform_text("istanbul", TextTransform::Uppercase, Some("tr-TR"), None), "İSTANBUL");
+    assert_eq!(text::transform_text("ISPARTA", TextTransform::Lowercase, Some("az"), None), "ısparta");
+
+    // A word can start in an earlier text node.
+    assert_eq!(text::transform_text("llo world", TextTransform::Capitalize, None, Some('e')), "llo World");
+    assert_eq!(text::transform_text("llo world", TextTransform::Capitalize, None, Some(' ')), "Llo World");
+
+    assert_eq!(text::small_caps("Hello, World", FontVariant::SmallCaps, None),
+               [("H".to_string(), false), ("ELLO, ".to_string(), true), ("W".to_string(), false), ("ORLD".to_string(), true)]);
+    assert_eq!(text::small_caps("Hi 2", FontVariant::AllSmallCaps, None), [("HI 2".to_string(), true)]);
+}
+
+fn lay_out(html: &str, css: &str) -> Vec<TextFragment> {
+    let css: String = format!("html, body, p {{ display: block; }} body {{ margin: 0px; }} {}", css);
+    let mut page: Page = Page::load(html, &css).unwrap();
+    page.set_deterministic(true);
+    page.set_viewport(400, 100);
+    page.with_layout_tree(|root: &LayoutBox| {
+        let mut out: Vec<TextFragment> = Vec::new();
+        fragments(root, &mut out);
+        out
+    })
+}
+
+#[test]
+fn lays_out_transformed_text_and_small_caps() {
+    let lines: Vec<TextFragment> = lay_out("<html><p>hello <b>wo</b>rld</p></html>", "p { text-transform: capitalize; }");
+    let texts: Vec<&str> = lines.iter().map(|fragment: &TextFragment| fragment.run.text.as_str()).collect();
+    assert_eq!(texts, ["Hello ", "Wo", "rld"]);
+
+    let lines: Vec<TextFragment> = lay_out("<html><p>Caps</p></html>", "p { font-variant: small-caps; font-size: 20px; }");
+    let runs: Vec<(&str, f32, f32)> = lines.iter().map(|fragment: &TextFragment| (fragment.run.text.as_str(), fragment.run.size, fragment.rect.x)).collect();
+    assert_eq!(runs, [("C", 20.0, 0.0), ("APS", 14.0, 10.0)]);
+    // The small capitals sit on the same baseline.
+    let baseline = |fragment: &TextFragment| fragment.rect.y + fragment.run.metrics.ascent;
+    assert!((baseline(&lines[0]) - baseline(&lines[1])).abs() < 0.001);
+}
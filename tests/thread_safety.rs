@@ -0,0 +1,53 @@
+//! Checks that documents and stylesheets can be moved to other threads, and shared by
+//! several threads styling, laying out, and painting them at once.
+
+use build_a_browser_engine_in_rust::css::{self, Color};
+use build_a_browser_engine_in_rust::dom::Document;
+use build_a_browser_engine_in_rust::fonts::FontCollection;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::image::ImageCache;
+use build_a_browser_engine_in_rust::layout::{self, LayoutBox};
+use build_a_browser_engine_in_rust::painting::{self, Canvas};
+use build_a_browser_engine_in_rust::style::{self, StyledNode};
+use std::thread;
+
+/// Style, lay out, and paint `document` into a canvas `width` pixels wide.
+fn render(document: &Document, stylesheet: &css::Stylesheet, width: f32) -> Canvas {
+    let style_root: StyledNode = style::style_tree(&document.tree, document.root_element, stylesheet);
+    let mut viewport: layout::Dimensions = Default::default();
+    viewport.content.width = width;
+    viewport.content.height = 40.0;
+    let arena: layout::Bump = layout::Bump::new();
+    let root: LayoutBox = layout::layout_tree(&style_root, viewport, &ImageCache::new(), &FontCollection::new(), &arena).unwrap();
+    painting::paint(&root, viewport.content)
+}
+
+#[test]
+fn moves_documents_between_threads() {
+    let (document, stylesheet) = thread::spawn(|| {
+        let document: Document = html::parse("<div class=\"a\"></div><div class=\"b\"></div>").unwrap();
+        let stylesheet: css::Stylesheet = css::parse("html, body, div { display: block; } div { height: 10px; }\
+                                                      .a { background: #ff0000; } .b { background: #0000ff; }").unwrap();
+        (document, stylesheet)
+    }).join().unwrap();
+    assert_eq!(document.get_elements_by_class_name("b").len(), 1);
+    let canvas: Canvas = render(&document, &stylesheet, 4.0);
+    assert_eq!(canvas.pixels[0], Color { r: 255, g: 0, b: 0, a: 255 });
+    assert_eq!(canvas.pixels[4 * 10], Color { r: 0, g: 0, b: 255, a: 255 });
+}
+
+#[test]
+fn shares_documents_across_threads() {
+    let document: Document = html::parse("<p>Some text</p><ul><li>one</li><li>two</li></ul>").unwrap();
+    let stylesheet: css::Stylesheet = css::parse("html, body, p, ul, li { display: block; } li { height: 5px; background: #00ff00; }").unwrap();
+    let expected: Canvas = render(&document, &stylesheet, 20.0);
+
+    let canvases: Vec<Canvas> = thread::scope(|scope: &thread::Scope| {
+        let workers: Vec<thread::ScopedJoinHandle<Canvas>> = (0..4).map(|_| scope.spawn(|| render(&document, &stylesheet, 20.0))).collect();
+        workers.into_iter().map(|worker: thread::ScopedJoinHandle<Canvas>| worker.join().unwrap()).collect()
+    });
+    for canvas in canvases {
+        assert_eq!((canvas.width, canvas.height), (expected.width, expected.height));
+        assert!(canvas.pixels == expected.pixels);
+    }
+}
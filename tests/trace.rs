@@ -0,0 +1,42 @@
+//! Checks that a page records its pipeline stages and exports them, with the embedder's
+//! own events, as a Chrome trace.
+
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::trace::{self, TraceEvent};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+const HTML: &str = "<html><body><p>Hello, <b>world</b>!</p></body></html>";
+const CSS: &str = "html, body, p { display: block; } b { color: #ff0000; }";
+
+#[test]
+fn exports_pipeline_stages_and_embedder_events() {
+    let mut page: Page = Page::load(HTML, CSS).unwrap();
+    page.set_viewport(100, 50);
+    page.paint();
+
+    let start: Option<Instant> = trace::now();
+    let mut present: TraceEvent = TraceEvent::since("present", "embedder", start).unwrap();
+    present.args.push(("frame".to_string(), 1));
+    page.add_trace_event(present);
+
+    let names: Vec<&str> = page.trace_events().iter().map(|event: &&TraceEvent| event.name.as_str()).collect();
+    for stage in ["parse html", "parse css", "style", "layout", "display list", "paint", "present"] {
+        assert!(names.contains(&stage), "no {:?} event in {:?}", stage, names);
+    }
+    let starts: Vec<_> = page.trace_events().iter().map(|event: &&TraceEvent| event.start).collect();
+    assert!(starts.windows(2).all(|pair| pair[0] <= pair[1]));
+
+    let path: PathBuf = std::env::temp_dir().join(format!("trace-{}.json", std::process::id()));
+    page.export_trace(&path).unwrap();
+    let json: String = fs::read_to_string(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(json.starts_with("{\"traceEvents\":["));
+    assert!(json.contains("\"name\":\"layout\",\"cat\":\"engine\",\"ph\":\"X\""));
+    assert!(json.contains("\"name\":\"present\",\"cat\":\"embedder\""));
+    assert!(json.contains("\"args\":{\"frame\":1}"));
+
+    page.clear_trace();
+    assert!(page.trace_events().is_empty());
+}
@@ -0,0 +1,96 @@
+//! Checks that each stage of the pipeline runs in a `tracing` span with counts of the work
+//! it did, and that `browser-engine bench` prints them.
+//!
+//! Run with `cargo test --features tracing --test tracing_spans`.
+
+#![cfg(feature = "tracing")]
+
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::dom::Document;
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::fonts::FontCollection;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::image::ImageCache;
+use build_a_browser_engine_in_rust::layout::{self, LayoutBox};
+use build_a_browser_engine_in_rust::painting::{self, DisplayList};
+use build_a_browser_engine_in_rust::style::{self, StyledNode};
+use build_a_browser_engine_in_rust::trace::{RecordedSpan, Recorder};
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+const HTML: &str = "<html><body><p>Hello, <b>world</b>!</p></body></html>";
+const CSS: &str = "html, body, p { display: block; } b { color: #ff0000; }";
+
+fn count_boxes(layout_box: &LayoutBox) -> usize {
+    1 + layout_box.children.iter().map(count_boxes).sum::<usize>()
+}
+
+fn count_styled(node: &StyledNode) -> usize {
+    1 + node.children.iter().map(count_styled).sum::<usize>()
+}
+
+/// The counts a span recorded, sorted by field name.
+fn counts(span: &RecordedSpan) -> Vec<(&'static str, u64)> {
+    let mut counts: Vec<(&'static str, u64)> = span.counts.iter().map(|(&field, &count): (&&'static str, &u64)| (field, count)).collect();
+    counts.sort();
+    counts
+}
+
+#[test]
+fn records_each_stage_with_its_counts() {
+    let recorder: Recorder = Recorder::default();
+    let (nodes, boxes, items): (usize, usize, usize) = tracing::subscriber::with_default(recorder.clone(), || {
+        let document: Document = html::parse(HTML).unwrap();
+        let stylesheet: css::Stylesheet = css::parse(CSS).unwrap();
+        let style_root: StyledNode = style::style_tree(&document.tree, document.root_element, &stylesheet);
+        let arena: layout::Bump = layout::Bump::new();
+        let mut viewport: layout::Dimensions = Default::default();
+        (viewport.content.width, viewport.content.height) = (100.0, 50.0);
+        let root: LayoutBox = layout::layout_tree(&style_root, viewport, &ImageCache::new(), &FontCollection::new(), &arena).unwrap();
+        let display_list: DisplayList = painting::build_display_list(&root);
+        painting::rasterize(&display_list, layout::Rect { x: 0.0, y: 0.0, width: 100.0, height: 50.0 });
+        (count_styled(&style_root), count_boxes(&root), display_list.len())
+    });
+
+    let spans: Vec<RecordedSpan> = recorder.spans();
+    let names: Vec<&str> = spans.iter().map(|span: &RecordedSpan| span.name).collect();
+    assert_eq!(names, ["html::parse", "css::parse", "style::style_tree", "layout::layout_tree", "painting::display", "painting::rasterize"]);
+    assert_eq!(counts(&spans[0]), [("bytes", HTML.len() as u64), ("nodes", nodes as u64)]);
+    assert_eq!(counts(&spans[1]), [("bytes", CSS.len() as u64), ("rules", 2)]);
+    assert_eq!(counts(&spans[2]), [("nodes", nodes as u64)]);
+    assert_eq!(counts(&spans[3]), [("boxes", boxes as u64)]);
+    assert_eq!(counts(&spans[4]), [("items", items as u64)]);
+    assert_eq!(counts(&spans[5]), [("items", items as u64), ("pixels", 100 * 50)]);
+}
+
+#[test]
+fn pages_run_their_stages_in_spans_too() {
+    let mut page: Page = Page::load(HTML, CSS).unwrap();
+    page.set_viewport(40, 20);
+    let recorder: Recorder = Recorder::default();
+    tracing::subscriber::with_default(recorder.clone(), || page.paint());
+    let names: Vec<&str> = recorder.spans().iter().map(|span: &RecordedSpan| span.name).collect();
+    assert_eq!(names, ["style::style_tree", "layout::layout_tree", "painting::display", "painting::rasterize"]);
+    assert_eq!(recorder.spans()[3].counts["pixels"], 40 * 20);
+
+    // Painting again reuses everything, so there are no more spans.
+    tracing::subscriber::with_default(recorder.clone(), || page.paint());
+    assert_eq!(recorder.spans().len(), 4);
+}
+
+#[test]
+fn bench_prints_the_work_per_run() {
+    let html: PathBuf = std::env::temp_dir().join(format!("tracing-spans-{}.html", std::process::id()));
+    fs::write(&html, format!("<style>{}</style>{}", CSS, HTML)).unwrap();
+    let output: Output = Command::new(env!("CARGO_BIN_EXE_browser-engine"))
+        .args(["bench", html.to_str().unwrap(), "--iterations", "2", "--size", "100x50"])
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout: String = String::from_utf8(output.stdout).unwrap();
+    let work: Vec<&str> = stdout.split("\nwork per run:\n").nth(1).unwrap().lines().collect();
+    let names: Vec<&str> = work.iter().map(|line: &&str| line.split_whitespace().next().unwrap()).collect();
+    assert_eq!(names, ["html::parse", "css::parse", "style::style_tree", "layout::layout_tree", "painting::display", "painting::rasterize"]);
+    assert!(work[5].ends_with(" pixels=5000"), "{}", work[5]);
+}
@@ -0,0 +1,55 @@
+//! Checks `text-overflow: ellipsis` and `line-clamp`, with the stand-in font (every
+//! character 8px wide, the ellipsis included).
+
+mod common;
+
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::layout::{LayoutBox, TextFragment};
+use common::{fragments, texts};
+
+/// The text fragments of `html`, laid out 100px wide, and the height of its first `p`.
+fn lay_out(html: &str, css: &str) -> (Vec<TextFragment>, f32) {
+    let css: String = format!("head {{ display: none; }} html, body, p {{ display: block; }} body {{ margin: 0px; }} {}", css);
+    let mut page: Page = Page::load(html, &css).unwrap();
+    page.set_deterministic(true);
+    page.set_viewport(100, 100);
+    page.with_layout_tree(|root: &LayoutBox| {
+        let mut out: Vec<TextFragment> = Vec::new();
+        fragments(root, &mut out);
+        let body: &LayoutBox = &root.children[0];
+        (out, body.children[0].dimensions.content.height)
+    })
+}
+
+#[test]
+fn ends_overflowing_lines_with_an_ellipsis() {
+    let css: &str = "p { white-space: nowrap; overflow: hidden; text-overflow: ellipsis; }";
+    // "aaaa bbbbbbbbbb" is 120px. The ellipsis leaves 92px, room for 11 characters.
+    let (lines, _) = lay_out("<html><p>aaaa bbbbbbbbbb</p></html>", css);
+    assert_eq!(texts(&lines), ["aaaa bbbbbb", "\u{2026}"]);
+    assert_eq!((lines[1].rect.x, lines[1].rect.width), (88.0, 8.0));
+
+    // A line that fits isn't touched, and a space before the cut is dropped.
+    let (lines, _) = lay_out("<html><p>aaaa bbbb</p></html>", css);
+    assert_eq!(texts(&lines), ["aaaa bbbb"]);
+    let (lines, _) = lay_out("<html><p>aaaaaaaaaaa bbbb</p></html>", css);
+    assert_eq!(texts(&lines), ["aaaaaaaaaaa", "\u{2026}"]);
+
+    // Only in a block that hides its overflow.
+    let (lines, _) = lay_out("<html><p>aaaa bbbbbbbbbb</p></html>", "p { white-space: nowrap; text-overflow: ellipsis; }");
+    assert_eq!(texts(&lines), ["aaaa bbbbbbbbbb"]);
+}
+
+#[test]
+fn clamps_blocks_to_a_number_of_lines() {
+    let html: &str = "<html><p>aaaa bbbb cccc dddd eeee ffff</p></html>";
+    let (lines, height) = lay_out(html, "p { overflow: hidden; -webkit-line-clamp: 2; }");
+    assert_eq!(texts(&lines), ["aaaa bbbb", "cccc dddd", "\u{2026}", "eeee ffff"]);
+    assert_eq!(lines[2].rect.x, 72.0);
+    assert!((height - 2.0 * 19.2).abs() < 0.001);
+
+    // A block that's no longer than the clamp is left alone.
+    let (lines, height) = lay_out(html, "p { line-clamp: 3; }");
+    assert_eq!(texts(&lines), ["aaaa bbbb", "cccc dddd", "eeee ffff"]);
+    assert!((height - 3.0 * 19.2).abs() < 0.001);
+}
@@ -0,0 +1,30 @@
+//! Checks what the viewer relies on that can be checked without opening a window: that a
+//! page says whether images are still loading (so the viewer knows to keep repainting),
+//! and that `view` explains itself when the binary was built without the viewer.
+
+use build_a_browser_engine_in_rust::engine::Page;
+use std::time::Duration;
+
+#[test]
+fn says_whether_images_are_still_loading() {
+    let page: Page = Page::load("<p>no images</p>", "").unwrap();
+    assert!(!page.is_loading_images());
+
+    let missing: String = std::env::temp_dir().join(format!("viewer-{}-missing.png", std::process::id())).display().to_string();
+    let mut page: Page = Page::load(&format!("<img src=\"{}\">", missing), "").unwrap();
+    assert!(page.wait_for_images(Duration::from_secs(5)));
+    assert!(!page.is_loading_images());
+}
+
+#[cfg(not(feature = "viewer"))]
+#[test]
+fn view_needs_the_viewer_feature() {
+    let html: std::path::PathBuf = std::env::temp_dir().join(format!("viewer-{}.html", std::process::id()));
+    std::fs::write(&html, "<p>x</p>").unwrap();
+    let output: std::process::Output = std::process::Command::new(env!("CARGO_BIN_EXE_browser-engine"))
+        .args(["view", html.to_str().unwrap()])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("browser-engine: this binary was built without the `viewer` feature"));
+}
@@ -0,0 +1,59 @@
+//! Checks that pages are laid out and scaled the way their viewport meta tags ask.
+
+use build_a_browser_engine_in_rust::engine::Page;
+use build_a_browser_engine_in_rust::layout::LayoutBox;
+use build_a_browser_engine_in_rust::viewport::{ViewportMeta, ViewportWidth};
+
+/// A page with this viewport meta tag (or none), in an 800x600 viewport, and the width
+/// its root box was laid out at.
+fn lay_out(meta: Option<&str>) -> (Page, f32) {
+    let head: String = meta.map(|content: &str| format!("<head><meta name=\"viewport\" content=\"{}\"></head>", content)).unwrap_or_default();
+    let html: String = format!("<html>{}<body><p>Hello</p></body></html>", head);
+    let mut page: Page = Page::load(&html, "head { display: none; } html, body, p { display: block; }").unwrap();
+    page.set_deterministic(true);
+    page.set_viewport(800, 600);
+    let width: f32 = page.with_layout_tree(|root: &LayoutBox| root.dimensions.content.width);
+    (page, width)
+}
+
+#[test]
+fn parses_viewport_meta_tags() {
+    assert_eq!(ViewportMeta::parse("width=device-width, initial-scale=1"),
+               ViewportMeta { width: Some(ViewportWidth::DeviceWidth), initial_scale: Some(1.0), ..ViewportMeta::default() });
+    assert_eq!(ViewportMeta::parse("WIDTH=320;maximum-scale=2 user-scalable=no"),
+               ViewportMeta { width: Some(ViewportWidth::Px(320.0)), maximum_scale: Some(2.0), ..ViewportMeta::default() });
+    // Values that don't parse are ignored, and ones out of range are clamped.
+    assert_eq!(ViewportMeta::parse("width=wide, initial-scale=0, minimum-scale=50"),
+               ViewportMeta { minimum_scale: Some(10.0), ..ViewportMeta::default() });
+}
+
+#[test]
+fn lays_out_at_the_width_and_scale_the_page_asks_for() {
+    let (page, width) = lay_out(None);
+    assert_eq!((page.page_viewport(), page.scale(), width), ((800.0, 1.0), 1.0, 800.0));
+
+    // The usual tag changes nothing.
+    let (page, width) = lay_out(Some("width=device-width, initial-scale=1"));
+    assert_eq!((page.scale(), width), (1.0, 800.0));
+
+    // A width on its own is fit to the screen.
+    let (page, width) = lay_out(Some("width=1600"));
+    assert_eq!((page.scale(), width), (0.5, 1600.0));
+
+    // A scale on its own lays out as wide as fits at that scale.
+    let (page, width) = lay_out(Some("initial-scale=2"));
+    assert_eq!((page.scale(), width), (2.0, 400.0));
+
+    // With both, the layout is at least as wide as the screen.
+    let (mut page, width) = lay_out(Some("width=1000, initial-scale=1"));
+    assert_eq!((page.scale(), width), (1.0, 1000.0));
+    assert_eq!(page.max_scroll().0, 200.0);
+    let (page, width) = lay_out(Some("width=100, initial-scale=2"));
+    assert_eq!((page.scale(), width), (2.0, 400.0));
+
+    // The limits hold the scale in, and the user's zoom is on top.
+    let (mut page, width) = lay_out(Some("width=200, maximum-scale=2"));
+    assert_eq!((page.scale(), width), (2.0, 400.0));
+    page.set_zoom(2.0);
+    assert_eq!(page.scale(), 4.0);
+}
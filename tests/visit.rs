@@ -0,0 +1,127 @@
+//! Checks that visitors walk the DOM, style, and layout trees in order, entering each
+//! node before its children and leaving it after, and that they can skip or stop.
+
+use build_a_browser_engine_in_rust::css;
+use build_a_browser_engine_in_rust::dom::{Document, Element, NodeId, NodeType};
+use build_a_browser_engine_in_rust::fonts::FontCollection;
+use build_a_browser_engine_in_rust::html;
+use build_a_browser_engine_in_rust::image::ImageCache;
+use build_a_browser_engine_in_rust::layout::{self, BoxType, LayoutBox};
+use build_a_browser_engine_in_rust::style::{self, Display, StyledNode};
+use build_a_browser_engine_in_rust::visit::{Flow, NodeVisitor};
+
+/// Logs every call, skipping the children of elements named `skip` and stopping at
+/// elements named `stop`.
+#[derive(Default)]
+struct Logger {
+    log: Vec<String>,
+    skip: &'static str,
+    stop: &'static str,
+}
+
+impl NodeVisitor for Logger {
+    fn enter_element(&mut self, _: NodeId, element: &Element) -> Flow {
+        self.log.push(format!("<{}>", element.tag_name));
+        if element.tag_name == self.stop {
+            Flow::Stop
+        } else if element.tag_name == self.skip {
+            Flow::SkipChildren
+        } else {
+            Flow::Continue
+        }
+    }
+    fn exit_element(&mut self, _: NodeId, element: &Element) {
+        self.log.push(format!("</{}>", element.tag_name));
+    }
+    fn enter_text(&mut self, _: NodeId, text: &str) -> Flow {
+        self.log.push(text.to_string());
+        Flow::Continue
+    }
+}
+
+fn page() -> Document {
+    html::parse("<div><p>a<em>b</em></p><ul><li>c</li></ul></div>").unwrap()
+}
+
+#[test]
+fn walks_the_dom_in_order() {
+    let document: Document = page();
+    let div: NodeId = document.get_elements_by_tag_name("div")[0];
+    let mut logger: Logger = Logger::default();
+    assert_eq!(document.tree.visit(div, &mut logger), Flow::Continue);
+    assert_eq!(logger.log, ["<div>", "<p>", "a", "<em>", "b", "</em>", "</p>", "<ul>", "<li>", "c", "</li>", "</ul>", "</div>"]);
+}
+
+#[test]
+fn skips_children_and_stops() {
+    let document: Document = page();
+    let div: NodeId = document.get_elements_by_tag_name("div")[0];
+    let mut logger: Logger = Logger { skip: "p", ..Logger::default() };
+    document.tree.visit(div, &mut logger);
+    // A skipped node is still left, but its children are never entered.
+    assert_eq!(logger.log, ["<div>", "<p>", "</p>", "<ul>", "<li>", "c", "</li>", "</ul>", "</div>"]);
+
+    let mut logger: Logger = Logger { stop: "em", ..Logger::default() };
+    assert_eq!(document.tree.visit(div, &mut logger), Flow::Stop);
+    assert_eq!(logger.log, ["<div>", "<p>", "a", "<em>"]);
+}
+
+#[test]
+fn dom_visitors_work_on_the_style_tree() {
+    let document: Document = page();
+    let stylesheet: css::Stylesheet = css::parse("ul { display: none; }").unwrap();
+    let div: NodeId = document.get_elements_by_tag_name("div")[0];
+    let root: StyledNode = style::style_tree(&document.tree, div, &stylesheet);
+    let mut from_style: Logger = Logger::default();
+    root.visit(&mut from_style);
+    let mut from_dom: Logger = Logger::default();
+    document.tree.visit(div, &mut from_dom);
+    assert_eq!(from_style.log, from_dom.log);
+
+    /// The tags of elements that are displayed, skipping hidden subtrees.
+    struct Shown(Vec<String>);
+    impl NodeVisitor for Shown {
+        fn enter_styled_node(&mut self, styled_node: &StyledNode) -> Flow {
+            if styled_node.display() == Display::None {
+                return Flow::SkipChildren;
+            }
+            if let NodeType::Element(ref element) = styled_node.node.node_type {
+                self.0.push(element.tag_name.to_string());
+            }
+            Flow::Continue
+        }
+    }
+    let mut shown: Shown = Shown(Vec::new());
+    root.visit(&mut shown);
+    assert_eq!(shown.0, ["div", "p", "em"]);
+}
+
+#[test]
+fn walks_layout_boxes() {
+    /// The depth of each box, and whether it's anonymous.
+    struct Boxes { depth: usize, log: Vec<(usize, bool)> }
+    impl NodeVisitor for Boxes {
+        fn enter_layout_box(&mut self, layout_box: &LayoutBox) -> Flow {
+            self.log.push((self.depth, matches!(layout_box.box_type, BoxType::AnonymousBlock)));
+            self.depth += 1;
+            Flow::Continue
+        }
+        fn exit_layout_box(&mut self, _: &LayoutBox) {
+            self.depth -= 1;
+        }
+    }
+
+    let document: Document = html::parse("<div><p>a</p><span>b</span></div>").unwrap();
+    let stylesheet: css::Stylesheet = css::parse("div, p { display: block; }").unwrap();
+    let div: NodeId = document.get_elements_by_tag_name("div")[0];
+    let style_root: StyledNode = style::style_tree(&document.tree, div, &stylesheet);
+    let arena: layout::Bump = layout::Bump::new();
+    let root: LayoutBox = layout::layout_tree(&style_root, Default::default(), &ImageCache::new(), &FontCollection::new(), &arena).unwrap();
+
+    let mut boxes: Boxes = Boxes { depth: 0, log: Vec::new() };
+    root.visit(&mut boxes);
+    // The div, the p with its text wrapped in an anonymous block, then another anonymous
+    // block around the span.
+    assert_eq!(boxes.log, [(0, false), (1, false), (2, true), (3, false), (1, true), (2, false), (3, false)]);
+    assert_eq!(boxes.depth, 0);
+}
@@ -0,0 +1,60 @@
+//! Checks the `Engine` that the `wasm` feature exports to JavaScript: the RGBA buffer it
+//! renders into, and the input it passes on to the page. The calls are plain Rust, so they
+//! run natively as well as in WebAssembly.
+//!
+//! Run with `cargo test --features wasm --test wasm_engine`.
+
+#![cfg(feature = "wasm")]
+
+use build_a_browser_engine_in_rust::css::Color;
+use build_a_browser_engine_in_rust::painting::Canvas;
+use build_a_browser_engine_in_rust::wasm::Engine;
+
+const CSS: &str = "head { display: none; } html, body, div { display: block; } body { margin: 0; height: 100px; } \
+                   div { height: 10px; background: #ff0000; } div:hover { background: #0000ff; }";
+
+/// The RGBA bytes of the pixel at (`x`, `y`) in a buffer `width` pixels wide.
+fn pixel(rgba: &[u8], width: usize, x: usize, y: usize) -> [u8; 4] {
+    let start: usize = (y * width + x) * 4;
+    rgba[start..start + 4].try_into().unwrap()
+}
+
+#[test]
+fn converts_canvases_to_rgba_bytes() {
+    let canvas: Canvas = Canvas {
+        pixels: vec![Color { r: 1, g: 2, b: 3, a: 4 }, Color { r: 5, g: 6, b: 7, a: 8 }],
+        width: 2,
+        height: 1,
+    };
+    assert_eq!(canvas.to_rgba(), [1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn renders_the_viewport_into_an_rgba_buffer() {
+    let mut engine: Engine = Engine::new("<div></div>", CSS).unwrap();
+    engine.set_viewport(20, 30);
+    assert_eq!((engine.width(), engine.height()), (20, 30));
+    let rgba: Vec<u8> = engine.render();
+    assert_eq!(rgba.len(), 20 * 30 * 4);
+    assert_eq!(pixel(&rgba, 20, 0, 0), [255, 0, 0, 255]);
+    assert_eq!(pixel(&rgba, 20, 19, 9), [255, 0, 0, 255]);
+    assert_eq!(pixel(&rgba, 20, 0, 10), [255, 255, 255, 255]);
+}
+
+#[test]
+fn passes_input_on_to_the_page() {
+    let mut engine: Engine = Engine::new("<div></div>", CSS).unwrap();
+    engine.set_viewport(20, 30);
+    engine.render();
+
+    // Hovering over the div restyles it; moving off it restyles it back.
+    assert!(engine.hover_at(5.0, 5.0));
+    assert_eq!(pixel(&engine.render(), 20, 5, 5), [0, 0, 255, 255]);
+    assert!(!engine.hover_at(6.0, 5.0));
+    assert!(engine.hover_at(5.0, 25.0));
+    assert_eq!(pixel(&engine.render(), 20, 5, 5), [255, 0, 0, 255]);
+
+    // Scrolling moves the div up out of the viewport.
+    engine.scroll_by(0.0, 10.0);
+    assert_eq!(pixel(&engine.render(), 20, 5, 5), [255, 255, 255, 255]);
+}
@@ -0,0 +1,83 @@
+//! Checks that `browser-engine watch` renders the page again whenever its HTML or CSS
+//! changes, saying which file changed and how long each step took.
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdout, Command, Output, Stdio};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// Kills the watcher when the test ends, even if it fails.
+struct Watcher(Child);
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// The first pixel's RGB of a binary PPM image with a 3-line header.
+fn first_pixel(path: &PathBuf) -> [u8; 3] {
+    let bytes: Vec<u8> = fs::read(path).unwrap();
+    let header_len: usize = bytes.iter().enumerate().filter(|&(_, &byte): &(usize, &u8)| byte == b'\n').nth(2).unwrap().0 + 1;
+    [bytes[header_len], bytes[header_len + 1], bytes[header_len + 2]]
+}
+
+/// Wait for the watcher's next line of output.
+fn next_line(lines: &Receiver<String>) -> String {
+    lines.recv_timeout(Duration::from_secs(30)).expect("the watcher stopped printing")
+}
+
+#[test]
+fn renders_again_when_files_change() {
+    let dir: PathBuf = std::env::temp_dir();
+    let (html, css, out) = (dir.join(format!("watch-{}.html", std::process::id())),
+                            dir.join(format!("watch-{}.css", std::process::id())),
+                            dir.join(format!("watch-{}.ppm", std::process::id())));
+    fs::write(&html, "<div></div>").unwrap();
+    fs::write(&css, "head { display: none; } html, body, div { display: block; } body { margin: 0; } div { height: 10px; background: #ff0000; }").unwrap();
+
+    let mut watcher: Watcher = Watcher(Command::new(env!("CARGO_BIN_EXE_browser-engine"))
+        .args(["watch", html.to_str().unwrap(), "--css", css.to_str().unwrap(), "--size", "20x20", "-o", out.to_str().unwrap()])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap());
+    let stdout: ChildStdout = watcher.0.stdout.take().unwrap();
+    let (sender, lines): (Sender<String>, Receiver<String>) = mpsc::channel();
+    thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    assert_eq!(next_line(&lines), format!("wrote {}; watching for changes (Ctrl-C to stop)", out.display()));
+    assert_eq!(first_pixel(&out), [255, 0, 0]);
+
+    // A new stylesheet is parsed again; the document is kept.
+    thread::sleep(Duration::from_millis(50));
+    fs::write(&css, "head { display: none; } html, body, div { display: block; } body { margin: 0; } div { height: 10px; background: #0000ff; }").unwrap();
+    let line: String = next_line(&lines);
+    assert!(line.starts_with(&format!("{} changed: parse css ", css.display())), "{}", line);
+    assert!(line.contains(", style+layout ") && line.contains(", paint ") && line.contains("; total "), "{}", line);
+    assert_eq!(first_pixel(&out), [0, 0, 255]);
+
+    // New HTML is parsed into a new document, with the stylesheet kept.
+    thread::sleep(Duration::from_millis(50));
+    fs::write(&html, "<p></p>").unwrap();
+    let line: String = next_line(&lines);
+    assert!(line.starts_with(&format!("{} changed: parse html ", html.display())), "{}", line);
+    assert!(!line.contains("parse css"), "{}", line);
+    assert_eq!(first_pixel(&out), [255, 255, 255]);
+}
+
+#[test]
+fn only_watches_local_files() {
+    let output: Output = Command::new(env!("CARGO_BIN_EXE_browser-engine")).args(["watch", "http://127.0.0.1:1/"]).output().unwrap();
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("browser-engine: watch only works on local files"));
+}
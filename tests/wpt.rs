@@ -0,0 +1,32 @@
+//! Runs the Web Platform Tests listed in `tests/wpt/css21.list`, a curated subset of
+//! CSS 2.1 written in the WPT format. They aren't copied from WPT; point the `wpt`
+//! command at a checkout to run the real ones.
+
+use build_a_browser_engine_in_rust::wpt::{self, Outcome, Summary};
+use std::path::{Path, PathBuf};
+
+#[test]
+fn wpt_subset() {
+    let root: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/wpt");
+    let tests: Vec<PathBuf> = wpt::load_list(&root.join("css21.list"), &root).unwrap_or_else(|err: String| panic!("{}", err));
+    assert!(!tests.is_empty(), "css21.list lists no tests");
+
+    let mut summary: Summary = Default::default();
+    let mut failures: Vec<String> = Vec::new();
+    for test in &tests {
+        let outcome: Outcome = wpt::run_test(test);
+        summary.add(&outcome);
+        if outcome != Outcome::Pass {
+            failures.push(format!("{}: {}", test.display(), outcome));
+        }
+    }
+    assert!(failures.is_empty(), "{}:\n{}", summary, failures.join("\n"));
+}
+
+#[test]
+fn every_test_in_the_directory_is_listed() {
+    let root: PathBuf = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/wpt");
+    let listed: Vec<PathBuf> = wpt::load_list(&root.join("css21.list"), &root).unwrap();
+    let found: Vec<PathBuf> = wpt::find_tests(&root).unwrap();
+    assert_eq!(found, listed);
+}
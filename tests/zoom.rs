@@ -0,0 +1,69 @@
+//! Checks that zooming and high-DPI screens lay the page out at fewer CSS px and paint it
+//! scaled up, so the same canvas shows bigger content.
+
+use build_a_browser_engine_in_rust::css::Color;
+use build_a_browser_engine_in_rust::engine::{Page, ZOOM_RANGE};
+use build_a_browser_engine_in_rust::layout::LayoutBox;
+use build_a_browser_engine_in_rust::painting::Canvas;
+
+const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+
+/// A 100x100 page with a red square 10px on a side at its top left.
+fn load() -> Page {
+    let mut page: Page = Page::load("<div></div>", "head { display: none; } html, body, div { display: block; } body { margin: 0; } \
+                                                    div { width: 10px; height: 10px; background: #ff0000; }").unwrap();
+    page.set_viewport(100, 100);
+    page
+}
+
+/// How wide the square is on the canvas, in device px.
+fn painted_width(page: &mut Page) -> usize {
+    let canvas: &Canvas = page.paint();
+    (0..canvas.width).take_while(|&x: &usize| canvas.pixels[x] == RED).count()
+}
+
+/// The width the root box was laid out at, in CSS px.
+fn layout_width(page: &Page) -> f32 {
+    page.with_layout_tree(|root: &LayoutBox| root.dimensions.content.width)
+}
+
+#[test]
+fn zooming_scales_layout_and_painting() {
+    let mut page: Page = load();
+    assert_eq!((page.zoom(), page.scale(), painted_width(&mut page)), (1.0, 1.0, 10));
+
+    page.set_zoom(2.0);
+    assert_eq!((page.scale(), layout_width(&page), painted_width(&mut page)), (2.0, 50.0, 20));
+    assert_eq!(page.paint().pixels[25 * 100], WHITE);
+    // The canvas stays the viewport's size.
+    assert_eq!((page.paint().width, page.paint().height), (100, 100));
+
+    page.set_zoom(0.5);
+    assert_eq!((layout_width(&page), painted_width(&mut page)), (200.0, 5));
+}
+
+#[test]
+fn clamps_zoom_to_its_range() {
+    let mut page: Page = load();
+    page.set_zoom(100.0);
+    assert_eq!(page.zoom(), ZOOM_RANGE.1);
+    page.set_zoom(0.0);
+    assert_eq!(page.zoom(), ZOOM_RANGE.0);
+}
+
+#[test]
+fn device_scale_multiplies_with_zoom() {
+    let mut page: Page = load();
+    page.set_device_scale(2.0);
+    assert_eq!((page.device_scale(), page.scale(), painted_width(&mut page)), (2.0, 2.0, 20));
+    page.set_zoom(1.5);
+    assert_eq!((page.scale(), painted_width(&mut page)), (3.0, 30));
+    // Scales that make no sense are ignored.
+    page.set_device_scale(0.0);
+    page.set_device_scale(-1.0);
+    assert_eq!(page.device_scale(), 2.0);
+
+    // Points on the screen are in device px, so they land on the same content.
+    assert_eq!(page.node_at(25.0, 25.0), page.document().get_elements_by_tag_name("div").first().copied());
+}